@@ -0,0 +1,96 @@
+//! Compares estimation and merging of dense sketches against the plain
+//! per-register loops they replaced: summing `2^-register` with `exp2` or
+//! `powf`, and taking the maximum one byte at a time. Also times a `count`
+//! after every insertion at several sizes, which the kept histogram makes
+//! independent of the number of registers.
+//!
+//! Run with `cargo bench --bench estimate`.
+
+extern crate hyperloglog;
+
+use std::time::Instant;
+
+use hyperloglog::HyperLogLog;
+
+const ROUNDS: usize = 500;
+
+fn report(name: &str, ops: u64, start: Instant) {
+    let elapsed = start.elapsed();
+    println!(
+        "{:<24} {:>10.1} ns/op  ({:?} total)",
+        name,
+        elapsed.as_nanos() as f64 / ops as f64,
+        elapsed
+    );
+}
+
+fn sketch(m: usize, items: std::ops::Range<u64>) -> HyperLogLog {
+    let mut h = HyperLogLog::new(m);
+    h.extend(items);
+    h
+}
+
+fn main() {
+    let m = 1 << 16;
+    let a = sketch(m, 0..1_000_000);
+    let b = sketch(m, 500_000..1_500_000);
+    let mut total = 0.;
+
+    // `count` caches its result, so time the uncached computation.
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        total += a.estimate_details().harmonic_sum;
+    }
+    report("harmonic sum (kept)", ROUNDS as u64, start);
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        total += a.registers().map(|r| (-f64::from(r)).exp2()).sum::<f64>();
+    }
+    report("harmonic sum (exp2 loop)", ROUNDS as u64, start);
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        total += a.registers().map(|r| 2f64.powf(-f64::from(r))).sum::<f64>();
+    }
+    report("harmonic sum (powf loop)", ROUNDS as u64, start);
+
+    // Merging into the same sketch repeatedly still scans every register.
+    let mut merged = a.clone();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        merged.merge(&b).unwrap();
+    }
+    report("merge (wide max)", ROUNDS as u64, start);
+
+    let mut mine: Vec<u8> = a.registers().collect();
+    let theirs: Vec<u8> = b.registers().collect();
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        let mut changed = false;
+        for (r, &o) in mine.iter_mut().zip(&theirs) {
+            if *r < o {
+                *r = o;
+                changed = true;
+            }
+        }
+        total += f64::from(u8::from(changed));
+    }
+    report("merge (byte loop)", ROUNDS as u64, start);
+    total += merged.count() + f64::from(mine[0]);
+
+    // Each insertion moves at most one register between histogram buckets,
+    // so the estimate that follows it, uncached, costs the same at every size.
+    for &p in &[10, 14, 18] {
+        let mut h = sketch(1 << p, 0..1_000_000);
+        let ops = 100_000;
+        let start = Instant::now();
+        for i in 0..ops {
+            h.add(2_000_000 + i);
+            total += h.estimate_details().estimate;
+        }
+        report(&format!("add + estimate (p = {})", p), ops, start);
+    }
+
+    println!("(checksum {})", total);
+}
@@ -0,0 +1,123 @@
+//! Compares ways of feeding a sketch: `add`, which hashes each item, against
+//! `add_hashed` on hashes computed upstream, and both against their batched
+//! `extend`, `add_all` and `add_slice` forms; and `add` against `add_u64`
+//! and `add_u128` on 100 million integer keys.
+//!
+//! Run with `cargo bench --bench hashed`.
+
+extern crate hyperloglog;
+
+use std::time::Instant;
+
+use hyperloglog::HyperLogLog;
+
+const ITEMS: u64 = 10_000_000;
+const INTEGERS: u64 = 100_000_000;
+
+fn report(name: &str, ops: u64, start: Instant) {
+    let elapsed = start.elapsed();
+    println!(
+        "{:<24} {:>10.1} ns/op  ({:?} total)",
+        name,
+        elapsed.as_nanos() as f64 / ops as f64,
+        elapsed
+    );
+}
+
+/// A stand-in for an upstream 64-bit hash such as xxhash: murmur3's finalizer.
+fn fmix64(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    h ^= h >> 33;
+    h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    h ^ (h >> 33)
+}
+
+fn main() {
+    let m = 1 << 14;
+
+    let mut by_item = HyperLogLog::new(m);
+    let start = Instant::now();
+    for i in 0..ITEMS {
+        by_item.add(i);
+    }
+    report("add", ITEMS, start);
+
+    let mut extended = HyperLogLog::new(m);
+    let start = Instant::now();
+    extended.extend(0..ITEMS);
+    report("extend", ITEMS, start);
+
+    let hashes: Vec<u64> = (0..ITEMS).map(fmix64).collect();
+    let mut by_hash = HyperLogLog::new(m);
+    let start = Instant::now();
+    for &h in &hashes {
+        by_hash.add_hashed(h);
+    }
+    report("add_hashed", ITEMS, start);
+
+    let mut extended_hashed = HyperLogLog::new(m);
+    let start = Instant::now();
+    extended_hashed.extend_hashed(hashes.iter().cloned());
+    report("extend_hashed", ITEMS, start);
+
+    // Small strings, a million distinct ones added ten times over.
+    let words: Vec<String> = (0..ITEMS / 10).map(|i| format!("k{:x}", i)).collect();
+    let strs: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+
+    let mut by_str = HyperLogLog::new(m);
+    let start = Instant::now();
+    for _ in 0..10 {
+        for s in &strs {
+            by_str.add(s);
+        }
+    }
+    report("add (strings)", ITEMS, start);
+
+    let mut all_strs = HyperLogLog::new(m);
+    let start = Instant::now();
+    for _ in 0..10 {
+        all_strs.add_all(&strs);
+    }
+    report("add_all (strings)", ITEMS, start);
+
+    let mut sliced_strs = HyperLogLog::new(m);
+    let start = Instant::now();
+    for _ in 0..10 {
+        sliced_strs.add_slice(&strs);
+    }
+    report("add_slice (strings)", ITEMS, start);
+
+    let mut by_add = HyperLogLog::new(m);
+    let start = Instant::now();
+    for i in 0..INTEGERS {
+        by_add.add(i);
+    }
+    report("add (integers)", INTEGERS, start);
+
+    let mut by_u64 = HyperLogLog::new(m);
+    let start = Instant::now();
+    for i in 0..INTEGERS {
+        by_u64.add_u64(i);
+    }
+    report("add_u64", INTEGERS, start);
+
+    let mut by_u128 = HyperLogLog::new(m);
+    let start = Instant::now();
+    for i in 0..INTEGERS {
+        by_u128.add_u128(u128::from(i) << 64 | u128::from(i));
+    }
+    report("add_u128", INTEGERS, start);
+
+    let total = by_item.count()
+        + extended.count()
+        + by_hash.count()
+        + extended_hashed.count()
+        + by_str.count()
+        + all_strs.count()
+        + sliced_strs.count()
+        + by_add.count()
+        + by_u64.count()
+        + by_u128.count();
+    println!("(checksum {})", total);
+}
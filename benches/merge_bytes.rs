@@ -0,0 +1,76 @@
+//! Compares merging serialized sketches into an accumulator in place, with
+//! `merge_from_bytes`, against decoding each with `from_bytes` and merging
+//! the result, for dense and compressed frames of sparsely and fully filled
+//! sketches.
+//!
+//! Run with `cargo bench --bench merge_bytes`.
+
+extern crate hyperloglog;
+
+use std::time::Instant;
+
+use hyperloglog::HyperLogLog;
+
+const FRAMES: usize = 2_000;
+
+fn report(name: &str, ops: usize, start: Instant) {
+    let elapsed = start.elapsed();
+    println!(
+        "{:<40} {:>10.1} ns/op  ({:?} total)",
+        name,
+        elapsed.as_nanos() as f64 / ops as f64,
+        elapsed
+    );
+}
+
+/// `FRAMES` sketches of `items` items each, encoded densely or compressed.
+fn frames(items: u64, compressed: bool) -> Vec<Vec<u8>> {
+    (0..FRAMES as u64)
+        .map(|frame| {
+            let mut h = HyperLogLog::new(1 << 14);
+            h.extend(frame * items..(frame + 1) * items);
+            if compressed {
+                h.to_bytes_compressed()
+            } else {
+                h.to_bytes()
+            }
+        })
+        .collect()
+}
+
+fn main() {
+    let mut checksum = 0.;
+    for &(fill, items) in &[("50 items", 50), ("100k items", 100_000)] {
+        for &(format, compressed) in &[("dense", false), ("compressed", true)] {
+            let frames = frames(items, compressed);
+
+            let mut decoded = HyperLogLog::new(1 << 14);
+            let start = Instant::now();
+            for bytes in &frames {
+                decoded
+                    .merge(&HyperLogLog::from_bytes(bytes).unwrap())
+                    .unwrap();
+            }
+            report(
+                &format!("{}, {}, decode then merge", fill, format),
+                FRAMES,
+                start,
+            );
+
+            let mut in_place = HyperLogLog::new(1 << 14);
+            let start = Instant::now();
+            for bytes in &frames {
+                in_place.merge_from_bytes(bytes).unwrap();
+            }
+            report(
+                &format!("{}, {}, merge_from_bytes", fill, format),
+                FRAMES,
+                start,
+            );
+
+            assert_eq!(in_place, decoded);
+            checksum += in_place.count();
+        }
+    }
+    println!("(checksum {})", checksum);
+}
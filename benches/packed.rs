@@ -0,0 +1,56 @@
+//! Compares the byte-per-register and 6-bit packed layouts.
+//!
+//! Run with `cargo bench --bench packed`.
+
+extern crate hyperloglog;
+
+use std::time::Instant;
+
+use hyperloglog::{HyperLogLog, PackedHyperLogLog};
+
+const ITEMS: u64 = 2_000_000;
+const COUNTS: usize = 200;
+
+fn report(name: &str, ops: u64, start: Instant) {
+    let elapsed = start.elapsed();
+    println!(
+        "{:<24} {:>10.1} ns/op  ({:?} total)",
+        name,
+        elapsed.as_nanos() as f64 / ops as f64,
+        elapsed
+    );
+}
+
+fn main() {
+    let m = 1 << 14;
+
+    let mut plain = HyperLogLog::new(m);
+    let start = Instant::now();
+    for i in 0..ITEMS {
+        plain.add(i);
+    }
+    report("add (u8 registers)", ITEMS, start);
+
+    let mut packed = PackedHyperLogLog::new(m);
+    let start = Instant::now();
+    for i in 0..ITEMS {
+        packed.add(i);
+    }
+    report("add (packed)", ITEMS, start);
+
+    let mut total = 0.;
+    let start = Instant::now();
+    for _ in 0..COUNTS {
+        // `count` caches its result, so time the uncached computation.
+        total += plain.estimate_details().estimate;
+    }
+    report("count (u8 registers)", COUNTS as u64, start);
+
+    let start = Instant::now();
+    for _ in 0..COUNTS {
+        total += packed.count();
+    }
+    report("count (packed)", COUNTS as u64, start);
+
+    println!("(checksum {})", total);
+}
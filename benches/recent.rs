@@ -0,0 +1,74 @@
+//! Measures the recent-hash cache on Zipf-distributed streams, where a few
+//! hot items make up most additions, against the same streams without it.
+//!
+//! Run with `cargo bench --bench recent`.
+
+extern crate hyperloglog;
+
+use std::time::Instant;
+
+use hyperloglog::HyperLogLog;
+
+const EVENTS: usize = 10_000_000;
+const KEYS: usize = 1_000_000;
+
+fn report(name: &str, ops: usize, start: Instant) {
+    let elapsed = start.elapsed();
+    println!(
+        "{:<32} {:>10.1} ns/op  ({:?} total)",
+        name,
+        elapsed.as_nanos() as f64 / ops as f64,
+        elapsed
+    );
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// `EVENTS` keys drawn from `0..KEYS`, key `k` with probability
+/// proportional to `1 / (k + 1)^s`.
+fn zipf(s: f64) -> Vec<u64> {
+    let mut cumulative = Vec::with_capacity(KEYS);
+    let mut total = 0.;
+    for k in 0..KEYS {
+        total += 1. / ((k + 1) as f64).powf(s);
+        cumulative.push(total);
+    }
+    let mut state = 95;
+    (0..EVENTS)
+        .map(|_| {
+            let u = (splitmix64(&mut state) >> 11) as f64 / (1u64 << 53) as f64 * total;
+            cumulative.partition_point(|&c| c < u) as u64
+        })
+        .collect()
+}
+
+fn main() {
+    let mut checksum = 0.;
+    for &s in &[1.1, 2.0] {
+        let events = zipf(s);
+        for &(layout, sparse) in &[("sparse", true), ("dense", false)] {
+            for &capacity in &[0, 256, 4096] {
+                let mut h = HyperLogLog::builder()
+                    .precision(14)
+                    .sparse(sparse)
+                    .recent_cache(capacity)
+                    .build()
+                    .unwrap();
+                let start = Instant::now();
+                for &key in &events {
+                    h.add(key);
+                }
+                let name = format!("s = {}, {}, cache {}", s, layout, capacity);
+                report(&name, EVENTS, start);
+                checksum += h.count();
+            }
+        }
+    }
+    println!("(checksum {})", checksum);
+}
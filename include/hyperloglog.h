@@ -0,0 +1,88 @@
+#ifndef HYPERLOGLOG_H
+#define HYPERLOGLOG_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// The call succeeded.
+#define HLL_OK 0
+
+// A required pointer was null, or a sketch pointer didn't point at a live
+// sketch.
+#define HLL_ERR_INVALID_POINTER -1
+
+// The two sketches have different precisions.
+#define HLL_ERR_PRECISION_MISMATCH -2
+
+// The output buffer is too small; the size needed was written back.
+#define HLL_ERR_BUFFER_TOO_SMALL -3
+
+// The call panicked. This is a bug in the crate.
+#define HLL_ERR_PANIC -4
+
+// The two sketches hash items differently, such as a sketch deserialized
+// from one seeded with another key.
+#define HLL_ERR_HASHER_MISMATCH -5
+
+// An opaque HyperLogLog sketch.
+typedef struct Hll Hll;
+
+// Creates an empty sketch with `2^precision` registers, or returns null
+// unless `precision` is from 4 to 24.
+struct Hll *hll_new(uint8_t precision);
+
+// Frees a sketch. Null is ignored.
+//
+// # Safety
+//
+// `hll` must be null or a pointer from this interface not yet freed.
+void hll_free(struct Hll *hll);
+
+// Adds the item whose bytes are the `len` bytes at `data`.
+//
+// # Safety
+//
+// `hll` must be null or a live sketch, and `data` must be null or point at
+// `len` readable bytes. `data` may be null only if `len` is 0.
+int hll_add_bytes(struct Hll *hll, const uint8_t *data, size_t len);
+
+// Estimates the number of distinct items added, or returns NaN for an
+// invalid pointer.
+//
+// # Safety
+//
+// `hll` must be null or a live sketch.
+double hll_count(const struct Hll *hll);
+
+// Folds `src` into `dst`.
+//
+// # Safety
+//
+// Both pointers must be null or live sketches.
+int hll_merge(struct Hll *dst, const struct Hll *src);
+
+// Writes the sketch in the crate's binary format to `out_buf`.
+//
+// `*out_len` gives the buffer's size on entry and is set to the encoded
+// size. If the buffer is null or too small, nothing is written and the
+// call returns `HLL_ERR_BUFFER_TOO_SMALL`, so passing a null buffer asks
+// for the size.
+//
+// # Safety
+//
+// `hll` must be null or a live sketch, `out_len` must be null or valid to
+// read and write, and `out_buf` must be null or point at `*out_len`
+// writable bytes.
+int hll_serialize(const struct Hll *hll, uint8_t *out_buf, size_t *out_len);
+
+// Decodes a sketch written by [`hll_serialize`], or returns null if `buf`
+// is null or doesn't hold one.
+//
+// # Safety
+//
+// `buf` must be null or point at `len` readable bytes.
+struct Hll *hll_deserialize(const uint8_t *buf, size_t len);
+
+#endif  /* HYPERLOGLOG_H */
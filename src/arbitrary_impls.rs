@@ -0,0 +1,197 @@
+//! `Arbitrary` for sketches, for fuzzing and property-testing the code that
+//! consumes them.
+//!
+//! Generated sketches are ones this crate could have built: a power-of-two
+//! register count and ranks a 64-bit hash can produce at that precision. For
+//! the other side of a decoder,
+//! [`HyperLogLog::arbitrary_corrupt_bytes`] generates encodings that
+//! [`HyperLogLog::from_bytes`] must reject.
+
+use alloc::vec::Vec;
+
+use arbitrary::{Arbitrary, Error, Result, Unstructured};
+
+use {max_rank, HyperLogLog, StableBuildHasher, MIN_PRECISION};
+
+/// The largest precision of a generated sketch, so that one takes at most
+/// 64 KiB.
+const MAX_ARBITRARY_PRECISION: u8 = 16;
+
+/// Generates a sketch with 2^4 to 2^16 registers, either by adding arbitrary
+/// hashes, which leaves a lightly filled sketch sparse, or by filling the
+/// registers with arbitrary legal ranks. A hasher with keys gets arbitrary
+/// ones.
+impl<'a, S: StableBuildHasher> Arbitrary<'a> for HyperLogLog<S> {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<HyperLogLog<S>> {
+        let p = u.int_in_range(MIN_PRECISION..=MAX_ARBITRARY_PRECISION)?;
+        let build_hasher = match S::from_keys(None) {
+            Some(build_hasher) => build_hasher,
+            None => S::from_keys(Some(u.arbitrary()?)).ok_or(Error::IncorrectFormat)?,
+        };
+        if u.arbitrary()? {
+            let mut h = HyperLogLog::with_hasher(1 << p, build_hasher);
+            for hash in u.arbitrary_iter()? {
+                h.add_hashed(hash?);
+            }
+            Ok(h)
+        } else {
+            let max = max_rank(p);
+            let registers = (0..1usize << p)
+                .map(|_| u.int_in_range(0..=max))
+                .collect::<Result<Vec<u8>>>()?;
+            Ok(HyperLogLog::from_dense(p, registers, build_hasher))
+        }
+    }
+}
+
+impl<S: StableBuildHasher> HyperLogLog<S> {
+    /// Generates bytes that [`from_bytes`](HyperLogLog::from_bytes) rejects,
+    /// for negative tests of code decoding sketches.
+    ///
+    /// They are the encoding of an arbitrary sketch, dense or compressed,
+    /// broken in one way: cut short, followed by extra bytes, or with a bad
+    /// magic number, version, precision or hash backend, or a register
+    /// holding a rank no hash could produce.
+    pub fn arbitrary_corrupt_bytes(u: &mut Unstructured) -> Result<Vec<u8>> {
+        let sketch = HyperLogLog::<S>::arbitrary(u)?;
+        let mut bytes = if u.arbitrary()? {
+            sketch.to_bytes()
+        } else {
+            sketch.to_bytes_compressed()
+        };
+        match u.int_in_range(0..=6)? {
+            0 => {
+                let len = u.int_in_range(0..=bytes.len() - 1)?;
+                bytes.truncate(len);
+            }
+            1 => {
+                bytes.push(u.arbitrary()?);
+                bytes.extend(u.arbitrary_iter::<u8>()?.flatten());
+            }
+            2 => {
+                let at = u.int_in_range(0..=3)?;
+                bytes[at] ^= u.int_in_range(1..=255)?;
+            }
+            3 => {
+                // Any version but 1 and 2, the two `from_bytes` reads.
+                let version = u.int_in_range(2..=255)?;
+                bytes[4] = if version == 2 { 0 } else { version };
+            }
+            4 => {
+                let precision = u.int_in_range(32..=255)?;
+                bytes[5] = if precision == 32 { 0 } else { precision };
+            }
+            5 => {
+                let code = u.int_in_range(3..=7)?;
+                bytes[6] = bytes[6] & !(7 << 1) | code << 1;
+            }
+            _ => {
+                bytes = sketch.to_bytes();
+                let m = sketch.num_registers();
+                let index = u.int_in_range(0..=m - 1)?;
+                let at = bytes.len() - m + index;
+                bytes[at] = u.int_in_range(max_rank(sketch.precision) + 1..=255)?;
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    use simulation::splitmix64;
+    use {DefaultBuildHasher, HyperLogLog, SeededState, StableBuildHasher};
+
+    /// Fuzzer-like input: a few bytes to a few tens of kilobytes.
+    fn input(state: &mut u64) -> Vec<u8> {
+        let len = 1usize << (splitmix64(state) % 16);
+        (0..len).map(|_| splitmix64(state) as u8).collect()
+    }
+
+    /// The invariants every sketch satisfies, checked on three generated
+    /// ones brought to a common precision.
+    fn check_invariants<S: StableBuildHasher + Clone>(input: &[u8]) {
+        let mut u = Unstructured::new(input);
+        let sketches: Vec<HyperLogLog<S>> = (0..3)
+            .map(|_| HyperLogLog::arbitrary(&mut u).unwrap())
+            .collect();
+        for h in &sketches {
+            let count = h.count();
+            assert!(count.is_finite() && count >= 0., "count {}", count);
+            assert_eq!(HyperLogLog::<S>::from_bytes(&h.to_bytes()).as_ref(), Ok(h));
+            // Merging is idempotent.
+            let mut twice = h.clone();
+            twice.merge(h).unwrap();
+            assert_eq!(&twice, h);
+        }
+
+        let p = sketches.iter().map(HyperLogLog::precision).min().unwrap();
+        // Generated keys differ from sketch to sketch.
+        let common: Vec<HyperLogLog<S>> = sketches
+            .iter()
+            .map(|h| {
+                let mut h = h.reduce_precision(p).unwrap();
+                h.assume_same_hasher();
+                h
+            })
+            .collect();
+        let (a, b, c) = (&common[0], &common[1], &common[2]);
+        // Commutative and associative.
+        assert_eq!(a.union(b).unwrap(), b.union(a).unwrap());
+        assert_eq!(
+            a.union(b).unwrap().union(c).unwrap(),
+            a.union(&b.union(c).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn generated_sketches_keep_invariants() {
+        let mut state = 68;
+        for _ in 0..300 {
+            let input = input(&mut state);
+            check_invariants::<DefaultBuildHasher>(&input);
+            check_invariants::<SeededState>(&input);
+        }
+    }
+
+    #[test]
+    fn generated_sketches_are_varied() {
+        let mut state = 68;
+        let (mut sparse, mut dense, mut precisions) = (0, 0, Vec::new());
+        for _ in 0..300 {
+            let input = input(&mut state);
+            let h: HyperLogLog = HyperLogLog::arbitrary(&mut Unstructured::new(&input)).unwrap();
+            if h.is_sparse() {
+                sparse += 1;
+            } else {
+                dense += 1;
+            }
+            if !precisions.contains(&h.precision()) {
+                precisions.push(h.precision());
+            }
+        }
+        assert!(
+            sparse > 30 && dense > 30,
+            "{} sparse, {} dense",
+            sparse,
+            dense
+        );
+        precisions.sort_unstable();
+        assert_eq!(precisions, (4..=16).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn corrupt_bytes_are_rejected() {
+        let mut state = 68;
+        for _ in 0..2_000 {
+            let input = input(&mut state);
+            let mut u = Unstructured::new(&input);
+            let bytes = HyperLogLog::<DefaultBuildHasher>::arbitrary_corrupt_bytes(&mut u).unwrap();
+            assert!(HyperLogLog::<DefaultBuildHasher>::from_bytes(&bytes).is_err());
+            let bytes = HyperLogLog::<SeededState>::arbitrary_corrupt_bytes(&mut u).unwrap();
+            assert!(HyperLogLog::<SeededState>::from_bytes(&bytes).is_err());
+        }
+    }
+}
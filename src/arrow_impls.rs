@@ -0,0 +1,318 @@
+//! Passing sketches through Apache Arrow arrays, for approximate distinct
+//! count aggregates in DataFusion and other Arrow engines.
+//!
+//! An aggregate's accumulator needs four things, and each is one call:
+//! [`add_arrow_array`](HyperLogLog::add_arrow_array) adds a batch of input
+//! values, [`to_scalar_state`](HyperLogLog::to_scalar_state) gives the
+//! partial state as one `FixedSizeBinary` value in the crate's binary
+//! format, [`merge_arrow_states`](HyperLogLog::merge_arrow_states) merges
+//! an array of other accumulators' states, and [`count`](HyperLogLog::count)
+//! gives the result. DataFusion itself isn't a dependency; its
+//! `Accumulator` for `approx_count_distinct` is a sketch that forwards to
+//! those, with [`state_data_type`](HyperLogLog::state_data_type) as the
+//! type of its one state field:
+//!
+//! ```ignore
+//! impl Accumulator for ApproxDistinct {
+//!     fn update_batch(&mut self, values: &[ArrayRef]) -> Result<()> {
+//!         self.0.add_arrow_array(&values[0]).map_err(external)?;
+//!         Ok(())
+//!     }
+//!
+//!     fn merge_batch(&mut self, states: &[ArrayRef]) -> Result<()> {
+//!         self.0.merge_arrow_states(&states[0]).map_err(external)?;
+//!         Ok(())
+//!     }
+//!
+//!     fn state(&mut self) -> Result<Vec<ScalarValue>> {
+//!         let state = self.0.to_scalar_state().into_inner();
+//!         Ok(vec![ScalarValue::try_from_array(&state, 0)?])
+//!     }
+//!
+//!     fn evaluate(&mut self) -> Result<ScalarValue> {
+//!         Ok(ScalarValue::UInt64(Some(self.0.count().round() as u64)))
+//!     }
+//!
+//!     fn size(&self) -> usize {
+//!         self.0.memory_bytes()
+//!     }
+//! }
+//! ```
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    ArrowPrimitiveType, Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type, UInt32Type,
+    UInt64Type, UInt8Type,
+};
+use arrow_array::{Array, FixedSizeBinaryArray, Scalar};
+use arrow_schema::DataType;
+
+use error::ArrowStateError;
+use {HyperLogLog, StableBuildHasher};
+
+impl<S: BuildHasher> HyperLogLog<S> {
+    /// Adds every non-null value of `array`, returning how many raised a
+    /// register, as [`add_all`](HyperLogLog::add_all) does.
+    ///
+    /// String and binary values (`Utf8`, `LargeUtf8`, `Utf8View`, `Binary`,
+    /// `LargeBinary`, `BinaryView` and `FixedSizeBinary`) are added as
+    /// [`add_bytes`](HyperLogLog::add_bytes) adds their bytes, so a string
+    /// and the same bytes as binary are the same item. Integers of every
+    /// width are added as [`add`](HyperLogLog::add) adds the native value,
+    /// so an `Int64` column agrees with `add(x as i64)`, but the same number
+    /// in an `Int32` column is a different item. Any other type fails with
+    /// [`ArrowStateError::UnsupportedType`] and adds nothing.
+    pub fn add_arrow_array(&mut self, array: &dyn Array) -> Result<usize, ArrowStateError> {
+        let hashes: Vec<u64> = match *array.data_type() {
+            DataType::Int8 => self.primitive_hashes::<Int8Type>(array),
+            DataType::Int16 => self.primitive_hashes::<Int16Type>(array),
+            DataType::Int32 => self.primitive_hashes::<Int32Type>(array),
+            DataType::Int64 => self.primitive_hashes::<Int64Type>(array),
+            DataType::UInt8 => self.primitive_hashes::<UInt8Type>(array),
+            DataType::UInt16 => self.primitive_hashes::<UInt16Type>(array),
+            DataType::UInt32 => self.primitive_hashes::<UInt32Type>(array),
+            DataType::UInt64 => self.primitive_hashes::<UInt64Type>(array),
+            ref other => match string_values(array).or_else(|| binary_values(array)) {
+                Some(values) => values.flatten().map(|v| self.hash_bytes(v)).collect(),
+                None => return Err(ArrowStateError::UnsupportedType(other.clone())),
+            },
+        };
+        Ok(self.raise_hashes(hashes.into_iter()))
+    }
+
+    fn primitive_hashes<T: ArrowPrimitiveType>(&self, array: &dyn Array) -> Vec<u64>
+    where
+        T::Native: Hash,
+    {
+        let array = array.as_primitive::<T>();
+        array
+            .iter()
+            .flatten()
+            .map(|v| self.build_hasher.hash_one(v))
+            .collect()
+    }
+}
+
+impl<S: StableBuildHasher> HyperLogLog<S> {
+    /// The sketch as a one-value `FixedSizeBinary` array of its
+    /// [`to_bytes`](HyperLogLog::to_bytes) encoding, for an aggregate's
+    /// partial state.
+    pub fn to_scalar_state(&self) -> Scalar<FixedSizeBinaryArray> {
+        let state = FixedSizeBinaryArray::try_from_iter(Some(self.to_bytes()).into_iter())
+            .expect("one value has one width");
+        Scalar::new(state)
+    }
+
+    /// The type of [`to_scalar_state`](HyperLogLog::to_scalar_state)'s
+    /// value: `FixedSizeBinary` of a width that depends only on the
+    /// precision, the hasher and whether the sketch is monotonic, so every
+    /// accumulator of one aggregate has the same.
+    pub fn state_data_type(&self) -> DataType {
+        DataType::FixedSizeBinary(self.bytes_len() as i32)
+    }
+
+    /// Decodes the state at `index` of `states`, a `FixedSizeBinary`,
+    /// `Binary`, `LargeBinary` or `BinaryView` array of sketches in either
+    /// version of the binary format.
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn from_scalar_state(
+        states: &dyn Array,
+        index: usize,
+    ) -> Result<HyperLogLog<S>, ArrowStateError> {
+        let state = binary_values(states)
+            .ok_or_else(|| ArrowStateError::UnsupportedType(states.data_type().clone()))?
+            .nth(index)
+            .expect("state index out of bounds")
+            .ok_or(ArrowStateError::NullState(index))?;
+        HyperLogLog::from_bytes(state).map_err(|error| ArrowStateError::Decode { index, error })
+    }
+
+    /// Merges every non-null state of `states` into `self`, as an
+    /// aggregate's final stage combines its partial states, returning
+    /// whether any register rose.
+    ///
+    /// `states` is of the types [`from_scalar_state`] reads. On an error,
+    /// the states before the failing one have been merged.
+    ///
+    /// [`from_scalar_state`]: HyperLogLog::from_scalar_state
+    pub fn merge_arrow_states(&mut self, states: &dyn Array) -> Result<bool, ArrowStateError> {
+        let values = binary_values(states)
+            .ok_or_else(|| ArrowStateError::UnsupportedType(states.data_type().clone()))?;
+        let mut raised = false;
+        for (index, state) in values.enumerate() {
+            if let Some(state) = state {
+                let other = HyperLogLog::from_bytes(state)
+                    .map_err(|error| ArrowStateError::Decode { index, error })?;
+                raised |= self
+                    .merge(&other)
+                    .map_err(|error| ArrowStateError::Merge { index, error })?;
+            }
+        }
+        Ok(raised)
+    }
+}
+
+type Values<'a> = Box<dyn Iterator<Item = Option<&'a [u8]>> + 'a>;
+
+/// The bytes of each value of a string array, or `None` for another type.
+fn string_values(array: &dyn Array) -> Option<Values<'_>> {
+    Some(match *array.data_type() {
+        DataType::Utf8 => Box::new(
+            array
+                .as_string::<i32>()
+                .iter()
+                .map(|v| v.map(str::as_bytes)),
+        ),
+        DataType::LargeUtf8 => Box::new(
+            array
+                .as_string::<i64>()
+                .iter()
+                .map(|v| v.map(str::as_bytes)),
+        ),
+        DataType::Utf8View => Box::new(array.as_string_view().iter().map(|v| v.map(str::as_bytes))),
+        _ => return None,
+    })
+}
+
+/// Each value of a binary array, or `None` for another type.
+fn binary_values(array: &dyn Array) -> Option<Values<'_>> {
+    Some(match *array.data_type() {
+        DataType::Binary => Box::new(array.as_binary::<i32>().iter()),
+        DataType::LargeBinary => Box::new(array.as_binary::<i64>().iter()),
+        DataType::BinaryView => Box::new(array.as_binary_view().iter()),
+        DataType::FixedSizeBinary(_) => Box::new(array.as_fixed_size_binary().iter()),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use arrow_array::{
+        Array, BinaryArray, FixedSizeBinaryArray, Float64Array, Int32Array, Int64Array,
+        LargeStringArray, StringArray, UInt8Array,
+    };
+    use arrow_schema::DataType;
+
+    use error::{ArrowStateError, DecodeError, MergeError};
+    use simulation::splitmix64;
+    use {HyperLogLog, SeededState};
+
+    #[test]
+    fn adds_values_as_add_and_add_bytes_do() {
+        let mut h = HyperLogLog::new_seeded(1 << 8, 89);
+        let mut expected = h.clone();
+        let strings = StringArray::from(vec![Some("a"), None, Some("b"), Some("a")]);
+        assert_eq!(h.add_arrow_array(&strings), Ok(2));
+        expected.add_bytes("a");
+        expected.add_bytes("b");
+        assert_eq!(h, expected);
+
+        // The same bytes as binary or large strings are the same items.
+        let binary = BinaryArray::from(vec![Some(&b"b"[..]), Some(&b"c"[..]), None]);
+        assert_eq!(h.add_arrow_array(&binary), Ok(1));
+        let large = LargeStringArray::from(vec!["a", "c"]);
+        assert_eq!(h.add_arrow_array(&large), Ok(0));
+        expected.add_bytes("c");
+        assert_eq!(h, expected);
+
+        h.add_arrow_array(&Int64Array::from(vec![Some(-5), None, Some(7)]))
+            .unwrap();
+        h.add_arrow_array(&UInt8Array::from(vec![200])).unwrap();
+        expected.add(-5i64);
+        expected.add(7i64);
+        expected.add(200u8);
+        assert_eq!(h, expected);
+
+        assert_eq!(
+            h.add_arrow_array(&Float64Array::from(vec![1.5])),
+            Err(ArrowStateError::UnsupportedType(DataType::Float64))
+        );
+        assert_eq!(h, expected);
+    }
+
+    #[test]
+    fn merges_partial_states_like_an_aggregate() {
+        let mut state = 89;
+        let mut exact = HashSet::new();
+        let mut partials = Vec::new();
+        for _ in 0..4 {
+            let mut partial = HyperLogLog::new_seeded(1 << 12, 89);
+            for _ in 0..3 {
+                let values: Vec<Option<i32>> = (0..2_000)
+                    .map(|_| match splitmix64(&mut state) % 10_000 {
+                        0..=99 => None,
+                        v => Some(v as i32),
+                    })
+                    .collect();
+                exact.extend(values.iter().flatten().cloned());
+                partial.add_arrow_array(&Int32Array::from(values)).unwrap();
+            }
+            let scalar = partial.to_scalar_state().into_inner();
+            assert_eq!(*scalar.data_type(), partial.state_data_type());
+            partials.push(scalar.value(0).to_vec());
+        }
+        let mut states: Vec<Option<Vec<u8>>> = partials.iter().cloned().map(Some).collect();
+        states.insert(2, None);
+        let states = FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+            states.into_iter(),
+            partials[0].len() as i32,
+        )
+        .unwrap();
+
+        let mut total = HyperLogLog::new_seeded(1 << 12, 89);
+        assert_eq!(total.merge_arrow_states(&states), Ok(true));
+        assert_eq!(total.merge_arrow_states(&states), Ok(false));
+        let mut expected = HyperLogLog::new_seeded(1 << 12, 89);
+        for partial in &partials {
+            expected
+                .merge(&HyperLogLog::from_bytes(partial).unwrap())
+                .unwrap();
+        }
+        assert_eq!(total, expected);
+        let truth = exact.len() as f64;
+        assert!((total.count() - truth).abs() < 3. * total.error_estimate() * truth);
+
+        let first: HyperLogLog<SeededState> = HyperLogLog::from_scalar_state(&states, 0).unwrap();
+        assert_eq!(first.to_bytes(), partials[0]);
+        assert_eq!(
+            HyperLogLog::<SeededState>::from_scalar_state(&states, 2),
+            Err(ArrowStateError::NullState(2))
+        );
+    }
+
+    #[test]
+    fn rejects_bad_states() {
+        let mut h = HyperLogLog::new_seeded(1 << 4, 89);
+        let good = h.to_bytes();
+        let coarse = HyperLogLog::new_seeded(1 << 5, 89).to_bytes();
+        let states = BinaryArray::from(vec![&good[..], &coarse[..], b"junk"]);
+        assert_eq!(
+            h.merge_arrow_states(&states),
+            Err(ArrowStateError::Merge {
+                index: 1,
+                error: MergeError::PrecisionMismatch { left: 4, right: 5 }
+            })
+        );
+        assert_eq!(
+            h.merge_arrow_states(&states.slice(2, 1)),
+            Err(ArrowStateError::Decode {
+                index: 0,
+                error: DecodeError::Truncated {
+                    expected: 7,
+                    actual: 4
+                }
+            })
+        );
+        assert_eq!(
+            h.merge_arrow_states(&StringArray::from(vec!["state"])),
+            Err(ArrowStateError::UnsupportedType(DataType::Utf8))
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Adding the items of an async `Stream`, for ingestion loops that receive
+//! items asynchronously.
+//!
+//! The futures poll the stream until it ends, adding each item as it
+//! arrives, so nothing is buffered. A stream that always has an item ready
+//! would otherwise keep one `poll` running until it ended; after every
+//! `YIELD_EVERY` items the future instead wakes itself and returns
+//! `Pending`, letting the executor run other tasks. Only `futures-core` is
+//! needed, and any executor can drive the futures.
+//!
+//! The streams must be `Unpin`; pin one that isn't with `Box::pin`.
+
+use core::future::Future;
+use core::hash::{BuildHasher, Hash};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use futures_core::Stream;
+
+#[cfg(target_has_atomic = "8")]
+use AtomicHyperLogLog;
+use HyperLogLog;
+#[cfg(feature = "std")]
+use ShardedHyperLogLog;
+
+/// How many items a stream future adds in one `poll` before yielding.
+const YIELD_EVERY: u64 = 1024;
+
+/// Feeds every item of `stream` to `add`, resolving to the number of items.
+struct AddStream<St, F> {
+    stream: St,
+    add: F,
+    added: u64,
+}
+
+impl<St, F> Future for AddStream<St, F>
+where
+    St: Stream + Unpin,
+    F: FnMut(St::Item) + Unpin,
+{
+    type Output = u64;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<u64> {
+        let this = self.get_mut();
+        for _ in 0..YIELD_EVERY {
+            match Pin::new(&mut this.stream).poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    (this.add)(item);
+                    this.added += 1;
+                }
+                Poll::Ready(None) => return Poll::Ready(this.added),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+fn add_stream<St, F>(stream: St, add: F) -> AddStream<St, F> {
+    AddStream {
+        stream,
+        add,
+        added: 0,
+    }
+}
+
+impl<S: BuildHasher> HyperLogLog<S> {
+    /// Adds every item of `stream` as [`add`](HyperLogLog::add) would,
+    /// resolving to the number of items once the stream ends.
+    ///
+    /// The future yields to the executor after every 1024 items, so a
+    /// stream that is always ready won't hold up other tasks.
+    pub fn add_stream<'a, St>(&'a mut self, stream: St) -> impl Future<Output = u64> + 'a
+    where
+        St: Stream + Unpin + 'a,
+        St::Item: Hash,
+    {
+        add_stream(stream, move |item| self.add(item))
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl<S: BuildHasher> AtomicHyperLogLog<S> {
+    /// Adds every item of `stream`, resolving to the number of items once
+    /// the stream ends. Any number of these can run at once, from tasks on
+    /// any threads.
+    pub fn add_stream<'a, St>(&'a self, stream: St) -> impl Future<Output = u64> + 'a
+    where
+        St: Stream + Unpin + 'a,
+        St::Item: Hash,
+    {
+        add_stream(stream, move |item| self.add(item))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<S: BuildHasher + Clone> ShardedHyperLogLog<S> {
+    /// Adds every item of `stream` to the shard of whichever thread polls
+    /// the future, resolving to the number of items once the stream ends.
+    /// Any number of these can run at once, from tasks on any threads.
+    pub fn add_stream<'a, St>(&'a self, stream: St) -> impl Future<Output = u64> + 'a
+    where
+        St: Stream + Unpin + 'a,
+        St::Item: Hash,
+    {
+        add_stream(stream, move |item| self.add(item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+    use std::sync::Arc;
+    use std::task::Wake;
+
+    use futures_util::stream;
+
+    use super::YIELD_EVERY;
+    use HyperLogLog;
+
+    struct Woken;
+
+    impl Wake for Woken {
+        fn wake(self: Arc<Woken>) {}
+    }
+
+    /// Polls `future` to completion on this thread, returning the result and
+    /// how many polls it took.
+    fn block_on<F: Future>(future: F) -> (F::Output, usize) {
+        let waker = Arc::new(Woken).into();
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        let mut polls = 1;
+        loop {
+            if let Poll::Ready(out) = Pin::as_mut(&mut future).poll(&mut cx) {
+                return (out, polls);
+            }
+            polls += 1;
+        }
+    }
+
+    #[test]
+    fn matches_synchronous_ingestion() {
+        let mut sync = HyperLogLog::new_seeded(1 << 12, 77);
+        (0..10_000u32).for_each(|i| sync.add(i));
+
+        let mut streamed = HyperLogLog::new_seeded(1 << 12, 77);
+        let (added, polls) = block_on(streamed.add_stream(stream::iter(0..10_000u32)));
+        assert_eq!(added, 10_000);
+        assert_eq!(streamed, sync);
+        // One poll per YIELD_EVERY items, and one more to see the end.
+        assert_eq!(polls as u64, 10_000 / YIELD_EVERY + 1);
+
+        let mut empty = HyperLogLog::new(1 << 4);
+        assert_eq!(
+            block_on(empty.add_stream(stream::iter(Vec::<u8>::new()))),
+            (0, 1)
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", target_has_atomic = "8", not(target_arch = "wasm32")))]
+    fn concurrent_tasks() {
+        use {AtomicHyperLogLog, SeededState, ShardedHyperLogLog};
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(4)
+            .build()
+            .unwrap();
+        // Spawned tasks must be 'static, and so must what they borrow.
+        let atomic: &'static _ = Box::leak(Box::new(AtomicHyperLogLog::with_hasher(
+            1 << 12,
+            SeededState::new(77),
+        )));
+        let sharded: &'static _ = Box::leak(Box::new(ShardedHyperLogLog::with_shards(
+            1 << 12,
+            4,
+            SeededState::new(77),
+        )));
+        let mut tasks = Vec::new();
+        for t in 0..8u64 {
+            // Overlapping ranges.
+            let items = t * 5_000..t * 5_000 + 12_000;
+            tasks.push(runtime.spawn(atomic.add_stream(stream::iter(items.clone()))));
+            tasks.push(runtime.spawn(sharded.add_stream(stream::iter(items))));
+        }
+        let added: u64 = tasks
+            .into_iter()
+            .map(|task| runtime.block_on(task).unwrap())
+            .sum();
+        assert_eq!(added, 2 * 8 * 12_000);
+
+        let mut sync = HyperLogLog::new_seeded(1 << 12, 77);
+        (0..47_000u64).for_each(|i| sync.add(i));
+        assert_eq!(atomic.snapshot(), sync);
+        assert_eq!(sharded.snapshot(), sync);
+        assert_eq!(sharded.count(), sync.count());
+    }
+}
@@ -0,0 +1,247 @@
+//! A sketch that many threads can add to at once without locking.
+//!
+//! The only register update is a monotonic maximum, so each register is an
+//! `AtomicU8` raised with `fetch_max`. Updates commute, which makes relaxed
+//! ordering enough: once every writer has finished, the registers are exactly
+//! those a single-threaded sketch fed the same items would hold.
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use error::MergeError;
+use {
+    check_register_count, estimate, estimate_beta, estimate_improved, index_and_rank,
+    rank_histogram, standard_error, DefaultBuildHasher, HasherId, HyperLogLog,
+};
+
+/// A HyperLogLog sketch with atomic registers, whose `add` takes `&self` so
+/// it can be shared between threads, e.g. behind an `Arc`.
+///
+/// `count` may run while other threads are adding; it then reflects some of
+/// the concurrent additions and not others. Convert to a [`HyperLogLog`] to
+/// serialize a sketch or merge it with others.
+pub struct AtomicHyperLogLog<S = DefaultBuildHasher> {
+    registers: Vec<AtomicU8>,
+    precision: u8,
+    build_hasher: S,
+}
+
+impl AtomicHyperLogLog {
+    /// Creates a sketch with `m` registers; panics like [`HyperLogLog::new`].
+    pub fn new(m: usize) -> AtomicHyperLogLog {
+        AtomicHyperLogLog::with_hasher(m, DefaultBuildHasher::default())
+    }
+}
+
+impl<S> AtomicHyperLogLog<S> {
+    fn values(&self) -> impl ExactSizeIterator<Item = u8> + '_ {
+        self.registers.iter().map(|r| r.load(Ordering::Relaxed))
+    }
+
+    /// Adds an item by its precomputed 64-bit hash; see [`HyperLogLog::add_hashed`].
+    pub fn add_hashed(&self, hash: u64) {
+        let (index, rank) = index_and_rank(hash, self.precision);
+        let register = &self.registers[index];
+        // Most adds don't raise the register; a plain load avoids contending
+        // on the cache line for those.
+        if register.load(Ordering::Relaxed) < rank {
+            register.fetch_max(rank, Ordering::Relaxed);
+        }
+    }
+
+    /// Estimates the cardinality; see [`HyperLogLog::count`].
+    pub fn count(&self) -> f64 {
+        estimate(&rank_histogram(self.values()))
+    }
+
+    /// Estimates the cardinality with LogLog-Beta; see [`HyperLogLog::count_beta`].
+    pub fn count_beta(&self) -> f64 {
+        estimate_beta(&rank_histogram(self.values()))
+    }
+
+    /// Estimates the cardinality with Ertl's improved raw estimator; see
+    /// [`HyperLogLog::count_improved`].
+    pub fn count_improved(&self) -> f64 {
+        estimate_improved(&rank_histogram(self.values()))
+    }
+
+    /// The standard error of the estimate; see [`HyperLogLog::error_estimate`].
+    pub fn error_estimate(&self) -> f64 {
+        standard_error(self.precision)
+    }
+
+    /// Copies the current registers into a plain sketch.
+    pub fn snapshot(&self) -> HyperLogLog<S>
+    where
+        S: Clone,
+    {
+        HyperLogLog::from_dense(
+            self.precision,
+            self.values().collect(),
+            self.build_hasher.clone(),
+        )
+    }
+}
+
+impl<S: BuildHasher> AtomicHyperLogLog<S> {
+    /// Creates a sketch with `m` registers that hashes items with `build_hasher`.
+    pub fn with_hasher(m: usize, build_hasher: S) -> AtomicHyperLogLog<S> {
+        let precision = match check_register_count(m) {
+            Ok(p) => p,
+            Err(e) => panic!("{}", e),
+        };
+        AtomicHyperLogLog {
+            registers: (0..m).map(|_| AtomicU8::new(0)).collect(),
+            precision,
+            build_hasher,
+        }
+    }
+
+    pub fn add<H: Hash>(&self, item: H) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h);
+    }
+
+    /// Folds `other` into `self` by taking the register-wise maximum, failing
+    /// as [`HyperLogLog::merge`] does if the two have different precisions or
+    /// hashers.
+    pub fn merge(&self, other: &HyperLogLog<S>) -> Result<(), MergeError> {
+        if self.precision != other.precision {
+            return Err(MergeError::PrecisionMismatch {
+                left: self.precision,
+                right: other.precision,
+            });
+        }
+        let left = HasherId::of(&self.build_hasher);
+        match other.hasher_id() {
+            Some(right) if right != left => return Err(MergeError::HasherMismatch { left, right }),
+            _ => {}
+        }
+        for (register, o) in self.registers.iter().zip(other.registers()) {
+            register.fetch_max(o, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+}
+
+/// Takes the sketch's registers and hasher. An atomic sketch has nowhere to
+/// keep the rest, so its [estimator](HyperLogLog::set_estimator), the
+/// [monotonic](HyperLogLog::set_monotonic) watermark and
+/// [`items_added`](HyperLogLog::items_added) are dropped: it counts with the
+/// bias-corrected estimator, and converting back starts them afresh.
+impl<S> From<HyperLogLog<S>> for AtomicHyperLogLog<S> {
+    fn from(hll: HyperLogLog<S>) -> AtomicHyperLogLog<S> {
+        AtomicHyperLogLog {
+            registers: hll.registers().map(AtomicU8::new).collect(),
+            precision: hll.precision,
+            build_hasher: hll.build_hasher,
+        }
+    }
+}
+
+impl<S> From<AtomicHyperLogLog<S>> for HyperLogLog<S> {
+    fn from(sketch: AtomicHyperLogLog<S>) -> HyperLogLog<S> {
+        let registers = sketch
+            .registers
+            .into_iter()
+            .map(AtomicU8::into_inner)
+            .collect();
+        HyperLogLog::from_dense(sketch.precision, registers, sketch.build_hasher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::AtomicHyperLogLog;
+    use error::MergeError;
+    use HyperLogLog;
+
+    #[test]
+    fn concurrent_adds_match_single_threaded() {
+        const THREADS: u64 = 16;
+        let shared = Arc::new(AtomicHyperLogLog::new(1 << 12));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let shared = Arc::clone(&shared);
+                // Each range overlaps half of the next thread's.
+                thread::spawn(move || {
+                    for i in t * 10_000..t * 10_000 + 20_000 {
+                        shared.add(i);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let mut single = HyperLogLog::new(1 << 12);
+        single.extend(0..(THREADS + 1) * 10_000);
+        let shared = Arc::try_unwrap(shared).ok().unwrap();
+        assert_eq!(shared.count(), single.count());
+        let plain = HyperLogLog::from(shared);
+        assert_eq!(
+            plain.registers().collect::<Vec<_>>(),
+            single.registers().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn count_while_adding() {
+        let shared = Arc::new(AtomicHyperLogLog::new(1 << 10));
+        let writer = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                for i in 0..100_000u64 {
+                    shared.add(i);
+                }
+            })
+        };
+        // Concurrent reads see a partial sketch, never a torn or corrupt one.
+        let bound = 100_000. * (1. + 3. * shared.error_estimate());
+        while !writer.is_finished() {
+            let c = shared.count();
+            assert!((0. ..=bound).contains(&c), "{}", c);
+        }
+        writer.join().unwrap();
+        let mut single = HyperLogLog::new(1 << 10);
+        single.extend(0..100_000u64);
+        assert_eq!(shared.count(), single.count());
+    }
+
+    #[test]
+    fn round_trip_and_merge() {
+        let mut plain = HyperLogLog::new(1 << 8);
+        plain.extend(0..5_000u64);
+        let atomic = AtomicHyperLogLog::from(plain.clone());
+        assert_eq!(atomic.snapshot(), plain);
+
+        let mut other = HyperLogLog::new(1 << 8);
+        other.extend(4_000..9_000u64);
+        atomic.merge(&other).unwrap();
+        plain.merge(&other).unwrap();
+        assert_eq!(HyperLogLog::from(atomic), plain);
+
+        let small = AtomicHyperLogLog::new(1 << 4);
+        assert!(small.merge(&other).is_err());
+
+        let seeded = AtomicHyperLogLog::from(HyperLogLog::new_seeded(1 << 8, 99));
+        let mut reseeded = HyperLogLog::new_seeded(1 << 8, 100);
+        reseeded.add(1u64);
+        assert_eq!(
+            seeded.merge(&reseeded),
+            Err(MergeError::HasherMismatch {
+                left: seeded.snapshot().hasher_id().unwrap(),
+                right: reseeded.hasher_id().unwrap()
+            })
+        );
+        assert_eq!(seeded.count(), 0.);
+        reseeded.assume_same_hasher();
+        seeded.merge(&reseeded).unwrap();
+        assert!(seeded.snapshot().registers().eq(reseeded.registers()));
+    }
+}
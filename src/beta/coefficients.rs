@@ -0,0 +1,174 @@
+//! Generated by `cargo test --release generate_beta_coefficients -- --ignored`;
+//! do not edit.
+//!
+//! `COEFFICIENTS[p - 4]` holds the coefficient of `z`, then of `ln(z + 1)`
+//! to the first through seventh powers, for precision `p`.
+
+#[rustfmt::skip]
+pub(crate) const COEFFICIENTS: [[f64; 8]; 15] = [
+    // p = 4, 5000 trials
+    [
+        3.9452470913697975e3,
+        -3.960506129818113e3,
+        -1.9052292488818825e3,
+        -7.849483009223097e2,
+        -3.7969772186047585e1,
+        -1.0381559926098005e2,
+        1.6572244643879554e1,
+        -4.051313998189173e0,
+    ],
+    // p = 5, 5000 trials
+    [
+        -5.442599132730172e2,
+        5.480861298317595e2,
+        2.5228673630712686e2,
+        1.2531918316384692e2,
+        -8.718005143965664e0,
+        2.0424601959242075e1,
+        -3.6611520612146227e0,
+        6.834529184205627e-1,
+    ],
+    // p = 6, 5000 trials
+    [
+        1.5006332011737921e1,
+        -1.5155479593655262e1,
+        -9.043567497358127e0,
+        -8.447633740611481e-1,
+        -1.337626432140035e0,
+        -1.1478837188194675e-1,
+        4.174113703990988e-2,
+        -1.6606204813863056e-2,
+    ],
+    // p = 7, 5000 trials
+    [
+        -6.4074037295243205e0,
+        5.351655896327075e0,
+        3.9876947554416895e0,
+        9.29006867814356e-1,
+        -1.369907725478937e-1,
+        3.3442077378722773e-1,
+        -7.297476032998222e-2,
+        1.0733404230749173e-2,
+    ],
+    // p = 8, 5000 trials
+    [
+        -5.92159792970672e0,
+        6.332230112903528e0,
+        -6.993714439091474e-1,
+        6.03671162492974e0,
+        -3.1466112378160083e0,
+        1.2415165243988817e0,
+        -2.128926402517191e-1,
+        1.8916870987375427e-2,
+    ],
+    // p = 9, 5000 trials
+    [
+        -5.002445317467896e-1,
+        -7.827655324345982e-1,
+        1.9121129290363388e0,
+        -1.1588467001605016e0,
+        4.3621249077480856e-1,
+        -7.181244132573321e-2,
+        5.614561589818979e-3,
+        2.8829822826324608e-5,
+    ],
+    // p = 10, 5000 trials
+    [
+        -5.564124568093766e-1,
+        4.294721839789155e-1,
+        -7.409804120385377e-1,
+        1.2925633035097819e0,
+        -6.57067869272414e-1,
+        1.901241345259512e-1,
+        -2.6044682450591095e-2,
+        1.6238343312582292e-3,
+    ],
+    // p = 11, 5000 trials
+    [
+        -4.61322531225114e-1,
+        1.560362534691667e-1,
+        -5.860816675306915e-1,
+        1.1843942124498348e0,
+        -5.945032092297847e-1,
+        1.6004455947551055e-1,
+        -2.0173880087141605e-2,
+        1.1545503117603138e-3,
+    ],
+    // p = 12, 2730 trials
+    [
+        -4.7790989737511885e-1,
+        9.233037282669212e-1,
+        -2.4389860501327947e0,
+        2.7353279314625834e0,
+        -1.2286822154522337e0,
+        2.994578993858541e-1,
+        -3.5623719178244334e-2,
+        1.8721859081142528e-3,
+    ],
+    // p = 13, 1260 trials
+    [
+        -3.680239461619966e-1,
+        -1.7313979854808514e0,
+        3.3126991282360803e0,
+        -1.9823107091475625e0,
+        6.206755200518301e-1,
+        -8.987946100070725e-2,
+        6.451600275554454e-3,
+        -5.781961384835756e-5,
+    ],
+    // p = 14, as published
+    [
+        -3.70393911e-1,
+        7.0471823e-2,
+        1.7393686e-1,
+        1.6339839e-1,
+        -9.237745e-2,
+        3.738027e-2,
+        -5.384159e-3,
+        4.2419e-4,
+    ],
+    // p = 15, 273 trials
+    [
+        -3.895618303618786e-1,
+        3.174804671581574e0,
+        -5.268369670972626e0,
+        4.640417455191543e0,
+        -1.8530282442379784e0,
+        3.8960728158964436e-1,
+        -4.025217847213794e-2,
+        1.8361746777577903e-3,
+    ],
+    // p = 16, 128 trials
+    [
+        -3.5027665770060573e-1,
+        -4.076330048496556e0,
+        8.745065036675822e0,
+        -6.606830330030268e0,
+        2.276222691679524e0,
+        -3.690455286666351e-1,
+        2.7607103391985206e-2,
+        -5.433600869765972e-4,
+    ],
+    // p = 17, 60 trials
+    [
+        -3.555060423079411e-1,
+        -1.380979329388894e1,
+        1.0877162387785042e1,
+        -3.2309739466772354e0,
+        2.2321199247191695e-1,
+        8.86327604841519e-2,
+        -1.780967126073391e-2,
+        1.1813566941119781e-3,
+    ],
+    // p = 18, 28 trials
+    [
+        -3.753479870056317e-1,
+        1.3354794771385647e1,
+        -4.644320316486454e1,
+        3.4940146569730885e1,
+        -1.1641492932785455e1,
+        1.9813468185427507e0,
+        -1.6882387910772054e-1,
+        6.042594453852171e-3,
+    ],
+];
@@ -0,0 +1,220 @@
+//! The LogLog-Beta estimator (Qin, Kim & Tung, 2016).
+//!
+//! LogLog-Beta replaces linear counting and the bias tables with one formula,
+//!
+//! ```text
+//! E = alpha * m * (m - z) / (sum + beta(z))
+//! ```
+//!
+//! where `z` is the number of zero registers, `sum` is the usual
+//! `Σ 2^-register`, and `beta(z)` is a degree-7 polynomial in `ln(z + 1)` plus
+//! a linear term in `z`. Because `E` varies smoothly with `z`, the estimate has
+//! no step where linear counting would have handed over to the raw estimate.
+//!
+//! The paper publishes coefficients for `p = 14`, which are used as is. The
+//! other precisions from 4 to 18 were fitted by simulation; regenerate them with
+//!
+//! ```text
+//! cargo test --release generate_beta_coefficients -- --ignored
+//! ```
+
+mod coefficients;
+
+use self::coefficients::COEFFICIENTS;
+use {alpha, math};
+
+pub(crate) const MIN_PRECISION: u8 = 4;
+pub(crate) const MAX_PRECISION: u8 = 18;
+
+fn beta(coefficients: &[f64; 8], zeros: usize) -> f64 {
+    let z = zeros as f64;
+    let zl = math::ln(z + 1.);
+    let mut polynomial = 0.;
+    for &c in coefficients[1..].iter().rev() {
+        polynomial = (polynomial + c) * zl;
+    }
+    coefficients[0] * z + polynomial
+}
+
+/// The LogLog-Beta estimate for `m` registers, or `None` if there are no
+/// coefficients for that size.
+pub(crate) fn estimate(m: usize, sum: f64, zeros: usize) -> Option<f64> {
+    let p = m.trailing_zeros() as u8;
+    if !(MIN_PRECISION..=MAX_PRECISION).contains(&p) {
+        return None;
+    }
+    let coefficients = &COEFFICIENTS[usize::from(p - MIN_PRECISION)];
+    let mf = m as f64;
+    Some(alpha(m) * mf * (mf - zeros as f64) / (sum + beta(coefficients, zeros)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate, MAX_PRECISION, MIN_PRECISION};
+    use alpha;
+    use simulation::Sim;
+    use std::fmt::Write;
+
+    /// Coefficients from the LogLog-Beta paper, for `p = 14`.
+    const PUBLISHED_P14: [f64; 8] = [
+        -0.370393911,
+        0.070471823,
+        0.17393686,
+        0.16339839,
+        -0.09237745,
+        0.03738027,
+        -0.005384159,
+        0.00042419,
+    ];
+
+    /// Incremental least squares by Givens rotations, which stays accurate
+    /// even though the powers of `ln(z + 1)` are nearly collinear.
+    struct LeastSquares {
+        /// Upper-triangular `R`, augmented with the rotated right-hand side.
+        r: [[f64; 9]; 8],
+    }
+
+    impl LeastSquares {
+        fn new() -> LeastSquares {
+            LeastSquares { r: [[0.; 9]; 8] }
+        }
+
+        fn add_row(&mut self, mut row: [f64; 9]) {
+            for k in 0..8 {
+                if row[k] == 0. {
+                    continue;
+                }
+                let (a, b) = (self.r[k][k], row[k]);
+                let h = a.hypot(b);
+                let (c, s) = (a / h, b / h);
+                for (x, y) in self.r[k][k..].iter_mut().zip(&mut row[k..]) {
+                    let (a, b) = (*x, *y);
+                    *x = c * a + s * b;
+                    *y = c * b - s * a;
+                }
+            }
+        }
+
+        fn solve(&self) -> [f64; 8] {
+            let mut x = [0.; 8];
+            for k in (0..8).rev() {
+                let known: f64 = (k + 1..8).map(|j| self.r[k][j] * x[j]).sum();
+                x[k] = (self.r[k][8] - known) / self.r[k][k];
+            }
+            x
+        }
+    }
+
+    /// Log-spaced cardinalities from 1 to well past the point where the
+    /// last register is filled, beyond which `beta` no longer matters.
+    fn checkpoints(p: u8) -> Vec<usize> {
+        let m = (1usize << p) as f64;
+        let max_n = 2. * m * m.ln();
+        let mut out: Vec<usize> = (0..=200)
+            .map(|k| max_n.powf(f64::from(k) / 200.).round() as usize)
+            .collect();
+        out.dedup();
+        out
+    }
+
+    /// Fits `beta` so that `E` matches the true cardinality, minimizing the
+    /// squared relative error of `E`.
+    fn fit(p: u8, trials: usize, seed: u64) -> [f64; 8] {
+        let m = 1usize << p;
+        let mf = m as f64;
+        let points = checkpoints(p);
+        let mut ls = LeastSquares::new();
+        let mut rng = seed;
+        for _ in 0..trials {
+            let mut sim = Sim::new(p);
+            for &n in &points {
+                sim.advance_to(n, &mut rng);
+                if sim.zeros == 0 {
+                    break;
+                }
+                // The denominator that would make E exact; an error of d in
+                // beta is a relative error of about d / denominator in E.
+                let denominator = alpha(m) * mf * (mf - sim.zeros as f64) / n as f64;
+                let weight = 1. / denominator;
+                let z = sim.zeros as f64;
+                let zl = (z + 1.).ln();
+                let mut row = [0.; 9];
+                row[0] = z * weight;
+                for (k, cell) in row[1..8].iter_mut().enumerate() {
+                    *cell = zl.powi(k as i32 + 1) * weight;
+                }
+                row[8] = (denominator - sim.sum) * weight;
+                ls.add_row(row);
+            }
+        }
+        ls.solve()
+    }
+
+    #[test]
+    #[ignore]
+    fn generate_beta_coefficients() {
+        let mut out = String::new();
+        for p in MIN_PRECISION..=MAX_PRECISION {
+            let m = 1usize << p;
+            let trials = ((1 << 28) / (2 * m * p as usize)).clamp(20, 5_000);
+            let coefficients = if p == 14 {
+                writeln!(out, "    // p = 14, as published").unwrap();
+                PUBLISHED_P14
+            } else {
+                writeln!(out, "    // p = {}, {} trials", p, trials).unwrap();
+                fit(p, trials, 0xbe7a_0000 + u64::from(p))
+            };
+            writeln!(out, "    [").unwrap();
+            for c in &coefficients {
+                writeln!(out, "        {:e},", c).unwrap();
+            }
+            writeln!(out, "    ],").unwrap();
+        }
+        let source = format!(
+            "//! Generated by `cargo test --release generate_beta_coefficients -- --ignored`;\n\
+             //! do not edit.\n\
+             //!\n\
+             //! `COEFFICIENTS[p - 4]` holds the coefficient of `z`, then of `ln(z + 1)`\n\
+             //! to the first through seventh powers, for precision `p`.\n\
+             \n\
+             #[rustfmt::skip]\n\
+             pub(crate) const COEFFICIENTS: [[f64; 8]; 15] = [\n{}];\n",
+            out
+        );
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/beta/coefficients.rs");
+        ::std::fs::write(path, source).unwrap();
+    }
+
+    #[test]
+    fn empty_sketch_estimates_zero() {
+        for p in MIN_PRECISION..=MAX_PRECISION {
+            let m = 1usize << p;
+            assert_eq!(estimate(m, m as f64, m), Some(0.));
+        }
+        assert_eq!(estimate(8, 8., 8), None);
+        assert_eq!(estimate(1 << 19, 1., 0), None);
+    }
+
+    #[test]
+    fn simulated_error_is_unbiased() {
+        // A different seed from the fit, across the range where beta matters.
+        let p = 10;
+        let m = 1usize << p;
+        let trials = 200;
+        let points: Vec<usize> = vec![m / 10, m / 2, m, 2 * m, 4 * m];
+        let mut errors = vec![0.; points.len()];
+        let mut rng = 0x0dd_ba11;
+        for _ in 0..trials {
+            let mut sim = Sim::new(p);
+            for (error, &n) in errors.iter_mut().zip(&points) {
+                sim.advance_to(n, &mut rng);
+                let e = estimate(m, sim.sum, sim.zeros).unwrap();
+                *error += (e - n as f64) / n as f64;
+            }
+        }
+        for (&n, error) in points.iter().zip(errors) {
+            let mean = error / trials as f64;
+            assert!(mean.abs() < 0.005, "n = {}: mean error {}", n, mean);
+        }
+    }
+}
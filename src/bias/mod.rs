@@ -0,0 +1,238 @@
+//! HyperLogLog++ empirical bias correction (Heule, Nunkesser & Hall, 2013).
+//!
+//! For precisions 4 through 18, the raw HyperLogLog estimate overshoots the
+//! true cardinality by a predictable amount when it lies below about `5m`.
+//! `tables.rs` records, for about a hundred cardinalities per precision, the
+//! mean raw estimate and its mean bias; [`estimate_bias`] looks up the bias of
+//! a raw estimate by interpolating between its neighbours in that table.
+//!
+//! The tables are generated by simulation rather than copied from the paper,
+//! because they depend on the exact rank and `alpha` conventions of this
+//! crate's estimator. Regenerate them with
+//!
+//! ```text
+//! cargo test --release generate_bias_tables -- --ignored
+//! ```
+
+mod tables;
+
+use self::tables::{BIAS, RAW_ESTIMATES};
+
+pub(crate) const MIN_PRECISION: u8 = 4;
+pub(crate) const MAX_PRECISION: u8 = 18;
+
+/// Cardinalities below which linear counting beats the bias-corrected raw
+/// estimate, as published with HyperLogLog++, for precisions 4 through 18.
+const THRESHOLDS: [u32; 15] = [
+    10, 20, 40, 80, 220, 400, 900, 1800, 3100, 6500, 11500, 20000, 50000, 120000, 350000,
+];
+
+/// The most entries a precision has in the tables.
+const TABLE_LEN: usize = 100;
+
+/// [`RAW_ESTIMATES`] and [`BIAS`] in Q32.32 fixed point, converted at compile
+/// time, for [`estimate_bias_fixed`]. Each row has zeros past its entries.
+static RAW_ESTIMATES_FIXED: [[i64; TABLE_LEN]; 15] = to_fixed(&RAW_ESTIMATES);
+static BIAS_FIXED: [[i64; TABLE_LEN]; 15] = to_fixed(&BIAS);
+
+const fn to_fixed(tables: &[&[f64]; 15]) -> [[i64; TABLE_LEN]; 15] {
+    let mut out = [[0; TABLE_LEN]; 15];
+    let mut i = 0;
+    while i < tables.len() {
+        let mut k = 0;
+        while k < tables[i].len() {
+            let x = tables[i][k] * (1u64 << 32) as f64;
+            out[i][k] = if x < 0. {
+                -((0.5 - x) as i64)
+            } else {
+                (x + 0.5) as i64
+            };
+            k += 1;
+        }
+        i += 1;
+    }
+    out
+}
+
+fn table_index(p: u8) -> Option<usize> {
+    if (MIN_PRECISION..=MAX_PRECISION).contains(&p) {
+        Some(usize::from(p - MIN_PRECISION))
+    } else {
+        None
+    }
+}
+
+/// The linear-counting threshold for precision `p`, if bias correction applies.
+pub(crate) fn threshold(p: u8) -> Option<f64> {
+    table_index(p).map(|i| f64::from(THRESHOLDS[i]))
+}
+
+/// Like [`threshold`], as an integer.
+pub(crate) fn threshold_count(p: u8) -> Option<u32> {
+    table_index(p).map(|i| THRESHOLDS[i])
+}
+
+/// The expected bias of raw estimate `raw` at precision `p`, or `None` if the
+/// correction doesn't apply: unsupported precision, or `raw > 5m`.
+pub(crate) fn estimate_bias(raw: f64, p: u8) -> Option<f64> {
+    let i = table_index(p)?;
+    if raw > 5. * (1u64 << p) as f64 {
+        return None;
+    }
+    let estimates = RAW_ESTIMATES[i];
+    let biases = BIAS[i];
+
+    // The table is sorted by raw estimate and averaged over many trials, so
+    // it is smooth enough to interpolate linearly between neighbours.
+    // Averaging the nearest few entries instead, as the paper does, turns the
+    // bias into a step function and the estimate into a sawtooth.
+    let at = match estimates.binary_search_by(|e| e.partial_cmp(&raw).unwrap()) {
+        Ok(at) => return Some(biases[at]),
+        Err(at) => at,
+    };
+    if at == 0 {
+        return Some(biases[0]);
+    }
+    if at == estimates.len() {
+        return Some(biases[at - 1]);
+    }
+    let t = (raw - estimates[at - 1]) / (estimates[at] - estimates[at - 1]);
+    Some(biases[at - 1] + t * (biases[at] - biases[at - 1]))
+}
+
+/// Like [`estimate_bias`], for a raw estimate in Q32.32 fixed point, with no
+/// floating-point arithmetic.
+pub(crate) fn estimate_bias_fixed(raw: u128, p: u8) -> Option<i128> {
+    let i = table_index(p)?;
+    if raw > 5 << (32 + p) {
+        return None;
+    }
+    // Below 5 · 2^18 · 2^32, so it fits.
+    let raw = raw as i64;
+    let len = RAW_ESTIMATES[i].len();
+    let estimates = &RAW_ESTIMATES_FIXED[i][..len];
+    let biases = &BIAS_FIXED[i][..len];
+    let at = match estimates.binary_search(&raw) {
+        Ok(at) => return Some(biases[at].into()),
+        Err(at) => at,
+    };
+    if at == 0 {
+        return Some(biases[0].into());
+    }
+    if at == len {
+        return Some(biases[at - 1].into());
+    }
+    let (e0, e1) = (i128::from(estimates[at - 1]), i128::from(estimates[at]));
+    let (b0, b1) = (i128::from(biases[at - 1]), i128::from(biases[at]));
+    Some(b0 + (i128::from(raw) - e0) * (b1 - b0) / (e1 - e0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate_bias, tables, threshold, MAX_PRECISION, MIN_PRECISION};
+    use simulation::Sim;
+    use std::fmt::Write;
+
+    /// Simulates `trials` sketches at precision `p`, returning for each
+    /// checkpoint cardinality the mean raw estimate.
+    fn simulate(p: u8, checkpoints: &[usize], trials: usize, seed: u64) -> Vec<f64> {
+        let mut totals = vec![0.; checkpoints.len()];
+        let mut rng = seed;
+        for _ in 0..trials {
+            let mut sim = Sim::new(p);
+            for (total, &n) in totals.iter_mut().zip(checkpoints) {
+                sim.advance_to(n, &mut rng);
+                *total += sim.raw();
+            }
+        }
+        totals.into_iter().map(|t| t / trials as f64).collect()
+    }
+
+    fn checkpoints(p: u8, points: usize) -> Vec<usize> {
+        let max_n = 6usize << p;
+        let mut out: Vec<usize> = (1..=points).map(|k| k * max_n / points).collect();
+        out.dedup();
+        out
+    }
+
+    #[test]
+    #[ignore]
+    fn generate_bias_tables() {
+        let mut raw_out = String::new();
+        let mut bias_out = String::new();
+        for p in MIN_PRECISION..=MAX_PRECISION {
+            let m = 1usize << p;
+            let trials = ((1 << 26) / (6 * m)).clamp(200, 50_000);
+            let points = checkpoints(p, 100);
+            let means = simulate(p, &points, trials, 0x5eed_0000 + u64::from(p));
+            writeln!(raw_out, "    // p = {}, {} trials", p, trials).unwrap();
+            writeln!(raw_out, "    &[").unwrap();
+            writeln!(bias_out, "    // p = {}", p).unwrap();
+            writeln!(bias_out, "    &[").unwrap();
+            for (&n, &raw) in points.iter().zip(&means) {
+                writeln!(raw_out, "        {:.4},", raw).unwrap();
+                writeln!(bias_out, "        {:.4},", raw - n as f64).unwrap();
+            }
+            writeln!(raw_out, "    ],").unwrap();
+            writeln!(bias_out, "    ],").unwrap();
+        }
+
+        let source = format!(
+            "//! Generated by `cargo test --release generate_bias_tables -- --ignored`; do not edit.\n\
+             //!\n\
+             //! `RAW_ESTIMATES[p - 4][k]` is the mean raw estimate at the k-th simulated\n\
+             //! cardinality for precision `p`, and `BIAS[p - 4][k]` its mean excess over\n\
+             //! the true cardinality.\n\
+             \n\
+             #[rustfmt::skip]\n\
+             pub(crate) const RAW_ESTIMATES: [&[f64]; 15] = [\n{}];\n\
+             \n\
+             #[rustfmt::skip]\n\
+             pub(crate) const BIAS: [&[f64]; 15] = [\n{}];\n",
+            raw_out, bias_out
+        );
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/bias/tables.rs");
+        ::std::fs::write(path, source).unwrap();
+    }
+
+    #[test]
+    fn tables_are_sorted() {
+        for p in MIN_PRECISION..=MAX_PRECISION {
+            let raw = tables::RAW_ESTIMATES[usize::from(p - MIN_PRECISION)];
+            assert!(raw.windows(2).all(|w| w[0] < w[1]), "p = {}", p);
+            assert!(*raw.last().unwrap() > 5. * (1u64 << p) as f64);
+        }
+    }
+
+    #[test]
+    fn correction_applies_only_in_range() {
+        assert!(estimate_bias(100., 3).is_none());
+        assert!(estimate_bias(100., 19).is_none());
+        assert!(estimate_bias(5. * 1024. + 1., 10).is_none());
+        assert!(estimate_bias(1024., 10).is_some());
+        assert_eq!(threshold(14), Some(11500.));
+    }
+
+    #[test]
+    fn correction_reduces_mid_range_bias() {
+        // A different seed from the table generator, so the tables aren't
+        // being tested against the very data they were fitted to.
+        let p = 10;
+        let m = 1usize << p;
+        let points: Vec<usize> = vec![m * 3, m * 7 / 2, m * 4, m * 9 / 2];
+        let means = simulate(p, &points, 400, 0xfeed_beef);
+        let mut raw_error = 0.;
+        let mut corrected_error = 0.;
+        for (&n, &raw) in points.iter().zip(&means) {
+            let corrected = raw - estimate_bias(raw, p).unwrap();
+            raw_error += ((raw - n as f64) / n as f64).abs();
+            corrected_error += ((corrected - n as f64) / n as f64).abs();
+        }
+        assert!(
+            corrected_error < raw_error / 2.,
+            "corrected {} vs raw {}",
+            corrected_error,
+            raw_error
+        );
+    }
+}
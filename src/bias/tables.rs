@@ -0,0 +1,3097 @@
+//! Generated by `cargo test --release generate_bias_tables -- --ignored`; do not edit.
+//!
+//! `RAW_ESTIMATES[p - 4][k]` is the mean raw estimate at the k-th simulated
+//! cardinality for precision `p`, and `BIAS[p - 4][k]` its mean excess over
+//! the true cardinality.
+
+#[rustfmt::skip]
+pub(crate) const RAW_ESTIMATES: [&[f64]; 15] = [
+    // p = 4, 50000 trials
+    &[
+        10.7680,
+        11.2384,
+        11.7240,
+        12.2248,
+        12.7407,
+        13.2729,
+        13.8196,
+        14.3813,
+        14.9622,
+        15.5624,
+        16.1726,
+        16.7973,
+        17.4436,
+        18.0977,
+        18.7704,
+        19.4542,
+        20.1663,
+        20.8838,
+        21.6118,
+        22.3517,
+        23.1124,
+        23.8821,
+        24.6699,
+        25.4679,
+        26.2842,
+        27.1114,
+        27.9478,
+        28.7892,
+        29.6385,
+        30.5010,
+        31.3739,
+        32.2488,
+        33.1365,
+        34.0385,
+        34.9395,
+        35.8586,
+        36.7784,
+        37.7063,
+        38.6495,
+        39.5950,
+        40.5520,
+        41.4954,
+        42.4489,
+        43.4120,
+        44.3755,
+        45.3438,
+        46.3140,
+        47.2834,
+        48.2578,
+        49.2470,
+        50.2228,
+        51.2169,
+        52.1980,
+        53.1844,
+        54.1790,
+        55.1596,
+        56.1304,
+        57.1223,
+        58.1160,
+        59.0856,
+        60.0788,
+        61.0694,
+        62.0744,
+        63.0866,
+        64.0895,
+        65.0878,
+        66.1032,
+        67.0727,
+        68.0844,
+        69.0639,
+        70.0679,
+        71.0711,
+        72.0924,
+        73.0904,
+        74.0937,
+        75.0797,
+        76.0662,
+        77.0591,
+        78.0720,
+        79.0556,
+        80.0527,
+        81.0544,
+        82.0532,
+        83.0555,
+        84.0293,
+        85.0685,
+        86.0449,
+        87.0440,
+        88.0391,
+        89.0537,
+        90.0638,
+        91.0479,
+        92.0719,
+        93.0732,
+        94.0658,
+        95.0585,
+        96.0648,
+    ],
+    // p = 5, 50000 trials
+    &[
+        22.7803,
+        23.7539,
+        24.7585,
+        25.7915,
+        26.8530,
+        27.9526,
+        29.0777,
+        30.2314,
+        31.4162,
+        32.6247,
+        33.8662,
+        35.1329,
+        35.7783,
+        37.0985,
+        38.4460,
+        39.8290,
+        41.2284,
+        42.6595,
+        44.1085,
+        45.5872,
+        47.0965,
+        48.6225,
+        50.1713,
+        51.7551,
+        53.3522,
+        54.1666,
+        55.7960,
+        57.4581,
+        59.1398,
+        60.8273,
+        62.5491,
+        64.2880,
+        66.0362,
+        67.8069,
+        69.5998,
+        71.4053,
+        73.1963,
+        74.1110,
+        75.9320,
+        77.7636,
+        79.6234,
+        81.4879,
+        83.3844,
+        85.2753,
+        87.1477,
+        89.0308,
+        90.9515,
+        92.8667,
+        94.7748,
+        96.7094,
+        97.6827,
+        99.6502,
+        101.5850,
+        103.5339,
+        105.4840,
+        107.4935,
+        109.4705,
+        111.4096,
+        113.3664,
+        115.3411,
+        117.3369,
+        119.3032,
+        120.2773,
+        122.2729,
+        124.2685,
+        126.2879,
+        128.2633,
+        130.2543,
+        132.2581,
+        134.2677,
+        136.2751,
+        138.3076,
+        140.3027,
+        142.3155,
+        144.2983,
+        145.2968,
+        147.2736,
+        149.2623,
+        151.2434,
+        153.2064,
+        155.2008,
+        157.1892,
+        159.2192,
+        161.1775,
+        163.1771,
+        165.1906,
+        167.1463,
+        168.1559,
+        170.1772,
+        172.1311,
+        174.1427,
+        176.1814,
+        178.1628,
+        180.1603,
+        182.1348,
+        184.1451,
+        186.1587,
+        188.1666,
+        190.1527,
+        192.1281,
+    ],
+    // p = 6, 50000 trials
+    &[
+        46.8208,
+        48.7977,
+        50.8351,
+        52.9294,
+        55.0796,
+        57.2919,
+        58.9975,
+        61.3083,
+        63.6871,
+        66.1208,
+        68.6112,
+        71.1584,
+        73.0955,
+        75.7486,
+        78.4441,
+        81.1900,
+        84.0027,
+        86.8578,
+        89.0393,
+        92.0063,
+        95.0061,
+        98.0540,
+        101.1663,
+        104.2962,
+        107.4495,
+        109.8559,
+        113.1326,
+        116.4246,
+        119.7344,
+        123.1074,
+        126.5116,
+        129.0917,
+        132.5603,
+        136.0618,
+        139.5719,
+        143.1467,
+        146.7533,
+        149.4565,
+        153.0795,
+        156.7318,
+        160.3904,
+        164.1161,
+        167.8073,
+        170.6066,
+        174.3612,
+        178.1671,
+        181.9461,
+        185.7818,
+        189.5855,
+        193.3769,
+        196.2550,
+        200.0982,
+        203.9382,
+        207.8005,
+        211.7015,
+        215.6445,
+        218.5934,
+        222.5007,
+        226.3774,
+        230.2957,
+        234.2404,
+        238.2147,
+        241.1573,
+        245.1344,
+        249.0702,
+        253.0442,
+        257.0079,
+        260.9721,
+        263.9435,
+        267.9067,
+        271.8646,
+        275.8384,
+        279.8030,
+        283.7921,
+        287.7811,
+        290.7802,
+        294.7287,
+        298.7695,
+        302.7910,
+        306.8104,
+        310.7883,
+        313.7690,
+        317.7934,
+        321.8079,
+        325.7947,
+        329.7681,
+        333.7974,
+        336.8393,
+        340.8464,
+        344.8925,
+        348.8888,
+        352.8518,
+        356.8252,
+        359.8221,
+        363.8320,
+        367.8020,
+        371.7796,
+        375.7550,
+        379.8123,
+        383.8437,
+    ],
+    // p = 7, 50000 trials
+    &[
+        94.9509,
+        98.9424,
+        103.0439,
+        106.7325,
+        111.0579,
+        115.4994,
+        119.4856,
+        124.1429,
+        128.9267,
+        133.1997,
+        138.1924,
+        143.2845,
+        147.8389,
+        153.1559,
+        158.5668,
+        163.3973,
+        169.0142,
+        174.7261,
+        179.8180,
+        185.6974,
+        191.6832,
+        197.0080,
+        203.1850,
+        209.4713,
+        215.8458,
+        221.4837,
+        227.9885,
+        234.6015,
+        240.4151,
+        247.1716,
+        253.9805,
+        259.9711,
+        266.9282,
+        273.9063,
+        280.0743,
+        287.1940,
+        294.3532,
+        300.6256,
+        307.8965,
+        315.2383,
+        321.6778,
+        329.0741,
+        336.4739,
+        343.0299,
+        350.5138,
+        358.0097,
+        364.6548,
+        372.2492,
+        379.8689,
+        387.4972,
+        394.2260,
+        401.8955,
+        409.5950,
+        416.3638,
+        424.0832,
+        431.8603,
+        438.6645,
+        446.4711,
+        454.3185,
+        461.2139,
+        469.0907,
+        476.9791,
+        483.8700,
+        491.7714,
+        499.6651,
+        506.5549,
+        514.5198,
+        522.4493,
+        529.4554,
+        537.4346,
+        545.3421,
+        552.3143,
+        560.2550,
+        568.1599,
+        576.0565,
+        583.0184,
+        591.0334,
+        599.0518,
+        605.9422,
+        613.9348,
+        621.9358,
+        628.8910,
+        636.8676,
+        644.9382,
+        651.9139,
+        659.9179,
+        667.8822,
+        674.8730,
+        682.8851,
+        690.8490,
+        697.8831,
+        705.8724,
+        713.7983,
+        720.7881,
+        728.7410,
+        736.8288,
+        743.8742,
+        751.8569,
+        759.9038,
+        767.8354,
+    ],
+    // p = 8, 43690 trials
+    &[
+        191.1763,
+        198.6737,
+        206.8970,
+        214.8200,
+        222.9460,
+        231.8488,
+        240.3913,
+        249.1334,
+        258.7011,
+        267.8464,
+        277.2145,
+        287.4141,
+        297.1938,
+        307.8178,
+        317.9820,
+        328.3088,
+        339.5538,
+        350.2786,
+        361.1662,
+        372.9650,
+        384.2402,
+        395.6492,
+        407.9908,
+        419.7582,
+        432.4389,
+        444.4833,
+        456.6653,
+        469.8438,
+        482.3777,
+        494.9346,
+        508.5333,
+        521.4289,
+        534.3546,
+        548.3347,
+        561.5249,
+        574.8183,
+        589.0966,
+        602.5867,
+        617.1144,
+        630.8348,
+        644.5851,
+        659.3052,
+        673.1961,
+        687.1863,
+        702.2031,
+        716.3434,
+        730.5607,
+        745.7230,
+        760.0253,
+        775.3678,
+        789.7490,
+        804.2296,
+        819.6952,
+        834.2622,
+        848.7767,
+        864.3292,
+        878.9362,
+        893.5944,
+        909.2688,
+        923.9732,
+        938.7763,
+        954.5396,
+        969.3371,
+        985.1866,
+        999.9853,
+        1014.9208,
+        1030.6199,
+        1045.3781,
+        1060.3703,
+        1076.2083,
+        1091.0670,
+        1105.8735,
+        1121.7568,
+        1136.6165,
+        1152.5597,
+        1167.4936,
+        1182.4495,
+        1198.2576,
+        1213.2416,
+        1228.2820,
+        1244.2328,
+        1259.2391,
+        1274.2653,
+        1290.2676,
+        1305.1518,
+        1319.9637,
+        1335.9684,
+        1350.9212,
+        1366.9662,
+        1382.0018,
+        1397.0229,
+        1413.0277,
+        1428.0855,
+        1443.0420,
+        1458.9928,
+        1473.9744,
+        1488.9334,
+        1505.0037,
+        1519.9073,
+        1535.9573,
+    ],
+    // p = 9, 21845 trials
+    &[
+        383.1391,
+        398.6587,
+        414.6174,
+        430.4957,
+        447.3229,
+        464.5640,
+        482.2277,
+        499.7527,
+        518.2857,
+        537.2637,
+        556.0318,
+        575.7975,
+        595.9799,
+        616.5754,
+        636.9230,
+        658.3280,
+        680.1119,
+        701.5683,
+        724.0970,
+        747.0034,
+        770.2532,
+        793.1315,
+        817.0811,
+        841.3691,
+        865.9799,
+        890.0811,
+        915.3194,
+        940.8194,
+        965.8073,
+        991.8616,
+        1018.2011,
+        1044.7376,
+        1070.7055,
+        1097.8354,
+        1125.1644,
+        1151.9468,
+        1179.6865,
+        1207.5775,
+        1235.7522,
+        1263.1364,
+        1291.5863,
+        1320.1706,
+        1347.8513,
+        1376.7765,
+        1405.8350,
+        1434.9960,
+        1463.3240,
+        1492.6677,
+        1522.2489,
+        1551.8293,
+        1580.5169,
+        1610.3589,
+        1640.2936,
+        1669.1623,
+        1699.2150,
+        1729.4514,
+        1759.6453,
+        1788.9516,
+        1819.2144,
+        1849.5609,
+        1878.9540,
+        1909.4738,
+        1940.0132,
+        1970.7134,
+        2000.2472,
+        2030.9094,
+        2061.5596,
+        2091.1437,
+        2121.8409,
+        2152.6379,
+        2183.3543,
+        2213.0177,
+        2244.0524,
+        2274.8559,
+        2305.6548,
+        2335.5289,
+        2366.3651,
+        2397.1992,
+        2427.1595,
+        2458.1143,
+        2488.9969,
+        2519.8778,
+        2549.9284,
+        2580.9155,
+        2611.7761,
+        2641.5749,
+        2672.6886,
+        2703.4206,
+        2734.6548,
+        2764.5191,
+        2795.2459,
+        2826.2722,
+        2856.1971,
+        2887.1352,
+        2917.9603,
+        2948.8809,
+        2978.8153,
+        3009.7068,
+        3040.6338,
+        3071.6146,
+    ],
+    // p = 10, 10922 trials
+    &[
+        767.5755,
+        798.1370,
+        830.0629,
+        862.3210,
+        895.9689,
+        929.9568,
+        965.3684,
+        1000.9546,
+        1037.3992,
+        1075.2607,
+        1113.4341,
+        1153.0183,
+        1192.7676,
+        1234.0176,
+        1275.2986,
+        1318.1129,
+        1361.0068,
+        1404.6337,
+        1449.7284,
+        1494.7131,
+        1541.2644,
+        1587.7228,
+        1635.5576,
+        1683.3786,
+        1732.5228,
+        1781.5897,
+        1831.1360,
+        1882.1741,
+        1933.0159,
+        1985.0798,
+        2036.6797,
+        2089.7990,
+        2142.5621,
+        2195.6395,
+        2250.1636,
+        2304.1226,
+        2359.5071,
+        2414.5539,
+        2470.5884,
+        2526.1692,
+        2583.0498,
+        2639.0694,
+        2695.4817,
+        2753.1171,
+        2810.1130,
+        2868.4399,
+        2926.2577,
+        2984.9329,
+        3042.9321,
+        3101.9422,
+        3160.5985,
+        3219.2907,
+        3278.8018,
+        3338.0134,
+        3398.3016,
+        3457.4766,
+        3517.9051,
+        3577.3376,
+        3637.0320,
+        3697.5569,
+        3757.7078,
+        3818.4989,
+        3878.1999,
+        3939.3219,
+        3999.3603,
+        4060.5928,
+        4120.7587,
+        4181.1739,
+        4242.9504,
+        4303.4899,
+        4365.0673,
+        4425.2695,
+        4487.1911,
+        4547.4734,
+        4609.4391,
+        4670.3016,
+        4731.1723,
+        4792.6188,
+        4853.4069,
+        4915.0745,
+        4976.3888,
+        5038.2466,
+        5098.7433,
+        5159.5236,
+        5221.5956,
+        5282.2085,
+        5344.5203,
+        5405.0651,
+        5466.7192,
+        5527.6769,
+        5589.7623,
+        5650.6283,
+        5711.4771,
+        5773.6192,
+        5834.5962,
+        5896.3051,
+        5957.3715,
+        6019.0417,
+        6080.1316,
+        6141.9398,
+    ],
+    // p = 11, 5461 trials
+    &[
+        1535.9269,
+        1597.6008,
+        1661.0151,
+        1726.1073,
+        1792.8444,
+        1861.3231,
+        1931.4634,
+        2003.3546,
+        2076.3656,
+        2151.6260,
+        2228.6002,
+        2307.2101,
+        2387.3069,
+        2469.0436,
+        2552.3138,
+        2637.2121,
+        2722.7462,
+        2810.8666,
+        2900.1083,
+        2990.8747,
+        3083.2773,
+        3177.0544,
+        3272.2591,
+        3368.5300,
+        3466.3991,
+        3564.6691,
+        3664.7726,
+        3765.8555,
+        3867.9500,
+        3971.5660,
+        4075.9345,
+        4181.8285,
+        4288.2929,
+        4394.7074,
+        4503.0651,
+        4612.1252,
+        4722.3811,
+        4832.7621,
+        4944.0249,
+        5056.4128,
+        5169.3614,
+        5282.2100,
+        5396.2144,
+        5511.6003,
+        5626.7655,
+        5742.6762,
+        5859.1638,
+        5976.0182,
+        6092.6865,
+        6210.6096,
+        6327.7217,
+        6445.9658,
+        6564.7758,
+        6683.8531,
+        6803.2719,
+        6923.1095,
+        7044.0508,
+        7164.2261,
+        7283.0159,
+        7404.1059,
+        7525.3971,
+        7646.7137,
+        7767.7224,
+        7888.7916,
+        8010.5403,
+        8132.1525,
+        8252.4574,
+        8375.0549,
+        8497.0675,
+        8619.4048,
+        8741.1777,
+        8863.5597,
+        8985.1115,
+        9106.9270,
+        9229.0024,
+        9350.3303,
+        9472.3583,
+        9594.3726,
+        9717.4328,
+        9839.9619,
+        9962.7094,
+        10085.0359,
+        10207.6706,
+        10329.0970,
+        10451.4534,
+        10574.3542,
+        10698.0401,
+        10821.4652,
+        10944.5798,
+        11067.2660,
+        11189.3281,
+        11310.7529,
+        11432.2958,
+        11555.8478,
+        11679.0150,
+        11802.2960,
+        11925.5183,
+        12048.0332,
+        12170.4160,
+        12292.4441,
+    ],
+    // p = 12, 2730 trials
+    &[
+        3073.0784,
+        3196.4699,
+        3323.2083,
+        3453.4078,
+        3586.5994,
+        3723.5832,
+        3864.1066,
+        4007.6752,
+        4154.1286,
+        4304.6873,
+        4458.5018,
+        4615.6649,
+        4775.3797,
+        4938.9271,
+        5105.4664,
+        5275.6869,
+        5447.4371,
+        5622.8672,
+        5801.4330,
+        5982.8843,
+        6166.2207,
+        6353.5949,
+        6543.8415,
+        6736.5270,
+        6932.4485,
+        7129.6283,
+        7329.4771,
+        7531.9099,
+        7737.4341,
+        7943.4346,
+        8152.2659,
+        8362.8345,
+        8574.8387,
+        8787.9372,
+        9004.1025,
+        9223.2610,
+        9442.8145,
+        9663.2205,
+        9886.1291,
+        10111.5480,
+        10337.1247,
+        10563.4623,
+        10791.3248,
+        11021.3019,
+        11251.7914,
+        11480.4905,
+        11712.8892,
+        11944.8496,
+        12179.0151,
+        12412.6805,
+        12647.1217,
+        12883.2117,
+        13119.7772,
+        13357.8785,
+        13595.6705,
+        13834.3745,
+        14074.0168,
+        14314.1605,
+        14553.8932,
+        14795.0529,
+        15034.5129,
+        15275.5522,
+        15515.7387,
+        15758.7507,
+        16000.4209,
+        16244.3596,
+        16487.0389,
+        16730.9615,
+        16973.5720,
+        17218.5279,
+        17460.6932,
+        17703.9287,
+        17948.8127,
+        18192.4938,
+        18436.7431,
+        18680.8166,
+        18925.6270,
+        19169.9940,
+        19413.9473,
+        19655.3369,
+        19900.0810,
+        20145.2036,
+        20389.3063,
+        20632.9196,
+        20877.1098,
+        21122.6994,
+        21367.0759,
+        21609.7278,
+        21855.1875,
+        22099.3700,
+        22344.8774,
+        22589.4143,
+        22835.3829,
+        23081.7665,
+        23328.3308,
+        23573.6072,
+        23817.7123,
+        24064.9360,
+        24311.0050,
+        24555.7838,
+    ],
+    // p = 13, 1365 trials
+    &[
+        6147.5726,
+        6394.2085,
+        6647.2458,
+        6907.6795,
+        7174.3666,
+        7448.4235,
+        7728.6794,
+        8016.5189,
+        8310.8176,
+        8611.4992,
+        8917.6598,
+        9231.9327,
+        9552.1547,
+        9878.8551,
+        10210.8173,
+        10550.9643,
+        10895.6855,
+        11247.3737,
+        11603.9129,
+        11967.6828,
+        12336.2234,
+        12709.9574,
+        13089.6249,
+        13474.7105,
+        13865.2206,
+        14260.3286,
+        14661.3468,
+        15066.9253,
+        15476.5656,
+        15889.2912,
+        16307.6027,
+        16728.1850,
+        17154.4709,
+        17582.4604,
+        18014.9239,
+        18450.4533,
+        18888.0709,
+        19330.8140,
+        19776.6698,
+        20223.5867,
+        20673.5932,
+        21126.4523,
+        21582.8721,
+        22040.2774,
+        22500.3898,
+        22961.6882,
+        23426.4901,
+        23892.2310,
+        24360.9140,
+        24828.6408,
+        25300.9723,
+        25774.3070,
+        26249.2547,
+        26724.8697,
+        27202.3789,
+        27678.4302,
+        28155.4538,
+        28635.4441,
+        29116.1430,
+        29593.8749,
+        30078.3642,
+        30564.0900,
+        31047.9503,
+        31532.9523,
+        32018.5246,
+        32505.3025,
+        32989.5447,
+        33475.3140,
+        33961.5475,
+        34446.9864,
+        34934.2701,
+        35424.3954,
+        35913.8282,
+        36404.2837,
+        36894.1382,
+        37381.9445,
+        37871.2159,
+        38365.1173,
+        38852.9290,
+        39340.6013,
+        39827.4916,
+        40318.1217,
+        40807.5872,
+        41300.5103,
+        41793.3137,
+        42281.8628,
+        42774.0764,
+        43262.8040,
+        43754.7123,
+        44244.5091,
+        44736.3271,
+        45227.4773,
+        45722.2901,
+        46215.4987,
+        46710.0335,
+        47203.0973,
+        47692.8491,
+        48181.2840,
+        48672.4148,
+        49162.6826,
+    ],
+    // p = 14, 682 trials
+    &[
+        12296.2223,
+        12789.1938,
+        13295.9897,
+        13816.5427,
+        14350.4766,
+        14897.9134,
+        15458.4086,
+        16032.5639,
+        16620.3061,
+        17221.8972,
+        17836.7039,
+        18464.8135,
+        19105.1582,
+        19758.6056,
+        20424.8310,
+        21102.7109,
+        21793.7485,
+        22496.6415,
+        23209.3912,
+        23935.6324,
+        24674.5148,
+        25421.7806,
+        26182.8268,
+        26950.9584,
+        27730.6132,
+        28519.6997,
+        29319.9059,
+        30129.2246,
+        30946.0769,
+        31770.7979,
+        32605.5245,
+        33448.9060,
+        34297.1695,
+        35153.1573,
+        36016.5711,
+        36888.9696,
+        37766.4255,
+        38653.8719,
+        39544.6319,
+        40441.4446,
+        41341.8228,
+        42249.8751,
+        43157.6374,
+        44074.4002,
+        44994.6074,
+        45918.8640,
+        46843.5544,
+        47773.6915,
+        48709.5948,
+        49651.8631,
+        50592.7467,
+        51539.8875,
+        52485.3277,
+        53435.8897,
+        54386.7202,
+        55345.9680,
+        56308.6150,
+        57267.1934,
+        58239.2251,
+        59201.2778,
+        60163.6271,
+        61128.6628,
+        62097.3760,
+        63073.2373,
+        64047.6658,
+        65019.4824,
+        65994.4808,
+        66972.3828,
+        67948.6756,
+        68923.8655,
+        69896.4684,
+        70871.1717,
+        71849.6437,
+        72831.0539,
+        73809.9780,
+        74789.0524,
+        75765.9982,
+        76743.9694,
+        77724.8817,
+        78700.3923,
+        79676.9346,
+        80658.5620,
+        81640.2616,
+        82617.6485,
+        83594.1832,
+        84570.0396,
+        85547.6074,
+        86525.5185,
+        87505.8950,
+        88490.4526,
+        89473.3185,
+        90450.3604,
+        91434.7858,
+        92412.9281,
+        93393.1491,
+        94383.0562,
+        95359.9197,
+        96345.4924,
+        97330.3289,
+        98315.2532,
+    ],
+    // p = 15, 341 trials
+    &[
+        24593.7524,
+        25579.8219,
+        26593.1958,
+        27634.7132,
+        28702.1415,
+        29796.9875,
+        30919.4263,
+        32068.0295,
+        33243.2426,
+        34446.1288,
+        35675.5103,
+        36931.5415,
+        38213.4668,
+        39522.1298,
+        40852.5898,
+        42204.9204,
+        43587.3812,
+        44989.3465,
+        46418.4213,
+        47870.1866,
+        49345.1943,
+        50844.4473,
+        52361.6575,
+        53901.2806,
+        55457.8507,
+        57032.2697,
+        58629.1839,
+        60244.4528,
+        61881.2409,
+        63533.1251,
+        65201.3619,
+        66884.4609,
+        68590.3037,
+        70304.7141,
+        72037.5543,
+        73777.4974,
+        75533.0290,
+        77305.9636,
+        79087.1628,
+        80879.6361,
+        82690.7841,
+        84501.9459,
+        86321.4233,
+        88148.5667,
+        89985.3041,
+        91845.7599,
+        93704.7829,
+        95567.5759,
+        97441.4024,
+        99326.2534,
+        101201.8549,
+        103091.3664,
+        104988.4331,
+        106891.9227,
+        108791.7255,
+        110711.5992,
+        112620.8536,
+        114533.8427,
+        116468.1130,
+        118394.3090,
+        120323.6832,
+        122249.2828,
+        124181.5161,
+        126103.8957,
+        128040.0332,
+        129981.1117,
+        131914.7013,
+        133855.7809,
+        135812.9966,
+        137768.0057,
+        139722.5596,
+        141679.5231,
+        143631.7507,
+        145590.4698,
+        147546.6523,
+        149509.6834,
+        151466.4175,
+        153430.5128,
+        155389.7916,
+        157352.4980,
+        159312.1249,
+        161278.2161,
+        163230.8861,
+        165185.3012,
+        167143.9512,
+        169104.5283,
+        171078.8889,
+        173043.1822,
+        175011.4956,
+        176982.6523,
+        178940.6692,
+        180898.6399,
+        182867.1920,
+        184836.8766,
+        186811.8702,
+        188781.4807,
+        190744.7300,
+        192720.5517,
+        194674.6551,
+        196638.4142,
+    ],
+    // p = 16, 200 trials
+    &[
+        49188.5629,
+        51160.3263,
+        53185.6089,
+        55265.5762,
+        57399.3425,
+        59590.1496,
+        61835.7691,
+        64132.3775,
+        66488.2229,
+        68891.0833,
+        71348.8781,
+        73859.9173,
+        76421.3399,
+        79035.2220,
+        81702.6444,
+        84416.3849,
+        87175.8398,
+        89985.8848,
+        92848.2373,
+        95749.1349,
+        98702.0713,
+        101694.6740,
+        104731.1342,
+        107816.7102,
+        110929.6319,
+        114085.6624,
+        117274.1463,
+        120506.3318,
+        123768.9527,
+        127076.0793,
+        130407.4766,
+        133782.9658,
+        137188.9023,
+        140622.0212,
+        144075.5247,
+        147563.4793,
+        151086.0419,
+        154629.5448,
+        158188.1452,
+        161769.5020,
+        165376.9144,
+        169003.5424,
+        172651.0939,
+        176310.7205,
+        180004.1565,
+        183697.9737,
+        187415.2342,
+        191138.9483,
+        194869.6314,
+        198615.8583,
+        202396.2931,
+        206169.6261,
+        209980.0719,
+        213770.2361,
+        217588.2644,
+        221402.4891,
+        225236.5618,
+        229084.2890,
+        232933.4175,
+        236796.5733,
+        240645.5864,
+        244493.4583,
+        248338.3373,
+        252197.9586,
+        256073.1100,
+        259950.0032,
+        263840.5850,
+        267741.2021,
+        271632.5067,
+        275528.0667,
+        279437.1966,
+        283328.3881,
+        287233.9138,
+        291141.9579,
+        295051.6609,
+        298968.9187,
+        302876.0812,
+        306812.0003,
+        310711.5000,
+        314629.9444,
+        318570.9768,
+        322485.0454,
+        326408.9562,
+        330356.2928,
+        334274.5738,
+        338207.5356,
+        342129.6626,
+        346023.2912,
+        349936.6644,
+        353880.4451,
+        357793.9209,
+        361706.1619,
+        365631.2092,
+        369567.0546,
+        373496.1313,
+        377475.7053,
+        381399.4724,
+        385337.2561,
+        389251.0879,
+        393217.6752,
+    ],
+    // p = 17, 200 trials
+    &[
+        98376.7724,
+        102321.5259,
+        106375.4803,
+        110539.8049,
+        114812.9171,
+        119190.7974,
+        123680.3410,
+        128274.6248,
+        132977.8383,
+        137786.9339,
+        142710.9574,
+        147735.4771,
+        152858.7523,
+        158082.3952,
+        163412.0381,
+        168847.0336,
+        174376.0040,
+        179999.6309,
+        185719.1383,
+        191533.5683,
+        197438.1200,
+        203432.3798,
+        209495.7599,
+        215653.5602,
+        221899.7099,
+        228215.8538,
+        234601.2933,
+        241069.2959,
+        247609.6141,
+        254220.5363,
+        260896.9926,
+        267645.6938,
+        274440.5366,
+        281300.7889,
+        288225.5089,
+        295209.0713,
+        302221.0245,
+        309291.9369,
+        316398.6260,
+        323586.5137,
+        330814.8056,
+        338062.8959,
+        345345.9190,
+        352662.1019,
+        360023.7526,
+        367398.7101,
+        374839.4932,
+        382308.0352,
+        389806.0028,
+        397321.5229,
+        404854.4620,
+        412441.1495,
+        420020.4147,
+        427625.1392,
+        435269.3456,
+        442916.6275,
+        450598.2673,
+        458281.1560,
+        465966.9584,
+        473669.4093,
+        481394.5568,
+        489129.3489,
+        496878.9836,
+        504618.4213,
+        512353.5904,
+        520114.3912,
+        527903.5236,
+        535659.4249,
+        543449.7157,
+        551238.6171,
+        559045.3393,
+        566876.1907,
+        574690.7898,
+        582473.5292,
+        590308.3631,
+        598150.2176,
+        605954.5470,
+        613748.8184,
+        621579.6237,
+        629412.4035,
+        637267.1460,
+        645100.0184,
+        652928.6876,
+        660768.2546,
+        668635.2850,
+        676456.7657,
+        684339.7696,
+        692183.7873,
+        700053.9149,
+        707897.3919,
+        715714.5690,
+        723622.2530,
+        731492.1011,
+        739406.1225,
+        747275.9067,
+        755137.9945,
+        763000.9137,
+        770853.6093,
+        778683.7325,
+        786500.1075,
+    ],
+    // p = 18, 200 trials
+    &[
+        196754.3707,
+        204644.7519,
+        212752.4752,
+        221077.3830,
+        229618.8037,
+        238375.9319,
+        247351.8070,
+        256547.7495,
+        265956.7500,
+        275581.2522,
+        285413.3432,
+        295460.3557,
+        305714.6746,
+        316173.1360,
+        326832.5917,
+        337685.4988,
+        348742.7688,
+        359996.7116,
+        371428.9703,
+        383059.9281,
+        394860.9859,
+        406841.3489,
+        418975.1997,
+        431283.7242,
+        443760.5066,
+        456398.4602,
+        469176.9915,
+        482125.4520,
+        495202.3204,
+        508439.5062,
+        521799.1218,
+        535293.3458,
+        548898.3610,
+        562630.5488,
+        576441.3678,
+        590379.3065,
+        604441.4933,
+        618586.1946,
+        632845.9990,
+        647172.4120,
+        661601.8466,
+        676111.7292,
+        690727.1153,
+        705384.7494,
+        720117.8430,
+        734930.8511,
+        749762.2627,
+        764677.0327,
+        779662.1549,
+        794682.7532,
+        809763.2206,
+        824878.0261,
+        840040.0732,
+        855275.0388,
+        870548.3556,
+        885800.4646,
+        901123.2039,
+        916506.2699,
+        931855.9542,
+        947266.3938,
+        962693.9628,
+        978101.7012,
+        993638.4819,
+        1009184.0550,
+        1024700.0291,
+        1040222.2697,
+        1055795.8365,
+        1071356.2087,
+        1086903.7480,
+        1102463.3004,
+        1118082.5298,
+        1133692.3209,
+        1149305.4028,
+        1164946.6307,
+        1180632.7756,
+        1196299.5752,
+        1211965.2890,
+        1227636.2695,
+        1243317.3912,
+        1259015.0673,
+        1274718.4925,
+        1290404.1254,
+        1306081.8165,
+        1321758.1839,
+        1337497.8724,
+        1353187.2646,
+        1368871.8746,
+        1384633.8796,
+        1400343.5801,
+        1416086.9763,
+        1431756.4427,
+        1447464.7479,
+        1463217.8633,
+        1478959.3820,
+        1494689.6331,
+        1510370.7543,
+        1526106.6098,
+        1541825.7844,
+        1557509.6029,
+        1573254.3950,
+    ],
+];
+
+#[rustfmt::skip]
+pub(crate) const BIAS: [&[f64]; 15] = [
+    // p = 4
+    &[
+        10.7680,
+        10.2384,
+        9.7240,
+        9.2248,
+        8.7407,
+        8.2729,
+        7.8196,
+        7.3813,
+        6.9622,
+        6.5624,
+        6.1726,
+        5.7973,
+        5.4436,
+        5.0977,
+        4.7704,
+        4.4542,
+        4.1663,
+        3.8838,
+        3.6118,
+        3.3517,
+        3.1124,
+        2.8821,
+        2.6699,
+        2.4679,
+        2.2842,
+        2.1114,
+        1.9478,
+        1.7892,
+        1.6385,
+        1.5010,
+        1.3739,
+        1.2488,
+        1.1365,
+        1.0385,
+        0.9395,
+        0.8586,
+        0.7784,
+        0.7063,
+        0.6495,
+        0.5950,
+        0.5520,
+        0.4954,
+        0.4489,
+        0.4120,
+        0.3755,
+        0.3438,
+        0.3140,
+        0.2834,
+        0.2578,
+        0.2470,
+        0.2228,
+        0.2169,
+        0.1980,
+        0.1844,
+        0.1790,
+        0.1596,
+        0.1304,
+        0.1223,
+        0.1160,
+        0.0856,
+        0.0788,
+        0.0694,
+        0.0744,
+        0.0866,
+        0.0895,
+        0.0878,
+        0.1032,
+        0.0727,
+        0.0844,
+        0.0639,
+        0.0679,
+        0.0711,
+        0.0924,
+        0.0904,
+        0.0937,
+        0.0797,
+        0.0662,
+        0.0591,
+        0.0720,
+        0.0556,
+        0.0527,
+        0.0544,
+        0.0532,
+        0.0555,
+        0.0293,
+        0.0685,
+        0.0449,
+        0.0440,
+        0.0391,
+        0.0537,
+        0.0638,
+        0.0479,
+        0.0719,
+        0.0732,
+        0.0658,
+        0.0585,
+        0.0648,
+    ],
+    // p = 5
+    &[
+        21.7803,
+        20.7539,
+        19.7585,
+        18.7915,
+        17.8530,
+        16.9526,
+        16.0777,
+        15.2314,
+        14.4162,
+        13.6247,
+        12.8662,
+        12.1329,
+        11.7783,
+        11.0985,
+        10.4460,
+        9.8290,
+        9.2284,
+        8.6595,
+        8.1085,
+        7.5872,
+        7.0965,
+        6.6225,
+        6.1713,
+        5.7551,
+        5.3522,
+        5.1666,
+        4.7960,
+        4.4581,
+        4.1398,
+        3.8273,
+        3.5491,
+        3.2880,
+        3.0362,
+        2.8069,
+        2.5998,
+        2.4053,
+        2.1963,
+        2.1110,
+        1.9320,
+        1.7636,
+        1.6234,
+        1.4879,
+        1.3844,
+        1.2753,
+        1.1477,
+        1.0308,
+        0.9515,
+        0.8667,
+        0.7748,
+        0.7094,
+        0.6827,
+        0.6502,
+        0.5850,
+        0.5339,
+        0.4840,
+        0.4935,
+        0.4705,
+        0.4096,
+        0.3664,
+        0.3411,
+        0.3369,
+        0.3032,
+        0.2773,
+        0.2729,
+        0.2685,
+        0.2879,
+        0.2633,
+        0.2543,
+        0.2581,
+        0.2677,
+        0.2751,
+        0.3076,
+        0.3027,
+        0.3155,
+        0.2983,
+        0.2968,
+        0.2736,
+        0.2623,
+        0.2434,
+        0.2064,
+        0.2008,
+        0.1892,
+        0.2192,
+        0.1775,
+        0.1771,
+        0.1906,
+        0.1463,
+        0.1559,
+        0.1772,
+        0.1311,
+        0.1427,
+        0.1814,
+        0.1628,
+        0.1603,
+        0.1348,
+        0.1451,
+        0.1587,
+        0.1666,
+        0.1527,
+        0.1281,
+    ],
+    // p = 6
+    &[
+        43.8208,
+        41.7977,
+        39.8351,
+        37.9294,
+        36.0796,
+        34.2919,
+        32.9975,
+        31.3083,
+        29.6871,
+        28.1208,
+        26.6112,
+        25.1584,
+        24.0955,
+        22.7486,
+        21.4441,
+        20.1900,
+        19.0027,
+        17.8578,
+        17.0393,
+        16.0063,
+        15.0061,
+        14.0540,
+        13.1663,
+        12.2962,
+        11.4495,
+        10.8559,
+        10.1326,
+        9.4246,
+        8.7344,
+        8.1074,
+        7.5116,
+        7.0917,
+        6.5603,
+        6.0618,
+        5.5719,
+        5.1467,
+        4.7533,
+        4.4565,
+        4.0795,
+        3.7318,
+        3.3904,
+        3.1161,
+        2.8073,
+        2.6066,
+        2.3612,
+        2.1671,
+        1.9461,
+        1.7818,
+        1.5855,
+        1.3769,
+        1.2550,
+        1.0982,
+        0.9382,
+        0.8005,
+        0.7015,
+        0.6445,
+        0.5934,
+        0.5007,
+        0.3774,
+        0.2957,
+        0.2404,
+        0.2147,
+        0.1573,
+        0.1344,
+        0.0702,
+        0.0442,
+        0.0079,
+        -0.0279,
+        -0.0565,
+        -0.0933,
+        -0.1354,
+        -0.1616,
+        -0.1970,
+        -0.2079,
+        -0.2189,
+        -0.2198,
+        -0.2713,
+        -0.2305,
+        -0.2090,
+        -0.1896,
+        -0.2117,
+        -0.2310,
+        -0.2066,
+        -0.1921,
+        -0.2053,
+        -0.2319,
+        -0.2026,
+        -0.1607,
+        -0.1536,
+        -0.1075,
+        -0.1112,
+        -0.1482,
+        -0.1748,
+        -0.1779,
+        -0.1680,
+        -0.1980,
+        -0.2204,
+        -0.2450,
+        -0.1877,
+        -0.1563,
+    ],
+    // p = 7
+    &[
+        87.9509,
+        83.9424,
+        80.0439,
+        76.7325,
+        73.0579,
+        69.4994,
+        66.4856,
+        63.1429,
+        59.9267,
+        57.1997,
+        54.1924,
+        51.2845,
+        48.8389,
+        46.1559,
+        43.5668,
+        41.3973,
+        39.0142,
+        36.7261,
+        34.8180,
+        32.6974,
+        30.6832,
+        29.0080,
+        27.1850,
+        25.4713,
+        23.8458,
+        22.4837,
+        20.9885,
+        19.6015,
+        18.4151,
+        17.1716,
+        15.9805,
+        14.9711,
+        13.9282,
+        12.9063,
+        12.0743,
+        11.1940,
+        10.3532,
+        9.6256,
+        8.8965,
+        8.2383,
+        7.6778,
+        7.0741,
+        6.4739,
+        6.0299,
+        5.5138,
+        5.0097,
+        4.6548,
+        4.2492,
+        3.8689,
+        3.4972,
+        3.2260,
+        2.8955,
+        2.5950,
+        2.3638,
+        2.0832,
+        1.8603,
+        1.6645,
+        1.4711,
+        1.3185,
+        1.2139,
+        1.0907,
+        0.9791,
+        0.8700,
+        0.7714,
+        0.6651,
+        0.5549,
+        0.5198,
+        0.4493,
+        0.4554,
+        0.4346,
+        0.3421,
+        0.3143,
+        0.2550,
+        0.1599,
+        0.0565,
+        0.0184,
+        0.0334,
+        0.0518,
+        -0.0578,
+        -0.0652,
+        -0.0642,
+        -0.1090,
+        -0.1324,
+        -0.0618,
+        -0.0861,
+        -0.0821,
+        -0.1178,
+        -0.1270,
+        -0.1149,
+        -0.1510,
+        -0.1169,
+        -0.1276,
+        -0.2017,
+        -0.2119,
+        -0.2590,
+        -0.1712,
+        -0.1258,
+        -0.1431,
+        -0.0962,
+        -0.1646,
+    ],
+    // p = 8
+    &[
+        176.1763,
+        168.6737,
+        160.8970,
+        153.8200,
+        146.9460,
+        139.8488,
+        133.3913,
+        127.1334,
+        120.7011,
+        114.8464,
+        109.2145,
+        103.4141,
+        98.1938,
+        92.8178,
+        87.9820,
+        83.3088,
+        78.5538,
+        74.2786,
+        70.1662,
+        65.9650,
+        62.2402,
+        58.6492,
+        54.9908,
+        51.7582,
+        48.4389,
+        45.4833,
+        42.6653,
+        39.8438,
+        37.3777,
+        34.9346,
+        32.5333,
+        30.4289,
+        28.3546,
+        26.3347,
+        24.5249,
+        22.8183,
+        21.0966,
+        19.5867,
+        18.1144,
+        16.8348,
+        15.5851,
+        14.3052,
+        13.1961,
+        12.1863,
+        11.2031,
+        10.3434,
+        9.5607,
+        8.7230,
+        8.0253,
+        7.3678,
+        6.7490,
+        6.2296,
+        5.6952,
+        5.2622,
+        4.7767,
+        4.3292,
+        3.9362,
+        3.5944,
+        3.2688,
+        2.9732,
+        2.7763,
+        2.5396,
+        2.3371,
+        2.1866,
+        1.9853,
+        1.9208,
+        1.6199,
+        1.3781,
+        1.3703,
+        1.2083,
+        1.0670,
+        0.8735,
+        0.7568,
+        0.6165,
+        0.5597,
+        0.4936,
+        0.4495,
+        0.2576,
+        0.2416,
+        0.2820,
+        0.2328,
+        0.2391,
+        0.2653,
+        0.2676,
+        0.1518,
+        -0.0363,
+        -0.0316,
+        -0.0788,
+        -0.0338,
+        0.0018,
+        0.0229,
+        0.0277,
+        0.0855,
+        0.0420,
+        -0.0072,
+        -0.0256,
+        -0.0666,
+        0.0037,
+        -0.0927,
+        -0.0427,
+    ],
+    // p = 9
+    &[
+        353.1391,
+        337.6587,
+        322.6174,
+        308.4957,
+        294.3229,
+        280.5640,
+        267.2277,
+        254.7527,
+        242.2857,
+        230.2637,
+        219.0318,
+        207.7975,
+        196.9799,
+        186.5754,
+        176.9230,
+        167.3280,
+        158.1119,
+        149.5683,
+        141.0970,
+        133.0034,
+        125.2532,
+        118.1315,
+        111.0811,
+        104.3691,
+        97.9799,
+        92.0811,
+        86.3194,
+        80.8194,
+        75.8073,
+        70.8616,
+        66.2011,
+        61.7376,
+        57.7055,
+        53.8354,
+        50.1644,
+        46.9468,
+        43.6865,
+        40.5775,
+        37.7522,
+        35.1364,
+        32.5863,
+        30.1706,
+        27.8513,
+        25.7765,
+        23.8350,
+        21.9960,
+        20.3240,
+        18.6677,
+        17.2489,
+        15.8293,
+        14.5169,
+        13.3589,
+        12.2936,
+        11.1623,
+        10.2150,
+        9.4514,
+        8.6453,
+        7.9516,
+        7.2144,
+        6.5609,
+        5.9540,
+        5.4738,
+        5.0132,
+        4.7134,
+        4.2472,
+        3.9094,
+        3.5596,
+        3.1437,
+        2.8409,
+        2.6379,
+        2.3543,
+        2.0177,
+        2.0524,
+        1.8559,
+        1.6548,
+        1.5289,
+        1.3651,
+        1.1992,
+        1.1595,
+        1.1143,
+        0.9969,
+        0.8778,
+        0.9284,
+        0.9155,
+        0.7761,
+        0.5749,
+        0.6886,
+        0.4206,
+        0.6548,
+        0.5191,
+        0.2459,
+        0.2722,
+        0.1971,
+        0.1352,
+        -0.0397,
+        -0.1191,
+        -0.1847,
+        -0.2932,
+        -0.3662,
+        -0.3854,
+    ],
+    // p = 10
+    &[
+        706.5755,
+        676.1370,
+        646.0629,
+        617.3210,
+        588.9689,
+        561.9568,
+        535.3684,
+        509.9546,
+        485.3992,
+        461.2607,
+        438.4341,
+        416.0183,
+        394.7676,
+        374.0176,
+        354.2986,
+        335.1129,
+        317.0068,
+        299.6337,
+        282.7284,
+        266.7131,
+        251.2644,
+        236.7228,
+        222.5576,
+        209.3786,
+        196.5228,
+        184.5897,
+        173.1360,
+        162.1741,
+        152.0159,
+        142.0798,
+        132.6797,
+        123.7990,
+        115.5621,
+        107.6395,
+        100.1636,
+        93.1226,
+        86.5071,
+        80.5539,
+        74.5884,
+        69.1692,
+        64.0498,
+        59.0694,
+        54.4817,
+        50.1171,
+        46.1130,
+        42.4399,
+        39.2577,
+        35.9329,
+        32.9321,
+        29.9422,
+        27.5985,
+        25.2907,
+        22.8018,
+        21.0134,
+        19.3016,
+        17.4766,
+        15.9051,
+        14.3376,
+        13.0320,
+        11.5569,
+        10.7078,
+        9.4989,
+        8.1999,
+        7.3219,
+        6.3603,
+        5.5928,
+        4.7587,
+        4.1739,
+        3.9504,
+        3.4899,
+        3.0673,
+        2.2695,
+        2.1911,
+        1.4734,
+        1.4391,
+        1.3016,
+        1.1723,
+        0.6188,
+        0.4069,
+        0.0745,
+        0.3888,
+        0.2466,
+        -0.2567,
+        -0.4764,
+        -0.4044,
+        -0.7915,
+        -0.4797,
+        -0.9349,
+        -1.2808,
+        -1.3231,
+        -1.2377,
+        -1.3717,
+        -1.5229,
+        -1.3808,
+        -1.4038,
+        -1.6949,
+        -1.6285,
+        -1.9583,
+        -1.8684,
+        -2.0602,
+    ],
+    // p = 11
+    &[
+        1413.9269,
+        1352.6008,
+        1293.0151,
+        1235.1073,
+        1178.8444,
+        1124.3231,
+        1071.4634,
+        1020.3546,
+        971.3656,
+        923.6260,
+        877.6002,
+        833.2101,
+        790.3069,
+        749.0436,
+        709.3138,
+        671.2121,
+        634.7462,
+        599.8666,
+        566.1083,
+        533.8747,
+        503.2773,
+        474.0544,
+        446.2591,
+        419.5300,
+        394.3991,
+        370.6691,
+        347.7726,
+        325.8555,
+        304.9500,
+        285.5660,
+        266.9345,
+        249.8285,
+        233.2929,
+        217.7074,
+        203.0651,
+        189.1252,
+        176.3811,
+        163.7621,
+        152.0249,
+        141.4128,
+        131.3614,
+        122.2100,
+        113.2144,
+        105.6003,
+        97.7655,
+        90.6762,
+        84.1638,
+        78.0182,
+        71.6865,
+        66.6096,
+        61.7217,
+        56.9658,
+        52.7758,
+        48.8531,
+        45.2719,
+        42.1095,
+        40.0508,
+        37.2261,
+        34.0159,
+        32.1059,
+        30.3971,
+        28.7137,
+        26.7224,
+        24.7916,
+        23.5403,
+        22.1525,
+        20.4574,
+        20.0549,
+        19.0675,
+        18.4048,
+        17.1777,
+        16.5597,
+        15.1115,
+        13.9270,
+        13.0024,
+        12.3303,
+        11.3583,
+        10.3726,
+        10.4328,
+        9.9619,
+        9.7094,
+        9.0359,
+        8.6706,
+        8.0970,
+        7.4534,
+        7.3542,
+        8.0401,
+        8.4652,
+        8.5798,
+        8.2660,
+        7.3281,
+        6.7529,
+        5.2958,
+        5.8478,
+        6.0150,
+        6.2960,
+        6.5183,
+        6.0332,
+        5.4160,
+        4.4441,
+    ],
+    // p = 12
+    &[
+        2828.0784,
+        2705.4699,
+        2586.2083,
+        2470.4078,
+        2358.5994,
+        2249.5832,
+        2144.1066,
+        2041.6752,
+        1943.1286,
+        1847.6873,
+        1755.5018,
+        1666.6649,
+        1581.3797,
+        1498.9271,
+        1419.4664,
+        1343.6869,
+        1270.4371,
+        1199.8672,
+        1132.4330,
+        1067.8843,
+        1006.2207,
+        947.5949,
+        891.8415,
+        838.5270,
+        788.4485,
+        740.6283,
+        694.4771,
+        650.9099,
+        610.4341,
+        571.4346,
+        534.2659,
+        498.8345,
+        464.8387,
+        432.9372,
+        403.1025,
+        376.2610,
+        349.8145,
+        325.2205,
+        302.1291,
+        281.5480,
+        261.1247,
+        242.4623,
+        224.3248,
+        208.3019,
+        192.7914,
+        176.4905,
+        162.8892,
+        148.8496,
+        137.0151,
+        124.6805,
+        114.1217,
+        104.2117,
+        94.7772,
+        86.8785,
+        79.6705,
+        72.3745,
+        66.0168,
+        60.1605,
+        54.8932,
+        50.0529,
+        43.5129,
+        38.5522,
+        33.7387,
+        30.7507,
+        26.4209,
+        24.3596,
+        22.0389,
+        19.9615,
+        16.5720,
+        15.5279,
+        12.6932,
+        9.9287,
+        8.8127,
+        6.4938,
+        4.7431,
+        3.8166,
+        2.6270,
+        0.9940,
+        -1.0527,
+        -4.6631,
+        -5.9190,
+        -6.7964,
+        -8.6937,
+        -10.0804,
+        -11.8902,
+        -12.3006,
+        -13.9241,
+        -16.2722,
+        -16.8125,
+        -18.6300,
+        -19.1226,
+        -19.5857,
+        -19.6171,
+        -19.2335,
+        -18.6692,
+        -18.3928,
+        -20.2877,
+        -19.0640,
+        -18.9950,
+        -20.2162,
+    ],
+    // p = 13
+    &[
+        5656.5726,
+        5411.2085,
+        5173.2458,
+        4941.6795,
+        4717.3666,
+        4499.4235,
+        4288.6794,
+        4084.5189,
+        3887.8176,
+        3696.4992,
+        3511.6598,
+        3333.9327,
+        3163.1547,
+        2997.8551,
+        2838.8173,
+        2686.9643,
+        2540.6855,
+        2400.3737,
+        2265.9129,
+        2137.6828,
+        2015.2234,
+        1896.9574,
+        1785.6249,
+        1678.7105,
+        1577.2206,
+        1481.3286,
+        1390.3468,
+        1304.9253,
+        1222.5656,
+        1144.2912,
+        1070.6027,
+        1000.1850,
+        934.4709,
+        871.4604,
+        811.9239,
+        756.4533,
+        702.0709,
+        653.8140,
+        607.6698,
+        563.5867,
+        521.5932,
+        483.4523,
+        447.8721,
+        414.2774,
+        382.3898,
+        352.6882,
+        325.4901,
+        300.2310,
+        276.9140,
+        252.6408,
+        233.9723,
+        215.3070,
+        199.2547,
+        182.8697,
+        169.3789,
+        153.4302,
+        139.4538,
+        127.4441,
+        117.1430,
+        102.8749,
+        96.3642,
+        90.0900,
+        82.9503,
+        75.9523,
+        70.5246,
+        65.3025,
+        58.5447,
+        52.3140,
+        47.5475,
+        40.9864,
+        37.2701,
+        35.3954,
+        33.8282,
+        32.2837,
+        30.1382,
+        26.9445,
+        24.2159,
+        27.1173,
+        22.9290,
+        19.6013,
+        14.4916,
+        14.1217,
+        11.5872,
+        13.5103,
+        14.3137,
+        11.8628,
+        12.0764,
+        9.8040,
+        9.7123,
+        8.5091,
+        8.3271,
+        8.4773,
+        11.2901,
+        13.4987,
+        16.0335,
+        18.0973,
+        15.8491,
+        13.2840,
+        12.4148,
+        10.6826,
+    ],
+    // p = 14
+    &[
+        11313.2223,
+        10823.1938,
+        10346.9897,
+        9884.5427,
+        9435.4766,
+        8999.9134,
+        8577.4086,
+        8168.5639,
+        7773.3061,
+        7391.8972,
+        7023.7039,
+        6668.8135,
+        6326.1582,
+        5996.6056,
+        5679.8310,
+        5374.7109,
+        5082.7485,
+        4802.6415,
+        4532.3912,
+        4275.6324,
+        4031.5148,
+        3795.7806,
+        3573.8268,
+        3358.9584,
+        3154.6132,
+        2960.6997,
+        2777.9059,
+        2604.2246,
+        2438.0769,
+        2279.7979,
+        2131.5245,
+        1991.9060,
+        1857.1695,
+        1730.1573,
+        1610.5711,
+        1499.9696,
+        1394.4255,
+        1298.8719,
+        1206.6319,
+        1120.4446,
+        1037.8228,
+        962.8751,
+        887.6374,
+        821.4002,
+        758.6074,
+        699.8640,
+        641.5544,
+        588.6915,
+        541.5948,
+        499.8631,
+        457.7467,
+        421.8875,
+        384.3277,
+        351.8897,
+        319.7202,
+        295.9680,
+        275.6150,
+        251.1934,
+        240.2251,
+        219.2778,
+        198.6271,
+        180.6628,
+        166.3760,
+        159.2373,
+        150.6658,
+        139.4824,
+        131.4808,
+        126.3828,
+        119.6756,
+        111.8655,
+        101.4684,
+        93.1717,
+        88.6437,
+        87.0539,
+        81.9780,
+        78.0524,
+        71.9982,
+        66.9694,
+        64.8817,
+        57.3923,
+        50.9346,
+        49.5620,
+        48.2616,
+        42.6485,
+        36.1832,
+        29.0396,
+        23.6074,
+        18.5185,
+        15.8950,
+        17.4526,
+        17.3185,
+        11.3604,
+        12.7858,
+        7.9281,
+        5.1491,
+        12.0562,
+        5.9197,
+        8.4924,
+        10.3289,
+        11.2532,
+    ],
+    // p = 15
+    &[
+        22627.7524,
+        21647.8219,
+        20695.1958,
+        19770.7132,
+        18872.1415,
+        18000.9875,
+        17157.4263,
+        16340.0295,
+        15549.2426,
+        14786.1288,
+        14049.5103,
+        13339.5415,
+        12654.4668,
+        11997.1298,
+        11361.5898,
+        10747.9204,
+        10164.3812,
+        9600.3465,
+        9063.4213,
+        8549.1866,
+        8058.1943,
+        7591.4473,
+        7142.6575,
+        6716.2806,
+        6305.8507,
+        5914.2697,
+        5545.1839,
+        5194.4528,
+        4865.2409,
+        4551.1251,
+        4253.3619,
+        3970.4609,
+        3710.3037,
+        3458.7141,
+        3225.5543,
+        2999.4974,
+        2789.0290,
+        2594.9636,
+        2410.1628,
+        2236.6361,
+        2081.7841,
+        1926.9459,
+        1780.4233,
+        1641.5667,
+        1512.3041,
+        1406.7599,
+        1299.7829,
+        1196.5759,
+        1104.4024,
+        1022.2534,
+        931.8549,
+        855.3664,
+        786.4331,
+        723.9227,
+        657.7255,
+        611.5992,
+        554.8536,
+        501.8427,
+        470.1130,
+        430.3090,
+        393.6832,
+        353.2828,
+        318.5161,
+        274.8957,
+        245.0332,
+        220.1117,
+        187.7013,
+        162.7809,
+        153.9966,
+        143.0057,
+        131.5596,
+        122.5231,
+        108.7507,
+        101.4698,
+        90.6523,
+        87.6834,
+        78.4175,
+        76.5128,
+        69.7916,
+        66.4980,
+        60.1249,
+        60.2161,
+        46.8861,
+        35.3012,
+        27.9512,
+        22.5283,
+        30.8889,
+        28.1822,
+        30.4956,
+        35.6523,
+        27.6692,
+        19.6399,
+        22.1920,
+        25.8766,
+        34.8702,
+        38.4807,
+        35.7300,
+        45.5517,
+        33.6551,
+        30.4142,
+    ],
+    // p = 16
+    &[
+        45256.5629,
+        43296.3263,
+        41389.6089,
+        39537.5762,
+        37739.3425,
+        35998.1496,
+        34310.7691,
+        32675.3775,
+        31099.2229,
+        29570.0833,
+        28095.8781,
+        26674.9173,
+        25303.3399,
+        23985.2220,
+        22720.6444,
+        21502.3849,
+        20329.8398,
+        19207.8848,
+        18137.2373,
+        17106.1349,
+        16127.0713,
+        15187.6740,
+        14292.1342,
+        13445.7102,
+        12625.6319,
+        11849.6624,
+        11106.1463,
+        10406.3318,
+        9736.9527,
+        9112.0793,
+        8511.4766,
+        7953.9658,
+        7427.9023,
+        6929.0212,
+        6450.5247,
+        6006.4793,
+        5597.0419,
+        5207.5448,
+        4834.1452,
+        4483.5020,
+        4158.9144,
+        3853.5424,
+        3569.0939,
+        3295.7205,
+        3057.1565,
+        2818.9737,
+        2604.2342,
+        2395.9483,
+        2194.6314,
+        2007.8583,
+        1856.2931,
+        1697.6261,
+        1576.0719,
+        1434.2361,
+        1320.2644,
+        1202.4891,
+        1103.5618,
+        1019.2890,
+        936.4175,
+        867.5733,
+        784.5864,
+        700.4583,
+        612.3373,
+        539.9586,
+        483.1100,
+        428.0032,
+        386.5850,
+        355.2021,
+        313.5067,
+        277.0667,
+        254.1966,
+        213.3881,
+        186.9138,
+        162.9579,
+        139.6609,
+        124.9187,
+        100.0812,
+        104.0003,
+        71.5000,
+        57.9444,
+        66.9768,
+        48.0454,
+        39.9562,
+        55.2928,
+        41.5738,
+        42.5356,
+        32.6626,
+        -6.7088,
+        -25.3356,
+        -13.5549,
+        -32.0791,
+        -51.8381,
+        -58.7908,
+        -55.9454,
+        -58.8687,
+        -11.2947,
+        -19.5276,
+        -13.7439,
+        -31.9121,
+        1.6752,
+    ],
+    // p = 17
+    &[
+        90512.7724,
+        86593.5259,
+        82783.4803,
+        79082.8049,
+        75491.9171,
+        72005.7974,
+        68630.3410,
+        65360.6248,
+        62199.8383,
+        59143.9339,
+        56203.9574,
+        53364.4771,
+        50622.7523,
+        47982.3952,
+        45448.0381,
+        43018.0336,
+        40683.0040,
+        38442.6309,
+        36297.1383,
+        34247.5683,
+        32288.1200,
+        30417.3798,
+        28616.7599,
+        26910.5602,
+        25291.7099,
+        23743.8538,
+        22265.2933,
+        20869.2959,
+        19544.6141,
+        18291.5363,
+        17103.9926,
+        15987.6938,
+        14918.5366,
+        13914.7889,
+        12974.5089,
+        12094.0713,
+        11242.0245,
+        10447.9369,
+        9690.6260,
+        9014.5137,
+        8377.8056,
+        7761.8959,
+        7180.9190,
+        6632.1019,
+        6129.7526,
+        5640.7101,
+        5216.4932,
+        4821.0352,
+        4455.0028,
+        4105.5229,
+        3774.4620,
+        3497.1495,
+        3212.4147,
+        2952.1392,
+        2732.3456,
+        2515.6275,
+        2332.2673,
+        2151.1560,
+        1972.9584,
+        1810.4093,
+        1671.5568,
+        1542.3489,
+        1426.9836,
+        1302.4213,
+        1173.5904,
+        1069.3912,
+        994.5236,
+        886.4249,
+        811.7157,
+        736.6171,
+        679.3393,
+        645.1907,
+        595.7898,
+        514.5292,
+        484.3631,
+        462.2176,
+        402.5470,
+        332.8184,
+        298.6237,
+        267.4035,
+        258.1460,
+        226.0184,
+        190.6876,
+        166.2546,
+        168.2850,
+        125.7657,
+        144.7696,
+        123.7873,
+        129.9149,
+        109.3919,
+        61.5690,
+        105.2530,
+        111.1011,
+        160.1225,
+        165.9067,
+        163.9945,
+        161.9137,
+        150.6093,
+        116.7325,
+        68.1075,
+    ],
+    // p = 18
+    &[
+        181026.3707,
+        173187.7519,
+        165567.4752,
+        158163.3830,
+        150975.8037,
+        144004.9319,
+        137251.8070,
+        130718.7495,
+        124399.7500,
+        118295.2522,
+        112398.3432,
+        106717.3557,
+        101242.6746,
+        95973.1360,
+        90903.5917,
+        86027.4988,
+        81356.7688,
+        76881.7116,
+        72584.9703,
+        68487.9281,
+        64559.9859,
+        60811.3489,
+        57217.1997,
+        53796.7242,
+        50544.5066,
+        47454.4602,
+        44503.9915,
+        41724.4520,
+        39072.3204,
+        36580.5062,
+        34212.1218,
+        31977.3458,
+        29853.3610,
+        27857.5488,
+        25939.3678,
+        24148.3065,
+        22482.4933,
+        20898.1946,
+        19429.9990,
+        18027.4120,
+        16727.8466,
+        15509.7292,
+        14396.1153,
+        13324.7494,
+        12329.8430,
+        11413.8511,
+        10516.2627,
+        9703.0327,
+        8959.1549,
+        8250.7532,
+        7603.2206,
+        6989.0261,
+        6423.0732,
+        5929.0388,
+        5473.3556,
+        4997.4646,
+        4591.2039,
+        4245.2699,
+        3866.9542,
+        3548.3938,
+        3246.9628,
+        2926.7012,
+        2734.4819,
+        2552.0550,
+        2339.0291,
+        2132.2697,
+        1977.8365,
+        1809.2087,
+        1627.7480,
+        1459.3004,
+        1349.5298,
+        1230.3209,
+        1115.4028,
+        1027.6307,
+        984.7756,
+        923.5752,
+        860.2890,
+        803.2695,
+        755.3912,
+        724.0673,
+        699.4925,
+        656.1254,
+        604.8165,
+        553.1839,
+        563.8724,
+        524.2646,
+        480.8746,
+        513.8796,
+        495.5801,
+        509.9763,
+        450.4427,
+        430.7479,
+        454.8633,
+        467.3820,
+        469.6331,
+        421.7543,
+        428.6098,
+        419.7844,
+        374.6029,
+        390.3950,
+    ],
+];
@@ -0,0 +1,175 @@
+//! `hll`: count distinct lines and combine sketch files from the shell.
+//!
+//! ```text
+//! hll count < items.txt
+//! hll sketch -o monday.hll < monday.txt
+//! hll merge monday.hll tuesday.hll -o week.hll
+//! hll inspect week.hll
+//! hll count --estimator ertl < items.txt
+//! ```
+//!
+//! Every line of stdin, without its `\n` or `\r\n`, is one item, hashed as
+//! its bytes under `SeededState::new(0)` like the wasm, C, and Python
+//! bindings, so files written here can be merged with sketches from those.
+//! Sketch files hold the crate's binary format.
+
+extern crate clap;
+extern crate hyperloglog;
+
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use hyperloglog::{
+    BiasCorrectedEstimator, ClassicEstimator, ErtlEstimator, Estimator, HyperLogLog,
+    LogLogBetaEstimator, SeededState,
+};
+
+const SEED: u64 = 0;
+
+#[derive(Parser)]
+#[command(
+    name = "hll",
+    version,
+    about = "Count distinct items with HyperLogLog sketches"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the estimated number of distinct lines on stdin.
+    Count {
+        /// Use 2^PRECISION registers, from 4 to 24.
+        #[arg(short, long, default_value_t = 14)]
+        precision: u8,
+        /// Estimate with ESTIMATOR instead of the bias-corrected default.
+        #[arg(short, long)]
+        estimator: Option<EstimatorName>,
+    },
+    /// Write a sketch of the lines on stdin.
+    Sketch {
+        /// Use 2^PRECISION registers, from 4 to 24.
+        #[arg(short, long, default_value_t = 14)]
+        precision: u8,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Write the union of sketch files, which must share a precision.
+    Merge {
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Print a sketch file's precision, non-zero registers, and estimate.
+    Inspect {
+        path: PathBuf,
+        /// Estimate with ESTIMATOR instead of the sketch's own.
+        #[arg(short, long)]
+        estimator: Option<EstimatorName>,
+    },
+}
+
+/// The estimators `--estimator` can name.
+#[derive(Clone, Copy, ValueEnum)]
+enum EstimatorName {
+    BiasCorrected,
+    Classic,
+    #[value(name = "loglog-beta")]
+    LogLogBeta,
+    Ertl,
+}
+
+impl EstimatorName {
+    fn estimator(self) -> &'static dyn Estimator {
+        match self {
+            EstimatorName::BiasCorrected => &BiasCorrectedEstimator,
+            EstimatorName::Classic => &ClassicEstimator,
+            EstimatorName::LogLogBeta => &LogLogBetaEstimator,
+            EstimatorName::Ertl => &ErtlEstimator,
+        }
+    }
+}
+
+/// The sketch's estimate, with `estimator` if one was named.
+fn estimate(sketch: &HyperLogLog<SeededState>, estimator: Option<EstimatorName>) -> f64 {
+    match estimator {
+        Some(name) => sketch.count_with(name.estimator()),
+        None => sketch.count(),
+    }
+}
+
+/// Sketches every line of `input`; see [`HyperLogLog::add_lines`].
+fn sketch_lines<R: BufRead>(input: R, precision: u8) -> Result<HyperLogLog<SeededState>, String> {
+    let mut sketch = HyperLogLog::builder()
+        .precision(precision)
+        .seed(SEED)
+        .build()
+        .map_err(|e| e.to_string())?;
+    sketch
+        .add_lines(input)
+        .map_err(|e| format!("reading stdin: {}", e))?;
+    Ok(sketch)
+}
+
+fn read_sketch(path: &Path) -> Result<HyperLogLog<SeededState>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    HyperLogLog::from_bytes(&bytes).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn write_sketch(path: &Path, sketch: &HyperLogLog<SeededState>) -> Result<(), String> {
+    fs::write(path, sketch.to_bytes()).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Count {
+            precision,
+            estimator,
+        } => {
+            let stdin = io::stdin();
+            let sketch = sketch_lines(stdin.lock(), precision)?;
+            println!("{}", estimate(&sketch, estimator).round());
+        }
+        Command::Sketch { precision, output } => {
+            let stdin = io::stdin();
+            let sketch = sketch_lines(stdin.lock(), precision)?;
+            write_sketch(&output, &sketch)?;
+        }
+        Command::Merge { inputs, output } => {
+            let mut total = read_sketch(&inputs[0])?;
+            for path in &inputs[1..] {
+                let sketch = read_sketch(path)?;
+                total.merge(&sketch).map_err(|e| {
+                    format!("{} and {}: {}", inputs[0].display(), path.display(), e)
+                })?;
+            }
+            write_sketch(&output, &total)?;
+        }
+        Command::Inspect { path, estimator } => {
+            let sketch = read_sketch(&path)?;
+            let nonzero = sketch.registers().filter(|&r| r != 0).count();
+            println!("precision: {}", sketch.precision());
+            println!(
+                "registers: {} of {} non-zero",
+                nonzero,
+                sketch.registers().len()
+            );
+            println!("estimate: {}", estimate(&sketch, estimator).round());
+        }
+    }
+    Ok(())
+}
+
+fn main() {
+    if let Err(message) = run(Cli::parse().command) {
+        eprintln!("hll: {}", message);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,255 @@
+//! Estimating from and merging encoded sketches where they lie, without
+//! decoding them first.
+
+use core::fmt;
+
+use encoding::{
+    read_header, read_watermark, validate_registers, VERSION, VERSION_COUNTED, WATERMARK_LEN,
+};
+use error::{DecodeError, MergeError};
+use {
+    rank_histogram, BuiltinEstimator, DefaultBuildHasher, Estimator, HasherId, HyperLogLog,
+    StableBuildHasher,
+};
+
+/// A sketch borrowed from bytes written by [`HyperLogLog::to_bytes`], read in
+/// place.
+///
+/// [`parse`](HyperLogLogRef::parse) checks the header and every register
+/// once; after that nothing allocates but
+/// [`to_owned`](HyperLogLogRef::to_owned). Only the uncompressed format can
+/// be borrowed: bytes from [`HyperLogLog::to_bytes_compressed`] fail with
+/// [`DecodeError::UnsupportedVersion`] and must be decoded with
+/// [`HyperLogLog::from_bytes`].
+#[derive(Clone, Copy)]
+pub struct HyperLogLogRef<'a, S = DefaultBuildHasher> {
+    registers: &'a [u8],
+    precision: u8,
+    watermark: Option<f64>,
+    estimator: BuiltinEstimator,
+    items_added: u64,
+    build_hasher: S,
+}
+
+impl<'a, S: StableBuildHasher + Clone> HyperLogLogRef<'a, S> {
+    /// Borrows the sketch `bytes` hold, which must be nothing else.
+    pub fn parse(bytes: &'a [u8]) -> Result<HyperLogLogRef<'a, S>, DecodeError> {
+        let (sketch, rest) = HyperLogLogRef::parse_prefix(bytes)?;
+        if !rest.is_empty() {
+            return Err(DecodeError::TrailingBytes {
+                expected: bytes.len() - rest.len(),
+                actual: bytes.len(),
+            });
+        }
+        Ok(sketch)
+    }
+
+    /// Borrows the sketch at the start of `bytes`, returning it and the
+    /// bytes after it, for walking a buffer of sketches written one after
+    /// another.
+    pub fn parse_prefix(bytes: &'a [u8]) -> Result<(HyperLogLogRef<'a, S>, &'a [u8]), DecodeError> {
+        let header = read_header::<S>(bytes, &[VERSION, VERSION_COUNTED])?;
+        let p = header.precision;
+        let registers_end = header.body_at + (1usize << p);
+        let end = if header.watermark {
+            registers_end + WATERMARK_LEN
+        } else {
+            registers_end
+        };
+        if bytes.len() < end {
+            return Err(DecodeError::Truncated {
+                expected: end,
+                actual: bytes.len(),
+            });
+        }
+        let registers = &bytes[header.body_at..registers_end];
+        validate_registers(p, registers)?;
+        let watermark = if header.watermark {
+            Some(read_watermark(&bytes[registers_end..])?)
+        } else {
+            None
+        };
+        let sketch = HyperLogLogRef {
+            registers,
+            precision: p,
+            watermark,
+            estimator: header.estimator,
+            items_added: header.items_added,
+            build_hasher: header.build_hasher,
+        };
+        Ok((sketch, &bytes[end..]))
+    }
+
+    /// Estimates the cardinality as [`HyperLogLog::count`] would for the
+    /// decoded sketch, reading every register.
+    pub fn count(&self) -> f64 {
+        let histogram = rank_histogram(self.registers.iter().cloned());
+        let estimate = self.estimator.estimate(self.precision, &histogram);
+        match self.watermark {
+            Some(watermark) => watermark.max(estimate),
+            None => estimate,
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The number of items added to the sketch; see
+    /// [`HyperLogLog::items_added`].
+    pub fn items_added(&self) -> u64 {
+        self.items_added
+    }
+
+    /// The register values in index order, borrowed from the bytes.
+    pub fn registers(&self) -> &'a [u8] {
+        self.registers
+    }
+
+    /// Merges the borrowed sketch into `target`, as [`HyperLogLog::merge`]
+    /// would merge the decoded one.
+    pub fn merge_into(&self, target: &mut HyperLogLog<S>) -> Result<(), MergeError> {
+        if target.precision != self.precision {
+            return Err(MergeError::PrecisionMismatch {
+                left: target.precision,
+                right: self.precision,
+            });
+        }
+        target.check_hasher(Some(HasherId::of(&self.build_hasher)))?;
+        let nonzero = self
+            .registers
+            .iter()
+            .enumerate()
+            .filter(|&(_, &rank)| rank > 0)
+            .map(|(index, &rank)| (index, rank));
+        target.raise_registers(nonzero);
+        if let Some(watermark) = target.watermark {
+            target.watermark = Some(watermark.max(self.count()));
+        }
+        target.items_added = target.items_added.saturating_add(self.items_added);
+        Ok(())
+    }
+
+    /// Copies the registers into an owned, dense sketch.
+    pub fn to_owned(&self) -> HyperLogLog<S> {
+        let mut h = HyperLogLog::from_dense(
+            self.precision,
+            self.registers.to_vec(),
+            self.build_hasher.clone(),
+        );
+        h.watermark = self.watermark;
+        h.estimator = self.estimator;
+        h.items_added = self.items_added;
+        h
+    }
+}
+
+impl<'a, S> fmt::Debug for HyperLogLogRef<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HyperLogLogRef")
+            .field("precision", &self.precision)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HyperLogLogRef;
+    use error::DecodeError;
+    use simulation::splitmix64;
+    use {HyperLogLog, SeededState};
+
+    #[test]
+    fn matches_the_decoded_sketch() {
+        let mut state = 83;
+        for &n in &[0, 50, 30_000] {
+            let mut h = HyperLogLog::new_seeded(1 << 10, 83);
+            (0..n).for_each(|_| h.add_hashed(splitmix64(&mut state)));
+            let bytes = h.to_bytes();
+            let view: HyperLogLogRef<SeededState> = HyperLogLogRef::parse(&bytes).unwrap();
+            assert_eq!(view.count(), h.count());
+            assert_eq!(view.precision(), 10);
+            assert!(view.registers().iter().cloned().eq(h.registers()));
+            assert_eq!(view.to_owned(), h);
+
+            let mut target = HyperLogLog::new_seeded(1 << 10, 83);
+            target.add_hashed(splitmix64(&mut state));
+            let mut expected = target.clone();
+            expected.merge(&h).unwrap();
+            view.merge_into(&mut target).unwrap();
+            assert_eq!(target, expected);
+            assert_eq!(target.count(), expected.count());
+        }
+    }
+
+    #[test]
+    fn carries_the_watermark() {
+        let mut h = HyperLogLog::new_seeded(1 << 6, 86);
+        h.extend(0..100u32);
+        h.set_monotonic(true);
+        h.watermark = Some(h.count() + 2.);
+        let mut bytes = h.to_bytes();
+        bytes.extend(HyperLogLog::new_seeded(1 << 4, 86).to_bytes());
+
+        let (view, rest) = HyperLogLogRef::<SeededState>::parse_prefix(&bytes).unwrap();
+        assert_eq!(view.count(), h.count());
+        assert_eq!(view.to_owned(), h);
+        assert_eq!(
+            HyperLogLogRef::<SeededState>::parse(rest).unwrap().count(),
+            0.
+        );
+
+        let mut target = HyperLogLog::new_seeded(1 << 6, 86);
+        target.set_monotonic(true);
+        view.merge_into(&mut target).unwrap();
+        assert_eq!(target.count(), h.count());
+    }
+
+    #[test]
+    fn rejects_what_from_bytes_rejects() {
+        let mut h = HyperLogLog::new_seeded(1 << 4, 83);
+        h.extend(0..20u32);
+        let bytes = h.to_bytes();
+        let parse = |b: &[u8]| HyperLogLogRef::<SeededState>::parse(b).map(|v| v.count());
+
+        assert_eq!(
+            parse(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::Truncated {
+                expected: bytes.len(),
+                actual: bytes.len() - 1
+            })
+        );
+        let mut long = bytes.clone();
+        long.push(0);
+        assert_eq!(
+            parse(&long),
+            Err(DecodeError::TrailingBytes {
+                expected: bytes.len(),
+                actual: bytes.len() + 1
+            })
+        );
+        let mut bad = bytes.clone();
+        *bad.last_mut().unwrap() = 62;
+        assert_eq!(
+            parse(&bad),
+            Err(DecodeError::InvalidRegister {
+                index: 15,
+                value: 62
+            })
+        );
+        assert_eq!(
+            parse(&h.to_bytes_compressed()),
+            Err(DecodeError::UnsupportedVersion(5))
+        );
+        // A view checks the hasher like from_bytes.
+        assert_eq!(
+            HyperLogLogRef::<SeededState>::parse(&HyperLogLog::new(1 << 4).to_bytes())
+                .map(|v| v.count()),
+            Err(DecodeError::HasherMismatch)
+        );
+        assert!(HyperLogLogRef::<SeededState>::parse(&bytes)
+            .unwrap()
+            .merge_into(&mut HyperLogLog::new_seeded(1 << 5, 83))
+            .is_err());
+    }
+}
@@ -0,0 +1,308 @@
+//! Confidence intervals around a cardinality estimate.
+//!
+//! The HyperLogLog estimate has a relative standard error of about
+//! `1.04 / √m`. Linear counting (Whang et al., 1990) has a variance of
+//! `m (e^t - t - 1)` with `t = n / m`, which is smaller for small loads and
+//! takes over from the HyperLogLog bound whenever `count` uses linear
+//! counting. Both are normal approximations, so the interval is the estimate
+//! plus or minus a whole number of standard deviations. While the bias
+//! correction applies, up to about `5m`, the corrected estimate's error is
+//! somewhat below `1.04 / √m`, so the intervals there err on the wide side.
+
+use details::{EstimateDetails, EstimationMethod};
+use {math, standard_error};
+
+/// How many standard deviations an [`EstimateBounds`] interval spans on each
+/// side of the estimate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Confidence {
+    /// One standard deviation, covering the true count about 68% of the time.
+    P68,
+    /// Two standard deviations, about 95%.
+    P95,
+    /// Three standard deviations, about 99.7%.
+    P99,
+}
+
+impl Confidence {
+    fn sigmas(self) -> f64 {
+        match self {
+            Confidence::P68 => 1.,
+            Confidence::P95 => 2.,
+            Confidence::P99 => 3.,
+        }
+    }
+}
+
+/// An estimate with lower and upper bounds, returned by
+/// [`HyperLogLog::count_with_bounds`](::HyperLogLog::count_with_bounds).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EstimateBounds {
+    /// The lower bound, never below the number of non-zero registers, since
+    /// each of those was raised by a different item.
+    pub lower: f64,
+    /// The [bias-corrected](::BiasCorrectedEstimator) estimate, which is
+    /// what `count()` returns unless the sketch has another
+    /// [estimator](::HyperLogLog::set_estimator) or is
+    /// [monotonic](::HyperLogLog::set_monotonic).
+    pub estimate: f64,
+    /// The upper bound, infinite once the estimate passes about `2^64 / 30`,
+    /// where it is no longer reliable.
+    pub upper: f64,
+}
+
+/// Whether a sketch has seen more than a threshold of distinct items,
+/// returned by [`HyperLogLog::exceeds`](::HyperLogLog::exceeds).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exceeds {
+    /// More non-zero registers than the threshold: more distinct items
+    /// were certainly added.
+    Definitely,
+    /// The estimate is above the threshold, though the registers don't
+    /// prove it.
+    Probably,
+    /// The estimate is at or below the threshold.
+    No,
+}
+
+/// Past this estimate, a thirtieth of the 64-bit hash space (the limit the
+/// original paper uses for 32-bit hashes), hash collisions and registers at
+/// their maximum rank make the estimate unreliable.
+const RELIABLE_LIMIT: f64 = 18_446_744_073_709_551_616. / 30.;
+
+/// The standard deviation of `details.estimate` for a sketch with `m`
+/// registers, or infinity past the reliable range.
+pub(crate) fn standard_deviation(details: &EstimateDetails, m: usize) -> f64 {
+    let estimate = details.estimate;
+    if estimate.is_nan() || estimate > RELIABLE_LIMIT {
+        return f64::INFINITY;
+    }
+    match details.method {
+        EstimationMethod::LinearCounting => {
+            // Linear counting estimates `t` as ln(m / zeros), so `e^t` is just
+            // m / zeros.
+            let t = estimate / m as f64;
+            let e_t = m as f64 / details.zero_registers as f64;
+            math::sqrt(m as f64 * (e_t - t - 1.).max(0.))
+        }
+        EstimationMethod::HyperLogLog => estimate * standard_error(m.trailing_zeros() as u8),
+    }
+}
+
+/// The interval around `details.estimate` for a sketch with `m` registers.
+pub(crate) fn bounds(
+    details: &EstimateDetails,
+    m: usize,
+    confidence: Confidence,
+) -> EstimateBounds {
+    let estimate = details.estimate;
+    let spread = confidence.sigmas() * standard_deviation(details, m);
+    let nonzero = (m - details.zero_registers) as f64;
+    EstimateBounds {
+        lower: (estimate - spread).max(nonzero),
+        estimate,
+        upper: estimate + spread,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bounds, standard_deviation, Confidence, EstimateBounds, Exceeds};
+    use simulation::{splitmix64, Sim};
+    use {piecewise_details, HyperLogLog};
+
+    /// The fraction of `trials` simulated sketches whose interval contains the
+    /// true count, at each of `checkpoints`.
+    fn coverage(p: u8, checkpoints: &[usize], trials: usize, confidence: Confidence) -> Vec<f64> {
+        let m = 1usize << p;
+        let mut hits = vec![0usize; checkpoints.len()];
+        let mut rng = 0xb0_0d5 + u64::from(p);
+        for _ in 0..trials {
+            let mut sim = Sim::new(p);
+            for (hit, &n) in hits.iter_mut().zip(checkpoints) {
+                sim.advance_to(n, &mut rng);
+                let b = bounds(&piecewise_details(m, sim.sum, sim.zeros), m, confidence);
+                assert!(b.lower <= b.estimate && b.estimate <= b.upper);
+                assert!(b.lower >= (m - sim.zeros) as f64);
+                if b.lower <= n as f64 && n as f64 <= b.upper {
+                    *hit += 1;
+                }
+            }
+        }
+        hits.into_iter().map(|h| h as f64 / trials as f64).collect()
+    }
+
+    #[test]
+    fn intervals_cover_true_count() {
+        // Linear counting at light and heavy loads, then HyperLogLog with and
+        // without bias correction. Bias correction shrinks the error below
+        // 1.04 / √m, so there the intervals only have to be wide enough.
+        let checkpoints = [(100, true), (800, true), (3_000, false), (20_000, true)];
+        let ns: Vec<usize> = checkpoints.iter().map(|&(n, _)| n).collect();
+        let trials = 2_000;
+        for &(confidence, expected) in &[
+            (Confidence::P68, 0.683),
+            (Confidence::P95, 0.954),
+            (Confidence::P99, 0.997),
+        ] {
+            let rates = coverage(10, &ns, trials, confidence);
+            for (&(n, exact), &rate) in checkpoints.iter().zip(&rates) {
+                let in_range = if exact {
+                    (rate - expected).abs() < 0.03
+                } else {
+                    rate > expected - 0.03
+                };
+                assert!(
+                    in_range,
+                    "{:?} at n = {}: {} of trials covered",
+                    confidence, n, rate
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bounds_of_small_sketches() {
+        let mut h = HyperLogLog::new(1 << 12);
+        let zero = EstimateBounds {
+            lower: 0.,
+            estimate: 0.,
+            upper: 0.,
+        };
+        assert_eq!(h.count_with_bounds(Confidence::P99), zero);
+
+        h.add("one");
+        let b = h.count_with_bounds(Confidence::P99);
+        assert_eq!((b.lower, b.estimate), (1., h.count()));
+        assert!(b.upper < 1.1);
+
+        h.extend(0..100_000u64);
+        let (b68, b95, b99) = (
+            h.count_with_bounds(Confidence::P68),
+            h.count_with_bounds(Confidence::P95),
+            h.count_with_bounds(Confidence::P99),
+        );
+        assert!(b99.lower < b95.lower && b95.lower < b68.lower && b68.lower < b68.estimate);
+        assert!(b68.estimate < b68.upper && b68.upper < b95.upper && b95.upper < b99.upper);
+        let half_width = b95.upper - b95.estimate;
+        assert!((half_width - 2. * h.count() * h.error_estimate()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn relative_error_tracks_observed_error() {
+        let p = 10;
+        let m = 1usize << p;
+        let checkpoints = [10, 100, 800, 3_000, 20_000, 200_000];
+        let trials = 1_000;
+        let mut squared = vec![0.; checkpoints.len()];
+        let mut reported = vec![0.; checkpoints.len()];
+        let mut rng = 0x5eed;
+        for _ in 0..trials {
+            let mut sim = Sim::new(p);
+            for (i, &n) in checkpoints.iter().enumerate() {
+                sim.advance_to(n, &mut rng);
+                let details = piecewise_details(m, sim.sum, sim.zeros);
+                let error = (details.estimate - n as f64) / n as f64;
+                squared[i] += error * error;
+                reported[i] += standard_deviation(&details, m) / details.estimate;
+            }
+        }
+        for (i, &n) in checkpoints.iter().enumerate() {
+            let observed = (squared[i] / trials as f64).sqrt();
+            let reported = reported[i] / trials as f64;
+            // Bias correction leaves the estimate a little better than
+            // reported around n = 3m.
+            assert!(
+                observed / reported > 0.8 && observed / reported < 1.15,
+                "n = {}: observed {}, reported {}",
+                n,
+                observed,
+                reported
+            );
+        }
+    }
+
+    #[test]
+    fn relative_error_edge_cases() {
+        let mut h = HyperLogLog::new(1 << 12);
+        assert_eq!(h.relative_error(), 0.);
+        assert_eq!(HyperLogLog::standard_error(1 << 12), h.error_estimate());
+        h.extend(0..100u64);
+        // Linear counting's error tends to 1 / √(2m) at light loads.
+        assert!(h.relative_error() < h.error_estimate() * 0.7);
+        h.extend(0..1_000_000u64);
+        assert_eq!(h.relative_error(), h.error_estimate());
+
+        // Every register at its maximum rank: far past the reliable range.
+        let mut saturated = HyperLogLog::new(16);
+        for i in 0..16u64 {
+            saturated.add_hashed(i << 60);
+        }
+        assert_eq!(saturated.relative_error(), f64::INFINITY);
+        let b = saturated.count_with_bounds(Confidence::P68);
+        assert_eq!((b.lower, b.upper), (16., f64::INFINITY));
+    }
+
+    #[test]
+    fn deterministic_bound_never_passes_the_true_count() {
+        let mut rng = 92;
+        for trial in 0..40 {
+            let p = 4 + (trial % 9) as u8;
+            let mut h = HyperLogLog::new_seeded(1 << p, 92);
+            // Items drawn with repeats from a pool, so the true count lags
+            // the number added.
+            let pool = 1 + splitmix64(&mut rng) % 20_000;
+            let mut seen = std::collections::HashSet::new();
+            for _ in 0..splitmix64(&mut rng) % 30_000 {
+                let item = splitmix64(&mut rng) % pool;
+                h.add(item);
+                seen.insert(item);
+                let exact = seen.len() as u64;
+                let lower = h.count_lower_bound();
+                assert!(lower <= exact, "{} > {} at p = {}", lower, exact, p);
+                if !lower.is_multiple_of(97) {
+                    continue;
+                }
+                let nonzero = h.registers().filter(|&r| r != 0).count() as u64;
+                assert_eq!(lower, nonzero);
+                assert!(h.count_probable_lower_bound() >= lower);
+                assert!(h.count_upper_bound() >= h.count_probable_lower_bound());
+                assert!(h.count_upper_bound() as f64 >= h.count());
+                for &threshold in &[exact.saturating_sub(1), exact, lower, lower / 2] {
+                    match h.exceeds(threshold) {
+                        Exceeds::Definitely => assert!(exact > threshold),
+                        Exceeds::Probably => assert!(h.count_u64() > threshold),
+                        Exceeds::No => assert!(h.count_u64() <= threshold),
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exceeds_short_circuits_on_registers() {
+        let mut h = HyperLogLog::new(1 << 10);
+        assert_eq!(h.exceeds(0), Exceeds::No);
+        assert_eq!((h.count_lower_bound(), h.count_upper_bound()), (0, 0));
+        h.add(1u64);
+        assert_eq!(h.exceeds(0), Exceeds::Definitely);
+        assert_eq!(h.exceeds(1), Exceeds::No);
+
+        h.extend(0..100_000u64);
+        // Every register is set, so no more than 1024 items are certain.
+        assert_eq!(h.count_lower_bound(), 1 << 10);
+        assert_eq!(h.exceeds(1_000), Exceeds::Definitely);
+        assert_eq!(h.exceeds(50_000), Exceeds::Probably);
+        assert_eq!(h.exceeds(200_000), Exceeds::No);
+        assert!(h.count_probable_lower_bound() > 85_000);
+        assert!(h.count_upper_bound() < 115_000);
+
+        // Merging and clearing keep the bound in step with the registers.
+        let mut small = HyperLogLog::new(1 << 10);
+        small.add(2u64);
+        small.merge(&h).unwrap();
+        assert_eq!(small.count_lower_bound(), 1 << 10);
+        small.clear();
+        assert_eq!(small.count_lower_bound(), 0);
+    }
+}
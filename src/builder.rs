@@ -0,0 +1,257 @@
+//! A builder for sketches configured in several ways at once.
+
+use core::hash::BuildHasher;
+
+use error::BuildError;
+use {
+    encoding, precision_for_error, BuiltinEstimator, DefaultBuildHasher, HyperLogLog, SeededState,
+    DEFAULT_REGISTERS, MIN_PRECISION,
+};
+
+/// Configures and builds a [`HyperLogLog`].
+///
+/// Unset options take the defaults [`HyperLogLog::new`] and
+/// [`HyperLogLog::default`] use: 2^14 registers, [`DefaultBuildHasher`] and a
+/// sparse start where the precision allows it. The builder is `Clone`, so one
+/// configuration can stamp out many identical, mergeable sketches.
+#[derive(Debug, Clone)]
+pub struct HyperLogLogBuilder<S = DefaultBuildHasher> {
+    precision: Option<u8>,
+    error_rate: Option<f64>,
+    sparse: Option<bool>,
+    monotonic: bool,
+    recent_cache: usize,
+    estimator: BuiltinEstimator,
+    build_hasher: S,
+}
+
+impl HyperLogLogBuilder {
+    /// A builder with every option at its default.
+    pub fn new() -> HyperLogLogBuilder {
+        HyperLogLogBuilder {
+            precision: None,
+            error_rate: None,
+            sparse: None,
+            monotonic: false,
+            recent_cache: 0,
+            estimator: BuiltinEstimator::default(),
+            build_hasher: DefaultBuildHasher::default(),
+        }
+    }
+}
+
+impl Default for HyperLogLogBuilder {
+    fn default() -> HyperLogLogBuilder {
+        HyperLogLogBuilder::new()
+    }
+}
+
+impl<S> HyperLogLogBuilder<S> {
+    /// Uses `2^precision` registers. Conflicts with
+    /// [`error_rate`](HyperLogLogBuilder::error_rate).
+    pub fn precision(mut self, precision: u8) -> HyperLogLogBuilder<S> {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// Uses the fewest registers whose standard error is at most `epsilon`, as
+    /// [`HyperLogLog::with_error`] does. Conflicts with
+    /// [`precision`](HyperLogLogBuilder::precision).
+    pub fn error_rate(mut self, epsilon: f64) -> HyperLogLogBuilder<S> {
+        self.error_rate = Some(epsilon);
+        self
+    }
+
+    /// Starts sparse (the default) or dense.
+    pub fn sparse(mut self, sparse: bool) -> HyperLogLogBuilder<S> {
+        self.sparse = Some(sparse);
+        self
+    }
+
+    /// Makes the sketch's count never decrease; see
+    /// [`HyperLogLog::set_monotonic`].
+    pub fn monotonic(mut self, monotonic: bool) -> HyperLogLogBuilder<S> {
+        self.monotonic = monotonic;
+        self
+    }
+
+    /// Puts a cache of about `capacity` recent hashes in front of the
+    /// registers; see [`HyperLogLog::set_recent_cache`].
+    pub fn recent_cache(mut self, capacity: usize) -> HyperLogLogBuilder<S> {
+        self.recent_cache = capacity;
+        self
+    }
+
+    /// Makes [`count`](HyperLogLog::count) estimate with `estimator`; see
+    /// [`HyperLogLog::set_estimator`].
+    pub fn estimator<E: Into<BuiltinEstimator>>(mut self, estimator: E) -> HyperLogLogBuilder<S> {
+        self.estimator = estimator.into();
+        self
+    }
+
+    /// Hashes items with `build_hasher`, replacing any hasher or seed set
+    /// before.
+    pub fn hasher<T>(self, build_hasher: T) -> HyperLogLogBuilder<T> {
+        HyperLogLogBuilder {
+            precision: self.precision,
+            error_rate: self.error_rate,
+            sparse: self.sparse,
+            monotonic: self.monotonic,
+            recent_cache: self.recent_cache,
+            estimator: self.estimator,
+            build_hasher,
+        }
+    }
+
+    /// Hashes items with SipHash-2-4 keyed by `seed`, as
+    /// [`HyperLogLog::new_seeded`] does, replacing any hasher or seed set
+    /// before.
+    pub fn seed(self, seed: u64) -> HyperLogLogBuilder<SeededState> {
+        self.hasher(SeededState::new(seed))
+    }
+}
+
+impl<S: BuildHasher> HyperLogLogBuilder<S> {
+    /// Builds the sketch, or explains which options are unusable or conflict.
+    pub fn build(self) -> Result<HyperLogLog<S>, BuildError> {
+        let m = match (self.precision, self.error_rate) {
+            (Some(precision), Some(error_rate)) => {
+                return Err(BuildError::PrecisionAndErrorRate {
+                    precision,
+                    error_rate,
+                })
+            }
+            (Some(p), None) => {
+                if !(MIN_PRECISION..=encoding::MAX_PRECISION).contains(&p) {
+                    return Err(BuildError::InvalidPrecision(p));
+                }
+                1usize
+                    .checked_shl(u32::from(p))
+                    .ok_or(BuildError::InvalidPrecision(p))?
+            }
+            (None, Some(epsilon)) => 1 << precision_for_error(epsilon)?,
+            (None, None) => DEFAULT_REGISTERS,
+        };
+        let mut h = HyperLogLog::try_with_hasher(m, self.build_hasher)?;
+        if self.sparse == Some(false) {
+            h.set_sparse_threshold(0);
+        }
+        h.set_monotonic(self.monotonic);
+        h.set_recent_cache(self.recent_cache);
+        h.set_estimator(self.estimator);
+        Ok(h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::BuildHasherDefault;
+
+    use super::HyperLogLogBuilder;
+    use sip::SipHasher24;
+    use {BuildError, BuiltinEstimator, ErtlEstimator, HyperLogLog, SeededState};
+
+    fn registers<S>(h: &HyperLogLog<S>) -> Vec<u8> {
+        h.registers().collect()
+    }
+
+    #[test]
+    fn defaults_match_new() {
+        let mut built = HyperLogLogBuilder::new().build().unwrap();
+        let mut new = HyperLogLog::new(1 << 14);
+        assert_eq!(built.precision(), 14);
+        assert!(built.is_sparse());
+        built.extend(0..1_000u64);
+        new.extend(0..1_000u64);
+        assert_eq!(registers(&built), registers(&new));
+    }
+
+    #[test]
+    fn each_option() {
+        let h = HyperLogLog::builder().precision(10).build().unwrap();
+        assert_eq!(h.num_registers(), 1 << 10);
+
+        let h = HyperLogLog::builder().error_rate(0.01).build().unwrap();
+        assert_eq!(
+            h.precision(),
+            HyperLogLog::with_error(0.01).unwrap().precision()
+        );
+
+        let h = HyperLogLog::builder().sparse(false).build().unwrap();
+        assert!(!h.is_sparse());
+        let h = HyperLogLog::builder().sparse(true).build().unwrap();
+        assert!(h.is_sparse());
+
+        let h = HyperLogLog::builder().build().unwrap();
+        assert_eq!(h.estimator(), BuiltinEstimator::BiasCorrected);
+        let h = HyperLogLog::builder()
+            .estimator(ErtlEstimator)
+            .build()
+            .unwrap();
+        assert_eq!(h.estimator(), BuiltinEstimator::Ertl);
+
+        let mut seeded = HyperLogLog::builder()
+            .seed(7)
+            .precision(12)
+            .build()
+            .unwrap();
+        let mut expected = HyperLogLog::new_seeded(1 << 12, 7);
+        seeded.extend(0..1_000u64);
+        expected.extend(0..1_000u64);
+        assert_eq!(registers(&seeded), registers(&expected));
+
+        let build = BuildHasherDefault::<SipHasher24>::default();
+        let mut custom = HyperLogLog::builder()
+            .seed(7)
+            .hasher(build.clone())
+            .precision(12)
+            .build()
+            .unwrap();
+        let mut expected = HyperLogLog::with_hasher(1 << 12, build);
+        custom.extend(0..1_000u64);
+        expected.extend(0..1_000u64);
+        assert_eq!(registers(&custom), registers(&expected));
+    }
+
+    #[test]
+    fn clones_build_mergeable_sketches() {
+        let config = HyperLogLog::builder().seed(3).precision(10).sparse(false);
+        let mut sketches: Vec<HyperLogLog<SeededState>> =
+            (0..4).map(|_| config.clone().build().unwrap()).collect();
+        for (i, h) in sketches.iter_mut().enumerate() {
+            h.extend(i as u64 * 100..(i as u64 + 1) * 100);
+        }
+        let mut all = config.build().unwrap();
+        all.extend(0..400u64);
+        let mut merged = sketches.pop().unwrap();
+        for h in &sketches {
+            merged.merge(h).unwrap();
+        }
+        assert_eq!(registers(&merged), registers(&all));
+    }
+
+    #[test]
+    fn rejects_conflicting_and_invalid_options() {
+        assert_eq!(
+            HyperLogLog::builder()
+                .precision(12)
+                .error_rate(0.01)
+                .build()
+                .unwrap_err(),
+            BuildError::PrecisionAndErrorRate {
+                precision: 12,
+                error_rate: 0.01
+            }
+        );
+        for &p in &[0, 3, 25, 33, 200] {
+            assert_eq!(
+                HyperLogLog::builder().precision(p).build().unwrap_err(),
+                BuildError::InvalidPrecision(p)
+            );
+        }
+        assert_eq!(
+            HyperLogLog::builder().error_rate(0.).build().unwrap_err(),
+            BuildError::InvalidErrorRate(0.)
+        );
+    }
+}
@@ -0,0 +1,71 @@
+//! A cached cardinality estimate that `count(&self)` can fill in.
+//!
+//! The estimate is stored as the bits of an `f64` in an atomic, rather than in
+//! a `Cell`, so that sketches stay `Sync`. On targets without 64-bit atomics
+//! nothing is cached and every `count` recomputes.
+
+#[cfg(test)]
+use core::sync::atomic::AtomicUsize;
+#[cfg(target_has_atomic = "64")]
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Marks an empty cache. It is a NaN, which no estimate ever is.
+#[cfg(target_has_atomic = "64")]
+const EMPTY: u64 = u64::MAX;
+
+pub(crate) struct EstimateCache {
+    #[cfg(target_has_atomic = "64")]
+    bits: AtomicU64,
+    /// How many times the estimate was actually computed.
+    #[cfg(test)]
+    pub(crate) misses: AtomicUsize,
+}
+
+impl EstimateCache {
+    pub(crate) fn new() -> EstimateCache {
+        EstimateCache {
+            #[cfg(target_has_atomic = "64")]
+            bits: AtomicU64::new(EMPTY),
+            #[cfg(test)]
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the cached estimate, computing and storing it if there is none.
+    pub(crate) fn get_or_compute<F: FnOnce() -> f64>(&self, compute: F) -> f64 {
+        #[cfg(target_has_atomic = "64")]
+        {
+            let bits = self.bits.load(Ordering::Relaxed);
+            if bits != EMPTY {
+                return f64::from_bits(bits);
+            }
+        }
+        #[cfg(test)]
+        self.misses
+            .fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+        let estimate = compute();
+        #[cfg(target_has_atomic = "64")]
+        self.bits.store(estimate.to_bits(), Ordering::Relaxed);
+        estimate
+    }
+
+    /// Forgets the cached estimate after the registers changed.
+    pub(crate) fn invalidate(&mut self) {
+        #[cfg(target_has_atomic = "64")]
+        {
+            *self.bits.get_mut() = EMPTY;
+        }
+    }
+}
+
+/// A clone keeps the cached estimate, which is still valid for the same registers.
+impl Clone for EstimateCache {
+    fn clone(&self) -> EstimateCache {
+        EstimateCache {
+            #[cfg(target_has_atomic = "64")]
+            bits: AtomicU64::new(self.bits.load(Ordering::Relaxed)),
+            #[cfg(test)]
+            misses: AtomicUsize::new(0),
+        }
+    }
+}
@@ -0,0 +1,469 @@
+//! Reading and writing the states ClickHouse's `uniqHLL12` aggregate function
+//! stores.
+//!
+//! `uniqHLL12` keeps up to 16 distinct keys exactly, then switches to a
+//! sketch of 4096 registers over 32-bit hashes with the original paper's
+//! estimator, the one [`HyperLogLog32::count`] computes. A state, as
+//! `uniqHLL12State` returns it, is a byte saying which (0 small, 1 large)
+//! followed by:
+//!
+//! - small: the number of keys as a LEB128 varint, then each key;
+//! - large: the registers in 5 bits each, least significant bits first,
+//!   2560 bytes in all.
+//!
+//! A key is the value itself for integer columns and the CityHash64 of the
+//! value for string columns. ClickHouse hashes it with `intHash32`, Thomas
+//! Wang's 64-to-32-bit mix, and takes the register index from the low 12
+//! bits and the rank from the trailing zeros of the rest, while
+//! [`HyperLogLog32`] uses the top bits and leading zeros. [`ClickHouseState`]
+//! rearranges the bits of the ClickHouse hash so the two agree register for
+//! register.
+//!
+//! Small states of columns narrower than 64 bits store narrower keys, and
+//! aren't supported: only states of 64-bit integer and string columns are.
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hasher};
+
+use error::ClickHouseCodecError;
+use HyperLogLog32;
+
+const PRECISION: u8 = 12;
+const REGISTERS: usize = 1 << PRECISION;
+const RANK_BITS: usize = 5;
+const LARGE_LEN: usize = REGISTERS * RANK_BITS / 8;
+const SMALL: u8 = 0;
+const LARGE: u8 = 1;
+/// The most keys a small state holds before ClickHouse switches to large.
+const SMALL_SET_SIZE: u64 = 16;
+const KEY_LEN: usize = 8;
+/// The largest rank ClickHouse gives a register: the 20 bits above the
+/// index all zero.
+const MAX_RANK: u8 = 21;
+
+/// Thomas Wang's 64-bit mix, as ClickHouse's `intHash32` computes it before
+/// truncating.
+fn wang64(key: u64) -> u64 {
+    let mut key = (!key).wrapping_add(key << 18);
+    key ^= key.rotate_right(31);
+    key = key.wrapping_mul(21);
+    key ^= key.rotate_right(11);
+    key = key.wrapping_add(key << 6);
+    key ^ key.rotate_right(22)
+}
+
+/// ClickHouse's `intHash32` with no salt.
+fn int_hash32(key: u64) -> u32 {
+    wang64(key) as u32
+}
+
+/// Moves the register index from the low 12 bits of a ClickHouse hash to the
+/// top, and reverses the remaining 20 bits so their trailing zeros become
+/// leading zeros.
+fn sketch_hash(clickhouse: u32) -> u32 {
+    let index = clickhouse & (REGISTERS as u32 - 1);
+    index << (32 - PRECISION) | (clickhouse >> PRECISION).reverse_bits() >> PRECISION
+}
+
+/// A `BuildHasher` that hashes like ClickHouse's `uniqHLL12`, for sketches
+/// read with [`HyperLogLog32::from_clickhouse_hll12`].
+///
+/// An item that writes a single integer, as Rust's integer types do, hashes
+/// exactly as ClickHouse hashes that integer as a key; signed integers are
+/// sign-extended, as ClickHouse extends them. This crate doesn't implement
+/// CityHash64, so add strings by their CityHash64 with
+/// [`HyperLogLog32::add_key`] to match ClickHouse. Other items still count
+/// correctly, just not compatibly.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClickHouseState;
+
+/// The hasher built by [`ClickHouseState`].
+#[derive(Debug, Clone, Default)]
+pub struct ClickHouseHasher {
+    key: u64,
+    words: usize,
+}
+
+impl Hasher for ClickHouseHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(8) {
+            let mut word = [0u8; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            self.write_u64(u64::from_le_bytes(word));
+        }
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.write_u64(n.into());
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write_u64(n.into());
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write_u64(n.into());
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        // One integer is the key itself; anything longer is mixed into one.
+        self.key = if self.words == 0 {
+            n
+        } else {
+            wang64(self.key) ^ n
+        };
+        self.words += 1;
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i8(&mut self, n: i8) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i16(&mut self, n: i16) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i32(&mut self, n: i32) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_i64(&mut self, n: i64) {
+        self.write_u64(n as u64);
+    }
+
+    fn write_isize(&mut self, n: isize) {
+        self.write_u64(n as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        u64::from(sketch_hash(int_hash32(self.key)))
+    }
+}
+
+impl BuildHasher for ClickHouseState {
+    type Hasher = ClickHouseHasher;
+
+    fn build_hasher(&self) -> ClickHouseHasher {
+        ClickHouseHasher::default()
+    }
+}
+
+/// Reads the LEB128 varint at the start of `bytes`, which begin `offset`
+/// bytes into the state, returning it and its length.
+fn read_varint(bytes: &[u8], offset: usize) -> Result<(u64, usize), ClickHouseCodecError> {
+    let mut value = 0u64;
+    // Ten bytes hold 64 bits; ClickHouse writes at most nine.
+    for (i, &byte) in bytes.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(ClickHouseCodecError::Truncated {
+        expected: offset + bytes.len() + 1,
+        actual: offset + bytes.len(),
+    })
+}
+
+fn check_len(expected: usize, actual: usize) -> Result<(), ClickHouseCodecError> {
+    if actual < expected {
+        return Err(ClickHouseCodecError::Truncated { expected, actual });
+    }
+    if actual > expected {
+        return Err(ClickHouseCodecError::TrailingBytes { expected, actual });
+    }
+    Ok(())
+}
+
+fn decode_large(bytes: &[u8]) -> Result<Vec<u8>, ClickHouseCodecError> {
+    check_len(1 + LARGE_LEN, bytes.len())?;
+    let payload = &bytes[1..];
+    let mut registers = Vec::with_capacity(REGISTERS);
+    for index in 0..REGISTERS {
+        let bit = index * RANK_BITS;
+        let next = payload.get(bit / 8 + 1).cloned().unwrap_or(0);
+        let word = u16::from(payload[bit / 8]) | u16::from(next) << 8;
+        let value = ((word >> (bit % 8)) & 0x1f) as u8;
+        if value > MAX_RANK {
+            return Err(ClickHouseCodecError::InvalidRegister { index, value });
+        }
+        registers.push(value);
+    }
+    Ok(registers)
+}
+
+fn encode_large(registers: &[u8]) -> Vec<u8> {
+    // One byte of slack lets every register write two bytes.
+    let mut out = vec![0u8; 1 + LARGE_LEN + 1];
+    out[0] = LARGE;
+    for (i, &r) in registers.iter().enumerate() {
+        let bit = 8 + i * RANK_BITS;
+        let word = u16::from(r) << (bit % 8);
+        out[bit / 8] |= word as u8;
+        out[bit / 8 + 1] |= (word >> 8) as u8;
+    }
+    out.truncate(1 + LARGE_LEN);
+    out
+}
+
+impl HyperLogLog32<ClickHouseState> {
+    /// Creates an empty sketch in `uniqHLL12`'s layout: 4096 registers,
+    /// hashing like ClickHouse.
+    pub fn new_clickhouse_hll12() -> HyperLogLog32<ClickHouseState> {
+        HyperLogLog32::with_hasher(REGISTERS, ClickHouseState)
+    }
+
+    /// Adds a key the way `uniqHLL12` does: an integer column's value, or
+    /// the CityHash64 of a string column's.
+    pub fn add_key(&mut self, key: u64) {
+        self.add_hashed(sketch_hash(int_hash32(key)));
+    }
+
+    /// Reads a `uniqHLL12` state of a 64-bit integer or string column, as
+    /// `uniqHLL12State` returns it.
+    ///
+    /// The keys of a small state are added to the registers, so the sketch
+    /// estimates their number rather than knowing it exactly as ClickHouse
+    /// does; with at most 16 keys in 4096 registers, the estimate is within
+    /// an item.
+    pub fn from_clickhouse_hll12(
+        bytes: &[u8],
+    ) -> Result<HyperLogLog32<ClickHouseState>, ClickHouseCodecError> {
+        let layout = *bytes.first().ok_or(ClickHouseCodecError::Truncated {
+            expected: 1,
+            actual: 0,
+        })?;
+        match layout {
+            SMALL => {
+                let (keys, len) = read_varint(&bytes[1..], 1)?;
+                if keys > SMALL_SET_SIZE {
+                    return Err(ClickHouseCodecError::TooManyKeys(keys));
+                }
+                let start = 1 + len;
+                check_len(start + keys as usize * KEY_LEN, bytes.len())?;
+                let mut h = HyperLogLog32::new_clickhouse_hll12();
+                for key in bytes[start..].chunks(KEY_LEN) {
+                    let mut word = [0u8; 8];
+                    word.copy_from_slice(key);
+                    h.add_key(u64::from_le_bytes(word));
+                }
+                Ok(h)
+            }
+            LARGE => Ok(HyperLogLog32::from_dense(
+                PRECISION,
+                decode_large(bytes)?,
+                ClickHouseState,
+            )),
+            other => Err(ClickHouseCodecError::UnknownLayout(other)),
+        }
+    }
+
+    /// Writes the sketch as a large `uniqHLL12` state, which ClickHouse can
+    /// merge with `uniqHLL12Merge` and finalize.
+    ///
+    /// Fails with [`ClickHouseCodecError::WrongPrecision`] unless the sketch
+    /// has 4096 registers.
+    pub fn to_clickhouse_hll12(&self) -> Result<Vec<u8>, ClickHouseCodecError> {
+        if self.precision() != PRECISION {
+            return Err(ClickHouseCodecError::WrongPrecision(self.precision()));
+        }
+        Ok(encode_large(self.registers()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::hash::BuildHasher;
+
+    use super::{int_hash32, sketch_hash, ClickHouseState, LARGE_LEN, MAX_RANK};
+    use error::ClickHouseCodecError;
+    use simulation::splitmix64;
+    use HyperLogLog32;
+
+    // No ClickHouse server is at hand, so the fixtures below are built by
+    // hand from the layout in ClickHouse's HyperLogLogCounter.h,
+    // HyperLogLogWithSmallSetOptimization.h and CompactArray.h rather than
+    // dumped from `uniqHLL12State`.
+
+    /// Keys 1, 2 and 3.
+    fn small_fixture() -> Vec<u8> {
+        let mut bytes = vec![0, 3];
+        for key in 1..=3u64 {
+            bytes.extend_from_slice(&key.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Register 0 holds 1, register 1 holds 21 and register 4095 holds 2.
+    fn large_fixture() -> Vec<u8> {
+        let mut bytes = vec![0u8; 1 + LARGE_LEN];
+        bytes[0] = 1;
+        bytes[1] = 0xa1;
+        bytes[2] = 0x02;
+        bytes[LARGE_LEN] = 0x10;
+        bytes
+    }
+
+    #[test]
+    fn rank_and_index_match_clickhouse() {
+        // HyperLogLogCounter::insert, transcribed.
+        fn clickhouse_insert(hash: u32) -> (usize, u8) {
+            let bucket = (hash & 4095) as usize;
+            let tail = hash >> 12;
+            let rank = if tail == 0 {
+                MAX_RANK
+            } else {
+                tail.trailing_zeros() as u8 + 1
+            };
+            (bucket, rank)
+        }
+        let mut rng = 81;
+        for i in 0..10_000 {
+            // Include hashes with long runs of trailing zeros.
+            let hash = (splitmix64(&mut rng) as u32) << (i % 32);
+            let mut h = HyperLogLog32::new_clickhouse_hll12();
+            h.add_hashed(sketch_hash(hash));
+            let (bucket, rank) = clickhouse_insert(hash);
+            assert_eq!(h.registers()[bucket], rank, "{:x}", hash);
+            assert_eq!(h.registers().iter().filter(|&&r| r > 0).count(), 1);
+        }
+    }
+
+    #[test]
+    fn hasher_hashes_integers_as_keys() {
+        let hash = |item: &dyn Fn(&mut HyperLogLog32<ClickHouseState>)| {
+            let mut h = HyperLogLog32::new_clickhouse_hll12();
+            item(&mut h);
+            h
+        };
+        let key = |k: u64| hash(&move |h| h.add_key(k));
+        assert_eq!(hash(&|h| h.add(7u8)), key(7));
+        assert_eq!(hash(&|h| h.add(7u32)), key(7));
+        assert_eq!(hash(&|h| h.add(7u64)), key(7));
+        // Signed keys are sign-extended to 64 bits.
+        assert_eq!(hash(&|h| h.add(-1i32)), key(u64::MAX));
+        assert_eq!(hash(&|h| h.add(-2i64)), key(u64::MAX - 1));
+        assert_eq!(
+            ClickHouseState.hash_one(5u16) as u32,
+            sketch_hash(int_hash32(5))
+        );
+        // Longer items mix every word in.
+        assert_ne!(
+            ClickHouseState.hash_one((1u64, 2u64)),
+            ClickHouseState.hash_one((1u64, 3u64))
+        );
+    }
+
+    #[test]
+    fn reads_small_fixture() {
+        let h = HyperLogLog32::from_clickhouse_hll12(&small_fixture()).unwrap();
+        let mut expected = HyperLogLog32::new_clickhouse_hll12();
+        (1..=3).for_each(|k| expected.add_key(k));
+        assert_eq!(h, expected);
+        assert_eq!(h.count().round(), 3.);
+        assert_eq!(
+            HyperLogLog32::from_clickhouse_hll12(&[0, 0])
+                .unwrap()
+                .count(),
+            0.
+        );
+    }
+
+    #[test]
+    fn reads_large_fixture() {
+        let h = HyperLogLog32::from_clickhouse_hll12(&large_fixture()).unwrap();
+        let regs = h.registers();
+        assert_eq!((regs[0], regs[1], regs[4095]), (1, 21, 2));
+        assert_eq!(regs.iter().filter(|&&r| r != 0).count(), 3);
+        assert_eq!(h.to_clickhouse_hll12().unwrap(), large_fixture());
+    }
+
+    #[test]
+    fn round_trips_large_states() {
+        for &n in &[0u64, 100, 200_000] {
+            let mut h = HyperLogLog32::new_clickhouse_hll12();
+            (0..n).for_each(|k| h.add_key(k));
+            let bytes = h.to_clickhouse_hll12().unwrap();
+            let back = HyperLogLog32::from_clickhouse_hll12(&bytes).unwrap();
+            assert_eq!(back, h);
+            assert_eq!(back.count(), h.count());
+            assert!((back.count() - n as f64).abs() < n as f64 * 0.05 + 1.);
+        }
+        assert_eq!(
+            HyperLogLog32::with_hasher(1 << 10, ClickHouseState).to_clickhouse_hll12(),
+            Err(ClickHouseCodecError::WrongPrecision(10))
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let with = |mut bytes: Vec<u8>, edit: &dyn Fn(&mut Vec<u8>)| {
+            edit(&mut bytes);
+            bytes
+        };
+        let cases: Vec<(Vec<u8>, ClickHouseCodecError)> = vec![
+            (
+                vec![],
+                ClickHouseCodecError::Truncated {
+                    expected: 1,
+                    actual: 0,
+                },
+            ),
+            (vec![2], ClickHouseCodecError::UnknownLayout(2)),
+            (
+                vec![0],
+                ClickHouseCodecError::Truncated {
+                    expected: 2,
+                    actual: 1,
+                },
+            ),
+            (vec![0, 17], ClickHouseCodecError::TooManyKeys(17)),
+            (vec![0, 0x80, 0x01], ClickHouseCodecError::TooManyKeys(128)),
+            (
+                with(small_fixture(), &|b| b.truncate(20)),
+                ClickHouseCodecError::Truncated {
+                    expected: 26,
+                    actual: 20,
+                },
+            ),
+            (
+                with(small_fixture(), &|b| b.push(0)),
+                ClickHouseCodecError::TrailingBytes {
+                    expected: 26,
+                    actual: 27,
+                },
+            ),
+            (
+                with(large_fixture(), &|b| b.truncate(100)),
+                ClickHouseCodecError::Truncated {
+                    expected: 1 + LARGE_LEN,
+                    actual: 100,
+                },
+            ),
+            (
+                with(large_fixture(), &|b| b.push(0)),
+                ClickHouseCodecError::TrailingBytes {
+                    expected: 1 + LARGE_LEN,
+                    actual: 2 + LARGE_LEN,
+                },
+            ),
+            (
+                with(large_fixture(), &|b| b[1] = 0x1f),
+                ClickHouseCodecError::InvalidRegister {
+                    index: 0,
+                    value: 31,
+                },
+            ),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(
+                HyperLogLog32::from_clickhouse_hll12(&bytes).unwrap_err(),
+                expected
+            );
+        }
+    }
+}
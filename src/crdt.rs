@@ -0,0 +1,221 @@
+//! The sketch as a grow-only CRDT for the `crdts` crate.
+//!
+//! Replicas can ship whole states, merged with [`CvRDT::merge`], or single
+//! register raises, applied with [`CmRDT::apply`]. An op is an
+//! `(index, rank)` pair, from [`add_op`](HyperLogLog::add_op) or
+//! [`add_hashed_op`](HyperLogLog::add_hashed_op): applying it raises
+//! register `index` to at least `rank`, so ops can be applied in any order
+//! and any number of times. States travel in any of the sketch's
+//! encodings, and ops as the same pairs a [`SketchDelta`](::SketchDelta)
+//! lists, which serde can serialize as they are.
+//!
+//! Replicas must agree on the precision and hasher, as for
+//! [`HyperLogLog::merge`], and on whether they are monotonic.
+//!
+//! The [number of items added](HyperLogLog::items_added) is not part of the
+//! CRDT: merges sum it, so it counts a state once for every time it is
+//! merged in, and applying an op adds nothing to it.
+
+use core::convert::TryFrom;
+use core::hash::{BuildHasher, Hash};
+use core::iter;
+
+use crdts::{CmRDT, CvRDT};
+
+use error::{MergeError, RegisterError};
+use {index_and_rank, max_rank, HyperLogLog};
+
+impl<S: BuildHasher> HyperLogLog<S> {
+    /// The op that adds `item` when applied, leaving `self` unchanged.
+    pub fn add_op<H: Hash>(&self, item: H) -> (u32, u8) {
+        self.add_hashed_op(self.build_hasher.hash_one(item))
+    }
+
+    /// The op that adds the item with this precomputed 64-bit hash when
+    /// applied; see [`add_hashed`](HyperLogLog::add_hashed).
+    pub fn add_hashed_op(&self, hash: u64) -> (u32, u8) {
+        let (index, rank) = index_and_rank(hash, self.precision);
+        // Indices are below 2^24, the most registers a sketch has.
+        (u32::try_from(index).unwrap(), rank)
+    }
+}
+
+impl<S: BuildHasher> CvRDT for HyperLogLog<S> {
+    type Validation = MergeError;
+
+    fn validate_merge(&self, other: &HyperLogLog<S>) -> Result<(), MergeError> {
+        self.check_compatible(other)
+    }
+
+    /// Merges as [`HyperLogLog::merge`] does. Panics if `validate_merge`
+    /// would fail.
+    fn merge(&mut self, other: HyperLogLog<S>) {
+        if let Err(e) = HyperLogLog::merge(self, &other) {
+            panic!("{}", e);
+        }
+    }
+}
+
+impl<S: BuildHasher> CmRDT for HyperLogLog<S> {
+    type Op = (u32, u8);
+    type Validation = RegisterError;
+
+    /// Fails if the index is past the last register, or the rank is above
+    /// any a hash could give at this precision.
+    fn validate_op(&self, &(index, rank): &(u32, u8)) -> Result<(), RegisterError> {
+        let index = index as usize;
+        let maximum = max_rank(self.precision);
+        if index >= self.num_registers() {
+            return Err(RegisterError::IndexOutOfRange {
+                index,
+                registers: self.num_registers(),
+            });
+        }
+        if rank > maximum {
+            return Err(RegisterError::InvalidRegister {
+                index,
+                value: rank,
+                maximum,
+            });
+        }
+        Ok(())
+    }
+
+    /// Raises the register to at least the rank. Panics if `validate_op`
+    /// would fail.
+    fn apply(&mut self, op: (u32, u8)) {
+        if let Err(e) = self.validate_op(&op) {
+            panic!("{}", e);
+        }
+        let (index, rank) = op;
+        if rank > 0 {
+            self.raise_registers(iter::once((index as usize, rank)));
+        }
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    extern crate proptest;
+    extern crate serde_json;
+
+    use crdts::{CmRDT, CvRDT};
+
+    use self::proptest::collection::vec;
+    use self::proptest::prelude::*;
+    use self::proptest::test_runner::{Config, RngAlgorithm, TestRng, TestRunner};
+    use error::{MergeError, RegisterError};
+    use {HyperLogLog, SeededState};
+
+    fn runner() -> TestRunner {
+        let mut seed = [0u8; 32];
+        seed[0] = 93;
+        let config = Config {
+            cases: 100,
+            failure_persistence: None,
+            ..Config::default()
+        };
+        TestRunner::new_with_rng(config, TestRng::from_seed(RngAlgorithm::ChaCha, &seed))
+    }
+
+    /// One step on three replicas: `0` adds an item at replica `a`, `1`
+    /// sends replica `b`'s state to `a`, and `2` sends `a` every op `b` has
+    /// made so far.
+    type Step = (u8, usize, usize, u64);
+
+    #[test]
+    fn replicas_converge_under_any_interleaving() {
+        let step = (0..3u8, 0..3usize, 0..3usize, any::<u64>());
+        let strategy = (vec(step, 0..600), any::<bool>());
+        runner()
+            .run(&strategy, |(steps, monotonic): (Vec<Step>, bool)| {
+                let mut fresh = HyperLogLog::new_seeded(1 << 8, 93);
+                fresh.set_monotonic(monotonic);
+                let mut replicas = vec![fresh.clone(); 3];
+                // Each replica's ops, serialized as they would be sent.
+                let mut sent: Vec<Vec<String>> = vec![Vec::new(); 3];
+                let mut everything = fresh;
+                for (kind, a, b, hash) in steps {
+                    match kind {
+                        0 => {
+                            let op = replicas[a].add_hashed_op(hash);
+                            replicas[a].apply(op);
+                            sent[a].push(serde_json::to_string(&op).unwrap());
+                            everything.add_hashed(hash);
+                        }
+                        1 => {
+                            let bytes = replicas[b].to_bytes();
+                            let state = HyperLogLog::<SeededState>::from_bytes(&bytes).unwrap();
+                            replicas[a].validate_merge(&state).unwrap();
+                            CvRDT::merge(&mut replicas[a], state);
+                        }
+                        _ => {
+                            for json in &sent[b] {
+                                let op: (u32, u8) = serde_json::from_str(json).unwrap();
+                                replicas[a].validate_op(&op).unwrap();
+                                replicas[a].apply(op);
+                            }
+                        }
+                    }
+                }
+
+                // A final exchange of states: the first replica takes in
+                // the others, and they take it back.
+                for i in 1..3 {
+                    let other = replicas[i].clone();
+                    CvRDT::merge(&mut replicas[0], other);
+                }
+                for i in 1..3 {
+                    let first = replicas[0].clone();
+                    CvRDT::merge(&mut replicas[i], first);
+                }
+                for replica in &replicas {
+                    prop_assert!(replica.registers().eq(everything.registers()));
+                    prop_assert_eq!(replica, &replicas[0]);
+                    prop_assert_eq!(replica.count(), replicas[0].count());
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn validates_merges_and_ops() {
+        let mut h = HyperLogLog::new(1 << 4);
+        assert_eq!(
+            h.validate_merge(&HyperLogLog::new(1 << 5)),
+            Err(MergeError::PrecisionMismatch { left: 4, right: 5 })
+        );
+        assert_eq!(
+            h.validate_op(&(16, 1)),
+            Err(RegisterError::IndexOutOfRange {
+                index: 16,
+                registers: 16
+            })
+        );
+        assert_eq!(
+            h.validate_op(&(3, 62)),
+            Err(RegisterError::InvalidRegister {
+                index: 3,
+                value: 62,
+                maximum: 61
+            })
+        );
+        assert_eq!(h.validate_op(&(15, 0)), Ok(()));
+        h.apply((15, 0));
+        assert!(h.is_empty());
+
+        let op = h.add_op("item");
+        h.apply(op);
+        h.apply(op);
+        let mut added = HyperLogLog::new(1 << 4);
+        added.add("item");
+        assert_eq!(h, added);
+    }
+
+    #[test]
+    #[should_panic(expected = "register index 16 is out of range for 16 registers")]
+    fn rejects_invalid_ops() {
+        HyperLogLog::new(1 << 4).apply((16, 1));
+    }
+}
@@ -0,0 +1,631 @@
+//! Reading and writing Apache DataSketches HLL sketch images.
+//!
+//! An image starts with a preamble of 2, 3 or 10 little-endian 32-bit words,
+//! depending on the sketch's mode. The first word holds the preamble length,
+//! serialization version 1, family 7 and `lgK`, the log of the register
+//! count (4 to 21); the second holds the size of the coupon array, the flags,
+//! a count or minimum, and the mode byte, whose low two bits give the current
+//! mode and next two the register type (`HLL_4`, `HLL_6` or `HLL_8`).
+//!
+//! Small sketches are in `LIST` or `SET` mode: a list or hash table of 32-bit
+//! coupons, each holding a 26-bit register address and a 6-bit value. Large
+//! ones are in `HLL` mode, with a 40-byte preamble followed by the registers.
+//! Only `HLL_8`, one byte per register, is read in `HLL` mode; `HLL_4` and
+//! `HLL_6` pack the registers more tightly and are rejected. Unions serialize
+//! their gadget as an `HLL_8` image flagged for its derived fields to be
+//! rebuilt, which is read like any other since only the registers are used.
+//!
+//! DataSketches hashes items with 128-bit MurmurHash3 and seed 9001, taking
+//! the register index from the low bits of the first half and the value from
+//! the leading zeros of the second. Because the index depends on `lgK`,
+//! [`DataSketchesState`] records it.
+
+use alloc::vec::Vec;
+use core::cmp;
+use core::hash::{BuildHasher, Hasher};
+
+use error::DataSketchesCodecError;
+use murmur3::{le64, murmur3_x64_128};
+use {max_rank, HyperLogLog};
+
+/// The seed DataSketches passes to MurmurHash3 by default.
+const SEED: u64 = 9001;
+const SERIAL_VERSION: u8 = 1;
+const FAMILY: u8 = 7;
+const MIN_LG_K: u8 = 4;
+const MAX_LG_K: u8 = 21;
+
+const LIST: u8 = 0;
+const SET: u8 = 1;
+const HLL: u8 = 2;
+const HLL_8: u8 = 2;
+
+const LIST_PREAMBLE_INTS: u8 = 2;
+const SET_PREAMBLE_INTS: u8 = 3;
+const HLL_PREAMBLE_INTS: u8 = 10;
+const HLL_REGISTERS_START: usize = 40;
+
+const EMPTY_FLAG: u8 = 4;
+const COMPACT_FLAG: u8 = 8;
+/// Set when the HIP estimate is unusable, as it is for images written here.
+const OUT_OF_ORDER_FLAG: u8 = 16;
+
+/// Coupons hold the low 26 bits of the first hash half as the address.
+const ADDRESS_BITS: u32 = 26;
+/// The largest value a DataSketches register can hold.
+const MAX_VALUE: u8 = 63;
+/// The size of the coupon list DataSketches starts an empty sketch with.
+const EMPTY_LG_ARR: u8 = 3;
+
+fn le32(bytes: &[u8], at: usize) -> u32 {
+    le64(&bytes[at..at + 4]) as u32
+}
+
+/// Moves the register index from the low `lg_k` bits of the first hash half
+/// to the top, followed by the second half, whose leading zeros give the rank.
+///
+/// The ranks agree with DataSketches' register values except past this
+/// crate's maximum of `65 - lg_k`, which DataSketches lets reach 63; that
+/// takes over 40 leading zeros and doesn't happen in practice.
+fn sketch_hash((h1, h2): (u64, u64), lg_k: u8) -> u64 {
+    let index = h1 & ((1 << lg_k) - 1);
+    index << (64 - lg_k) | h2 >> lg_k
+}
+
+/// A `BuildHasher` that hashes like a DataSketches HLL sketch with `2^lg_k`
+/// registers, for sketches read with [`HyperLogLog::from_datasketches_bytes`].
+///
+/// DataSketches hashes an item's raw bytes (a `long` as its 8 little-endian
+/// bytes), and skips empty strings and arrays. `Hash` implementations write
+/// more than that, so add items with [`HyperLogLog::add_element`] or
+/// [`HyperLogLog::add_long`] to match DataSketches; `add` still counts
+/// correctly, just not compatibly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataSketchesState {
+    lg_k: u8,
+}
+
+impl DataSketchesState {
+    /// The hasher for sketches with `2^lg_k` registers.
+    ///
+    /// Panics unless `lg_k` is between 4 and 21, as DataSketches requires.
+    pub fn new(lg_k: u8) -> DataSketchesState {
+        assert!(
+            (MIN_LG_K..=MAX_LG_K).contains(&lg_k),
+            "DataSketches lgK must be between {} and {}, not {}",
+            MIN_LG_K,
+            MAX_LG_K,
+            lg_k
+        );
+        DataSketchesState { lg_k }
+    }
+
+    fn hash(&self, item: &[u8]) -> u64 {
+        sketch_hash(murmur3_x64_128(item, SEED), self.lg_k)
+    }
+}
+
+/// The hasher built by [`DataSketchesState`]: MurmurHash3 over every byte
+/// written.
+#[derive(Debug, Clone)]
+pub struct DataSketchesHasher {
+    state: DataSketchesState,
+    bytes: Vec<u8>,
+}
+
+impl Hasher for DataSketchesHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        self.state.hash(&self.bytes)
+    }
+}
+
+impl BuildHasher for DataSketchesState {
+    type Hasher = DataSketchesHasher;
+
+    fn build_hasher(&self) -> DataSketchesHasher {
+        DataSketchesHasher {
+            state: *self,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+fn check_len(bytes: &[u8], expected: usize) -> Result<(), DataSketchesCodecError> {
+    if bytes.len() < expected {
+        return Err(DataSketchesCodecError::Truncated {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+    Ok(())
+}
+
+/// Raises registers from `count` coupons starting at byte `start`, skipping
+/// the empty slots of a hash table.
+fn decode_coupons(
+    bytes: &[u8],
+    start: usize,
+    count: usize,
+    registers: &mut [u8],
+) -> Result<(), DataSketchesCodecError> {
+    check_len(bytes, start + 4 * count)?;
+    for i in 0..count {
+        let coupon = le32(bytes, start + 4 * i);
+        if coupon == 0 {
+            continue;
+        }
+        let value = (coupon >> ADDRESS_BITS) as u8;
+        if value == 0 {
+            return Err(DataSketchesCodecError::InvalidCoupon(coupon));
+        }
+        let slot = coupon as usize & (registers.len() - 1);
+        registers[slot] = cmp::max(registers[slot], value);
+    }
+    Ok(())
+}
+
+/// The number of coupons stored in a `LIST` or `SET` image: exactly `count`
+/// when it is compact, otherwise the whole `2^lg_arr` table.
+fn coupon_slots(compact: bool, count: usize, lg_arr: u8) -> Result<usize, DataSketchesCodecError> {
+    if compact {
+        Ok(count)
+    } else if lg_arr <= ADDRESS_BITS as u8 {
+        Ok(1 << lg_arr)
+    } else {
+        Err(DataSketchesCodecError::InvalidLgArr(lg_arr))
+    }
+}
+
+fn decode(bytes: &[u8]) -> Result<(u8, Vec<u8>), DataSketchesCodecError> {
+    check_len(bytes, 8)?;
+    let (preamble_ints, version, family, lg_k) = (bytes[0], bytes[1], bytes[2], bytes[3]);
+    let (lg_arr, flags, mode_byte) = (bytes[4], bytes[5], bytes[7]);
+    if family != FAMILY {
+        return Err(DataSketchesCodecError::WrongFamily(family));
+    }
+    if version != SERIAL_VERSION {
+        return Err(DataSketchesCodecError::UnsupportedVersion(version));
+    }
+    if !(MIN_LG_K..=MAX_LG_K).contains(&lg_k) {
+        return Err(DataSketchesCodecError::InvalidLgK(lg_k));
+    }
+    let mode = mode_byte & 3;
+    let expected_ints = match mode {
+        LIST => LIST_PREAMBLE_INTS,
+        SET => SET_PREAMBLE_INTS,
+        HLL => HLL_PREAMBLE_INTS,
+        _ => return Err(DataSketchesCodecError::UnknownMode(mode)),
+    };
+    if preamble_ints != expected_ints {
+        return Err(DataSketchesCodecError::PreambleMismatch {
+            mode,
+            preamble_ints,
+        });
+    }
+
+    let k = 1usize << lg_k;
+    let mut registers = vec![0u8; k];
+    if flags & EMPTY_FLAG != 0 {
+        return Ok((lg_k, registers));
+    }
+    let compact = flags & COMPACT_FLAG != 0;
+    match mode {
+        LIST => {
+            let slots = coupon_slots(compact, usize::from(bytes[6]), lg_arr)?;
+            decode_coupons(bytes, 8, slots, &mut registers)?;
+        }
+        SET => {
+            check_len(bytes, 12)?;
+            let slots = coupon_slots(compact, le32(bytes, 8) as usize, lg_arr)?;
+            decode_coupons(bytes, 12, slots, &mut registers)?;
+        }
+        _ => {
+            let register_type = (mode_byte >> 2) & 3;
+            if register_type != HLL_8 {
+                return Err(DataSketchesCodecError::UnsupportedType(register_type));
+            }
+            check_len(bytes, HLL_REGISTERS_START + k)?;
+            registers.copy_from_slice(&bytes[HLL_REGISTERS_START..HLL_REGISTERS_START + k]);
+            if let Some(index) = registers.iter().position(|&r| r > MAX_VALUE) {
+                return Err(DataSketchesCodecError::InvalidRegister {
+                    index,
+                    value: registers[index],
+                });
+            }
+        }
+    }
+    let cap = max_rank(lg_k);
+    for r in &mut registers {
+        *r = cmp::min(*r, cap);
+    }
+    Ok((lg_k, registers))
+}
+
+impl HyperLogLog<DataSketchesState> {
+    /// Creates an empty sketch with `2^lg_k` registers that hashes like a
+    /// DataSketches HLL sketch with the same `lgK`.
+    ///
+    /// Panics unless `lg_k` is between 4 and 21.
+    pub fn new_datasketches(lg_k: u8) -> HyperLogLog<DataSketchesState> {
+        HyperLogLog::with_hasher(1 << lg_k, DataSketchesState::new(lg_k))
+    }
+
+    /// Adds an item's bytes the way DataSketches' `update(byte[])` and
+    /// `update(String)` (with UTF-8 bytes) do; like them, skips empty items.
+    pub fn add_element(&mut self, element: &[u8]) {
+        if !element.is_empty() {
+            let hash = self.build_hasher.hash(element);
+            self.add_hashed(hash);
+        }
+    }
+
+    /// Adds a number the way DataSketches' `update(long)` does.
+    pub fn add_long(&mut self, datum: i64) {
+        let hash = self.build_hasher.hash(&datum.to_le_bytes());
+        self.add_hashed(hash);
+    }
+
+    /// Reads a DataSketches HLL sketch image, as written by `toCompactByteArray`
+    /// or `toUpdatableByteArray` in Java or `serialize_compact` in C++.
+    ///
+    /// `LIST` and `SET` images are read whatever their register type, `HLL`
+    /// images only for `HLL_8`. The stored estimator state is ignored. Values
+    /// above this crate's maximum rank of `65 - lgK` are lowered to it.
+    pub fn from_datasketches_bytes(
+        bytes: &[u8],
+    ) -> Result<HyperLogLog<DataSketchesState>, DataSketchesCodecError> {
+        let (lg_k, registers) = decode(bytes)?;
+        Ok(HyperLogLog::from_dense(
+            lg_k,
+            registers,
+            DataSketchesState::new(lg_k),
+        ))
+    }
+
+    /// Writes the sketch as a compact DataSketches `HLL_8` image, or as an
+    /// empty `LIST` image if nothing has been added.
+    ///
+    /// The image is flagged out of order, so DataSketches estimates from the
+    /// registers rather than from its HIP accumulator, which isn't tracked
+    /// here.
+    ///
+    /// Panics unless the sketch has the `2^lgK` registers its hasher was made
+    /// for; [`reduce_precision`](HyperLogLog::reduce_precision) keeps the high
+    /// bits of each register index, while DataSketches keeps the low ones.
+    pub fn to_datasketches_bytes(&self) -> Vec<u8> {
+        let lg_k = self.precision;
+        assert_eq!(
+            lg_k,
+            self.build_hasher.lg_k,
+            "the sketch has {} registers but hashes for lgK = {}",
+            self.num_registers(),
+            self.build_hasher.lg_k
+        );
+        if self.is_empty() {
+            let mode = HLL_8 << 2 | LIST;
+            return vec![
+                LIST_PREAMBLE_INTS,
+                SERIAL_VERSION,
+                FAMILY,
+                lg_k,
+                EMPTY_LG_ARR,
+                EMPTY_FLAG | COMPACT_FLAG,
+                0,
+                mode,
+            ];
+        }
+        let counts = self.rank_counts();
+        // DataSketches keeps the sum of 2^-value in two parts, split at 32,
+        // to hold on to the precision of the small terms.
+        let (mut kxq0, mut kxq1) = (0.0f64, 0.0f64);
+        let mut scale = 1.;
+        for (value, &n) in counts.iter().enumerate() {
+            if value < 32 {
+                kxq0 += n as f64 * scale;
+            } else {
+                kxq1 += n as f64 * scale;
+            }
+            scale *= 0.5;
+        }
+        let mut out = Vec::with_capacity(HLL_REGISTERS_START + self.num_registers());
+        out.extend_from_slice(&[
+            HLL_PREAMBLE_INTS,
+            SERIAL_VERSION,
+            FAMILY,
+            lg_k,
+            0,
+            COMPACT_FLAG | OUT_OF_ORDER_FLAG,
+            // For HLL_8 the current minimum is always 0...
+            0,
+            HLL_8 << 2 | HLL,
+        ]);
+        out.extend_from_slice(&0f64.to_le_bytes());
+        out.extend_from_slice(&kxq0.to_le_bytes());
+        out.extend_from_slice(&kxq1.to_le_bytes());
+        // ...and the number of registers at it is the number of zeros.
+        out.extend_from_slice(&(counts[0] as u32).to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend(self.registers());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cmp;
+    use core::hash::{BuildHasher, Hasher};
+
+    use super::{sketch_hash, DataSketchesState, HLL_REGISTERS_START};
+    use error::DataSketchesCodecError;
+    use simulation::splitmix64;
+    use {index_and_rank, max_rank, HyperLogLog};
+
+    // No JVM is at hand, so the fixtures below are built by hand from the
+    // layout in DataSketches' HllPreambleUtil rather than written by the Java
+    // library, and estimates are compared with this crate's own only.
+
+    fn preamble(
+        preamble_ints: u8,
+        lg_k: u8,
+        lg_arr: u8,
+        flags: u8,
+        byte6: u8,
+        mode: u8,
+    ) -> Vec<u8> {
+        vec![preamble_ints, 1, 7, lg_k, lg_arr, flags, byte6, mode]
+    }
+
+    /// A DataSketches coupon: a 26-bit address and a 6-bit value.
+    fn coupon(address: u32, value: u32) -> [u8; 4] {
+        (value << 26 | address).to_le_bytes()
+    }
+
+    /// An `HLL_8` image with `lgK = 10`: register 3 holds 1, register 500
+    /// holds 12 and register 1023 holds 63.
+    fn hll8_fixture() -> Vec<u8> {
+        let mut bytes = preamble(10, 10, 0, 8 | 16, 0, 2 << 2 | 2);
+        bytes.extend_from_slice(&[0; 32]);
+        let mut registers = [0u8; 1024];
+        registers[3] = 1;
+        registers[500] = 12;
+        registers[1023] = 63;
+        bytes.extend_from_slice(&registers);
+        bytes
+    }
+
+    fn registers(h: &HyperLogLog<DataSketchesState>) -> Vec<u8> {
+        h.registers().collect()
+    }
+
+    #[test]
+    fn index_and_rank_match_coupons() {
+        // The coupon DataSketches makes from a hash, and the register and
+        // value it lands on, transcribed.
+        fn coupon_slot_value(h1: u64, h2: u64, lg_k: u8) -> (usize, u8) {
+            let address = (h1 & ((1 << 26) - 1)) as u32;
+            let value = cmp::min(h2.leading_zeros(), 62) + 1;
+            let coupon = value << 26 | address;
+            ((coupon & ((1 << lg_k) - 1)) as usize, (coupon >> 26) as u8)
+        }
+        let mut rng = 12;
+        for i in 0..10_000 {
+            let lg_k = 4 + (i % 18) as u8;
+            let h1 = splitmix64(&mut rng);
+            // Include second halves with long runs of leading zeros.
+            let h2 = splitmix64(&mut rng) >> (i % 64);
+            let (slot, value) = coupon_slot_value(h1, h2, lg_k);
+            assert_eq!(
+                index_and_rank(sketch_hash((h1, h2), lg_k), lg_k),
+                (slot, cmp::min(value, max_rank(lg_k))),
+                "{:x} {:x}",
+                h1,
+                h2
+            );
+        }
+    }
+
+    #[test]
+    fn hasher_matches_add_long() {
+        let mut by_element = HyperLogLog::new_datasketches(12);
+        let state = DataSketchesState::new(12);
+        let mut by_hasher = HyperLogLog::new_datasketches(12);
+        for i in 0..1_000i64 {
+            by_element.add_long(i);
+            let mut hasher = state.build_hasher();
+            hasher.write(&i.to_le_bytes());
+            by_hasher.add_hashed(hasher.finish());
+        }
+        assert_eq!(registers(&by_hasher), registers(&by_element));
+
+        let mut h = HyperLogLog::new_datasketches(12);
+        h.add_element(b"");
+        assert!(h.is_empty());
+    }
+
+    #[test]
+    fn reads_hll8_fixture() {
+        let h = HyperLogLog::from_datasketches_bytes(&hll8_fixture()).unwrap();
+        assert_eq!(h.num_registers(), 1024);
+        let regs = registers(&h);
+        // 63 is past this crate's maximum rank for 1024 registers.
+        assert_eq!((regs[3], regs[500], regs[1023]), (1, 12, 55));
+        assert_eq!(regs.iter().filter(|&&r| r != 0).count(), 3);
+
+        let bytes = h.to_datasketches_bytes();
+        let mut expected = hll8_fixture();
+        expected[HLL_REGISTERS_START + 1023] = 55;
+        let kxq0: f64 = 1021. + 0.5 + 1. / 4096.;
+        let kxq1 = 0.5f64.powi(55);
+        expected[16..24].copy_from_slice(&kxq0.to_le_bytes());
+        expected[24..32].copy_from_slice(&kxq1.to_le_bytes());
+        expected[32..36].copy_from_slice(&1021u32.to_le_bytes());
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn reads_list_and_set_fixtures() {
+        let coupons = [coupon(0x3ff_0005, 7), coupon(9, 2), coupon(0x100_0009, 4)];
+        let check = |bytes: &[u8]| {
+            let h = HyperLogLog::from_datasketches_bytes(bytes).unwrap();
+            let regs = registers(&h);
+            assert_eq!(regs.len(), 256);
+            assert_eq!((regs[5], regs[9]), (7, 4));
+            assert_eq!(regs.iter().filter(|&&r| r != 0).count(), 2);
+        };
+
+        let mut compact_list = preamble(2, 8, 3, 8, 3, 2 << 2);
+        for c in &coupons {
+            compact_list.extend_from_slice(c);
+        }
+        check(&compact_list);
+
+        // An updatable list has room for 2^lgArr coupons.
+        let mut list = preamble(2, 8, 3, 0, 3, 1 << 2);
+        for c in &coupons {
+            list.extend_from_slice(c);
+        }
+        list.extend_from_slice(&[0; 20]);
+        check(&list);
+
+        let mut compact_set = preamble(3, 8, 5, 8, 0, 1);
+        compact_set.extend_from_slice(&3u32.to_le_bytes());
+        for c in &coupons {
+            compact_set.extend_from_slice(c);
+        }
+        check(&compact_set);
+
+        let mut set = preamble(3, 8, 5, 0, 0, 1);
+        set.extend_from_slice(&3u32.to_le_bytes());
+        let mut table = [[0u8; 4]; 32];
+        table[4] = coupons[0];
+        table[17] = coupons[1];
+        table[30] = coupons[2];
+        for c in &table {
+            set.extend_from_slice(c);
+        }
+        check(&set);
+
+        let empty = preamble(2, 8, 3, 4 | 8, 0, 2 << 2);
+        assert!(HyperLogLog::from_datasketches_bytes(&empty)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn round_trips() {
+        for &(lg_k, n) in &[(4u8, 0i64), (11, 10), (12, 100_000), (21, 50_000)] {
+            let mut h = HyperLogLog::new_datasketches(lg_k);
+            for i in 0..n {
+                h.add_long(i);
+            }
+            let bytes = h.to_datasketches_bytes();
+            let back = HyperLogLog::from_datasketches_bytes(&bytes).unwrap();
+            assert_eq!(back.precision(), lg_k);
+            assert_eq!(registers(&back), registers(&h), "lgK = {}", lg_k);
+            assert_eq!(back.count(), h.count());
+            assert_eq!(back.to_datasketches_bytes(), bytes);
+            // The hasher comes along, so adding more keeps agreeing.
+            let (mut a, mut b) = (h.clone(), back);
+            a.add_element(b"more");
+            b.add_element(b"more");
+            assert_eq!(registers(&a), registers(&b));
+        }
+        let mut h = HyperLogLog::new_datasketches(12);
+        for i in 0..100_000 {
+            h.add_long(i);
+        }
+        assert!((h.count() - 100_000.).abs() < 100_000. * 0.05);
+    }
+
+    #[test]
+    #[should_panic(expected = "hashes for lgK = 12")]
+    fn refuses_to_write_folded_sketch() {
+        let mut h = HyperLogLog::new_datasketches(12);
+        h.add_long(1);
+        h.reduce_precision(10).unwrap().to_datasketches_bytes();
+    }
+
+    #[test]
+    fn rejects_unsupported_and_malformed_input() {
+        let with = |at: usize, value: u8| {
+            let mut b = hll8_fixture();
+            b[at] = value;
+            b
+        };
+        let cases: Vec<(Vec<u8>, DataSketchesCodecError)> = vec![
+            (
+                vec![10, 1, 7],
+                DataSketchesCodecError::Truncated {
+                    expected: 8,
+                    actual: 3,
+                },
+            ),
+            (with(2, 3), DataSketchesCodecError::WrongFamily(3)),
+            (with(1, 2), DataSketchesCodecError::UnsupportedVersion(2)),
+            (with(3, 22), DataSketchesCodecError::InvalidLgK(22)),
+            (with(7, 2 << 2 | 3), DataSketchesCodecError::UnknownMode(3)),
+            (
+                with(0, 2),
+                DataSketchesCodecError::PreambleMismatch {
+                    mode: 2,
+                    preamble_ints: 2,
+                },
+            ),
+            (with(7, 2), DataSketchesCodecError::UnsupportedType(0)),
+            (
+                with(7, 1 << 2 | 2),
+                DataSketchesCodecError::UnsupportedType(1),
+            ),
+            (
+                with(HLL_REGISTERS_START + 7, 64),
+                DataSketchesCodecError::InvalidRegister {
+                    index: 7,
+                    value: 64,
+                },
+            ),
+            (
+                hll8_fixture()[..100].to_vec(),
+                DataSketchesCodecError::Truncated {
+                    expected: HLL_REGISTERS_START + 1024,
+                    actual: 100,
+                },
+            ),
+            (
+                {
+                    let mut b = preamble(2, 8, 3, 8, 1, 2 << 2);
+                    b.extend_from_slice(&coupon(5, 0));
+                    b
+                },
+                DataSketchesCodecError::InvalidCoupon(5),
+            ),
+            (
+                preamble(2, 8, 3, 8, 2, 2 << 2),
+                DataSketchesCodecError::Truncated {
+                    expected: 16,
+                    actual: 8,
+                },
+            ),
+            (
+                preamble(3, 8, 5, 0, 0, 1),
+                DataSketchesCodecError::Truncated {
+                    expected: 12,
+                    actual: 8,
+                },
+            ),
+            (
+                preamble(2, 8, 40, 0, 1, 2 << 2),
+                DataSketchesCodecError::InvalidLgArr(40),
+            ),
+        ];
+        for (bytes, expected) in cases {
+            assert_eq!(
+                HyperLogLog::from_datasketches_bytes(&bytes).unwrap_err(),
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,234 @@
+//! A HyperLogLog whose items fade out with a half-life, for "recent
+//! distinct" trends without the bookkeeping of an exact sliding window.
+//!
+//! Lowering a register by one is the same as keeping each item that reached
+//! it with probability one half: ranks are geometric, so a rank that survives
+//! the decrement is again geometric. Each register is lowered by one per
+//! half-life, so the sketch remains an ordinary HyperLogLog of a random
+//! sample in which an item `k` half-lives old survives with probability
+//! `2^-k`.
+//!
+//! Registers don't all decay at once, which would make the estimate halve in
+//! steps. Register `j` decays `j / m` of a half-life later than register 0,
+//! so the estimate falls smoothly and tracks `Σ 2^(-age / half_life)` over the
+//! distinct items, each aged from its latest occurrence, to within 6%. A
+//! steady stream of `r` new items per half-life thus settles at about
+//! `r / ln 2 ≈ 1.44 r`.
+//!
+//! Decay is lazy: each register records the decay period it was last raised
+//! in, and is lowered by the periods since whenever it is read.
+
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use error::BuildError;
+use {encoding, estimate, index_and_rank, DefaultBuildHasher, MIN_PRECISION};
+
+/// A HyperLogLog sketch over timestamped items in which each item's
+/// contribution halves every `half_life`.
+///
+/// Timestamps are plain `u64`s in whatever unit `half_life` is given in.
+#[derive(Clone)]
+pub struct DecayingHyperLogLog<S = DefaultBuildHasher> {
+    ranks: Vec<u8>,
+    /// The decay period of each register when its rank was set.
+    periods: Vec<u64>,
+    precision: u8,
+    half_life: u64,
+    build_hasher: S,
+}
+
+impl DecayingHyperLogLog {
+    /// Creates a sketch with `2^precision` registers whose items' weights
+    /// halve every `half_life`.
+    ///
+    /// Panics unless `precision` is from 4 to 24 and
+    /// `half_life` is positive.
+    pub fn new(precision: u8, half_life: u64) -> DecayingHyperLogLog {
+        DecayingHyperLogLog::with_hasher(precision, half_life, DefaultBuildHasher::default())
+    }
+}
+
+impl<S: BuildHasher> DecayingHyperLogLog<S> {
+    /// Like [`DecayingHyperLogLog::new`], hashing items with `build_hasher`.
+    pub fn with_hasher(precision: u8, half_life: u64, build_hasher: S) -> DecayingHyperLogLog<S> {
+        let m = match 1usize.checked_shl(u32::from(precision)) {
+            Some(m) if (MIN_PRECISION..=encoding::MAX_PRECISION).contains(&precision) => m,
+            _ => panic!("{}", BuildError::InvalidPrecision(precision)),
+        };
+        assert!(half_life > 0, "the half-life must be positive");
+        DecayingHyperLogLog {
+            ranks: vec![0; m],
+            periods: vec![0; m],
+            precision,
+            half_life,
+            build_hasher,
+        }
+    }
+
+    pub fn add<H: Hash>(&mut self, item: H, at: u64) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h, at);
+    }
+}
+
+impl<S> DecayingHyperLogLog<S> {
+    /// The number of times register `index` has decayed by time `at`: its
+    /// periods start `index / m` of a half-life after register 0's.
+    fn period(&self, index: usize, at: u64) -> u64 {
+        let m = self.ranks.len() as u128;
+        let h = u128::from(self.half_life);
+        ((u128::from(at) * m + index as u128 * h) / (h * m)) as u64
+    }
+
+    /// Register `index`'s rank as of period `period`.
+    fn rank_at(&self, index: usize, period: u64) -> u8 {
+        let elapsed = period.saturating_sub(self.periods[index]);
+        self.ranks[index].saturating_sub(cmp::min(elapsed, 255) as u8)
+    }
+
+    /// Adds an item by its precomputed 64-bit hash; see
+    /// [`HyperLogLog::add_hashed`](::HyperLogLog::add_hashed).
+    ///
+    /// An item stamped earlier than a register's latest raise is decayed to
+    /// that raise's period before the two are compared.
+    pub fn add_hashed(&mut self, hash: u64, at: u64) {
+        let (index, rank) = index_and_rank(hash, self.precision);
+        let period = self.period(index, at);
+        if period >= self.periods[index] {
+            if rank > self.rank_at(index, period) {
+                self.ranks[index] = rank;
+                self.periods[index] = period;
+            }
+        } else {
+            let late = cmp::min(self.periods[index] - period, 255) as u8;
+            self.ranks[index] = cmp::max(self.ranks[index], rank.saturating_sub(late));
+        }
+    }
+
+    /// Estimates the decayed number of distinct items at time `at`, each item
+    /// weighted by about `2^(-age / half_life)` from its latest occurrence.
+    ///
+    /// Items stamped after `at` count as if added at `at`.
+    pub fn count(&self, at: u64) -> f64 {
+        let mut counts = [0u64; 65];
+        for index in 0..self.ranks.len() {
+            let rank = self.rank_at(index, self.period(index, at));
+            counts[rank as usize] += 1;
+        }
+        estimate(&counts)
+    }
+
+    pub fn half_life(&self) -> u64 {
+        self.half_life
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The memory the sketch uses: nine bytes per register, a rank and the
+    /// period it was set in.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of::<Self>()
+            + self.ranks.capacity() * mem::size_of::<u8>()
+            + self.periods.capacity() * mem::size_of::<u64>()
+    }
+}
+
+impl<S> fmt::Debug for DecayingHyperLogLog<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DecayingHyperLogLog")
+            .field("precision", &self.precision)
+            .field("half_life", &self.half_life)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DecayingHyperLogLog;
+    use simulation::splitmix64;
+    use {standard_error, HyperLogLog};
+
+    #[test]
+    fn idle_sketch_decays_to_zero() {
+        let (p, half_life) = (12, 60);
+        let mut h = DecayingHyperLogLog::new(p, half_life);
+        let mut plain = HyperLogLog::new(1 << p);
+        let n = 200_000;
+        let mut rng = 1;
+        for _ in 0..n {
+            let hash = splitmix64(&mut rng);
+            h.add_hashed(hash, 0);
+            plain.add_hashed(hash);
+        }
+        assert_eq!(h.count(0), plain.count());
+
+        // Halving per half-life, to within the 6% of staggered decay and the
+        // sketch's own error, while enough of each register's items are left.
+        let mut previous = h.count(0);
+        for step in 1..=7 * half_life / 15 {
+            let at = step * 15;
+            let expected = n as f64 * 0.5f64.powf(at as f64 / half_life as f64);
+            let counted = h.count(at);
+            assert!(counted <= previous);
+            assert!(
+                (counted / expected - 1.).abs() < 0.06 + 4. * standard_error(p),
+                "at {}: counted {}, expected {}",
+                at,
+                counted,
+                expected
+            );
+            previous = counted;
+        }
+        assert_eq!(h.count(30 * half_life), 0.);
+    }
+
+    #[test]
+    fn steady_stream_settles_at_rate_over_ln_2() {
+        // 30 new items per time unit, 3,000 per half-life.
+        let (p, half_life) = (12, 100);
+        let mut h = DecayingHyperLogLog::new(p, half_life);
+        let mut rng = 2;
+        let expected = 30. * half_life as f64 / 2f64.ln();
+        for at in 0..20 * half_life {
+            for _ in 0..30 {
+                h.add_hashed(splitmix64(&mut rng), at);
+            }
+            if at >= 10 * half_life && at % 37 == 0 {
+                let counted = h.count(at);
+                assert!(
+                    (counted / expected - 1.).abs() < 0.1,
+                    "at {}: counted {}, expected {}",
+                    at,
+                    counted,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn late_items_are_decayed_first() {
+        let mut h = DecayingHyperLogLog::new(4, 10);
+        // Register 0, rank 5, in period 3; then rank 7 from period 0.
+        h.add_hashed(1 << 55, 30);
+        h.add_hashed(1 << 53, 0);
+        assert_eq!((h.ranks[0], h.periods[0]), (5, 3));
+        h.add_hashed(1 << 52, 5);
+        assert_eq!((h.ranks[0], h.periods[0]), (5, 3));
+        h.add_hashed(1 << 50, 5);
+        assert_eq!((h.ranks[0], h.periods[0]), (7, 3));
+        assert_eq!(h.rank_at(0, 5), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "precision")]
+    fn rejects_bad_precision() {
+        DecayingHyperLogLog::new(3, 10);
+    }
+}
@@ -0,0 +1,402 @@
+//! The registers one sketch has raised since an earlier snapshot, for
+//! replicating a sketch without shipping all of it.
+//!
+//! A replica that applies the [`SketchDelta`] from
+//! [`HyperLogLog::diff`] raises those registers to at least the new ranks,
+//! so deltas can be applied in any order and any number of times: a replica
+//! that has seen every delta holds the merge of every sketch they came from.
+//!
+//! The encoding, all integers little-endian:
+//!
+//! | offset | size   | contents                                      |
+//! |--------|--------|-----------------------------------------------|
+//! | 0      | 4      | magic `b"HLLD"`                               |
+//! | 4      | 1      | format version (2)                            |
+//! | 5      | 1      | precision `p`                                 |
+//! | 6      | 8      | the [`HasherId`] fingerprint                  |
+//! | 14     | 4      | number of changed registers `n`               |
+//! | 18     | 5 · n  | each register's index as a `u32`, ascending,  |
+//! |        |        | then its new rank                             |
+//!
+//! A delta without a hasher ID, from a sketch that
+//! [assumes](HyperLogLog::assume_same_hasher) the hashers match, is written
+//! in version 1, which has no ID field.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::hash::BuildHasher;
+
+use encoding::{MAX_PRECISION, MIN_PRECISION};
+use error::{DecodeError, MergeError};
+use {max_rank, HasherId, HyperLogLog};
+
+const MAGIC: [u8; 4] = *b"HLLD";
+const VERSION: u8 = 1;
+const VERSION_HASHED: u8 = 2;
+/// The length of the version 1 header; version 2 adds the hasher ID.
+const HEADER_LEN: usize = 10;
+const HASHER_LEN: usize = 8;
+const ENTRY_LEN: usize = 5;
+
+/// The registers a sketch holds above an earlier snapshot of itself, as
+/// (index, rank) pairs in index order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SketchDelta {
+    precision: u8,
+    hasher: Option<HasherId>,
+    changes: Vec<(u32, u8)>,
+}
+
+impl SketchDelta {
+    /// The precision of the sketches the delta applies to.
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The [ID](HasherId) of the hasher of the sketch the delta was taken
+    /// from, which [`apply_delta`](HyperLogLog::apply_delta) checks, or
+    /// `None` if that sketch [assumes](HyperLogLog::assume_same_hasher) the
+    /// hashers match.
+    pub fn hasher_id(&self) -> Option<HasherId> {
+        self.hasher
+    }
+
+    /// The changed registers' indices and new ranks, in index order.
+    pub fn changes(&self) -> &[(u32, u8)] {
+        &self.changes
+    }
+
+    /// The number of changed registers.
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Whether no register changed, so applying the delta does nothing.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Encodes the delta in the format described in the module docs: 18
+    /// bytes, or ten without a hasher ID, and five per changed register.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header_len = HEADER_LEN + if self.hasher.is_some() { HASHER_LEN } else { 0 };
+        let mut out = Vec::with_capacity(header_len + ENTRY_LEN * self.changes.len());
+        out.extend_from_slice(&MAGIC);
+        match self.hasher {
+            Some(id) => {
+                out.push(VERSION_HASHED);
+                out.push(self.precision);
+                out.extend_from_slice(&id.fingerprint().to_le_bytes());
+            }
+            None => {
+                out.push(VERSION);
+                out.push(self.precision);
+            }
+        }
+        out.extend_from_slice(&(self.changes.len() as u32).to_le_bytes());
+        for &(index, rank) in &self.changes {
+            out.extend_from_slice(&index.to_le_bytes());
+            out.push(rank);
+        }
+        out
+    }
+
+    /// Decodes a delta written by [`to_bytes`](SketchDelta::to_bytes).
+    ///
+    /// Never panics: indices out of range or out of order, and ranks no hash
+    /// could produce, fail with a `DecodeError`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SketchDelta, DecodeError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DecodeError::Truncated {
+                expected: HEADER_LEN,
+                actual: bytes.len(),
+            });
+        }
+        let mut magic = [0u8; 4];
+        magic.copy_from_slice(&bytes[..4]);
+        if magic != MAGIC {
+            return Err(DecodeError::BadMagic(magic));
+        }
+        let header_len = match bytes[4] {
+            VERSION => HEADER_LEN,
+            VERSION_HASHED => HEADER_LEN + HASHER_LEN,
+            version => return Err(DecodeError::UnsupportedVersion(version)),
+        };
+        let precision = bytes[5];
+        if !(MIN_PRECISION..=MAX_PRECISION).contains(&precision) {
+            return Err(DecodeError::InvalidPrecision(precision));
+        }
+        if bytes.len() < header_len {
+            return Err(DecodeError::Truncated {
+                expected: header_len,
+                actual: bytes.len(),
+            });
+        }
+        let hasher = if header_len > HEADER_LEN {
+            let mut id = [0u8; HASHER_LEN];
+            id.copy_from_slice(&bytes[6..6 + HASHER_LEN]);
+            Some(HasherId(u64::from_le_bytes(id)))
+        } else {
+            None
+        };
+        let n_at = header_len - 4;
+        let n = u32::from_le_bytes([
+            bytes[n_at],
+            bytes[n_at + 1],
+            bytes[n_at + 2],
+            bytes[n_at + 3],
+        ]);
+        let expected = (n as usize)
+            .checked_mul(ENTRY_LEN)
+            .and_then(|len| len.checked_add(header_len))
+            .unwrap_or(usize::MAX);
+        if bytes.len() < expected {
+            return Err(DecodeError::Truncated {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        if bytes.len() > expected {
+            return Err(DecodeError::TrailingBytes {
+                expected,
+                actual: bytes.len(),
+            });
+        }
+        let m = 1u64 << precision;
+        let max = max_rank(precision);
+        let mut changes = Vec::with_capacity(n as usize);
+        for (entry, chunk) in bytes[header_len..].chunks(ENTRY_LEN).enumerate() {
+            let index = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            let ascending = changes.last().is_none_or(|&(last, _)| index > last);
+            if u64::from(index) >= m || !ascending {
+                return Err(DecodeError::InvalidIndex { entry, index });
+            }
+            let rank = chunk[4];
+            if rank == 0 || rank > max {
+                return Err(DecodeError::InvalidRegister {
+                    index: index as usize,
+                    value: rank,
+                });
+            }
+            changes.push((index, rank));
+        }
+        Ok(SketchDelta {
+            precision,
+            hasher,
+            changes,
+        })
+    }
+}
+
+impl<S: BuildHasher> HyperLogLog<S> {
+    /// The registers where `self` holds a higher rank than `since`, usually
+    /// an earlier snapshot of it, for [`apply_delta`](HyperLogLog::apply_delta)
+    /// on a replica that has already seen `since`.
+    pub fn diff(&self, since: &HyperLogLog<S>) -> Result<SketchDelta, MergeError> {
+        self.check_compatible(since)?;
+        let m = self.num_registers();
+        let changes = self
+            .registers
+            .iter(m)
+            .zip(since.registers.iter(m))
+            .enumerate()
+            .filter(|&(_, (new, old))| new > old)
+            .map(|(index, (new, _))| {
+                // Indices are below 2^24, the most registers a sketch has.
+                (u32::try_from(index).unwrap(), new)
+            })
+            .collect();
+        Ok(SketchDelta {
+            precision: self.precision,
+            hasher: self.hasher_id(),
+            changes,
+        })
+    }
+
+    /// Raises each register in `delta` to at least its rank, as if merging
+    /// the sketch it was taken from, failing as [`merge`](HyperLogLog::merge)
+    /// does if that sketch had another precision or hasher.
+    pub fn apply_delta(&mut self, delta: &SketchDelta) -> Result<(), MergeError> {
+        if self.precision != delta.precision {
+            return Err(MergeError::PrecisionMismatch {
+                left: self.precision,
+                right: delta.precision,
+            });
+        }
+        self.check_hasher(delta.hasher)?;
+        let updates = delta
+            .changes
+            .iter()
+            .map(|&(index, rank)| (index as usize, rank));
+        self.raise_registers(updates);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SketchDelta;
+    use error::{DecodeError, MergeError};
+    use simulation::splitmix64;
+    use HyperLogLog;
+
+    #[test]
+    fn replicas_converge() {
+        let mut state = 72;
+        let mut a = HyperLogLog::new(1 << 10);
+        let mut b = HyperLogLog::new(1 << 10);
+        let mut everything = HyperLogLog::new(1 << 10);
+        // Each replica as of its last delta, after taking the other's.
+        let (mut a_sent, mut b_sent) = (a.clone(), b.clone());
+        for cycle in 0..40 {
+            // More local writes as the cycles go on, so both replicas pass
+            // from sparse to dense.
+            for _ in 0..cycle * 10 {
+                let h = splitmix64(&mut state);
+                everything.add_hashed(h);
+                if h.is_multiple_of(3) {
+                    b.add_hashed(h);
+                } else {
+                    a.add_hashed(h);
+                }
+            }
+            let to_b = SketchDelta::from_bytes(&a.diff(&a_sent).unwrap().to_bytes()).unwrap();
+            let to_a = SketchDelta::from_bytes(&b.diff(&b_sent).unwrap().to_bytes()).unwrap();
+            b.apply_delta(&to_b).unwrap();
+            a.apply_delta(&to_a).unwrap();
+            a_sent = a.clone();
+            b_sent = b.clone();
+            assert_eq!(a, b, "cycle {}", cycle);
+        }
+        assert!(!a.is_sparse());
+        assert_eq!(a, everything);
+        assert_eq!(a.count(), everything.count());
+    }
+
+    #[test]
+    fn applying_is_idempotent_and_commutative() {
+        let mut state = 72;
+        let base = HyperLogLog::new(1 << 8);
+        let mut deltas = Vec::new();
+        for _ in 0..2 {
+            let mut h = base.clone();
+            (0..300).for_each(|_| h.add_hashed(splitmix64(&mut state)));
+            deltas.push(h.diff(&base).unwrap());
+        }
+        let mut forward = base.clone();
+        forward.apply_delta(&deltas[0]).unwrap();
+        forward.apply_delta(&deltas[1]).unwrap();
+        let mut backward = base.clone();
+        backward.apply_delta(&deltas[1]).unwrap();
+        backward.apply_delta(&deltas[0]).unwrap();
+        backward.apply_delta(&deltas[1]).unwrap();
+        assert_eq!(forward, backward);
+        assert!(forward.diff(&backward).unwrap().is_empty());
+
+        assert_eq!(
+            HyperLogLog::new(1 << 8).diff(&HyperLogLog::new(1 << 9)),
+            Err(MergeError::PrecisionMismatch { left: 8, right: 9 })
+        );
+        assert_eq!(
+            HyperLogLog::new(1 << 9).apply_delta(&deltas[0]),
+            Err(MergeError::PrecisionMismatch { left: 9, right: 8 })
+        );
+    }
+
+    #[test]
+    fn deltas_carry_the_hasher() {
+        let mut seeded = HyperLogLog::new_seeded(1 << 4, 99);
+        seeded.add(1u64);
+        let bytes = seeded
+            .diff(&HyperLogLog::new_seeded(1 << 4, 99))
+            .unwrap()
+            .to_bytes();
+        let delta = SketchDelta::from_bytes(&bytes).unwrap();
+        assert_eq!((bytes[4], delta.hasher_id()), (2, seeded.hasher_id()));
+        let mut other = HyperLogLog::new_seeded(1 << 4, 100);
+        assert_eq!(
+            other.apply_delta(&delta),
+            Err(MergeError::HasherMismatch {
+                left: other.hasher_id().unwrap(),
+                right: seeded.hasher_id().unwrap()
+            })
+        );
+        assert_eq!(other, HyperLogLog::new_seeded(1 << 4, 100));
+        let mut same = HyperLogLog::new_seeded(1 << 4, 99);
+        same.apply_delta(&delta).unwrap();
+        assert_eq!(same, seeded);
+
+        // A sketch assuming the hashers match sends deltas without an ID,
+        // in version 1, which apply to any sketch.
+        seeded.assume_same_hasher();
+        let bytes = seeded
+            .diff(&HyperLogLog::new_seeded(1 << 4, 99))
+            .unwrap()
+            .to_bytes();
+        assert_eq!((bytes[4], bytes.len()), (1, 15));
+        let delta = SketchDelta::from_bytes(&bytes).unwrap();
+        assert_eq!(delta.hasher_id(), None);
+        other.apply_delta(&delta).unwrap();
+        assert_eq!(other, seeded);
+    }
+
+    #[test]
+    fn rejects_corrupt_bytes() {
+        let mut h = HyperLogLog::new(1 << 4);
+        h.add_hashed(0x1100_0000_0000_0000);
+        h.add_hashed(0x3200_0000_0000_0000);
+        let bytes = h.diff(&HyperLogLog::new(1 << 4)).unwrap().to_bytes();
+        assert_eq!(bytes.len(), 28);
+        assert_eq!(
+            SketchDelta::from_bytes(&bytes).unwrap().changes(),
+            &[(1, 4), (3, 3)]
+        );
+
+        let decode = |edit: &dyn Fn(&mut Vec<u8>)| {
+            let mut bad = bytes.clone();
+            edit(&mut bad);
+            SketchDelta::from_bytes(&bad)
+        };
+        assert_eq!(
+            decode(&|b| b.truncate(27)),
+            Err(DecodeError::Truncated {
+                expected: 28,
+                actual: 27
+            })
+        );
+        assert_eq!(
+            decode(&|b| b[14] = 0xff),
+            Err(DecodeError::Truncated {
+                expected: 18 + 5 * 255,
+                actual: 28
+            })
+        );
+        assert_eq!(
+            decode(&|b| b.push(0)),
+            Err(DecodeError::TrailingBytes {
+                expected: 28,
+                actual: 29
+            })
+        );
+        assert_eq!(decode(&|b| b[5] = 0), Err(DecodeError::InvalidPrecision(0)));
+        // Out of order, then past the 16 registers.
+        assert_eq!(
+            decode(&|b| b[23] = 1),
+            Err(DecodeError::InvalidIndex { entry: 1, index: 1 })
+        );
+        assert_eq!(
+            decode(&|b| b[23] = 16),
+            Err(DecodeError::InvalidIndex {
+                entry: 1,
+                index: 16
+            })
+        );
+        assert_eq!(
+            decode(&|b| b[27] = 62),
+            Err(DecodeError::InvalidRegister {
+                index: 3,
+                value: 62
+            })
+        );
+    }
+}
@@ -0,0 +1,33 @@
+//! The intermediate values behind a cardinality estimate, for diagnostics.
+
+/// Which formula the [bias-corrected](::BiasCorrectedEstimator) estimate
+/// used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EstimationMethod {
+    /// Linear counting over the empty registers, used for small cardinalities.
+    LinearCounting,
+    /// The HyperLogLog harmonic-mean estimate, bias-corrected where tables exist.
+    HyperLogLog,
+}
+
+/// A breakdown of how an estimate was reached, returned by
+/// [`HyperLogLog::estimate_details`](::HyperLogLog::estimate_details).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EstimateDetails {
+    /// The [bias-corrected](::BiasCorrectedEstimator) estimate, which is
+    /// what `count()` returns unless the sketch has another
+    /// [estimator](::HyperLogLog::set_estimator) or is
+    /// [monotonic](::HyperLogLog::set_monotonic).
+    pub estimate: f64,
+    /// The branch that produced `estimate`.
+    pub method: EstimationMethod,
+    /// The uncorrected HyperLogLog estimate `α m² / Σ 2^-register`.
+    pub raw: f64,
+    /// The linear-counting estimate `m ln(m / zeros)`, or `None` when no
+    /// register is empty and it is undefined.
+    pub linear_count: Option<f64>,
+    /// The number of registers that have never been raised.
+    pub zero_registers: usize,
+    /// The harmonic sum `Σ 2^-register` over all registers.
+    pub harmonic_sum: f64,
+}
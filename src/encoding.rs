@@ -0,0 +1,1226 @@
+//! A compact, versioned binary encoding for sketches.
+//!
+//! Version 1 layout (all integers little-endian):
+//!
+//! | offset | size   | contents                                      |
+//! |--------|--------|-----------------------------------------------|
+//! | 0      | 4      | magic `b"HLLR"`                               |
+//! | 4      | 1      | format version (1)                            |
+//! | 5      | 1      | precision `p`                                 |
+//! | 6      | 1      | flags; bit 0 set if hasher keys follow, bits  |
+//! |        |        | 1-3 the [`HashBackend`] (0 for native), bit 4 |
+//! |        |        | set for a [`HyperLogLog32`](::HyperLogLog32), |
+//! |        |        | bit 5 set if a watermark ends the sketch,     |
+//! |        |        | bits 6-7 the default [`BuiltinEstimator`] (0  |
+//! |        |        | for bias-corrected)                           |
+//! | 7      | 0 / 16 | hasher keys `k0`, `k1` as two `u64`s          |
+//! | ...    | 2^p    | one byte per register                         |
+//! | ...    | 0 / 8  | the watermark of a monotonic sketch, an `f64` |
+//!
+//! The watermark (see [`HyperLogLog::set_monotonic`]) comes last in every
+//! version, so that readers from before it was added fail on the
+//! trailing bytes rather than misreading the registers. Readers from before
+//! the estimator bits ignore them, and estimate as they always did.
+//!
+//! A hasher without keys other than the default is recorded with backend 3,
+//! [`HashBackend::Custom`], and its [`HasherId`](::HasherId) as `k0`, with
+//! `k1` 0, so that it can't be decoded into a sketch hashing items another
+//! way. Readers from before it fail on the unknown backend.
+//!
+//! Version 2, from [`HyperLogLog::to_bytes_compressed`], has the same header
+//! with version 2 and replaces the register bytes with a stream of four-bit
+//! nibbles, high nibble first, the last byte padded with a zero nibble:
+//!
+//! | nibbles            | registers                                      |
+//! |--------------------|------------------------------------------------|
+//! | `1`..=`14`         | one register holding that value                |
+//! | `15`, `hi`, `lo`   | one register holding `hi << 4 \| lo`            |
+//! | `0`, varint `n`    | `n + 1` zero registers                         |
+//!
+//! A varint is little-endian groups of three bits, one per nibble, with the
+//! nibble's top bit set on every group but the last.
+//!
+//! Version 3 holds a [`HybridHyperLogLog`](::HybridHyperLogLog) still in its
+//! exact mode: the same header, then the 64-bit hashes of the distinct items
+//! as `u64`s in ascending order. Hybrid sketches past the exact mode are
+//! written as version 2 or 5.
+//!
+//! Versions 4 and 5 are versions 1 and 2 with the number of items added to
+//! the sketch (see [`HyperLogLog::items_added`]), a `u64`, between the
+//! header and the registers. [`HyperLogLog`] writes these once anything
+//! has been added, so that sketches that never had items, such as those
+//! built from registers, still read in older versions of the crate.
+//! Sketches of 32-bit hashes are always written as versions 1 and 2, and
+//! sketches read from those versions have added no items.
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use error::DecodeError;
+use {
+    hash32, max_rank, simd,
+    sparse::{self, Registers},
+    BuiltinEstimator, DefaultBuildHasher, Estimator, HashBackend, HasherId, HyperLogLog,
+    StableBuildHasher,
+};
+
+pub(crate) const MAGIC: [u8; 4] = *b"HLLR";
+pub(crate) const VERSION: u8 = 1;
+pub(crate) const VERSION_COMPRESSED: u8 = 2;
+pub(crate) const VERSION_COUNTED: u8 = 4;
+pub(crate) const VERSION_COMPRESSED_COUNTED: u8 = 5;
+/// Every version a [`HyperLogLog`] can be read from.
+pub(crate) const SKETCH_VERSIONS: [u8; 4] = [
+    VERSION,
+    VERSION_COMPRESSED,
+    VERSION_COUNTED,
+    VERSION_COMPRESSED_COUNTED,
+];
+/// The precisions sketches are built with, and so the only ones decoded:
+/// a larger sketch couldn't start sparse, so a few bytes of compressed zeros
+/// could make it ask for gigabytes of registers.
+pub(crate) const MIN_PRECISION: u8 = ::MIN_PRECISION;
+pub(crate) const MAX_PRECISION: u8 = sparse::MAX_SPARSE_PRECISION;
+
+pub(crate) const FLAG_KEYED: u8 = 1;
+const BACKEND_SHIFT: u8 = 1;
+const BACKEND_MASK: u8 = 7;
+/// Set for sketches of 32-bit hashes.
+pub(crate) const FLAG_HASH32: u8 = 1 << 4;
+/// Set when the sketch ends in the watermark of a monotonic sketch.
+pub(crate) const FLAG_WATERMARK: u8 = 1 << 5;
+/// The estimator takes the two top bits.
+const ESTIMATOR_SHIFT: u8 = 6;
+/// The length of the watermark.
+pub(crate) const WATERMARK_LEN: usize = 8;
+pub(crate) const HEADER_LEN: usize = 7;
+/// The length of the number of items added.
+pub(crate) const ITEMS_LEN: usize = 8;
+/// The length of a header with hasher keys and the number of items added.
+pub(crate) const MAX_HEADER_LEN: usize = HEADER_LEN + 16 + ITEMS_LEN;
+
+/// The nibble that starts a run of zero registers.
+const ZERO_RUN: u8 = 0;
+/// The nibble that escapes a register value too large for one nibble.
+const ESCAPE: u8 = 15;
+
+/// Checks that `registers` is a plausible register array for precision `p`.
+pub(crate) fn validate_registers(p: u8, registers: &[u8]) -> Result<(), DecodeError> {
+    validate_registers_up_to(p, registers, max_rank(p))
+}
+
+/// Checks that `registers` is a register array for precision `p` with no
+/// rank over `max`.
+pub(crate) fn validate_registers_up_to(
+    p: u8,
+    registers: &[u8],
+    max: u8,
+) -> Result<(), DecodeError> {
+    if !(MIN_PRECISION..=MAX_PRECISION).contains(&p) {
+        return Err(DecodeError::InvalidPrecision(p));
+    }
+    if registers.len() != 1usize << p {
+        return Err(DecodeError::RegisterCount {
+            precision: p,
+            count: registers.len(),
+        });
+    }
+    match registers.iter().position(|&r| r > max) {
+        Some(index) => Err(DecodeError::InvalidRegister {
+            index,
+            value: registers[index],
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Whether `version` writes one byte per register, rather than compressing
+/// them.
+pub(crate) fn is_dense(version: u8) -> bool {
+    version == VERSION || version == VERSION_COUNTED
+}
+
+/// Whether `version` has the number of items added after the header.
+pub(crate) fn is_counted(version: u8) -> bool {
+    version == VERSION_COUNTED || version == VERSION_COMPRESSED_COUNTED
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&bytes[..8]);
+    u64::from_le_bytes(word)
+}
+
+/// Splits the watermark off the end of `bytes` if the header says there is
+/// one, returning the bytes before it and the watermark.
+pub(crate) fn split_watermark(
+    bytes: &[u8],
+    body_at: usize,
+    flagged: bool,
+) -> Result<(&[u8], Option<f64>), DecodeError> {
+    if !flagged {
+        return Ok((bytes, None));
+    }
+    if bytes.len() < body_at + WATERMARK_LEN {
+        return Err(DecodeError::Truncated {
+            expected: body_at + WATERMARK_LEN,
+            actual: bytes.len(),
+        });
+    }
+    let (rest, trailer) = bytes.split_at(bytes.len() - WATERMARK_LEN);
+    Ok((rest, Some(read_watermark(trailer)?)))
+}
+
+/// Reads the watermark in the first eight bytes, rejecting values no
+/// estimate could have.
+pub(crate) fn read_watermark(bytes: &[u8]) -> Result<f64, DecodeError> {
+    let watermark = f64::from_bits(read_u64(bytes));
+    if watermark.is_finite() && watermark >= 0. {
+        Ok(watermark)
+    } else {
+        Err(DecodeError::InvalidWatermark)
+    }
+}
+
+fn backend_of(flags: u8) -> Result<HashBackend, DecodeError> {
+    let code = flags >> BACKEND_SHIFT & BACKEND_MASK;
+    HashBackend::from_code(code).ok_or(DecodeError::UnknownHashBackend(code))
+}
+
+/// Packs nibbles into bytes, high nibble first.
+struct NibbleWriter {
+    out: Vec<u8>,
+    half: bool,
+}
+
+impl NibbleWriter {
+    fn push(&mut self, nibble: u8) {
+        if self.half {
+            *self.out.last_mut().unwrap() |= nibble;
+        } else {
+            self.out.push(nibble << 4);
+        }
+        self.half = !self.half;
+    }
+
+    fn push_varint(&mut self, mut n: u64) {
+        while n >= 8 {
+            self.push(8 | (n & 7) as u8);
+            n >>= 3;
+        }
+        self.push(n as u8);
+    }
+}
+
+/// Reads the nibbles packed in `bytes[start..]`.
+struct NibbleReader<'a> {
+    bytes: &'a [u8],
+    /// The index of the next nibble, counting from `bytes[start]`.
+    next: usize,
+    start: usize,
+}
+
+impl<'a> NibbleReader<'a> {
+    fn pop(&mut self) -> Result<u8, DecodeError> {
+        let at = self.start + self.next / 2;
+        let byte = *self.bytes.get(at).ok_or(DecodeError::Truncated {
+            expected: at + 1,
+            actual: self.bytes.len(),
+        })?;
+        let nibble = if self.next.is_multiple_of(2) {
+            byte >> 4
+        } else {
+            byte & 15
+        };
+        self.next += 1;
+        Ok(nibble)
+    }
+
+    /// Reads a varint, or `None` if it doesn't fit in a `u64`.
+    fn pop_varint(&mut self) -> Result<Option<u64>, DecodeError> {
+        let mut n = 0u64;
+        let mut shift = 0;
+        loop {
+            let nibble = self.pop()?;
+            let group = u64::from(nibble & 7);
+            if shift >= 64 || group << shift >> shift != group {
+                return Ok(None);
+            }
+            n |= group << shift;
+            if nibble & 8 == 0 {
+                return Ok(Some(n));
+            }
+            shift += 3;
+        }
+    }
+}
+
+/// Appends the version 2 encoding of `registers`.
+pub(crate) fn compress<I: Iterator<Item = u8>>(registers: I, out: Vec<u8>) -> Vec<u8> {
+    let mut writer = NibbleWriter { out, half: false };
+    let mut zeros = 0u64;
+    for r in registers {
+        if r == 0 {
+            zeros += 1;
+            continue;
+        }
+        if zeros > 0 {
+            writer.push(ZERO_RUN);
+            writer.push_varint(zeros - 1);
+            zeros = 0;
+        }
+        if r < ESCAPE {
+            writer.push(r);
+        } else {
+            writer.push(ESCAPE);
+            writer.push(r >> 4);
+            writer.push(r & 15);
+        }
+    }
+    if zeros > 0 {
+        writer.push(ZERO_RUN);
+        writer.push_varint(zeros - 1);
+    }
+    writer.out
+}
+
+/// Decodes the `2^p` registers compressed in `bytes[start..]`, which must
+/// hold nothing else.
+pub(crate) fn decompress(p: u8, bytes: &[u8], start: usize) -> Result<Vec<u8>, DecodeError> {
+    let mut registers = vec![0; 1usize << p];
+    for_each_compressed(p, bytes, start, |index, r| registers[index] = r)?;
+    Ok(registers)
+}
+
+/// Decodes the `2^p` registers compressed in `bytes[start..]`, which must
+/// hold nothing else, checking that none is above `max`.
+///
+/// Every register set takes at least a nibble, so bytes too short to set
+/// more than `threshold` decode sparse, and longer ones decode dense, into
+/// at most eight times their size.
+pub(crate) fn decompress_registers(
+    p: u8,
+    bytes: &[u8],
+    start: usize,
+    max: u8,
+    threshold: usize,
+) -> Result<Registers, DecodeError> {
+    let registers = if 2 * bytes.len().saturating_sub(start) > threshold {
+        Registers::Dense(decompress(p, bytes, start)?)
+    } else {
+        let mut entries = Vec::new();
+        for_each_compressed(p, bytes, start, |index, value| {
+            if value > 0 {
+                entries.push(sparse::encode(index, value));
+            }
+        })?;
+        Registers::Sparse(entries)
+    };
+    match registers.first_above(max) {
+        Some((index, value)) => Err(DecodeError::InvalidRegister { index, value }),
+        None => Ok(registers),
+    }
+}
+
+/// Calls `f` with the index and value of each non-zero register of the
+/// `2^p` compressed in `bytes[start..]`, which must hold nothing else.
+///
+/// The values aren't checked against the precision, and on an error `f` has
+/// seen the registers before it.
+pub(crate) fn for_each_compressed<F: FnMut(usize, u8)>(
+    p: u8,
+    bytes: &[u8],
+    start: usize,
+    mut f: F,
+) -> Result<(), DecodeError> {
+    let m = 1usize << p;
+    let mut index = 0;
+    let mut reader = NibbleReader {
+        bytes,
+        next: 0,
+        start,
+    };
+    while index < m {
+        match reader.pop()? {
+            ZERO_RUN => {
+                let run = reader.pop_varint()?;
+                let count = run
+                    .and_then(|n| usize::try_from(n).ok())
+                    .and_then(|n| n.checked_add(1 + index))
+                    .unwrap_or(usize::MAX);
+                if count > m {
+                    return Err(DecodeError::RegisterCount {
+                        precision: p,
+                        count,
+                    });
+                }
+                index = count;
+            }
+            ESCAPE => {
+                let hi = reader.pop()?;
+                f(index, hi << 4 | reader.pop()?);
+                index += 1;
+            }
+            r => {
+                f(index, r);
+                index += 1;
+            }
+        }
+    }
+    let expected = start + reader.next.div_ceil(2);
+    if bytes.len() > expected {
+        return Err(DecodeError::TrailingBytes {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+    if reader.next % 2 == 1 && bytes[expected - 1] & 15 != 0 {
+        return Err(DecodeError::NonZeroPadding);
+    }
+    Ok(())
+}
+
+/// The registers of version 1 bytes, which must be exactly `2^p` long after
+/// the header, followed by `trailer` more bytes.
+pub(crate) fn dense_body(
+    p: u8,
+    bytes: &[u8],
+    body_at: usize,
+    trailer: usize,
+) -> Result<&[u8], DecodeError> {
+    let end = body_at + (1usize << p);
+    let expected = end + trailer;
+    if bytes.len() < expected {
+        return Err(DecodeError::Truncated {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+    if bytes.len() > expected {
+        return Err(DecodeError::TrailingBytes {
+            expected,
+            actual: bytes.len(),
+        });
+    }
+    Ok(&bytes[body_at..end])
+}
+
+impl HashBackend {
+    /// The backend recorded in bytes written by
+    /// [`HyperLogLog::to_bytes`], for choosing the hasher type to decode them
+    /// with.
+    pub fn of_encoded(bytes: &[u8]) -> Result<HashBackend, DecodeError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DecodeError::Truncated {
+                expected: HEADER_LEN,
+                actual: bytes.len(),
+            });
+        }
+        if bytes[..4] != MAGIC {
+            let mut magic = [0u8; 4];
+            magic.copy_from_slice(&bytes[..4]);
+            return Err(DecodeError::BadMagic(magic));
+        }
+        backend_of(bytes[6])
+    }
+}
+
+impl<S: StableBuildHasher> HyperLogLog<S> {
+    /// Encodes the sketch in the crate's versioned binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_bytes(&mut out);
+        out
+    }
+
+    /// Appends what [`to_bytes`](HyperLogLog::to_bytes) returns to `out`.
+    pub(crate) fn write_bytes(&self, out: &mut Vec<u8>) {
+        let m = self.num_registers();
+        let (header, len) = self.header_bytes(self.counted(VERSION));
+        out.reserve(self.bytes_len());
+        out.extend_from_slice(&header[..len]);
+        out.extend(self.registers.iter(m));
+        self.push_watermark(out);
+    }
+
+    /// The length of what [`to_bytes`](HyperLogLog::to_bytes) returns,
+    /// which depends only on the precision, the hasher, whether the sketch
+    /// is monotonic and whether anything has been added.
+    pub(crate) fn bytes_len(&self) -> usize {
+        let (_, header_len) = self.header_bytes(self.counted(VERSION));
+        let watermark_len = if self.watermark.is_some() {
+            WATERMARK_LEN
+        } else {
+            0
+        };
+        header_len + self.num_registers() + watermark_len
+    }
+
+    /// Encodes the sketch in the compressed version of the format, which
+    /// run-length encodes zero registers and packs the rest into nibbles.
+    ///
+    /// Sparsely filled sketches shrink the most: 50 items at precision 14
+    /// take about 140 bytes instead of 16 KiB. A full sketch takes a little
+    /// over half its dense size. [`HyperLogLog::from_bytes`] reads either
+    /// version.
+    pub fn to_bytes_compressed(&self) -> Vec<u8> {
+        let m = self.num_registers();
+        let header = self.header(self.counted(VERSION_COMPRESSED), 0);
+        let mut out = compress(self.registers.iter(m), header);
+        self.push_watermark(&mut out);
+        out
+    }
+
+    /// Appends the watermark of a monotonic sketch.
+    pub(crate) fn push_watermark(&self, out: &mut Vec<u8>) {
+        if let Some(watermark) = self.watermark {
+            out.extend_from_slice(&watermark.to_le_bytes());
+        }
+    }
+
+    /// `version`, [`VERSION`] or [`VERSION_COMPRESSED`], or its counted
+    /// counterpart if anything has been added.
+    pub(crate) fn counted(&self, version: u8) -> u8 {
+        match version {
+            _ if self.items_added == 0 => version,
+            VERSION => VERSION_COUNTED,
+            _ => VERSION_COMPRESSED_COUNTED,
+        }
+    }
+
+    /// The header for `version`, with room for `body` more bytes.
+    pub(crate) fn header(&self, version: u8, body: usize) -> Vec<u8> {
+        let (header, len) = self.header_bytes(version);
+        let mut out = Vec::with_capacity(len + body);
+        out.extend_from_slice(&header[..len]);
+        out
+    }
+
+    /// The header for `version`, in the first `len` bytes of the array,
+    /// with the number of items added if the version has it.
+    pub(crate) fn header_bytes(&self, version: u8) -> ([u8; MAX_HEADER_LEN], usize) {
+        let watermark = if self.watermark.is_some() {
+            FLAG_WATERMARK
+        } else {
+            0
+        };
+        let flags = watermark | self.estimator.code() << ESTIMATOR_SHIFT;
+        let (mut out, len) = header_bytes(version, self.precision, &self.build_hasher, flags);
+        if !is_counted(version) {
+            return (out, len);
+        }
+        out[len..len + ITEMS_LEN].copy_from_slice(&self.items_added.to_le_bytes());
+        (out, len + ITEMS_LEN)
+    }
+
+    /// Decodes a sketch written by [`HyperLogLog::to_bytes`] or
+    /// [`HyperLogLog::to_bytes_compressed`], telling them apart by the
+    /// version byte. Sketches written before the number of items added was
+    /// recorded decode as having added none.
+    ///
+    /// Never panics: malformed input of any kind produces a `DecodeError`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<HyperLogLog<S>, DecodeError> {
+        let header = read_header(bytes, &SKETCH_VERSIONS)?;
+        let p = header.precision;
+        let (registers, watermark) = if is_dense(header.version) {
+            let trailer = if header.watermark { WATERMARK_LEN } else { 0 };
+            let registers = dense_body(p, bytes, header.body_at, trailer)?;
+            let watermark = split_watermark(bytes, header.body_at, header.watermark)?.1;
+            validate_registers(p, registers)?;
+            (Registers::Dense(registers.to_vec()), watermark)
+        } else {
+            let (body, watermark) = split_watermark(bytes, header.body_at, header.watermark)?;
+            let threshold = sparse::default_sparse_threshold(p);
+            let registers = decompress_registers(p, body, header.body_at, max_rank(p), threshold)?;
+            (registers, watermark)
+        };
+        let mut h = HyperLogLog::from_stored(p, registers, header.build_hasher);
+        h.watermark = watermark;
+        h.estimator = header.estimator;
+        h.items_added = header.items_added;
+        Ok(h)
+    }
+
+    /// Merges the sketch in `bytes` into this one, as decoding it with
+    /// [`from_bytes`](HyperLogLog::from_bytes) and then
+    /// [`merge`](HyperLogLog::merge)-ing it in would, returning whether any
+    /// register rose. The registers are read where they lie, in either
+    /// format, so no decoded sketch is built.
+    ///
+    /// Fails with the error `from_bytes` would give on malformed bytes,
+    /// with [`DecodeError::PrecisionMismatch`] if the precisions differ, and
+    /// with [`DecodeError::HasherMismatch`] if the sketch was hashed
+    /// differently from this one. The whole sketch is checked before any
+    /// register is taken, so this one is unchanged after an error.
+    pub fn merge_from_bytes(&mut self, bytes: &[u8]) -> Result<bool, DecodeError> {
+        let header = read_header::<S>(bytes, &SKETCH_VERSIONS)?;
+        let p = header.precision;
+        if p != self.precision {
+            return Err(DecodeError::PrecisionMismatch {
+                expected: self.precision,
+                found: p,
+            });
+        }
+        self.check_hasher(Some(HasherId::of(&header.build_hasher)))
+            .map_err(|_| DecodeError::HasherMismatch)?;
+        let (raised, histogram, watermark) = if is_dense(header.version) {
+            let trailer = if header.watermark { WATERMARK_LEN } else { 0 };
+            let registers = dense_body(p, bytes, header.body_at, trailer)?;
+            let watermark = split_watermark(bytes, header.body_at, header.watermark)?.1;
+            validate_registers(p, registers)?;
+            let histogram = self.watermark.map(|_| simd::histogram(registers));
+            (self.raise_to(registers), histogram, watermark)
+        } else {
+            let (body, watermark) = split_watermark(bytes, header.body_at, header.watermark)?;
+            let m = self.num_registers();
+            // Decoding first checks the whole sketch, so that a corrupt one
+            // changes nothing.
+            let threshold = self.sparse_threshold;
+            let other = decompress_registers(p, body, header.body_at, max_rank(p), threshold)?;
+            let raised = if self.is_sparse() {
+                let raised = self.registers.merge(&other, m);
+                if raised {
+                    self.recount();
+                }
+                self.check_promotion();
+                raised
+            } else {
+                match other {
+                    Registers::Dense(ref registers) => self.raise_to(registers),
+                    Registers::Sparse(ref entries) => {
+                        self.raise_registers(entries.iter().map(|&e| sparse::decode(e))) > 0
+                    }
+                }
+            };
+            (
+                raised,
+                self.watermark.map(|_| other.histogram(m)),
+                watermark,
+            )
+        };
+        if let (Some(highest), Some(histogram)) = (self.watermark, histogram) {
+            let count = header.estimator.estimate(p, &histogram);
+            self.watermark = Some(highest.max(count).max(watermark.unwrap_or(0.)));
+        }
+        self.items_added = self.items_added.saturating_add(header.items_added);
+        Ok(raised)
+    }
+}
+
+/// The header of a sketch hashing with `build_hasher`, with `flags` added
+/// to the hasher's, in the first `len` bytes of the array.
+pub(crate) fn header_bytes<S: StableBuildHasher>(
+    version: u8,
+    precision: u8,
+    build_hasher: &S,
+    flags: u8,
+) -> ([u8; MAX_HEADER_LEN], usize) {
+    let mut out = [0u8; MAX_HEADER_LEN];
+    out[..4].copy_from_slice(&MAGIC);
+    out[4] = version;
+    out[5] = precision;
+    let (backend, keys) = recorded_hasher(build_hasher);
+    let flags = flags | backend.code() << BACKEND_SHIFT;
+    match keys {
+        Some((k0, k1)) => {
+            out[6] = flags | FLAG_KEYED;
+            out[7..15].copy_from_slice(&k0.to_le_bytes());
+            out[15..23].copy_from_slice(&k1.to_le_bytes());
+            (out, HEADER_LEN + 16)
+        }
+        None => {
+            out[6] = flags;
+            (out, HEADER_LEN)
+        }
+    }
+}
+
+/// The backend and keys a sketch records for `build_hasher`: its own, or
+/// for a hasher without keys that isn't the default,
+/// [`HashBackend::Custom`] with the hasher's [`HasherId`] as the first key.
+pub(crate) fn recorded_hasher<S: StableBuildHasher>(
+    build_hasher: &S,
+) -> (HashBackend, Option<(u64, u64)>) {
+    let keys = build_hasher.keys();
+    if S::BACKEND != HashBackend::Native || keys.is_some() {
+        return (S::BACKEND, keys);
+    }
+    let id = HasherId::of(build_hasher);
+    if id == HasherId::of(&DefaultBuildHasher::default()) {
+        (HashBackend::Native, None)
+    } else {
+        (HashBackend::Custom, Some((id.fingerprint(), 0)))
+    }
+}
+
+/// Rebuilds the hasher recorded as `backend` and `keys`, or returns `None`
+/// if `S` would record another.
+pub(crate) fn decode_hasher<S: StableBuildHasher>(
+    backend: HashBackend,
+    keys: Option<(u64, u64)>,
+) -> Option<S> {
+    let own_keys = if backend == HashBackend::Custom {
+        None
+    } else {
+        keys
+    };
+    let build_hasher = S::from_keys(own_keys)?;
+    if recorded_hasher(&build_hasher) == (backend, keys) {
+        Some(build_hasher)
+    } else {
+        None
+    }
+}
+
+/// The fields shared by every version of the format.
+pub(crate) struct Header<S> {
+    pub(crate) version: u8,
+    pub(crate) precision: u8,
+    pub(crate) build_hasher: S,
+    /// Whether a watermark ends the sketch.
+    pub(crate) watermark: bool,
+    pub(crate) estimator: BuiltinEstimator,
+    /// The number of items added, or 0 for versions without it.
+    pub(crate) items_added: u64,
+    /// The offset of the first byte after the header.
+    pub(crate) body_at: usize,
+}
+
+/// The fields shared by every version of the format, before the hasher is
+/// checked against a hasher type.
+pub(crate) struct RawHeader {
+    pub(crate) version: u8,
+    pub(crate) precision: u8,
+    pub(crate) backend: HashBackend,
+    pub(crate) keys: Option<(u64, u64)>,
+    /// Whether the sketch is of 32-bit hashes.
+    pub(crate) hash32: bool,
+    /// Whether a watermark ends the sketch.
+    pub(crate) watermark: bool,
+    pub(crate) estimator: BuiltinEstimator,
+    /// The number of items added, or 0 for versions without it.
+    pub(crate) items_added: u64,
+    /// The offset of the first byte after the header.
+    pub(crate) body_at: usize,
+}
+
+impl RawHeader {
+    /// The highest rank a register of the sketch can hold.
+    pub(crate) fn max_rank(&self) -> u8 {
+        if self.hash32 {
+            hash32::max_rank(self.precision)
+        } else {
+            max_rank(self.precision)
+        }
+    }
+}
+
+/// Reads the header of bytes in one of `versions`, from a sketch of 64-bit
+/// hashes.
+pub(crate) fn read_header<S: StableBuildHasher>(
+    bytes: &[u8],
+    versions: &[u8],
+) -> Result<Header<S>, DecodeError> {
+    read_header_of(bytes, versions, false)
+}
+
+/// Reads the header of bytes in one of `versions`, from a sketch of 32-bit
+/// hashes if `hash32` and of 64-bit ones otherwise.
+pub(crate) fn read_header_of<S: StableBuildHasher>(
+    bytes: &[u8],
+    versions: &[u8],
+    hash32: bool,
+) -> Result<Header<S>, DecodeError> {
+    let raw = read_raw_header(bytes, versions)?;
+    if raw.hash32 != hash32 {
+        return Err(DecodeError::HasherMismatch);
+    }
+    let build_hasher = decode_hasher(raw.backend, raw.keys).ok_or(DecodeError::HasherMismatch)?;
+    Ok(Header {
+        version: raw.version,
+        precision: raw.precision,
+        build_hasher,
+        watermark: raw.watermark,
+        estimator: raw.estimator,
+        items_added: raw.items_added,
+        body_at: raw.body_at,
+    })
+}
+
+/// Reads the header of bytes in one of `versions`, whatever their hasher.
+pub(crate) fn read_raw_header(bytes: &[u8], versions: &[u8]) -> Result<RawHeader, DecodeError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(DecodeError::Truncated {
+            expected: HEADER_LEN,
+            actual: bytes.len(),
+        });
+    }
+    let mut magic = [0u8; 4];
+    magic.copy_from_slice(&bytes[..4]);
+    if magic != MAGIC {
+        return Err(DecodeError::BadMagic(magic));
+    }
+    let version = bytes[4];
+    if !versions.contains(&version) {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+    let precision = bytes[5];
+    if !(MIN_PRECISION..=MAX_PRECISION).contains(&precision) {
+        return Err(DecodeError::InvalidPrecision(precision));
+    }
+    let flags = bytes[6];
+    let backend = backend_of(flags)?;
+    let keyed = flags & FLAG_KEYED != 0;
+    let hash32 = flags & FLAG_HASH32 != 0;
+    if hash32 && precision > hash32::MAX_PRECISION {
+        return Err(DecodeError::InvalidPrecision(precision));
+    }
+    let keys_end = HEADER_LEN + if keyed { 16 } else { 0 };
+    let body_at = keys_end + if is_counted(version) { ITEMS_LEN } else { 0 };
+    if bytes.len() < body_at {
+        return Err(DecodeError::Truncated {
+            expected: body_at,
+            actual: bytes.len(),
+        });
+    }
+    let keys = if keyed {
+        Some((read_u64(&bytes[7..]), read_u64(&bytes[15..])))
+    } else {
+        None
+    };
+    Ok(RawHeader {
+        version,
+        precision,
+        backend,
+        keys,
+        hash32,
+        watermark: flags & FLAG_WATERMARK != 0,
+        // Two bits hold every code there is.
+        estimator: BuiltinEstimator::from_code(flags >> ESTIMATOR_SHIFT).unwrap_or_default(),
+        items_added: if is_counted(version) {
+            read_u64(&bytes[keys_end..])
+        } else {
+            0
+        },
+        body_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FLAG_WATERMARK, HEADER_LEN, WATERMARK_LEN};
+    use error::DecodeError;
+    use simulation::splitmix64;
+    use {max_rank, BuiltinEstimator, HashBackend, HyperLogLog, HyperLogLog32, SeededState};
+
+    fn unhex(s: &str) -> Vec<u8> {
+        let digits: Vec<u8> = s
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .flat_map(|line| line.bytes())
+            .filter(|b| b.is_ascii_hexdigit())
+            .collect();
+        digits
+            .chunks(2)
+            .map(|pair| u8::from_str_radix(::std::str::from_utf8(pair).unwrap(), 16).unwrap())
+            .collect()
+    }
+
+    fn golden_registers() -> Vec<u8> {
+        (0..16).map(|i| i % 5).collect()
+    }
+
+    fn golden_sketch() -> HyperLogLog {
+        HyperLogLog::from_dense(4, golden_registers(), Default::default())
+    }
+
+    #[test]
+    fn golden_default() {
+        let fixture = unhex(include_str!("../tests/fixtures/p4_default.hex"));
+        let h = golden_sketch();
+        assert_eq!(h.to_bytes(), fixture);
+        let back: HyperLogLog = HyperLogLog::from_bytes(&fixture).unwrap();
+        assert_eq!(
+            back.registers().collect::<Vec<_>>(),
+            h.registers().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn golden_seeded() {
+        let fixture = unhex(include_str!("../tests/fixtures/p4_seeded.hex"));
+        let keys = SeededState::with_keys(0x0706_0504_0302_0100, 0x0f0e_0d0c_0b0a_0908);
+        let h = HyperLogLog::from_dense(4, golden_registers(), keys);
+        assert_eq!(h.to_bytes(), fixture);
+        let back: HyperLogLog<SeededState> = HyperLogLog::from_bytes(&fixture).unwrap();
+        assert_eq!(back.build_hasher, h.build_hasher);
+        assert_eq!(
+            back.registers().collect::<Vec<_>>(),
+            h.registers().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn round_trip_preserves_count() {
+        let mut h = HyperLogLog::new(1 << 10);
+        for i in 0..50_000u64 {
+            h.add(i);
+        }
+        let back: HyperLogLog = HyperLogLog::from_bytes(&h.to_bytes()).unwrap();
+        assert_eq!(
+            back.registers().collect::<Vec<_>>(),
+            h.registers().collect::<Vec<_>>()
+        );
+        assert_eq!(back.count(), h.count());
+    }
+
+    #[test]
+    fn rejects_malformed_input() {
+        let good = golden_sketch().to_bytes();
+        let decode = |b: &[u8]| HyperLogLog::from_bytes(b).map(|_: HyperLogLog| ());
+
+        for len in 0..good.len() {
+            match decode(&good[..len]) {
+                Err(DecodeError::Truncated { .. }) => {}
+                other => panic!("length {}: {:?}", len, other),
+            }
+        }
+
+        let mut trailing = good.clone();
+        trailing.push(0);
+        assert!(matches!(
+            decode(&trailing),
+            Err(DecodeError::TrailingBytes { .. })
+        ));
+
+        let mut magic = good.clone();
+        magic[0] = b'X';
+        assert!(matches!(decode(&magic), Err(DecodeError::BadMagic(_))));
+
+        let mut version = good.clone();
+        version[4] = 3;
+        assert_eq!(decode(&version), Err(DecodeError::UnsupportedVersion(3)));
+
+        let mut precision = good.clone();
+        precision[5] = 0;
+        assert_eq!(decode(&precision), Err(DecodeError::InvalidPrecision(0)));
+        precision[5] = 200;
+        assert_eq!(decode(&precision), Err(DecodeError::InvalidPrecision(200)));
+
+        let mut register = good.clone();
+        register[7 + 3] = 62;
+        assert_eq!(
+            decode(&register),
+            Err(DecodeError::InvalidRegister {
+                index: 3,
+                value: 62
+            })
+        );
+
+        // Keyed bytes can't be decoded into an unkeyed hasher and vice versa.
+        let seeded = HyperLogLog::new_seeded(1 << 4, 1).to_bytes();
+        assert_eq!(decode(&seeded), Err(DecodeError::HasherMismatch));
+        assert!(HyperLogLog::<SeededState>::from_bytes(&good).is_err());
+
+        let mut backend = good.clone();
+        backend[6] = 7 << 1;
+        assert_eq!(decode(&backend), Err(DecodeError::UnknownHashBackend(7)));
+        assert_eq!(
+            HashBackend::of_encoded(&backend),
+            Err(DecodeError::UnknownHashBackend(7))
+        );
+    }
+
+    #[test]
+    fn compressed_round_trips_random_fills() {
+        let mut state = 54;
+        for &p in &[4u8, 9, 14] {
+            for &fill in &[0.0, 0.002, 0.05, 0.5, 1.0] {
+                let registers: Vec<u8> = (0..1usize << p)
+                    .map(|_| {
+                        let draw = splitmix64(&mut state);
+                        if (draw >> 11) as f64 / (1u64 << 53) as f64 >= fill {
+                            0
+                        } else if draw & 1 == 0 {
+                            1 + (draw >> 1) as u8 % 6
+                        } else {
+                            1 + (draw >> 1) as u8 % max_rank(p)
+                        }
+                    })
+                    .collect();
+                let keys = SeededState::with_keys(state, !state);
+                let h = HyperLogLog::from_dense(p, registers.clone(), keys);
+                let bytes = h.to_bytes_compressed();
+                assert_eq!(bytes[4], 2);
+                let back: HyperLogLog<SeededState> = HyperLogLog::from_bytes(&bytes).unwrap();
+                assert_eq!(back.registers().collect::<Vec<_>>(), registers);
+                assert_eq!(back.build_hasher, h.build_hasher);
+                assert!(bytes.len() <= h.to_bytes().len(), "p {} fill {}", p, fill);
+            }
+        }
+    }
+
+    #[test]
+    fn compressed_sparse_sketch_is_small() {
+        let mut h = HyperLogLog::new(1 << 14);
+        for i in 0..50u64 {
+            h.add(i);
+        }
+        let bytes = h.to_bytes_compressed();
+        assert!(bytes.len() < 160, "{} bytes", bytes.len());
+        let back: HyperLogLog = HyperLogLog::from_bytes(&bytes).unwrap();
+        assert_eq!(back.count(), h.count());
+        assert_eq!(HyperLogLog::new(1 << 14).to_bytes_compressed().len(), 7 + 3);
+    }
+
+    #[test]
+    fn decoding_allocates_only_for_set_registers() {
+        // 13 bytes claiming 2^32 empty registers, which would take 4 GiB.
+        let huge = *b"HLLR\x02\x20\x00\x0f\xff\xff\xff\xff\xf3";
+        assert_eq!(
+            HyperLogLog::from_bytes(&huge).map(|_: HyperLogLog| ()),
+            Err(DecodeError::InvalidPrecision(32))
+        );
+
+        // The largest sketch there is decodes sparse, as small as it was.
+        let mut h = HyperLogLog::new(1 << 24);
+        (0..100u32).for_each(|i| h.add(i));
+        let back: HyperLogLog = HyperLogLog::from_bytes(&h.to_bytes_compressed()).unwrap();
+        assert!(back.is_sparse());
+        assert!(back.memory_bytes() < 4096, "{} bytes", back.memory_bytes());
+        assert_eq!(back, h);
+        assert_eq!(back.count(), h.count());
+    }
+
+    #[test]
+    fn rejects_malformed_compressed_input() {
+        let decode = |b: &[u8]| HyperLogLog::from_bytes(b).map(|_: HyperLogLog| ());
+        let good = golden_sketch().to_bytes_compressed();
+        decode(&good).unwrap();
+        for len in 0..good.len() {
+            match decode(&good[..len]) {
+                Err(DecodeError::Truncated { .. }) => {}
+                other => panic!("length {}: {:?}", len, other),
+            }
+        }
+        let mut trailing = good.clone();
+        trailing.push(0);
+        assert!(matches!(
+            decode(&trailing),
+            Err(DecodeError::TrailingBytes { .. })
+        ));
+
+        let header = &good[..7];
+        let with_body = |body: &[u8]| [header, body].concat();
+        // A one and a run of sixteen zeros, or a run far past the end.
+        assert_eq!(
+            decode(&with_body(&[0x10, 0xf1])),
+            Err(DecodeError::RegisterCount {
+                precision: 4,
+                count: 17
+            })
+        );
+        assert!(matches!(
+            decode(&with_body(&[
+                0x0f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x10
+            ])),
+            Err(DecodeError::RegisterCount { .. })
+        ));
+        // Fourteen zeros and two ones, then a nibble of padding.
+        assert!(decode(&with_body(&[0x0d, 0x11, 0x10])).is_ok());
+        assert_eq!(
+            decode(&with_body(&[0x0d, 0x11, 0x13])),
+            Err(DecodeError::NonZeroPadding)
+        );
+        // Escaped values are still checked against the precision.
+        assert_eq!(
+            decode(&with_body(&[0x0e, 0x1f, 0x3e])),
+            Err(DecodeError::InvalidRegister {
+                index: 15,
+                value: 62
+            })
+        );
+    }
+
+    #[test]
+    fn round_trips_the_watermark() {
+        let mut h = HyperLogLog::new_seeded(1 << 6, 86);
+        h.extend(0..100u32);
+        let plain = h.to_bytes();
+        h.set_monotonic(true);
+        h.watermark = Some(h.count() + 1.5);
+        for bytes in &[h.to_bytes(), h.to_bytes_compressed()] {
+            assert_eq!(bytes[6] & FLAG_WATERMARK, FLAG_WATERMARK);
+            let back: HyperLogLog<SeededState> = HyperLogLog::from_bytes(bytes).unwrap();
+            assert_eq!(back, h);
+            assert_eq!(back.count(), h.count());
+        }
+        // The watermark follows the registers, which are unchanged.
+        let bytes = h.to_bytes();
+        assert_eq!(bytes.len(), plain.len() + WATERMARK_LEN);
+        assert_eq!(bytes[7..plain.len()], plain[7..]);
+
+        let mut bad = bytes.clone();
+        let at = bad.len() - WATERMARK_LEN;
+        bad[at..].copy_from_slice(&f64::NAN.to_le_bytes());
+        assert_eq!(
+            HyperLogLog::<SeededState>::from_bytes(&bad).map(|_| ()),
+            Err(DecodeError::InvalidWatermark)
+        );
+        // Without the flag, the watermark is trailing bytes.
+        let mut unflagged = bytes.clone();
+        unflagged[6] &= !FLAG_WATERMARK;
+        assert_eq!(
+            HyperLogLog::<SeededState>::from_bytes(&unflagged).map(|_| ()),
+            Err(DecodeError::TrailingBytes {
+                expected: plain.len(),
+                actual: bytes.len()
+            })
+        );
+        assert_eq!(
+            HyperLogLog::<SeededState>::from_bytes(&bytes[..HEADER_LEN + 40]).map(|_| ()),
+            Err(DecodeError::Truncated {
+                expected: bytes.len(),
+                actual: HEADER_LEN + 40
+            })
+        );
+    }
+
+    #[cfg(all(feature = "xxhash", feature = "murmur3"))]
+    #[test]
+    fn records_hash_backend() {
+        use {Murmur3State, Xxh3State};
+
+        let mut xxh3 = HyperLogLog::with_hasher(1 << 4, Xxh3State::with_seed(5));
+        xxh3.add_bytes("foo");
+        let bytes = xxh3.to_bytes();
+        assert_eq!(bytes[6], 1 << 1 | 1);
+        assert_eq!(HashBackend::of_encoded(&bytes), Ok(HashBackend::Xxh3));
+        let back: HyperLogLog<Xxh3State> = HyperLogLog::from_bytes(&bytes).unwrap();
+        assert_eq!(back.build_hasher, Xxh3State::with_seed(5));
+        assert_eq!(
+            back.registers().collect::<Vec<_>>(),
+            xxh3.registers().collect::<Vec<_>>()
+        );
+
+        // Unseeded, the marker alone tells the sketches apart.
+        let murmur = HyperLogLog::with_hasher(1 << 4, Murmur3State::new()).to_bytes();
+        assert_eq!(murmur[6], 2 << 1);
+        assert_eq!(HashBackend::of_encoded(&murmur), Ok(HashBackend::Murmur3));
+        assert_eq!(
+            HashBackend::of_encoded(&golden_sketch().to_bytes()),
+            Ok(HashBackend::Native)
+        );
+        assert!(HyperLogLog::<Murmur3State>::from_bytes(&bytes).is_err());
+        assert_eq!(
+            HyperLogLog::from_bytes(&murmur).map(|_: HyperLogLog| ()),
+            Err(DecodeError::HasherMismatch)
+        );
+        assert_eq!(
+            HyperLogLog::<Xxh3State>::from_bytes(&golden_sketch().to_bytes()).map(|_| ()),
+            Err(DecodeError::HasherMismatch)
+        );
+    }
+
+    #[test]
+    fn merges_bytes_as_decoding_would() {
+        let mut state = 100;
+        let mut sketch = |n: u64| {
+            let mut h = HyperLogLog::new_seeded(1 << 10, 100);
+            (0..n).for_each(|_| h.add_hashed(splitmix64(&mut state)));
+            h
+        };
+        // Sparse and dense sketches, with each other and with themselves.
+        for &(into, from) in &[
+            (0, 0),
+            (0, 40),
+            (30, 40),
+            (30, 5_000),
+            (5_000, 40),
+            (5_000, 0),
+        ] {
+            for &monotonic in &[false, true] {
+                let mut h = sketch(into);
+                h.set_monotonic(monotonic);
+                let mut other = sketch(from);
+                if monotonic {
+                    other.set_monotonic(true);
+                    other.watermark = Some(other.count() + 3.);
+                    other.set_estimator(BuiltinEstimator::Ertl);
+                }
+                for bytes in &[other.to_bytes(), other.to_bytes_compressed(), h.to_bytes()] {
+                    let decoded = HyperLogLog::from_bytes(bytes).unwrap();
+                    let mut expected = h.clone();
+                    let raised = expected.merge(&decoded).unwrap();
+                    let mut merged = h.clone();
+                    assert_eq!(merged.merge_from_bytes(bytes), Ok(raised));
+                    assert_eq!(merged, expected);
+                    assert_eq!(merged.count(), expected.count());
+                    assert_eq!(merged.items_added(), expected.items_added());
+                    assert_eq!(merged.merge_from_bytes(bytes), Ok(false));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn merging_bytes_fails_as_decoding_and_merging_would() {
+        let mut state = 100;
+        let mut h = HyperLogLog::new_seeded(1 << 8, 100);
+        (0..300).for_each(|_| h.add_hashed(splitmix64(&mut state)));
+        let before = h.clone();
+        let mut other = HyperLogLog::new_seeded(1 << 8, 100);
+        (0..3_000).for_each(|_| other.add_hashed(splitmix64(&mut state)));
+
+        let coarser = HyperLogLog::new_seeded(1 << 9, 100).to_bytes();
+        assert_eq!(
+            h.merge_from_bytes(&coarser),
+            Err(DecodeError::PrecisionMismatch {
+                expected: 8,
+                found: 9
+            })
+        );
+        let reseeded = HyperLogLog::new_seeded(1 << 8, 101).to_bytes_compressed();
+        assert_eq!(
+            h.merge_from_bytes(&reseeded),
+            Err(DecodeError::HasherMismatch)
+        );
+        let narrow = HyperLogLog32::with_hasher(1 << 8, h.build_hasher).to_bytes();
+        assert_eq!(
+            h.merge_from_bytes(&narrow),
+            Err(DecodeError::HasherMismatch)
+        );
+
+        // A fault in the last register, or the last byte, of either format
+        // leaves this sketch unchanged.
+        let mut registers: Vec<u8> = other.registers().collect();
+        registers[255] = max_rank(8) + 1;
+        let invalid = HyperLogLog::from_dense(8, registers, h.build_hasher);
+        let compressed = other.to_bytes_compressed();
+        let mut trailing = compressed.clone();
+        trailing.push(0);
+        let corrupt = [
+            invalid.to_bytes(),
+            other.to_bytes()[..HEADER_LEN + 100].to_vec(),
+            invalid.to_bytes_compressed(),
+            compressed[..compressed.len() - 1].to_vec(),
+            trailing,
+        ];
+        let empty = HyperLogLog::new_seeded(1 << 8, 100);
+        for bytes in &corrupt {
+            let error = HyperLogLog::<SeededState>::from_bytes(bytes).unwrap_err();
+            assert_eq!(h.merge_from_bytes(bytes), Err(error.clone()));
+            assert_eq!(h, before);
+            assert_eq!(h.count(), before.count());
+            assert_eq!(h.items_added(), before.items_added());
+            let mut sparse = empty.clone();
+            assert_eq!(sparse.merge_from_bytes(bytes), Err(error));
+            assert_eq!(sparse, empty);
+            assert!(sparse.is_sparse());
+        }
+        let mut expected = before.clone();
+        expected.merge(&other).unwrap();
+        h.merge_from_bytes(&other.to_bytes()).unwrap();
+        assert_eq!(h, expected);
+        assert_eq!(h.items_added(), expected.items_added());
+    }
+}
@@ -0,0 +1,628 @@
+use alloc::string::String;
+#[cfg(feature = "arrow")]
+use arrow_schema::DataType;
+use core::error::Error;
+use core::fmt;
+
+use HasherId;
+
+/// Returned when two sketches cannot be combined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeError {
+    /// The sketches have different precisions (register counts `2^left` and `2^right`).
+    PrecisionMismatch { left: u8, right: u8 },
+    /// The sketches have different sizes: linear counters with different
+    /// numbers of bits, KMV sketches keeping different numbers of hashes, or
+    /// HyperMinHash sketches keeping different numbers of sub-bucket bits.
+    SizeMismatch { left: usize, right: usize },
+    /// The sketches are of different types.
+    KindMismatch,
+    /// The sketch at `index` in a batch has a different precision from the
+    /// first one's.
+    PrecisionMismatchAt {
+        index: usize,
+        expected: u8,
+        found: u8,
+    },
+    /// A batch merge was given no sketches.
+    NoSketches,
+    /// The sketches couldn't be folded to a common precision.
+    Fold(FoldError),
+    /// The sketches hash items differently: their hashers are of different
+    /// hash functions, or have different keys.
+    HasherMismatch { left: HasherId, right: HasherId },
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MergeError::PrecisionMismatch { left, right } => write!(
+                f,
+                "cannot merge sketches with different precisions: {} ({} registers) vs {} ({} registers)",
+                left,
+                1usize << left,
+                right,
+                1usize << right
+            ),
+            MergeError::SizeMismatch { left, right } => write!(
+                f,
+                "cannot merge sketches with different sizes: {} vs {}",
+                left, right
+            ),
+            MergeError::KindMismatch => write!(f, "cannot merge sketches of different types"),
+            MergeError::PrecisionMismatchAt {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "cannot merge sketch {}: precision {} ({} registers) differs from the first sketch's {} ({} registers)",
+                index,
+                found,
+                1usize << found,
+                expected,
+                1usize << expected
+            ),
+            MergeError::NoSketches => write!(f, "cannot merge an empty collection of sketches"),
+            MergeError::Fold(ref e) => write!(f, "cannot merge at a common precision: {}", e),
+            MergeError::HasherMismatch { left, right } => write!(
+                f,
+                "cannot merge sketches hashed differently: hasher {:016x} vs {:016x}",
+                left.fingerprint(),
+                right.fingerprint()
+            ),
+        }
+    }
+}
+
+impl Error for MergeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            MergeError::Fold(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Returned when a sketch cannot be folded to the requested precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FoldError {
+    /// Folding can only lower the precision, never raise it.
+    PrecisionTooHigh { current: u8, requested: u8 },
+    /// The requested precision is below the smallest a sketch can have.
+    PrecisionTooLow { requested: u8, minimum: u8 },
+}
+
+impl fmt::Display for FoldError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FoldError::PrecisionTooHigh { current, requested } => write!(
+                f,
+                "cannot fold a sketch of precision {} up to precision {}",
+                current, requested
+            ),
+            FoldError::PrecisionTooLow { requested, minimum } => write!(
+                f,
+                "precision {} is below the minimum of {}",
+                requested, minimum
+            ),
+        }
+    }
+}
+
+impl Error for FoldError {}
+
+/// Returned when a sketch can't be built with the requested configuration.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildError {
+    /// The register count is below the minimum of 16 (this includes zero).
+    TooFewRegisters { registers: usize, minimum: usize },
+    /// The register count is above the maximum: 2^24, or 2^16 for a
+    /// [`HyperLogLog32`](::HyperLogLog32).
+    TooManyRegisters { registers: usize, maximum: usize },
+    /// The register count isn't a power of two.
+    NotPowerOfTwo(usize),
+    /// The target relative error isn't in `(0, 1]`.
+    InvalidErrorRate(f64),
+    /// The target relative error is finer than the largest sketch can reach.
+    ErrorRateUnreachable { target: f64, best: f64 },
+    /// The memory budget can't hold the registers of the smallest sketch.
+    BudgetTooSmall { bytes: usize, minimum: usize },
+    /// The precision is outside 4 to 24 (4 to 18 for
+    /// [`HyperLogLog::with_precision`](::HyperLogLog::with_precision)), or
+    /// too large to address on this platform.
+    InvalidPrecision(u8),
+    /// Both a precision and a target error rate were given.
+    PrecisionAndErrorRate { precision: u8, error_rate: f64 },
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::TooFewRegisters { registers, minimum } => write!(
+                f,
+                "register count {} is below the minimum of {}",
+                registers, minimum
+            ),
+            BuildError::TooManyRegisters { registers, maximum } => write!(
+                f,
+                "register count {} is above the maximum of {}",
+                registers, maximum
+            ),
+            BuildError::NotPowerOfTwo(m) => {
+                write!(f, "register count {} is not a power of two", m)
+            }
+            BuildError::InvalidErrorRate(e) => {
+                write!(f, "target error rate {} is not in (0, 1]", e)
+            }
+            BuildError::ErrorRateUnreachable { target, best } => write!(
+                f,
+                "target error rate {} is below the best achievable {}",
+                target, best
+            ),
+            BuildError::BudgetTooSmall { bytes, minimum } => write!(
+                f,
+                "memory budget of {} bytes is below the minimum of {}",
+                bytes, minimum
+            ),
+            BuildError::InvalidPrecision(p) => write!(f, "precision {} is not supported", p),
+            BuildError::PrecisionAndErrorRate {
+                precision,
+                error_rate,
+            } => write!(
+                f,
+                "both precision {} and error rate {} were given; choose one",
+                precision, error_rate
+            ),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// Returned when a register array can't be turned into a sketch, or a
+/// register change can't be applied to one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RegisterError {
+    /// The array's length isn't a supported register count.
+    RegisterCount(BuildError),
+    /// A register holds a rank above `maximum`, the largest a 64-bit hash
+    /// can produce at the array's precision.
+    InvalidRegister {
+        index: usize,
+        value: u8,
+        maximum: u8,
+    },
+    /// A register index past the last of the sketch's `registers`.
+    IndexOutOfRange { index: usize, registers: usize },
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RegisterError::RegisterCount(e) => e.fmt(f),
+            RegisterError::InvalidRegister {
+                index,
+                value,
+                maximum,
+            } => write!(
+                f,
+                "register {} holds impossible rank {} (the maximum is {})",
+                index, value, maximum
+            ),
+            RegisterError::IndexOutOfRange { index, registers } => write!(
+                f,
+                "register index {} is out of range for {} registers",
+                index, registers
+            ),
+        }
+    }
+}
+
+impl Error for RegisterError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            RegisterError::RegisterCount(ref e) => Some(e),
+            RegisterError::InvalidRegister { .. } | RegisterError::IndexOutOfRange { .. } => None,
+        }
+    }
+}
+
+/// Returned when bytes cannot be decoded into a sketch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended before the sketch did. For compressed sketches,
+    /// `expected` is only a lower bound.
+    Truncated { expected: usize, actual: usize },
+    /// The input has bytes left over after the sketch.
+    TrailingBytes { expected: usize, actual: usize },
+    /// The input doesn't start with the sketch magic number.
+    BadMagic([u8; 4]),
+    /// The input was written by a newer, unknown version of the format.
+    UnsupportedVersion(u8),
+    /// The precision is outside the supported range.
+    InvalidPrecision(u8),
+    /// The number of registers doesn't match the precision.
+    RegisterCount { precision: u8, count: usize },
+    /// A register holds a rank that a 64-bit hash can't produce at this precision.
+    InvalidRegister { index: usize, value: u8 },
+    /// Entry `entry` of a [`SketchDelta`](::SketchDelta) has a register
+    /// index past the last register or not above the previous entry's.
+    InvalidIndex { entry: usize, index: u32 },
+    /// The recorded hasher keys, hash backend or hash width don't fit the
+    /// sketch type being decoded into, or the sketch being merged into
+    /// hashes differently.
+    HasherMismatch,
+    /// A sketch of precision `found` can't be merged into one of precision
+    /// `expected`.
+    PrecisionMismatch { expected: u8, found: u8 },
+    /// The recorded hash backend is one this version doesn't know.
+    UnknownHashBackend(u8),
+    /// A compressed sketch ends in a half-used byte whose unused nibble isn't
+    /// zero.
+    NonZeroPadding,
+    /// The text isn't unpadded URL-safe base64; `index` is the offset of the
+    /// first bad character.
+    InvalidBase64 { index: usize },
+    /// A [`TypedHyperLogLog`](::TypedHyperLogLog) was tagged with `found`
+    /// rather than the `expected` item type.
+    TagMismatch { expected: String, found: String },
+    /// The watermark of a monotonic sketch is negative or not finite.
+    InvalidWatermark,
+    /// A [`HyperLogLogRing`](::HyperLogLogRing) has no buckets, a zero
+    /// bucket duration or buckets of different precisions.
+    InvalidRing,
+    /// Reading the sketch from a stream failed with this error.
+    #[cfg(feature = "std")]
+    Io(std::io::ErrorKind),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::Truncated { expected, actual } => write!(
+                f,
+                "truncated sketch: expected {} bytes, got {}",
+                expected, actual
+            ),
+            DecodeError::TrailingBytes { expected, actual } => write!(
+                f,
+                "trailing bytes after sketch: expected {} bytes, got {}",
+                expected, actual
+            ),
+            DecodeError::BadMagic(magic) => write!(f, "bad magic number {:02x?}", magic),
+            DecodeError::UnsupportedVersion(v) => write!(f, "unsupported format version {}", v),
+            DecodeError::InvalidPrecision(p) => write!(f, "invalid precision {}", p),
+            DecodeError::RegisterCount { precision, count } => write!(
+                f,
+                "expected {} registers for precision {}, got {}",
+                1usize << precision,
+                precision,
+                count
+            ),
+            DecodeError::InvalidRegister { index, value } => {
+                write!(f, "register {} holds impossible rank {}", index, value)
+            }
+            DecodeError::InvalidIndex { entry, index } => write!(
+                f,
+                "delta entry {} has out-of-range or unordered register index {}",
+                entry, index
+            ),
+            DecodeError::HasherMismatch => {
+                f.write_str("recorded hasher doesn't match the requested hasher type")
+            }
+            DecodeError::PrecisionMismatch { expected, found } => write!(
+                f,
+                "cannot merge a sketch of precision {} into one of precision {}",
+                found, expected
+            ),
+            DecodeError::UnknownHashBackend(code) => write!(f, "unknown hash backend {}", code),
+            DecodeError::NonZeroPadding => {
+                f.write_str("non-zero padding after compressed registers")
+            }
+            DecodeError::InvalidBase64 { index } => {
+                write!(f, "invalid base64 at character {}", index)
+            }
+            DecodeError::TagMismatch {
+                ref expected,
+                ref found,
+            } => write!(f, "sketch of {:?} items, expected {:?}", found, expected),
+            DecodeError::InvalidWatermark => f.write_str("invalid monotonic watermark"),
+            DecodeError::InvalidRing => f.write_str("invalid sketch ring layout"),
+            #[cfg(feature = "std")]
+            DecodeError::Io(kind) => write!(f, "error reading sketch: {}", kind),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Returned when serialized sketches can't be merged by
+/// [`merge_serialized`](::merge_serialized).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeBytesError {
+    /// The existing value couldn't be decoded.
+    Existing(DecodeError),
+    /// The operand at `index` couldn't be decoded.
+    Operand { index: usize, error: DecodeError },
+    /// The operand at `index` has a different precision from the first sketch.
+    PrecisionMismatch {
+        index: usize,
+        expected: u8,
+        found: u8,
+    },
+    /// The operand at `index` was hashed with a different hash backend, keys
+    /// or hash width from the first sketch.
+    HasherMismatch { index: usize },
+    /// There was neither an existing value nor any operands.
+    NoSketches,
+}
+
+impl fmt::Display for MergeBytesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MergeBytesError::Existing(ref e) => write!(f, "bad existing sketch: {}", e),
+            MergeBytesError::Operand { index, ref error } => {
+                write!(f, "bad sketch in operand {}: {}", index, error)
+            }
+            MergeBytesError::PrecisionMismatch {
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "cannot merge operand {}: precision {} differs from the first sketch's {}",
+                index, found, expected
+            ),
+            MergeBytesError::HasherMismatch { index } => write!(
+                f,
+                "cannot merge operand {}: hashed differently from the first sketch",
+                index
+            ),
+            MergeBytesError::NoSketches => f.write_str("no sketches to merge"),
+        }
+    }
+}
+
+impl Error for MergeBytesError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            MergeBytesError::Existing(ref e) | MergeBytesError::Operand { error: ref e, .. } => {
+                Some(e)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returned when bytes cannot be read as a Redis HyperLogLog string.
+#[cfg(feature = "redis-compat")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedisCodecError {
+    /// The input ended before the header or the dense registers did, or in
+    /// the middle of a sparse opcode.
+    Truncated { expected: usize, actual: usize },
+    /// A dense sketch has bytes left over after its registers.
+    TrailingBytes { expected: usize, actual: usize },
+    /// The input doesn't start with `HYLL`.
+    BadMagic([u8; 4]),
+    /// The encoding byte is neither dense (0) nor sparse (1).
+    UnknownEncoding(u8),
+    /// The sparse opcodes describe this many registers instead of 16384.
+    SparseRegisterCount(usize),
+    /// A dense register holds a rank above 51, which Redis never writes.
+    InvalidRegister { index: usize, value: u8 },
+}
+
+#[cfg(feature = "redis-compat")]
+impl fmt::Display for RedisCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RedisCodecError::Truncated { expected, actual } => write!(
+                f,
+                "truncated Redis sketch: expected {} bytes, got {}",
+                expected, actual
+            ),
+            RedisCodecError::TrailingBytes { expected, actual } => write!(
+                f,
+                "trailing bytes after dense Redis sketch: expected {} bytes, got {}",
+                expected, actual
+            ),
+            RedisCodecError::BadMagic(magic) => {
+                write!(f, "bad Redis sketch magic number {:02x?}", magic)
+            }
+            RedisCodecError::UnknownEncoding(e) => write!(f, "unknown Redis sketch encoding {}", e),
+            RedisCodecError::SparseRegisterCount(n) => write!(
+                f,
+                "sparse Redis sketch describes {} registers instead of 16384",
+                n
+            ),
+            RedisCodecError::InvalidRegister { index, value } => {
+                write!(f, "register {} holds impossible rank {}", index, value)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "redis-compat")]
+impl Error for RedisCodecError {}
+
+/// Returned when bytes cannot be read as a ClickHouse `uniqHLL12` state, or a
+/// sketch cannot be written as one.
+#[cfg(feature = "clickhouse-compat")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClickHouseCodecError {
+    /// The input ended before the layout byte, the key count, the keys or
+    /// the registers did.
+    Truncated { expected: usize, actual: usize },
+    /// The state has bytes left over after its keys or registers, or its
+    /// keys aren't 8 bytes each.
+    TrailingBytes { expected: usize, actual: usize },
+    /// The layout byte is neither small (0) nor large (1).
+    UnknownLayout(u8),
+    /// A small state claims more than the 16 keys ClickHouse keeps before
+    /// switching to registers.
+    TooManyKeys(u64),
+    /// A register holds a rank above 21, which ClickHouse never writes.
+    InvalidRegister { index: usize, value: u8 },
+    /// The sketch to write doesn't have the 4096 registers (precision 12)
+    /// `uniqHLL12` uses.
+    WrongPrecision(u8),
+}
+
+#[cfg(feature = "clickhouse-compat")]
+impl fmt::Display for ClickHouseCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClickHouseCodecError::Truncated { expected, actual } => write!(
+                f,
+                "truncated ClickHouse uniqHLL12 state: expected {} bytes, got {}",
+                expected, actual
+            ),
+            ClickHouseCodecError::TrailingBytes { expected, actual } => write!(
+                f,
+                "trailing bytes after ClickHouse uniqHLL12 state: expected {} bytes, got {}",
+                expected, actual
+            ),
+            ClickHouseCodecError::UnknownLayout(l) => {
+                write!(f, "unknown ClickHouse uniqHLL12 state layout {}", l)
+            }
+            ClickHouseCodecError::TooManyKeys(n) => write!(
+                f,
+                "small ClickHouse uniqHLL12 state claims {} keys, more than 16",
+                n
+            ),
+            ClickHouseCodecError::InvalidRegister { index, value } => {
+                write!(f, "register {} holds impossible rank {}", index, value)
+            }
+            ClickHouseCodecError::WrongPrecision(p) => {
+                write!(f, "uniqHLL12 states have precision 12, not {}", p)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "clickhouse-compat")]
+impl Error for ClickHouseCodecError {}
+
+/// Returned when bytes cannot be read as an Apache DataSketches HLL sketch.
+#[cfg(feature = "datasketches-compat")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataSketchesCodecError {
+    /// The input ended before its preamble, coupons or registers did.
+    Truncated { expected: usize, actual: usize },
+    /// The family byte isn't 7, so this is some other kind of sketch.
+    WrongFamily(u8),
+    /// The serialization version isn't 1.
+    UnsupportedVersion(u8),
+    /// `lgK` is outside 4 to 21.
+    InvalidLgK(u8),
+    /// The mode is none of `LIST` (0), `SET` (1) or `HLL` (2).
+    UnknownMode(u8),
+    /// The preamble length doesn't match the mode.
+    PreambleMismatch { mode: u8, preamble_ints: u8 },
+    /// An `HLL` mode image holds `HLL_4` (0) or `HLL_6` (1) registers, which
+    /// aren't supported yet.
+    UnsupportedType(u8),
+    /// An updatable coupon table claims more than `2^26` slots.
+    InvalidLgArr(u8),
+    /// A coupon has an address but a value of 0.
+    InvalidCoupon(u32),
+    /// An `HLL_8` register holds a value above 63.
+    InvalidRegister { index: usize, value: u8 },
+}
+
+#[cfg(feature = "datasketches-compat")]
+impl fmt::Display for DataSketchesCodecError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DataSketchesCodecError::Truncated { expected, actual } => write!(
+                f,
+                "truncated DataSketches sketch: expected {} bytes, got {}",
+                expected, actual
+            ),
+            DataSketchesCodecError::WrongFamily(family) => {
+                write!(f, "DataSketches family {} is not HLL (7)", family)
+            }
+            DataSketchesCodecError::UnsupportedVersion(v) => {
+                write!(f, "unsupported DataSketches serialization version {}", v)
+            }
+            DataSketchesCodecError::InvalidLgK(lg_k) => {
+                write!(f, "DataSketches lgK {} is outside 4 to 21", lg_k)
+            }
+            DataSketchesCodecError::UnknownMode(mode) => {
+                write!(f, "unknown DataSketches sketch mode {}", mode)
+            }
+            DataSketchesCodecError::PreambleMismatch {
+                mode,
+                preamble_ints,
+            } => write!(
+                f,
+                "DataSketches preamble is {} ints, wrong for mode {}",
+                preamble_ints, mode
+            ),
+            DataSketchesCodecError::UnsupportedType(t) => write!(
+                f,
+                "DataSketches HLL_{} registers are not supported",
+                if t == 0 { 4 } else { 6 }
+            ),
+            DataSketchesCodecError::InvalidLgArr(lg_arr) => {
+                write!(f, "DataSketches coupon table lgArr {} is too large", lg_arr)
+            }
+            DataSketchesCodecError::InvalidCoupon(c) => {
+                write!(f, "DataSketches coupon {:#x} has no value", c)
+            }
+            DataSketchesCodecError::InvalidRegister { index, value } => {
+                write!(f, "register {} holds impossible value {}", index, value)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "datasketches-compat")]
+impl Error for DataSketchesCodecError {}
+
+/// Returned when an Arrow array can't be added to a sketch or read as
+/// sketch states.
+#[cfg(feature = "arrow")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArrowStateError {
+    /// The array is of a type whose values can't be added, or that can't
+    /// hold states.
+    UnsupportedType(DataType),
+    /// The state at `index` is null.
+    NullState(usize),
+    /// The state at `index` couldn't be decoded.
+    Decode { index: usize, error: DecodeError },
+    /// The state at `index` has a different precision from the sketch.
+    Merge { index: usize, error: MergeError },
+}
+
+#[cfg(feature = "arrow")]
+impl fmt::Display for ArrowStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArrowStateError::UnsupportedType(ref t) => {
+                write!(f, "unsupported Arrow data type {}", t)
+            }
+            ArrowStateError::NullState(index) => write!(f, "sketch state {} is null", index),
+            ArrowStateError::Decode { index, ref error } => {
+                write!(f, "bad sketch state {}: {}", index, error)
+            }
+            ArrowStateError::Merge { index, ref error } => {
+                write!(f, "cannot merge sketch state {}: {}", index, error)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl Error for ArrowStateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ArrowStateError::Decode { ref error, .. } => Some(error),
+            ArrowStateError::Merge { ref error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
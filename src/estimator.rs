@@ -0,0 +1,306 @@
+//! Interchangeable ways of turning the registers into an estimate, for
+//! comparing estimators on the same sketch.
+//!
+//! Every estimator here reads nothing but the precision and the histogram
+//! of register values the sketch keeps up to date, so
+//! [`count_with`](::HyperLogLog::count_with) costs the same at any size. All
+//! four have the same standard error, `1.04 / √m`, at cardinalities well
+//! past the number of registers; they differ at light loads, where the raw
+//! harmonic-mean estimate is biased, and in how much of the precision range
+//! they cover.
+
+use {
+    estimate, estimate_beta, estimate_improved, hll_cardinality, linear_count, sum_and_zeros,
+    SMALL_RANGE,
+};
+
+/// A cardinality estimator over a sketch's registers: from the precision
+/// `p` and `histogram[r]`, the number of the `2^p` registers holding `r`.
+///
+/// The trait is object-safe, so an estimator can be picked at run time as a
+/// `&dyn Estimator`.
+pub trait Estimator {
+    fn estimate(&self, precision: u8, histogram: &[u64; 65]) -> f64;
+}
+
+/// The estimator of the original HyperLogLog paper (Flajolet et al., 2007):
+/// linear counting while some registers are empty and the raw estimate is
+/// at most `2.5m`, and the raw estimate after that.
+///
+/// Its error is the usual `1.04 / √m` while linear counting is in force,
+/// and past `4m` or so, but just after the switch the raw estimate
+/// overcounts by about 2.5% at any precision, several standard errors at
+/// the higher ones: the bias that [`BiasCorrectedEstimator`] tabulates away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ClassicEstimator;
+
+/// HyperLogLog++'s estimator (Heule et al., 2013), and the one
+/// [`HyperLogLog::count`](::HyperLogLog::count) uses unless told otherwise:
+/// like [`ClassicEstimator`], but switching from linear counting at the
+/// empirical threshold for the precision, and less the raw estimate's
+/// tabulated bias below `5m`.
+///
+/// Within `1.04 / √m` or so at every cardinality for precisions 4 to 18,
+/// which have tables; at other precisions it is [`ClassicEstimator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct BiasCorrectedEstimator;
+
+/// The LogLog-Beta estimator (Qin et al., 2016), one formula across the
+/// whole range; see [`HyperLogLog::count_beta`](::HyperLogLog::count_beta).
+///
+/// About as accurate as [`BiasCorrectedEstimator`], without its step at the
+/// switch from linear counting. Precisions other than 4 to 18 have no
+/// coefficients and fall back to [`BiasCorrectedEstimator`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct LogLogBetaEstimator;
+
+/// Ertl's improved raw estimator (2017), corrected in closed form for empty
+/// and saturated registers; see
+/// [`HyperLogLog::count_improved`](::HyperLogLog::count_improved).
+///
+/// Within `1.04 / √m` or so at every cardinality and every precision, with
+/// no tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct ErtlEstimator;
+
+impl Estimator for ClassicEstimator {
+    fn estimate(&self, _precision: u8, histogram: &[u64; 65]) -> f64 {
+        let (m, sum, zero_count) = sum_and_zeros(histogram);
+        let raw = hll_cardinality(m, sum);
+        if zero_count > 0 && raw <= SMALL_RANGE * m as f64 {
+            linear_count(m, zero_count)
+        } else {
+            raw
+        }
+    }
+}
+
+impl Estimator for BiasCorrectedEstimator {
+    fn estimate(&self, _precision: u8, histogram: &[u64; 65]) -> f64 {
+        estimate(histogram)
+    }
+}
+
+impl Estimator for LogLogBetaEstimator {
+    fn estimate(&self, _precision: u8, histogram: &[u64; 65]) -> f64 {
+        estimate_beta(histogram)
+    }
+}
+
+impl Estimator for ErtlEstimator {
+    fn estimate(&self, _precision: u8, histogram: &[u64; 65]) -> f64 {
+        estimate_improved(histogram)
+    }
+}
+
+/// One of the crate's estimators, as a value a sketch can hold as its
+/// default and serialized sketches can record; see
+/// [`HyperLogLog::set_estimator`](::HyperLogLog::set_estimator).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BuiltinEstimator {
+    /// [`BiasCorrectedEstimator`], the default.
+    #[default]
+    BiasCorrected,
+    /// [`ClassicEstimator`].
+    Classic,
+    /// [`LogLogBetaEstimator`].
+    LogLogBeta,
+    /// [`ErtlEstimator`].
+    Ertl,
+}
+
+impl BuiltinEstimator {
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            BuiltinEstimator::BiasCorrected => 0,
+            BuiltinEstimator::Classic => 1,
+            BuiltinEstimator::LogLogBeta => 2,
+            BuiltinEstimator::Ertl => 3,
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Option<BuiltinEstimator> {
+        match code {
+            0 => Some(BuiltinEstimator::BiasCorrected),
+            1 => Some(BuiltinEstimator::Classic),
+            2 => Some(BuiltinEstimator::LogLogBeta),
+            3 => Some(BuiltinEstimator::Ertl),
+            _ => None,
+        }
+    }
+}
+
+impl Estimator for BuiltinEstimator {
+    fn estimate(&self, precision: u8, histogram: &[u64; 65]) -> f64 {
+        match *self {
+            BuiltinEstimator::BiasCorrected => {
+                BiasCorrectedEstimator.estimate(precision, histogram)
+            }
+            BuiltinEstimator::Classic => ClassicEstimator.estimate(precision, histogram),
+            BuiltinEstimator::LogLogBeta => LogLogBetaEstimator.estimate(precision, histogram),
+            BuiltinEstimator::Ertl => ErtlEstimator.estimate(precision, histogram),
+        }
+    }
+}
+
+impl From<ClassicEstimator> for BuiltinEstimator {
+    fn from(_: ClassicEstimator) -> BuiltinEstimator {
+        BuiltinEstimator::Classic
+    }
+}
+
+impl From<BiasCorrectedEstimator> for BuiltinEstimator {
+    fn from(_: BiasCorrectedEstimator) -> BuiltinEstimator {
+        BuiltinEstimator::BiasCorrected
+    }
+}
+
+impl From<LogLogBetaEstimator> for BuiltinEstimator {
+    fn from(_: LogLogBetaEstimator) -> BuiltinEstimator {
+        BuiltinEstimator::LogLogBeta
+    }
+}
+
+impl From<ErtlEstimator> for BuiltinEstimator {
+    fn from(_: ErtlEstimator) -> BuiltinEstimator {
+        BuiltinEstimator::Ertl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BiasCorrectedEstimator, BuiltinEstimator, ClassicEstimator, ErtlEstimator, Estimator,
+        LogLogBetaEstimator,
+    };
+    use simulation::splitmix64;
+    use {merge_serialized, standard_error, HyperLogLog, HyperLogLogRef, SeededState};
+
+    const BUILTINS: [BuiltinEstimator; 4] = [
+        BuiltinEstimator::BiasCorrected,
+        BuiltinEstimator::Classic,
+        BuiltinEstimator::LogLogBeta,
+        BuiltinEstimator::Ertl,
+    ];
+
+    fn sketch(p: u8, n: u64, state: &mut u64) -> HyperLogLog<SeededState> {
+        let mut h = HyperLogLog::new_seeded(1 << p, 97);
+        (0..n).for_each(|_| h.add_hashed(splitmix64(state)));
+        h
+    }
+
+    #[test]
+    fn builtins_stay_within_their_envelopes() {
+        let mut state = 97;
+        let estimators: [&dyn Estimator; 4] = [
+            &BiasCorrectedEstimator,
+            &ClassicEstimator,
+            &LogLogBetaEstimator,
+            &ErtlEstimator,
+        ];
+        for &p in &[8, 12] {
+            let m = (1u64 << p) as f64;
+            let sigma = standard_error(p);
+            for &load in &[0.1, 0.5, 1., 2., 2.6, 3., 5., 20.] {
+                let n = (load * m) as u64;
+                for _ in 0..8 {
+                    let h = sketch(p, n, &mut state);
+                    for (e, &builtin) in estimators.iter().zip(&BUILTINS[..]) {
+                        let estimate = h.count_with(*e);
+                        let error = estimate / n as f64 - 1.;
+                        // The classic estimator's bias past the switch from
+                        // linear counting, on top of the usual error.
+                        let bias = if builtin == BuiltinEstimator::Classic && load > 2.5 {
+                            0.03
+                        } else {
+                            0.
+                        };
+                        assert!(
+                            error.abs() < 4. * sigma + bias,
+                            "{:?} at p={}, n={}: estimate {}",
+                            builtin,
+                            p,
+                            n,
+                            estimate
+                        );
+                        assert_eq!(estimate, h.count_with(&builtin));
+                    }
+                }
+            }
+        }
+
+        // The bias itself, which at precision 14 is a few standard errors.
+        let mut classic = 0.;
+        let mut corrected = 0.;
+        let n = 41_000;
+        for _ in 0..8 {
+            let h = sketch(14, n, &mut state);
+            classic += h.count_with(&ClassicEstimator) / n as f64 - 1.;
+            corrected += h.count_with(&BiasCorrectedEstimator) / n as f64 - 1.;
+        }
+        assert!(classic / 8. > 0.015, "{}", classic / 8.);
+        assert!((corrected / 8.).abs() < 0.005, "{}", corrected / 8.);
+
+        // Without tables or coefficients, all but Ertl's are the classic one.
+        let h = sketch(20, 600_000, &mut state);
+        let classic = h.count_with(&ClassicEstimator);
+        assert_eq!(h.count_with(&BiasCorrectedEstimator), classic);
+        assert_eq!(h.count_with(&LogLogBetaEstimator), classic);
+        assert!((h.count_with(&ErtlEstimator) / 600_000. - 1.).abs() < 4. * standard_error(20));
+    }
+
+    #[test]
+    fn count_uses_the_default_estimator() {
+        let mut state = 97;
+        let mut h = sketch(10, 2_700, &mut state);
+        assert_eq!(h.estimator(), BuiltinEstimator::BiasCorrected);
+        assert_eq!(h.count(), h.count_with(&BiasCorrectedEstimator));
+        for &builtin in &BUILTINS {
+            h.set_estimator(builtin);
+            assert_eq!(h.estimator(), builtin);
+            assert_eq!(h.count(), h.count_with(&builtin));
+        }
+        h.set_estimator(LogLogBetaEstimator);
+        assert_eq!(h.count(), h.count_beta());
+        h.set_estimator(ErtlEstimator);
+        assert_eq!(h.count(), h.count_improved());
+        assert_ne!(h.count(), h.count_with(&ClassicEstimator));
+
+        // Set operations estimate with the first sketch's.
+        let other = sketch(10, 500, &mut state);
+        let mut union = h.clone();
+        union.merge(&other).unwrap();
+        assert_eq!(union.estimator(), BuiltinEstimator::Ertl);
+        assert_eq!(h.union_count(&other).unwrap(), union.count());
+    }
+
+    #[test]
+    fn the_default_survives_round_trips() {
+        let mut state = 97;
+        for &builtin in &BUILTINS {
+            let mut h = sketch(10, 2_700, &mut state);
+            h.set_estimator(builtin);
+            let expected = h.count_with(&builtin);
+            let check = |decoded: HyperLogLog<SeededState>| {
+                assert_eq!(decoded.estimator(), builtin);
+                assert_eq!(decoded.count(), expected);
+            };
+            let (dense, compressed) = (h.to_bytes(), h.to_bytes_compressed());
+            assert_eq!(dense[6] >> 6, builtin.code());
+            let borrowed = HyperLogLogRef::<SeededState>::parse(&dense).unwrap();
+            assert_eq!(borrowed.count(), expected);
+            check(borrowed.to_owned());
+            check(HyperLogLog::from_bytes(&dense).unwrap());
+            check(HyperLogLog::from_bytes(&compressed).unwrap());
+            check(h.freeze().thaw());
+            let merged = merge_serialized(Some(&dense), &[&compressed]).unwrap();
+            check(HyperLogLog::from_bytes(&merged).unwrap());
+            #[cfg(feature = "std")]
+            {
+                let mut buf = Vec::new();
+                h.write_to(&mut buf).unwrap();
+                check(HyperLogLog::read_from(&mut &buf[..]).unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,286 @@
+//! A C interface, for linking the crate into services written in other
+//! languages. `include/hyperloglog.h` declares it and is generated from this
+//! file by `cbindgen`.
+//!
+//! Sketches are opaque `Hll` pointers from [`hll_new`] or
+//! [`hll_deserialize`], released with [`hll_free`]. No panic crosses the
+//! boundary: failures are reported as null pointers, negative return codes,
+//! or a NaN count.
+//!
+//! Items are hashed with SipHash-2-4 under `SeededState::new(0)`, the hashing
+//! the wasm bindings use, so sketches serialized by either, or by a Rust
+//! `HyperLogLog::new_seeded(m, 0)` fed with
+//! [`add_bytes`](::HyperLogLog::add_bytes), can be merged.
+//!
+//! Null pointers are always rejected. Every sketch also carries a tag that is
+//! checked on entry and cleared when it is freed, which catches many, but not
+//! all, uses of freed or foreign pointers.
+
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+use std::{ptr, slice};
+
+use error::MergeError;
+use {HyperLogLog, SeededState};
+
+/// The call succeeded.
+pub const HLL_OK: c_int = 0;
+/// A required pointer was null, or a sketch pointer didn't point at a live
+/// sketch.
+pub const HLL_ERR_INVALID_POINTER: c_int = -1;
+/// The two sketches have different precisions.
+pub const HLL_ERR_PRECISION_MISMATCH: c_int = -2;
+/// The output buffer is too small; the size needed was written back.
+pub const HLL_ERR_BUFFER_TOO_SMALL: c_int = -3;
+/// The call panicked. This is a bug in the crate.
+pub const HLL_ERR_PANIC: c_int = -4;
+/// The two sketches hash items differently, such as a sketch deserialized
+/// from one seeded with another key.
+pub const HLL_ERR_HASHER_MISMATCH: c_int = -5;
+
+/// Marks a live sketch.
+const TAG: u64 = 0x4859_5045_524c_4f47;
+
+const SEED: u64 = 0;
+
+/// An opaque HyperLogLog sketch.
+pub struct Hll {
+    tag: u64,
+    sketch: HyperLogLog<SeededState>,
+}
+
+impl Hll {
+    fn boxed(sketch: HyperLogLog<SeededState>) -> *mut Hll {
+        Box::into_raw(Box::new(Hll { tag: TAG, sketch }))
+    }
+}
+
+/// Runs `f`, returning `on_panic` instead if it panics.
+fn guard<T, F: FnOnce() -> T>(on_panic: T, f: F) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(on_panic)
+}
+
+/// The sketch behind `hll`, or `None` for a null or untagged pointer.
+unsafe fn live<'a>(hll: *const Hll) -> Option<&'a Hll> {
+    hll.as_ref().filter(|h| h.tag == TAG)
+}
+
+unsafe fn live_mut<'a>(hll: *mut Hll) -> Option<&'a mut Hll> {
+    hll.as_mut().filter(|h| h.tag == TAG)
+}
+
+/// Creates an empty sketch with `2^precision` registers, or returns null
+/// unless `precision` is from 4 to 24.
+#[no_mangle]
+pub extern "C" fn hll_new(precision: u8) -> *mut Hll {
+    guard(ptr::null_mut(), || {
+        match HyperLogLog::builder()
+            .precision(precision)
+            .seed(SEED)
+            .build()
+        {
+            Ok(sketch) => Hll::boxed(sketch),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+/// Frees a sketch. Null is ignored.
+///
+/// # Safety
+///
+/// `hll` must be null or a pointer from this interface not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn hll_free(hll: *mut Hll) {
+    guard((), || {
+        if let Some(h) = live_mut(hll) {
+            h.tag = 0;
+            drop(Box::from_raw(hll));
+        }
+    })
+}
+
+/// Adds the item whose bytes are the `len` bytes at `data`.
+///
+/// # Safety
+///
+/// `hll` must be null or a live sketch, and `data` must be null or point at
+/// `len` readable bytes. `data` may be null only if `len` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn hll_add_bytes(hll: *mut Hll, data: *const u8, len: usize) -> c_int {
+    guard(HLL_ERR_PANIC, || {
+        let h = match live_mut(hll) {
+            Some(h) if !data.is_null() || len == 0 => h,
+            _ => return HLL_ERR_INVALID_POINTER,
+        };
+        let bytes = if len == 0 {
+            &[][..]
+        } else {
+            slice::from_raw_parts(data, len)
+        };
+        h.sketch.add_bytes(bytes);
+        HLL_OK
+    })
+}
+
+/// Estimates the number of distinct items added, or returns NaN for an
+/// invalid pointer.
+///
+/// # Safety
+///
+/// `hll` must be null or a live sketch.
+#[no_mangle]
+pub unsafe extern "C" fn hll_count(hll: *const Hll) -> f64 {
+    guard(f64::NAN, || {
+        live(hll).map_or(f64::NAN, |h| h.sketch.count())
+    })
+}
+
+/// Folds `src` into `dst`.
+///
+/// # Safety
+///
+/// Both pointers must be null or live sketches.
+#[no_mangle]
+pub unsafe extern "C" fn hll_merge(dst: *mut Hll, src: *const Hll) -> c_int {
+    guard(HLL_ERR_PANIC, || {
+        if ptr::eq(dst, src) {
+            // A union with itself changes nothing.
+            return if live(src).is_some() {
+                HLL_OK
+            } else {
+                HLL_ERR_INVALID_POINTER
+            };
+        }
+        match (live_mut(dst), live(src)) {
+            (Some(d), Some(s)) => match d.sketch.merge(&s.sketch) {
+                Ok(_) => HLL_OK,
+                Err(MergeError::HasherMismatch { .. }) => HLL_ERR_HASHER_MISMATCH,
+                Err(_) => HLL_ERR_PRECISION_MISMATCH,
+            },
+            _ => HLL_ERR_INVALID_POINTER,
+        }
+    })
+}
+
+/// Writes the sketch in the crate's binary format to `out_buf`.
+///
+/// `*out_len` gives the buffer's size on entry and is set to the encoded
+/// size. If the buffer is null or too small, nothing is written and the
+/// call returns `HLL_ERR_BUFFER_TOO_SMALL`, so passing a null buffer asks
+/// for the size.
+///
+/// # Safety
+///
+/// `hll` must be null or a live sketch, `out_len` must be null or valid to
+/// read and write, and `out_buf` must be null or point at `*out_len`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hll_serialize(
+    hll: *const Hll,
+    out_buf: *mut u8,
+    out_len: *mut usize,
+) -> c_int {
+    guard(HLL_ERR_PANIC, || {
+        let (h, out_len) = match (live(hll), out_len.as_mut()) {
+            (Some(h), Some(out_len)) => (h, out_len),
+            _ => return HLL_ERR_INVALID_POINTER,
+        };
+        let bytes = h.sketch.to_bytes();
+        let capacity = *out_len;
+        *out_len = bytes.len();
+        if out_buf.is_null() || capacity < bytes.len() {
+            return HLL_ERR_BUFFER_TOO_SMALL;
+        }
+        ptr::copy_nonoverlapping(bytes.as_ptr(), out_buf, bytes.len());
+        HLL_OK
+    })
+}
+
+/// Decodes a sketch written by [`hll_serialize`], or returns null if `buf`
+/// is null or doesn't hold one.
+///
+/// # Safety
+///
+/// `buf` must be null or point at `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn hll_deserialize(buf: *const u8, len: usize) -> *mut Hll {
+    guard(ptr::null_mut(), || {
+        if buf.is_null() {
+            return ptr::null_mut();
+        }
+        match HyperLogLog::from_bytes(slice::from_raw_parts(buf, len)) {
+            Ok(sketch) => Hll::boxed(sketch),
+            Err(_) => ptr::null_mut(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ptr;
+
+    use super::*;
+
+    #[test]
+    fn calls_from_rust() {
+        unsafe {
+            let a = hll_new(10);
+            let b = hll_new(10);
+            for i in 0..2_000u32 {
+                let item = i.to_le_bytes();
+                let target = if i < 1_000 { a } else { b };
+                assert_eq!(hll_add_bytes(target, item.as_ptr(), item.len()), HLL_OK);
+            }
+            assert_eq!(hll_merge(a, b), HLL_OK);
+            assert_eq!(hll_merge(a, a), HLL_OK);
+            let count = hll_count(a);
+            assert!((count - 2_000.).abs() < 150., "counted {}", count);
+
+            let mut len = 0;
+            assert_eq!(
+                hll_serialize(a, ptr::null_mut(), &mut len),
+                HLL_ERR_BUFFER_TOO_SMALL
+            );
+            let mut buf = vec![0u8; len];
+            assert_eq!(hll_serialize(a, buf.as_mut_ptr(), &mut len), HLL_OK);
+            let c = hll_deserialize(buf.as_ptr(), len);
+            assert_eq!(hll_count(c), count);
+            for &h in &[a, b, c] {
+                hll_free(h);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        unsafe {
+            assert!(hll_new(3).is_null());
+            assert!(hll_deserialize(ptr::null(), 10).is_null());
+            assert!(hll_deserialize(b"HLL".as_ptr(), 3).is_null());
+            assert!(hll_count(ptr::null()).is_nan());
+            assert_eq!(
+                hll_add_bytes(ptr::null_mut(), ptr::null(), 0),
+                HLL_ERR_INVALID_POINTER
+            );
+            hll_free(ptr::null_mut());
+
+            let a = hll_new(10);
+            let b = hll_new(12);
+            assert_eq!(hll_add_bytes(a, ptr::null(), 0), HLL_OK);
+            assert_eq!(hll_add_bytes(a, ptr::null(), 4), HLL_ERR_INVALID_POINTER);
+            assert_eq!(hll_merge(a, b), HLL_ERR_PRECISION_MISMATCH);
+            let bytes = HyperLogLog::new_seeded(1 << 10, 1).to_bytes();
+            let keyed = hll_deserialize(bytes.as_ptr(), bytes.len());
+            assert_eq!(hll_merge(a, keyed), HLL_ERR_HASHER_MISMATCH);
+            assert_eq!(hll_merge(a, ptr::null()), HLL_ERR_INVALID_POINTER);
+            assert_eq!(
+                hll_serialize(a, ptr::null_mut(), ptr::null_mut()),
+                HLL_ERR_INVALID_POINTER
+            );
+            for &h in &[a, b, keyed] {
+                hll_free(h);
+            }
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! A sketch whose registers live inline in a fixed-size array.
+//!
+//! The register count is a const generic rather than a precision, since stable
+//! Rust can't size an array by `1 << P`; a `ConstHyperLogLog<256>` has
+//! precision 8. Sizes that `HyperLogLog::new` would reject fail to compile.
+
+use core::convert::TryFrom;
+use core::hash::{BuildHasher, Hash};
+
+use error::MergeError;
+use fixed_point;
+use simd;
+use {
+    estimate, estimate_beta, estimate_improved, index_and_rank, standard_error, DefaultBuildHasher,
+    HyperLogLog,
+};
+
+/// A HyperLogLog sketch with `M` registers stored inline, with no heap
+/// allocation, that can be created in a `const` context.
+///
+/// Estimates are identical to a [`HyperLogLog`] of the same size fed the same
+/// items, and the two convert into each other.
+#[derive(Clone)]
+pub struct ConstHyperLogLog<const M: usize, S = DefaultBuildHasher> {
+    registers: [u8; M],
+    build_hasher: S,
+}
+
+impl<const M: usize> ConstHyperLogLog<M> {
+    /// Creates an empty sketch with the default hasher.
+    pub const fn new() -> ConstHyperLogLog<M> {
+        ConstHyperLogLog::with_hasher(DefaultBuildHasher::new())
+    }
+}
+
+impl<const M: usize> Default for ConstHyperLogLog<M> {
+    fn default() -> ConstHyperLogLog<M> {
+        ConstHyperLogLog::new()
+    }
+}
+
+impl<const M: usize, S> ConstHyperLogLog<M, S> {
+    /// Evaluated at compile time for each `M` that is used.
+    const PRECISION: u8 = {
+        assert!(
+            M >= 16 && M <= 1 << 24 && M.is_power_of_two(),
+            "ConstHyperLogLog register count must be a power of two from 16 to 2^24"
+        );
+        M.trailing_zeros() as u8
+    };
+
+    /// Creates an empty sketch that hashes items with `build_hasher`.
+    pub const fn with_hasher(build_hasher: S) -> ConstHyperLogLog<M, S> {
+        let _ = Self::PRECISION;
+        ConstHyperLogLog {
+            registers: [0; M],
+            build_hasher,
+        }
+    }
+
+    /// Adds an item by its precomputed 64-bit hash; see [`HyperLogLog::add_hashed`].
+    pub fn add_hashed(&mut self, hash: u64) {
+        let (index, rank) = index_and_rank(hash, Self::PRECISION);
+        if self.registers[index] < rank {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// Folds `other` into `self` by taking the register-wise maximum. Both
+    /// sketches have `M` registers, so unlike [`HyperLogLog::merge`] this
+    /// can't fail.
+    pub fn merge(&mut self, other: &ConstHyperLogLog<M, S>) {
+        simd::max_into(&mut self.registers, &other.registers);
+    }
+
+    pub fn count(&self) -> f64 {
+        estimate(&simd::histogram(&self.registers))
+    }
+
+    /// Estimates the cardinality with LogLog-Beta; see [`HyperLogLog::count_beta`].
+    pub fn count_beta(&self) -> f64 {
+        estimate_beta(&simd::histogram(&self.registers))
+    }
+
+    /// Estimates the cardinality with Ertl's improved raw estimator; see
+    /// [`HyperLogLog::count_improved`].
+    pub fn count_improved(&self) -> f64 {
+        estimate_improved(&simd::histogram(&self.registers))
+    }
+
+    /// Estimates the cardinality in integer arithmetic only; see
+    /// [`HyperLogLog::count_fixed`].
+    pub fn count_fixed(&self) -> u64 {
+        fixed_point::estimate(&simd::histogram(&self.registers))
+    }
+
+    pub fn error_estimate(&self) -> f64 {
+        standard_error(Self::PRECISION)
+    }
+}
+
+impl<const M: usize, S: BuildHasher> ConstHyperLogLog<M, S> {
+    pub fn add<H: Hash>(&mut self, item: H) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h);
+    }
+}
+
+impl<const M: usize, S> From<ConstHyperLogLog<M, S>> for HyperLogLog<S> {
+    fn from(sketch: ConstHyperLogLog<M, S>) -> HyperLogLog<S> {
+        let p = ConstHyperLogLog::<M, S>::PRECISION;
+        HyperLogLog::from_dense(p, sketch.registers.to_vec(), sketch.build_hasher)
+    }
+}
+
+/// Fails if the sketch doesn't have exactly `M` registers.
+impl<const M: usize, S> TryFrom<HyperLogLog<S>> for ConstHyperLogLog<M, S> {
+    type Error = MergeError;
+
+    fn try_from(hll: HyperLogLog<S>) -> Result<ConstHyperLogLog<M, S>, MergeError> {
+        let p = ConstHyperLogLog::<M, S>::PRECISION;
+        if hll.precision != p {
+            return Err(MergeError::PrecisionMismatch {
+                left: p,
+                right: hll.precision,
+            });
+        }
+        let mut registers = [0; M];
+        for (r, v) in registers.iter_mut().zip(hll.registers.iter(M)) {
+            *r = v;
+        }
+        Ok(ConstHyperLogLog {
+            registers,
+            build_hasher: hll.build_hasher,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConstHyperLogLog;
+    use std::convert::TryFrom;
+    use HyperLogLog;
+
+    // Builds in a const context.
+    static EMPTY: ConstHyperLogLog<16> = ConstHyperLogLog::new();
+
+    #[test]
+    fn matches_heap_sketch() {
+        let mut fixed = ConstHyperLogLog::<256>::new();
+        let mut heap = HyperLogLog::new(256);
+        for i in 0..10_000u64 {
+            fixed.add(i);
+            heap.add(i);
+        }
+        assert_eq!(
+            fixed.registers.to_vec(),
+            heap.registers().collect::<Vec<_>>()
+        );
+        assert_eq!(fixed.count(), heap.count());
+        assert_eq!(fixed.error_estimate(), heap.error_estimate());
+
+        let back = ConstHyperLogLog::<256>::try_from(heap.clone()).unwrap();
+        assert_eq!(back.registers, fixed.registers);
+        assert_eq!(HyperLogLog::from(fixed), heap);
+        assert!(ConstHyperLogLog::<512>::try_from(heap).is_err());
+    }
+
+    #[test]
+    fn merge_fixed() {
+        let mut a = ConstHyperLogLog::<64>::new();
+        let mut b = ConstHyperLogLog::<64>::new();
+        let mut both = ConstHyperLogLog::<64>::new();
+        for i in 0..300u64 {
+            a.add(i);
+            both.add(i);
+        }
+        for i in 200..700u64 {
+            b.add(i);
+            both.add(i);
+        }
+        a.merge(&b);
+        assert_eq!(a.registers, both.registers);
+        assert_eq!(EMPTY.count(), 0.);
+    }
+}
@@ -0,0 +1,204 @@
+//! Read-only snapshots of a sketch that are cheap to clone, for serving
+//! counts from a shared store.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::BuildHasher;
+
+use error::MergeError;
+use {BuiltinEstimator, DefaultBuildHasher, HasherId, HyperLogLog};
+
+/// An immutable copy of a [`HyperLogLog`], made by
+/// [`freeze`](HyperLogLog::freeze).
+///
+/// The registers are shared behind an `Arc`, so cloning one only bumps a
+/// reference count, and the estimate is computed once when the sketch is
+/// frozen. To change it, [`thaw`](FrozenHyperLogLog::thaw) a mutable copy.
+#[derive(Clone)]
+pub struct FrozenHyperLogLog<S = DefaultBuildHasher> {
+    registers: Arc<[u8]>,
+    precision: u8,
+    count: f64,
+    watermark: Option<f64>,
+    estimator: BuiltinEstimator,
+    items_added: u64,
+    hasher_assumed: bool,
+    build_hasher: S,
+}
+
+impl<S: BuildHasher + Clone> HyperLogLog<S> {
+    /// Copies the sketch into a [`FrozenHyperLogLog`], computing its
+    /// estimate.
+    pub fn freeze(&self) -> FrozenHyperLogLog<S> {
+        FrozenHyperLogLog {
+            registers: self.registers().collect::<Vec<u8>>().into(),
+            precision: self.precision,
+            count: self.count(),
+            watermark: self.watermark,
+            estimator: self.estimator,
+            items_added: self.items_added,
+            hasher_assumed: self.hasher_assumed,
+            build_hasher: self.build_hasher.clone(),
+        }
+    }
+}
+
+impl<S: BuildHasher + Clone> FrozenHyperLogLog<S> {
+    /// The estimate [`HyperLogLog::count`] gave when the sketch was frozen.
+    pub fn count(&self) -> f64 {
+        self.count
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The number of items added to the sketch before it was frozen; see
+    /// [`HyperLogLog::items_added`].
+    pub fn items_added(&self) -> u64 {
+        self.items_added
+    }
+
+    /// The register values in index order.
+    pub fn registers(&self) -> &[u8] {
+        &self.registers
+    }
+
+    /// Merges the frozen sketch into `target`, as
+    /// [`HyperLogLog::merge`] would merge the sketch it was frozen from.
+    pub fn merge_into(&self, target: &mut HyperLogLog<S>) -> Result<(), MergeError> {
+        if target.precision != self.precision {
+            return Err(MergeError::PrecisionMismatch {
+                left: target.precision,
+                right: self.precision,
+            });
+        }
+        let id = if self.hasher_assumed {
+            None
+        } else {
+            Some(HasherId::of(&self.build_hasher))
+        };
+        target.check_hasher(id)?;
+        let nonzero = self
+            .registers
+            .iter()
+            .enumerate()
+            .filter(|&(_, &rank)| rank > 0)
+            .map(|(index, &rank)| (index, rank));
+        target.raise_registers(nonzero);
+        if let Some(watermark) = target.watermark {
+            target.watermark = Some(watermark.max(self.count));
+        }
+        target.items_added = target.items_added.saturating_add(self.items_added);
+        Ok(())
+    }
+
+    /// Copies the registers into a new mutable sketch, which is dense
+    /// whatever the frozen sketch was made from.
+    pub fn thaw(&self) -> HyperLogLog<S> {
+        let mut h = HyperLogLog::from_dense(
+            self.precision,
+            self.registers.to_vec(),
+            self.build_hasher.clone(),
+        );
+        h.watermark = self.watermark;
+        h.estimator = self.estimator;
+        h.items_added = self.items_added;
+        h.hasher_assumed = self.hasher_assumed;
+        h
+    }
+}
+
+impl<S> fmt::Debug for FrozenHyperLogLog<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FrozenHyperLogLog")
+            .field("precision", &self.precision)
+            .field("count", &self.count)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::FrozenHyperLogLog;
+    use simulation::splitmix64;
+    use HyperLogLog;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn freeze_and_thaw_round_trip() {
+        let mut state = 80;
+        for &n in &[0, 100, 50_000] {
+            let mut h = HyperLogLog::new(1 << 12);
+            (0..n).for_each(|_| h.add_hashed(splitmix64(&mut state)));
+            let frozen = h.freeze();
+            assert_eq!(frozen.count(), h.count());
+            assert_eq!(frozen.precision(), 12);
+            assert_eq!(frozen.registers(), &h.registers().collect::<Vec<_>>()[..]);
+            let thawed = frozen.thaw();
+            assert_eq!(thawed, h);
+            assert_eq!(thawed.count(), h.count());
+
+            let mut merged = HyperLogLog::new(1 << 12);
+            merged.add_hashed(splitmix64(&mut state));
+            let mut expected = merged.clone();
+            expected.merge(&h).unwrap();
+            frozen.merge_into(&mut merged).unwrap();
+            assert_eq!(merged, expected);
+            assert_eq!(merged.count(), expected.count());
+        }
+        assert!(HyperLogLog::new(1 << 4)
+            .freeze()
+            .merge_into(&mut HyperLogLog::new(1 << 5))
+            .is_err());
+    }
+
+    #[test]
+    fn clones_share_registers() {
+        let mut h = HyperLogLog::new(1 << 14);
+        h.extend(0..10_000u32);
+        let frozen = h.freeze();
+        assert_send_sync::<FrozenHyperLogLog>();
+        let clones: Vec<_> = (0..3).map(|_| frozen.clone()).collect();
+        assert_eq!(Arc::strong_count(&frozen.registers), 4);
+        assert!(Arc::ptr_eq(&frozen.registers, &clones[2].registers));
+        drop(clones);
+        assert_eq!(Arc::strong_count(&frozen.registers), 1);
+
+        // Adding to the thawed copy leaves the frozen one alone.
+        let mut thawed = frozen.thaw();
+        thawed.extend(10_000..20_000u32);
+        assert_eq!(frozen.count(), h.count());
+        assert_eq!(frozen.thaw(), h);
+    }
+
+    #[test]
+    fn concurrent_readers_agree() {
+        let mut h = HyperLogLog::new(1 << 12);
+        h.extend(0..30_000u32);
+        let frozen = h.freeze();
+        let expected = h.count();
+        let counts: Vec<f64> = thread::scope(|scope| {
+            let readers: Vec<_> = (0..4)
+                .map(|_| {
+                    let frozen = frozen.clone();
+                    scope.spawn(move || {
+                        let mut total = HyperLogLog::new(1 << 12);
+                        for _ in 0..100 {
+                            assert_eq!(frozen.count(), expected);
+                            frozen.merge_into(&mut total).unwrap();
+                        }
+                        total.count()
+                    })
+                })
+                .collect();
+            readers.into_iter().map(|r| r.join().unwrap()).collect()
+        });
+        assert_eq!(counts, [expected; 4]);
+    }
+}
@@ -0,0 +1,360 @@
+//! HyperLogLog as originally published, over 32-bit hashes, for agreeing
+//! with systems built on it (Flajolet et al., 2007).
+//!
+//! A 32-bit hash leaves `32 - p` bits for the rank, and its space is small
+//! enough that collisions matter for large cardinalities: past `2^32 / 30`
+//! the raw estimate `E` is corrected to `-2^32 ln(1 - E / 2^32)`, which
+//! accounts for distinct items sharing a hash. Below that, the estimate is
+//! the raw one or, under `2.5 m` with empty registers, linear counting; the
+//! HyperLogLog++ bias tables are fitted to 64-bit hashes and aren't used.
+//!
+//! Encoded sketches set a flag for the hash width, so neither a
+//! [`HyperLogLog`](::HyperLogLog) nor [`merge_serialized`](::merge_serialized)
+//! will mix them with sketches of 64-bit hashes.
+
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+
+use encoding::{
+    compress, decompress, dense_body, header_bytes, read_header_of, validate_registers_up_to,
+    FLAG_HASH32, VERSION, VERSION_COMPRESSED,
+};
+use error::{BuildError, DecodeError, MergeError};
+use {
+    alpha, check_register_count, linear_count, math, rank_histogram, standard_error, sum_and_zeros,
+    DefaultBuildHasher, StableBuildHasher, SMALL_RANGE,
+};
+
+/// The largest precision, which leaves 16 bits of each hash for the rank.
+pub(crate) const MAX_PRECISION: u8 = 16;
+
+const TWO_32: f64 = 4_294_967_296.;
+
+pub(crate) fn max_rank(p: u8) -> u8 {
+    33 - p
+}
+
+/// A HyperLogLog sketch of 32-bit hashes, with the original paper's
+/// estimator.
+///
+/// Use it to reproduce the registers and estimates of a system using the
+/// 32-bit algorithm; fed the same hashes through
+/// [`add_hashed`](HyperLogLog32::add_hashed), it agrees register for
+/// register. Otherwise prefer [`HyperLogLog`](::HyperLogLog): 32-bit hashes
+/// collide often enough to hurt accuracy from a few hundred million items.
+#[derive(Clone)]
+pub struct HyperLogLog32<S = DefaultBuildHasher> {
+    registers: Vec<u8>,
+    precision: u8,
+    build_hasher: S,
+}
+
+impl HyperLogLog32 {
+    /// Creates a sketch with `m` registers, a power of two from 16 to 2^16.
+    ///
+    /// Panics if `m` isn't one.
+    pub fn new(m: usize) -> HyperLogLog32 {
+        HyperLogLog32::with_hasher(m, DefaultBuildHasher::default())
+    }
+}
+
+impl<S> HyperLogLog32<S> {
+    /// Creates a sketch with `m` registers that hashes items with
+    /// `build_hasher`; panics like [`new`](HyperLogLog32::new).
+    pub fn with_hasher(m: usize, build_hasher: S) -> HyperLogLog32<S> {
+        let precision = match check_register_count(m) {
+            Ok(p) if p > MAX_PRECISION => panic!(
+                "{}",
+                BuildError::TooManyRegisters {
+                    registers: m,
+                    maximum: 1 << MAX_PRECISION,
+                }
+            ),
+            Ok(p) => p,
+            Err(e) => panic!("{}", e),
+        };
+        HyperLogLog32 {
+            registers: vec![0; m],
+            precision,
+            build_hasher,
+        }
+    }
+
+    /// Builds a sketch from an already validated register array.
+    pub(crate) fn from_dense(
+        precision: u8,
+        registers: Vec<u8>,
+        build_hasher: S,
+    ) -> HyperLogLog32<S> {
+        HyperLogLog32 {
+            registers,
+            precision,
+            build_hasher,
+        }
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The register values, in index order.
+    pub fn registers(&self) -> &[u8] {
+        &self.registers
+    }
+
+    /// Adds an item by its precomputed 32-bit hash. The top `p` bits pick
+    /// the register and the rest give the rank, as in the original
+    /// algorithm.
+    pub fn add_hashed(&mut self, hash: u32) {
+        let index = (hash >> (32 - u32::from(self.precision))) as usize;
+        let w = hash << self.precision;
+        let rank = cmp::min(w.leading_zeros() as u8 + 1, max_rank(self.precision));
+        if self.registers[index] < rank {
+            self.registers[index] = rank;
+        }
+    }
+
+    /// Folds `other` into `self` by taking the register-wise maximum.
+    pub fn merge(&mut self, other: &HyperLogLog32<S>) -> Result<(), MergeError> {
+        if self.precision != other.precision {
+            return Err(MergeError::PrecisionMismatch {
+                left: self.precision,
+                right: other.precision,
+            });
+        }
+        for (r, &o) in self.registers.iter_mut().zip(&other.registers) {
+            *r = cmp::max(*r, o);
+        }
+        Ok(())
+    }
+
+    /// Estimates the cardinality with the original estimator and its
+    /// small- and large-range corrections.
+    ///
+    /// A sketch whose raw estimate reaches 2^32, where the large-range
+    /// correction has no finite value, estimates infinity.
+    pub fn count(&self) -> f64 {
+        let (m, sum, zeros) = sum_and_zeros(&rank_histogram(self.registers.iter().cloned()));
+        let raw = alpha(m) * m as f64 * m as f64 / sum;
+        if raw <= SMALL_RANGE * m as f64 {
+            if zeros > 0 {
+                linear_count(m, zeros)
+            } else {
+                raw
+            }
+        } else if raw <= TWO_32 / 30. {
+            raw
+        } else if raw < TWO_32 {
+            -TWO_32 * math::ln(1. - raw / TWO_32)
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    pub fn error_estimate(&self) -> f64 {
+        standard_error(self.precision)
+    }
+}
+
+impl<S: BuildHasher> HyperLogLog32<S> {
+    /// Adds an item, keeping the low 32 bits of its hash.
+    pub fn add<H: Hash>(&mut self, item: H) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h as u32);
+    }
+}
+
+impl<S: StableBuildHasher> HyperLogLog32<S> {
+    /// Encodes the sketch as [`HyperLogLog::to_bytes`](::HyperLogLog::to_bytes)
+    /// does, flagged as a sketch of 32-bit hashes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.header(VERSION);
+        out.extend_from_slice(&self.registers);
+        out
+    }
+
+    /// Encodes the sketch as
+    /// [`HyperLogLog::to_bytes_compressed`](::HyperLogLog::to_bytes_compressed)
+    /// does, flagged as a sketch of 32-bit hashes.
+    pub fn to_bytes_compressed(&self) -> Vec<u8> {
+        compress(
+            self.registers.iter().cloned(),
+            self.header(VERSION_COMPRESSED),
+        )
+    }
+
+    fn header(&self, version: u8) -> Vec<u8> {
+        let (header, len) = header_bytes(version, self.precision, &self.build_hasher, FLAG_HASH32);
+        header[..len].to_vec()
+    }
+
+    /// Decodes a sketch written by [`to_bytes`](HyperLogLog32::to_bytes) or
+    /// [`to_bytes_compressed`](HyperLogLog32::to_bytes_compressed).
+    ///
+    /// A sketch of 64-bit hashes fails with [`DecodeError::HasherMismatch`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<HyperLogLog32<S>, DecodeError> {
+        let header = read_header_of::<S>(bytes, &[VERSION, VERSION_COMPRESSED], true)?;
+        let p = header.precision;
+        let registers = if header.version == VERSION {
+            dense_body(p, bytes, header.body_at, 0)?.to_vec()
+        } else {
+            decompress(p, bytes, header.body_at)?
+        };
+        validate_registers_up_to(p, &registers, max_rank(p))?;
+        Ok(HyperLogLog32::from_dense(p, registers, header.build_hasher))
+    }
+}
+
+/// Sketches are equal when they have the same registers. The hashers are
+/// not compared.
+impl<S> PartialEq for HyperLogLog32<S> {
+    fn eq(&self, other: &HyperLogLog32<S>) -> bool {
+        self.registers == other.registers
+    }
+}
+
+impl<S> Eq for HyperLogLog32<S> {}
+
+/// Summarizes the sketch instead of listing every register.
+impl<S> fmt::Debug for HyperLogLog32<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let nonzero = self.registers.iter().filter(|&&r| r > 0).count();
+        f.debug_struct("HyperLogLog32")
+            .field("precision", &self.precision)
+            .field("nonzero_registers", &nonzero)
+            .field("estimate", &self.count())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HyperLogLog32, TWO_32};
+    use error::{DecodeError, MergeBytesError};
+    use simulation::splitmix64;
+    use {merge_serialized, HyperLogLog, SeededState};
+
+    fn unhex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn sketch(p: u8, registers: Vec<u8>) -> HyperLogLog32 {
+        let mut h = HyperLogLog32::new(1 << p);
+        assert_eq!(registers.len(), h.registers.len());
+        h.registers = registers;
+        h
+    }
+
+    fn assert_close(actual: f64, expected: f64, case: &str) {
+        assert!(
+            (actual - expected).abs() <= 1e-12 * expected,
+            "{}: {} vs {}",
+            case,
+            actual,
+            expected
+        );
+    }
+
+    /// Vectors from a transcription of the paper's algorithm; see
+    /// `generate.py` beside them.
+    #[test]
+    fn matches_reference_vectors() {
+        let mut checked = (0, 0);
+        for line in include_str!("../tests/fixtures/hll32/vectors.txt").lines() {
+            let fields: Vec<&str> = line.split(' ').collect();
+            match fields[0] {
+                "stream" => {
+                    let p: u8 = fields[1].parse().unwrap();
+                    let mut state: u64 = fields[2].parse().unwrap();
+                    let n: usize = fields[3].parse().unwrap();
+                    let mut h = HyperLogLog32::new(1 << p);
+                    (0..n).for_each(|_| h.add_hashed(splitmix64(&mut state) as u32));
+                    assert_eq!(h.registers(), &unhex(fields[5])[..], "{}", line);
+                    assert_close(h.count(), fields[4].parse().unwrap(), fields[3]);
+                    checked.0 += 1;
+                }
+                "registers" => {
+                    let h = sketch(fields[1].parse().unwrap(), unhex(fields[3]));
+                    assert_close(h.count(), fields[2].parse().unwrap(), fields[2]);
+                    checked.1 += 1;
+                }
+                _ => assert!(line.starts_with('#'), "{}", line),
+            }
+        }
+        assert_eq!(checked, (6, 14));
+    }
+
+    #[test]
+    fn large_range_correction() {
+        // Every register at `r` gives a raw estimate of alpha * m * 2^r.
+        let at = |r: u8| sketch(4, vec![r; 16]);
+        let raw = |r: u8| 0.673 * 16. * f64::from(1u32 << r);
+        assert!(raw(23) < TWO_32 / 30.);
+        assert_eq!(at(23).count(), raw(23));
+        assert!(raw(24) > TWO_32 / 30.);
+        assert!(at(24).count() > raw(24));
+        // Past 2^32 the correction has no finite value.
+        assert!(raw(29) > TWO_32);
+        assert_eq!(at(29).count(), f64::INFINITY);
+        assert_eq!(HyperLogLog32::new(1 << 4).count(), 0.);
+    }
+
+    #[test]
+    fn ranks_use_the_bits_below_the_index() {
+        let mut h = HyperLogLog32::new(1 << 4);
+        h.add_hashed(0x1800_0000);
+        h.add_hashed(0xf000_0001);
+        h.add_hashed(0x2000_0000);
+        assert_eq!(h.registers()[1], 1);
+        assert_eq!(h.registers()[15], 28);
+        // All 28 rank bits zero.
+        assert_eq!(h.registers()[2], 29);
+    }
+
+    #[test]
+    fn encoding_records_the_hash_width() {
+        let mut h = HyperLogLog32::with_hasher(1 << 10, SeededState::new(70));
+        (0..5_000u32).for_each(|i| h.add(i));
+        for bytes in &[h.to_bytes(), h.to_bytes_compressed()] {
+            let back = HyperLogLog32::<SeededState>::from_bytes(bytes).unwrap();
+            assert_eq!(back, h);
+            assert_eq!(
+                HyperLogLog::<SeededState>::from_bytes(bytes),
+                Err(DecodeError::HasherMismatch)
+            );
+        }
+        let wide = HyperLogLog::new_seeded(1 << 10, 70).to_bytes();
+        assert_eq!(
+            HyperLogLog32::<SeededState>::from_bytes(&wide),
+            Err(DecodeError::HasherMismatch)
+        );
+
+        // Merging serialized sketches keeps the flag and rejects mixing.
+        let merged = merge_serialized(None, &[&h.to_bytes(), &h.to_bytes()]).unwrap();
+        assert_eq!(
+            HyperLogLog32::<SeededState>::from_bytes(&merged),
+            Ok(h.clone())
+        );
+        assert_eq!(
+            merge_serialized(Some(&h.to_bytes()), &[&wide]),
+            Err(MergeBytesError::HasherMismatch { index: 0 })
+        );
+
+        // A rank only a 64-bit hash could produce.
+        let mut bytes = h.to_bytes();
+        let last = bytes.len() - 1;
+        bytes[last] = 24;
+        assert_eq!(
+            HyperLogLog32::<SeededState>::from_bytes(&bytes),
+            Err(DecodeError::InvalidRegister {
+                index: 1023,
+                value: 24
+            })
+        );
+    }
+}
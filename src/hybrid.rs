@@ -0,0 +1,338 @@
+//! A sketch that counts exactly until exact counting costs more memory than
+//! the sketch, like the sparse-then-dense representations of Redis and
+//! BigQuery.
+//!
+//! In exact mode the sketch keeps the set of 64-bit item hashes. Converting
+//! adds each of them to an empty [`HyperLogLog`], which leaves it exactly as
+//! it would be had it been fed the items all along, so nothing about the
+//! later estimates depends on when the conversion happened.
+
+use std::collections::HashSet;
+use std::hash::{BuildHasher, Hash};
+
+use encoding::{
+    read_header, VERSION, VERSION_COMPRESSED, VERSION_COMPRESSED_COUNTED, VERSION_COUNTED,
+};
+use error::{DecodeError, MergeError};
+use {DefaultBuildHasher, HyperLogLog, StableBuildHasher};
+
+/// The format version of an exact-mode sketch; see the `encoding` module.
+const VERSION_EXACT: u8 = 3;
+
+/// A [`HyperLogLog`] that returns exact counts until it holds more than a
+/// threshold of distinct items.
+#[derive(Clone, Debug)]
+pub struct HybridHyperLogLog<S = DefaultBuildHasher> {
+    /// The hashes seen so far, until the sketch takes over.
+    exact: Option<HashSet<u64>>,
+    /// Empty while `exact` is in use.
+    sketch: HyperLogLog<S>,
+    threshold: usize,
+}
+
+/// The default number of distinct items to count exactly: at 8 bytes a
+/// hash, a set this large holds as many bytes as the `m` dense registers.
+fn default_threshold(m: usize) -> usize {
+    m / 8
+}
+
+impl HybridHyperLogLog {
+    /// Creates a sketch with `m` registers; panics like [`HyperLogLog::new`].
+    pub fn new(m: usize) -> HybridHyperLogLog {
+        HybridHyperLogLog::with_hasher(m, DefaultBuildHasher::default())
+    }
+}
+
+impl<S> HybridHyperLogLog<S> {
+    fn from_sketch(sketch: HyperLogLog<S>, exact: Option<HashSet<u64>>) -> HybridHyperLogLog<S> {
+        HybridHyperLogLog {
+            threshold: default_threshold(sketch.num_registers()),
+            exact,
+            sketch,
+        }
+    }
+
+    /// How many distinct items are counted exactly before converting.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// Whether the sketch is still counting exactly.
+    pub fn is_exact(&self) -> bool {
+        self.exact.is_some()
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.sketch.precision
+    }
+}
+
+impl<S: BuildHasher> HybridHyperLogLog<S> {
+    /// Creates a sketch with `m` registers that hashes items with `build_hasher`.
+    pub fn with_hasher(m: usize, build_hasher: S) -> HybridHyperLogLog<S> {
+        HybridHyperLogLog::from_sketch(
+            HyperLogLog::with_hasher(m, build_hasher),
+            Some(HashSet::new()),
+        )
+    }
+
+    /// Sets how many distinct items are counted exactly, converting at once
+    /// if the sketch already holds more.
+    pub fn with_threshold(mut self, threshold: usize) -> HybridHyperLogLog<S> {
+        self.threshold = threshold;
+        self.check_conversion();
+        self
+    }
+
+    pub fn add<H: Hash>(&mut self, item: H) {
+        let h = self.sketch.build_hasher.hash_one(item);
+        self.add_hashed(h);
+    }
+
+    fn check_conversion(&mut self) {
+        if self
+            .exact
+            .as_ref()
+            .is_some_and(|e| e.len() > self.threshold)
+        {
+            self.convert();
+        }
+    }
+
+    /// Moves the exact hashes into the sketch.
+    fn convert(&mut self) {
+        if let Some(hashes) = self.exact.take() {
+            for h in hashes {
+                self.sketch.insert_hashed(h);
+            }
+        }
+    }
+
+    pub fn add_hashed(&mut self, hash: u64) {
+        match self.exact {
+            Some(ref mut hashes) => {
+                if hashes.insert(hash) && hashes.len() > self.threshold {
+                    self.convert();
+                }
+            }
+            None => {
+                self.sketch.insert_hashed(hash);
+            }
+        }
+    }
+
+    /// The number of distinct items, exact while [`is_exact`] holds and
+    /// estimated by the sketch after.
+    ///
+    /// [`is_exact`]: HybridHyperLogLog::is_exact
+    pub fn count(&self) -> f64 {
+        match self.exact {
+            Some(ref hashes) => hashes.len() as f64,
+            None => self.sketch.count(),
+        }
+    }
+
+    /// Folds `other` into `self`. Two exact sketches stay exact if their
+    /// union fits under `self`'s threshold; otherwise `self` converts first.
+    pub fn merge(&mut self, other: &HybridHyperLogLog<S>) -> Result<(), MergeError> {
+        self.sketch.check_compatible(&other.sketch)?;
+        match other.exact {
+            Some(ref hashes) => {
+                for &h in hashes {
+                    self.add_hashed(h);
+                }
+                Ok(())
+            }
+            None => {
+                self.convert();
+                self.sketch.merge(&other.sketch).map(drop)
+            }
+        }
+    }
+
+    /// The sketch, converting it if it was still exact.
+    pub fn into_sketch(mut self) -> HyperLogLog<S> {
+        self.convert();
+        self.sketch
+    }
+}
+
+impl<S: StableBuildHasher> HybridHyperLogLog<S> {
+    /// Encodes the sketch: the exact hashes as version 3 of the binary
+    /// format, or the sketch as [`HyperLogLog::to_bytes_compressed`] writes
+    /// it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self.exact {
+            Some(ref hashes) => {
+                let mut sorted: Vec<u64> = hashes.iter().copied().collect();
+                sorted.sort_unstable();
+                let mut out = self.sketch.header(VERSION_EXACT, 8 * sorted.len());
+                for h in sorted {
+                    out.extend_from_slice(&h.to_le_bytes());
+                }
+                out
+            }
+            None => self.sketch.to_bytes_compressed(),
+        }
+    }
+
+    /// Decodes bytes written by [`HybridHyperLogLog::to_bytes`] or by
+    /// [`HyperLogLog::to_bytes`]. The threshold isn't stored; the sketch gets
+    /// the default one, and converts if the decoded set is larger.
+    pub fn from_bytes(bytes: &[u8]) -> Result<HybridHyperLogLog<S>, DecodeError> {
+        let versions = [
+            VERSION,
+            VERSION_COMPRESSED,
+            VERSION_COUNTED,
+            VERSION_COMPRESSED_COUNTED,
+            VERSION_EXACT,
+        ];
+        let header = read_header::<S>(bytes, &versions)?;
+        if header.version != VERSION_EXACT {
+            return HyperLogLog::from_bytes(bytes).map(|h| HybridHyperLogLog::from_sketch(h, None));
+        }
+        let body = &bytes[header.body_at..];
+        if !body.len().is_multiple_of(8) {
+            return Err(DecodeError::Truncated {
+                expected: bytes.len() + 8 - body.len() % 8,
+                actual: bytes.len(),
+            });
+        }
+        let hashes = body
+            .chunks(8)
+            .map(|c| {
+                let mut word = [0u8; 8];
+                word.copy_from_slice(c);
+                u64::from_le_bytes(word)
+            })
+            .collect();
+        let sketch = HyperLogLog::from_dense(
+            header.precision,
+            vec![0; 1 << header.precision],
+            header.build_hasher,
+        );
+        let mut hybrid = HybridHyperLogLog::from_sketch(sketch, Some(hashes));
+        hybrid.check_conversion();
+        Ok(hybrid)
+    }
+}
+
+impl<S> From<HyperLogLog<S>> for HybridHyperLogLog<S> {
+    /// Wraps a sketch, which is never exact again even if it's empty.
+    fn from(sketch: HyperLogLog<S>) -> HybridHyperLogLog<S> {
+        HybridHyperLogLog::from_sketch(sketch, None)
+    }
+}
+
+impl<S: BuildHasher> From<HybridHyperLogLog<S>> for HyperLogLog<S> {
+    fn from(hybrid: HybridHyperLogLog<S>) -> HyperLogLog<S> {
+        hybrid.into_sketch()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HybridHyperLogLog;
+    use simulation::splitmix64;
+    use {HyperLogLog, SeededState};
+
+    #[test]
+    fn exact_below_the_threshold() {
+        let mut h = HybridHyperLogLog::new(1 << 14);
+        assert_eq!(h.threshold(), 2_048);
+        for n in 1..=2_048u32 {
+            h.add(n);
+            h.add(n / 2 + 1);
+            assert_eq!(h.count(), f64::from(n));
+        }
+        assert!(h.is_exact());
+        h.add(2_049u32);
+        assert!(!h.is_exact());
+        let count = h.count();
+        assert!((count - 2_049.).abs() < 60., "counted {}", count);
+    }
+
+    #[test]
+    fn conversion_matches_a_sketch_fed_all_along() {
+        let mut state = 58;
+        let mut hybrid = HybridHyperLogLog::new(1 << 10).with_threshold(100);
+        let mut plain = HyperLogLog::new(1 << 10);
+        for _ in 0..5_000 {
+            let h = splitmix64(&mut state) % 3_000;
+            hybrid.add(h);
+            plain.add(h);
+            if hybrid.is_exact() {
+                assert_eq!(
+                    hybrid.clone().into_sketch().registers().collect::<Vec<_>>(),
+                    plain.registers().collect::<Vec<_>>()
+                );
+            }
+        }
+        assert!(!hybrid.is_exact());
+        assert_eq!(hybrid.count(), plain.count());
+        assert_eq!(
+            hybrid.into_sketch().registers().collect::<Vec<_>>(),
+            plain.registers().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn merges_every_combination() {
+        let fill = |range: ::std::ops::Range<u32>| {
+            let mut h = HybridHyperLogLog::new(1 << 10);
+            range.for_each(|i| h.add(i));
+            h
+        };
+        let (small_a, small_b, large) = (fill(0..50), fill(40..90), fill(0..1_000));
+
+        let mut exact = small_a.clone();
+        exact.merge(&small_b).unwrap();
+        assert!(exact.is_exact());
+        assert_eq!(exact.count(), 90.);
+
+        let mut converting = small_a.clone().with_threshold(60);
+        converting.merge(&small_b).unwrap();
+        assert!(!converting.is_exact());
+
+        let mut everything = fill(0..1_000);
+        everything.merge(&small_a).unwrap();
+        let mut exact_into_dense = small_b.clone();
+        exact_into_dense.merge(&large).unwrap();
+        let mut dense = large.clone();
+        dense.merge(&large).unwrap();
+        for h in &[everything, exact_into_dense, dense] {
+            assert!(!h.is_exact());
+            assert_eq!(h.count(), large.count());
+        }
+
+        let mut other = HybridHyperLogLog::new(1 << 12);
+        assert!(other.merge(&small_a).is_err());
+    }
+
+    #[test]
+    fn serialization_round_trips_both_modes() {
+        let mut exact = HybridHyperLogLog::with_hasher(1 << 10, SeededState::new(9));
+        for i in 0..100u32 {
+            exact.add(i);
+        }
+        let bytes = exact.to_bytes();
+        assert_eq!(bytes[4], 3);
+        assert_eq!(bytes.len(), 7 + 16 + 8 * 100);
+        let back = HybridHyperLogLog::<SeededState>::from_bytes(&bytes).unwrap();
+        assert!(back.is_exact());
+        assert_eq!(back.count(), 100.);
+        assert!(HyperLogLog::<SeededState>::from_bytes(&bytes).is_err());
+
+        let mut dense = exact.clone();
+        for i in 0..1_000u32 {
+            dense.add(i);
+        }
+        let back = HybridHyperLogLog::<SeededState>::from_bytes(&dense.to_bytes()).unwrap();
+        assert!(!back.is_exact());
+        assert_eq!(back.count(), dense.count());
+        let plain = HyperLogLog::<SeededState>::from_bytes(&dense.to_bytes()).unwrap();
+        assert_eq!(plain, dense.into_sketch());
+
+        assert!(HybridHyperLogLog::<SeededState>::from_bytes(&bytes[..bytes.len() - 3]).is_err());
+    }
+}
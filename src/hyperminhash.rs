@@ -0,0 +1,326 @@
+//! HyperMinHash (Yu and Weber, "HyperMinHash: MinHash in LogLog space",
+//! 2017): HyperLogLog registers that also keep the `r` hash bits after each
+//! register's leading 1-bit.
+//!
+//! A register's rank and those bits together locate the smallest hash that
+//! landed in it to within a factor of `1 + 2^-r`, so two sketches' registers
+//! agree mostly where both sets' minimum came from a shared item, as
+//! one-permutation MinHash signatures do. The fraction of agreeing registers
+//! estimates the Jaccard similarity, after subtracting the agreements
+//! expected by chance between two sets of the sketches' estimated sizes. The
+//! ranks alone are HyperLogLog registers and give the same cardinality
+//! estimate.
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+
+use error::MergeError;
+use math;
+use {
+    check_register_count, estimate, index_and_rank, max_rank, rank_histogram, standard_error,
+    DefaultBuildHasher,
+};
+
+/// The most bits kept after the leading 1-bit; a register's rank and bits
+/// then still fit in a `u16`.
+const MAX_SUB_BUCKET_BITS: u8 = 10;
+
+/// A HyperLogLog sketch whose registers also support MinHash-style Jaccard
+/// similarity estimates, at 16 bits per register.
+///
+/// Cardinality estimates match a [`HyperLogLog`](::HyperLogLog) fed the same
+/// hashes.
+#[derive(Clone)]
+pub struct HyperMinHash<S = DefaultBuildHasher> {
+    /// `rank << r | (2^r - 1 - bits)`, so a larger value is a smaller hash
+    /// and merging takes the maximum. 0 means "never touched".
+    registers: Vec<u16>,
+    precision: u8,
+    sub_bucket_bits: u8,
+    build_hasher: S,
+}
+
+impl HyperMinHash {
+    /// Creates a sketch with `m` registers that each keep `r` bits besides
+    /// the rank.
+    ///
+    /// Panics like [`HyperLogLog::new`](::HyperLogLog::new) if `m` is not a
+    /// valid register count, and if `r` is not from 1 to 10.
+    pub fn new(m: usize, r: u8) -> HyperMinHash {
+        HyperMinHash::with_hasher(m, r, DefaultBuildHasher::default())
+    }
+}
+
+impl<S> HyperMinHash<S> {
+    /// The number of bits each register keeps besides the rank.
+    pub fn sub_bucket_bits(&self) -> u8 {
+        self.sub_bucket_bits
+    }
+
+    /// Adds an item by its precomputed 64-bit hash; see
+    /// [`HyperLogLog::add_hashed`](::HyperLogLog::add_hashed).
+    pub fn add_hashed(&mut self, hash: u64) {
+        let (i, rank) = index_and_rank(hash, self.precision);
+        let r = u32::from(self.sub_bucket_bits);
+        // The bits after the leading 1, zero-padded past the end of the hash.
+        let bits = if rank < max_rank(self.precision) {
+            (hash << self.precision << rank) >> (64 - r)
+        } else {
+            0
+        };
+        let mask = (1 << r) - 1;
+        let value = u16::from(rank) << r | (mask - bits as u16);
+        if value > self.registers[i] {
+            self.registers[i] = value;
+        }
+    }
+
+    fn ranks(&self) -> impl Iterator<Item = u8> + '_ {
+        let r = self.sub_bucket_bits;
+        self.registers.iter().map(move |&v| (v >> r) as u8)
+    }
+
+    fn check_compatible(&self, other: &HyperMinHash<S>) -> Result<(), MergeError> {
+        if self.precision != other.precision {
+            return Err(MergeError::PrecisionMismatch {
+                left: self.precision,
+                right: other.precision,
+            });
+        }
+        if self.sub_bucket_bits != other.sub_bucket_bits {
+            return Err(MergeError::SizeMismatch {
+                left: usize::from(self.sub_bucket_bits),
+                right: usize::from(other.sub_bucket_bits),
+            });
+        }
+        Ok(())
+    }
+
+    /// Folds `other` into `self` by taking the register-wise maximum. Fails
+    /// if the sketches differ in register count or sub-bucket bits.
+    pub fn merge(&mut self, other: &HyperMinHash<S>) -> Result<(), MergeError> {
+        self.check_compatible(other)?;
+        for (a, &b) in self.registers.iter_mut().zip(&other.registers) {
+            if b > *a {
+                *a = b;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn count(&self) -> f64 {
+        estimate(&rank_histogram(self.ranks()))
+    }
+
+    pub fn error_estimate(&self) -> f64 {
+        standard_error(self.precision)
+    }
+
+    /// Estimates the Jaccard similarity `|A ∩ B| / |A ∪ B|` of the items added
+    /// to `self` and `other`: the fraction of registers touched by either
+    /// that hold the same rank and bits in both, less the fraction expected
+    /// to agree by chance. Two empty sketches have similarity 0.
+    ///
+    /// The standard error is about `√(J (1 - J) / m)`, as for MinHash with
+    /// `m` buckets, so that small similarities come out far more accurately
+    /// than from HyperLogLog intersection estimates. Fails if the sketches
+    /// differ in register count or sub-bucket bits.
+    pub fn jaccard(&self, other: &HyperMinHash<S>) -> Result<f64, MergeError> {
+        self.check_compatible(other)?;
+        let (mut either, mut both) = (0usize, 0usize);
+        for (&a, &b) in self.registers.iter().zip(&other.registers) {
+            if a != 0 || b != 0 {
+                either += 1;
+                if a == b {
+                    both += 1;
+                }
+            }
+        }
+        if either == 0 {
+            return Ok(0.);
+        }
+        let chance = expected_collisions(
+            self.precision,
+            self.sub_bucket_bits,
+            self.count(),
+            other.count(),
+        );
+        Ok(((both as f64 - chance) / either as f64).max(0.))
+    }
+}
+
+impl<S: BuildHasher> HyperMinHash<S> {
+    /// Creates a sketch with `m` registers that each keep `r` bits besides
+    /// the rank, hashing items with `build_hasher`. Panics like
+    /// [`HyperMinHash::new`].
+    pub fn with_hasher(m: usize, r: u8, build_hasher: S) -> HyperMinHash<S> {
+        let precision = match check_register_count(m) {
+            Ok(p) => p,
+            Err(e) => panic!("{}", e),
+        };
+        assert!(
+            (1..=MAX_SUB_BUCKET_BITS).contains(&r),
+            "HyperMinHash keeps 1 to {} sub-bucket bits, not {}",
+            MAX_SUB_BUCKET_BITS,
+            r
+        );
+        HyperMinHash {
+            registers: vec![0; m],
+            precision,
+            sub_bucket_bits: r,
+            build_hasher,
+        }
+    }
+
+    pub fn add<H: Hash>(&mut self, item: H) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h);
+    }
+}
+
+/// `2^-e`.
+fn half_pow(e: u8) -> f64 {
+    1. / (1u64 << e) as f64
+}
+
+/// `P(min < hi) - P(min < lo)` for the smallest of `n` uniform hashes that
+/// land in one of `2^p` registers, as fractions `lo < hi` of the rest of
+/// the hash.
+fn min_in(n: f64, scale: f64, lo: f64, hi: f64) -> f64 {
+    let below = |x: f64| math::exp(n * math::ln_1p(-x * scale));
+    below(lo) - below(hi)
+}
+
+/// How many registers two sketches of disjoint sets of sizes `n` and `k`
+/// are expected to agree on: `2^p` times the chance that both minima fall
+/// in the same interval of a rank and its `r` bits.
+fn expected_collisions(p: u8, r: u8, n: f64, k: f64) -> f64 {
+    let scale = half_pow(p);
+    let cells = 1u32 << r;
+    let top = max_rank(p);
+    let mut total = 0.;
+    for rank in 1..top {
+        let width = half_pow(rank);
+        for j in 0..cells {
+            let lo = width * (1. + f64::from(j) / f64::from(cells));
+            let hi = width * (1. + f64::from(j + 1) / f64::from(cells));
+            total += min_in(n, scale, lo, hi) * min_in(k, scale, lo, hi);
+        }
+    }
+    // Below the last rank the hash has no bits left: one interval.
+    let last = half_pow(top - 1);
+    total += min_in(n, scale, 0., last) * min_in(k, scale, 0., last);
+    total / scale
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expected_collisions, HyperMinHash};
+    use simulation::splitmix64;
+    use HyperLogLog;
+
+    #[test]
+    fn keeps_the_bits_after_the_leading_one() {
+        let mut h = HyperMinHash::new(1 << 4, 3);
+        // Register 5, three leading zeros, then 1 and bits 101.
+        h.add_hashed(0x5 << 60 | 0b0001_1011 << 52);
+        assert_eq!(h.registers[5], 4 << 3 | (7 - 0b101));
+        // A smaller remainder wins at the same rank, a larger one doesn't.
+        h.add_hashed(0x5 << 60 | 0b0001_1001 << 52);
+        h.add_hashed(0x5 << 60 | 0b0001_1111 << 52);
+        assert_eq!(h.registers[5], 4 << 3 | (7 - 0b100));
+        // All-zero remainders rank highest.
+        h.add_hashed(0x5 << 60);
+        assert_eq!(h.registers[5], 61 << 3 | 7);
+    }
+
+    #[test]
+    fn cardinality_matches_the_plain_sketch() {
+        let mut state = 60;
+        for &n in &[100u64, 5_000, 200_000, 2_000_000] {
+            let mut h = HyperMinHash::new(1 << 12, 10);
+            let mut plain = HyperLogLog::new(1 << 12);
+            for _ in 0..n {
+                let x = splitmix64(&mut state);
+                h.add_hashed(x);
+                plain.add_hashed(x);
+            }
+            assert!(h.ranks().eq(plain.registers()), "n {}", n);
+            assert_eq!(h.count(), plain.count(), "n {}", n);
+        }
+    }
+
+    #[test]
+    fn jaccard_within_the_minhash_error() {
+        let mut state = 60;
+        let m = 1 << 12;
+        // (only in A, only in B, in both)
+        let cases = [
+            (50_000u64, 50_000u64, 0u64),
+            (99_000, 0, 1_000),
+            (45_000, 45_000, 10_000),
+            (900_000, 50_000, 50_000),
+            (25_000, 25_000, 50_000),
+            (5_000, 5_000, 90_000),
+            (0, 0, 100_000),
+        ];
+        for &(only_a, only_b, shared) in &cases {
+            let mut a = HyperMinHash::new(m, 10);
+            let mut b = HyperMinHash::new(m, 10);
+            for _ in 0..only_a {
+                a.add_hashed(splitmix64(&mut state));
+            }
+            for _ in 0..only_b {
+                b.add_hashed(splitmix64(&mut state));
+            }
+            for _ in 0..shared {
+                let x = splitmix64(&mut state);
+                a.add_hashed(x);
+                b.add_hashed(x);
+            }
+            let j = shared as f64 / (only_a + only_b + shared) as f64;
+            let estimate = a.jaccard(&b).unwrap();
+            let bound = 3. * (j * (1. - j) / m as f64).sqrt() + 0.005;
+            assert!(
+                (estimate - j).abs() < bound,
+                "J {}: estimated {}",
+                j,
+                estimate
+            );
+        }
+    }
+
+    #[test]
+    fn chance_collisions() {
+        // Disjoint sets agree on a register about 1/2^r of the time once
+        // registers are full; fewer bits collide more.
+        let (p, n) = (10, 1e6);
+        let few = expected_collisions(p, 2, n, n);
+        let many = expected_collisions(p, 10, n, n);
+        assert!(few > 50. * many, "{} vs {}", few, many);
+        assert!(many > 0. && many < 1., "{}", many);
+        assert!(expected_collisions(p, 4, 0., n) == 0.);
+    }
+
+    #[test]
+    fn merge_hyperminhash() {
+        let mut a = HyperMinHash::new(1 << 8, 6);
+        let mut b = HyperMinHash::new(1 << 8, 6);
+        let mut both = HyperMinHash::new(1 << 8, 6);
+        for i in 0..3_000u64 {
+            a.add(i);
+            both.add(i);
+        }
+        for i in 2_000..6_000u64 {
+            b.add(i);
+            both.add(i);
+        }
+        a.merge(&b).unwrap();
+        assert_eq!(a.registers, both.registers);
+        assert_eq!(a.jaccard(&both).unwrap(), a.jaccard(&a).unwrap());
+        assert!(a.jaccard(&HyperMinHash::new(1 << 8, 5)).is_err());
+        assert!(a.merge(&HyperMinHash::new(1 << 9, 6)).is_err());
+        let empty = HyperMinHash::new(1 << 8, 6);
+        assert_eq!(empty.jaccard(&empty).unwrap(), 0.);
+    }
+}
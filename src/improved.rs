@@ -0,0 +1,107 @@
+//! Ertl's improved raw estimator ("New cardinality estimation algorithms for
+//! HyperLogLog sketches", 2017).
+//!
+//! Under a Poisson model of the register values, the harmonic mean behind the
+//! raw estimate is only biased by the registers with nothing to average: the
+//! empty ones, and those at the maximum rank, whose hash ran out of bits.
+//! Replacing their terms with the corrections `m σ(C_0 / m)` and
+//! `m τ(1 - C_max / m)` gives
+//!
+//! ```text
+//! E = alpha_inf * m^2 / (m τ(1 - C_max / m) + Σ C_k 2^-k + m σ(C_0 / m))
+//! ```
+//!
+//! with `alpha_inf = 1 / (2 ln 2)`, which is accurate from empty to far past
+//! `2^64 / m` without linear counting, bias tables or thresholds.
+
+use math;
+
+/// `x + Σ_{k≥1} x^(2^k) 2^(k-1)`, infinite at `x = 1`.
+fn sigma(mut x: f64) -> f64 {
+    if x == 1. {
+        return f64::INFINITY;
+    }
+    let (mut y, mut z) = (1., x);
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if z == previous {
+            return z;
+        }
+    }
+}
+
+/// `(1 - x - Σ_{k≥1} (1 - x^(2^-k))^2 2^-k) / 3`.
+fn tau(mut x: f64) -> f64 {
+    if x == 0. || x == 1. {
+        return 0.;
+    }
+    let (mut y, mut z) = (1., 1. - x);
+    loop {
+        x = math::sqrt(x);
+        let previous = z;
+        y *= 0.5;
+        z -= (1. - x) * (1. - x) * y;
+        if z == previous {
+            return z / 3.;
+        }
+    }
+}
+
+/// The estimate from a histogram of register values, for `m` registers of
+/// which the highest possible value is `max_rank`.
+pub(crate) fn estimate(counts: &[u64; 65], m: usize, max_rank: u8) -> f64 {
+    let mf = m as f64;
+    let top = usize::from(max_rank);
+    // Horner's scheme from the top: Σ C_k 2^-k as (...(C_{q} + z)/2...)/2.
+    let mut z = mf * tau(1. - counts[top] as f64 / mf);
+    for k in (1..top).rev() {
+        z = 0.5 * (z + counts[k] as f64);
+    }
+    z += mf * sigma(counts[0] as f64 / mf);
+    mf * mf / (2. * core::f64::consts::LN_2 * z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{estimate, sigma, tau};
+
+    #[test]
+    fn corrections() {
+        assert_eq!(sigma(0.), 0.);
+        assert!(sigma(1.).is_infinite());
+        // 0.5 + 0.25 + 0.0625·2 + 0.00390625·4 + ...
+        assert!((sigma(0.5) - 0.890_747_074).abs() < 1e-6, "{}", sigma(0.5));
+        assert_eq!(tau(0.), 0.);
+        assert_eq!(tau(1.), 0.);
+        // 1 - 0.5 - (0.0429 + 0.0063 + 0.0009 + ...), over 3.
+        assert!((tau(0.5) - 0.149_929).abs() < 1e-6, "{}", tau(0.5));
+        for &x in &[1e-9, 0.01, 0.99, 1. - 1e-9] {
+            assert!(tau(x) > 0. && tau(x) < 1. / 3., "{}: {}", x, tau(x));
+        }
+    }
+
+    #[test]
+    fn empty_and_saturated() {
+        let mut counts = [0u64; 65];
+        counts[0] = 1 << 10;
+        assert_eq!(estimate(&counts, 1 << 10, 55), 0.);
+
+        // One rank-1 register among empties is about one item.
+        counts[0] -= 1;
+        counts[1] = 1;
+        let one = estimate(&counts, 1 << 10, 55);
+        assert!((one - 1.).abs() < 0.01, "{}", one);
+
+        // Every register at the top is beyond counting, but finite.
+        let mut full = [0u64; 65];
+        full[55] = 1 << 10;
+        assert!(estimate(&full, 1 << 10, 55).is_infinite());
+        full[55] -= 1;
+        full[54] = 1;
+        let huge = estimate(&full, 1 << 10, 55);
+        assert!(huge.is_finite() && huge > 1e19, "{}", huge);
+    }
+}
@@ -0,0 +1,164 @@
+//! Hashing one large item in pieces, for items that are read from a file or
+//! socket rather than held in memory.
+
+use core::fmt;
+use core::hash::{BuildHasher, Hasher};
+#[cfg(feature = "std")]
+use std::io;
+
+use HyperLogLog;
+
+/// One item being hashed piece by piece, made by
+/// [`start_item`](HyperLogLog::start_item).
+///
+/// Bytes written through `io::Write` (with the `std` feature) or `Hasher`
+/// are fed straight to the sketch's hasher, and
+/// [`finish`](ItemWriter::finish) adds the item. Dropping the writer without
+/// finishing it adds nothing, so an item abandoned halfway, say on an I/O
+/// error, isn't counted.
+pub struct ItemWriter<'a, S: BuildHasher + 'a> {
+    sketch: &'a mut HyperLogLog<S>,
+    hasher: S::Hasher,
+}
+
+impl<S: BuildHasher> HyperLogLog<S> {
+    /// Starts adding an item whose bytes are written to the returned
+    /// [`ItemWriter`] in as many pieces as is convenient.
+    ///
+    /// The item is added as [`add_bytes`](HyperLogLog::add_bytes) would add
+    /// all the pieces joined together, provided the hasher treats
+    /// consecutive writes as one stream of bytes. SipHash, the default
+    /// hasher and the XXH3, MurmurHash3, Redis and DataSketches hashers all
+    /// do; `ClickHouseState` pads each write to 8 bytes, so its pieces must
+    /// be multiples of 8 bytes long.
+    pub fn start_item(&mut self) -> ItemWriter<'_, S> {
+        let hasher = self.build_hasher.build_hasher();
+        ItemWriter {
+            sketch: self,
+            hasher,
+        }
+    }
+}
+
+impl<'a, S: BuildHasher> ItemWriter<'a, S> {
+    /// Adds the item written so far, returning whether it raised a register,
+    /// as [`insert`](HyperLogLog::insert) does. An item with nothing written
+    /// is the empty item, as `add_bytes(b"")` adds.
+    pub fn finish(self) -> bool {
+        let h = self.hasher.finish();
+        self.sketch.insert_hashed(h)
+    }
+}
+
+/// Writes are fed to the hasher; `finish` gives the hash the item would be
+/// added with, without adding it.
+impl<'a, S: BuildHasher> Hasher for ItemWriter<'a, S> {
+    fn write(&mut self, bytes: &[u8]) {
+        self.hasher.write(bytes);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hasher.finish()
+    }
+}
+
+/// Every write takes all of its bytes and never fails, so `io::copy` from a
+/// reader streams an item in without buffering it.
+#[cfg(feature = "std")]
+impl<'a, S: BuildHasher> io::Write for ItemWriter<'a, S> {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.hasher.write(bytes);
+        Ok(bytes.len())
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.hasher.write(bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a, S: BuildHasher> fmt::Debug for ItemWriter<'a, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ItemWriter")
+            .field("precision", &self.sketch.precision)
+            .finish()
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use core::hash::{BuildHasher, Hasher};
+    use std::io::{self, Write};
+
+    use simulation::splitmix64;
+    use {HyperLogLog, SeededState};
+
+    /// About 1 MB of pseudo-random bytes.
+    fn blob(state: &mut u64) -> Vec<u8> {
+        (0..1 << 17)
+            .flat_map(|_| splitmix64(state).to_le_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn pieces_hash_as_one_add_bytes() {
+        let mut state = 87;
+        let blobs: Vec<Vec<u8>> = (0..8).map(|_| blob(&mut state)).collect();
+        let mut whole = HyperLogLog::new_seeded(1 << 8, 87);
+        let mut once = whole.clone();
+        let mut copied = whole.clone();
+        let mut hashed = whole.clone();
+        for blob in &blobs {
+            whole.add_bytes(blob);
+
+            let mut item = once.start_item();
+            item.write_all(blob).unwrap();
+            item.finish();
+
+            let mut item = copied.start_item();
+            let mut reader = io::BufReader::with_capacity(8 << 10, &blob[..]);
+            io::copy(&mut reader, &mut item).unwrap();
+            item.finish();
+
+            // Uneven pieces, some empty.
+            let mut item = hashed.start_item();
+            let mut rest = &blob[..];
+            while !rest.is_empty() {
+                let len = (splitmix64(&mut state) % 1000) as usize;
+                let (piece, tail) = rest.split_at(len.min(rest.len()));
+                Hasher::write(&mut item, piece);
+                rest = tail;
+            }
+            let mut expected = SeededState::new(87).build_hasher();
+            expected.write(blob);
+            assert_eq!(Hasher::finish(&item), expected.finish());
+            item.finish();
+        }
+        assert!(whole.count() > 0.);
+        for h in &[once, copied, hashed] {
+            assert!(h.registers().eq(whole.registers()));
+            assert_eq!(*h, whole);
+        }
+    }
+
+    #[test]
+    fn only_finishing_adds_the_item() {
+        let mut h = HyperLogLog::new(1 << 8);
+        h.start_item();
+        {
+            let mut item = h.start_item();
+            item.write_all(b"abandoned").unwrap();
+        }
+        assert_eq!(h, HyperLogLog::new(1 << 8));
+
+        assert!(h.start_item().finish());
+        assert!(!h.start_item().finish());
+        let mut expected = HyperLogLog::new(1 << 8);
+        expected.add_bytes(b"");
+        assert_eq!(h, expected);
+    }
+}
@@ -0,0 +1,87 @@
+//! Estimating the distinct items of an iterator in one call.
+
+use core::hash::Hash;
+
+use error::BuildError;
+use HyperLogLog;
+
+/// Cardinality estimation as an iterator method, for every iterator of
+/// hashable items.
+///
+/// Items stream into a [`HyperLogLog`] through
+/// [`add_all`](HyperLogLog::add_all) and are never collected. The sketch
+/// uses the default hasher, so the estimate for the same items can vary
+/// between builds of a program; build the sketch by hand to choose one.
+pub trait EstimateDistinct: Iterator + Sized
+where
+    Self::Item: Hash,
+{
+    /// Estimates the number of distinct items with a sketch of the default
+    /// precision, 14.
+    fn estimate_cardinality(self) -> f64 {
+        self.build_sketch().count()
+    }
+
+    /// Estimates the number of distinct items with a sketch of `2^precision`
+    /// registers, for a precision
+    /// [`HyperLogLog::with_precision`] accepts.
+    fn estimate_cardinality_with(self, precision: u8) -> Result<f64, BuildError> {
+        Ok(self.build_sketch_with(precision)?.count())
+    }
+
+    /// Adds the items to a sketch of the default precision and returns it,
+    /// for merging with others or counting later.
+    fn build_sketch(self) -> HyperLogLog {
+        let mut h = HyperLogLog::default();
+        h.add_all(self);
+        h
+    }
+
+    /// Like [`build_sketch`](EstimateDistinct::build_sketch), with a sketch
+    /// of `2^precision` registers.
+    fn build_sketch_with(self, precision: u8) -> Result<HyperLogLog, BuildError> {
+        let mut h = HyperLogLog::with_precision(precision)?;
+        h.add_all(self);
+        Ok(h)
+    }
+}
+
+impl<I: Iterator> EstimateDistinct for I where I::Item: Hash {}
+
+#[cfg(test)]
+mod tests {
+    use super::EstimateDistinct;
+    use error::BuildError;
+    use HyperLogLog;
+
+    #[test]
+    fn matches_a_sketch_built_by_hand() {
+        let mut by_hand = HyperLogLog::with_precision(14).unwrap();
+        (0..50_000u32).for_each(|i| by_hand.add(i));
+        assert_eq!((0..50_000u32).build_sketch(), by_hand);
+        assert_eq!((0..50_000u32).estimate_cardinality(), by_hand.count());
+        // Repeats don't count.
+        let estimate = (0..50_000u32).chain(0..50_000).estimate_cardinality();
+        assert_eq!(estimate, by_hand.count());
+
+        let mut small = HyperLogLog::new(1 << 8);
+        (0..1_000u32).for_each(|i| small.add(i));
+        assert_eq!(
+            (0..1_000u32).estimate_cardinality_with(8),
+            Ok(small.count())
+        );
+        assert_eq!(
+            (0..1_000u32).estimate_cardinality_with(19),
+            Err(BuildError::InvalidPrecision(19))
+        );
+    }
+
+    #[test]
+    fn counts_lines() {
+        let text = "apple\nbanana\napple\ncherry\nbanana\napple\n";
+        assert_eq!(text.lines().estimate_cardinality().round(), 3.);
+        let mut by_hand = HyperLogLog::with_precision(10).unwrap();
+        text.lines().for_each(|line| by_hand.add(line));
+        assert_eq!(text.lines().build_sketch_with(10), Ok(by_hand));
+    }
+}
@@ -0,0 +1,356 @@
+//! Maximum-likelihood estimates of how two sketches overlap, after Ertl,
+//! "New cardinality estimation algorithms for HyperLogLog sketches" (2017),
+//! section 5.
+//!
+//! Under the Poisson model, the items only in `A`, only in `B`, and in both
+//! reach each register as independent streams with rates `λa / m`, `λb / m`,
+//! and `λx / m`. Register `i` of `A` is the larger of the maxima of the first
+//! and third streams, and of `B` the larger of the second and third. That
+//! gives the probability of every pair `(A[i], B[i])`:
+//!
+//! - `a < b`: `B`'s register comes from `B`-only items, so the probability is
+//!   `P(maxB = b) · P(maxAX = a)`, where `maxAX` has rate `(λa + λx) / m`;
+//! - `a > b`: symmetrically;
+//! - `a = b = k`: either the shared stream reached `k` and neither other
+//!   stream passed it, or both other streams reached `k` and the shared one
+//!   stayed below.
+//!
+//! The log-likelihood sums over the distinct pairs, weighted by how often
+//! each occurs. Its maximum is found by Newton's method in `ln λ`, with a
+//! backtracking line search, damped toward gradient ascent where the Hessian
+//! isn't negative definite.
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use {math, max_rank};
+
+/// At most this many Newton steps are taken before giving up.
+const MAX_ITERATIONS: usize = 200;
+/// The finite-difference step in `ln λ`.
+const H: f64 = 1e-4;
+/// No rate is taken below this many items, where the likelihood is flat.
+const MIN_RATE: f64 = 1e-6;
+
+/// `2^-k` for `k` up to 63.
+fn scale(k: u8) -> f64 {
+    1. / (1u64 << k) as f64
+}
+
+/// `ln(1 - e^-x)` for `x > 0`.
+fn ln_one_minus_exp(x: f64) -> f64 {
+    math::ln(-math::exp_m1(-x))
+}
+
+/// `ln(e^u + e^v)`.
+fn ln_add(u: f64, v: f64) -> f64 {
+    let (hi, lo) = if u > v { (u, v) } else { (v, u) };
+    if lo == f64::NEG_INFINITY {
+        hi
+    } else {
+        hi + math::ln_1p(math::exp(lo - hi))
+    }
+}
+
+/// The distribution of one register's maximum rank over a stream with rate
+/// `r` per register, with ranks capped at `q + 1`.
+#[derive(Clone, Copy)]
+struct Register {
+    r: f64,
+    q: u8,
+}
+
+impl Register {
+    /// `ln P(K = k)`.
+    fn ln_pmf(self, k: u8) -> f64 {
+        if k == 0 {
+            -self.r
+        } else if k <= self.q {
+            let x = self.r * scale(k);
+            -x + ln_one_minus_exp(x)
+        } else {
+            ln_one_minus_exp(self.r * scale(self.q))
+        }
+    }
+
+    /// `ln P(K ≤ k)`.
+    fn ln_cdf(self, k: u8) -> f64 {
+        if k <= self.q {
+            -self.r * scale(k)
+        } else {
+            0.
+        }
+    }
+}
+
+/// How often each pair of register values occurs across two sketches.
+pub(crate) struct PairCounts {
+    /// `(a, b, count)` for every pair that occurs.
+    pairs: Vec<(u8, u8, u64)>,
+    m: f64,
+    q: u8,
+}
+
+impl PairCounts {
+    pub(crate) fn new<I: Iterator<Item = (u8, u8)>>(p: u8, pairs: I) -> PairCounts {
+        let width = usize::from(max_rank(p)) + 1;
+        let mut counts = vec![0u64; width * width];
+        for (a, b) in pairs {
+            counts[usize::from(a) * width + usize::from(b)] += 1;
+        }
+        let pairs = counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &c)| c > 0)
+            .map(|(i, &c)| ((i / width) as u8, (i % width) as u8, c))
+            .collect();
+        PairCounts {
+            pairs,
+            m: (1u64 << p) as f64,
+            q: max_rank(p) - 1,
+        }
+    }
+
+    /// Whether either sketch has no items.
+    fn either_empty(&self) -> bool {
+        self.pairs.iter().all(|&(a, _, _)| a == 0) || self.pairs.iter().all(|&(_, b, _)| b == 0)
+    }
+
+    fn log_likelihood(&self, theta: [f64; 3]) -> f64 {
+        let register = |ln_rate: f64| Register {
+            r: math::exp(ln_rate) / self.m,
+            q: self.q,
+        };
+        let (a_only, b_only, both) = (register(theta[0]), register(theta[1]), register(theta[2]));
+        let a_all = Register {
+            r: a_only.r + both.r,
+            q: self.q,
+        };
+        let b_all = Register {
+            r: b_only.r + both.r,
+            q: self.q,
+        };
+        let mut total = 0.;
+        for &(a, b, count) in &self.pairs {
+            let ln_p = if a < b {
+                b_only.ln_pmf(b) + a_all.ln_pmf(a)
+            } else if a > b {
+                a_only.ln_pmf(a) + b_all.ln_pmf(b)
+            } else if a == 0 {
+                -(a_only.r + b_only.r + both.r)
+            } else {
+                let shared = both.ln_pmf(a) + a_only.ln_cdf(a) + b_only.ln_cdf(a);
+                let separate = both.ln_cdf(a - 1) + a_only.ln_pmf(a) + b_only.ln_pmf(a);
+                ln_add(shared, separate)
+            };
+            total += count as f64 * ln_p;
+        }
+        total
+    }
+
+    /// The gradient and Hessian of the log-likelihood at `theta`, by central
+    /// differences.
+    fn derivatives(&self, theta: [f64; 3]) -> ([f64; 3], [[f64; 3]; 3]) {
+        let at = |di: [f64; 3]| {
+            self.log_likelihood([theta[0] + di[0], theta[1] + di[1], theta[2] + di[2]])
+        };
+        let unit = |i: usize, h: f64| {
+            let mut d = [0.; 3];
+            d[i] = h;
+            d
+        };
+        let center = at([0.; 3]);
+        let mut gradient = [0.; 3];
+        let mut hessian = [[0.; 3]; 3];
+        for i in 0..3 {
+            let (plus, minus) = (at(unit(i, H)), at(unit(i, -H)));
+            gradient[i] = (plus - minus) / (2. * H);
+            hessian[i][i] = (plus - 2. * center + minus) / (H * H);
+            for j in 0..i {
+                let corner = |si: f64, sj: f64| {
+                    let mut d = unit(i, si * H);
+                    d[j] = sj * H;
+                    at(d)
+                };
+                let h = (corner(1., 1.) - corner(1., -1.) - corner(-1., 1.) + corner(-1., -1.))
+                    / (4. * H * H);
+                hessian[i][j] = h;
+                hessian[j][i] = h;
+            }
+        }
+        (gradient, hessian)
+    }
+
+    /// The maximum-likelihood `[λa, λb, λx]`, starting the search from
+    /// `start`, or `None` if the search doesn't converge.
+    pub(crate) fn maximize(&self, start: [f64; 3]) -> Option<[f64; 3]> {
+        if self.either_empty() {
+            return None;
+        }
+        let floor = math::ln(MIN_RATE);
+        let mut theta = [0.; 3];
+        for i in 0..3 {
+            theta[i] = math::ln(start[i].max(1.));
+        }
+        let mut value = self.log_likelihood(theta);
+        for _ in 0..MAX_ITERATIONS {
+            let (gradient, hessian) = self.derivatives(theta);
+            let mut step = ascent_step(hessian, gradient);
+            // Never move more than a factor of e^2 in a single step.
+            let longest = step.iter().fold(0f64, |acc, d| acc.max(d.abs()));
+            if longest > 2. {
+                step.iter_mut().for_each(|d| *d *= 2. / longest);
+            }
+            let mut t = 1.;
+            loop {
+                let mut next = theta;
+                for i in 0..3 {
+                    next[i] = (theta[i] + t * step[i]).max(floor);
+                }
+                let next_value = self.log_likelihood(next);
+                if next_value >= value {
+                    let moved = (0..3).fold(0f64, |acc, i| acc.max((next[i] - theta[i]).abs()));
+                    theta = next;
+                    value = next_value;
+                    if moved < 1e-7 {
+                        return finite_rates(theta);
+                    }
+                    break;
+                }
+                t /= 2.;
+                if t < 1e-9 {
+                    // No ascent along the step: a maximum, to within the
+                    // finite differences.
+                    return finite_rates(theta);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// A Newton step for maximizing, damped toward diagonally scaled gradient
+/// ascent (Levenberg–Marquardt) until the system to solve is positive
+/// definite, so that the step always goes uphill.
+fn ascent_step(hessian: [[f64; 3]; 3], gradient: [f64; 3]) -> [f64; 3] {
+    let curvature = |i: usize| hessian[i][i].abs().max(1e-12);
+    let mut damping = 0.;
+    while damping <= 1e6 {
+        let mut a = negate(hessian);
+        for (i, row) in a.iter_mut().enumerate() {
+            row[i] += damping * curvature(i);
+        }
+        if positive_definite(a) {
+            if let Some(step) = solve(a, gradient) {
+                return step;
+            }
+        }
+        damping = if damping == 0. { 1e-3 } else { damping * 10. };
+    }
+    [
+        gradient[0] / curvature(0),
+        gradient[1] / curvature(1),
+        gradient[2] / curvature(2),
+    ]
+}
+
+/// Whether the symmetric `a` is positive definite, by its leading minors.
+fn positive_definite(a: [[f64; 3]; 3]) -> bool {
+    a[0][0] > 0. && a[0][0] * a[1][1] - a[0][1] * a[1][0] > 0. && det(a) > 0.
+}
+
+fn finite_rates(theta: [f64; 3]) -> Option<[f64; 3]> {
+    let rates = [
+        math::exp(theta[0]),
+        math::exp(theta[1]),
+        math::exp(theta[2]),
+    ];
+    if rates.iter().all(|r| r.is_finite()) {
+        Some(rates)
+    } else {
+        None
+    }
+}
+
+fn negate(a: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let mut out = a;
+    out.iter_mut().flatten().for_each(|x| *x = -*x);
+    out
+}
+
+fn det(a: [[f64; 3]; 3]) -> f64 {
+    a[0][0] * (a[1][1] * a[2][2] - a[1][2] * a[2][1])
+        - a[0][1] * (a[1][0] * a[2][2] - a[1][2] * a[2][0])
+        + a[0][2] * (a[1][0] * a[2][1] - a[1][1] * a[2][0])
+}
+
+/// Solves `a x = b` by Cramer's rule, or `None` if `a` is singular.
+fn solve(a: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let d = det(a);
+    if d == 0. || !d.is_finite() {
+        return None;
+    }
+    let mut x = [0.; 3];
+    for (j, xj) in x.iter_mut().enumerate() {
+        let mut aj = a;
+        for i in 0..3 {
+            aj[i][j] = b[i];
+        }
+        *xj = det(aj) / d;
+    }
+    Some(x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ln_add, solve, PairCounts, Register};
+
+    #[test]
+    fn register_distribution_sums_to_one() {
+        for &r in &[0.01, 1., 300., 1e12] {
+            let register = Register { r, q: 52 };
+            let total: f64 = (0..=53).map(|k| register.ln_pmf(k).exp()).sum();
+            assert!((total - 1.).abs() < 1e-12, "rate {}: {}", r, total);
+            let below: f64 = (0..=10).map(|k| register.ln_pmf(k).exp()).sum();
+            assert!((below - register.ln_cdf(10).exp()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn pair_probabilities_sum_to_one() {
+        // Every (a, b) for four registers' worth of p = 60, q = 4.
+        let pairs = PairCounts {
+            pairs: (0..6u8)
+                .flat_map(|a| (0..6u8).map(move |b| (a, b, 1)))
+                .collect(),
+            m: 1.,
+            q: 4,
+        };
+        let theta = [0.3f64.ln(), 2f64.ln(), 0.7f64.ln()];
+        let total: f64 = pairs
+            .pairs
+            .iter()
+            .map(|&(a, b, _)| {
+                PairCounts {
+                    pairs: vec![(a, b, 1)],
+                    m: 1.,
+                    q: 4,
+                }
+                .log_likelihood(theta)
+                .exp()
+            })
+            .sum();
+        assert!((total - 1.).abs() < 1e-12, "{}", total);
+    }
+
+    #[test]
+    fn helpers() {
+        assert!((ln_add(1f64.ln(), 2f64.ln()) - 3f64.ln()).abs() < 1e-15);
+        assert_eq!(ln_add(f64::NEG_INFINITY, 0.), 0.);
+        let x = solve([[2., 1., 0.], [1., 3., 1.], [0., 1., 4.]], [3., 5., 5.]).unwrap();
+        for (got, want) in x.iter().zip(&[1., 1., 1.]) {
+            assert!((got - want).abs() < 1e-12);
+        }
+        assert!(solve([[1., 2., 3.], [2., 4., 6.], [0., 0., 1.]], [1., 1., 1.]).is_none());
+    }
+}
@@ -0,0 +1,313 @@
+//! A K-Minimum-Values sketch: the `k` smallest distinct hashes seen.
+//!
+//! If `n` distinct items hash uniformly into `[0, 2^64)`, the `k`-th smallest
+//! hash lands near `k / n` of the way through the range, so
+//! `(k - 1) / (kth / 2^64)` is an unbiased estimate of `n` with a relative
+//! standard error of about `1 / √(k - 2)`. Unlike HyperLogLog registers, the
+//! kept hashes are a uniform random sample of the distinct items, so the
+//! overlap of two sketches' samples estimates their Jaccard similarity, and
+//! with it the size of their intersection, directly.
+
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use error::MergeError;
+use DefaultBuildHasher;
+
+/// The size of the hash space, `2^64`.
+const HASH_SPACE: f64 = 18_446_744_073_709_551_616.;
+
+/// A sketch keeping the `k` smallest distinct 64-bit hashes of its items.
+#[derive(Clone)]
+pub struct KMinValues<S = DefaultBuildHasher> {
+    k: usize,
+    /// The smallest hashes seen, ascending and without duplicates.
+    values: Vec<u64>,
+    build_hasher: S,
+}
+
+impl KMinValues {
+    /// Creates a sketch that keeps `k` hashes.
+    ///
+    /// Panics if `k` is below 2.
+    pub fn new(k: usize) -> KMinValues {
+        KMinValues::with_hasher(k, DefaultBuildHasher::default())
+    }
+}
+
+impl<S: BuildHasher> KMinValues<S> {
+    /// Creates a sketch that keeps `k` hashes and hashes items with
+    /// `build_hasher`.
+    ///
+    /// Panics if `k` is below 2.
+    pub fn with_hasher(k: usize, build_hasher: S) -> KMinValues<S> {
+        assert!(
+            k >= 2,
+            "a KMV sketch must keep at least 2 hashes, not {}",
+            k
+        );
+        KMinValues {
+            k,
+            values: Vec::with_capacity(k),
+            build_hasher,
+        }
+    }
+
+    pub fn add<H: Hash>(&mut self, item: H) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h);
+    }
+}
+
+impl<S> KMinValues<S> {
+    /// Adds an item by its precomputed 64-bit hash, which must be uniformly
+    /// distributed over the whole range.
+    pub fn add_hashed(&mut self, hash: u64) {
+        if self.values.len() == self.k && hash >= self.values[self.k - 1] {
+            return;
+        }
+        if let Err(at) = self.values.binary_search(&hash) {
+            if self.values.len() == self.k {
+                self.values.pop();
+            }
+            self.values.insert(at, hash);
+        }
+    }
+
+    /// The number of hashes the sketch keeps once it is full.
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    /// Estimates the number of distinct items added. Until `k` distinct hashes
+    /// have been seen, this is exactly how many have.
+    pub fn count(&self) -> f64 {
+        if self.values.len() < self.k {
+            return self.values.len() as f64;
+        }
+        let kth = self.values[self.k - 1] as f64 / HASH_SPACE;
+        (self.k - 1) as f64 / kth
+    }
+
+    fn check_k(&self, other: &KMinValues<S>) -> Result<(), MergeError> {
+        if self.k != other.k {
+            return Err(MergeError::SizeMismatch {
+                left: self.k,
+                right: other.k,
+            });
+        }
+        Ok(())
+    }
+
+    /// Folds `other` into `self`, keeping the `k` smallest hashes of the two,
+    /// so that `self` is the sketch of the items added to either. Fails if
+    /// the sketches keep different numbers of hashes.
+    pub fn merge(&mut self, other: &KMinValues<S>) -> Result<(), MergeError> {
+        self.check_k(other)?;
+        let mut merged = Vec::with_capacity(self.k);
+        let (mut a, mut b) = (
+            self.values.iter().peekable(),
+            other.values.iter().peekable(),
+        );
+        while merged.len() < self.k {
+            let next = match (a.peek(), b.peek()) {
+                (Some(&&x), Some(&&y)) => {
+                    if x <= y {
+                        a.next();
+                    }
+                    if y <= x {
+                        b.next();
+                    }
+                    cmp::min(x, y)
+                }
+                (Some(&&x), None) => {
+                    a.next();
+                    x
+                }
+                (None, Some(&&y)) => {
+                    b.next();
+                    y
+                }
+                (None, None) => break,
+            };
+            merged.push(next);
+        }
+        self.values = merged;
+        Ok(())
+    }
+
+    /// Estimates the Jaccard similarity `|A ∩ B| / |A ∪ B|` of the items added
+    /// to `self` and `other`: the fraction of the `k` smallest hashes of their
+    /// union that both sketches kept. Two empty sketches have similarity 0.
+    pub fn jaccard(&self, other: &KMinValues<S>) -> Result<f64, MergeError> {
+        self.check_k(other)?;
+        let (mut a, mut b) = (
+            self.values.iter().peekable(),
+            other.values.iter().peekable(),
+        );
+        let (mut union, mut both) = (0usize, 0usize);
+        while union < self.k {
+            match (a.peek(), b.peek()) {
+                (Some(&&x), Some(&&y)) => {
+                    if x == y {
+                        both += 1;
+                    }
+                    if x <= y {
+                        a.next();
+                    }
+                    if y <= x {
+                        b.next();
+                    }
+                }
+                (Some(_), None) => {
+                    a.next();
+                }
+                (None, Some(_)) => {
+                    b.next();
+                }
+                (None, None) => break,
+            }
+            union += 1;
+        }
+        if union == 0 {
+            return Ok(0.);
+        }
+        Ok(both as f64 / union as f64)
+    }
+
+    /// Estimates how many items `self` and `other` have in common, as their
+    /// Jaccard similarity times the size of their union.
+    ///
+    /// Unlike the inclusion–exclusion estimate of
+    /// [`HyperLogLog::intersection_count`](::HyperLogLog::intersection_count),
+    /// the error scales with the intersection itself.
+    pub fn intersection_count(&self, other: &KMinValues<S>) -> Result<f64, MergeError>
+    where
+        S: Clone,
+    {
+        let jaccard = self.jaccard(other)?;
+        let mut union = self.clone();
+        union.merge(other)?;
+        Ok(jaccard * union.count())
+    }
+
+    /// The memory the sketch uses, including its kept hashes on the heap.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of::<Self>() + self.values.capacity() * mem::size_of::<u64>()
+    }
+}
+
+/// Adds every item, as [`add`](KMinValues::add) would one at a time.
+impl<S: BuildHasher, T: Hash> Extend<T> for KMinValues<S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.add(item);
+        }
+    }
+}
+
+impl<S> fmt::Debug for KMinValues<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KMinValues")
+            .field("k", &self.k)
+            .field("kept", &self.values.len())
+            .field("estimate", &self.count())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::KMinValues;
+    use simulation::splitmix64;
+    use MergeError;
+
+    fn filled(k: usize, hashes: &[u64]) -> KMinValues {
+        let mut s = KMinValues::new(k);
+        for &h in hashes {
+            s.add_hashed(h);
+        }
+        s
+    }
+
+    #[test]
+    fn exact_below_k_and_ignores_duplicates() {
+        let mut s = KMinValues::new(100);
+        assert_eq!(s.count(), 0.);
+        for _ in 0..3 {
+            s.extend(0..50u64);
+        }
+        assert_eq!(s.count(), 50.);
+        assert!(s.values.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn accurate_within_expected_error() {
+        let k = 1024;
+        let bound = 3. / ((k - 2) as f64).sqrt();
+        let mut rng = 31;
+        for &n in &[2_000usize, 50_000, 500_000] {
+            for _ in 0..5 {
+                let hashes: Vec<u64> = (0..n).map(|_| splitmix64(&mut rng)).collect();
+                let s = filled(k, &hashes);
+                assert_eq!(s.values.len(), k);
+                let error = (s.count() - n as f64).abs() / n as f64;
+                assert!(error < bound, "n = {}: counted {}", n, s.count());
+            }
+        }
+    }
+
+    #[test]
+    fn merge_of_disjoint_streams_is_union() {
+        let mut rng = 32;
+        let a: Vec<u64> = (0..30_000).map(|_| splitmix64(&mut rng)).collect();
+        let b: Vec<u64> = (0..20_000).map(|_| splitmix64(&mut rng)).collect();
+        let mut merged = filled(512, &a);
+        merged.merge(&filled(512, &b)).unwrap();
+        let all: Vec<u64> = a.iter().chain(&b).cloned().collect();
+        let union = filled(512, &all);
+        assert_eq!(merged.values, union.values);
+        assert_eq!(merged.count(), union.count());
+
+        // Merging with itself, or with a smaller overlapping sketch, changes
+        // nothing.
+        let before = merged.values.clone();
+        merged.merge(&union).unwrap();
+        merged.merge(&filled(512, &a[..100])).unwrap();
+        assert_eq!(merged.values, before);
+
+        assert_eq!(
+            merged.merge(&KMinValues::new(256)).unwrap_err(),
+            MergeError::SizeMismatch {
+                left: 512,
+                right: 256
+            }
+        );
+    }
+
+    #[test]
+    fn jaccard_and_intersection() {
+        let k = 2048;
+        let mut rng = 33;
+        let hashes: Vec<u64> = (0..40_000).map(|_| splitmix64(&mut rng)).collect();
+        // 10,000 shared items out of a union of 40,000: J = 0.25.
+        let a = filled(k, &hashes[..25_000]);
+        let b = filled(k, &hashes[15_000..]);
+        let j = a.jaccard(&b).unwrap();
+        assert!(
+            (j - 0.25).abs() < 3. * (0.25f64 * 0.75 / k as f64).sqrt(),
+            "{}",
+            j
+        );
+        let i = a.intersection_count(&b).unwrap();
+        assert!((i - 10_000.).abs() < 1_500., "{}", i);
+
+        assert_eq!(a.jaccard(&a).unwrap(), 1.);
+        let empty = KMinValues::new(k);
+        assert_eq!(empty.jaccard(&empty).unwrap(), 0.);
+        assert_eq!(a.jaccard(&empty).unwrap(), 0.);
+    }
+}
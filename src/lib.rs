@@ -1,150 +1,3953 @@
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+//! HyperLogLog cardinality sketches.
+//!
+//! The crate is `no_std` with `alloc` when the default `std` feature is off;
+//! enable the `libm` feature instead to supply the floating-point math.
 
-pub struct HyperLogLog {
-    registers: Vec<u32>,
-    hasher: DefaultHasher,
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(not(any(feature = "std", feature = "libm")))]
+compile_error!("without the `std` feature, enable `libm` for the floating-point math");
+
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "arbitrary")]
+extern crate arbitrary;
+#[cfg(feature = "arrow")]
+extern crate arrow_array;
+#[cfg(feature = "arrow")]
+extern crate arrow_schema;
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[cfg(feature = "crdt")]
+extern crate crdts;
+#[cfg(feature = "async")]
+extern crate futures_core;
+#[cfg(all(feature = "async", test))]
+extern crate futures_util;
+#[cfg(all(feature = "libm", not(feature = "std")))]
+extern crate libm;
+#[cfg(feature = "metrics")]
+extern crate metrics;
+#[cfg(all(feature = "metrics", test))]
+extern crate metrics_util;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "rkyv")]
+extern crate rkyv;
+#[cfg(feature = "rocksdb")]
+extern crate rocksdb;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "sled")]
+extern crate sled;
+#[cfg(feature = "sqlx-postgres")]
+extern crate sqlx;
+#[cfg(all(
+    test,
+    any(feature = "async", feature = "sqlx-postgres"),
+    not(target_arch = "wasm32")
+))]
+extern crate tokio;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(all(test, feature = "wasm", target_arch = "wasm32"))]
+extern crate wasm_bindgen_test;
+
+use alloc::vec::Vec;
+use core::cmp;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+use core::iter::{FromIterator, Sum};
+use core::mem;
+
+/// `Hasher` methods writing integers little-endian, with `usize` and `isize`
+/// widened to 64 bits, so that a value hashes the same on every platform.
+/// Expands inside an `impl Hasher` that defines `write`.
+macro_rules! little_endian_writes {
+    () => {
+        fn write_u16(&mut self, i: u16) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u32(&mut self, i: u32) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u64(&mut self, i: u64) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_u128(&mut self, i: u128) {
+            self.write(&i.to_le_bytes());
+        }
+
+        fn write_usize(&mut self, i: usize) {
+            self.write_u64(i as u64);
+        }
+
+        fn write_i16(&mut self, i: i16) {
+            self.write_u16(i as u16);
+        }
+
+        fn write_i32(&mut self, i: i32) {
+            self.write_u32(i as u32);
+        }
+
+        fn write_i64(&mut self, i: i64) {
+            self.write_u64(i as u64);
+        }
+
+        fn write_i128(&mut self, i: i128) {
+            self.write_u128(i as u128);
+        }
+
+        fn write_isize(&mut self, i: isize) {
+            self.write_u64(i as u64);
+        }
+    };
+}
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impls;
+#[cfg(feature = "arrow")]
+mod arrow_impls;
+#[cfg(feature = "async")]
+mod async_stream;
+#[cfg(target_has_atomic = "8")]
+mod atomic;
+mod beta;
+mod bias;
+mod borrowed;
+mod bounds;
+mod builder;
+mod cache;
+#[cfg(feature = "clickhouse-compat")]
+mod clickhouse;
+#[cfg(feature = "crdt")]
+mod crdt;
+#[cfg(feature = "datasketches-compat")]
+mod datasketches;
+mod decay;
+mod delta;
+mod details;
+mod encoding;
+mod error;
+mod estimator;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod fixed;
+mod fixed_point;
+#[cfg(target_has_atomic = "ptr")]
+mod frozen;
+mod hash32;
+#[cfg(feature = "std")]
+mod hybrid;
+mod hyperminhash;
+mod improved;
+mod item_writer;
+mod iter;
+mod joint;
+mod kmv;
+mod linear;
+#[cfg(feature = "std")]
+mod lines;
+#[cfg(feature = "std")]
+mod map;
+mod math;
+mod merge_bytes;
+#[cfg(any(feature = "murmur3", feature = "datasketches-compat"))]
+mod murmur3;
+mod ops;
+mod packed;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "rayon")]
+mod rayon_impls;
+mod recent;
+#[cfg(feature = "redis-compat")]
+mod redis;
+#[cfg(feature = "metrics")]
+mod reporter;
+mod ring;
+#[cfg(feature = "rkyv")]
+mod rkyv_impls;
+mod sampling;
+#[cfg(feature = "serde")]
+mod serde_impls;
+mod sets;
+#[cfg(feature = "std")]
+mod sharded;
+mod simd;
+#[cfg(test)]
+mod simulation;
+mod sip;
+mod sketch;
+mod sliding;
+mod sparse;
+#[cfg(feature = "sqlx-postgres")]
+mod sqlx_impls;
+#[cfg(feature = "std")]
+mod stream;
+mod tailcut;
+#[cfg(any(feature = "testing", test))]
+pub mod testing;
+mod text;
+mod typed;
+#[cfg(feature = "std")]
+mod validating;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "xxhash")]
+mod xxh3;
+
+#[cfg(target_has_atomic = "8")]
+pub use atomic::AtomicHyperLogLog;
+pub use borrowed::HyperLogLogRef;
+pub use bounds::{Confidence, EstimateBounds, Exceeds};
+pub use builder::HyperLogLogBuilder;
+use cache::EstimateCache;
+#[cfg(feature = "clickhouse-compat")]
+pub use clickhouse::{ClickHouseHasher, ClickHouseState};
+#[cfg(feature = "datasketches-compat")]
+pub use datasketches::{DataSketchesHasher, DataSketchesState};
+pub use decay::DecayingHyperLogLog;
+pub use delta::SketchDelta;
+pub use details::{EstimateDetails, EstimationMethod};
+#[cfg(feature = "arrow")]
+pub use error::ArrowStateError;
+#[cfg(feature = "clickhouse-compat")]
+pub use error::ClickHouseCodecError;
+#[cfg(feature = "datasketches-compat")]
+pub use error::DataSketchesCodecError;
+#[cfg(feature = "redis-compat")]
+pub use error::RedisCodecError;
+pub use error::{BuildError, DecodeError, FoldError, MergeBytesError, MergeError, RegisterError};
+pub use estimator::{
+    BiasCorrectedEstimator, BuiltinEstimator, ClassicEstimator, ErtlEstimator, Estimator,
+    LogLogBetaEstimator,
+};
+pub use fixed::ConstHyperLogLog;
+#[cfg(target_has_atomic = "ptr")]
+pub use frozen::FrozenHyperLogLog;
+pub use hash32::HyperLogLog32;
+#[cfg(feature = "std")]
+pub use hybrid::HybridHyperLogLog;
+pub use hyperminhash::HyperMinHash;
+pub use item_writer::ItemWriter;
+pub use iter::EstimateDistinct;
+pub use kmv::KMinValues;
+pub use linear::LinearCounter;
+#[cfg(feature = "std")]
+pub use map::HyperLogLogMap;
+pub use merge_bytes::merge_serialized;
+#[cfg(feature = "rocksdb")]
+pub use merge_bytes::rocksdb_merge;
+#[cfg(feature = "sled")]
+pub use merge_bytes::sled_merge_operator;
+#[cfg(feature = "murmur3")]
+pub use murmur3::{Murmur3Hasher, Murmur3State};
+pub use packed::PackedHyperLogLog;
+#[cfg(feature = "python")]
+pub use python::PyHyperLogLog;
+use recent::RecentHashes;
+#[cfg(feature = "redis-compat")]
+pub use redis::{RedisHasher, RedisState};
+#[cfg(feature = "metrics")]
+pub use reporter::MetricsReporter;
+pub use ring::HyperLogLogRing;
+#[cfg(feature = "rkyv")]
+pub use rkyv_impls::ArchivedHyperLogLog;
+pub use sampling::SamplingModel;
+#[cfg(feature = "std")]
+pub use sharded::ShardedHyperLogLog;
+pub use sip::{SeededState, SipHasher24};
+pub use sketch::CardinalitySketch;
+pub use sliding::SlidingHyperLogLog;
+use sparse::{default_sparse_threshold, Registers};
+pub use tailcut::TailCutHyperLogLog;
+pub use typed::TypedHyperLogLog;
+#[cfg(feature = "std")]
+pub use validating::{ValidatingHyperLogLog, ValidationReport};
+#[cfg(feature = "wasm")]
+pub use wasm::{WasmHyperLogLog, WASM_SEED};
+#[cfg(feature = "xxhash")]
+pub use xxh3::{Xxh3Hasher, Xxh3State};
+
+/// The hasher used by [`HyperLogLog::new`]: [`SipHasher24`] with both keys
+/// zero, so that independently constructed sketches hash items identically
+/// and can be merged, with or without `std`. (`RandomState` picks new keys
+/// per instance, which would make every merge meaningless, and the standard
+/// library's `DefaultHasher` may change between Rust releases.)
+pub type DefaultBuildHasher = BuildHasherDefault<SipHasher24>;
+
+/// The hash function a [`StableBuildHasher`] computes, recorded in
+/// serialized sketches so that one can't be decoded into a sketch hashing
+/// items differently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HashBackend {
+    /// One of the crate's own hashers: the default hasher, without keys, or
+    /// SipHash-2-4 with the keys of a [`SeededState`].
+    Native,
+    /// 64-bit XXH3, as `Xxh3State` computes with the `xxhash` feature.
+    Xxh3,
+    /// MurmurHash3 x64_128 truncated to 64 bits, as `Murmur3State` computes
+    /// with the `murmur3` feature.
+    Murmur3,
+    /// A hash function the crate doesn't know, such as a `BuildHasherDefault`
+    /// of some other `Hasher`, recorded by its [`HasherId`] alone. It decodes
+    /// only into a hasher with the same ID, and never into one of the
+    /// crate's own.
+    Custom,
+}
+
+impl HashBackend {
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            HashBackend::Native => 0,
+            HashBackend::Xxh3 => 1,
+            HashBackend::Murmur3 => 2,
+            HashBackend::Custom => 3,
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Option<HashBackend> {
+        match code {
+            0 => Some(HashBackend::Native),
+            1 => Some(HashBackend::Xxh3),
+            2 => Some(HashBackend::Murmur3),
+            3 => Some(HashBackend::Custom),
+            _ => None,
+        }
+    }
+}
+
+/// What items are hashed with, as merges check it: the hash of a fixed probe
+/// value, which tells apart any two hashers that hash items differently,
+/// whatever their types or keys, barring a one-in-2^64 collision.
+///
+/// Sketches of one hasher type differ only in their hashers' keys, so
+/// sketches seeded differently, or built with a per-instance `RandomState`,
+/// have different IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HasherId(u64);
+
+impl HasherId {
+    /// The ID of the hash function `build_hasher` builds.
+    pub fn of<S: BuildHasher>(build_hasher: &S) -> HasherId {
+        HasherId(build_hasher.hash_one(b"hyperloglog hasher id"))
+    }
+
+    /// The probe value's hash.
+    pub fn fingerprint(self) -> u64 {
+        self.0
+    }
+}
+
+/// A `BuildHasher` whose configuration can be written alongside a serialized
+/// sketch and restored from it, so that the decoded sketch hashes items the
+/// same way the original did.
+///
+/// `BuildHasherDefault<H>` has no configuration to record, and is recorded
+/// as [`HashBackend::Custom`] with its [`HasherId`] unless it is the default
+/// hasher; [`SeededState`] records its two SipHash keys. Hashers other than
+/// the crate's own name their [`HashBackend`] as well.
+pub trait StableBuildHasher: BuildHasher + Sized {
+    /// The hash function this hasher computes.
+    const BACKEND: HashBackend = HashBackend::Native;
+
+    /// The keys to record, or `None` for a hasher without keys.
+    fn keys(&self) -> Option<(u64, u64)>;
+
+    /// Rebuilds the hasher from recorded keys, or returns `None` if `keys`
+    /// doesn't describe a hasher of this type.
+    fn from_keys(keys: Option<(u64, u64)>) -> Option<Self>;
+}
+
+impl<H: Hasher + Default> StableBuildHasher for BuildHasherDefault<H> {
+    fn keys(&self) -> Option<(u64, u64)> {
+        None
+    }
+
+    fn from_keys(keys: Option<(u64, u64)>) -> Option<Self> {
+        match keys {
+            None => Some(BuildHasherDefault::default()),
+            Some(_) => None,
+        }
+    }
+}
+
+impl StableBuildHasher for SeededState {
+    fn keys(&self) -> Option<(u64, u64)> {
+        Some(SeededState::keys(self))
+    }
+
+    fn from_keys(keys: Option<(u64, u64)>) -> Option<Self> {
+        keys.map(|(k0, k1)| SeededState::with_keys(k0, k1))
+    }
+}
+
+/// The largest rank a register can hold at precision `p`: the 64 - p bits left
+/// after the index are all zero.
+fn max_rank(p: u8) -> u8 {
+    65 - p
+}
+
+/// One step of splitmix64 (Steele, Lea and Flood, 2014): a bijection on
+/// `u64` in which every output bit depends on every input bit, so even
+/// consecutive integers come out uniformly spread.
+fn mix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Mixes a 128-bit key down to 64 bits, as [`mix64`] mixes the low half
+/// alone when the high half is zero.
+fn mix128(key: u128) -> u64 {
+    let (high, low) = ((key >> 64) as u64, key as u64);
+    if high == 0 {
+        mix64(low)
+    } else {
+        mix64(low ^ mix64(high))
+    }
+}
+
+/// Splits a hash into a register index and rank.
+///
+/// The top `p` bits pick the register; the rank is the position of the first
+/// 1-bit in the remaining `64 - p` bits, so it is always >= 1 and a register
+/// value of 0 means "never touched".
+fn index_and_rank(hash: u64, p: u8) -> (usize, u8) {
+    let index = (hash >> (64 - u32::from(p))) as usize;
+    let w = hash << p;
+    let rank = cmp::min(w.leading_zeros() as u8 + 1, max_rank(p));
+    (index, rank)
+}
+
+fn alpha(m: usize) -> f64 {
+    match m {
+        16 => 0.673,
+        32 => 0.697,
+        64 => 0.709,
+        _ => 0.7213 / (1.0 + 1.079 / (m as f64)),
+    }
+}
+
+fn hll_cardinality(m: usize, sum: f64) -> f64 {
+    let mf64 = m as f64;
+    alpha(m) * mf64 * mf64 / sum
+}
+
+fn linear_count(m: usize, zero_count: usize) -> f64 {
+    let m = m as f64;
+    m * math::ln(m / (zero_count as f64))
+}
+
+/// The smallest precision a new sketch can have.
+const MIN_PRECISION: u8 = 4;
+
+/// Checks that a sketch can have `m` registers, returning its precision.
+///
+/// Fewer than 16 registers are rejected: the estimator's constants and
+/// corrections aren't calibrated for them, and the error would be over 26%
+/// anyway.
+fn check_register_count(m: usize) -> Result<u8, BuildError> {
+    let minimum = 1usize << MIN_PRECISION;
+    let maximum = 1u64 << encoding::MAX_PRECISION;
+    if m < minimum {
+        return Err(BuildError::TooFewRegisters {
+            registers: m,
+            minimum,
+        });
+    }
+    if m as u64 > maximum {
+        return Err(BuildError::TooManyRegisters {
+            registers: m,
+            maximum: maximum as usize,
+        });
+    }
+    if !m.is_power_of_two() {
+        return Err(BuildError::NotPowerOfTwo(m));
+    }
+    Ok(m.trailing_zeros() as u8)
+}
+
+/// The standard error of a sketch with `2^p` registers.
+fn standard_error(p: u8) -> f64 {
+    1.04 / math::sqrt((1u64 << p) as f64)
+}
+
+/// `part / whole` clamped to `[0, 1]`, and 0 when `whole` is.
+fn ratio(part: f64, whole: f64) -> f64 {
+    if whole > 0. {
+        (part / whole).clamp(0., 1.)
+    } else {
+        0.
+    }
+}
+
+/// The smallest precision whose standard error is at most `epsilon`.
+fn precision_for_error(epsilon: f64) -> Result<u8, BuildError> {
+    // Also rejects NaN.
+    if !(epsilon > 0. && epsilon <= 1.) {
+        return Err(BuildError::InvalidErrorRate(epsilon));
+    }
+    match (MIN_PRECISION..=encoding::MAX_PRECISION).find(|&p| standard_error(p) <= epsilon) {
+        Some(p) => Ok(p),
+        None => Err(BuildError::ErrorRateUnreachable {
+            target: epsilon,
+            best: standard_error(encoding::MAX_PRECISION),
+        }),
+    }
+}
+
+/// Counts the registers holding each value, for register layouts that can
+/// only be iterated; dense byte registers use [`simd::histogram`].
+fn rank_histogram<I: Iterator<Item = u8>>(registers: I) -> [u64; 65] {
+    let mut counts = [0u64; 65];
+    for r in registers {
+        counts[usize::from(r)] += 1;
+    }
+    counts
+}
+
+/// Returns the register count `m`, `Σ 2^-register` and the number of zero
+/// registers from a histogram of register values.
+///
+/// Each term `count · 2^-rank` is exact and they are added in rank order, so
+/// the sum depends only on the histogram. While `p` plus the largest register
+/// is at most 53, every partial sum is a multiple of `2^-max` below `2^p` and
+/// so fits in an `f64`: the sum is then exact, bit for bit the same as a sum
+/// of `2^-register` over the registers in any order.
+fn sum_and_zeros(counts: &[u64; 65]) -> (usize, f64, usize) {
+    let mut sum = 0.;
+    let mut scale = 1.;
+    for &c in counts.iter() {
+        sum += c as f64 * scale;
+        scale *= 0.5;
+    }
+    let m = counts.iter().sum::<u64>() as usize;
+    (m, sum, counts[0] as usize)
+}
+
+/// Estimates the cardinality from a histogram of register values, shared by
+/// every register layout in the crate.
+fn estimate(counts: &[u64; 65]) -> f64 {
+    estimate_details(counts).estimate
+}
+
+/// Like [`estimate`], but returns every intermediate value.
+fn estimate_details(counts: &[u64; 65]) -> EstimateDetails {
+    let (m, sum, zero_count) = sum_and_zeros(counts);
+    piecewise_details(m, sum, zero_count)
+}
+
+/// Below this multiple of `m`, linear counting beats the uncorrected raw
+/// estimate. The bound comes from the raw estimator's small-range bias rather
+/// than from the hash width, so it carries over to 64-bit hashes unchanged;
+/// it is only used for precisions without HyperLogLog++ tables, and by
+/// [`ClassicEstimator`].
+const SMALL_RANGE: f64 = 2.5;
+
+/// Rounds an estimate to the nearest integer, halves away from zero. Negative
+/// and NaN estimates give 0, and those past `u64::MAX` saturate.
+fn round_count(estimate: f64) -> u64 {
+    // `as` truncates and saturates, and below 2^53, where estimates can have
+    // a fraction, the fraction it drops is computed exactly.
+    let whole = estimate as u64;
+    if estimate - whole as f64 >= 0.5 {
+        whole.saturating_add(1)
+    } else {
+        whole
+    }
+}
+
+/// The piecewise estimate for 64-bit hashes:
+///
+/// - linear counting while some registers are empty and its estimate is below
+///   the HyperLogLog++ threshold for the precision (or, without tables, while
+///   the raw estimate is below `2.5m`);
+/// - otherwise the raw estimate, less its tabulated bias while it is below `5m`.
+///
+/// There is deliberately no large-range correction. The original paper's
+/// `-2^32 ln(1 - E / 2^32)` compensates for collisions in a 32-bit hash space;
+/// with 64-bit hashes collisions stay negligible until far beyond any
+/// realistic cardinality, and that formula is undefined once `E` passes 2^32.
+fn piecewise_estimate(m: usize, sum: f64, zero_count: usize) -> f64 {
+    piecewise_details(m, sum, zero_count).estimate
+}
+
+fn piecewise_details(m: usize, sum: f64, zero_count: usize) -> EstimateDetails {
+    let p = m.trailing_zeros() as u8;
+    let raw = hll_cardinality(m, sum);
+    let lc = if zero_count > 0 {
+        Some(linear_count(m, zero_count))
+    } else {
+        None
+    };
+    let small = lc.is_some_and(|lc| match bias::threshold(p) {
+        // HyperLogLog++ decides on the linear-counting estimate itself.
+        Some(threshold) => lc <= threshold,
+        None => raw <= SMALL_RANGE * m as f64,
+    });
+    let (estimate, method) = match lc {
+        Some(lc) if small => (lc, EstimationMethod::LinearCounting),
+        _ => {
+            let corrected = match bias::estimate_bias(raw, p) {
+                Some(bias) => raw - bias,
+                None => raw,
+            };
+            (corrected, EstimationMethod::HyperLogLog)
+        }
+    };
+    EstimateDetails {
+        estimate,
+        method,
+        raw,
+        linear_count: lc,
+        zero_registers: zero_count,
+        harmonic_sum: sum,
+    }
+}
+
+/// The LogLog-Beta estimate from a histogram of register values, falling back
+/// to [`estimate`] for sizes without beta coefficients.
+fn estimate_beta(counts: &[u64; 65]) -> f64 {
+    let (m, sum, zero_count) = sum_and_zeros(counts);
+    beta::estimate(m, sum, zero_count).unwrap_or_else(|| piecewise_estimate(m, sum, zero_count))
+}
+
+/// Ertl's improved raw estimate from a histogram of register values.
+fn estimate_improved(counts: &[u64; 65]) -> f64 {
+    let m = counts.iter().sum::<u64>() as usize;
+    improved::estimate(counts, m, max_rank(m.trailing_zeros() as u8))
+}
+
+/// A HyperLogLog cardinality sketch.
+///
+/// Create one with [`HyperLogLog::with_precision`], or with [`Default`] for
+/// precision 14. A new sketch starts in a sparse representation that only stores the
+/// registers that have been touched, and converts itself to a dense array of
+/// one byte per register once that is smaller (see
+/// [`HyperLogLog::set_sparse_threshold`]). The representation never affects
+/// the estimate. Alongside the registers, every sketch keeps a 520-byte
+/// histogram of their values, which makes [`count`](HyperLogLog::count)
+/// constant-time.
+#[derive(Clone)]
+pub struct HyperLogLog<S = DefaultBuildHasher> {
+    registers: Registers,
+    /// How many registers hold each value, kept up to date as registers
+    /// change so that estimating never has to scan them.
+    histogram: [u64; 65],
+    precision: u8,
+    sparse_threshold: usize,
+    build_hasher: S,
+    cache: EstimateCache,
+    /// The highest estimate a monotonic sketch has reached, or `None` if the
+    /// sketch isn't monotonic.
+    watermark: Option<f64>,
+    /// Recently added hashes whose register updates can be skipped, if the
+    /// cache is on.
+    recent: Option<RecentHashes>,
+    /// What [`count`](HyperLogLog::count) estimates with.
+    estimator: BuiltinEstimator,
+    /// How many items have been added, repeats included.
+    items_added: u64,
+    /// Whether merges skip the hasher check, once told to
+    /// [assume](HyperLogLog::assume_same_hasher) the hashers match.
+    hasher_assumed: bool,
+}
+
+impl HyperLogLog {
+    /// Creates a sketch with `2^p` registers, for `p` from 4 to 18: the
+    /// precisions the HyperLogLog++ bias corrections are tabulated for. Its
+    /// standard error is `1.04 / √(2^p)`, about 0.8% at the default of 14.
+    ///
+    /// Other precisions fail with [`BuildError::InvalidPrecision`]. Sketches
+    /// of up to 2^24 registers can be made with [`HyperLogLog::builder`].
+    pub fn with_precision(p: u8) -> Result<HyperLogLog, BuildError> {
+        if !(MIN_PRECISION..=bias::MAX_PRECISION).contains(&p) {
+            return Err(BuildError::InvalidPrecision(p));
+        }
+        HyperLogLog::try_new(1 << p)
+    }
+
+    /// Creates a sketch with `m` registers, for code written against the
+    /// register count; new code should prefer
+    /// [`with_precision`](HyperLogLog::with_precision).
+    ///
+    /// Panics unless `m` is a power of two from 16 to 2^24; see
+    /// [`HyperLogLog::try_new`] for a version that returns an error instead.
+    pub fn new(m: usize) -> HyperLogLog {
+        HyperLogLog::with_hasher(m, DefaultBuildHasher::default())
+    }
+
+    /// Creates a sketch with `m` registers, or explains why `m` is unusable.
+    pub fn try_new(m: usize) -> Result<HyperLogLog, BuildError> {
+        HyperLogLog::try_with_hasher(m, DefaultBuildHasher::default())
+    }
+
+    /// The standard error of a sketch with `m` registers, `1.04 / √m`, for
+    /// choosing a size before building one.
+    pub fn standard_error(m: usize) -> f64 {
+        1.04 / math::sqrt(m as f64)
+    }
+
+    /// A builder for a sketch configured in several ways at once.
+    pub fn builder() -> HyperLogLogBuilder {
+        HyperLogLogBuilder::new()
+    }
+
+    /// Creates the smallest sketch whose standard error,
+    /// [`error_estimate`](HyperLogLog::error_estimate), is at most `epsilon`.
+    ///
+    /// Sketches have at least 16 registers, so any `epsilon` of 0.26 or more
+    /// gives the same, smallest sketch.
+    pub fn with_error(epsilon: f64) -> Result<HyperLogLog, BuildError> {
+        Ok(HyperLogLog::new(1 << precision_for_error(epsilon)?))
+    }
+
+    /// Creates the most accurate sketch whose dense register array, one byte
+    /// per register, fits in `bytes`.
+    pub fn with_memory_budget(bytes: usize) -> Result<HyperLogLog, BuildError> {
+        let minimum = 1usize << MIN_PRECISION;
+        if bytes < minimum {
+            return Err(BuildError::BudgetTooSmall { bytes, minimum });
+        }
+        let p = cmp::min(
+            (usize::BITS - 1 - bytes.leading_zeros()) as u8,
+            encoding::MAX_PRECISION,
+        );
+        Ok(HyperLogLog::new(1 << p))
+    }
+
+    /// Builds a sketch from a register array produced elsewhere, such as by
+    /// another HyperLogLog implementation, without this crate's serializer.
+    ///
+    /// `registers[i]` is register `i`'s value, and its length gives the
+    /// precision `p`. For the sketch to agree with one built here, the
+    /// producer must lay out its registers the way
+    /// [`add_hashed`](HyperLogLog::add_hashed) does: a 64-bit hash raises the
+    /// register indexed by its top `p` bits to its rank, one more than the
+    /// number of leading zeros in the remaining `64 - p` bits (at most
+    /// `65 - p`), and 0 means untouched.
+    ///
+    /// Fails unless the length is a power of two from 16 to 2^24 and every
+    /// value is at most `65 - p`.
+    pub fn from_registers(registers: Vec<u8>) -> Result<HyperLogLog, RegisterError> {
+        HyperLogLog::from_registers_with_hasher(registers, DefaultBuildHasher::default())
+    }
+}
+
+impl HyperLogLog<SeededState> {
+    /// Creates a sketch with `m` registers whose item-to-register mapping is
+    /// fully determined by `seed`, using keyed SipHash-2-4.
+    ///
+    /// Sketches created with the same seed can be built in different
+    /// processes or on different machines and still be merged, as those of
+    /// [`HyperLogLog::new`] can; unlike those, sketches with different seeds
+    /// hash items independently.
+    pub fn new_seeded(m: usize, seed: u64) -> HyperLogLog<SeededState> {
+        HyperLogLog::with_hasher(m, SeededState::new(seed))
+    }
+
+    /// Like [`HyperLogLog::new_seeded`], but with both SipHash keys given explicitly.
+    pub fn new_with_keys(m: usize, k0: u64, k1: u64) -> HyperLogLog<SeededState> {
+        HyperLogLog::with_hasher(m, SeededState::with_keys(k0, k1))
+    }
+}
+
+impl<S: BuildHasher> HyperLogLog<S> {
+    /// Creates a sketch with `m` registers that hashes items with `build_hasher`.
+    ///
+    /// Sketches can only be meaningfully merged or compared if they were built
+    /// with hashers that produce the same hash for the same item; merges
+    /// check that they were, by their [`HasherId`]s.
+    ///
+    /// Panics if `m` is unusable, like [`HyperLogLog::new`].
+    pub fn with_hasher(m: usize, build_hasher: S) -> HyperLogLog<S> {
+        match HyperLogLog::try_with_hasher(m, build_hasher) {
+            Ok(h) => h,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like [`HyperLogLog::with_hasher`], but returns an error for an unusable `m`.
+    pub fn try_with_hasher(m: usize, build_hasher: S) -> Result<HyperLogLog<S>, BuildError> {
+        let precision = check_register_count(m)?;
+        let mut histogram = [0; 65];
+        histogram[0] = m as u64;
+        Ok(HyperLogLog {
+            registers: Registers::Sparse(Vec::new()),
+            histogram,
+            precision,
+            sparse_threshold: default_sparse_threshold(precision),
+            hasher_assumed: false,
+            build_hasher,
+            cache: EstimateCache::new(),
+            watermark: None,
+            recent: None,
+            estimator: BuiltinEstimator::default(),
+            items_added: 0,
+        })
+    }
+
+    /// Adds an item, hashing it with the sketch's hasher.
+    ///
+    /// The item can be borrowed: `&T` hashes exactly as `T` does, so
+    /// `h.add(&name)`, `h.add("literal")` and `h.add(name)` all raise the
+    /// same register, and nothing needs to be cloned or moved in.
+    pub fn add<H: Hash>(&mut self, item: H) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h);
+    }
+
+    /// Adds the item whose representation is exactly `bytes`, hashed with a
+    /// single `write` and no length prefix or other framing.
+    ///
+    /// [`add`](HyperLogLog::add) goes through `Hash`, which frames a `str`
+    /// differently from a byte slice or array, so `"foo"` and `b"foo"` would
+    /// be counted as different items. Use this instead when the same values
+    /// arrive as different Rust types, or when sketches must agree with ones
+    /// built by other languages from the same hash function.
+    pub fn add_bytes(&mut self, bytes: impl AsRef<[u8]>) {
+        let h = self.hash_bytes(bytes.as_ref());
+        self.add_hashed(h);
+    }
+
+    /// The hash [`add_bytes`](HyperLogLog::add_bytes) adds `bytes` by.
+    fn hash_bytes(&self, bytes: &[u8]) -> u64 {
+        let mut hasher = self.build_hasher.build_hasher();
+        hasher.write(bytes);
+        hasher.finish()
+    }
+
+    /// Adds an item by its precomputed 64-bit hash, skipping the sketch's hasher.
+    ///
+    /// The caller is responsible for supplying a well-distributed hash: every
+    /// bit matters, since the top bits pick the register and the rest give its
+    /// rank. Mixing `add` and `add_hashed` in one sketch, or merging sketches
+    /// fed by both, only makes sense if the upstream hash is the same function
+    /// the sketch's hasher computes.
+    pub fn add_hashed(&mut self, hash: u64) {
+        self.insert_hashed(hash);
+    }
+
+    /// Adds an integer key, mixing it with splitmix64 instead of hashing it
+    /// with the sketch's hasher: several times faster than
+    /// [`add`](HyperLogLog::add) for IDs and other integers, and just as
+    /// accurate for sequential ones.
+    ///
+    /// This is a hash of its own, not the one `add` computes, and ignores
+    /// the hasher and any seed, so a key added with both counts twice. Feed
+    /// each sketch, and every sketch merged with it, integer keys through
+    /// one of the two only; the sketch doesn't record which.
+    pub fn add_u64(&mut self, key: u64) {
+        self.add_hashed(mix64(key));
+    }
+
+    /// Like [`add_u64`](HyperLogLog::add_u64) for 128-bit keys such as
+    /// UUIDs. A key below 2^64 is added as `add_u64` adds it, so keys can be
+    /// widened without changing the sketch.
+    pub fn add_u128(&mut self, key: u128) {
+        self.add_hashed(mix128(key));
+    }
+
+    /// Adds an item like [`add`](HyperLogLog::add), returning whether it
+    /// raised a register.
+    ///
+    /// `true` means the sketch changed, so the item's hash had not been added
+    /// to it, or to any sketch merged into it, before. `false` means only
+    /// that the item would not change the estimate, not that it was
+    /// definitely seen before: a new item whose rank is no higher than its
+    /// register's returns `false` too, as most do once the sketch is large.
+    /// Repeats always return `false`, so the error is one-sided.
+    pub fn insert<H: Hash>(&mut self, item: H) -> bool {
+        let h = self.build_hasher.hash_one(item);
+        self.insert_hashed(h)
+    }
+
+    /// Like [`insert`](HyperLogLog::insert) for a precomputed 64-bit hash; see
+    /// [`add_hashed`](HyperLogLog::add_hashed).
+    pub fn insert_hashed(&mut self, hash: u64) -> bool {
+        self.items_added = self.items_added.saturating_add(1);
+        if let Some(ref mut recent) = self.recent {
+            if recent.check_and_insert(hash) {
+                return false;
+            }
+        }
+        let (m, v) = index_and_rank(hash, self.precision);
+
+        match self.registers.raise(m, v) {
+            Some(old) => {
+                self.histogram[usize::from(old)] -= 1;
+                self.histogram[usize::from(v)] += 1;
+                self.registers_changed();
+                self.check_promotion();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds every item, hashing each with the sketch's hasher, and returns how
+    /// many of them raised a register: a cheap signal of how much the sketch
+    /// is still changing. Equivalent to [`extend`](Extend::extend), which
+    /// calls this and drops the count.
+    pub fn add_all<I, T>(&mut self, items: I) -> usize
+    where
+        I: IntoIterator<Item = T>,
+        T: Hash,
+    {
+        let HyperLogLog {
+            ref mut registers,
+            ref mut histogram,
+            precision,
+            sparse_threshold,
+            ref build_hasher,
+            ref mut recent,
+            ref mut items_added,
+            ..
+        } = *self;
+        let mut added = 0;
+        let updates = items
+            .into_iter()
+            .map(|item| build_hasher.hash_one(item))
+            .inspect(|_| added += 1)
+            .filter(|&h| recent.as_mut().is_none_or(|r| !r.check_and_insert(h)))
+            .map(|h| index_and_rank(h, precision));
+        let raised = registers.raise_all(updates, 1 << precision, sparse_threshold, histogram);
+        *items_added = items_added.saturating_add(added);
+        if raised > 0 {
+            self.registers_changed();
+        }
+        raised
+    }
+
+    /// Like [`add_all`](HyperLogLog::add_all) for a slice. Items are hashed a
+    /// block at a time into a buffer before any register is touched, so the
+    /// hashing loop has no dependency on register updates and can be
+    /// pipelined or vectorized.
+    pub fn add_slice<T: Hash>(&mut self, items: &[T]) -> usize {
+        const BLOCK: usize = 64;
+        let mut hashes = [0u64; BLOCK];
+        let mut raised = 0;
+        for block in items.chunks(BLOCK) {
+            for (h, item) in hashes.iter_mut().zip(block) {
+                *h = self.build_hasher.hash_one(item);
+            }
+            raised += self.raise_hashes(hashes[..block.len()].iter().cloned());
+        }
+        raised
+    }
+
+    /// Whether no item has been added since the sketch was created or cleared.
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty()
+    }
+
+    /// How many items have been added since the sketch was created or
+    /// cleared, repeats included, summed over the sketches merged into it.
+    /// Items skipped by the [recent-hash cache](HyperLogLog::set_recent_cache)
+    /// count too. Saturates at `u64::MAX`.
+    ///
+    /// Only this type keeps the count: a sketch converted from another of
+    /// the crate's sketch types, or decoded from a foreign format, starts
+    /// at 0.
+    pub fn items_added(&self) -> u64 {
+        self.items_added
+    }
+
+    /// The average number of times each distinct item was added:
+    /// [`items_added`](HyperLogLog::items_added) over
+    /// [`count`](HyperLogLog::count), 10 for 10M events from 1M distinct
+    /// users. An empty sketch has a ratio of 0.
+    pub fn duplication_ratio(&self) -> f64 {
+        if self.is_empty() {
+            return 0.;
+        }
+        self.items_added as f64 / self.count()
+    }
+
+    /// Resets the sketch to empty in place, keeping its register allocation,
+    /// hasher and configuration, so one sketch can be reused per window.
+    pub fn clear(&mut self) {
+        self.registers.clear();
+        self.recount();
+        if let Some(ref mut recent) = self.recent {
+            recent.clear();
+        }
+        if self.watermark.is_some() {
+            self.watermark = Some(0.);
+        }
+        self.items_added = 0;
+    }
+
+    /// Makes [`count`](HyperLogLog::count) monotonic: the sketch remembers
+    /// the highest estimate it has reached and never reports lower, even
+    /// where the estimate itself dips as items are added, as it can where
+    /// `count` switches from linear counting to the HyperLogLog estimate.
+    ///
+    /// The remembered estimate is kept by [`to_bytes`](HyperLogLog::to_bytes)
+    /// and the crate's other encodings, and by serde, but not by `rkyv`
+    /// archives or the formats of other systems. [`merge`](HyperLogLog::merge)
+    /// into a monotonic sketch keeps the higher of the two. [`clear`](HyperLogLog::clear) resets it to
+    /// 0. Every addition that raises a register recomputes the estimate, so
+    /// adding to a monotonic sketch is somewhat slower. Turning the mode off
+    /// forgets the remembered estimate.
+    pub fn set_monotonic(&mut self, monotonic: bool) {
+        self.watermark = if monotonic {
+            Some(self.current_estimate())
+        } else {
+            None
+        };
+    }
+
+    /// Whether [`count`](HyperLogLog::count) is monotonic; see
+    /// [`set_monotonic`](HyperLogLog::set_monotonic).
+    pub fn is_monotonic(&self) -> bool {
+        self.watermark.is_some()
+    }
+
+    /// Makes [`count`](HyperLogLog::count), and the estimates built on it,
+    /// use `estimator`, one of the crate's own; [`BiasCorrectedEstimator`]
+    /// until then. [`count_with`](HyperLogLog::count_with) takes any other.
+    ///
+    /// The choice is kept by [`to_bytes`](HyperLogLog::to_bytes) and the
+    /// crate's other encodings, and by serde, but not by `rkyv` archives or
+    /// the formats of other systems. Merging keeps the target's choice. A
+    /// [monotonic](HyperLogLog::set_monotonic) sketch keeps the highest
+    /// estimate it has reached with the estimator before.
+    pub fn set_estimator<E: Into<BuiltinEstimator>>(&mut self, estimator: E) {
+        self.estimator = estimator.into();
+        self.cache.invalidate();
+    }
+
+    /// What [`count`](HyperLogLog::count) estimates with; see
+    /// [`set_estimator`](HyperLogLog::set_estimator).
+    pub fn estimator(&self) -> BuiltinEstimator {
+        self.estimator
+    }
+
+    /// Puts a cache of the last `capacity` or so distinct hashes added, rounded
+    /// up to a power of two, in front of the registers, or removes it if
+    /// `capacity` is 0. An item whose hash is in the cache is skipped
+    /// without touching the registers; any other is added as usual and
+    /// replaces one entry.
+    ///
+    /// This only pays on very repetitive streams, where a few hot items make
+    /// up most additions, and then most for sparse sketches, whose register
+    /// updates cost the most; on less skewed streams the misses cost more
+    /// than the hits save, and `add` gets slower. Items are still hashed,
+    /// so the most it saves is the update. The cache can only skip additions that
+    /// would change nothing, so counts and registers are the same with it
+    /// as without. It is emptied by [`clear`](HyperLogLog::clear), kept by
+    /// `clone`, and not kept by any encoding or serde: a decoded sketch has
+    /// none. [`add_bytes`](HyperLogLog::add_bytes),
+    /// [`add_u64`](HyperLogLog::add_u64) and every batched form of `add`
+    /// consult it too, but [`merge`](HyperLogLog::merge) doesn't.
+    pub fn set_recent_cache(&mut self, capacity: usize) {
+        self.recent = if capacity == 0 {
+            None
+        } else {
+            Some(RecentHashes::new(capacity))
+        };
+    }
+
+    /// The number of hashes the cache [`set_recent_cache`](HyperLogLog::set_recent_cache)
+    /// put in front of the registers can hold, or 0 without one.
+    pub fn recent_cache_capacity(&self) -> usize {
+        self.recent.as_ref().map_or(0, RecentHashes::capacity)
+    }
+
+    /// Whether the sketch is still in its sparse representation.
+    pub fn is_sparse(&self) -> bool {
+        self.registers.is_sparse()
+    }
+
+    /// Sets how many touched registers the sparse representation may hold
+    /// before the sketch converts itself to dense registers, converting it
+    /// immediately if it already holds more. A threshold of 0 makes the
+    /// sketch dense.
+    ///
+    /// The default is `m / 4`, the point where the sparse list (four bytes
+    /// per touched register) would outgrow the dense array.
+    pub fn set_sparse_threshold(&mut self, entries: usize) {
+        self.sparse_threshold = entries;
+        self.check_promotion();
+    }
+
+    fn check_promotion(&mut self) {
+        let m = self.num_registers();
+        self.registers.promote_past(m, self.sparse_threshold);
+    }
+
+    /// Folds `other` into `self` by taking the register-wise maximum, so that
+    /// `self` estimates the cardinality of the union of both streams.
+    ///
+    /// Returns whether any register rose: `false` means `self` already
+    /// [dominated](HyperLogLog::dominates) `other` and is unchanged, so
+    /// there is nothing new to persist or pass on.
+    ///
+    /// Merging is commutative, associative and idempotent, however the two
+    /// sketches store their registers, so replicas that merge each other in
+    /// any order, any number of times, end up with the same registers: the
+    /// sketch is a grow-only state-based CRDT.
+    ///
+    /// A monotonic sketch keeps the higher of the two sketches' remembered
+    /// estimates, taking the estimate of a sketch that isn't monotonic. That
+    /// merge is commutative and idempotent too, but the remembered estimate
+    /// may include that of an intermediate merge, so replicas that merged
+    /// in different groupings agree on it only once they have exchanged
+    /// their final states.
+    ///
+    /// The [number of items added](HyperLogLog::items_added) is summed, which
+    /// is neither idempotent nor a join: merging the same sketch in twice
+    /// counts its items twice.
+    ///
+    /// Fails with [`MergeError::PrecisionMismatch`] if the precisions differ,
+    /// and with [`MergeError::HasherMismatch`] if the sketches hash items
+    /// differently, as told by their [`HasherId`]s, unless either was told
+    /// to [assume otherwise](HyperLogLog::assume_same_hasher). The other
+    /// operations on two sketches fail the same way.
+    pub fn merge(&mut self, other: &HyperLogLog<S>) -> Result<bool, MergeError> {
+        self.check_compatible(other)?;
+        let m = self.num_registers();
+        let raised = self.registers.merge(&other.registers, m);
+        if raised {
+            self.recount();
+        }
+        self.check_promotion();
+        if let Some(watermark) = self.watermark {
+            self.watermark = Some(watermark.max(other.count()));
+        }
+        self.items_added = self.items_added.saturating_add(other.items_added);
+        Ok(raised)
+    }
+
+    /// Whether every register of `self` is at least the matching register of
+    /// `other`, so that merging `other` in would change nothing. A sketch
+    /// dominates every sketch of a subset of its items, built with the same
+    /// hasher.
+    pub fn dominates(&self, other: &HyperLogLog<S>) -> Result<bool, MergeError> {
+        self.check_compatible(other)?;
+        Ok(self.registers().zip(other.registers()).all(|(a, b)| a >= b))
+    }
+
+    fn check_compatible(&self, other: &HyperLogLog<S>) -> Result<(), MergeError> {
+        if self.precision != other.precision {
+            return Err(MergeError::PrecisionMismatch {
+                left: self.precision,
+                right: other.precision,
+            });
+        }
+        self.check_hasher(other.hasher_id())
+    }
+
+    /// Fails unless `other` is `None`, the sketch's own ID is, or they match.
+    fn check_hasher(&self, other: Option<HasherId>) -> Result<(), MergeError> {
+        match (self.hasher_id(), other) {
+            (Some(left), Some(right)) if left != right => {
+                Err(MergeError::HasherMismatch { left, right })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The [ID](HasherId) of the sketch's hasher, which merges and other
+    /// operations on two sketches check, or `None` after
+    /// [`assume_same_hasher`](HyperLogLog::assume_same_hasher).
+    pub fn hasher_id(&self) -> Option<HasherId> {
+        if self.hasher_assumed {
+            return None;
+        }
+        Some(HasherId::of(&self.build_hasher))
+    }
+
+    /// Turns off the hasher check for operations between this sketch and
+    /// any other, whichever side it is on, for callers who know the two
+    /// hash alike although their hashers don't: sketches fed only
+    /// [precomputed hashes](HyperLogLog::add_hashed), say, whose hashers
+    /// are never used. The check is off until the sketch is dropped, in
+    /// clones and unions of it too, but not in sketches decoded from its
+    /// serialized forms.
+    ///
+    /// Operations between sketches that really do hash differently give
+    /// meaningless results.
+    pub fn assume_same_hasher(&mut self) {
+        self.hasher_assumed = true;
+    }
+
+    /// Returns a new sketch estimating the union of `self` and `other`.
+    pub fn union(&self, other: &HyperLogLog<S>) -> Result<HyperLogLog<S>, MergeError>
+    where
+        S: Clone,
+    {
+        let mut merged = HyperLogLog {
+            registers: self.registers.clone(),
+            histogram: self.histogram,
+            precision: self.precision,
+            sparse_threshold: self.sparse_threshold,
+            build_hasher: self.build_hasher.clone(),
+            cache: self.cache.clone(),
+            watermark: self.watermark,
+            recent: self.recent.clone(),
+            estimator: self.estimator,
+            items_added: self.items_added,
+            hasher_assumed: self.hasher_assumed,
+        };
+        merged.merge(other)?;
+        Ok(merged)
+    }
+
+    /// Returns a new sketch estimating the union of all of `sketches`, for
+    /// combining the partial sketches of a sharded job in one call.
+    ///
+    /// Every precision is checked before anything is merged; the first
+    /// sketch that differs from the first one's is reported by its index.
+    /// The sketches are then folded into a single copy of the first, with
+    /// no other allocation. Fails with [`MergeError::NoSketches`] if there
+    /// are none.
+    pub fn merge_many<'a, I>(sketches: I) -> Result<HyperLogLog<S>, MergeError>
+    where
+        I: IntoIterator<Item = &'a HyperLogLog<S>>,
+        I::IntoIter: Clone,
+        S: Clone + 'a,
+    {
+        let mut sketches = sketches.into_iter();
+        let first = sketches.next().ok_or(MergeError::NoSketches)?;
+        if let Some((index, h)) = sketches
+            .clone()
+            .enumerate()
+            .find(|&(_, h)| h.precision != first.precision)
+        {
+            return Err(MergeError::PrecisionMismatchAt {
+                index: index + 1,
+                expected: first.precision,
+                found: h.precision,
+            });
+        }
+        let mut merged = first.clone();
+        for h in sketches {
+            merged.merge(h)?;
+        }
+        Ok(merged)
+    }
+
+    /// Folds the sketch down to precision `new_p`, so it can be merged with
+    /// sketches built at that precision.
+    ///
+    /// Each new register covers `2^(p - new_p)` old ones. The low index bits
+    /// that no longer pick a register become the leading bits of the rank: a
+    /// register whose dropped bits are nonzero gets the rank of their first
+    /// 1-bit, and one whose dropped bits are all zero keeps its rank offset by
+    /// `p - new_p`. The result is exactly the sketch `new_p` would have built
+    /// from the same hashes.
+    pub fn reduce_precision(&self, new_p: u8) -> Result<HyperLogLog<S>, FoldError>
+    where
+        S: Clone,
+    {
+        if new_p > self.precision {
+            return Err(FoldError::PrecisionTooHigh {
+                current: self.precision,
+                requested: new_p,
+            });
+        }
+        if new_p < MIN_PRECISION {
+            return Err(FoldError::PrecisionTooLow {
+                requested: new_p,
+                minimum: MIN_PRECISION,
+            });
+        }
+        let shift = self.precision - new_p;
+        let mut folded = vec![0u8; 1 << new_p];
+        for (index, rank) in self.registers().enumerate() {
+            if rank == 0 {
+                continue;
+            }
+            let dropped = index & ((1 << shift) - 1);
+            let new_rank = if dropped == 0 {
+                rank + shift
+            } else {
+                // The dropped bits sit at the top of the new rank's bits.
+                (dropped.leading_zeros() - (usize::BITS - u32::from(shift))) as u8 + 1
+            };
+            let r = &mut folded[index >> shift];
+            *r = cmp::max(*r, new_rank);
+        }
+        let mut h = HyperLogLog::from_dense(new_p, folded, self.build_hasher.clone());
+        h.watermark = self.watermark;
+        h.items_added = self.items_added;
+        Ok(h)
+    }
+
+    /// Merges `other` into `self` even if their precisions differ, first
+    /// folding whichever has more registers down to the other's precision
+    /// with [`HyperLogLog::reduce_precision`]. Returns the precision `self`
+    /// ends up with, which is lower than before if `self` was the one folded.
+    ///
+    /// Fails with [`MergeError::Fold`] if the common precision is below the
+    /// minimum, and like [`merge`](HyperLogLog::merge) if the hashers
+    /// differ, in both cases leaving `self` unchanged.
+    pub fn merge_compatible(&mut self, other: &HyperLogLog<S>) -> Result<u8, MergeError>
+    where
+        S: Clone,
+    {
+        self.check_hasher(other.hasher_id())?;
+        let target = cmp::min(self.precision, other.precision);
+        if target < MIN_PRECISION {
+            return Err(MergeError::Fold(FoldError::PrecisionTooLow {
+                requested: target,
+                minimum: MIN_PRECISION,
+            }));
+        }
+        if self.precision > target {
+            *self = self.reduce_precision(target).map_err(MergeError::Fold)?;
+        }
+        if other.precision > target {
+            let folded = other.reduce_precision(target).map_err(MergeError::Fold)?;
+            self.merge(&folded)?;
+        } else {
+            self.merge(other)?;
+        }
+        Ok(target)
+    }
+
+    /// Estimates the number of distinct items added.
+    ///
+    /// The sketch keeps a histogram of its register values up to date as
+    /// items are added, so this takes the same constant time at any number
+    /// of registers, and gives exactly the estimate a scan of the registers
+    /// would. The estimate is also cached until a register changes.
+    ///
+    /// A [monotonic](HyperLogLog::set_monotonic) sketch returns the highest
+    /// estimate it has reached instead whenever that is higher.
+    pub fn count(&self) -> f64 {
+        let estimate = self.current_estimate();
+        match self.watermark {
+            Some(watermark) => watermark.max(estimate),
+            None => estimate,
+        }
+    }
+
+    /// Like [`count`](HyperLogLog::count), rounded to the nearest integer
+    /// (halves round up) and saturating at `u64::MAX`.
+    ///
+    /// An empty sketch counts 0, and a sketch that has had any item added
+    /// counts at least 1.
+    pub fn count_u64(&self) -> u64 {
+        if self.is_empty() {
+            return 0;
+        }
+        cmp::max(round_count(self.count()), 1)
+    }
+
+    /// Returns the [bias-corrected](BiasCorrectedEstimator) estimate, what
+    /// `count()` gives by default, with an interval around it that contains
+    /// the true count with about the given confidence.
+    ///
+    /// The interval is narrower while linear counting is in use, whose error
+    /// is smaller than the HyperLogLog estimate's at light loads.
+    pub fn count_with_bounds(&self, confidence: Confidence) -> EstimateBounds {
+        bounds::bounds(&self.estimate_details(), self.num_registers(), confidence)
+    }
+
+    /// A lower bound on the number of distinct items added that always holds:
+    /// the number of non-zero registers, each of which was raised by a
+    /// different item. Kept up to date as registers change, so it costs no
+    /// more than a subtraction.
+    pub fn count_lower_bound(&self) -> u64 {
+        self.num_registers() as u64 - self.histogram[0]
+    }
+
+    /// A tighter lower bound that holds with about 99.7% confidence, the
+    /// lower end of [`count_with_bounds`](HyperLogLog::count_with_bounds)
+    /// at [`Confidence::P99`] rounded down. At light loads it comes from
+    /// linear counting's error. Never below
+    /// [`count_lower_bound`](HyperLogLog::count_lower_bound).
+    pub fn count_probable_lower_bound(&self) -> u64 {
+        self.count_with_bounds(Confidence::P99).lower as u64
+    }
+
+    /// An upper bound that holds with about 99.7% confidence, the upper end
+    /// of [`count_with_bounds`](HyperLogLog::count_with_bounds) at
+    /// [`Confidence::P99`] rounded up. No upper bound holds for certain,
+    /// since any number of items can share registers; past the reliable
+    /// range this is `u64::MAX`.
+    pub fn count_upper_bound(&self) -> u64 {
+        let upper = self.count_with_bounds(Confidence::P99).upper;
+        // `as` truncates and saturates, so round up what it cut off.
+        let whole = upper as u64;
+        if (whole as f64) < upper {
+            whole.saturating_add(1)
+        } else {
+            whole
+        }
+    }
+
+    /// Whether more than `threshold` distinct items have been added:
+    /// [`Exceeds::Definitely`] if [`count_lower_bound`](HyperLogLog::count_lower_bound)
+    /// is above it, which is checked first and without floating point, and
+    /// otherwise [`Exceeds::Probably`] if the rounded estimate,
+    /// [`count_u64`](HyperLogLog::count_u64), is.
+    pub fn exceeds(&self, threshold: u64) -> Exceeds {
+        if self.count_lower_bound() > threshold {
+            Exceeds::Definitely
+        } else if self.count_u64() > threshold {
+            Exceeds::Probably
+        } else {
+            Exceeds::No
+        }
+    }
+
+    /// Returns the [bias-corrected](BiasCorrectedEstimator) estimate, what
+    /// `count()` gives by default, along with the values behind it: the raw
+    /// and linear-counting estimates, which of them was used, the number of
+    /// empty registers and the harmonic sum.
+    pub fn estimate_details(&self) -> EstimateDetails {
+        estimate_details(&self.rank_counts())
+    }
+
+    /// Estimates the cardinality of the union of `self` and `other` without
+    /// modifying either or allocating a merged sketch, with `self`'s
+    /// [estimator](HyperLogLog::set_estimator).
+    pub fn union_count(&self, other: &HyperLogLog<S>) -> Result<f64, MergeError> {
+        self.check_compatible(other)?;
+        let m = self.num_registers();
+        let maxima = self
+            .registers
+            .iter(m)
+            .zip(other.registers.iter(m))
+            .map(|(a, b)| cmp::max(a, b));
+        Ok(self
+            .estimator
+            .estimate(self.precision, &rank_histogram(maxima)))
+    }
+
+    /// Estimates how many items `self` and `other` have in common, by
+    /// inclusion–exclusion: `|A| + |B| - |A ∪ B|`, clamped at zero.
+    ///
+    /// The error of this estimate scales with the size of the union, not the
+    /// intersection: each term carries an error of about
+    /// [`error_estimate`](HyperLogLog::error_estimate) times its own size. An
+    /// overlap that is small compared to the sets may come out as anything
+    /// from zero to several times its true size.
+    pub fn intersection_count(&self, other: &HyperLogLog<S>) -> Result<f64, MergeError> {
+        let union = self.union_count(other)?;
+        Ok((self.count() + other.count() - union).max(0.))
+    }
+
+    /// Estimates how many items `self` and `other` have in common with
+    /// Ertl's joint maximum-likelihood estimator, which reads the register
+    /// pairs themselves rather than only the three cardinalities
+    /// inclusion–exclusion works from.
+    ///
+    /// Small overlaps between large or very differently sized sets come out
+    /// far more accurately than from
+    /// [`intersection_count`](HyperLogLog::intersection_count), whose result
+    /// this falls back to if the likelihood's maximum can't be found. The
+    /// estimate is never negative.
+    pub fn intersection_count_mle(&self, other: &HyperLogLog<S>) -> Result<f64, MergeError> {
+        let [_, _, both] = self.joint_estimate(other)?;
+        Ok(both)
+    }
+
+    /// Estimates how many items of `self` are not in `other`, `|A \ B|`,
+    /// from the same joint estimate as
+    /// [`intersection_count_mle`](HyperLogLog::intersection_count_mle).
+    ///
+    /// As with the intersection, the error scales with the size of the
+    /// union, not of the difference, so a difference much smaller than
+    /// [`error_estimate`](HyperLogLog::error_estimate) times the union can
+    /// come out as anything from zero to several times its true size. The
+    /// estimate is never negative, and is exactly 0 when no register of
+    /// `self` is above `other`'s, as for identical sketches or a subset.
+    pub fn difference_count(&self, other: &HyperLogLog<S>) -> Result<f64, MergeError> {
+        let [only_a, _, _] = self.joint_estimate(other)?;
+        Ok(if self.registers_within(other) {
+            0.
+        } else {
+            only_a
+        })
+    }
+
+    /// Estimates how many items are in exactly one of `self` and `other`,
+    /// `|A \ B| + |B \ A|`.
+    ///
+    /// The error is as for
+    /// [`difference_count`](HyperLogLog::difference_count). The estimate is
+    /// never negative, and is exactly 0 for identical sketches.
+    pub fn symmetric_difference_count(&self, other: &HyperLogLog<S>) -> Result<f64, MergeError> {
+        let [only_a, only_b, _] = self.joint_estimate(other)?;
+        let only_a = if self.registers_within(other) {
+            0.
+        } else {
+            only_a
+        };
+        let only_b = if other.registers_within(self) {
+            0.
+        } else {
+            only_b
+        };
+        Ok(only_a + only_b)
+    }
+
+    /// Whether every register of `self` is at most `other`'s, so that
+    /// `self` adds nothing to their union.
+    fn registers_within(&self, other: &HyperLogLog<S>) -> bool {
+        let m = self.num_registers();
+        self.registers
+            .iter(m)
+            .zip(other.registers.iter(m))
+            .all(|(a, b)| a <= b)
+    }
+
+    /// Estimates the Jaccard similarity `|A ∩ B| / |A ∪ B|` of `self` and
+    /// `other`, from the same joint estimate as
+    /// [`intersection_count_mle`](HyperLogLog::intersection_count_mle).
+    ///
+    /// The result is a ratio of estimates, each off by about
+    /// [`error_estimate`](HyperLogLog::error_estimate) of the union. Its
+    /// absolute error is therefore roughly that standard error, so
+    /// similarities much smaller than it are hard to tell from zero. The
+    /// result is always in `[0, 1]`, and is 0 if either sketch is empty.
+    pub fn jaccard(&self, other: &HyperLogLog<S>) -> Result<f64, MergeError> {
+        let [only_a, only_b, both] = self.joint_estimate(other)?;
+        Ok(ratio(both, only_a + only_b + both))
+    }
+
+    /// Estimates the fraction of the items in `self` that are also in
+    /// `other`, `|A ∩ B| / |A|`.
+    ///
+    /// The error is as for [`jaccard`](HyperLogLog::jaccard), relative to
+    /// the union, so it is largest when `self` is much smaller than
+    /// `other`. The result is always in `[0, 1]`, and is 0 if either sketch
+    /// is empty.
+    pub fn containment(&self, other: &HyperLogLog<S>) -> Result<f64, MergeError> {
+        let [only_a, _, both] = self.joint_estimate(other)?;
+        Ok(ratio(both, only_a + both))
+    }
+
+    /// Estimates the overlap coefficient `|A ∩ B| / min(|A|, |B|)`, which
+    /// is 1 when one set contains the other.
+    ///
+    /// The error is as for [`containment`](HyperLogLog::containment) of
+    /// the smaller set in the larger. The result is always in `[0, 1]`,
+    /// and is 0 if either sketch is empty.
+    pub fn overlap_coefficient(&self, other: &HyperLogLog<S>) -> Result<f64, MergeError> {
+        let [only_a, only_b, both] = self.joint_estimate(other)?;
+        Ok(ratio(both, both + only_a.min(only_b)))
+    }
+
+    /// The maximum-likelihood cardinalities of the items only in `self`,
+    /// only in `other`, and in both, falling back to inclusion–exclusion.
+    fn joint_estimate(&self, other: &HyperLogLog<S>) -> Result<[f64; 3], MergeError> {
+        let union = self.union_count(other)?;
+        let (a, b) = (self.count(), other.count());
+        let inclusion_exclusion = (a + b - union).max(0.);
+        let m = self.num_registers();
+        let pairs = joint::PairCounts::new(
+            self.precision,
+            self.registers.iter(m).zip(other.registers.iter(m)),
+        );
+        let start = [
+            (union - b).max(0.),
+            (union - a).max(0.),
+            inclusion_exclusion,
+        ];
+        Ok(match pairs.maximize(start) {
+            Some(rates) => rates,
+            None if a == 0. || b == 0. => [a, b, 0.],
+            None => [
+                (a - inclusion_exclusion).max(0.),
+                (b - inclusion_exclusion).max(0.),
+                inclusion_exclusion,
+            ],
+        })
+    }
+
+    /// Estimates the cardinality with the LogLog-Beta estimator.
+    ///
+    /// Unlike [`count`](HyperLogLog::count), which switches from linear
+    /// counting to the bias-corrected raw estimate at a threshold, LogLog-Beta
+    /// is one smooth formula across the whole range, so the estimate has no
+    /// step as the cardinality grows. The accuracy is about the same. Sketches
+    /// with fewer than 16 or more than 2^18 registers have no beta coefficients
+    /// and fall back to `count`.
+    pub fn count_beta(&self) -> f64 {
+        estimate_beta(&self.rank_counts())
+    }
+
+    /// Estimates the cardinality with Ertl's improved raw estimator.
+    ///
+    /// Like [`count_beta`](HyperLogLog::count_beta), this is one formula over
+    /// the whole range, with no step where [`count`](HyperLogLog::count)
+    /// switches from linear counting. Instead of fitted coefficients it
+    /// corrects the raw estimate in closed form for the empty registers and
+    /// for those at the highest rank, so it works at every precision and
+    /// stays accurate at cardinalities near `2^64`.
+    pub fn count_improved(&self) -> f64 {
+        estimate_improved(&self.rank_counts())
+    }
+
+    /// Estimates the cardinality with `estimator`, whatever the sketch's own
+    /// [estimator](HyperLogLog::set_estimator), for comparing estimators on
+    /// the same registers. Neither cached nor held up by the watermark of a
+    /// [monotonic](HyperLogLog::set_monotonic) sketch.
+    pub fn count_with<E: Estimator + ?Sized>(&self, estimator: &E) -> f64 {
+        estimator.estimate(self.precision, &self.rank_counts())
+    }
+
+    /// Estimates the cardinality as [`count_u64`](HyperLogLog::count_u64)
+    /// does with the default, [bias-corrected](BiasCorrectedEstimator)
+    /// estimator, in integer arithmetic only, for targets without a
+    /// floating-point unit.
+    ///
+    /// The estimate is computed in fixed point with 32 fractional bits, the
+    /// harmonic sum exactly. The rounding this adds is far below one item,
+    /// so the result is `count_u64()` or, where the two round an estimate
+    /// near a half differently, one away from it.
+    pub fn count_fixed(&self) -> u64 {
+        if self.is_empty() {
+            return 0;
+        }
+        cmp::max(fixed_point::estimate(&self.rank_counts()), 1)
+    }
+
+    /// The standard error of the HyperLogLog estimate for this many
+    /// registers, `1.04 / √m`, whatever the sketch holds. See
+    /// [`relative_error`](HyperLogLog::relative_error) for the error of the
+    /// current estimate.
+    pub fn error_estimate(&self) -> f64 {
+        standard_error(self.precision)
+    }
+
+    /// The relative standard error of the current estimate.
+    ///
+    /// While [`count`](HyperLogLog::count) uses linear counting, this is
+    /// linear counting's error, which falls to about `1 / √(2m)` at light
+    /// loads; afterwards it is `1.04 / √m`. An empty sketch is exact and
+    /// returns 0. Past about `2^64 / 30`, where the 64-bit hashes are too
+    /// crowded for the estimate to mean much, it returns infinity.
+    pub fn relative_error(&self) -> f64 {
+        let details = self.estimate_details();
+        let sd = bounds::standard_deviation(&details, self.num_registers());
+        if details.estimate == 0. || sd.is_infinite() {
+            sd
+        } else {
+            sd / details.estimate
+        }
+    }
+}
+
+/// Adds every item, equivalent to calling [`add`](HyperLogLog::add) on each
+/// but faster for long iterators, since a dense sketch skips the per-item
+/// representation checks. Borrowing iterators such as `names.iter()` add the
+/// same registers as consuming ones.
+impl<S: BuildHasher, T: Hash> Extend<T> for HyperLogLog<S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        self.add_all(items);
+    }
+}
+
+/// Sketches are equal when they have the same size, the same register values,
+/// whether each is sparse or dense, and the same monotonic watermark, if any.
+/// The hashers are not compared.
+impl<S> PartialEq for HyperLogLog<S> {
+    fn eq(&self, other: &HyperLogLog<S>) -> bool {
+        if self.precision != other.precision || self.watermark != other.watermark {
+            return false;
+        }
+        let m = self.num_registers();
+        match (&self.registers, &other.registers) {
+            (Registers::Dense(a), Registers::Dense(b)) => a == b,
+            (Registers::Sparse(a), Registers::Sparse(b)) => a == b,
+            (a, b) => a.iter(m).eq(b.iter(m)),
+        }
+    }
+}
+
+impl<S> Eq for HyperLogLog<S> {}
+
+/// Summarizes the sketch instead of listing every register.
+impl<S> fmt::Debug for HyperLogLog<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let counts = self.rank_counts();
+        let nonzero = self.num_registers() - counts[0] as usize;
+        f.debug_struct("HyperLogLog")
+            .field("precision", &self.precision)
+            .field("nonzero_registers", &nonzero)
+            .field("sparse", &self.registers.is_sparse())
+            .field(
+                "estimate",
+                &self.estimator.estimate(self.precision, &counts),
+            )
+            .finish()
+    }
+}
+
+/// The precision of a default sketch: a standard error of about 0.8% for
+/// 16 KiB of registers.
+const DEFAULT_PRECISION: u8 = 14;
+const DEFAULT_REGISTERS: usize = 1 << DEFAULT_PRECISION;
+
+/// An empty sketch with precision 14, 2^14 registers.
+impl<S: BuildHasher + Default> Default for HyperLogLog<S> {
+    fn default() -> HyperLogLog<S> {
+        HyperLogLog::with_hasher(DEFAULT_REGISTERS, S::default())
+    }
+}
+
+/// Collects items into a default sketch with 2^14 registers. To collect into
+/// a sketch of another size, create it first and extend it instead:
+/// `let mut h = HyperLogLog::with_precision(10)?; h.extend(items);`.
+impl<S: BuildHasher + Default, T: Hash> FromIterator<T> for HyperLogLog<S> {
+    fn from_iter<I: IntoIterator<Item = T>>(items: I) -> HyperLogLog<S> {
+        let mut h = HyperLogLog::default();
+        h.extend(items);
+        h
+    }
+}
+
+/// Merges the sketches into the first, so that `partials.into_iter().sum()`
+/// is their union. An empty iterator sums to a default, empty sketch.
+///
+/// Panics if the precisions differ, naming them; use
+/// [`HyperLogLog::merge_many`] to get the error instead.
+impl<S: BuildHasher + Default> Sum for HyperLogLog<S> {
+    fn sum<I: Iterator<Item = HyperLogLog<S>>>(mut sketches: I) -> HyperLogLog<S> {
+        let mut merged = sketches.next().unwrap_or_default();
+        for h in sketches {
+            if let Err(e) = merged.merge(&h) {
+                panic!("{}", e);
+            }
+        }
+        merged
+    }
+}
+
+/// Like the `Sum` of owned sketches, cloning the first.
+impl<'a, S: BuildHasher + Clone + Default + 'a> Sum<&'a HyperLogLog<S>> for HyperLogLog<S> {
+    fn sum<I: Iterator<Item = &'a HyperLogLog<S>>>(mut sketches: I) -> HyperLogLog<S> {
+        let mut merged = sketches.next().cloned().unwrap_or_default();
+        for h in sketches {
+            if let Err(e) = merged.merge(h) {
+                panic!("{}", e);
+            }
+        }
+        merged
+    }
+}
+
+/// The same as [`HyperLogLog::from_registers`], for any default hasher.
+impl<S: BuildHasher + Default> TryFrom<Vec<u8>> for HyperLogLog<S> {
+    type Error = RegisterError;
+
+    fn try_from(registers: Vec<u8>) -> Result<HyperLogLog<S>, RegisterError> {
+        HyperLogLog::from_registers_with_hasher(registers, S::default())
+    }
+}
+
+impl<S> HyperLogLog<S> {
+    /// Like [`HyperLogLog::from_registers`], for a sketch that will hash
+    /// items with `build_hasher`.
+    pub fn from_registers_with_hasher(
+        registers: Vec<u8>,
+        build_hasher: S,
+    ) -> Result<HyperLogLog<S>, RegisterError> {
+        let p = check_register_count(registers.len()).map_err(RegisterError::RegisterCount)?;
+        let maximum = max_rank(p);
+        if let Some(index) = registers.iter().position(|&r| r > maximum) {
+            return Err(RegisterError::InvalidRegister {
+                index,
+                value: registers[index],
+                maximum,
+            });
+        }
+        Ok(HyperLogLog::from_dense(p, registers, build_hasher))
+    }
+
+    /// Consumes the sketch, returning its register values in index order,
+    /// the layout [`HyperLogLog::from_registers`] takes.
+    pub fn into_registers(self) -> Vec<u8> {
+        match self.registers {
+            Registers::Dense(registers) => registers,
+            ref sparse => sparse.to_dense(1 << self.precision),
+        }
+    }
+
+    /// Builds a dense sketch from an already validated register array.
+    fn from_dense(precision: u8, registers: Vec<u8>, build_hasher: S) -> HyperLogLog<S> {
+        HyperLogLog::from_stored(precision, Registers::Dense(registers), build_hasher)
+    }
+
+    /// Builds a sketch from already validated registers, in either
+    /// representation.
+    fn from_stored(precision: u8, registers: Registers, build_hasher: S) -> HyperLogLog<S> {
+        HyperLogLog {
+            histogram: registers.histogram(1 << precision),
+            registers,
+            precision,
+            sparse_threshold: default_sparse_threshold(precision),
+            hasher_assumed: false,
+            build_hasher,
+            cache: EstimateCache::new(),
+            watermark: None,
+            recent: None,
+            estimator: BuiltinEstimator::default(),
+            items_added: 0,
+        }
+    }
+
+    /// Rebuilds the histogram from the registers after a change too broad to
+    /// track register by register.
+    fn recount(&mut self) {
+        self.histogram = self.registers.histogram(self.num_registers());
+        self.registers_changed();
+    }
+
+    /// Forgets the cached estimate after the registers changed, and raises
+    /// the watermark of a monotonic sketch to the new estimate.
+    fn registers_changed(&mut self) {
+        self.cache.invalidate();
+        if let Some(watermark) = self.watermark {
+            self.watermark = Some(watermark.max(self.current_estimate()));
+        }
+    }
+
+    /// The estimate from the registers as they are, ignoring any watermark.
+    fn current_estimate(&self) -> f64 {
+        self.cache
+            .get_or_compute(|| self.estimator.estimate(self.precision, &self.rank_counts()))
+    }
+
+    /// Adds every hash in `hashes`, as [`add_hashed`](HyperLogLog::add_hashed)
+    /// would one at a time.
+    pub fn extend_hashed<I: IntoIterator<Item = u64>>(&mut self, hashes: I) {
+        self.raise_hashes(hashes.into_iter());
+    }
+
+    /// Adds every hash, returning how many of them raised a register.
+    fn raise_hashes<I: Iterator<Item = u64>>(&mut self, hashes: I) -> usize {
+        let p = self.precision;
+        let mut added = 0;
+        let hashes = hashes.inspect(|_| added += 1);
+        let raised = match self.recent.take() {
+            Some(mut recent) => {
+                let fresh = hashes.filter(|&h| !recent.check_and_insert(h));
+                let raised = self.raise_registers(fresh.map(|h| index_and_rank(h, p)));
+                self.recent = Some(recent);
+                raised
+            }
+            None => self.raise_registers(hashes.map(|h| index_and_rank(h, p))),
+        };
+        self.items_added = self.items_added.saturating_add(added);
+        raised
+    }
+
+    /// Raises registers to at least the given ranks, returning how many rose.
+    fn raise_registers<I: Iterator<Item = (usize, u8)>>(&mut self, updates: I) -> usize {
+        let p = self.precision;
+        let raised =
+            self.registers
+                .raise_all(updates, 1 << p, self.sparse_threshold, &mut self.histogram);
+        if raised > 0 {
+            self.registers_changed();
+        }
+        raised
+    }
+
+    /// Raises the registers to at least those of the validated dense array
+    /// `other`, returning whether any rose.
+    fn raise_to(&mut self, other: &[u8]) -> bool {
+        if let Registers::Dense(ref mut registers) = self.registers {
+            let raised = simd::max_into(registers, other);
+            if raised {
+                self.recount();
+            }
+            return raised;
+        }
+        let nonzero = other
+            .iter()
+            .enumerate()
+            .filter(|&(_, &rank)| rank > 0)
+            .map(|(index, &rank)| (index, rank));
+        self.raise_registers(nonzero) > 0
+    }
+
+    /// The precision `p`: the sketch has `2^p` registers.
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The register count, `2^precision()`.
+    pub fn num_registers(&self) -> usize {
+        1 << self.precision
+    }
+
+    /// Iterates over all register values in index order, regardless of
+    /// whether the sketch is sparse.
+    pub fn registers(&self) -> impl ExactSizeIterator<Item = u8> + '_ {
+        self.registers.iter(self.num_registers())
+    }
+
+    /// A 64-bit hash of the precision and the registers, for telling
+    /// cheaply whether two replicas differ without sending either.
+    ///
+    /// It is SipHash-2-4 with both keys zero over the precision byte and the
+    /// register values in index order, so it is the same on every platform,
+    /// for sparse and dense sketches alike, and after any round trip through
+    /// serialization. Neither the hasher nor a monotonic sketch's watermark
+    /// is included. It is not cryptographic: it catches accidental
+    /// differences, but sketches can be crafted to collide.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = SipHasher24::new_with_keys(0, 0);
+        hasher.write(&[self.precision]);
+        match self.registers {
+            Registers::Dense(ref registers) => hasher.write(registers),
+            Registers::Sparse(_) => {
+                let mut chunk = [0; 64];
+                let mut registers = self.registers();
+                loop {
+                    let len = chunk
+                        .iter_mut()
+                        .zip(&mut registers)
+                        .map(|(slot, r)| *slot = r)
+                        .count();
+                    hasher.write(&chunk[..len]);
+                    if len < chunk.len() {
+                        break;
+                    }
+                }
+            }
+        }
+        hasher.finish()
+    }
+
+    /// The number of registers holding each rank, indexed by rank; entry 0
+    /// counts the empty registers. A sketch has at most 2^24 registers, so
+    /// every count fits.
+    pub fn register_histogram(&self) -> [u32; 65] {
+        let mut histogram = [0u32; 65];
+        for (h, &c) in histogram.iter_mut().zip(self.rank_counts().iter()) {
+            *h = cmp::min(c, u64::from(u32::MAX)) as u32;
+        }
+        histogram
+    }
+
+    fn rank_counts(&self) -> [u64; 65] {
+        self.histogram
+    }
+
+    /// The memory the sketch uses, including its register storage on the heap.
+    pub fn memory_bytes(&self) -> usize {
+        let recent = self.recent.as_ref().map_or(0, RecentHashes::heap_bytes);
+        mem::size_of::<Self>() + self.registers.heap_bytes() + recent
+    }
+
+    /// All register values in index order, regardless of representation.
+    #[cfg(feature = "serde")]
+    fn dense_registers(&self) -> Vec<u8> {
+        self.registers.to_dense(self.num_registers())
+    }
 }
 
-impl HyperLogLog {
-    pub fn new(m: usize) -> HyperLogLog {
-        HyperLogLog {
-            registers: vec![0; m],
-            hasher: DefaultHasher::new(),
+#[cfg(test)]
+mod tests {
+    #[cfg(not(target_arch = "wasm32"))]
+    extern crate proptest;
+
+    use super::{
+        alpha, estimate, hll_cardinality, index_and_rank, max_rank, merge_serialized, mix128,
+        mix64, round_count, standard_error, sum_and_zeros, BiasCorrectedEstimator, BuildError,
+        DecodeError, DefaultBuildHasher, ErtlEstimator, EstimationMethod, Estimator, FoldError,
+        HashBackend, HasherId, HyperLogLog, HyperLogLogRef, LogLogBetaEstimator, MergeBytesError,
+        MergeError, RegisterError, SeededState,
+    };
+    use encoding::HEADER_LEN;
+    use simulation::splitmix64;
+    use std::collections::HashSet;
+    use std::convert::TryFrom;
+    use std::env;
+    use std::hash::{BuildHasher, BuildHasherDefault, Hasher};
+    use std::process::Command;
+    use std::sync::atomic::Ordering;
+    use testing::{error_profile, error_profile_with};
+
+    /// A deliberately simple FNV-1a hasher with a murmur3 finalizer, standing
+    /// in for fxhash/ahash.
+    #[derive(Default)]
+    struct Fnv(u64);
+
+    impl Hasher for Fnv {
+        fn finish(&self) -> u64 {
+            let mut h = self.0;
+            h ^= h >> 33;
+            h = h.wrapping_mul(0xff51_afd7_ed55_8ccd);
+            h ^= h >> 33;
+            h = h.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+            h ^ (h >> 33)
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            if self.0 == 0 {
+                self.0 = 0xcbf2_9ce4_8422_2325;
+            }
+            for &b in bytes {
+                self.0 ^= u64::from(b);
+                self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+    }
+
+    type FnvBuildHasher = BuildHasherDefault<Fnv>;
+
+    fn regs<S>(h: &HyperLogLog<S>) -> Vec<u8> {
+        h.registers().collect()
+    }
+
+    fn assert_close(a: f64, b: f64, err: f64) {
+        if (a == 0.) && (b == 0.) {
+            return;
+        }
+        let diff = (a - b).abs();
+        let sum = (a.abs() + b.abs()) / 2.;
+        assert!(diff / sum < err, "a: {}, b: {}", a, b);
+    }
+
+    #[test]
+    fn it_works() {
+        let mut h = HyperLogLog::new(16);
+        assert_eq!(h.count(), 0.);
+        assert!((h.error_estimate() - 0.26).abs() < 1e-12);
+
+        let words = vec![
+            "Hello!",
+            "World!",
+            "Hello!",
+            "Something!",
+            "Else!",
+            "Else!",
+            "Hello!",
+            "1",
+            "2",
+            "3",
+            "4",
+            "3",
+            "2",
+            "1",
+        ];
+
+        for w in &words {
+            h.add(w);
+        }
+
+        // Repeats change nothing: this is the sketch of the eight distinct words.
+        let mut distinct = HyperLogLog::new(16);
+        for w in words.iter().collect::<HashSet<_>>() {
+            distinct.add(w);
+        }
+        assert_eq!(h, distinct);
+        assert!(h.count() > 0.);
+
+        // And sketches this small count that few items to within their error.
+        assert_accurate(4, &[8], 100, 1);
+    }
+
+    #[test]
+    fn large_test() {
+        let mut h = HyperLogLog::new(1 << 8);
+        assert_eq!(h.count(), 0.);
+        assert!(h.error_estimate() < 0.1);
+
+        let n = 10_000;
+        for i in 1..n {
+            h.add(i * 3);
+        }
+        let count = h.count();
+        for _ in 1..10 {
+            for i in 1..n {
+                h.add(i * 3);
+            }
+        }
+        assert_eq!(h.count(), count);
+
+        assert_accurate(8, &[n], 20, 2);
+    }
+
+    #[test]
+    fn rank_uses_bits_below_index() {
+        let mut h = HyperLogLog::new(1 << 4);
+        for i in 0..10_000u64 {
+            h.add(i);
+        }
+        // Ranks count leading zeros of the 60 bits left after the index, plus one.
+        assert!(h.registers().all(|r| (1..=61).contains(&r)));
+    }
+
+    /// Checks the bias, spread and worst case of `trials` sketches against
+    /// the standard error at each cardinality.
+    fn assert_accurate(p: u8, cardinalities: &[u64], trials: usize, seed: u64) {
+        let sigma = standard_error(p);
+        for stats in error_profile(p, cardinalities, trials, seed) {
+            let case = format!("p = {}: {:?}", p, stats);
+            assert!(
+                stats.mean_relative_error.abs() < 3. * sigma / (trials as f64).sqrt(),
+                "biased, {}",
+                case
+            );
+            assert!(stats.std_dev < 1.5 * sigma, "spread, {}", case);
+            assert!(
+                stats.worst_relative_error.abs() < 4. * sigma,
+                "worst, {}",
+                case
+            );
+        }
+    }
+
+    #[test]
+    fn accuracy_across_cardinalities() {
+        assert_accurate(
+            12,
+            &[300, 1_000, 10_000, 100_000, 1_000_000, 3_000_000],
+            2,
+            78,
+        );
+    }
+
+    #[test]
+    fn raw_estimate_is_alpha_m_squared_over_sum() {
+        // Every register at rank k sums to m * 2^-k, so the raw estimate is
+        // alpha * m * 2^k; empty registers count as 2^0.
+        for &p in &[4u8, 10, 14] {
+            let m = 1usize << p;
+            for k in 0..20u8 {
+                let mut counts = [0u64; 65];
+                counts[k as usize] = m as u64;
+                let (_, sum, _) = sum_and_zeros(&counts);
+                let expected = alpha(m) * m as f64 * f64::from(1u32 << k);
+                assert_close(hll_cardinality(m, sum), expected, 1e-12);
+            }
+        }
+        // Registers store the first 1-bit's position: a hash whose bits
+        // after the index start with `1` has rank 1, not 0.
+        assert_eq!(index_and_rank(1 << 53, 10), (0, 1));
+        assert_eq!(index_and_rank(1 << 52, 10), (0, 2));
+    }
+
+    #[test]
+    fn accuracy_across_precisions() {
+        for &p in &[6u8, 8, 10, 12, 14] {
+            assert_accurate(
+                p,
+                &[10, 100, 1_000, 5_000, 20_000, 100_000],
+                10,
+                u64::from(p),
+            );
+        }
+    }
+
+    /// Checks one sketch's estimates as it fills through `checkpoints`: each
+    /// within a few standard errors, about one of them on average, and no
+    /// jump between neighbouring checkpoints across the range where `count`
+    /// switches estimators bigger than the noise of the extra items.
+    fn assert_smooth<E: Estimator>(estimator: &E, p: u8, mut checkpoints: Vec<u64>, seed: u64) {
+        let m = 1u64 << p;
+        let sigma = standard_error(p);
+        checkpoints.sort();
+        checkpoints.dedup();
+
+        let profile = error_profile_with(estimator, p, &checkpoints, 1, seed);
+        let mut last: Option<f64> = None;
+        let mut squared_errors = 0.;
+        for stats in &profile {
+            let (n, error) = (stats.cardinality, stats.mean_relative_error);
+            assert!(error.abs() < 3. * sigma, "p = {}, n = {}: {}", p, n, error);
+            squared_errors += error * error;
+            if let Some(previous) = last.filter(|_| n > m / 2 && n < 3 * m) {
+                let step = error - previous;
+                assert!(step.abs() < sigma / 2., "p = {}, n = {}: {}", p, n, step);
+            }
+            last = Some(error);
+        }
+        let rms = (squared_errors / profile.len() as f64).sqrt();
+        assert!(rms < 1.5 * sigma, "p = {}: rms error {}", p, rms);
+    }
+
+    #[test]
+    fn beta_accuracy_across_cardinalities() {
+        for &p in &[12u8, 14] {
+            let m = 1u64 << p;
+            let checkpoints: Vec<u64> = (1..=6)
+                .flat_map(|e| vec![10u64.pow(e), 3 * 10u64.pow(e)])
+                .chain(Some(10_000_000))
+                // Close steps across the range where `count` switches estimators.
+                .chain((50..300).map(|k| m * k / 100))
+                .collect();
+            assert_smooth(&LogLogBetaEstimator, p, checkpoints.clone(), u64::from(p));
+            assert_smooth(&BiasCorrectedEstimator, p, checkpoints, u64::from(p));
+        }
+    }
+
+    #[test]
+    fn improved_accuracy_is_smooth() {
+        for &p in &[10u8, 12, 14] {
+            let m = 1u64 << p;
+            // Four points per decade from 10 to 10^7, and close steps
+            // across the range where `count` switches estimators.
+            let checkpoints: Vec<u64> = (4..=28)
+                .map(|e| 10f64.powf(f64::from(e) / 4.) as u64)
+                .chain((50..300).map(|k| m * k / 100))
+                .collect();
+            assert_smooth(&ErtlEstimator, p, checkpoints, 78 + u64::from(p));
+        }
+    }
+
+    /// Feeds `n` distinct pseudo-random 64-bit hashes to the sketch.
+    fn add_random_hashes(h: &mut HyperLogLog, n: u64, rng: &mut u64) {
+        for _ in 0..n {
+            h.add_hashed(splitmix64(rng));
+        }
+    }
+
+    #[test]
+    fn borrowed_items_hash_like_owned_ones() {
+        let names: Vec<String> = (0..5_000).map(|i| format!("user-{}", i)).collect();
+        let mut owned = HyperLogLog::new(1 << 10);
+        for name in names.clone() {
+            owned.add(name);
+        }
+        let mut borrowed = HyperLogLog::new(1 << 10);
+        for name in &names {
+            borrowed.add(name);
+            borrowed.add(name.as_str());
+        }
+        assert_eq!(owned, borrowed);
+
+        // Extend and FromIterator take borrowing iterators too.
+        let mut extended = HyperLogLog::new(1 << 10);
+        extended.extend(names.iter());
+        assert_eq!(extended, owned);
+        let collected: HyperLogLog = names.iter().map(String::as_str).collect();
+        assert_eq!(collected, names.into_iter().collect::<HyperLogLog>());
+
+        let mut literal = HyperLogLog::new(1 << 4);
+        literal.add("literal");
+        let mut string = HyperLogLog::new(1 << 4);
+        string.add(String::from("literal"));
+        assert_eq!(literal, string);
+    }
+
+    #[test]
+    fn add_hashed_matches_add() {
+        let build = FnvBuildHasher::default();
+        let mut by_item = HyperLogLog::with_hasher(1 << 10, build.clone());
+        let mut by_hash = HyperLogLog::with_hasher(1 << 10, build.clone());
+        for i in 0..20_000u64 {
+            by_item.add(i);
+            by_hash.add_hashed(build.hash_one(i));
+        }
+        assert_eq!(regs(&by_item), regs(&by_hash));
+    }
+
+    #[test]
+    fn insert_reports_raised_registers() {
+        let mut h = HyperLogLog::new(1 << 10);
+        assert!(h.insert("apple"));
+        assert!(!h.insert("apple"));
+        assert!(!h.insert_hashed(h.build_hasher.hash_one("apple")));
+
+        // Every repeat misses, and every hit was a change to the registers.
+        let mut raised = 0;
+        for i in 0..5_000u64 {
+            let before = regs(&h);
+            let changed = h.insert(i);
+            assert_eq!(changed, regs(&h) != before);
+            raised += changed as usize;
+            assert!(!h.insert(i));
+        }
+        assert!(raised > 0 && raised < 5_000);
+
+        // Both representations agree.
+        let mut dense = HyperLogLog::new(1 << 10);
+        dense.set_sparse_threshold(0);
+        assert!(!dense.is_sparse());
+        assert!(dense.insert("apple"));
+        assert!(!dense.insert("apple"));
+    }
+
+    #[test]
+    fn add_bytes_ignores_the_type() {
+        let build = FnvBuildHasher::default();
+        let mut raw = build.build_hasher();
+        raw.write(b"foo");
+        let expected = index_and_rank(raw.finish(), 10);
+
+        let sketches: Vec<HyperLogLog<FnvBuildHasher>> = (0..3)
+            .map(|i| {
+                let mut h = HyperLogLog::with_hasher(1 << 10, build.clone());
+                match i {
+                    0 => h.add_bytes("foo"),
+                    1 => h.add_bytes(String::from("foo")),
+                    _ => h.add_bytes(b"foo".as_slice()),
+                }
+                h
+            })
+            .collect();
+        for h in &sketches {
+            let touched: Vec<(usize, u8)> = regs(h)
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, r)| r > 0)
+                .collect();
+            assert_eq!(touched, [expected]);
+        }
+    }
+
+    #[test]
+    fn extend_matches_add() {
+        let words: Vec<String> = (0..5_000).map(|i| format!("word{}", i % 3_000)).collect();
+        let mut by_add = HyperLogLog::new(1 << 10);
+        for w in &words {
+            by_add.add(w);
+        }
+
+        let mut from_slice = HyperLogLog::new(1 << 10);
+        from_slice.extend(words.iter());
+        assert_eq!(regs(&from_slice), regs(&by_add));
+
+        let set: HashSet<&String> = words.iter().collect();
+        let mut from_set = HyperLogLog::new(1 << 10);
+        from_set.extend(set);
+        assert_eq!(regs(&from_set), regs(&by_add));
+
+        // Chained calls that start sparse and promote partway through.
+        let mut chained = HyperLogLog::new(1 << 10);
+        chained.extend(&words[..10]);
+        assert!(chained.is_sparse());
+        chained.extend(&words[10..2_000]);
+        chained.extend(&words[2_000..]);
+        assert!(!chained.is_sparse());
+        assert_eq!(regs(&chained), regs(&by_add));
+        assert_eq!(chained.count(), by_add.count());
+    }
+
+    #[test]
+    fn collect_into_default_sketch() {
+        let empty: HyperLogLog = HyperLogLog::default();
+        assert_eq!(empty.count(), 0.0);
+        assert_eq!(empty.num_registers(), 1 << 14);
+
+        let from_range: HyperLogLog = (0..50_000u64).collect();
+        assert_eq!(from_range.num_registers(), 1 << 14);
+        assert_close(
+            from_range.count(),
+            50_000.,
+            from_range.error_estimate() * 3.,
+        );
+
+        let words = ["apple", "banana", "cherry", "apple", "banana"];
+        let from_strs: HyperLogLog = words.iter().collect();
+        let mut by_add: HyperLogLog = HyperLogLog::default();
+        for w in &words {
+            by_add.add(w);
+        }
+        assert_eq!(regs(&from_strs), regs(&by_add));
+        assert_close(from_strs.count(), 3., 0.01);
+
+        let from_strings: HyperLogLog = (0..1_000).map(|i| format!("user-{}", i % 400)).collect();
+        assert_close(
+            from_strings.count(),
+            400.,
+            from_strings.error_estimate() * 3.,
+        );
+    }
+
+    #[test]
+    fn try_new_rejects_unusable_sizes() {
+        let cases = [
+            (0, "register count 0 is below the minimum of 16"),
+            (1, "register count 1 is below the minimum of 16"),
+            (3, "register count 3 is below the minimum of 16"),
+            (8, "register count 8 is below the minimum of 16"),
+            (1_000, "register count 1000 is not a power of two"),
+            (16_385, "register count 16385 is not a power of two"),
+        ];
+        for &(m, message) in &cases {
+            let err = HyperLogLog::try_new(m).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+        #[cfg(target_pointer_width = "64")]
+        assert!(matches!(
+            HyperLogLog::try_new(1 << 33),
+            Err(BuildError::TooManyRegisters { .. })
+        ));
+        assert_eq!(HyperLogLog::try_new(16).unwrap().num_registers(), 16);
+        assert_eq!(
+            HyperLogLog::try_new(1 << 20).unwrap().num_registers(),
+            1 << 20
+        );
+    }
+
+    #[test]
+    fn with_precision_takes_tabulated_precisions() {
+        assert_eq!(
+            HyperLogLog::with_precision(14).unwrap().num_registers(),
+            16384
+        );
+        for p in 4..=18 {
+            let h = HyperLogLog::with_precision(p).unwrap();
+            assert_eq!(h.precision(), p);
+            assert_eq!(h, HyperLogLog::new(1 << p));
+        }
+        for &p in &[0u8, 1, 3, 19, 32, 255] {
+            assert_eq!(
+                HyperLogLog::with_precision(p),
+                Err(BuildError::InvalidPrecision(p))
+            );
+        }
+        let default: HyperLogLog = HyperLogLog::default();
+        assert_eq!(default.precision(), 14);
+    }
+
+    #[test]
+    #[should_panic(expected = "register count 1000 is not a power of two")]
+    fn new_panics_on_unusable_size() {
+        HyperLogLog::new(1_000);
+    }
+
+    #[test]
+    fn with_error_picks_smallest_sufficient_size() {
+        let h = HyperLogLog::with_error(0.01).unwrap();
+        assert_eq!(h.num_registers(), 16_384);
+        assert!(h.error_estimate() <= 0.01);
+        assert!(HyperLogLog::new(8_192).error_estimate() > 0.01);
+
+        // Exactly on a boundary: 1.04 / sqrt(4096) = 0.01625.
+        assert_eq!(
+            HyperLogLog::with_error(0.01625).unwrap().num_registers(),
+            4_096
+        );
+        assert_eq!(HyperLogLog::with_error(1.).unwrap().num_registers(), 16);
+        assert_eq!(HyperLogLog::with_error(0.3).unwrap().num_registers(), 16);
+
+        for &bad in &[0., -0.1, 1.5, f64::NAN] {
+            match HyperLogLog::with_error(bad) {
+                Err(BuildError::InvalidErrorRate(_)) => {}
+                other => panic!("{}: {:?}", bad, other.map(|h| h.num_registers())),
+            }
+        }
+        assert!(matches!(
+            HyperLogLog::with_error(1e-6),
+            Err(BuildError::ErrorRateUnreachable { .. })
+        ));
+    }
+
+    #[test]
+    fn with_memory_budget_fits_registers() {
+        let sizes: Vec<usize> = [16, 17, 31, 32, 1_000, 16_384, 20_000]
+            .iter()
+            .map(|&b| HyperLogLog::with_memory_budget(b).unwrap().num_registers())
+            .collect();
+        assert_eq!(sizes, [16, 16, 16, 32, 512, 16_384, 16_384]);
+        assert_eq!(
+            HyperLogLog::with_memory_budget(15).map(|h| h.num_registers()),
+            Err(BuildError::BudgetTooSmall {
+                bytes: 15,
+                minimum: 16
+            })
+        );
+    }
+
+    #[test]
+    fn count_is_cached_until_a_register_changes() {
+        let misses = |h: &HyperLogLog| h.cache.misses.load(Ordering::Relaxed);
+        let mut h = HyperLogLog::new(1 << 10);
+        h.extend(0..5_000u64);
+        let first = h.count();
+        assert_eq!(misses(&h), 1);
+        assert_eq!(h.count().to_bits(), first.to_bits());
+        assert_eq!(misses(&h), 1);
+
+        // Re-adding items already seen, or merging a subset, changes nothing.
+        h.extend(0..5_000u64);
+        let mut subset = HyperLogLog::new(1 << 10);
+        subset.extend(0..100u64);
+        h.merge(&subset).unwrap();
+        h.count();
+        assert_eq!(misses(&h), 1);
+
+        // Interleaved adds and counts always match a fresh computation.
+        for i in 5_000..6_000u64 {
+            h.add(i);
+            let mut fresh = HyperLogLog::new(1 << 10);
+            fresh.extend(0..=i);
+            assert_eq!(h.count(), fresh.count(), "after {}", i);
+        }
+        let mut more = HyperLogLog::new(1 << 10);
+        more.extend(6_000..20_000u64);
+        h.merge(&more).unwrap();
+        let mut fresh = HyperLogLog::new(1 << 10);
+        fresh.extend(0..20_000u64);
+        assert_eq!(h.count(), fresh.count());
+    }
+
+    #[test]
+    fn monotonic_count_never_decreases() {
+        let mut dropped = false;
+        for &p in &[4u8, 7, 10, 12] {
+            let m = 1usize << p;
+            let mut plain = HyperLogLog::new_seeded(m, 86);
+            let mut monotonic = HyperLogLog::builder()
+                .precision(p)
+                .seed(86)
+                .monotonic(true)
+                .build()
+                .unwrap();
+            let mut highest = 0f64;
+            let mut previous = 0f64;
+            // One item at a time, across the switch from linear counting.
+            for i in 0..6 * m as u64 {
+                plain.add(i);
+                monotonic.add(i);
+                dropped |= plain.count() < highest;
+                highest = highest.max(plain.count());
+                let count = monotonic.count();
+                assert!(
+                    count >= previous,
+                    "p = {}, item {}: {} < {}",
+                    p,
+                    i,
+                    count,
+                    previous
+                );
+                assert_eq!(count, highest, "p = {}, item {}", p, i);
+                previous = count;
+            }
+        }
+        // Without the mode, the count does go backwards.
+        assert!(dropped);
+    }
+
+    #[test]
+    fn monotonic_watermark_merges_and_resets() {
+        // Stop just past a dip, where the watermark is above the estimate.
+        let mut a = HyperLogLog::new_seeded(1 << 7, 86);
+        a.set_monotonic(true);
+        for i in 0..6u64 << 7 {
+            a.add(i);
+            if a.count() > a.current_estimate() {
+                break;
+            }
+        }
+        assert!(a.count() > a.current_estimate());
+        let watermark = a.count();
+        assert_eq!(a.count_u64(), round_count(watermark));
+
+        // Merging keeps the higher watermark, and a plain sketch counts
+        // with its estimate.
+        let mut b = HyperLogLog::new_seeded(1 << 7, 86);
+        b.add(0u64);
+        a.merge(&b).unwrap();
+        assert_eq!(a.count(), watermark);
+        let mut high = b.clone();
+        high.set_monotonic(true);
+        high.watermark = Some(watermark + 100.);
+        assert_eq!(a.union(&high).unwrap().count(), watermark + 100.);
+        b.merge(&a).unwrap();
+        assert!(!b.is_monotonic());
+        assert!(b.count() < watermark);
+
+        // Equality and conversions keep the watermark.
+        assert_ne!(a, a.union(&high).unwrap());
+        assert_eq!(a.freeze().thaw(), a);
+        assert_eq!(a.reduce_precision(7).unwrap(), a);
+
+        a.clear();
+        assert!(a.is_monotonic());
+        assert_eq!(a.count(), 0.);
+        a.add(1u64);
+        assert_eq!(a.count(), a.current_estimate());
+
+        let mut off = high.clone();
+        off.set_monotonic(false);
+        assert_eq!(off.count(), off.current_estimate());
+        off.set_monotonic(true);
+        assert_eq!(off.count(), off.current_estimate());
+    }
+
+    #[test]
+    fn clone_and_compare() {
+        let mut a = HyperLogLog::new(1 << 8);
+        a.extend(0..30u64);
+        let mut b = a.clone();
+        assert_eq!(a, b);
+        b.add(1_000_000u64);
+        assert_ne!(a, b);
+        a.add(1_000_000u64);
+        assert_eq!(a, b);
+
+        // The representation doesn't matter, the size does.
+        let mut dense = a.clone();
+        dense.set_sparse_threshold(0);
+        assert!(a.is_sparse() && !dense.is_sparse());
+        assert_eq!(a, dense);
+        assert_ne!(HyperLogLog::new(1 << 8), HyperLogLog::new(1 << 9));
+    }
+
+    #[test]
+    fn details_switch_from_linear_counting() {
+        let mut h = HyperLogLog::with_hasher(1 << 10, FnvBuildHasher::default());
+        let d = h.estimate_details();
+        assert_eq!(d.method, EstimationMethod::LinearCounting);
+        assert_eq!((d.estimate, d.zero_registers), (0., 1 << 10));
+        assert_eq!(d.harmonic_sum, 1024.);
+
+        let mut last = EstimationMethod::LinearCounting;
+        for n in (0..20_000u64).step_by(250) {
+            h.extend(n..n + 250);
+            let d = h.estimate_details();
+            assert_eq!(d.estimate, h.count());
+            if d.method == EstimationMethod::LinearCounting {
+                assert_eq!(
+                    last,
+                    EstimationMethod::LinearCounting,
+                    "flipped back at {}",
+                    n
+                );
+                assert_eq!(Some(d.estimate), d.linear_count);
+            }
+            last = d.method;
+        }
+        let d = h.estimate_details();
+        assert_eq!(d.method, EstimationMethod::HyperLogLog);
+        assert_eq!(d.linear_count.is_some(), d.zero_registers > 0);
+    }
+
+    /// Runs this test again in a child process with `--nocapture`, and checks
+    /// that nothing is printed between the child's markers.
+    #[test]
+    fn count_writes_nothing_to_stdout() {
+        const CHILD: &str = "HLL_COUNT_STDOUT_CHILD";
+        if env::var_os(CHILD).is_some() {
+            let mut h = HyperLogLog::new(1 << 10);
+            h.extend(0..10_000u64);
+            print!("<<");
+            h.count();
+            h.count_beta();
+            h.estimate_details();
+            print!(">>");
+            return;
+        }
+        let out = Command::new(env::current_exe().unwrap())
+            .args([
+                "tests::count_writes_nothing_to_stdout",
+                "--exact",
+                "--nocapture",
+            ])
+            .env(CHILD, "1")
+            .output()
+            .unwrap();
+        assert!(out.status.success());
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        assert!(stdout.contains("<<>>"), "{}", stdout);
+    }
+
+    #[test]
+    fn debug_is_a_short_summary() {
+        let mut small = HyperLogLog::new(1 << 4);
+        small.add(1u64);
+        let debug = format!("{:?}", small);
+        assert!(debug.contains("precision: 4"), "{}", debug);
+        assert!(debug.contains("nonzero_registers: 1"), "{}", debug);
+
+        let mut large = HyperLogLog::new(1 << 18);
+        large.extend(0..100_000u64);
+        let debug = format!("{:?}", large);
+        assert!(debug.len() < 120, "{}", debug);
+    }
+
+    #[test]
+    fn add_all_matches_add() {
+        let words: Vec<String> = (0..20_000).map(|i| format!("w{}", i % 7_000)).collect();
+        let mut by_add = HyperLogLog::new(1 << 10);
+        for w in &words {
+            by_add.add(w);
+        }
+
+        let mut by_all = HyperLogLog::new(1 << 10);
+        let raised = by_all.add_all(&words);
+        assert_eq!(regs(&by_all), regs(&by_add));
+        let nonzero = by_all.registers().filter(|&r| r != 0).count();
+        assert!(raised >= nonzero, "{} < {}", raised, nonzero);
+        assert_eq!(by_all.add_all(&words), 0);
+
+        let strs: Vec<&str> = words.iter().map(|w| w.as_str()).collect();
+        let mut by_slice = HyperLogLog::new(1 << 10);
+        assert_eq!(by_slice.add_slice(&strs[..]), raised);
+        assert_eq!(regs(&by_slice), regs(&by_add));
+        assert_eq!(by_slice.add_slice(&strs[..5]), 0);
+    }
+
+    #[test]
+    fn extend_hashed_matches_add_hashed() {
+        let hashes: Vec<u64> = (0..3_000u64)
+            .map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+            .collect();
+        let mut one_by_one = HyperLogLog::new(1 << 8);
+        for &h in &hashes {
+            one_by_one.add_hashed(h);
         }
+        let mut batched = HyperLogLog::new(1 << 8);
+        batched.extend_hashed(hashes[..40].iter().cloned());
+        batched.extend_hashed(hashes[40..].iter().cloned());
+        assert_eq!(regs(&batched), regs(&one_by_one));
     }
 
-    pub fn add<H: Hash>(&mut self, item: H) {
-        self.hasher = DefaultHasher::new();
-        item.hash(&mut self.hasher);
-        let h = self.hasher.finish();
-        let m = (h % self.registers.len() as u64) as usize;
-        let v = h.leading_zeros();
-
-        if self.registers[m] < v {
-            self.registers[m] = v
+    #[test]
+    fn accuracy_at_hundreds_of_millions() {
+        let mut h = HyperLogLog::new(1 << 14);
+        let mut rng = 0x64_b175;
+        let mut added = 0;
+        for &n in &[1_000_000u64, 10_000_000, 150_000_000] {
+            add_random_hashes(&mut h, n - added, &mut rng);
+            added = n;
+            assert_close(h.count(), n as f64, h.error_estimate() * 3.);
+            assert_close(h.count_beta(), n as f64, h.error_estimate() * 3.);
+        }
+    }
+
+    #[test]
+    fn no_saturation_far_beyond_32_bits() {
+        // Sample each register directly from its distribution after `n`
+        // items: the register is below k with probability exp(-(n/m) 2^-k).
+        let p = 12;
+        let m = 1usize << p;
+        let mut rng = 0x5a7_u64;
+        for &n in &[1e10, 1e13, 1e16] {
+            let lambda = n / m as f64;
+            let registers = (0..m)
+                .map(|_| {
+                    let u = (splitmix64(&mut rng) >> 11) as f64 / (1u64 << 53) as f64;
+                    let k = (lambda / -u.ln()).log2().ceil();
+                    k.max(0.).min(f64::from(max_rank(p))) as u8
+                })
+                .collect();
+            let h: HyperLogLog = HyperLogLog::from_dense(p, registers, Default::default());
+            assert_close(h.count(), n, h.error_estimate() * 3.);
+        }
+    }
+
+    #[test]
+    fn merge_disjoint_streams() {
+        let mut a = HyperLogLog::new(1 << 10);
+        let mut b = HyperLogLog::new(1 << 10);
+        let mut both = HyperLogLog::new(1 << 10);
+        for i in 0..5_000u64 {
+            a.add(i);
+            both.add(i);
+        }
+        for i in 5_000..12_000u64 {
+            b.add(i);
+            both.add(i);
+        }
+
+        a.merge(&b).unwrap();
+        assert_eq!(regs(&a), regs(&both));
+        assert_eq!(a.count(), both.count());
+        assert_close(a.count(), 12_000., a.error_estimate() * 3.);
+    }
+
+    #[test]
+    fn merge_overlapping_streams() {
+        let mut a = HyperLogLog::new(1 << 10);
+        let mut b = HyperLogLog::new(1 << 10);
+        let mut both = HyperLogLog::new(1 << 10);
+        for i in 0..8_000u64 {
+            a.add(i);
+            both.add(i);
+        }
+        for i in 4_000..12_000u64 {
+            b.add(i);
+            both.add(i);
+        }
+
+        let u = a.union(&b).unwrap();
+        assert_eq!(regs(&u), regs(&both));
+        assert_eq!(u.count(), both.count());
+        assert_close(u.count(), 12_000., u.error_estimate() * 3.);
+    }
+
+    #[test]
+    fn merge_with_empty() {
+        let mut a = HyperLogLog::new(1 << 8);
+        let empty = HyperLogLog::new(1 << 8);
+        for i in 0..1_000u64 {
+            a.add(i);
+        }
+        let before = a.count();
+
+        a.merge(&empty).unwrap();
+        assert_eq!(a.count(), before);
+
+        let u = empty.union(&a).unwrap();
+        assert_eq!(regs(&u), regs(&a));
+    }
+
+    #[test]
+    fn fingerprint_tracks_the_registers() {
+        let mut state = 88;
+        let mut a = HyperLogLog::new(1 << 12);
+        let first = splitmix64(&mut state);
+        a.add_hashed(first);
+        (0..200).for_each(|_| a.add_hashed(splitmix64(&mut state)));
+        assert!(a.is_sparse());
+        let fingerprint = a.fingerprint();
+        assert_eq!(a.clone().fingerprint(), fingerprint);
+        let decoded: HyperLogLog = HyperLogLog::from_bytes(&a.to_bytes()).unwrap();
+        assert_eq!(decoded.fingerprint(), fingerprint);
+        let mut dense = a.clone();
+        dense.set_sparse_threshold(0);
+        assert!(!dense.is_sparse());
+        assert_eq!(dense.fingerprint(), fingerprint);
+
+        // A repeat changes nothing; a new item that raises a register does.
+        let mut b = a.clone();
+        assert!(!b.insert_hashed(first));
+        assert_eq!(b.fingerprint(), fingerprint);
+        while !b.insert_hashed(splitmix64(&mut state)) {}
+        assert_ne!(b.fingerprint(), fingerprint);
+        assert_ne!(
+            HyperLogLog::new(1 << 4).fingerprint(),
+            HyperLogLog::new(1 << 5).fingerprint()
+        );
+        // Pinned, so that a change of hash or layout is caught.
+        assert_eq!(
+            HyperLogLog::new(1 << 4).fingerprint(),
+            6_342_638_390_090_719_300
+        );
+    }
+
+    #[test]
+    fn merge_reports_whether_registers_rose() {
+        let mut state = 88;
+        let mut small = HyperLogLog::new(1 << 10);
+        (0..500).for_each(|_| small.add_hashed(splitmix64(&mut state)));
+        let mut large = small.clone();
+        (0..5_000).for_each(|_| large.add_hashed(splitmix64(&mut state)));
+        assert_eq!(large.dominates(&small), Ok(true));
+        assert_eq!(small.dominates(&large), Ok(false));
+        assert_eq!(small.dominates(&small), Ok(true));
+        assert!(small.dominates(&HyperLogLog::new(1 << 11)).is_err());
+
+        let before = large.clone();
+        assert_eq!(large.merge(&small), Ok(false));
+        assert_eq!(large, before);
+        assert_eq!(large.fingerprint(), before.fingerprint());
+        assert_eq!(small.merge(&large), Ok(true));
+        assert_eq!(small, large);
+        assert_eq!(small.merge(&HyperLogLog::new(1 << 10)), Ok(false));
+    }
+
+    #[test]
+    fn counts_items_added_with_repeats() {
+        let mut state = 98;
+        let mut h = HyperLogLog::new(1 << 12);
+        assert_eq!((h.items_added(), h.duplication_ratio()), (0, 0.));
+        // 2,000 items, added ten times over. `add`, `add_u64` and
+        // `add_hashed` hash an item three different ways, so that is 6,000
+        // distinct hashes.
+        let items: Vec<u64> = (0..2_000).map(|_| splitmix64(&mut state)).collect();
+        for &item in &items {
+            h.add(item);
+            h.add_u64(item);
+            h.add_hashed(item);
+            h.insert_hashed(item);
+        }
+        assert_eq!(h.add_all(&items), 0);
+        h.add_slice(&items);
+        h.extend(&items);
+        h.extend_hashed(items.iter().cloned());
+        h.set_recent_cache(64);
+        // Hits in the cache still count.
+        items.iter().for_each(|&item| {
+            h.add_hashed(item);
+            h.add_hashed(item);
+        });
+        assert_eq!(h.items_added(), 20_000);
+        let ratio = h.duplication_ratio();
+        assert!(
+            (ratio * 0.3 - 1.).abs() < 4. * standard_error(12),
+            "{}",
+            ratio
+        );
+        assert_eq!(ratio, 20_000. / h.count());
+
+        h.clear();
+        assert_eq!((h.items_added(), h.duplication_ratio()), (0, 0.));
+        // Folding keeps the count; it is of items, not registers.
+        h.add_u64(1);
+        assert_eq!(h.reduce_precision(4).unwrap().items_added(), 1);
+    }
+
+    #[test]
+    fn merges_sum_items_added() {
+        let mut state = 98;
+        let empty = HyperLogLog::new_seeded(1 << 8, 98);
+        let (mut a, mut b) = (empty.clone(), empty.clone());
+        (0..300).for_each(|_| a.add_hashed(splitmix64(&mut state) % 100));
+        (0..50).for_each(|_| b.add_hashed(splitmix64(&mut state)));
+
+        let mut merged = a.clone();
+        merged.merge(&b).unwrap();
+        assert_eq!(merged.items_added(), 350);
+        assert_eq!(a.union(&b).unwrap().items_added(), 350);
+        assert_eq!(
+            HyperLogLog::merge_many(&[a.clone(), b.clone(), empty.clone()])
+                .unwrap()
+                .items_added(),
+            350
+        );
+        // Merging is idempotent for the registers, but not for the count.
+        merged.merge(&b).unwrap();
+        assert_eq!(merged.items_added(), 400);
+        assert_eq!(merged, a.union(&b).unwrap());
+
+        let bytes = merge_serialized(Some(&a.to_bytes()), &[&b.to_bytes_compressed()]).unwrap();
+        assert_eq!(
+            HyperLogLog::<SeededState>::from_bytes(&bytes)
+                .unwrap()
+                .items_added(),
+            350
+        );
+        let mut target = a.clone();
+        HyperLogLogRef::<SeededState>::parse(&b.to_bytes())
+            .unwrap()
+            .merge_into(&mut target)
+            .unwrap();
+        b.freeze().merge_into(&mut target).unwrap();
+        assert_eq!(target.items_added(), 400);
+        empty.freeze().merge_into(&mut target).unwrap();
+        assert_eq!(target.items_added(), 400);
+    }
+
+    #[test]
+    fn encodings_carry_items_added() {
+        let mut state = 98;
+        let mut h = HyperLogLog::new_seeded(1 << 6, 98);
+        (0..70).for_each(|_| h.add_hashed(splitmix64(&mut state) % 10));
+        let check = |decoded: HyperLogLog<SeededState>| {
+            assert_eq!(decoded, h);
+            assert_eq!(decoded.items_added(), 70);
+            assert_eq!(decoded.duplication_ratio(), h.duplication_ratio());
         };
+        let (dense, compressed) = (h.to_bytes(), h.to_bytes_compressed());
+        assert_eq!((dense[4], compressed[4]), (4, 5));
+        check(HyperLogLog::from_bytes(&dense).unwrap());
+        check(HyperLogLog::from_bytes(&compressed).unwrap());
+        let borrowed = HyperLogLogRef::<SeededState>::parse(&dense).unwrap();
+        assert_eq!(borrowed.items_added(), 70);
+        check(borrowed.to_owned());
+        assert_eq!(h.freeze().items_added(), 70);
+        check(h.freeze().thaw());
+        #[cfg(feature = "std")]
+        {
+            let mut buf = Vec::new();
+            h.write_to(&mut buf).unwrap();
+            assert_eq!(buf, dense);
+            check(HyperLogLog::read_from(&mut &buf[..]).unwrap());
+        }
+
+        // A sketch nothing was added to is written in the older versions,
+        // without the count, and sketches read from them have added nothing.
+        let mut cleared = h.clone();
+        cleared.clear();
+        let (old, old_compressed) = (cleared.to_bytes(), cleared.to_bytes_compressed());
+        assert_eq!((old[4], old_compressed[4]), (1, 2));
+        assert_eq!(dense.len(), old.len() + 8);
+        let mut registers = old.clone();
+        registers[HEADER_LEN + 16 + 3] = 2;
+        for bytes in &[old, old_compressed, registers] {
+            let back = HyperLogLog::<SeededState>::from_bytes(bytes).unwrap();
+            assert_eq!(back.items_added(), 0);
+            assert_eq!(back.duplication_ratio(), 0.);
+        }
     }
 
-    fn alpha(m: usize) -> f64 {
-        if m == 16 {
-            return 0.673;
-        } else if m == 32 {
-            return 0.697;
-        } else if m == 64 {
-            return 0.709;
+    #[test]
+    fn integer_keys_count_accurately() {
+        // Sequential and strided keys are where a weak mixer would leave
+        // registers unused or ranks correlated.
+        for &p in &[10u8, 14] {
+            for &n in &[1_000u64, 100_000, 1_000_000] {
+                let mut sequential = HyperLogLog::new(1 << p);
+                let mut strided = HyperLogLog::new(1 << p);
+                let mut high = HyperLogLog::new(1 << p);
+                let mut top = HyperLogLog::new(1 << p);
+                for i in 0..n {
+                    sequential.add_u64(i);
+                    strided.add_u64(i << 20);
+                    high.add_u128(u128::from(i) << 64);
+                    top.add_u128(u128::from(i) << 100);
+                }
+                let bound = 4. * standard_error(p);
+                for h in &[sequential, strided, high, top] {
+                    let error = (h.count() - n as f64).abs() / n as f64;
+                    assert!(error < bound, "p = {}, n = {}: {}", p, n, h.count());
+                }
+            }
         }
-        return 0.7213 / (1.0 + 1.079 / (m as f64));
     }
 
-    fn hll_cardinality(&self) -> f64 {
-        let m = self.registers.len();
-        let mf64 = m as f64;
-        let sum: f64 = self
-            .registers
-            .iter()
-            .map(|mj| 2f64.powf(-(*mj as f64)))
-            .sum();
-        let z = 1. / sum;
-        println!("Sum: {}; z: {}", sum, z);
-        return HyperLogLog::alpha(m) * mf64 * mf64 * 2. * z;
+    #[test]
+    fn integer_mixers_avalanche() {
+        // Flipping any input bit flips each output bit about half the time.
+        let mut state = 94;
+        let trials = 1_000;
+        let mut flips64 = vec![[0u32; 64]; 64];
+        let mut flips128 = vec![[0u32; 64]; 128];
+        for _ in 0..trials {
+            let x = splitmix64(&mut state);
+            let y = u128::from(splitmix64(&mut state)) << 64 | u128::from(x);
+            for (bit, row) in flips64.iter_mut().enumerate() {
+                let diff = mix64(x) ^ mix64(x ^ 1 << bit);
+                for (out, count) in row.iter_mut().enumerate() {
+                    *count += (diff >> out & 1) as u32;
+                }
+            }
+            for (bit, row) in flips128.iter_mut().enumerate() {
+                let diff = mix128(y) ^ mix128(y ^ 1 << bit);
+                for (out, count) in row.iter_mut().enumerate() {
+                    *count += (diff >> out & 1) as u32;
+                }
+            }
+        }
+        for row in flips64.iter().chain(&flips128) {
+            for &count in row.iter() {
+                let rate = f64::from(count) / f64::from(trials);
+                assert!((rate - 0.5).abs() < 0.09, "flip rate {}", rate);
+            }
+        }
     }
 
-    fn linear_count(&self, zero_count: usize) -> f64 {
-        let m: f64 = self.registers.len() as f64;
-        return m * (m / (zero_count as f64)).ln();
+    #[test]
+    fn integer_keys_are_their_own_hash_domain() {
+        let mut by_u64 = HyperLogLog::new(1 << 10);
+        let mut by_u128 = HyperLogLog::new(1 << 10);
+        let mut by_add = HyperLogLog::new(1 << 10);
+        for i in 0..1_000u64 {
+            by_u64.add_u64(i);
+            by_u128.add_u128(u128::from(i));
+            by_add.add(i);
+        }
+        assert_eq!(by_u64, by_u128);
+        assert_ne!(by_u64, by_add);
+        // The hasher's seed plays no part.
+        let mut seeded = HyperLogLog::new_seeded(1 << 10, 94);
+        (0..1_000u64).for_each(|i| seeded.add_u64(i));
+        assert!(seeded.registers().eq(by_u64.registers()));
     }
 
-    pub fn count(&self) -> f64 {
-        let m = self.registers.len();
-        let est = self.hll_cardinality();
-        if est > 2.5 * (m as f64) {
-            return est;
+    #[test]
+    fn recent_cache_changes_nothing() {
+        // A skewed stream: most additions are one of a few hot items.
+        let mut state = 95;
+        let items: Vec<u64> = (0..50_000)
+            .map(|_| {
+                let r = splitmix64(&mut state);
+                if r.is_multiple_of(20) {
+                    r
+                } else {
+                    r % 7
+                }
+            })
+            .collect();
+        for &m in &[1 << 8, 1 << 14] {
+            let mut plain = HyperLogLog::new(m);
+            let mut inserted = Vec::new();
+            for &item in &items {
+                inserted.push(plain.insert(item));
+            }
+            for &capacity in &[1, 7, 1_000] {
+                let mut cached = HyperLogLog::builder()
+                    .precision(m.trailing_zeros() as u8)
+                    .recent_cache(capacity)
+                    .build()
+                    .unwrap();
+                assert_eq!(cached.recent_cache_capacity(), capacity.next_power_of_two());
+                assert_eq!(
+                    cached.memory_bytes(),
+                    HyperLogLog::new(m).memory_bytes() + 8 * capacity.next_power_of_two()
+                );
+                for (&item, &expected) in items.iter().zip(&inserted) {
+                    assert_eq!(cached.insert(item), expected);
+                }
+                assert_eq!(cached, plain);
+                assert_eq!(cached.count(), plain.count());
+
+                let hashes: Vec<u64> = items
+                    .iter()
+                    .map(|i| plain.build_hasher.hash_one(i))
+                    .collect();
+                let mut batched = HyperLogLog::new(m);
+                batched.set_recent_cache(capacity);
+                batched.extend(&items[..20_000]);
+                batched.add_slice(&items[20_000..35_000]);
+                batched.extend_hashed(hashes[35_000..].iter().cloned());
+                assert_eq!(batched, plain);
+
+                // Clearing empties the cache, so items come back.
+                cached.clear();
+                cached.add(items[0]);
+                assert_eq!(cached.count_lower_bound(), 1);
+
+                // No encoding carries the cache.
+                let decoded =
+                    HyperLogLog::<super::DefaultBuildHasher>::from_bytes(&batched.to_bytes())
+                        .unwrap();
+                assert_eq!(decoded.recent_cache_capacity(), 0);
+                assert_eq!(decoded.to_bytes(), plain.to_bytes());
+                assert_eq!(
+                    batched.clone().recent_cache_capacity(),
+                    capacity.next_power_of_two()
+                );
+                batched.set_recent_cache(0);
+                assert_eq!(batched.recent_cache_capacity(), 0);
+            }
         }
+    }
+
+    /// Merge is a join for sketches of every size, sparse or dense, with or
+    /// without a cached estimate, and for monotonic sketches.
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn merge_is_a_join() {
+        use self::proptest::collection::vec;
+        use self::proptest::prelude::*;
+        use self::proptest::test_runner::{Config, RngAlgorithm, TestRng, TestRunner};
+
+        let mut seed = [0u8; 32];
+        seed[0] = 93;
+        let config = Config {
+            cases: 200,
+            failure_persistence: None,
+            ..Config::default()
+        };
+        let mut runner =
+            TestRunner::new_with_rng(config, TestRng::from_seed(RngAlgorithm::ChaCha, &seed));
+        // Up to 1500 hashes into 256 registers straddles the sparse
+        // threshold and the switch from linear counting.
+        let sketch = (vec(any::<u64>(), 0..1_500), any::<bool>());
+        let strategy = (sketch.clone(), sketch.clone(), sketch, any::<bool>());
+        runner
+            .run(&strategy, |(a, b, c, monotonic)| {
+                let build = |(hashes, cached): (Vec<u64>, bool)| {
+                    let mut h = HyperLogLog::new(1 << 8);
+                    h.set_monotonic(monotonic);
+                    h.extend_hashed(hashes);
+                    if cached {
+                        h.count();
+                    }
+                    h
+                };
+                let (a, b, c) = (build(a), build(b), build(c));
+                let join = |x: &HyperLogLog, y: &HyperLogLog| {
+                    let mut x = x.clone();
+                    x.merge(y).unwrap();
+                    x
+                };
 
-        // We have an estimate fewer than 5/2 m; may want to try "linear counting"
-        let zero_count = self.registers.iter().filter(|&&n| n == 0).count();
-        if zero_count == 0 {
-            // If there are no zeros, linear_count will be way off
-            return est;
+                let (ab, ba) = (join(&a, &b), join(&b, &a));
+                prop_assert_eq!(&ab, &ba);
+                prop_assert_eq!(ab.count(), ba.count());
+                prop_assert_eq!(ab.is_sparse(), ba.is_sparse());
+                prop_assert_eq!(&join(&a, &a), &a);
+                prop_assert_eq!(join(&a, &a).count(), a.count());
+                prop_assert_eq!(&join(&ab, &b), &ab);
+
+                let (left, right) = (join(&ab, &c), join(&a, &join(&b, &c)));
+                prop_assert!(left.registers().eq(right.registers()));
+                prop_assert_eq!(left.is_sparse(), right.is_sparse());
+                if !monotonic {
+                    prop_assert_eq!(&left, &right);
+                    prop_assert_eq!(left.count(), right.count());
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn union_and_intersection_counts() {
+        let n = 20_000u64;
+        for &overlap in &[0, n / 10, n * 9 / 10] {
+            let mut a = HyperLogLog::with_hasher(1 << 14, FnvBuildHasher::default());
+            let mut b = a.clone();
+            a.extend(0..n);
+            b.extend(n - overlap..2 * n - overlap);
+            let union = (2 * n - overlap) as f64;
+            let sigma = a.error_estimate();
+
+            let mut merged = a.clone();
+            merged.merge(&b).unwrap();
+            assert_eq!(a.union_count(&b).unwrap(), merged.count());
+            assert_close(a.union_count(&b).unwrap(), union, 3. * sigma);
+
+            // The intersection's error is relative to the union, not itself.
+            let shared = a.intersection_count(&b).unwrap();
+            assert!(shared >= 0.);
+            assert!(
+                (shared - overlap as f64).abs() < 3. * sigma * union,
+                "overlap {}: estimated {}",
+                overlap,
+                shared
+            );
+            assert_eq!(shared, b.intersection_count(&a).unwrap());
         }
 
-        return self.linear_count(zero_count);
+        let small = HyperLogLog::new(1 << 4);
+        let large = HyperLogLog::new(1 << 5);
+        assert!(small.union_count(&large).is_err());
+        assert!(small.intersection_count(&large).is_err());
     }
 
-    pub fn error_estimate(&self) -> f64 {
-        let m = self.registers.len() as f64;
-        return 1.04 / m.sqrt();
+    #[test]
+    fn merge_rejects_mismatched_sizes() {
+        let mut a = HyperLogLog::new(1 << 8);
+        let b = HyperLogLog::new(1 << 10);
+        let err = a.merge(&b).unwrap_err();
+        assert_eq!(err, MergeError::PrecisionMismatch { left: 8, right: 10 });
+        assert!(err.to_string().contains("256"));
+        assert!(a.union(&b).is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::HyperLogLog;
+    #[test]
+    fn registers_round_trip() {
+        let mut h = HyperLogLog::new(1 << 10);
+        h.extend(0..5_000u64);
+        let registers = h.clone().into_registers();
+        assert_eq!(registers, regs(&h));
+        assert_eq!(HyperLogLog::from_registers(registers.clone()).unwrap(), h);
+        assert_eq!(HyperLogLog::try_from(registers).unwrap(), h);
 
-    fn assert_close(a: f64, b: f64, err: f64) {
-        if (a == 0.) && (b == 0.) {
-            return;
+        let mut sparse = HyperLogLog::new(1 << 10);
+        sparse.add("only");
+        assert!(sparse.is_sparse());
+        let restored = HyperLogLog::from_registers(sparse.clone().into_registers()).unwrap();
+        assert_eq!(restored, sparse);
+        assert_eq!(restored.count(), sparse.count());
+    }
+
+    #[test]
+    fn from_registers_validates() {
+        assert_eq!(
+            HyperLogLog::from_registers(vec![0; 1_000]).unwrap_err(),
+            RegisterError::RegisterCount(BuildError::NotPowerOfTwo(1_000))
+        );
+        let mut registers = vec![0; 1 << 10];
+        registers[17] = 200;
+        let err = HyperLogLog::from_registers(registers.clone()).unwrap_err();
+        assert_eq!(
+            err,
+            RegisterError::InvalidRegister {
+                index: 17,
+                value: 200,
+                maximum: 55
+            }
+        );
+        assert!(err.to_string().contains("200"));
+        registers[17] = 55;
+        assert!(HyperLogLog::from_registers(registers).is_ok());
+    }
+
+    #[test]
+    fn merge_many_and_sum_match_a_single_sketch() {
+        let mut whole = HyperLogLog::new(1 << 12);
+        let partials: Vec<HyperLogLog> = (0..100u64)
+            .map(|shard| {
+                let mut h = HyperLogLog::new(1 << 12);
+                h.extend(shard * 1_000..(shard + 1) * 1_000);
+                h
+            })
+            .collect();
+        whole.extend(0..100_000u64);
+
+        let merged = HyperLogLog::merge_many(&partials).unwrap();
+        assert_eq!(merged, whole);
+        assert_eq!(partials.iter().sum::<HyperLogLog>(), whole);
+        assert_eq!(partials.into_iter().sum::<HyperLogLog>(), whole);
+        assert!(Vec::<HyperLogLog>::new()
+            .into_iter()
+            .sum::<HyperLogLog>()
+            .is_empty());
+    }
+
+    #[test]
+    fn merge_many_reports_the_mismatched_index() {
+        let mut partials: Vec<HyperLogLog> = (0..5).map(|_| HyperLogLog::new(1 << 10)).collect();
+        partials[3] = HyperLogLog::new(1 << 8);
+        let err = HyperLogLog::merge_many(&partials).unwrap_err();
+        assert_eq!(
+            err,
+            MergeError::PrecisionMismatchAt {
+                index: 3,
+                expected: 10,
+                found: 8
+            }
+        );
+        assert_eq!(
+            HyperLogLog::merge_many(&partials[..0]).unwrap_err(),
+            MergeError::NoSketches
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "different precisions: 10 (1024 registers) vs 8")]
+    fn sum_panics_on_mismatch() {
+        let partials = vec![HyperLogLog::new(1 << 10), HyperLogLog::new(1 << 8)];
+        let _: HyperLogLog = partials.into_iter().sum();
+    }
+
+    #[test]
+    fn custom_hasher_is_reproducible() {
+        let mut a = HyperLogLog::with_hasher(1 << 8, FnvBuildHasher::default());
+        let mut b = HyperLogLog::with_hasher(1 << 8, FnvBuildHasher::default());
+        let mut default = HyperLogLog::new(1 << 8);
+        for i in 0..2_000u64 {
+            a.add(i);
+            b.add(i);
+            default.add(i);
         }
-        let diff = (a - b).abs();
-        let sum = (a.abs() + b.abs()) / 2.;
-        assert!(diff / sum < err, "a: {}, b: {}", a, b);
+
+        assert_eq!(regs(&a), regs(&b));
+        assert_ne!(regs(&a), regs(&default));
+        assert_close(a.count(), 2_000., a.error_estimate() * 3.);
+
+        a.merge(&b).unwrap();
+        assert_eq!(regs(&a), regs(&b));
+    }
+
+    fn seeded_sketch(seed: u64) -> HyperLogLog<super::SeededState> {
+        let mut h = HyperLogLog::new_seeded(1 << 10, seed);
+        for i in 0..5_000u64 {
+            h.add(i);
+        }
+        h.add("a string");
+        h
     }
 
     #[test]
-    fn it_works() {
-        let mut h = HyperLogLog::new(4);
-        assert_eq!(h.count(), 0.);
-        assert_close(h.error_estimate(), 0.52, 1e-7);
+    fn seeded_sketches_are_identical() {
+        let a = seeded_sketch(7);
+        let b = seeded_sketch(7);
+        assert_eq!(regs(&a), regs(&b));
+        assert_ne!(regs(&a), regs(&seeded_sketch(8)));
+        assert_close(a.count(), 5_001., a.error_estimate() * 3.);
 
-        let words = vec![
-            "Hello!",
-            "World!",
-            "Hello!",
-            "Something!",
-            "Else!",
-            "Else!",
-            "Hello!",
-            "1",
-            "2",
-            "3",
-            "4",
-            "3",
-            "2",
-            "1",
-        ];
+        let mut k = HyperLogLog::new_with_keys(1 << 10, 1, 2);
+        k.add(1u64);
+        let mut k2 = HyperLogLog::new_with_keys(1 << 10, 1, 2);
+        k2.add(1u64);
+        assert_eq!(regs(&k), regs(&k2));
+    }
 
-        for w in words {
-            h.add(w);
+    #[test]
+    fn merges_refuse_sketches_hashed_differently() {
+        let (mut a, b) = (seeded_sketch(99), seeded_sketch(100));
+        let (left, right) = (a.hasher_id().unwrap(), b.hasher_id().unwrap());
+        assert_ne!(left, right);
+        assert_eq!(left, seeded_sketch(99).hasher_id().unwrap());
+        let mismatch = MergeError::HasherMismatch { left, right };
+        let before = a.clone();
+        assert_eq!(a.merge(&b), Err(mismatch));
+        assert_eq!(a.union(&b).err(), Some(mismatch));
+        assert_eq!(a.union_count(&b), Err(mismatch));
+        assert_eq!(a.intersection_count(&b), Err(mismatch));
+        assert_eq!(a.dominates(&b), Err(mismatch));
+        assert_eq!(
+            a.merge_compatible(&b.reduce_precision(8).unwrap()),
+            Err(mismatch)
+        );
+        assert_eq!(
+            b.clone().merge_compatible(&a),
+            Err(MergeError::HasherMismatch {
+                left: right,
+                right: left
+            })
+        );
+        assert_eq!(regs(&a), regs(&before));
+        // Precisions are checked first.
+        let small = HyperLogLog::new_seeded(1 << 8, 100);
+        assert_eq!(
+            a.merge(&small),
+            Err(MergeError::PrecisionMismatch { left: 10, right: 8 })
+        );
+        assert!(mismatch
+            .to_string()
+            .starts_with("cannot merge sketches hashed differently: hasher "));
+
+        // Decoding restores the hasher, and the check with it.
+        let bytes = b.to_bytes();
+        let decoded = HyperLogLog::<SeededState>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.hasher_id(), Some(right));
+        assert_eq!(a.merge(&decoded), Err(mismatch));
+        assert_eq!(b.freeze().merge_into(&mut a), Err(mismatch));
+        let borrowed = HyperLogLogRef::<SeededState>::parse(&bytes).unwrap();
+        assert_eq!(borrowed.merge_into(&mut a), Err(mismatch));
+        assert_eq!(a.merge(&seeded_sketch(99)), Ok(false));
+    }
+
+    #[test]
+    fn assuming_the_same_hasher_skips_the_check() {
+        use std::collections::hash_map::RandomState;
+
+        // Sketches fed only precomputed hashes, whose per-instance hashers
+        // never run.
+        let mut state = 99;
+        let mut a = HyperLogLog::with_hasher(1 << 8, RandomState::new());
+        let mut b = HyperLogLog::with_hasher(1 << 8, RandomState::new());
+        (0..500).for_each(|_| a.add_hashed(splitmix64(&mut state)));
+        (0..500).for_each(|_| b.add_hashed(splitmix64(&mut state)));
+        assert!(matches!(
+            a.merge(&b),
+            Err(MergeError::HasherMismatch { .. })
+        ));
+
+        b.assume_same_hasher();
+        assert_eq!(b.hasher_id(), None);
+        let union = a.union_count(&b).unwrap();
+        assert_eq!(b.clone().merge(&a), Ok(true));
+        assert_eq!(a.merge(&b), Ok(true));
+        assert_eq!(a.count(), union);
+        assert_eq!(b.freeze().thaw().hasher_id(), None);
+        b.freeze().merge_into(&mut a).unwrap();
+        b.clear();
+        assert_eq!(b.hasher_id(), None);
+
+        // Sketches decoded from it are checked again.
+        let mut c = HyperLogLog::new_seeded(1 << 8, 98);
+        c.assume_same_hasher();
+        let d = HyperLogLog::<SeededState>::from_bytes(&c.to_bytes()).unwrap();
+        assert!(d.hasher_id().is_some());
+        assert!(HyperLogLog::new_seeded(1 << 8, 99).merge(&d).is_err());
+    }
+
+    #[test]
+    fn default_hasher_is_the_same_in_every_build() {
+        // SipHash-2-4 with both keys zero, with or without `std`, as the
+        // sketches it writes record no keys to tell builds apart by.
+        let h = DefaultBuildHasher::default();
+        assert_eq!(h.hash_one(42u64), 0x0fc2_553f_0761_9dd3);
+        assert_eq!(
+            HasherId::of(&h),
+            HasherId::of(&SeededState::with_keys(0, 0))
+        );
+    }
+
+    #[test]
+    fn custom_hashers_are_recorded_by_id() {
+        let mut fnv = HyperLogLog::with_hasher(1 << 6, FnvBuildHasher::default());
+        fnv.add(1u64);
+        let id = HasherId::of(&FnvBuildHasher::default());
+        assert_eq!(fnv.hasher_id(), Some(id));
+        let bytes = fnv.to_bytes();
+        assert_eq!(HashBackend::of_encoded(&bytes), Ok(HashBackend::Custom));
+        assert_eq!(bytes[7..15], id.fingerprint().to_le_bytes());
+        let back = HyperLogLog::<FnvBuildHasher>::from_bytes(&bytes).unwrap();
+        assert_eq!((regs(&back), back.hasher_id()), (regs(&fnv), Some(id)));
+        let compressed = fnv.to_bytes_compressed();
+        assert!(HyperLogLog::<FnvBuildHasher>::from_bytes(&compressed).is_ok());
+
+        // A custom hasher decodes neither as the default nor as another
+        // custom one, and the default doesn't decode as a custom one.
+        assert_eq!(
+            HyperLogLog::<DefaultBuildHasher>::from_bytes(&bytes).map(drop),
+            Err(DecodeError::HasherMismatch)
+        );
+        type OtherBuildHasher = BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+        assert_eq!(
+            HyperLogLog::<OtherBuildHasher>::from_bytes(&bytes).map(drop),
+            Err(DecodeError::HasherMismatch)
+        );
+        let default = HyperLogLog::new(1 << 6).to_bytes();
+        assert_eq!(HashBackend::of_encoded(&default), Ok(HashBackend::Native));
+        assert_eq!(
+            HyperLogLog::<FnvBuildHasher>::from_bytes(&default).map(drop),
+            Err(DecodeError::HasherMismatch)
+        );
+        assert_eq!(
+            merge_serialized(Some(&default), &[&bytes]),
+            Err(MergeBytesError::HasherMismatch { index: 0 })
+        );
+        assert!(merge_serialized(None, &[&bytes, &compressed]).is_ok());
+    }
+
+    #[test]
+    fn registers_are_one_byte_each() {
+        let mut h = HyperLogLog::new(1 << 14);
+        for i in 0..100_000u64 {
+            h.add(i);
+        }
+        assert!(!h.is_sparse());
+        let overhead = ::std::mem::size_of::<HyperLogLog>();
+        assert_eq!(h.memory_bytes(), overhead + (1 << 14));
+        assert_close(h.count(), 100_000., h.error_estimate() * 3.);
+    }
+
+    #[test]
+    fn mle_intersection_beats_inclusion_exclusion() {
+        let mut state = 59;
+        let (large, small, trials) = (500_000u64, 50_000u64, 8);
+        for &overlap in &[small / 1_000, small / 100, small / 10] {
+            let (mut mle_error, mut ie_error) = (0., 0.);
+            for _ in 0..trials {
+                let mut a = HyperLogLog::new(1 << 12);
+                let mut b = HyperLogLog::new(1 << 12);
+                for i in 0..large + small - overlap {
+                    let h = splitmix64(&mut state);
+                    if i < large {
+                        a.add_hashed(h);
+                    }
+                    if i >= large - overlap {
+                        b.add_hashed(h);
+                    }
+                }
+                let mle = a.intersection_count_mle(&b).unwrap();
+                assert!(mle >= 0.);
+                mle_error += (mle - overlap as f64).abs();
+                ie_error += (a.intersection_count(&b).unwrap() - overlap as f64).abs();
+            }
+            assert!(
+                mle_error < 0.8 * ie_error,
+                "overlap {}: mean error {} vs {} by inclusion-exclusion",
+                overlap,
+                mle_error / trials as f64,
+                ie_error / trials as f64
+            );
+        }
+
+        let empty = HyperLogLog::new(1 << 12);
+        let mut full = HyperLogLog::new(1 << 12);
+        (0..1_000u32).for_each(|i| full.add(i));
+        assert_eq!(full.intersection_count_mle(&empty), Ok(0.));
+        assert!(full
+            .intersection_count_mle(&HyperLogLog::new(1 << 10))
+            .is_err());
+    }
+
+    #[test]
+    fn similarities_of_known_overlaps() {
+        let mut state = 79;
+        let hashes: Vec<u64> = (0..40_000).map(|_| splitmix64(&mut state)).collect();
+        let sketch = |range: std::ops::Range<usize>| {
+            let mut h = HyperLogLog::new(1 << 12);
+            hashes[range].iter().for_each(|&x| h.add_hashed(x));
+            h
+        };
+        let a = sketch(0..20_000);
+        let close = |actual: Result<f64, MergeError>, expected: f64| {
+            let actual = actual.unwrap();
+            assert!((0. ..=1.).contains(&actual), "{} out of range", actual);
+            assert!(
+                (actual - expected).abs() < 0.03,
+                "{} vs {}",
+                actual,
+                expected
+            );
+        };
+
+        close(a.jaccard(&a.clone()), 1.);
+        close(a.containment(&a.clone()), 1.);
+        close(a.overlap_coefficient(&a.clone()), 1.);
+
+        let disjoint = sketch(20_000..40_000);
+        close(a.jaccard(&disjoint), 0.);
+        close(a.containment(&disjoint), 0.);
+        close(a.overlap_coefficient(&disjoint), 0.);
+
+        // Half of each set is in the other.
+        let half = sketch(10_000..30_000);
+        close(a.jaccard(&half), 1. / 3.);
+        close(a.containment(&half), 0.5);
+        close(half.containment(&a), 0.5);
+        close(a.overlap_coefficient(&half), 0.5);
+
+        // A subset: all of it is in the superset, a quarter of the superset
+        // in it.
+        let subset = sketch(0..5_000);
+        close(subset.containment(&a), 1.);
+        close(a.containment(&subset), 0.25);
+        close(a.jaccard(&subset), 0.25);
+        close(a.overlap_coefficient(&subset), 1.);
+
+        let empty = HyperLogLog::new(1 << 12);
+        for (x, y) in [(&a, &empty), (&empty, &a), (&empty, &empty)] {
+            assert_eq!(x.jaccard(y), Ok(0.));
+            assert_eq!(x.containment(y), Ok(0.));
+            assert_eq!(x.overlap_coefficient(y), Ok(0.));
         }
+        assert!(a.jaccard(&HyperLogLog::new(1 << 10)).is_err());
 
-        for v in &h.registers {
-            println!("v: {}", v);
+        // Tiny and lopsided pairs, where the estimates are noisiest.
+        for (x, y) in [
+            (0..1, 0..2),
+            (0..3, 2..40),
+            (0..50, 10..20_000),
+            (0..7, 7..9),
+        ] {
+            let (x, y) = (sketch(x), sketch(y));
+            for value in &[x.jaccard(&y), x.containment(&y), y.overlap_coefficient(&x)] {
+                assert!((0. ..=1.).contains(value.as_ref().unwrap()));
+            }
         }
+    }
+
+    #[test]
+    fn differences_of_known_overlaps() {
+        let mut state = 82;
+        let hashes: Vec<u64> = (0..60_000).map(|_| splitmix64(&mut state)).collect();
+        let sketch = |range: std::ops::Range<usize>| {
+            let mut h = HyperLogLog::new(1 << 12);
+            hashes[range].iter().for_each(|&x| h.add_hashed(x));
+            h
+        };
+        let close = |actual: Result<f64, MergeError>, expected: f64, union: f64| {
+            let actual = actual.unwrap();
+            assert!(actual >= 0.);
+            // The error is relative to the union.
+            let bound = 3. * standard_error(12) * union;
+            assert!(
+                (actual - expected).abs() < bound,
+                "{} vs {}",
+                actual,
+                expected
+            );
+        };
+
+        let a = sketch(0..20_000);
+        assert_eq!(a.difference_count(&a.clone()), Ok(0.));
+        assert_eq!(a.symmetric_difference_count(&a.clone()), Ok(0.));
+
+        // A subset has nothing the superset lacks.
+        let superset = sketch(0..40_000);
+        assert_eq!(a.difference_count(&superset), Ok(0.));
+        close(superset.difference_count(&a), 20_000., 40_000.);
+        close(a.symmetric_difference_count(&superset), 20_000., 40_000.);
 
-        assert_close(h.count(), 4., h.error_estimate() * 3.);
+        let disjoint = sketch(20_000..50_000);
+        close(a.difference_count(&disjoint), 20_000., 50_000.);
+        close(disjoint.difference_count(&a), 30_000., 50_000.);
+        close(a.symmetric_difference_count(&disjoint), 50_000., 50_000.);
+
+        // Partial overlaps, large and small.
+        let half = sketch(10_000..30_000);
+        close(a.difference_count(&half), 10_000., 30_000.);
+        close(half.difference_count(&a), 10_000., 30_000.);
+        close(a.symmetric_difference_count(&half), 20_000., 30_000.);
+        let most = sketch(1_000..60_000);
+        close(a.difference_count(&most), 1_000., 60_000.);
+        close(most.difference_count(&a), 40_000., 60_000.);
+
+        let empty = HyperLogLog::new(1 << 12);
+        assert_eq!(empty.difference_count(&a), Ok(0.));
+        close(a.difference_count(&empty), 20_000., 20_000.);
+        assert!(a.difference_count(&HyperLogLog::new(1 << 10)).is_err());
+        assert!(a
+            .symmetric_difference_count(&HyperLogLog::new(1 << 10))
+            .is_err());
     }
 
     #[test]
-    fn large_test() {
-        let mut h = HyperLogLog::new(1 << 8);
+    fn merge_compatible_folds_the_finer_sketch() {
+        let mut state = 57;
+        let hashes: Vec<u64> = (0..40_000).map(|_| splitmix64(&mut state)).collect();
+        let (old, new) = hashes.split_at(15_000);
+        let mut coarse = HyperLogLog::new(1 << 12);
+        let mut fine = HyperLogLog::new(1 << 14);
+        let mut native = HyperLogLog::new(1 << 12);
+        old.iter().for_each(|&h| coarse.add_hashed(h));
+        new.iter().for_each(|&h| fine.add_hashed(h));
+        hashes.iter().for_each(|&h| native.add_hashed(h));
+
+        let mut into_coarse = coarse.clone();
+        assert_eq!(into_coarse.merge_compatible(&fine), Ok(12));
+        let mut into_fine = fine.clone();
+        assert_eq!(into_fine.merge_compatible(&coarse), Ok(12));
+        for merged in &[into_coarse, into_fine] {
+            assert_eq!(merged.precision(), 12);
+            assert_eq!(
+                merged.registers().collect::<Vec<_>>(),
+                native.registers().collect::<Vec<_>>()
+            );
+            assert_eq!(merged.count(), native.count());
+        }
+
+        let mut same = coarse.clone();
+        assert_eq!(same.merge_compatible(&coarse), Ok(12));
+        let tiny: HyperLogLog = HyperLogLog::from_dense(2, vec![0; 4], Default::default());
+        assert_eq!(
+            coarse.merge_compatible(&tiny),
+            Err(MergeError::Fold(FoldError::PrecisionTooLow {
+                requested: 2,
+                minimum: 4
+            }))
+        );
+        assert_eq!(coarse.precision(), 12);
+    }
+
+    #[test]
+    fn reduce_precision_matches_native_sketch() {
+        let mut high = HyperLogLog::with_hasher(1 << 14, FnvBuildHasher::default());
+        let mut low = HyperLogLog::with_hasher(1 << 12, FnvBuildHasher::default());
+        for i in 0..200_000u64 {
+            high.add(i);
+            low.add(i);
+        }
+        let folded = high.reduce_precision(12).unwrap();
+        assert_eq!(folded.precision(), 12);
+        assert_eq!(regs(&folded), regs(&low));
+        assert_eq!(folded.count(), low.count());
+        assert_close(folded.count(), 200_000., folded.error_estimate() * 3.);
+
+        // Folding a sparse sketch, and folding in several steps, agree too.
+        let mut small = HyperLogLog::new(1 << 14);
+        let mut native = HyperLogLog::new(1 << 4);
+        for i in 0..50u64 {
+            small.add(i);
+            native.add(i);
+        }
+        let stepwise = small.reduce_precision(10).unwrap().reduce_precision(4);
+        assert_eq!(regs(&stepwise.unwrap()), regs(&native));
+        assert_eq!(regs(&small.reduce_precision(14).unwrap()), regs(&small));
+    }
+
+    #[test]
+    fn reduce_precision_rejects_bad_targets() {
+        let h = HyperLogLog::new(1 << 12);
+        assert_eq!(
+            h.reduce_precision(13).unwrap_err(),
+            FoldError::PrecisionTooHigh {
+                current: 12,
+                requested: 13
+            }
+        );
+        assert_eq!(
+            h.reduce_precision(3).unwrap_err(),
+            FoldError::PrecisionTooLow {
+                requested: 3,
+                minimum: 4
+            }
+        );
+    }
+
+    #[test]
+    fn clear_resets_for_reuse() {
+        let mut h = HyperLogLog::new_seeded(1 << 12, 7);
+        assert!(h.is_empty());
+        h.extend(0..50_000u64);
+        assert!(!h.is_empty() && !h.is_sparse());
+        assert!(h.count() > 40_000.);
+        let bytes = h.memory_bytes();
+
+        h.clear();
+        assert!(h.is_empty());
         assert_eq!(h.count(), 0.);
-        assert!(h.error_estimate() < 0.1);
+        assert_eq!(h.memory_bytes(), bytes);
 
-        let n = 10_000;
+        h.extend(100_000..110_000u64);
+        let mut fresh = HyperLogLog::new_seeded(1 << 12, 7);
+        fresh.extend(100_000..110_000u64);
+        assert_eq!(regs(&h), regs(&fresh));
+        assert_close(h.count(), 10_000., h.error_estimate() * 3.);
 
-        for i in 1..n {
-            h.add(i * 3);
+        let mut sparse = HyperLogLog::new(1 << 12);
+        sparse.add(1u64);
+        sparse.clear();
+        assert!(sparse.is_empty() && sparse.is_sparse());
+    }
+
+    #[test]
+    fn round_count_boundaries() {
+        let below_half = 0.5f64 - f64::EPSILON / 4.;
+        assert_eq!(round_count(below_half), 0);
+        assert_eq!(round_count(0.5), 1);
+        assert_eq!(round_count(2.5), 3);
+        assert_eq!(round_count(2.5 - 2. * f64::EPSILON), 2);
+        assert_eq!(round_count(1e6 + 0.5), 1_000_001);
+        assert_eq!(round_count(1e6 + 0.4999), 1_000_000);
+        let big = (1u64 << 60) as f64;
+        assert_eq!(round_count(big), 1 << 60);
+
+        assert_eq!(round_count(-0.5), 0);
+        assert_eq!(round_count(-1e9), 0);
+        assert_eq!(round_count(f64::NAN), 0);
+        assert_eq!(round_count(1e30), u64::MAX);
+        assert_eq!(round_count(f64::INFINITY), u64::MAX);
+    }
+
+    #[test]
+    fn count_u64_empty_and_single_item() {
+        for p in 4..=18 {
+            let mut h = HyperLogLog::new(1 << p);
+            assert_eq!(h.count_u64(), 0, "p = {}", p);
+            h.add(p);
+            assert_eq!(h.count_u64(), 1, "p = {}", p);
+            h.clear();
+            assert_eq!(h.count_u64(), 0, "p = {}", p);
+
+            // The largest possible rank still counts as one item.
+            h.add_hashed(0);
+            assert!(h.count_u64() >= 1, "p = {}", p);
         }
 
-        assert_close(h.count(), n as f64, h.error_estimate() * 3.);
+        let mut h = HyperLogLog::new_seeded(1 << 12, 3);
+        h.extend(0..10_000u64);
+        assert_eq!(h.count_u64(), round_count(h.count()));
+    }
 
-        for _ in 1..10 {
-            for i in 1..n {
-                h.add(i * 3);
+    #[test]
+    fn introspection_accessors() {
+        let mut h = HyperLogLog::new(1 << 8);
+        assert_eq!((h.precision(), h.num_registers()), (8, 256));
+        assert_eq!(h.register_histogram()[0], 256);
+
+        h.extend(0..1_000u64);
+        let histogram = h.register_histogram();
+        assert_eq!(histogram.iter().sum::<u32>(), 256);
+        for (rank, &count) in histogram.iter().enumerate() {
+            let expected = h.registers().filter(|&r| usize::from(r) == rank).count();
+            assert_eq!(count as usize, expected, "rank {}", rank);
+        }
+        assert_eq!(h.registers().len(), 256);
+
+        let sparse = HyperLogLog::new(1 << 8);
+        assert!(sparse.memory_bytes() < h.memory_bytes());
+    }
+
+    #[test]
+    fn sparse_matches_dense() {
+        let mut sparse = HyperLogLog::new(1 << 14);
+        let mut dense = HyperLogLog::new(1 << 14);
+        dense.set_sparse_threshold(0);
+        assert!(sparse.is_sparse());
+        assert!(!dense.is_sparse());
+
+        for &n in &[1u64, 10, 100, 1_000, 4_000] {
+            for i in 0..n {
+                sparse.add(i);
+                dense.add(i);
+            }
+            assert!(sparse.is_sparse());
+            assert_eq!(sparse.count(), dense.count());
+            assert_eq!(regs(&sparse), regs(&dense));
+        }
+    }
+
+    #[test]
+    fn kept_histogram_matches_a_recount() {
+        fn check<S: BuildHasher>(h: &HyperLogLog<S>, step: usize) {
+            let fresh = h.registers.histogram(h.num_registers());
+            assert_eq!(h.histogram[..], fresh[..], "after step {}", step);
+            assert_eq!(h.count().to_bits(), estimate(&fresh).to_bits());
+        }
+
+        let mut rng = 0x4157;
+        let mut h = HyperLogLog::new_seeded(1 << 8, 1);
+        for step in 0..2_000 {
+            let n = (splitmix64(&mut rng) % 50) as usize;
+            match splitmix64(&mut rng) % 10 {
+                0..=3 => {
+                    for _ in 0..n {
+                        h.add(splitmix64(&mut rng) % 5_000);
+                    }
+                }
+                4 => {
+                    h.insert_hashed(splitmix64(&mut rng));
+                }
+                5 => {
+                    let items: Vec<u64> = (0..n).map(|_| splitmix64(&mut rng)).collect();
+                    h.add_slice(&items);
+                    h.extend_hashed(items);
+                }
+                6 => {
+                    let mut other = HyperLogLog::new_seeded(1 << 8, 1);
+                    other.extend((0..n * 10).map(|_| splitmix64(&mut rng)));
+                    h.merge(&other).unwrap();
+                }
+                7 => h.set_sparse_threshold(n),
+                8 => h = HyperLogLog::from_bytes(&h.to_bytes()).unwrap(),
+                _ if n < 3 => h.clear(),
+                _ => check(&h.reduce_precision(6).unwrap(), step),
             }
+            check(&h, step);
+        }
+        check(
+            &HyperLogLog::from_registers(h.into_registers()).unwrap(),
+            2_000,
+        );
+    }
+
+    #[test]
+    fn small_sparse_sketch_is_small() {
+        let mut h = HyperLogLog::new(1 << 14);
+        for i in 0..100u64 {
+            h.add(i);
+        }
+        assert!(h.is_sparse(), "100 items should stay sparse");
+        let bytes = h.memory_bytes() - ::std::mem::size_of::<HyperLogLog>();
+        assert!(bytes < 1024, "{} bytes", bytes);
+        assert_close(h.count(), 100., h.error_estimate() * 3.);
+    }
+
+    #[test]
+    fn sparse_promotes_automatically() {
+        let mut h = HyperLogLog::new(1 << 8);
+        for i in 0..200u64 {
+            h.add(i);
+        }
+        assert!(!h.is_sparse());
+
+        let mut h = HyperLogLog::new(1 << 8);
+        h.set_sparse_threshold(1_000);
+        for i in 0..200u64 {
+            h.add(i);
+        }
+        assert!(h.is_sparse());
+        h.set_sparse_threshold(10);
+        assert!(!h.is_sparse());
+    }
+
+    #[test]
+    fn merge_sparse_with_dense() {
+        let mut sparse = HyperLogLog::new(1 << 12);
+        let mut dense = HyperLogLog::new(1 << 12);
+        let mut both = HyperLogLog::new(1 << 12);
+        both.set_sparse_threshold(0);
+        dense.set_sparse_threshold(0);
+        for i in 0..50u64 {
+            sparse.add(i);
+            both.add(i);
         }
+        for i in 25..20_000u64 {
+            dense.add(i);
+            both.add(i);
+        }
+
+        let mut s = sparse.union(&dense).unwrap();
+        let d = dense.union(&sparse).unwrap();
+        assert_eq!(regs(&s), regs(&both));
+        assert_eq!(regs(&d), regs(&both));
+        assert_eq!(s.count(), both.count());
 
-        assert_close(h.count(), n as f64, h.error_estimate() * 3.);
+        // Two sparse sketches stay sparse while the union is small.
+        let mut other = HyperLogLog::new(1 << 12);
+        other.add("x");
+        s = sparse.union(&other).unwrap();
+        assert!(s.is_sparse());
+        assert_close(s.count(), 51., s.error_estimate() * 3.);
     }
 }
@@ -0,0 +1,200 @@
+//! A linear counter: one bit per bucket, estimating from the number of
+//! buckets still empty (Whang et al., 1990).
+//!
+//! With `m` bits and `n` distinct items the estimate `m ln(m / zeros)` has a
+//! standard error of `√(m (e^t - t - 1)) / n` for the load `t = n / m`, which
+//! beats a HyperLogLog of the same memory for loads up to a few items per
+//! bit. Past that the empty buckets run out and the error grows quickly.
+
+use alloc::vec::Vec;
+use core::cmp;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use error::MergeError;
+use {math, DefaultBuildHasher};
+
+/// A bitmap of hashed buckets that counts distinct items exactly until
+/// buckets start to collide and estimates the count after that.
+#[derive(Clone)]
+pub struct LinearCounter<S = DefaultBuildHasher> {
+    bits: Vec<u64>,
+    m: usize,
+    /// The number of bits set, kept up to date as items are added.
+    ones: usize,
+    build_hasher: S,
+}
+
+impl LinearCounter {
+    /// Creates a counter with `m` bits.
+    ///
+    /// Panics if `m` is 0.
+    pub fn new(m: usize) -> LinearCounter {
+        LinearCounter::with_hasher(m, DefaultBuildHasher::default())
+    }
+}
+
+impl<S: BuildHasher> LinearCounter<S> {
+    /// Creates a counter with `m` bits that hashes items with `build_hasher`.
+    ///
+    /// Panics if `m` is 0.
+    pub fn with_hasher(m: usize, build_hasher: S) -> LinearCounter<S> {
+        assert!(m > 0, "a linear counter needs at least one bit");
+        LinearCounter {
+            bits: vec![0; m.div_ceil(64)],
+            m,
+            ones: 0,
+            build_hasher,
+        }
+    }
+
+    pub fn add<H: Hash>(&mut self, item: H) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h);
+    }
+}
+
+impl<S> LinearCounter<S> {
+    /// Adds an item by its precomputed 64-bit hash. The top bits pick the
+    /// bucket, so they must be well distributed.
+    pub fn add_hashed(&mut self, hash: u64) {
+        // Maps the hash onto 0..m without the bias of a modulus.
+        let bucket = ((u128::from(hash) * self.m as u128) >> 64) as usize;
+        let (word, bit) = (bucket / 64, 1u64 << (bucket % 64));
+        if self.bits[word] & bit == 0 {
+            self.bits[word] |= bit;
+            self.ones += 1;
+        }
+    }
+
+    /// Estimates the number of distinct items added.
+    ///
+    /// Once every bit is set the counter is saturated, and this returns
+    /// `m ln m`, the estimate with one bit clear, as a lower bound.
+    pub fn count(&self) -> f64 {
+        let m = self.m as f64;
+        let zeros = cmp::max(self.m - self.ones, 1);
+        m * math::ln(m / zeros as f64)
+    }
+
+    /// Whether every bit is set, so that [`count`](LinearCounter::count) is
+    /// only a lower bound.
+    pub fn is_saturated(&self) -> bool {
+        self.ones == self.m
+    }
+
+    /// The number of bits.
+    pub fn num_bits(&self) -> usize {
+        self.m
+    }
+
+    /// Folds `other` into `self`, so that `self` counts the items added to
+    /// either. Fails if the counters have different numbers of bits.
+    pub fn merge(&mut self, other: &LinearCounter<S>) -> Result<(), MergeError> {
+        if self.m != other.m {
+            return Err(MergeError::SizeMismatch {
+                left: self.m,
+                right: other.m,
+            });
+        }
+        for (a, &b) in self.bits.iter_mut().zip(&other.bits) {
+            *a |= b;
+        }
+        self.ones = self.bits.iter().map(|w| w.count_ones() as usize).sum();
+        Ok(())
+    }
+
+    /// The memory the counter uses, including its bitmap on the heap.
+    pub fn memory_bytes(&self) -> usize {
+        mem::size_of::<Self>() + self.bits.capacity() * mem::size_of::<u64>()
+    }
+}
+
+/// Adds every item, as [`add`](LinearCounter::add) would one at a time.
+impl<S: BuildHasher, T: Hash> Extend<T> for LinearCounter<S> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        for item in items {
+            self.add(item);
+        }
+    }
+}
+
+impl<S> fmt::Debug for LinearCounter<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LinearCounter")
+            .field("bits", &self.m)
+            .field("set", &self.ones)
+            .field("estimate", &self.count())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::size_of;
+
+    use super::LinearCounter;
+    use MergeError;
+
+    #[test]
+    fn exact_while_sparse() {
+        let mut c = LinearCounter::new(1 << 16);
+        assert_eq!(c.count(), 0.);
+        c.add("one");
+        c.add("one");
+        assert!((c.count() - 1.).abs() < 1e-4);
+        assert_eq!(c.memory_bytes(), size_of::<LinearCounter>() + (1 << 16) / 8);
+    }
+
+    #[test]
+    fn accurate_up_to_fill_limit() {
+        let m = 10_000;
+        // Up to a load of 5 items per bit, when under 1% of the bits are
+        // still clear.
+        for &n in &[100u64, 1_000, 10_000, 30_000, 50_000] {
+            let t = n as f64 / m as f64;
+            let sd = (m as f64 * (t.exp() - t - 1.)).sqrt();
+            for run in 0..5 {
+                let mut c = LinearCounter::new(m);
+                c.extend(run << 32..(run << 32) + n);
+                assert!(
+                    (c.count() - n as f64).abs() < 4. * sd + 1.,
+                    "n = {}: counted {} (sd {})",
+                    n,
+                    c.count(),
+                    sd
+                );
+                assert!(!c.is_saturated());
+            }
+        }
+
+        let mut c = LinearCounter::new(64);
+        c.extend(0..10_000);
+        assert!(c.is_saturated());
+        assert_eq!(c.count(), 64. * 64f64.ln());
+    }
+
+    #[test]
+    fn merge_matches_union() {
+        let (mut a, mut b, mut both) = (
+            LinearCounter::new(5_000),
+            LinearCounter::new(5_000),
+            LinearCounter::new(5_000),
+        );
+        a.extend(0..3_000);
+        b.extend(2_000..6_000);
+        both.extend(0..6_000);
+        a.merge(&b).unwrap();
+        assert_eq!(a.bits, both.bits);
+        assert_eq!(a.count(), both.count());
+
+        assert_eq!(
+            a.merge(&LinearCounter::new(4_000)).unwrap_err(),
+            MergeError::SizeMismatch {
+                left: 5_000,
+                right: 4_000
+            }
+        );
+    }
+}
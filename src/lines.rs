@@ -0,0 +1,173 @@
+//! Counting the distinct lines of a reader.
+
+use std::io::{self, BufRead, ErrorKind};
+use std::vec::Vec;
+
+use core::hash::BuildHasher;
+
+use HyperLogLog;
+
+impl<S: BuildHasher> HyperLogLog<S> {
+    /// Adds each line of `reader` as [`add_bytes`](HyperLogLog::add_bytes)
+    /// would, returning how many lines were read.
+    ///
+    /// Lines end at `\n` or `\r\n`, and the terminator isn't part of the
+    /// item, so the same text with Unix or Windows line endings gives the
+    /// same sketch. A `\r` anywhere else, including at the end of a last
+    /// line with no `\n`, is kept. Empty lines are items too, but there is
+    /// no empty line after a final `\n`. The `hll` command-line tool reads
+    /// its input with this.
+    ///
+    /// Lines are hashed straight from the reader's buffer when they fit in
+    /// it; only a line spanning refills is copied, into one reused buffer.
+    /// The bytes needn't be UTF-8. On an I/O error the lines read so far
+    /// have been added.
+    pub fn add_lines<R: BufRead>(&mut self, mut reader: R) -> io::Result<u64> {
+        let mut lines = 0;
+        // The start of a line that ran past the end of the buffer.
+        let mut partial = Vec::new();
+        loop {
+            let used = {
+                let buf = match reader.fill_buf() {
+                    Ok(buf) => buf,
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                };
+                if buf.is_empty() {
+                    if !partial.is_empty() {
+                        self.add_bytes(&partial);
+                        lines += 1;
+                    }
+                    return Ok(lines);
+                }
+                match buf.iter().position(|&b| b == b'\n') {
+                    Some(end) if partial.is_empty() => {
+                        self.add_line(&buf[..end]);
+                        lines += 1;
+                        end + 1
+                    }
+                    Some(end) => {
+                        partial.extend_from_slice(&buf[..end]);
+                        self.add_line(&partial);
+                        partial.clear();
+                        lines += 1;
+                        end + 1
+                    }
+                    None => {
+                        partial.extend_from_slice(buf);
+                        buf.len()
+                    }
+                }
+            };
+            reader.consume(used);
+        }
+    }
+
+    /// Adds a line that ended in `\n`, less the `\r` of a `\r\n`.
+    fn add_line(&mut self, line: &[u8]) {
+        self.add_bytes(line.strip_suffix(b"\r").unwrap_or(line));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufReader, Read};
+
+    use simulation::splitmix64;
+    use HyperLogLog;
+
+    /// About 4 MB of lines of 0 to 200 bytes, some repeated, ending without
+    /// a newline.
+    fn synthetic(line_ending: &[u8]) -> (Vec<u8>, Vec<Vec<u8>>) {
+        let mut state = 64;
+        let (mut text, mut lines) = (Vec::new(), Vec::new());
+        while text.len() < 4 << 20 {
+            let r = splitmix64(&mut state);
+            let len = (r % 201) as usize;
+            let line: Vec<u8> = (0..len)
+                .map(|i| b"abcdefgh\t "[(r >> (i % 32)) as usize % 10])
+                .collect();
+            if !lines.is_empty() {
+                text.extend_from_slice(line_ending);
+            }
+            text.extend_from_slice(&line);
+            lines.push(line);
+            if r.is_multiple_of(5) {
+                let twice = lines[(r >> 40) as usize % lines.len()].clone();
+                text.extend_from_slice(line_ending);
+                text.extend_from_slice(&twice);
+                lines.push(twice);
+            }
+        }
+        (text, lines)
+    }
+
+    #[test]
+    fn matches_add_bytes_per_line() {
+        for &ending in &[&b"\n"[..], b"\r\n"] {
+            let (text, lines) = synthetic(ending);
+            let mut expected = HyperLogLog::new(1 << 12);
+            lines.iter().for_each(|l| expected.add_bytes(l));
+            // Buffers smaller and larger than a line, to split lines across
+            // refills.
+            for &capacity in &[7, 200, 64 << 10] {
+                let mut h = HyperLogLog::new(1 << 12);
+                let reader = BufReader::with_capacity(capacity, &text[..]);
+                assert_eq!(h.add_lines(reader).unwrap(), lines.len() as u64);
+                assert_eq!(h, expected, "capacity {}", capacity);
+            }
+        }
+    }
+
+    #[test]
+    fn line_endings() {
+        let count = |text: &[u8]| {
+            let mut h = HyperLogLog::new(1 << 8);
+            let lines = h.add_lines(text).unwrap();
+            (lines, h)
+        };
+        let mut abc = HyperLogLog::new(1 << 8);
+        ["a", "b", "", "c"].iter().for_each(|l| abc.add_bytes(l));
+        assert_eq!(count(b"a\nb\n\nc"), (4, abc.clone()));
+        assert_eq!(count(b"a\r\nb\r\n\r\nc\n"), (4, abc.clone()));
+        assert_eq!(count(b""), (0, HyperLogLog::new(1 << 8)));
+
+        // Only a \r right before a \n is dropped.
+        let mut kept = HyperLogLog::new(1 << 8);
+        ["a\rb", "c\r"].iter().for_each(|l| kept.add_bytes(l));
+        assert_eq!(count(b"a\rb\nc\r"), (2, kept));
+    }
+
+    /// Fails after `ok` bytes, which it reads one at a time.
+    struct Failing {
+        ok: usize,
+    }
+
+    impl Read for Failing {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.ok == 0 {
+                return Err(io::Error::other("disk on fire"));
+            }
+            self.ok -= 1;
+            buf[0] = if self.ok.is_multiple_of(2) {
+                b'\n'
+            } else {
+                b'x'
+            };
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn io_errors_are_returned() {
+        let mut h = HyperLogLog::new(1 << 8);
+        let mut reader = BufReader::new(Failing { ok: 5 });
+        let error = h.add_lines(&mut reader).unwrap_err();
+        assert_eq!(error.to_string(), "disk on fire");
+        // Read so far: "\n", "x\n", "x\n".
+        let mut read = HyperLogLog::new(1 << 8);
+        read.add_bytes(b"");
+        read.add_bytes(b"x");
+        assert_eq!(h, read);
+    }
+}
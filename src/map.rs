@@ -0,0 +1,230 @@
+//! A keyed collection of sketches sharing one configuration.
+
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+use std::borrow::Borrow;
+use std::collections::hash_map::{Entry, HashMap};
+
+use error::MergeError;
+use {check_register_count, DefaultBuildHasher, HyperLogLog};
+
+/// One [`HyperLogLog`] per key, all with the same register count and hasher,
+/// so that any two of them, or two maps, can be merged.
+///
+/// A key's sketch is created on its first [`add`](HyperLogLogMap::add), and
+/// starts sparse like any new sketch, so a map of many mostly small sketches
+/// stays small.
+#[derive(Clone, Debug)]
+pub struct HyperLogLogMap<K, S = DefaultBuildHasher> {
+    sketches: HashMap<K, HyperLogLog<S>>,
+    m: usize,
+    build_hasher: S,
+}
+
+impl<K: Hash + Eq> HyperLogLogMap<K> {
+    /// Creates an empty map whose sketches have `m` registers.
+    ///
+    /// Panics unless `m` is a power of two from 16 to 2^24, as for
+    /// [`HyperLogLog::new`].
+    pub fn new(m: usize) -> HyperLogLogMap<K> {
+        HyperLogLogMap::with_hasher(m, DefaultBuildHasher::default())
+    }
+}
+
+impl<K: Hash + Eq, S: BuildHasher + Clone> HyperLogLogMap<K, S> {
+    /// Creates an empty map whose sketches have `m` registers and hash items
+    /// with clones of `build_hasher`.
+    pub fn with_hasher(m: usize, build_hasher: S) -> HyperLogLogMap<K, S> {
+        if let Err(e) = check_register_count(m) {
+            panic!("{}", e);
+        }
+        HyperLogLogMap {
+            sketches: HashMap::new(),
+            m,
+            build_hasher,
+        }
+    }
+
+    fn sketch_mut(&mut self, key: K) -> &mut HyperLogLog<S> {
+        let (m, build_hasher) = (self.m, &self.build_hasher);
+        self.sketches
+            .entry(key)
+            .or_insert_with(|| HyperLogLog::with_hasher(m, build_hasher.clone()))
+    }
+
+    /// Adds `item` to `key`'s sketch, creating the sketch if needed.
+    pub fn add<H: Hash>(&mut self, key: K, item: H) {
+        self.sketch_mut(key).add(item);
+    }
+
+    /// Adds an item by its precomputed 64-bit hash to `key`'s sketch; see
+    /// [`HyperLogLog::add_hashed`].
+    pub fn add_hashed(&mut self, key: K, hash: u64) {
+        self.sketch_mut(key).add_hashed(hash);
+    }
+
+    /// Estimates the number of distinct items added under `key`, 0 for a key
+    /// never added to.
+    pub fn count<Q>(&self, key: &Q) -> f64
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.sketches.get(key).map_or(0., HyperLogLog::count)
+    }
+
+    /// The sketch for `key`, if anything has been added under it.
+    pub fn get<Q>(&self, key: &Q) -> Option<&HyperLogLog<S>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.sketches.get(key)
+    }
+
+    /// Merges `other` into `self` key by key: keys in both maps have their
+    /// sketches merged, and keys only in `other` get a copy of its sketch.
+    /// Fails, changing nothing, if the maps' sketches have different
+    /// register counts.
+    pub fn merge_from(&mut self, other: &HyperLogLogMap<K, S>) -> Result<(), MergeError>
+    where
+        K: Clone,
+    {
+        if self.m != other.m {
+            return Err(MergeError::PrecisionMismatch {
+                left: self.m.trailing_zeros() as u8,
+                right: other.m.trailing_zeros() as u8,
+            });
+        }
+        for (key, sketch) in &other.sketches {
+            match self.sketches.entry(key.clone()) {
+                Entry::Occupied(mut entry) => {
+                    entry.get_mut().merge(sketch)?;
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(sketch.clone());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<K, S> HyperLogLogMap<K, S> {
+    /// Each key with the estimate of its sketch, in arbitrary order.
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a K, f64)> + 'a
+    where
+        S: BuildHasher,
+    {
+        self.sketches.iter().map(|(key, h)| (key, h.count()))
+    }
+
+    /// Each key with its sketch, in arbitrary order.
+    pub fn sketches<'a>(&'a self) -> impl Iterator<Item = (&'a K, &'a HyperLogLog<S>)> + 'a {
+        self.sketches.iter()
+    }
+
+    /// The number of keys.
+    pub fn len(&self) -> usize {
+        self.sketches.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sketches.is_empty()
+    }
+
+    /// The register count every sketch in the map has.
+    pub fn num_registers(&self) -> usize {
+        self.m
+    }
+
+    /// The memory the map uses: its table of keys and sketches, allocated
+    /// slots included, and every sketch's register storage. Memory that keys
+    /// own on the heap, and the table's per-slot control bytes, aren't
+    /// counted.
+    pub fn total_memory_bytes(&self) -> usize {
+        let sketch_size = mem::size_of::<HyperLogLog<S>>();
+        let registers: usize = self
+            .sketches
+            .values()
+            .map(|h| h.memory_bytes() - sketch_size)
+            .sum();
+        mem::size_of::<Self>()
+            + self.sketches.capacity() * (mem::size_of::<K>() + sketch_size)
+            + registers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HyperLogLogMap;
+    use {HyperLogLog, MergeError};
+
+    /// A map of `keys`, each holding the items `offset..offset + n`.
+    fn filled(keys: &[&'static str], offset: u64, n: u64) -> HyperLogLogMap<&'static str> {
+        let mut map = HyperLogLogMap::new(1 << 10);
+        for &key in keys {
+            for i in offset..offset + n {
+                map.add(key, i);
+            }
+        }
+        map
+    }
+
+    /// A plain sketch of the same size holding each range in `items`.
+    fn plain(items: &[(u64, u64)]) -> HyperLogLog {
+        let mut h = HyperLogLog::new(1 << 10);
+        for &(start, end) in items {
+            h.extend(start..end);
+        }
+        h
+    }
+
+    #[test]
+    fn add_creates_sparse_sketches_lazily() {
+        let mut map = HyperLogLogMap::new(1 << 14);
+        assert!(map.is_empty());
+        assert_eq!(map.count(&0), 0.);
+        let empty = map.total_memory_bytes();
+        for customer in 0..1_000u32 {
+            map.add(customer, "first visit");
+            map.add(customer, "second visit");
+        }
+        assert_eq!(map.len(), 1_000);
+        assert!(map.get(&7).unwrap().is_sparse());
+        assert!((map.count(&7) - 2.).abs() < 0.01);
+        // Far less than 1,000 dense sketches of 16 KiB.
+        assert!(map.total_memory_bytes() - empty < 1_000 * 2_048);
+        let estimates: Vec<f64> = map.iter().map(|(_, c)| c).collect();
+        assert_eq!(estimates.len(), 1_000);
+    }
+
+    #[test]
+    fn merge_disjoint_keys() {
+        let mut a = filled(&["x", "y"], 0, 2_000);
+        let b = filled(&["z"], 5_000, 3_000);
+        a.merge_from(&b).unwrap();
+        assert_eq!(a.len(), 3);
+        assert_eq!(a.count("x"), plain(&[(0, 2_000)]).count());
+        assert_eq!(a.count("z"), plain(&[(5_000, 8_000)]).count());
+    }
+
+    #[test]
+    fn merge_overlapping_keys() {
+        let mut a = filled(&["x", "y"], 0, 2_000);
+        let b = filled(&["y", "z"], 1_000, 2_000);
+        a.merge_from(&b).unwrap();
+        assert_eq!(a.len(), 3);
+        assert_eq!(a.count("x"), plain(&[(0, 2_000)]).count());
+        // 0..2,000 and 1,000..3,000 under "y".
+        assert_eq!(a.count("y"), plain(&[(0, 3_000)]).count());
+        assert_eq!(a.count("z"), plain(&[(1_000, 3_000)]).count());
+
+        let mut small: HyperLogLogMap<&str> = HyperLogLogMap::new(1 << 8);
+        assert_eq!(
+            small.merge_from(&a).unwrap_err(),
+            MergeError::PrecisionMismatch { left: 8, right: 10 }
+        );
+        assert!(small.is_empty());
+    }
+}
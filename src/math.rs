@@ -0,0 +1,52 @@
+//! The floating-point functions the estimators need: inherent `f64` methods
+//! with `std`, and `libm` without it.
+
+#[cfg(feature = "std")]
+pub(crate) fn ln(x: f64) -> f64 {
+    x.ln()
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn sqrt(x: f64) -> f64 {
+    x.sqrt()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn ln(x: f64) -> f64 {
+    ::libm::log(x)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn sqrt(x: f64) -> f64 {
+    ::libm::sqrt(x)
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn exp_m1(x: f64) -> f64 {
+    x.exp_m1()
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn ln_1p(x: f64) -> f64 {
+    x.ln_1p()
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn exp(x: f64) -> f64 {
+    x.exp()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn exp_m1(x: f64) -> f64 {
+    ::libm::expm1(x)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn ln_1p(x: f64) -> f64 {
+    ::libm::log1p(x)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn exp(x: f64) -> f64 {
+    ::libm::exp(x)
+}
@@ -0,0 +1,484 @@
+//! Merging sketches in their serialized form, as the merge operator of a
+//! key-value store.
+//!
+//! With sketches stored as values, writers can add to one concurrently by
+//! each merging in a sketch of its own items, with no read-modify-write
+//! race: the store combines the values with [`merge_serialized`]. The
+//! `rocksdb` and `sled` features add adapters for those stores' merge
+//! operator signatures.
+
+use alloc::vec::Vec;
+
+use encoding::{
+    compress, decompress_registers, dense_body, is_counted, is_dense, read_raw_header,
+    split_watermark, validate_registers_up_to, RawHeader, ITEMS_LEN, SKETCH_VERSIONS,
+    VERSION_COMPRESSED, VERSION_COMPRESSED_COUNTED, WATERMARK_LEN,
+};
+use error::{DecodeError, MergeBytesError};
+use sparse::{default_sparse_threshold, Registers};
+use {simd, Estimator};
+
+/// Merges the sketches in `operands` into the `existing` one, if there is
+/// one, taking the register-wise maximum as
+/// [`HyperLogLog::merge`](::HyperLogLog::merge) does, and encodes the result
+/// as [`HyperLogLog::to_bytes_compressed`](::HyperLogLog::to_bytes_compressed)
+/// does.
+///
+/// Each sketch may be in either of the
+/// [`HyperLogLog::from_bytes`](::HyperLogLog::from_bytes) formats, and is
+/// decoded straight into the result without being built as a sketch, as are
+/// [`HyperLogLog32`](::HyperLogLog32) sketches. They must all have the
+/// precision, hasher and hash width of the first. Nothing about the
+/// hasher type needs to be known: the hasher recorded in the first sketch is
+/// carried through to the result, as is its
+/// [estimator](::HyperLogLog::set_estimator).
+///
+/// If the first sketch is [monotonic](::HyperLogLog::set_monotonic), so is
+/// the result, keeping the highest of its watermark, the counts of the
+/// others and the merged estimate, as `merge` would, and the sum of the
+/// [numbers of items added](::HyperLogLog::items_added).
+///
+/// The merge is associative, so it can also combine operands without the
+/// existing value, as RocksDB's partial merges do. Never panics: a sketch
+/// that doesn't decode or doesn't match fails the whole merge with an error
+/// saying which it was.
+pub fn merge_serialized(
+    existing: Option<&[u8]>,
+    operands: &[&[u8]],
+) -> Result<Vec<u8>, MergeBytesError> {
+    merge_frames(existing, operands.iter().cloned())
+}
+
+fn merge_frames<'a, I: IntoIterator<Item = &'a [u8]>>(
+    existing: Option<&[u8]>,
+    operands: I,
+) -> Result<Vec<u8>, MergeBytesError> {
+    let mut merged = match existing {
+        Some(bytes) => Some(Merged::start(bytes).map_err(MergeBytesError::Existing)?),
+        None => None,
+    };
+    for (index, bytes) in operands.into_iter().enumerate() {
+        match merged {
+            Some(ref mut merged) => merged.add(index, bytes)?,
+            None => {
+                let first = Merged::start(bytes)
+                    .map_err(|error| MergeBytesError::Operand { index, error })?;
+                merged = Some(first);
+            }
+        }
+    }
+    merged
+        .map(Merged::finish)
+        .ok_or(MergeBytesError::NoSketches)
+}
+
+/// The merge so far.
+struct Merged {
+    header: RawHeader,
+    /// The first sketch's header, without the number of items added, as
+    /// the start of the result.
+    out: Vec<u8>,
+    /// The registers, sparse until as many are set as in a sketch.
+    registers: Registers,
+    /// The watermark, if the first sketch is monotonic.
+    watermark: Option<f64>,
+    /// The number of items added to all the sketches so far.
+    items_added: u64,
+}
+
+impl Merged {
+    fn start(bytes: &[u8]) -> Result<Merged, DecodeError> {
+        let header = read_raw_header(bytes, &SKETCH_VERSIONS)?;
+        let p = header.precision;
+        let (bytes, watermark) = body_and_watermark(&header, bytes)?;
+        let registers = if is_dense(header.version) {
+            let body = dense_body(p, bytes, header.body_at, 0)?;
+            validate_registers_up_to(p, body, header.max_rank())?;
+            Registers::Dense(body.to_vec())
+        } else {
+            let threshold = default_sparse_threshold(p);
+            decompress_registers(p, bytes, header.body_at, header.max_rank(), threshold)?
+        };
+        let header_end = if is_counted(header.version) {
+            header.body_at - ITEMS_LEN
+        } else {
+            header.body_at
+        };
+        let mut out = bytes[..header_end].to_vec();
+        out[4] = VERSION_COMPRESSED;
+        Ok(Merged {
+            items_added: header.items_added,
+            header,
+            out,
+            registers,
+            watermark,
+        })
+    }
+
+    fn add(&mut self, index: usize, bytes: &[u8]) -> Result<(), MergeBytesError> {
+        let operand = |error| MergeBytesError::Operand { index, error };
+        let header = read_raw_header(bytes, &SKETCH_VERSIONS).map_err(operand)?;
+        if header.precision != self.header.precision {
+            return Err(MergeBytesError::PrecisionMismatch {
+                index,
+                expected: self.header.precision,
+                found: header.precision,
+            });
+        }
+        if header.backend != self.header.backend
+            || header.keys != self.header.keys
+            || header.hash32 != self.header.hash32
+        {
+            return Err(MergeBytesError::HasherMismatch { index });
+        }
+        let (bytes, watermark) = body_and_watermark(&header, bytes).map_err(operand)?;
+        let p = header.precision;
+        self.items_added = self.items_added.saturating_add(header.items_added);
+        let histogram = if is_dense(header.version) {
+            let body = dense_body(p, bytes, header.body_at, 0).map_err(operand)?;
+            validate_registers_up_to(p, body, header.max_rank()).map_err(operand)?;
+            self.registers.promote(1 << p);
+            if let Registers::Dense(ref mut registers) = self.registers {
+                simd::max_into(registers, body);
+            }
+            simd::histogram(body)
+        } else {
+            raise_compressed(&header, bytes, &mut self.registers).map_err(operand)?
+        };
+        if let Some(ref mut highest) = self.watermark {
+            let count = header.estimator.estimate(p, &histogram);
+            *highest = highest.max(count).max(watermark.unwrap_or(0.));
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.items_added != 0 {
+            self.out[4] = VERSION_COMPRESSED_COUNTED;
+            self.out.extend_from_slice(&self.items_added.to_le_bytes());
+        }
+        let m = 1 << self.header.precision;
+        let mut out = compress(self.registers.iter(m), self.out);
+        if let Some(watermark) = self.watermark {
+            let histogram = self.registers.histogram(m);
+            let merged = self
+                .header
+                .estimator
+                .estimate(self.header.precision, &histogram);
+            out.extend_from_slice(&watermark.max(merged).to_le_bytes());
+        }
+        out
+    }
+}
+
+/// The bytes of a sketch without its watermark, and the watermark. The
+/// lengths in errors count the watermark.
+fn body_and_watermark<'a>(
+    header: &RawHeader,
+    bytes: &'a [u8],
+) -> Result<(&'a [u8], Option<f64>), DecodeError> {
+    if is_dense(header.version) && header.watermark {
+        // Check the length first, for errors giving the whole frame's.
+        dense_body(header.precision, bytes, header.body_at, WATERMARK_LEN)?;
+    }
+    split_watermark(bytes, header.body_at, header.watermark)
+}
+
+/// Raises `registers` to the compressed registers in `bytes`, checking them
+/// as `from_bytes` does, and returns their histogram. On an error, none has
+/// been raised.
+fn raise_compressed(
+    header: &RawHeader,
+    bytes: &[u8],
+    registers: &mut Registers,
+) -> Result<[u64; 65], DecodeError> {
+    let p = header.precision;
+    let (m, threshold) = (1usize << p, default_sparse_threshold(p));
+    let other = decompress_registers(p, bytes, header.body_at, header.max_rank(), threshold)?;
+    registers.merge(&other, m);
+    registers.promote_past(m, threshold);
+    Ok(other.histogram(m))
+}
+
+/// [`merge_serialized`] as a RocksDB merge operator, for
+/// `Options::set_merge_operator_associative`.
+///
+/// Per RocksDB's contract, a sketch that can't be merged fails the merge,
+/// and reading the key then returns a corruption error.
+#[cfg(feature = "rocksdb")]
+pub fn rocksdb_merge(
+    _key: &[u8],
+    existing: Option<&[u8]>,
+    operands: &::rocksdb::MergeOperands,
+) -> Option<Vec<u8>> {
+    merge_frames(existing, operands).ok()
+}
+
+/// [`merge_serialized`] as a sled merge operator, for
+/// `Tree::set_merge_operator`, calling `on_error` with the key and the error
+/// for each sketch it skips.
+///
+/// A sled merge operator returning `None` deletes the key, so rather than
+/// fail, the operator drops whichever value can't be merged: a bad operand
+/// leaves the existing value as it was, and a bad existing value is
+/// replaced by the operand.
+#[cfg(feature = "sled")]
+pub fn sled_merge_operator<F>(on_error: F) -> impl ::sled::MergeOperator + Send + Sync + 'static
+where
+    F: Fn(&[u8], &MergeBytesError) + Send + Sync + 'static,
+{
+    move |key: &[u8], existing: Option<&[u8]>, operand: &[u8]| {
+        let error = match merge_serialized(existing, &[operand]) {
+            Ok(merged) => return Some(merged),
+            Err(error) => error,
+        };
+        on_error(key, &error);
+        if let MergeBytesError::Existing(_) = error {
+            match merge_serialized(None, &[operand]) {
+                Ok(merged) => return Some(merged),
+                Err(error) => on_error(key, &error),
+            }
+        }
+        existing.map(<[u8]>::to_vec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::merge_serialized;
+    use error::{DecodeError, MergeBytesError};
+    use simulation::splitmix64;
+    use {HyperLogLog, SeededState};
+
+    /// Sketches from four writers, each of its own and some shared items,
+    /// in the order they reach the store, half dense and half compressed.
+    fn writes(state: &mut u64) -> (Vec<Vec<u8>>, HyperLogLog<SeededState>) {
+        let mut all = HyperLogLog::new_seeded(1 << 10, 67);
+        let mut writes = Vec::new();
+        for _ in 0..60 {
+            let writer = splitmix64(state) % 4;
+            let mut batch = HyperLogLog::new_seeded(1 << 10, 67);
+            let n = splitmix64(state) % 500;
+            for _ in 0..n {
+                let item = splitmix64(state) % 20_000 + writer * 10_000;
+                batch.add(item);
+            }
+            all.merge(&batch).unwrap();
+            writes.push(if splitmix64(state).is_multiple_of(2) {
+                batch.to_bytes()
+            } else {
+                batch.to_bytes_compressed()
+            });
+        }
+        (writes, all)
+    }
+
+    #[test]
+    fn interleaved_merges_match_direct_merge() {
+        let mut state = 67;
+        for _ in 0..10 {
+            let (writes, all) = writes(&mut state);
+            // Merge the writes in random runs, some partially merged among
+            // themselves first, the way RocksDB compacts operands.
+            let mut value: Option<Vec<u8>> = None;
+            let mut rest = &writes[..];
+            while !rest.is_empty() {
+                let run = 1 + splitmix64(&mut state) as usize % 8;
+                let (now, later) = rest.split_at(run.min(rest.len()));
+                rest = later;
+                let operands: Vec<&[u8]> = now.iter().map(|w| &w[..]).collect();
+                let merged = if splitmix64(&mut state).is_multiple_of(2) {
+                    let partial = merge_serialized(None, &operands).unwrap();
+                    merge_serialized(value.as_ref().map(|v| &v[..]), &[&partial]).unwrap()
+                } else {
+                    merge_serialized(value.as_ref().map(|v| &v[..]), &operands).unwrap()
+                };
+                value = Some(merged);
+            }
+            let value = value.unwrap();
+            assert_eq!(value, all.to_bytes_compressed());
+            assert_eq!(HyperLogLog::from_bytes(&value), Ok(all));
+        }
+    }
+
+    #[test]
+    fn keeps_the_first_sketchs_watermark() {
+        let mut first = HyperLogLog::new_seeded(1 << 6, 86);
+        first.extend(0..40u32);
+        first.set_monotonic(true);
+        first.watermark = Some(first.count() + 3.);
+        let mut high = HyperLogLog::new_seeded(1 << 6, 86);
+        high.extend(30..60u32);
+        high.set_monotonic(true);
+        high.watermark = Some(1_000.);
+        let mut plain = HyperLogLog::new_seeded(1 << 6, 86);
+        plain.extend(50..90u32);
+
+        let mut expected = first.clone();
+        expected.merge(&high).unwrap();
+        expected.merge(&plain).unwrap();
+        for first in &[first.to_bytes(), first.to_bytes_compressed()] {
+            let operands = [
+                [high.to_bytes(), plain.to_bytes_compressed()],
+                [high.to_bytes_compressed(), plain.to_bytes()],
+            ];
+            for operands in &operands {
+                let merged = merge_serialized(Some(first), &[&operands[0], &operands[1]]).unwrap();
+                assert_eq!(merged, expected.to_bytes_compressed());
+                assert_eq!(HyperLogLog::from_bytes(&merged), Ok(expected.clone()));
+            }
+        }
+
+        // A plain first sketch stays plain.
+        let merged = merge_serialized(Some(&plain.to_bytes()), &[&high.to_bytes()]).unwrap();
+        let mut expected = plain.clone();
+        expected.merge(&high).unwrap();
+        assert_eq!(HyperLogLog::from_bytes(&merged), Ok(expected));
+    }
+
+    #[test]
+    fn errors_name_the_bad_sketch() {
+        let mut h = HyperLogLog::new_seeded(1 << 6, 67);
+        (0..100u32).for_each(|i| h.add(i));
+        let (dense, compressed) = (h.to_bytes(), h.to_bytes_compressed());
+        let other_seed = HyperLogLog::new_seeded(1 << 6, 68).to_bytes();
+        let other_precision = HyperLogLog::new_seeded(1 << 7, 67).to_bytes();
+
+        // The numbers of items added are summed, even for the same sketch.
+        let mut twice = h.clone();
+        twice.merge(&h).unwrap();
+        assert_eq!(
+            merge_serialized(None, &[&dense, &compressed]),
+            Ok(twice.to_bytes_compressed())
+        );
+        assert_eq!(
+            merge_serialized(None, &[]),
+            Err(MergeBytesError::NoSketches)
+        );
+        assert_eq!(
+            merge_serialized(Some(&dense[..5]), &[&dense]),
+            Err(MergeBytesError::Existing(DecodeError::Truncated {
+                expected: 7,
+                actual: 5
+            }))
+        );
+        assert_eq!(
+            merge_serialized(None, &[&dense, &compressed[..compressed.len() - 1]]),
+            Err(MergeBytesError::Operand {
+                index: 1,
+                error: DecodeError::Truncated {
+                    expected: compressed.len(),
+                    actual: compressed.len() - 1
+                }
+            })
+        );
+        assert_eq!(
+            merge_serialized(Some(&dense), &[&compressed, &other_precision]),
+            Err(MergeBytesError::PrecisionMismatch {
+                index: 1,
+                expected: 6,
+                found: 7
+            })
+        );
+        assert_eq!(
+            merge_serialized(Some(&dense), &[&other_seed]),
+            Err(MergeBytesError::HasherMismatch { index: 0 })
+        );
+        let mut bad_register = dense.clone();
+        bad_register[31 + 10] = 60;
+        assert_eq!(
+            merge_serialized(Some(&dense), &[&bad_register]),
+            Err(MergeBytesError::Operand {
+                index: 0,
+                error: DecodeError::InvalidRegister {
+                    index: 10,
+                    value: 60
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn huge_frames_are_rejected_before_allocating() {
+        // 13 bytes claiming 2^32 empty registers, which would take 4 GiB.
+        let huge = *b"HLLR\x02\x20\x00\x0f\xff\xff\xff\xff\xf3";
+        assert_eq!(
+            merge_serialized(None, &[&huge]),
+            Err(MergeBytesError::Operand {
+                index: 0,
+                error: DecodeError::InvalidPrecision(32)
+            })
+        );
+        let mut h = HyperLogLog::new_seeded(1 << 24, 54);
+        (0..100u32).for_each(|i| h.add(i));
+        let frame = h.to_bytes_compressed();
+        let mut twice = h.clone();
+        twice.merge(&h).unwrap();
+        assert_eq!(
+            merge_serialized(Some(&frame), &[&frame]),
+            Ok(twice.to_bytes_compressed())
+        );
+    }
+
+    #[test]
+    fn corrupt_operands_never_panic() {
+        let mut h = HyperLogLog::new_seeded(1 << 4, 67);
+        (0..10u32).for_each(|i| h.add(i));
+        let mut state = 67;
+        for frame in &[h.to_bytes(), h.to_bytes_compressed()] {
+            for len in 0..frame.len() {
+                assert!(merge_serialized(None, &[&frame[..len]]).is_err());
+            }
+            for _ in 0..2_000 {
+                let mut bad = frame.clone();
+                let at = splitmix64(&mut state) as usize % bad.len();
+                bad[at] ^= 1 << (splitmix64(&mut state) % 8);
+                if let Ok(merged) = merge_serialized(Some(frame), &[&bad]) {
+                    assert!(HyperLogLog::<SeededState>::from_bytes(&merged).is_ok());
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_merge_operator() {
+        use std::sync::{Arc, Mutex};
+        use std::thread;
+
+        let db = ::sled::Config::new().temporary(true).open().unwrap();
+        let skipped = Arc::new(Mutex::new(Vec::new()));
+        let report = skipped.clone();
+        db.set_merge_operator(super::sled_merge_operator(move |key, error| {
+            report.lock().unwrap().push((key.to_vec(), error.clone()));
+        }));
+
+        let mut state = 67;
+        let (writes, all) = writes(&mut state);
+        let writes = Arc::new(writes);
+        let writers: Vec<_> = (0..4)
+            .map(|t| {
+                let (db, writes) = (db.clone(), writes.clone());
+                thread::spawn(move || {
+                    for w in writes.iter().skip(t).step_by(4) {
+                        db.merge(b"visitors", w).unwrap();
+                    }
+                })
+            })
+            .collect();
+        writers.into_iter().for_each(|w| w.join().unwrap());
+        let value = db.get(b"visitors").unwrap().unwrap();
+        assert_eq!(&value[..], &all.to_bytes_compressed()[..]);
+
+        // A bad operand is skipped and reported, keeping the value.
+        db.merge(b"visitors", b"HLLR junk").unwrap();
+        assert_eq!(db.get(b"visitors").unwrap().unwrap(), value);
+        let skipped = skipped.lock().unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, b"visitors");
+        assert!(matches!(
+            skipped[0].1,
+            MergeBytesError::Operand { index: 0, .. }
+        ));
+    }
+}
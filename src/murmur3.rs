@@ -0,0 +1,268 @@
+//! MurmurHash3 x64_128, shared by the DataSketches codec and the
+//! [`Murmur3State`] hasher.
+//!
+//! Guava's `Hashing.murmur3_128(seed).hashBytes(..).asLong()` and Go's
+//! `murmur3.Sum64WithSeed` both truncate the 128-bit hash to its first
+//! 64-bit half, `h1`, which is what [`Murmur3State`] produces too.
+
+#[cfg(feature = "murmur3")]
+use alloc::vec::Vec;
+use core::cmp;
+#[cfg(feature = "murmur3")]
+use core::hash::{BuildHasher, Hasher};
+
+#[cfg(feature = "murmur3")]
+use {HashBackend, StableBuildHasher};
+
+/// The finalization step of MurmurHash3.
+fn fmix64(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    k ^ (k >> 33)
+}
+
+/// Reads up to 8 bytes as a little-endian word.
+pub(crate) fn le64(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0, |acc, &b| acc << 8 | u64::from(b))
+}
+
+/// MurmurHash3 x64_128, returning the two 64-bit halves.
+pub(crate) fn murmur3_x64_128(key: &[u8], seed: u64) -> (u64, u64) {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+    let (mut h1, mut h2) = (seed, seed);
+    let mut blocks = key.chunks_exact(16);
+    for block in &mut blocks {
+        h1 ^= le64(&block[..8])
+            .wrapping_mul(C1)
+            .rotate_left(31)
+            .wrapping_mul(C2);
+        h1 = h1
+            .rotate_left(27)
+            .wrapping_add(h2)
+            .wrapping_mul(5)
+            .wrapping_add(0x52dc_e729);
+        h2 ^= le64(&block[8..])
+            .wrapping_mul(C2)
+            .rotate_left(33)
+            .wrapping_mul(C1);
+        h2 = h2
+            .rotate_left(31)
+            .wrapping_add(h1)
+            .wrapping_mul(5)
+            .wrapping_add(0x3849_5ab5);
+    }
+    let tail = blocks.remainder();
+    if tail.len() > 8 {
+        h2 ^= le64(&tail[8..])
+            .wrapping_mul(C2)
+            .rotate_left(33)
+            .wrapping_mul(C1);
+    }
+    if !tail.is_empty() {
+        h1 ^= le64(&tail[..cmp::min(tail.len(), 8)])
+            .wrapping_mul(C1)
+            .rotate_left(31)
+            .wrapping_mul(C2);
+    }
+    let len = key.len() as u64;
+    h1 ^= len;
+    h2 ^= len;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix64(h1);
+    h2 = fmix64(h2);
+    h1 = h1.wrapping_add(h2);
+    (h1, h2.wrapping_add(h1))
+}
+
+/// A `BuildHasher` for MurmurHash3 x64_128 truncated to its first half,
+/// with a fixed 32-bit seed, matching Guava's and Go's 64-bit MurmurHash3
+/// given the same bytes and seed.
+///
+/// As with [`Xxh3State`](::Xxh3State), add items with
+/// [`HyperLogLog::add_bytes`](::HyperLogLog::add_bytes) to hash exactly their
+/// bytes. Integers are written little-endian on every platform, as Guava's
+/// `hashLong` and `hashInt` write them.
+#[cfg(feature = "murmur3")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Murmur3State {
+    seed: u32,
+}
+
+#[cfg(feature = "murmur3")]
+impl Murmur3State {
+    /// MurmurHash3 with seed 0, Guava's and Go's default.
+    pub fn new() -> Murmur3State {
+        Murmur3State::with_seed(0)
+    }
+
+    pub fn with_seed(seed: u32) -> Murmur3State {
+        Murmur3State { seed }
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+}
+
+/// The hasher built by [`Murmur3State`]: MurmurHash3 over every byte
+/// written.
+#[cfg(feature = "murmur3")]
+#[derive(Debug, Clone)]
+pub struct Murmur3Hasher {
+    seed: u32,
+    bytes: Vec<u8>,
+}
+
+#[cfg(feature = "murmur3")]
+impl Hasher for Murmur3Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    little_endian_writes!();
+
+    fn finish(&self) -> u64 {
+        murmur3_x64_128(&self.bytes, u64::from(self.seed)).0
+    }
+}
+
+#[cfg(feature = "murmur3")]
+impl BuildHasher for Murmur3State {
+    type Hasher = Murmur3Hasher;
+
+    fn build_hasher(&self) -> Murmur3Hasher {
+        Murmur3Hasher {
+            seed: self.seed,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+/// Records a nonzero seed as the first key.
+#[cfg(feature = "murmur3")]
+impl StableBuildHasher for Murmur3State {
+    const BACKEND: HashBackend = HashBackend::Murmur3;
+
+    fn keys(&self) -> Option<(u64, u64)> {
+        match self.seed {
+            0 => None,
+            seed => Some((u64::from(seed), 0)),
+        }
+    }
+
+    fn from_keys(keys: Option<(u64, u64)>) -> Option<Self> {
+        match keys {
+            None => Some(Murmur3State::new()),
+            Some((seed, 0)) if seed <= u64::from(u32::MAX) => {
+                Some(Murmur3State::with_seed(seed as u32))
+            }
+            Some(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::murmur3_x64_128;
+
+    #[test]
+    fn murmur3_matches_reference() {
+        // The widely published x64_128 vector for seed 0.
+        let (h1, h2) = murmur3_x64_128(b"The quick brown fox jumps over the lazy dog", 0);
+        assert_eq!((h1, h2), (0xe34b_bc7b_bc07_1b6c, 0x7a43_3ca9_c49a_9347));
+        assert_eq!(murmur3_x64_128(b"", 0), (0, 0));
+        // Every tail length takes a different path; all must differ, here
+        // with DataSketches' seed.
+        let data = b"0123456789abcdef0123456789abcdef";
+        let hashes: Vec<(u64, u64)> = (0..=data.len())
+            .map(|n| murmur3_x64_128(&data[..n], 9001))
+            .collect();
+        for (i, a) in hashes.iter().enumerate() {
+            for b in &hashes[i + 1..] {
+                assert_ne!(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn matches_reference_halves() {
+        // Both halves, from the `murmur3` crate's port of the reference
+        // implementation, across the one-block and many-block paths.
+        let counting = |n: usize| -> Vec<u8> { (0..n).map(|i| i as u8).collect() };
+        let cases: [(Vec<u8>, u64, (u64, u64)); 4] = [
+            (
+                b"foo".to_vec(),
+                0,
+                (0xe271_8657_01f5_4561, 0x7eaf_87e4_2bba_7d87),
+            ),
+            (
+                b"foo".to_vec(),
+                42,
+                (0xf456_9d51_6370_53f2, 0xa279_b5d8_eeb0_9aa9),
+            ),
+            (
+                counting(200),
+                0,
+                (0xe56c_d258_c34d_e533, 0xa479_a540_75b1_3eb8),
+            ),
+            (
+                counting(1000),
+                42,
+                (0x74ff_8d79_1b6a_58d5, 0xc300_b362_5188_bee2),
+            ),
+        ];
+        for &(ref input, seed, want) in &cases {
+            assert_eq!(murmur3_x64_128(input, seed), want, "{} bytes", input.len());
+        }
+    }
+
+    #[cfg(feature = "murmur3")]
+    #[test]
+    fn register_and_rank_vectors() {
+        use super::Murmur3State;
+        use {index_and_rank, HyperLogLog};
+
+        // What a p = 14 sketch fed these strings as raw bytes must touch, in
+        // any language.
+        for &(item, register, rank) in &[
+            ("foo", 14_492, 2),
+            ("hello", 13_046, 3),
+            ("hyperloglog", 7_200, 4),
+            ("The quick brown fox jumps over the lazy dog", 14_546, 1),
+        ] {
+            let mut h = HyperLogLog::with_hasher(1 << 14, Murmur3State::new());
+            h.add_bytes(item);
+            let touched: Vec<(usize, u8)> =
+                h.registers().enumerate().filter(|&(_, r)| r > 0).collect();
+            assert_eq!(touched, [(register, rank)], "{:?}", item);
+            let (h1, _) = murmur3_x64_128(item.as_bytes(), 0);
+            assert_eq!(index_and_rank(h1, 14), (register, rank));
+        }
+    }
+
+    #[cfg(feature = "murmur3")]
+    #[test]
+    fn hasher_records_seed() {
+        use core::hash::BuildHasher;
+
+        use super::Murmur3State;
+        use {HashBackend, StableBuildHasher};
+
+        let state = Murmur3State::with_seed(42);
+        assert_eq!(state.hash_one(-1i64), murmur3_x64_128(&[0xff; 8], 42).0);
+        assert_eq!(Murmur3State::BACKEND, HashBackend::Murmur3);
+        for &seed in &[0, 1, u32::MAX] {
+            let state = Murmur3State::with_seed(seed);
+            assert_eq!(Murmur3State::from_keys(state.keys()), Some(state));
+        }
+        assert_eq!(Murmur3State::from_keys(Some((1 << 32, 0))), None);
+        assert_eq!(Murmur3State::from_keys(Some((1, 1))), None);
+    }
+}
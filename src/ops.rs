@@ -0,0 +1,84 @@
+//! `|` and `|=` as sketch union.
+//!
+//! Operators can't return a `Result`, so combining sketches of different
+//! sizes panics with the [`MergeError`](crate::MergeError) message that
+//! [`HyperLogLog::merge`] would have returned.
+
+use core::hash::BuildHasher;
+use core::ops::{BitOr, BitOrAssign};
+
+use HyperLogLog;
+
+/// `&a | &b` is a new sketch of the union; panics if the sizes differ.
+impl<S: BuildHasher + Clone> BitOr<&HyperLogLog<S>> for &HyperLogLog<S> {
+    type Output = HyperLogLog<S>;
+
+    fn bitor(self, other: &HyperLogLog<S>) -> HyperLogLog<S> {
+        match self.union(other) {
+            Ok(h) => h,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// `a |= &b` merges `b` into `a`; panics if the sizes differ.
+impl<S: BuildHasher> BitOrAssign<&HyperLogLog<S>> for HyperLogLog<S> {
+    fn bitor_assign(&mut self, other: &HyperLogLog<S>) {
+        if let Err(e) = self.merge(other) {
+            panic!("{}", e);
+        }
+    }
+}
+
+/// `a |= b` merges `b` into `a`; panics if the sizes differ.
+impl<S: BuildHasher> BitOrAssign<HyperLogLog<S>> for HyperLogLog<S> {
+    fn bitor_assign(&mut self, other: HyperLogLog<S>) {
+        *self |= &other;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use HyperLogLog;
+
+    fn sketch(range: ::std::ops::Range<u64>) -> HyperLogLog {
+        let mut h = HyperLogLog::new(1 << 10);
+        h.extend(range);
+        h
+    }
+
+    #[test]
+    fn operators_match_merge() {
+        let a = sketch(0..3_000);
+        let b = sketch(2_000..9_000);
+        let mut merged = a.clone();
+        merged.merge(&b).unwrap();
+
+        let union = &a | &b;
+        assert_eq!(union, merged);
+        assert_eq!(union.count(), merged.count());
+
+        let mut assigned = a.clone();
+        assigned |= &b;
+        assert_eq!(assigned, merged);
+        let mut assigned = a;
+        assigned |= b;
+        assert_eq!(assigned, merged);
+    }
+
+    #[test]
+    fn operators_fold() {
+        let hourly: Vec<HyperLogLog> = (0..24).map(|h| sketch(h * 500..h * 500 + 1_000)).collect();
+        let daily = hourly.iter().fold(HyperLogLog::new(1 << 10), |mut acc, s| {
+            acc |= s;
+            acc
+        });
+        assert_eq!(daily, sketch(0..12_500));
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot merge sketches with different precisions")]
+    fn mismatched_sizes_panic() {
+        let _ = &HyperLogLog::new(1 << 10) | &HyperLogLog::new(1 << 11);
+    }
+}
@@ -0,0 +1,227 @@
+//! Python bindings through PyO3, built into an importable `hyperloglog`
+//! module by `maturin` (see `pyproject.toml`).
+//!
+//! Items are hashed by value, never with Python's `hash()`, which is salted
+//! per interpreter run for `str` and `bytes`. Each accepted type becomes a
+//! byte string that is hashed like [`add_bytes`](::HyperLogLog::add_bytes)
+//! under `SeededState::new(0)`, the hashing the wasm and C bindings use:
+//!
+//! - `str`: its UTF-8 encoding, so `"a"` and `b"a"` are the same item;
+//! - `bytes`: its contents;
+//! - `int`: its 8-byte little-endian two's complement, so `7` matches Rust's
+//!   `add_bytes(7i64.to_le_bytes())`. `bool` counts as `int`, and ints
+//!   outside the `i64` range raise `OverflowError`.
+//!
+//! Anything else raises `TypeError`.
+
+use std::borrow::Cow;
+
+use pyo3::exceptions::{PyTypeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyInt, PyString};
+
+use {HyperLogLog, SeededState};
+
+const SEED: u64 = 0;
+
+/// The bytes `item` is hashed as; see the module documentation.
+fn item_bytes<'a>(item: &'a Bound<'_, PyAny>) -> PyResult<Cow<'a, [u8]>> {
+    if let Ok(s) = item.cast::<PyString>() {
+        Ok(Cow::Owned(s.to_str()?.as_bytes().to_vec()))
+    } else if let Ok(b) = item.cast::<PyBytes>() {
+        Ok(Cow::Borrowed(b.as_bytes()))
+    } else if item.is_instance_of::<PyInt>() {
+        Ok(Cow::Owned(item.extract::<i64>()?.to_le_bytes().to_vec()))
+    } else {
+        Err(PyTypeError::new_err(format!(
+            "can only add str, bytes or int, not {}",
+            item.get_type().name()?
+        )))
+    }
+}
+
+fn value_error<E: ToString>(e: E) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+/// A HyperLogLog sketch usable from Python, as the class
+/// `hyperloglog.HyperLogLog`.
+#[pyclass(name = "HyperLogLog", module = "hyperloglog", skip_from_py_object)]
+#[derive(Clone, Debug)]
+pub struct PyHyperLogLog {
+    inner: HyperLogLog<SeededState>,
+}
+
+#[pymethods]
+impl PyHyperLogLog {
+    /// Creates a sketch with `2^precision` registers, raising `ValueError`
+    /// unless `precision` is from 4 to 24.
+    #[new]
+    #[pyo3(signature = (precision = 14))]
+    fn new(precision: u8) -> PyResult<PyHyperLogLog> {
+        let inner = HyperLogLog::builder()
+            .precision(precision)
+            .seed(SEED)
+            .build()
+            .map_err(value_error)?;
+        Ok(PyHyperLogLog { inner })
+    }
+
+    fn add(&mut self, item: &Bound<'_, PyAny>) -> PyResult<()> {
+        self.inner.add_bytes(item_bytes(item)?);
+        Ok(())
+    }
+
+    fn count(&self) -> f64 {
+        self.inner.count()
+    }
+
+    #[getter]
+    fn precision(&self) -> u8 {
+        self.inner.precision()
+    }
+
+    /// Folds `other` into this sketch, raising `ValueError` if their
+    /// precisions differ.
+    fn merge(&mut self, other: &PyHyperLogLog) -> PyResult<()> {
+        self.inner
+            .merge(&other.inner)
+            .map(drop)
+            .map_err(value_error)
+    }
+
+    /// `a | b` is a new sketch of the union.
+    fn __or__(&self, other: &PyHyperLogLog) -> PyResult<PyHyperLogLog> {
+        let inner = self.inner.union(&other.inner).map_err(value_error)?;
+        Ok(PyHyperLogLog { inner })
+    }
+
+    /// Encodes the sketch in the crate's binary format.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.inner.to_bytes())
+    }
+
+    /// Decodes a sketch written by `to_bytes`, raising `ValueError` on
+    /// malformed input.
+    #[staticmethod]
+    fn from_bytes(data: &[u8]) -> PyResult<PyHyperLogLog> {
+        let inner = HyperLogLog::from_bytes(data).map_err(value_error)?;
+        Ok(PyHyperLogLog { inner })
+    }
+
+    fn __getnewargs__(&self) -> (u8,) {
+        (self.inner.precision(),)
+    }
+
+    fn __getstate__<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        self.to_bytes(py)
+    }
+
+    fn __setstate__(&mut self, state: &[u8]) -> PyResult<()> {
+        self.inner = HyperLogLog::from_bytes(state).map_err(value_error)?;
+        Ok(())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "HyperLogLog(precision={}, count={:.1})",
+            self.inner.precision(),
+            self.inner.count()
+        )
+    }
+}
+
+impl PyHyperLogLog {
+    /// The wrapped sketch.
+    pub fn sketch(&self) -> &HyperLogLog<SeededState> {
+        &self.inner
+    }
+}
+
+#[pymodule]
+fn hyperloglog(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyHyperLogLog>()
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::exceptions::{PyOverflowError, PyTypeError};
+    use pyo3::prelude::*;
+    use pyo3::types::{PyBytes, PyString};
+
+    use super::{item_bytes, PyHyperLogLog, SEED};
+    use HyperLogLog;
+
+    fn with_python<F: for<'py> FnOnce(Python<'py>)>(f: F) {
+        Python::initialize();
+        Python::attach(f)
+    }
+
+    #[test]
+    fn items_hash_by_value() {
+        with_python(|py| {
+            let text = PyString::new(py, "héllo").into_any();
+            let bytes = PyBytes::new(py, "héllo".as_bytes()).into_any();
+            assert_eq!(item_bytes(&text).unwrap(), "héllo".as_bytes());
+            assert_eq!(item_bytes(&bytes).unwrap(), "héllo".as_bytes());
+
+            let int = |source: &str| {
+                py.eval(&std::ffi::CString::new(source).unwrap(), None, None)
+                    .unwrap()
+            };
+            assert_eq!(item_bytes(&int("-2")).unwrap(), &(-2i64).to_le_bytes()[..]);
+            assert_eq!(item_bytes(&int("True")).unwrap(), &1i64.to_le_bytes()[..]);
+            assert!(item_bytes(&int("2 ** 64"))
+                .unwrap_err()
+                .is_instance_of::<PyOverflowError>(py));
+            assert!(item_bytes(&int("1.5"))
+                .unwrap_err()
+                .is_instance_of::<PyTypeError>(py));
+        });
+    }
+
+    #[test]
+    fn matches_a_rust_sketch() {
+        with_python(|py| {
+            let mut h = PyHyperLogLog::new(10).unwrap();
+            let mut native = HyperLogLog::new_seeded(1 << 10, SEED);
+            for i in 0..3_000i64 {
+                let item = format!("user-{}", i);
+                h.add(&PyString::new(py, &item).into_any()).unwrap();
+                h.add(&i.into_pyobject(py).unwrap().into_any()).unwrap();
+                native.add_bytes(&item);
+                native.add_bytes(i.to_le_bytes());
+            }
+            assert_eq!(h.sketch(), &native);
+
+            let restored = PyHyperLogLog::from_bytes(h.to_bytes(py).as_bytes()).unwrap();
+            assert_eq!(restored.sketch(), &native);
+            let mut state = PyHyperLogLog::new(4).unwrap();
+            state.__setstate__(h.__getstate__(py).as_bytes()).unwrap();
+            assert_eq!(state.sketch(), &native);
+            assert!(PyHyperLogLog::from_bytes(b"nope").is_err());
+        });
+    }
+
+    #[test]
+    fn merge_and_union() {
+        with_python(|py| {
+            let mut a = PyHyperLogLog::new(12).unwrap();
+            let mut b = PyHyperLogLog::new(12).unwrap();
+            let mut both = PyHyperLogLog::new(12).unwrap();
+            for i in 0..2_000i64 {
+                let item = i.into_pyobject(py).unwrap().into_any();
+                if i < 1_200 {
+                    a.add(&item).unwrap();
+                } else {
+                    b.add(&item).unwrap();
+                }
+                both.add(&item).unwrap();
+            }
+            assert_eq!((a.__or__(&b).unwrap()).sketch(), both.sketch());
+            a.merge(&b).unwrap();
+            assert_eq!(a.sketch(), both.sketch());
+            assert!(a.merge(&PyHyperLogLog::new(10).unwrap()).is_err());
+        });
+    }
+}
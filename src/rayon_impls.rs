@@ -0,0 +1,144 @@
+//! Parallel ingestion and merging with rayon.
+//!
+//! Merging is a register-wise maximum, which is associative and commutative,
+//! so any split of the work produces exactly the registers a serial pass
+//! would.
+
+use core::hash::{BuildHasher, Hash};
+
+use rayon::iter::{IntoParallelIterator, ParallelExtend, ParallelIterator};
+
+use cache::EstimateCache;
+use error::MergeError;
+use recent::RecentHashes;
+use HyperLogLog;
+
+impl<S: Clone> HyperLogLog<S> {
+    /// An empty sketch with the same precision, hasher, sparse threshold,
+    /// recent-hash cache size and estimator.
+    fn empty_like(&self) -> HyperLogLog<S> {
+        let mut h = HyperLogLog {
+            registers: self.registers.clone(),
+            histogram: self.histogram,
+            precision: self.precision,
+            sparse_threshold: self.sparse_threshold,
+            build_hasher: self.build_hasher.clone(),
+            cache: EstimateCache::new(),
+            watermark: None,
+            recent: self
+                .recent
+                .as_ref()
+                .map(|r| RecentHashes::new(r.capacity())),
+            estimator: self.estimator,
+            items_added: 0,
+            hasher_assumed: self.hasher_assumed,
+        };
+        h.registers.clear();
+        h.recount();
+        h
+    }
+}
+
+/// Adds every item of a parallel iterator, hashing into per-thread sketches
+/// that are merged into `self` at the end.
+impl<S, T> ParallelExtend<T> for HyperLogLog<S>
+where
+    S: BuildHasher + Clone + Send + Sync,
+    T: Hash + Send,
+{
+    fn par_extend<I: IntoParallelIterator<Item = T>>(&mut self, items: I) {
+        let empty = self.empty_like();
+        let partial = items
+            .into_par_iter()
+            .fold(
+                || empty.clone(),
+                |mut h, item| {
+                    h.add(item);
+                    h
+                },
+            )
+            .reduce(
+                || empty.clone(),
+                |mut a, b| {
+                    a.merge(&b).expect("partial sketches share a precision");
+                    a
+                },
+            );
+        self.merge(&partial)
+            .expect("partial sketches share a precision");
+    }
+}
+
+impl<S: BuildHasher + Send> HyperLogLog<S> {
+    /// Merges many sketches in a parallel tree, returning `None` if there are
+    /// none. The result has the same registers as merging them one by one,
+    /// and fails in the same way if their precisions differ.
+    pub fn merge_all<I>(sketches: I) -> Result<Option<HyperLogLog<S>>, MergeError>
+    where
+        I: IntoParallelIterator<Item = HyperLogLog<S>>,
+    {
+        sketches
+            .into_par_iter()
+            .map(Ok)
+            .try_reduce_with(|mut a, b| {
+                a.merge(&b)?;
+                Ok(a)
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rayon::prelude::*;
+
+    use {HyperLogLog, MergeError};
+
+    fn registers(h: &HyperLogLog) -> Vec<u8> {
+        h.registers().collect()
+    }
+
+    #[test]
+    fn par_extend_matches_extend() {
+        for &n in &[0u64, 100, 200_000] {
+            let mut serial = HyperLogLog::new(1 << 12);
+            serial.add("already here");
+            let mut parallel = serial.clone();
+            serial.extend(0..n);
+            parallel.par_extend((0..n).into_par_iter());
+            assert_eq!(registers(&parallel), registers(&serial), "n = {}", n);
+            assert_eq!(parallel.count(), serial.count());
+        }
+    }
+
+    #[test]
+    fn merge_all_matches_serial_fold() {
+        let shards: Vec<HyperLogLog> = (0..500u64)
+            .map(|s| {
+                let mut h = HyperLogLog::new(1 << 10);
+                h.extend(s * 300..s * 300 + 1_000);
+                h
+            })
+            .collect();
+        let mut serial = shards[0].clone();
+        for shard in &shards[1..] {
+            serial.merge(shard).unwrap();
+        }
+        let parallel = HyperLogLog::merge_all(shards).unwrap().unwrap();
+        assert_eq!(registers(&parallel), registers(&serial));
+    }
+
+    #[test]
+    fn merge_all_checks_precision() {
+        assert!(HyperLogLog::merge_all(Vec::<HyperLogLog>::new())
+            .unwrap()
+            .is_none());
+
+        let mut shards: Vec<HyperLogLog> = (0..50).map(|_| HyperLogLog::new(1 << 10)).collect();
+        shards.push(HyperLogLog::new(1 << 8));
+        match HyperLogLog::merge_all(shards) {
+            Err(MergeError::PrecisionMismatch { .. }) => {}
+            other => panic!("{:?}", other.map(|h| h.map(|h| h.precision()))),
+        }
+    }
+}
@@ -0,0 +1,53 @@
+//! A small table of recently added hashes, for skipping the register
+//! update of items added again soon after.
+//!
+//! The table is direct-mapped: each hash has one slot, picked by its low
+//! bits, and a miss overwrites whatever the slot held. A hash is only put in
+//! a slot as it is added to the registers, and registers never fall until
+//! the sketch is cleared, which empties the table too, so a hit always
+//! means the register already holds at least the hash's rank.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem;
+
+/// Marks an empty slot. A hash of 0 is never cached, so it can't hit one.
+const EMPTY: u64 = 0;
+
+#[derive(Clone, Debug)]
+pub(crate) struct RecentHashes {
+    slots: Box<[u64]>,
+}
+
+impl RecentHashes {
+    /// A table of `capacity` slots rounded up to a power of two.
+    pub(crate) fn new(capacity: usize) -> RecentHashes {
+        let slots: Vec<u64> = vec![EMPTY; capacity.next_power_of_two()];
+        RecentHashes {
+            slots: slots.into_boxed_slice(),
+        }
+    }
+
+    /// Whether `hash` is in the table, putting it there if not.
+    #[inline]
+    pub(crate) fn check_and_insert(&mut self, hash: u64) -> bool {
+        let slot = &mut self.slots[hash as usize & (self.slots.len() - 1)];
+        if *slot == hash && hash != EMPTY {
+            return true;
+        }
+        *slot = hash;
+        false
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = EMPTY);
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.slots.len() * mem::size_of::<u64>()
+    }
+}
@@ -0,0 +1,218 @@
+//! Publishing sketch estimates as gauges through the `metrics` facade, for
+//! scraping by Prometheus or any other exporter.
+//!
+//! Only the facade is a dependency: the gauges go to whichever recorder the
+//! application has installed, and nowhere if it has none.
+
+use alloc::string::{String, ToString};
+use core::fmt::Display;
+use core::hash::BuildHasher;
+use core::mem;
+use std::collections::HashSet;
+
+use metrics::{gauge, Label};
+
+use {HyperLogLog, HyperLogLogMap};
+
+/// Records one `<prefix>_estimate` gauge per labeled sketch each time
+/// [`report`](MetricsReporter::report) is called, for calling on a scrape
+/// interval.
+///
+/// With [`details`](MetricsReporter::details) on, it also records
+/// `<prefix>_registers_nonzero` and `<prefix>_memory_bytes`. The prefix
+/// defaults to `hll` and the label key to `sketch`, so a sketch labeled
+/// `"signups"` is reported as `hll_estimate{sketch="signups"}`.
+///
+/// The facade has no way to remove a gauge, so when a label reported last
+/// time is missing from the next report its gauges are set to 0, once, and
+/// the label is forgotten. Exporters with an idle timeout will then drop
+/// them.
+#[derive(Debug, Clone)]
+pub struct MetricsReporter {
+    prefix: String,
+    label_key: String,
+    details: bool,
+    reported: HashSet<String>,
+}
+
+impl MetricsReporter {
+    /// A reporter with the `hll` prefix, the `sketch` label key and no
+    /// detail gauges.
+    pub fn new() -> MetricsReporter {
+        MetricsReporter {
+            prefix: "hll".to_string(),
+            label_key: "sketch".to_string(),
+            details: false,
+            reported: HashSet::new(),
+        }
+    }
+
+    /// Names the gauges `<prefix>_estimate` and so on.
+    pub fn prefix(mut self, prefix: &str) -> MetricsReporter {
+        self.prefix = prefix.to_string();
+        self
+    }
+
+    /// The label key that tells the sketches apart.
+    pub fn label_key(mut self, key: &str) -> MetricsReporter {
+        self.label_key = key.to_string();
+        self
+    }
+
+    /// Also records the nonzero register count and memory use of each sketch.
+    pub fn details(mut self, details: bool) -> MetricsReporter {
+        self.details = details;
+        self
+    }
+
+    /// Records the gauges of every `(label, sketch)` pair, and zeroes those of
+    /// labels reported last time but not this time.
+    pub fn report<'a, L, S, I>(&mut self, sketches: I)
+    where
+        I: IntoIterator<Item = (L, &'a HyperLogLog<S>)>,
+        L: Display,
+        S: BuildHasher + 'a,
+    {
+        let mut current = HashSet::new();
+        for (label, h) in sketches {
+            let label = label.to_string();
+            let nonzero = if self.details {
+                Some(h.num_registers() - h.estimate_details().zero_registers)
+            } else {
+                None
+            };
+            self.record(&label, h.count(), nonzero, h.memory_bytes());
+            current.insert(label);
+        }
+        let previous = mem::replace(&mut self.reported, current);
+        for label in previous.difference(&self.reported) {
+            self.record(label, 0., self.details.then_some(0), 0);
+        }
+    }
+
+    /// Reports every key of `map` with its sketch, the key's `Display` form
+    /// as the label.
+    pub fn report_map<K: Display, S: BuildHasher>(&mut self, map: &HyperLogLogMap<K, S>) {
+        self.report(map.sketches());
+    }
+
+    fn record(&self, label: &str, estimate: f64, nonzero: Option<usize>, memory: usize) {
+        let labels = || vec![Label::new(self.label_key.clone(), label.to_string())];
+        gauge!(self.name("estimate"), labels()).set(estimate);
+        if let Some(nonzero) = nonzero {
+            gauge!(self.name("registers_nonzero"), labels()).set(nonzero as f64);
+            gauge!(self.name("memory_bytes"), labels()).set(memory as f64);
+        }
+    }
+
+    fn name(&self, gauge: &str) -> String {
+        let mut name = String::with_capacity(self.prefix.len() + 1 + gauge.len());
+        name.push_str(&self.prefix);
+        name.push('_');
+        name.push_str(gauge);
+        name
+    }
+}
+
+impl Default for MetricsReporter {
+    fn default() -> MetricsReporter {
+        MetricsReporter::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use metrics::with_local_recorder;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use metrics_util::MetricKind;
+
+    use super::MetricsReporter;
+    use {HyperLogLog, HyperLogLogMap};
+
+    /// Every gauge in the snapshot, as `name{key=value}` with its value.
+    fn gauges(snapshotter: &Snapshotter) -> BTreeMap<String, f64> {
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter(|(key, _, _, _)| key.kind() == MetricKind::Gauge)
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels: Vec<_> = key
+                    .labels()
+                    .map(|l| format!("{}={}", l.key(), l.value()))
+                    .collect();
+                let name = format!("{}{{{}}}", key.name(), labels.join(","));
+                match value {
+                    DebugValue::Gauge(v) => (name, v.into_inner()),
+                    _ => unreachable!(),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn records_estimates_and_details() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let mut a = HyperLogLog::new(1 << 8);
+        a.extend(0..100u32);
+        let b = HyperLogLog::new(1 << 8);
+        let mut reporter = MetricsReporter::new().details(true);
+        with_local_recorder(&recorder, || reporter.report([("a", &a), ("b", &b)]));
+
+        let nonzero = 256 - a.estimate_details().zero_registers;
+        let expected: BTreeMap<String, f64> = [
+            ("hll_estimate{sketch=a}".to_string(), a.count()),
+            ("hll_estimate{sketch=b}".to_string(), 0.),
+            (
+                "hll_memory_bytes{sketch=a}".to_string(),
+                a.memory_bytes() as f64,
+            ),
+            (
+                "hll_memory_bytes{sketch=b}".to_string(),
+                b.memory_bytes() as f64,
+            ),
+            (
+                "hll_registers_nonzero{sketch=a}".to_string(),
+                nonzero as f64,
+            ),
+            ("hll_registers_nonzero{sketch=b}".to_string(), 0.),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        assert_eq!(gauges(&snapshotter), expected);
+    }
+
+    #[test]
+    fn zeroes_labels_that_disappear() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        let mut map = HyperLogLogMap::new(1 << 8);
+        (0..50u32).for_each(|i| map.add("old", i));
+        (0..80u32).for_each(|i| map.add("kept", i));
+        let mut reporter = MetricsReporter::new().prefix("users").label_key("cohort");
+        with_local_recorder(&recorder, || reporter.report_map(&map));
+        let first = gauges(&snapshotter);
+        assert_eq!(first.len(), 2);
+        assert_eq!(first["users_estimate{cohort=old}"], map.count("old"));
+        assert_eq!(first["users_estimate{cohort=kept}"], map.count("kept"));
+
+        let mut next = HyperLogLogMap::new(1 << 8);
+        (0..80u32).for_each(|i| next.add("kept", i));
+        (0..20u32).for_each(|i| next.add("new", i));
+        with_local_recorder(&recorder, || reporter.report_map(&next));
+        let second = gauges(&snapshotter);
+        assert_eq!(second.len(), 3);
+        assert_eq!(second["users_estimate{cohort=old}"], 0.);
+        assert_eq!(second["users_estimate{cohort=kept}"], map.count("kept"));
+        assert_eq!(second["users_estimate{cohort=new}"], next.count("new"));
+
+        // "old" was zeroed once and is no longer tracked.
+        assert_eq!(reporter.reported.len(), 2);
+        assert!(!reporter.reported.contains("old"));
+    }
+}
@@ -0,0 +1,483 @@
+//! A ring of sketches, one per fixed-length time bucket, for distinct
+//! counts over rolling retention windows such as the last 24 hours at
+//! hourly granularity.
+//!
+//! The encoding of a ring, all integers little-endian:
+//!
+//! | offset | size   | contents                                          |
+//! |--------|--------|---------------------------------------------------|
+//! | 0      | 4      | magic `b"HLLR"`                                   |
+//! | 4      | 1      | format version (1)                                |
+//! | 5      | 8      | bucket duration                                   |
+//! | 13     | 4      | number of buckets `n`                             |
+//! | 17     | 8      | the newest bucket's number                        |
+//! | 25     |        | `n` buckets, oldest first, each a `u32` length    |
+//! |        |        | and then the bucket's compressed sketch           |
+//!
+//! Bucket `k` covers the timestamps from `k · duration` up to, but not
+//! including, `(k + 1) · duration`, so the newest bucket's number fixes
+//! every bucket's boundaries.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+
+use error::DecodeError;
+use {check_register_count, DefaultBuildHasher, HyperLogLog, StableBuildHasher};
+
+const MAGIC: [u8; 4] = *b"HLLR";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 25;
+const LEN_PREFIX: usize = 4;
+
+/// A fixed number of [`HyperLogLog`] sketches, each holding the items
+/// added with timestamps in one bucket of `bucket_duration`, the newest
+/// bucket replacing the oldest as time moves on.
+///
+/// Timestamps are plain `u64`s in whatever unit the caller picks, as long
+/// as durations and windows use the same unit. Adding an item past the
+/// newest bucket [rotates](HyperLogLogRing::rotate) the ring forward to it;
+/// an item older than the oldest bucket is dropped. A window is answered by
+/// merging the buckets it touches, so it is rounded out to whole buckets.
+#[derive(Clone, PartialEq)]
+pub struct HyperLogLogRing<S = DefaultBuildHasher> {
+    /// Bucket number `k` is at `k % buckets.len()`.
+    buckets: Vec<HyperLogLog<S>>,
+    bucket_duration: u64,
+    /// The number of the newest bucket.
+    newest: u64,
+}
+
+impl HyperLogLogRing {
+    /// Creates a ring of `bucket_count` empty sketches with `m` registers,
+    /// each covering `bucket_duration`, the newest covering the first
+    /// bucket of timestamps.
+    ///
+    /// Panics unless `m` is a power of two from 16 to 2^24, as for
+    /// [`HyperLogLog::new`], or if `bucket_duration` or `bucket_count` is 0.
+    pub fn new(m: usize, bucket_duration: u64, bucket_count: usize) -> HyperLogLogRing {
+        HyperLogLogRing::with_hasher(
+            m,
+            bucket_duration,
+            bucket_count,
+            DefaultBuildHasher::default(),
+        )
+    }
+}
+
+impl<S: BuildHasher + Clone> HyperLogLogRing<S> {
+    /// Like [`HyperLogLogRing::new`], hashing items with clones of
+    /// `build_hasher`.
+    pub fn with_hasher(
+        m: usize,
+        bucket_duration: u64,
+        bucket_count: usize,
+        build_hasher: S,
+    ) -> HyperLogLogRing<S> {
+        if let Err(e) = check_register_count(m) {
+            panic!("{}", e);
+        }
+        assert!(bucket_duration > 0, "buckets must have a positive duration");
+        assert!(bucket_count > 0, "a ring needs at least one bucket");
+        HyperLogLogRing {
+            buckets: vec![HyperLogLog::with_hasher(m, build_hasher); bucket_count],
+            bucket_duration,
+            newest: 0,
+        }
+    }
+
+    /// Merges the buckets touching the `window` ending at `now`, those
+    /// holding timestamps after `now - window` up to and including `now`,
+    /// into one sketch.
+    ///
+    /// Panics if `window` is longer than the ring's
+    /// [`retention`](HyperLogLogRing::retention).
+    pub fn union_last(&self, window: u64, now: u64) -> HyperLogLog<S> {
+        assert!(
+            window <= self.retention(),
+            "window {} is longer than the retention of {}",
+            window,
+            self.retention()
+        );
+        let mut merged = self.buckets[0].clone();
+        merged.clear();
+        if window == 0 {
+            return merged;
+        }
+        let first = now.saturating_sub(window - 1) / self.bucket_duration;
+        let last = (now / self.bucket_duration).min(self.newest);
+        for k in first.max(self.oldest())..=last {
+            merged
+                .merge(&self.buckets[self.slot(k)])
+                .expect("buckets share a precision");
+        }
+        merged
+    }
+
+    /// Estimates the number of distinct items in the buckets touching the
+    /// `window` ending at `now`; see [`union_last`](HyperLogLogRing::union_last).
+    pub fn count_last(&self, window: u64, now: u64) -> f64 {
+        self.union_last(window, now).count()
+    }
+}
+
+impl<S: BuildHasher> HyperLogLogRing<S> {
+    /// Adds an item to the bucket of `timestamp`, rotating the ring forward
+    /// first if the bucket is newer than the newest. Does nothing if the
+    /// bucket is older than the oldest.
+    pub fn add<H: Hash>(&mut self, item: H, timestamp: u64) {
+        if let Some(bucket) = self.bucket_for(timestamp) {
+            bucket.add(item);
+        }
+    }
+
+    /// Adds an item by its precomputed 64-bit hash, as
+    /// [`add`](HyperLogLogRing::add) adds an item; see
+    /// [`HyperLogLog::add_hashed`].
+    pub fn add_hashed(&mut self, hash: u64, timestamp: u64) {
+        if let Some(bucket) = self.bucket_for(timestamp) {
+            bucket.add_hashed(hash);
+        }
+    }
+
+    fn bucket_for(&mut self, timestamp: u64) -> Option<&mut HyperLogLog<S>> {
+        self.rotate(timestamp);
+        let k = timestamp / self.bucket_duration;
+        if k < self.oldest() {
+            return None;
+        }
+        let slot = self.slot(k);
+        Some(&mut self.buckets[slot])
+    }
+
+    /// Moves the ring forward so that its newest bucket holds `now`,
+    /// emptying the buckets that fall out of it. Does nothing if the newest
+    /// bucket is already that new.
+    pub fn rotate(&mut self, now: u64) {
+        let target = now / self.bucket_duration;
+        if target <= self.newest {
+            return;
+        }
+        let stale = (target - self.newest).min(self.buckets.len() as u64);
+        for k in target - stale + 1..=target {
+            let slot = self.slot(k);
+            self.buckets[slot].clear();
+        }
+        self.newest = target;
+    }
+}
+
+impl<S> HyperLogLogRing<S> {
+    /// Each bucket's first timestamp and sketch, oldest first, leaving out
+    /// buckets from before the first timestamp.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, &HyperLogLog<S>)> + '_ {
+        (self.oldest()..=self.newest)
+            .map(move |k| (k * self.bucket_duration, &self.buckets[self.slot(k)]))
+    }
+
+    pub fn bucket_duration(&self) -> u64 {
+        self.bucket_duration
+    }
+
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// The span of timestamps the ring holds, `bucket_duration` times
+    /// `bucket_count`.
+    pub fn retention(&self) -> u64 {
+        self.bucket_duration
+            .saturating_mul(self.buckets.len() as u64)
+    }
+
+    /// The first timestamp of the newest bucket.
+    pub fn newest_bucket_start(&self) -> u64 {
+        self.newest * self.bucket_duration
+    }
+
+    /// The number of the oldest bucket still held.
+    fn oldest(&self) -> u64 {
+        (self.newest + 1).saturating_sub(self.buckets.len() as u64)
+    }
+
+    fn slot(&self, k: u64) -> usize {
+        (k % self.buckets.len() as u64) as usize
+    }
+}
+
+impl<S: StableBuildHasher> HyperLogLogRing<S> {
+    /// Encodes the ring, its bucket boundaries and every bucket, in the
+    /// format described in the module docs.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN);
+        out.extend_from_slice(&MAGIC);
+        out.push(VERSION);
+        out.extend_from_slice(&self.bucket_duration.to_le_bytes());
+        out.extend_from_slice(&(self.buckets.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.newest.to_le_bytes());
+        // The bucket after the newest is the oldest, so the slots in order
+        // from there are oldest first, including ones not yet used.
+        for k in self.newest + 1..=self.newest + self.buckets.len() as u64 {
+            let bucket = self.buckets[self.slot(k)].to_bytes_compressed();
+            out.extend_from_slice(&(bucket.len() as u32).to_le_bytes());
+            out.extend_from_slice(&bucket);
+        }
+        out
+    }
+
+    /// Decodes a ring written by [`to_bytes`](HyperLogLogRing::to_bytes).
+    ///
+    /// Never panics: a ring without buckets or with a zero duration, or
+    /// whose buckets differ in precision, fails with
+    /// [`DecodeError::InvalidRing`], and a bad bucket with the error
+    /// decoding it gave.
+    pub fn from_bytes(bytes: &[u8]) -> Result<HyperLogLogRing<S>, DecodeError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(DecodeError::Truncated {
+                expected: HEADER_LEN,
+                actual: bytes.len(),
+            });
+        }
+        let mut magic = [0u8; 4];
+        magic.copy_from_slice(&bytes[..4]);
+        if magic != MAGIC {
+            return Err(DecodeError::BadMagic(magic));
+        }
+        if bytes[4] != VERSION {
+            return Err(DecodeError::UnsupportedVersion(bytes[4]));
+        }
+        let bucket_duration = read_u64(&bytes[5..13]);
+        let count = read_u32(&bytes[13..17]) as usize;
+        let newest = read_u64(&bytes[17..25]);
+        if bucket_duration == 0 || count == 0 || newest.checked_add(count as u64).is_none() {
+            return Err(DecodeError::InvalidRing);
+        }
+
+        let mut buckets = Vec::with_capacity(count.min(bytes.len() / LEN_PREFIX));
+        let mut at = HEADER_LEN;
+        while buckets.len() < count {
+            let len = match bytes.get(at..at + LEN_PREFIX) {
+                Some(prefix) => read_u32(prefix) as usize,
+                None => {
+                    return Err(DecodeError::Truncated {
+                        expected: at + LEN_PREFIX,
+                        actual: bytes.len(),
+                    })
+                }
+            };
+            at += LEN_PREFIX;
+            let bucket = match bytes.get(at..at.saturating_add(len)) {
+                Some(bucket) => HyperLogLog::from_bytes(bucket)?,
+                None => {
+                    return Err(DecodeError::Truncated {
+                        expected: at.saturating_add(len),
+                        actual: bytes.len(),
+                    })
+                }
+            };
+            if buckets
+                .first()
+                .is_some_and(|first: &HyperLogLog<S>| first.precision != bucket.precision)
+            {
+                return Err(DecodeError::InvalidRing);
+            }
+            buckets.push(bucket);
+            at += len;
+        }
+        if at < bytes.len() {
+            return Err(DecodeError::TrailingBytes {
+                expected: at,
+                actual: bytes.len(),
+            });
+        }
+        // The encoding is oldest first; put each bucket in its slot.
+        let shift = ((newest + 1) % count as u64) as usize;
+        buckets.rotate_right(shift);
+        Ok(HyperLogLogRing {
+            buckets,
+            bucket_duration,
+            newest,
+        })
+    }
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(bytes);
+    u64::from_le_bytes(word)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut word = [0u8; 4];
+    word.copy_from_slice(bytes);
+    u32::from_le_bytes(word)
+}
+
+impl<S> fmt::Debug for HyperLogLogRing<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HyperLogLogRing")
+            .field("precision", &self.buckets[0].precision)
+            .field("bucket_duration", &self.bucket_duration)
+            .field("bucket_count", &self.buckets.len())
+            .field("newest_bucket_start", &self.newest_bucket_start())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::HyperLogLogRing;
+    use error::DecodeError;
+    use simulation::splitmix64;
+    use {standard_error, HyperLogLog, SeededState};
+
+    const HOUR: u64 = 3_600;
+
+    /// The hash of user `id`, the same every time the user appears.
+    fn user_hash(id: u64) -> u64 {
+        let mut state = id;
+        splitmix64(&mut state)
+    }
+
+    #[test]
+    fn windows_match_exact_distinct_counts() {
+        // Two days of 40 events a minute from a user base that drifts by 30
+        // users a minute, so that each window length sees a different
+        // number of distinct users.
+        let p = 12;
+        let mut ring = HyperLogLogRing::new(1 << p, HOUR, 24);
+        let mut last_seen: HashMap<u64, u64> = HashMap::new();
+        let mut rng = 91;
+        let bound = 4. * standard_error(p);
+        for minute in 0..48 * 60 {
+            for _ in 0..40 {
+                let t = minute * 60 + splitmix64(&mut rng) % 60;
+                let id = minute * 30 + splitmix64(&mut rng) % 5_000;
+                ring.add_hashed(user_hash(id), t);
+                let seen = last_seen.entry(id).or_insert(t);
+                *seen = (*seen).max(t);
+            }
+            if (minute + 1) % 60 != 0 {
+                continue;
+            }
+            // The last second of an hour, so windows of whole hours cover
+            // whole buckets.
+            let now = (minute + 1) * 60 - 1;
+            for &window in &[HOUR, 6 * HOUR, 24 * HOUR] {
+                if window > now + 1 {
+                    continue;
+                }
+                let exact = last_seen.values().filter(|&&t| now - t < window).count() as f64;
+                let counted = ring.count_last(window, now);
+                assert!(
+                    (counted - exact).abs() / exact < bound,
+                    "window {} at {}: counted {}, exact {}",
+                    window,
+                    now,
+                    counted,
+                    exact
+                );
+            }
+        }
+
+        // The buckets are the last 24 hours, each counting its own hour.
+        let buckets: Vec<_> = ring.buckets().collect();
+        assert_eq!(buckets.len(), 24);
+        assert_eq!(buckets[0].0, 24 * HOUR);
+        assert_eq!(buckets[23].0, 47 * HOUR);
+        assert_eq!(ring.newest_bucket_start(), 47 * HOUR);
+        let now = 48 * HOUR - 1;
+        assert_eq!(buckets[23].1.count(), ring.count_last(HOUR, now));
+        // A window of part of an hour takes the whole bucket.
+        assert_eq!(ring.count_last(1, now), ring.count_last(HOUR, now));
+        assert_eq!(ring.count_last(0, now), 0.);
+    }
+
+    #[test]
+    fn rotates_and_drops_old_items() {
+        let mut ring = HyperLogLogRing::new(1 << 8, 10, 3);
+        ring.add(1u32, 5);
+        ring.add(2u32, 15);
+        assert_eq!(ring.buckets().map(|(t, _)| t).collect::<Vec<_>>(), [0, 10]);
+        ring.add(3u32, 35);
+        // 35 is in bucket 3, so bucket 0 is gone and 5 is too old to add.
+        assert_eq!(
+            ring.buckets().map(|(t, _)| t).collect::<Vec<_>>(),
+            [10, 20, 30]
+        );
+        ring.add(4u32, 5);
+        let counts: Vec<f64> = ring.buckets().map(|(_, h)| h.count().round()).collect();
+        assert_eq!(counts, [1., 0., 1.]);
+        // A late item that is still held lands in its own bucket.
+        ring.add(5u32, 21);
+        assert_eq!(ring.count_last(30, 39).round(), 3.);
+        assert_eq!(ring.count_last(10, 39).round(), 1.);
+
+        // Rotating past every bucket empties the ring, and rotating back
+        // does nothing.
+        ring.rotate(1_000);
+        ring.rotate(0);
+        assert_eq!(ring.newest_bucket_start(), 1_000);
+        assert!(ring.buckets().all(|(_, h)| h.count() == 0.));
+        assert_eq!(ring.count_last(30, 1_009), 0.);
+    }
+
+    #[test]
+    #[should_panic(expected = "window 31 is longer than the retention of 30")]
+    fn rejects_windows_past_the_retention() {
+        HyperLogLogRing::new(1 << 8, 10, 3).count_last(31, 100);
+    }
+
+    #[test]
+    fn round_trips_through_bytes() {
+        let mut state = 91;
+        let mut ring = HyperLogLogRing::with_hasher(1 << 6, 7, 5, SeededState::new(91));
+        for t in 0..60 {
+            ring.add_hashed(splitmix64(&mut state), t);
+        }
+        let bytes = ring.to_bytes();
+        let decoded = HyperLogLogRing::<SeededState>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, ring);
+        assert!(decoded.buckets().eq(ring.buckets()));
+        assert_eq!(decoded.count_last(35, 59), ring.count_last(35, 59));
+
+        // A young ring still encodes every bucket.
+        let young = HyperLogLogRing::with_hasher(1 << 6, 7, 5, SeededState::new(91));
+        assert_eq!(
+            HyperLogLogRing::<SeededState>::from_bytes(&young.to_bytes()).unwrap(),
+            young
+        );
+
+        assert_eq!(
+            HyperLogLogRing::<SeededState>::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            DecodeError::Truncated {
+                expected: bytes.len(),
+                actual: bytes.len() - 1
+            }
+        );
+        let mut zero = bytes.clone();
+        zero[5..13].copy_from_slice(&0u64.to_le_bytes());
+        assert_eq!(
+            HyperLogLogRing::<SeededState>::from_bytes(&zero),
+            Err(DecodeError::InvalidRing)
+        );
+        assert_eq!(
+            HyperLogLogRing::<SeededState>::from_bytes(&young.to_bytes()[1..]).unwrap_err(),
+            DecodeError::BadMagic([b'L', b'L', b'R', 1])
+        );
+        // Buckets of different precisions don't make a ring.
+        let mut mixed = bytes[..25].to_vec();
+        mixed[13..17].copy_from_slice(&2u32.to_le_bytes());
+        for &m in &[1 << 6, 1 << 7] {
+            let bucket = HyperLogLog::new_seeded(m, 91).to_bytes_compressed();
+            mixed.extend_from_slice(&(bucket.len() as u32).to_le_bytes());
+            mixed.extend_from_slice(&bucket);
+        }
+        assert_eq!(
+            HyperLogLogRing::<SeededState>::from_bytes(&mixed),
+            Err(DecodeError::InvalidRing)
+        );
+    }
+}
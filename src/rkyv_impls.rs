@@ -0,0 +1,179 @@
+//! Zero-copy archiving with `rkyv`, for sketches whose hasher can be
+//! reproduced (see [`serde_impls`](::serde_impls) for why).
+//!
+//! A sketch archives as [`ArchivedHyperLogLog`]: the precision, hasher keys
+//! and backend code of [`HyperLogLog::to_bytes`], and the registers as one
+//! byte each. The archived registers are read in place, so
+//! [`ArchivedHyperLogLog::count`] works on a memory-mapped archive without
+//! deserializing it. Checked access validates the registers as `from_bytes`
+//! does.
+
+use core::hash::BuildHasher;
+
+use rkyv::bytecheck::{CheckBytes, Verify};
+use rkyv::munge::munge;
+use rkyv::primitive::ArchivedU64;
+use rkyv::rancor::{Fallible, Source};
+use rkyv::ser::{Allocator, Writer};
+use rkyv::vec::{ArchivedVec, VecResolver};
+use rkyv::{Archive, Deserialize, Place, Portable, Serialize};
+
+use encoding::{decode_hasher, recorded_hasher, validate_registers};
+use error::DecodeError;
+use {estimate, rank_histogram, HashBackend, HyperLogLog, StableBuildHasher};
+
+/// A sketch archived by `rkyv`, usable in place.
+#[derive(Portable, CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck, verify)]
+#[rkyv(crate = rkyv)]
+#[repr(C)]
+pub struct ArchivedHyperLogLog {
+    precision: u8,
+    /// The [`HashBackend`] code.
+    backend: u8,
+    keyed: bool,
+    keys: [ArchivedU64; 2],
+    registers: ArchivedVec<u8>,
+}
+
+impl ArchivedHyperLogLog {
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// The registers, one byte each, straight from the archive.
+    pub fn registers(&self) -> &[u8] {
+        self.registers.as_slice()
+    }
+
+    /// Estimates the cardinality as [`HyperLogLog::count`] would for the
+    /// archived sketch, reading the registers in place.
+    pub fn count(&self) -> f64 {
+        estimate(&rank_histogram(self.registers().iter().copied()))
+    }
+
+    fn keys(&self) -> Option<(u64, u64)> {
+        if self.keyed {
+            Some((self.keys[0].to_native(), self.keys[1].to_native()))
+        } else {
+            None
+        }
+    }
+}
+
+/// Rejects registers `from_bytes` would, after `bytecheck` has checked the
+/// layout.
+unsafe impl<C> Verify<C> for ArchivedHyperLogLog
+where
+    C: Fallible + ?Sized,
+    C::Error: Source,
+{
+    fn verify(&self, _: &mut C) -> Result<(), C::Error> {
+        validate_registers(self.precision, self.registers()).map_err(C::Error::new)?;
+        match HashBackend::from_code(self.backend) {
+            Some(_) => Ok(()),
+            None => Err(C::Error::new(DecodeError::UnknownHashBackend(self.backend))),
+        }
+    }
+}
+
+impl<S: StableBuildHasher> Archive for HyperLogLog<S> {
+    type Archived = ArchivedHyperLogLog;
+    type Resolver = VecResolver;
+
+    fn resolve(&self, resolver: VecResolver, out: Place<ArchivedHyperLogLog>) {
+        munge!(let ArchivedHyperLogLog { precision, backend, keyed, keys, registers } = out);
+        precision.write(self.precision);
+        let (recorded, k) = recorded_hasher(&self.build_hasher);
+        backend.write(recorded.code());
+        keyed.write(k.is_some());
+        let (k0, k1) = k.unwrap_or((0, 0));
+        keys.write([ArchivedU64::from_native(k0), ArchivedU64::from_native(k1)]);
+        ArchivedVec::resolve_from_len(self.num_registers(), resolver, registers);
+    }
+}
+
+impl<S, Ser> Serialize<Ser> for HyperLogLog<S>
+where
+    S: StableBuildHasher,
+    Ser: Fallible + Allocator + Writer + ?Sized,
+{
+    fn serialize(&self, serializer: &mut Ser) -> Result<VecResolver, Ser::Error> {
+        let m = self.num_registers();
+        ArchivedVec::serialize_from_iter(self.registers.iter(m), serializer)
+    }
+}
+
+/// Fails with [`DecodeError::HasherMismatch`] if the archive was written with
+/// a different hash backend or keying from `S`.
+impl<S, D> Deserialize<HyperLogLog<S>, D> for ArchivedHyperLogLog
+where
+    S: StableBuildHasher + BuildHasher,
+    D: Fallible + ?Sized,
+    D::Error: Source,
+{
+    fn deserialize(&self, _: &mut D) -> Result<HyperLogLog<S>, D::Error> {
+        let build_hasher = HashBackend::from_code(self.backend)
+            .and_then(|backend| decode_hasher(backend, self.keys()))
+            .ok_or_else(|| D::Error::new(DecodeError::HasherMismatch))?;
+        Ok(HyperLogLog::from_dense(
+            self.precision,
+            self.registers().to_vec(),
+            build_hasher,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rkyv::rancor::Error;
+
+    use super::ArchivedHyperLogLog;
+    use {HyperLogLog, SeededState};
+
+    #[test]
+    fn archived_count_matches() {
+        for &n in &[0u32, 200, 100_000] {
+            let mut h = HyperLogLog::new_seeded(1 << 12, 66);
+            (0..n).for_each(|i| h.add(i));
+            let bytes = rkyv::to_bytes::<Error>(&h).unwrap();
+            let archived = rkyv::access::<ArchivedHyperLogLog, Error>(&bytes).unwrap();
+            assert_eq!(archived.precision(), 12);
+            assert_eq!(archived.count(), h.count(), "n {}", n);
+            assert!(archived.registers().iter().copied().eq(h.registers()));
+
+            let back: HyperLogLog<SeededState> = rkyv::deserialize::<_, Error>(archived).unwrap();
+            assert_eq!(back, h);
+            assert_eq!(back.build_hasher.keys(), h.build_hasher.keys());
+        }
+    }
+
+    #[test]
+    fn rejects_corrupt_archives() {
+        let mut h: HyperLogLog = HyperLogLog::new(1 << 6);
+        (0..1_000u32).for_each(|i| h.add(i));
+        let bytes = rkyv::to_bytes::<Error>(&h).unwrap();
+        assert!(rkyv::access::<ArchivedHyperLogLog, Error>(&bytes).is_ok());
+
+        // The registers come first, then the root structure.
+        let mut bad_register = bytes.to_vec();
+        bad_register[3] = 60;
+        let mut bad_backend = bytes.to_vec();
+        let root = bytes.len() - ::core::mem::size_of::<ArchivedHyperLogLog>();
+        bad_backend[root + 1] = 7;
+        let mut bad_bool = bytes.to_vec();
+        bad_bool[root + 2] = 2;
+        let mut bad_length = bytes.to_vec();
+        let len_at = bytes.len() - 4;
+        bad_length[len_at] = 0xff;
+        for bad in &[bad_register, bad_backend, bad_bool, bad_length] {
+            let mut aligned = rkyv::util::AlignedVec::<16>::new();
+            aligned.extend_from_slice(bad);
+            assert!(rkyv::access::<ArchivedHyperLogLog, Error>(&aligned).is_err());
+        }
+        assert!(rkyv::access::<ArchivedHyperLogLog, Error>(&bytes[..bytes.len() - 1]).is_err());
+
+        let archived = rkyv::access::<ArchivedHyperLogLog, Error>(&bytes).unwrap();
+        assert!(rkyv::deserialize::<HyperLogLog<SeededState>, Error>(archived).is_err());
+    }
+}
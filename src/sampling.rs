@@ -0,0 +1,192 @@
+//! Scaling an estimate up for a stream that was sampled before it reached
+//! the sketch.
+//!
+//! A sketch of a sampled stream counts the distinct items that survived
+//! sampling, so the correction is the probability that a distinct item
+//! survives. That depends on how the stream was sampled. When whole items
+//! are kept or dropped, as hash-based sampling does, it is the sampling
+//! rate, whatever the items' frequencies. When occurrences are kept
+//! independently, an item seen many times almost surely survives while a
+//! singleton survives only at the sampling rate, so the correction depends
+//! on the frequencies, which the sketch can't see. [`SamplingModel`] makes
+//! the caller say which case applies.
+//!
+//! Dividing by the survival probability is unbiased, but the sketch saw
+//! only the surviving items, so the relative error grows by about
+//! `√((1 - q) / (q n))` for survival probability `q` and true count `n`:
+//! at a 1% rate, 10,000 singletons are estimated from about 100 survivors,
+//! give or take 10%.
+
+use core::hash::BuildHasher;
+
+use {math, HyperLogLog};
+
+/// How a stream was sampled before it was added to a sketch, for
+/// [`HyperLogLog::count_with_sampling`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SamplingModel {
+    /// Each distinct item was kept, with all its occurrences, or dropped,
+    /// as hash-based sampling (keeping an item when its hash falls below a
+    /// threshold) does. The estimate is divided by the rate, which is
+    /// unbiased whatever the frequencies.
+    DistinctItems,
+    /// Each occurrence was kept independently, and each distinct item is
+    /// assumed to occur `per_item` times in the unsampled stream. An item
+    /// survives with probability `1 - (1 - rate)^per_item`, and the
+    /// estimate is divided by that.
+    ///
+    /// With `per_item` 1, every item a singleton, this is division by the
+    /// rate. When `per_item` is well above `1 / rate`, nearly every item
+    /// survives and the estimate is barely changed; dividing it by the rate
+    /// would overcount many times over. When frequencies are mixed, no
+    /// single `per_item` is right: the true count lies between the
+    /// estimates that assume the lowest and the highest frequency.
+    Occurrences { per_item: f64 },
+}
+
+impl SamplingModel {
+    /// The probability that a distinct item reaches the sketch.
+    fn survival(self, rate: f64) -> f64 {
+        match self {
+            SamplingModel::DistinctItems => rate,
+            SamplingModel::Occurrences { per_item } => {
+                assert!(
+                    per_item >= 1.,
+                    "items occur at least once, not {} times",
+                    per_item
+                );
+                -math::exp_m1(per_item * math::ln_1p(-rate))
+            }
+        }
+    }
+}
+
+impl<S: BuildHasher> HyperLogLog<S> {
+    /// Estimates the cardinality of the stream the sketch's items were
+    /// sampled from at `rate`, correcting [`count`](HyperLogLog::count)
+    /// for the distinct items that sampling dropped under `model`.
+    ///
+    /// Panics unless `0 < rate <= 1`, or for an `Occurrences` model with
+    /// `per_item` below 1.
+    pub fn count_with_sampling(&self, rate: f64, model: SamplingModel) -> f64 {
+        assert!(
+            rate > 0. && rate <= 1.,
+            "sampling rate must be in (0, 1], not {}",
+            rate
+        );
+        self.count() / model.survival(rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SamplingModel::{DistinctItems, Occurrences};
+    use simulation::splitmix64;
+    use HyperLogLog;
+
+    /// Keeps a pseudo-random `rate` of draws from `state`.
+    fn keep(state: &mut u64, rate: f64) -> bool {
+        splitmix64(state) < (rate * u64::MAX as f64) as u64
+    }
+
+    /// Sketches `items`, each occurring `per_item` times, keeping each
+    /// occurrence with probability `rate`.
+    fn sample_occurrences(
+        h: &mut HyperLogLog,
+        items: core::ops::Range<u64>,
+        per_item: u64,
+        rate: f64,
+        state: &mut u64,
+    ) {
+        for item in items {
+            for _ in 0..per_item {
+                if keep(state, rate) {
+                    h.add(item);
+                }
+            }
+        }
+    }
+
+    fn assert_close(estimate: f64, truth: f64, tolerance: f64) {
+        let error = (estimate - truth) / truth;
+        assert!(error.abs() < tolerance, "{} vs {}", estimate, truth);
+    }
+
+    #[test]
+    fn singletons_scale_by_the_rate() {
+        let mut state = 85;
+        let mut h = HyperLogLog::new(1 << 14);
+        sample_occurrences(&mut h, 0..200_000, 1, 0.01, &mut state);
+        // About 2,000 survivors, so a sampling error near 2.2%.
+        let corrected = h.count_with_sampling(0.01, Occurrences { per_item: 1. });
+        assert_close(corrected, 200_000., 0.1);
+        assert_eq!(corrected, h.count_with_sampling(0.01, DistinctItems));
+    }
+
+    #[test]
+    fn heavy_hitters_barely_need_correcting() {
+        let mut state = 85;
+        let mut h = HyperLogLog::new(1 << 14);
+        sample_occurrences(&mut h, 0..5_000, 1_000, 0.01, &mut state);
+        let corrected = h.count_with_sampling(0.01, Occurrences { per_item: 1_000. });
+        assert_close(corrected, 5_000., 0.05);
+        assert!(corrected / h.count() < 1.001);
+        // Dividing by the rate would overcount a hundredfold.
+        assert!(h.count_with_sampling(0.01, DistinctItems) > 50. * 5_000.);
+    }
+
+    #[test]
+    fn hash_sampling_scales_by_the_rate() {
+        let mut state = 85;
+        let mut h = HyperLogLog::new(1 << 14);
+        for item in 0..100_000u64 {
+            // Whether an item is kept depends only on the item.
+            if keep(&mut item.clone(), 0.05) {
+                for _ in 0..1 + splitmix64(&mut state) % 20 {
+                    h.add(item);
+                }
+            }
+        }
+        // About 5,000 survivors, so a sampling error near 1.4%.
+        assert_close(h.count_with_sampling(0.05, DistinctItems), 100_000., 0.08);
+    }
+
+    #[test]
+    fn mixed_frequencies_are_bracketed() {
+        let mut state = 85;
+        let mut h = HyperLogLog::new(1 << 14);
+        sample_occurrences(&mut h, 0..20_000, 1, 0.01, &mut state);
+        sample_occurrences(&mut h, 20_000..22_000, 500, 0.01, &mut state);
+        let low = h.count_with_sampling(0.01, Occurrences { per_item: 500. });
+        let high = h.count_with_sampling(0.01, Occurrences { per_item: 1. });
+        // Neither assumption is right, and both are far off.
+        assert!(low < 0.2 * 22_000. && high > 5. * 22_000.);
+    }
+
+    #[test]
+    fn full_rate_is_the_plain_count() {
+        let mut h = HyperLogLog::new(1 << 10);
+        h.extend(0..3_000u32);
+        for model in [DistinctItems, Occurrences { per_item: 7. }] {
+            assert_eq!(h.count_with_sampling(1., model), h.count());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "sampling rate must be in (0, 1], not 0")]
+    fn rejects_a_zero_rate() {
+        HyperLogLog::new(1 << 4).count_with_sampling(0., DistinctItems);
+    }
+
+    #[test]
+    #[should_panic(expected = "sampling rate must be in (0, 1], not 1.5")]
+    fn rejects_a_rate_above_one() {
+        HyperLogLog::new(1 << 4).count_with_sampling(1.5, DistinctItems);
+    }
+
+    #[test]
+    #[should_panic(expected = "items occur at least once")]
+    fn rejects_fewer_than_one_occurrence() {
+        HyperLogLog::new(1 << 4).count_with_sampling(0.5, Occurrences { per_item: 0.5 });
+    }
+}
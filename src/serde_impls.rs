@@ -0,0 +1,538 @@
+//! `Serialize`/`Deserialize` for sketches whose hasher can be reproduced.
+//!
+//! Only sketches built with a [`StableBuildHasher`] (e.g. the default hasher
+//! or a [`SeededState`]) can be serialized: for any other hasher there is no
+//! way to record which keys were used, so a deserialized sketch could not be
+//! merged with anything. Seeded sketches record their keys.
+//!
+//! The representation is `{precision, registers, keys, backend}`, with
+//! registers written as one byte each and `backend` the code of the
+//! [`HashBackend`](::HashBackend) as in [`HyperLogLog::to_bytes`]. Self-describing
+//! formats omit `keys` when the hasher has none and `backend` when it is
+//! native. A hasher without keys other than the default records backend 3
+//! and its [`HasherId`](::HasherId) as its keys, as in the binary format.
+//!
+//! A [monotonic](HyperLogLog::set_monotonic) sketch adds its watermark: as a
+//! `watermark` field in self-describing formats, and in fixed-layout ones,
+//! whose struct has one shape, as eight more bytes after the registers, so
+//! that older readers reject it on the register count.
+//!
+//! A sketch with an [estimator](HyperLogLog::set_estimator) other than the
+//! default records its code, 1 to 3: as an `estimator` field in
+//! self-describing formats, and in fixed-layout ones in the top four bits of
+//! `backend`, which older readers reject as an unknown backend.
+//!
+//! The [number of items added](HyperLogLog::items_added), when not 0, goes
+//! in an `items_added` field in self-describing formats, and in fixed-layout
+//! ones as eight more bytes after the registers and any watermark, flagged
+//! by bit 6 of `backend`.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use encoding::{read_watermark, recorded_hasher, validate_registers, WATERMARK_LEN};
+use error::DecodeError;
+use {BuiltinEstimator, HashBackend, HyperLogLog, StableBuildHasher};
+
+const FIELDS: &[&str] = &["precision", "registers", "keys", "backend"];
+/// Every field a self-describing format may hold. Fixed-layout formats are
+/// read with [`FIELDS`], which doesn't count the watermark, so that sketches
+/// written before it was added still read.
+const ALL_FIELDS: &[&str] = &[
+    "precision",
+    "registers",
+    "keys",
+    "backend",
+    "watermark",
+    "estimator",
+    "items_added",
+];
+/// Where the estimator's code starts in the `backend` of fixed-layout
+/// formats.
+const ESTIMATOR_SHIFT: u8 = 4;
+/// Set in the `backend` of fixed-layout formats when the registers are
+/// followed by the number of items added.
+const ITEMS_FLAG: u8 = 1 << 6;
+
+struct RegisterBytes<'a>(&'a [u8]);
+
+impl<'a> Serialize for RegisterBytes<'a> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+struct RegisterBuf(Vec<u8>);
+
+impl<'de> Deserialize<'de> for RegisterBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<RegisterBuf, D::Error> {
+        struct BufVisitor;
+
+        impl<'de> Visitor<'de> for BufVisitor {
+            type Value = RegisterBuf;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a byte array of register values")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<RegisterBuf, E> {
+                Ok(RegisterBuf(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<RegisterBuf, E> {
+                Ok(RegisterBuf(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<RegisterBuf, A::Error> {
+                let mut v = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(b) = seq.next_element()? {
+                    v.push(b);
+                }
+                Ok(RegisterBuf(v))
+            }
+        }
+
+        deserializer.deserialize_bytes(BufVisitor)
+    }
+}
+
+/// The deserialized, not yet validated fields of a sketch.
+struct Fields {
+    precision: u8,
+    registers: Vec<u8>,
+    keys: Option<(u64, u64)>,
+    backend: u8,
+    watermark: Option<f64>,
+    estimator: u8,
+    items_added: u64,
+}
+
+impl Fields {
+    fn into_sketch<S: StableBuildHasher, E: de::Error>(mut self) -> Result<HyperLogLog<S>, E> {
+        let m = 1usize.checked_shl(u32::from(self.precision)).unwrap_or(0);
+        if self.watermark.is_none() && m > 0 && self.registers.len() == m + WATERMARK_LEN {
+            let trailer = self.registers.split_off(m);
+            self.watermark = Some(read_watermark(&trailer).map_err(E::custom)?);
+        }
+        if let Some(watermark) = self.watermark {
+            if !(watermark.is_finite() && watermark >= 0.) {
+                return Err(E::custom(DecodeError::InvalidWatermark));
+            }
+        }
+        validate_registers(self.precision, &self.registers).map_err(E::custom)?;
+        let backend = HashBackend::from_code(self.backend)
+            .ok_or_else(|| E::custom(format_args!("unknown hash backend {}", self.backend)))?;
+        let estimator = BuiltinEstimator::from_code(self.estimator)
+            .ok_or_else(|| E::custom(format_args!("unknown estimator {}", self.estimator)))?;
+        let own_keys = if backend == HashBackend::Custom {
+            None
+        } else {
+            self.keys
+        };
+        let build_hasher = S::from_keys(own_keys).ok_or_else(|| {
+            E::custom(match own_keys {
+                Some(_) => {
+                    "sketch was built with a keyed hasher, but the target hasher has no keys"
+                }
+                None => "sketch has no recorded hasher keys, but the target hasher needs them",
+            })
+        })?;
+        let (target, keys) = recorded_hasher(&build_hasher);
+        if backend != target {
+            return Err(E::custom(format_args!(
+                "sketch was built with the {:?} hash backend, but the target hasher is {:?}",
+                backend, target
+            )));
+        }
+        if self.keys != keys {
+            return Err(E::custom(
+                "sketch was built with a different custom hasher from the target's",
+            ));
+        }
+        let mut h = HyperLogLog::from_dense(self.precision, self.registers, build_hasher);
+        h.watermark = self.watermark;
+        h.estimator = estimator;
+        h.items_added = self.items_added;
+        Ok(h)
+    }
+}
+
+struct FieldsVisitor;
+
+impl<'de> Visitor<'de> for FieldsVisitor {
+    type Value = Fields;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a HyperLogLog sketch")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Fields, A::Error> {
+        let precision = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let registers: RegisterBuf = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        let keys = seq.next_element()?.unwrap_or(None);
+        let backend: u8 = seq.next_element()?.unwrap_or(0);
+        let mut registers = registers.0;
+        let mut items_added = 0;
+        if backend & ITEMS_FLAG != 0 {
+            let at = registers
+                .len()
+                .checked_sub(8)
+                .ok_or_else(|| de::Error::invalid_length(registers.len(), &self))?;
+            let mut items = [0; 8];
+            items.copy_from_slice(&registers[at..]);
+            items_added = u64::from_le_bytes(items);
+            registers.truncate(at);
+        }
+        Ok(Fields {
+            precision,
+            registers,
+            keys,
+            backend: backend & ((1 << ESTIMATOR_SHIFT) - 1),
+            watermark: None,
+            estimator: (backend & !ITEMS_FLAG) >> ESTIMATOR_SHIFT,
+            items_added,
+        })
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Fields, A::Error> {
+        let mut precision = None;
+        let mut registers: Option<RegisterBuf> = None;
+        let mut keys = None;
+        let mut backend = 0;
+        let mut watermark = None;
+        let mut estimator = 0;
+        let mut items_added = 0;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "precision" => precision = Some(map.next_value()?),
+                "registers" => registers = Some(map.next_value()?),
+                "keys" => keys = map.next_value()?,
+                "backend" => backend = map.next_value()?,
+                "watermark" => watermark = map.next_value()?,
+                "estimator" => estimator = map.next_value()?,
+                "items_added" => items_added = map.next_value()?,
+                other => return Err(de::Error::unknown_field(other, ALL_FIELDS)),
+            }
+        }
+        Ok(Fields {
+            precision: precision.ok_or_else(|| de::Error::missing_field("precision"))?,
+            registers: registers
+                .ok_or_else(|| de::Error::missing_field("registers"))?
+                .0,
+            keys,
+            backend,
+            watermark,
+            estimator,
+            items_added,
+        })
+    }
+}
+
+impl<S: StableBuildHasher> Serialize for HyperLogLog<S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        let (backend, keys) = recorded_hasher(&self.build_hasher);
+        // Self-describing formats omit absent keys and the native backend;
+        // fixed-layout formats like bincode always carry both fields so the
+        // struct has one shape.
+        let fixed = !serializer.is_human_readable();
+        let write_keys = keys.is_some() || fixed;
+        let write_backend = backend != HashBackend::Native || fixed;
+        let write_watermark = self.watermark.is_some() && !fixed;
+        let estimator = self.estimator.code();
+        let write_estimator = estimator != 0 && !fixed;
+        let write_items = self.items_added != 0 && !fixed;
+        let len = 2
+            + usize::from(write_keys)
+            + usize::from(write_backend)
+            + usize::from(write_watermark)
+            + usize::from(write_estimator)
+            + usize::from(write_items);
+        let mut registers = self.dense_registers();
+        let mut flags = estimator << ESTIMATOR_SHIFT;
+        if fixed {
+            self.push_watermark(&mut registers);
+            if self.items_added != 0 {
+                registers.extend_from_slice(&self.items_added.to_le_bytes());
+                flags |= ITEMS_FLAG;
+            }
+        }
+        let mut st = serializer.serialize_struct("HyperLogLog", len)?;
+        st.serialize_field("precision", &self.precision)?;
+        st.serialize_field("registers", &RegisterBytes(&registers))?;
+        if write_keys {
+            st.serialize_field("keys", &keys)?;
+        } else {
+            st.skip_field("keys")?;
+        }
+        if write_backend {
+            let backend = if fixed {
+                backend.code() | flags
+            } else {
+                backend.code()
+            };
+            st.serialize_field("backend", &backend)?;
+        } else {
+            st.skip_field("backend")?;
+        }
+        if write_watermark {
+            st.serialize_field("watermark", &self.watermark)?;
+        } else {
+            st.skip_field("watermark")?;
+        }
+        if write_estimator {
+            st.serialize_field("estimator", &estimator)?;
+        } else {
+            st.skip_field("estimator")?;
+        }
+        if write_items {
+            st.serialize_field("items_added", &self.items_added)?;
+        } else {
+            st.skip_field("items_added")?;
+        }
+        st.end()
+    }
+}
+
+impl<'de, S: StableBuildHasher> Deserialize<'de> for HyperLogLog<S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer
+            .deserialize_struct("HyperLogLog", FIELDS, FieldsVisitor)?
+            .into_sketch()
+    }
+}
+#[cfg(test)]
+mod tests {
+    extern crate bincode;
+    extern crate serde_json;
+
+    use BuiltinEstimator;
+    use HyperLogLog;
+    use SeededState;
+
+    fn populated(n: u64) -> HyperLogLog {
+        let mut h = HyperLogLog::new(1 << 8);
+        for i in 0..n {
+            h.add(i);
+        }
+        h
+    }
+
+    #[test]
+    fn json_round_trip() {
+        for &n in &[0, 10, 10_000] {
+            let h = populated(n);
+            let json = serde_json::to_string(&h).unwrap();
+            let back: HyperLogLog = serde_json::from_str(&json).unwrap();
+            assert_eq!(
+                back.registers().collect::<Vec<_>>(),
+                h.registers().collect::<Vec<_>>()
+            );
+            assert_eq!(back.count(), h.count());
+        }
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let h = populated(10_000);
+        let bytes = bincode::serialize(&h).unwrap();
+        // One byte per register, the number of items added and a small
+        // header.
+        assert!(bytes.len() < 256 + 8 + 16, "{} bytes", bytes.len());
+        let back: HyperLogLog = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(
+            back.registers().collect::<Vec<_>>(),
+            h.registers().collect::<Vec<_>>()
+        );
+        assert_eq!(back.count(), h.count());
+    }
+
+    #[test]
+    fn seeded_round_trip_records_keys() {
+        let mut h = HyperLogLog::new_seeded(1 << 6, 99);
+        for i in 0..500u64 {
+            h.add(i);
+        }
+        let json = serde_json::to_string(&h).unwrap();
+        assert!(json.contains("keys"));
+
+        let back: HyperLogLog<SeededState> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.build_hasher, h.build_hasher);
+        assert_eq!(
+            back.registers().collect::<Vec<_>>(),
+            h.registers().collect::<Vec<_>>()
+        );
+        assert_eq!(back.count(), h.count());
+
+        let bytes = bincode::serialize(&h).unwrap();
+        let back: HyperLogLog<SeededState> = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back.build_hasher, h.build_hasher);
+
+        // A seeded sketch must not silently lose its keys.
+        assert!(serde_json::from_str::<HyperLogLog>(&json).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_sketches() {
+        let bad = [
+            // register count doesn't match precision
+            r#"{"precision":4,"registers":[0,0,0]}"#,
+            // precision out of range
+            r#"{"precision":0,"registers":[0]}"#,
+            // rank larger than possible for a 64-bit hash at p=4
+            r#"{"precision":4,"registers":[0,62,0,0,0,0,0,0,0,0,0,0,0,0,0,0]}"#,
+            r#"{"registers":[0,0]}"#,
+            // another hash backend, and one that doesn't exist
+            r#"{"precision":4,"registers":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"backend":1}"#,
+            r#"{"precision":4,"registers":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"backend":9}"#,
+        ];
+        for json in &bad {
+            assert!(
+                serde_json::from_str::<HyperLogLog>(json).is_err(),
+                "{}",
+                json
+            );
+        }
+        let ok = r#"{"precision":4,"registers":[0,61,1,0,0,0,0,0,0,0,0,0,0,0,0,0]}"#;
+        assert!(serde_json::from_str::<HyperLogLog>(ok).is_ok());
+    }
+
+    #[test]
+    fn round_trips_the_watermark() {
+        let plain = populated(300);
+        let mut h = plain.clone();
+        h.set_monotonic(true);
+        h.watermark = Some(h.count() + 2.);
+
+        let json = serde_json::to_string(&h).unwrap();
+        assert!(json.contains("watermark"));
+        assert!(!serde_json::to_string(&plain).unwrap().contains("watermark"));
+        let back: HyperLogLog = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, h);
+        assert_eq!(back.count(), h.count());
+
+        // Fixed-layout formats add the watermark to the registers.
+        let bytes = bincode::serialize(&h).unwrap();
+        assert_eq!(bytes.len(), bincode::serialize(&plain).unwrap().len() + 8);
+        let back: HyperLogLog = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(back, h);
+
+        let negative =
+            r#"{"precision":4,"registers":[0,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"watermark":-1.0}"#;
+        assert!(serde_json::from_str::<HyperLogLog>(negative).is_err());
+    }
+
+    #[test]
+    fn round_trips_the_estimator() {
+        let plain = populated(300);
+        assert!(!serde_json::to_string(&plain).unwrap().contains("estimator"));
+        for &estimator in &[BuiltinEstimator::Classic, BuiltinEstimator::Ertl] {
+            let mut h = plain.clone();
+            h.set_estimator(estimator);
+            let json = serde_json::to_string(&h).unwrap();
+            assert!(json.contains(r#""estimator":"#), "{}", json);
+            let back: HyperLogLog = serde_json::from_str(&json).unwrap();
+            assert_eq!((back.estimator(), back.count()), (estimator, h.count()));
+
+            // Fixed-layout formats fold it into the backend, at no cost.
+            let bytes = bincode::serialize(&h).unwrap();
+            assert_eq!(bytes.len(), bincode::serialize(&plain).unwrap().len());
+            let back: HyperLogLog = bincode::deserialize(&bytes).unwrap();
+            assert_eq!((back.estimator(), back.count()), (estimator, h.count()));
+        }
+
+        let unknown =
+            r#"{"precision":4,"registers":[0,1,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"estimator":4}"#;
+        assert!(serde_json::from_str::<HyperLogLog>(unknown).is_err());
+    }
+
+    #[test]
+    fn round_trips_the_items_count() {
+        let mut h = populated(300);
+        h.add(0);
+        let json = serde_json::to_string(&h).unwrap();
+        assert!(json.contains(r#""items_added":301"#), "{}", json);
+        let back: HyperLogLog = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.items_added(), 301);
+        let empty = HyperLogLog::new(1 << 8);
+        assert!(!serde_json::to_string(&empty)
+            .unwrap()
+            .contains("items_added"));
+
+        // Fixed-layout formats append it to the registers, after the
+        // watermark if there is one.
+        let bytes = bincode::serialize(&h).unwrap();
+        assert_eq!(bytes.len(), bincode::serialize(&empty).unwrap().len() + 8);
+        let back: HyperLogLog = bincode::deserialize(&bytes).unwrap();
+        assert_eq!((back.items_added(), back.count()), (301, h.count()));
+        h.set_monotonic(true);
+        h.set_estimator(BuiltinEstimator::Ertl);
+        let back: HyperLogLog = bincode::deserialize(&bincode::serialize(&h).unwrap()).unwrap();
+        assert_eq!(back.items_added(), 301);
+        assert_eq!(back.estimator(), BuiltinEstimator::Ertl);
+        assert_eq!(back.watermark, h.watermark);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn records_custom_hashers_by_id() {
+        use core::hash::BuildHasherDefault;
+        use std::collections::hash_map::DefaultHasher;
+
+        // The standard library's hasher isn't the crate's default.
+        type Std = BuildHasherDefault<DefaultHasher>;
+        let mut h = HyperLogLog::with_hasher(1 << 4, Std::default());
+        h.add(1u64);
+        let json = serde_json::to_string(&h).unwrap();
+        assert!(json.contains(r#""backend":3"#), "{}", json);
+        let back: HyperLogLog<Std> = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.hasher_id(), h.hasher_id());
+        assert!(back.registers().eq(h.registers()));
+        let err = serde_json::from_str::<HyperLogLog>(&json).unwrap_err();
+        assert!(err.to_string().contains("Custom"), "{}", err);
+        let bytes = bincode::serialize(&h).unwrap();
+        assert!(bincode::deserialize::<HyperLogLog<Std>>(&bytes).is_ok());
+        assert!(bincode::deserialize::<HyperLogLog>(&bytes).is_err());
+
+        let default = serde_json::to_string(&HyperLogLog::new(1 << 4)).unwrap();
+        assert!(serde_json::from_str::<HyperLogLog<Std>>(&default).is_err());
+        let other = concat!(
+            r#"{"precision":4,"registers":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"#,
+            r#""keys":[1,0],"backend":3}"#
+        );
+        let err = serde_json::from_str::<HyperLogLog<Std>>(other).unwrap_err();
+        assert!(
+            err.to_string().contains("different custom hasher"),
+            "{}",
+            err
+        );
+    }
+
+    #[cfg(feature = "xxhash")]
+    #[test]
+    fn records_hash_backend() {
+        use Xxh3State;
+
+        let mut h = HyperLogLog::with_hasher(1 << 6, Xxh3State::new());
+        h.add_bytes("foo");
+        let json = serde_json::to_string(&h).unwrap();
+        assert!(json.contains(r#""backend":1"#), "{}", json);
+        assert!(!json.contains("keys"));
+        let back: HyperLogLog<Xxh3State> = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            back.registers().collect::<Vec<_>>(),
+            h.registers().collect::<Vec<_>>()
+        );
+        let bytes = bincode::serialize(&h).unwrap();
+        assert!(bincode::deserialize::<HyperLogLog<Xxh3State>>(&bytes).is_ok());
+        assert!(bincode::deserialize::<HyperLogLog>(&bytes).is_err());
+        assert!(serde_json::from_str::<HyperLogLog>(&json).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! Sketches of the standard library's exact sets, for code moving from
+//! counting distinct items in a set to estimating them.
+//!
+//! Every conversion adds the set's items through
+//! [`add_all`](HyperLogLog::add_all), the batch path, so a sketch of a set is
+//! the one adding its items one at a time would have built, only faster.
+
+use alloc::collections::BTreeSet;
+use core::hash::{BuildHasher, Hash};
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+
+use error::BuildError;
+use HyperLogLog;
+
+impl HyperLogLog {
+    /// A sketch of the items in `set` with `2^precision` registers, failing
+    /// like [`HyperLogLogBuilder::build`](::HyperLogLogBuilder::build) if the
+    /// precision is out of range. `HyperLogLog::from(&set)` uses the default
+    /// 2^14 registers.
+    #[cfg(feature = "std")]
+    pub fn from_set_with_precision<T: Hash, R>(
+        set: &HashSet<T, R>,
+        precision: u8,
+    ) -> Result<HyperLogLog, BuildError> {
+        let mut h = HyperLogLog::builder().precision(precision).build()?;
+        h.extend_from_set(set);
+        Ok(h)
+    }
+
+    /// Like [`from_set_with_precision`](HyperLogLog::from_set_with_precision),
+    /// for a `BTreeSet`.
+    pub fn from_btree_set_with_precision<T: Hash>(
+        set: &BTreeSet<T>,
+        precision: u8,
+    ) -> Result<HyperLogLog, BuildError> {
+        let mut h = HyperLogLog::builder().precision(precision).build()?;
+        h.extend_from_btree_set(set);
+        Ok(h)
+    }
+}
+
+impl<S: BuildHasher> HyperLogLog<S> {
+    /// Adds every item in `set`, returning how many raised a register, as
+    /// [`add_all`](HyperLogLog::add_all) does.
+    #[cfg(feature = "std")]
+    pub fn extend_from_set<T: Hash, R>(&mut self, set: &HashSet<T, R>) -> usize {
+        self.add_all(set)
+    }
+
+    /// Adds every item in `set`, returning how many raised a register, as
+    /// [`add_all`](HyperLogLog::add_all) does.
+    pub fn extend_from_btree_set<T: Hash>(&mut self, set: &BTreeSet<T>) -> usize {
+        self.add_all(set)
+    }
+}
+
+/// A default sketch, with 2^14 registers, of the items in the set.
+#[cfg(feature = "std")]
+impl<'a, T: Hash, R, S: BuildHasher + Default> From<&'a HashSet<T, R>> for HyperLogLog<S> {
+    fn from(set: &'a HashSet<T, R>) -> HyperLogLog<S> {
+        let mut h = HyperLogLog::default();
+        h.extend_from_set(set);
+        h
+    }
+}
+
+/// A default sketch, with 2^14 registers, of the items in the set.
+impl<'a, T: Hash, S: BuildHasher + Default> From<&'a BTreeSet<T>> for HyperLogLog<S> {
+    fn from(set: &'a BTreeSet<T>) -> HyperLogLog<S> {
+        let mut h = HyperLogLog::default();
+        h.extend_from_btree_set(set);
+        h
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use alloc::collections::BTreeSet;
+    use std::collections::HashSet;
+
+    use error::BuildError;
+    use simulation::splitmix64;
+    use HyperLogLog;
+
+    fn assert_near(h: &HyperLogLog, len: usize) {
+        let error = (h.count() - len as f64).abs() / len as f64;
+        assert!(
+            error < 3. * h.error_estimate(),
+            "{} items: {}",
+            len,
+            h.count()
+        );
+    }
+
+    #[test]
+    fn sketches_of_sets() {
+        let mut state = 63;
+        for &n in &[100, 10_000, 1_000_000] {
+            let items: Vec<u64> = (0..n).map(|_| splitmix64(&mut state)).collect();
+            let hash_set: HashSet<u64> = items.iter().copied().collect();
+            let btree_set: BTreeSet<u64> = items.iter().copied().collect();
+            let mut one_by_one = HyperLogLog::new(1 << 14);
+            items.iter().for_each(|i| one_by_one.add(i));
+
+            let from_hash = HyperLogLog::from(&hash_set);
+            let from_btree = HyperLogLog::from(&btree_set);
+            assert_near(&from_hash, hash_set.len());
+            assert_eq!(from_hash, one_by_one);
+            assert_eq!(from_btree, one_by_one);
+        }
+    }
+
+    #[test]
+    fn explicit_precision() {
+        let set: HashSet<String> = (0..5_000).map(|i| format!("user-{}", i)).collect();
+        let h = HyperLogLog::from_set_with_precision(&set, 10).unwrap();
+        assert_eq!(h.precision(), 10);
+        assert_near(&h, set.len());
+
+        let sorted: BTreeSet<&str> = set.iter().map(String::as_str).collect();
+        let b = HyperLogLog::from_btree_set_with_precision(&sorted, 10).unwrap();
+        assert_eq!(b, h);
+        assert_eq!(
+            HyperLogLog::from_set_with_precision(&set, 2),
+            Err(BuildError::InvalidPrecision(2))
+        );
+
+        let mut more = HyperLogLog::new(1 << 10);
+        more.extend_from_set(&set);
+        assert_eq!(more.extend_from_btree_set(&sorted), 0);
+        assert_eq!(more, h);
+    }
+}
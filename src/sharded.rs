@@ -0,0 +1,166 @@
+//! One plain sketch per writer thread, merged when read.
+//!
+//! Writers share a [`ShardedHyperLogLog`] by reference. Each thread is
+//! given a number the first time it adds to any sharded sketch, and always
+//! adds to the shard that number picks, so with no more threads than shards
+//! a writer never waits on another. Items are hashed before the shard's lock
+//! is taken.
+
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::vec::Vec;
+
+use {DefaultBuildHasher, HyperLogLog, SeededState};
+
+static NEXT_THREAD: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// This thread's number, in the order threads first add to a sharded
+    /// sketch.
+    static THREAD: usize = NEXT_THREAD.fetch_add(1, Ordering::Relaxed);
+}
+
+/// A sketch for many writer threads: `N` [`HyperLogLog`]s of the same size
+/// and hasher, each behind its own lock, which
+/// [`snapshot`](ShardedHyperLogLog::snapshot) merges into one.
+pub struct ShardedHyperLogLog<S = DefaultBuildHasher> {
+    shards: Vec<Mutex<HyperLogLog<S>>>,
+    build_hasher: S,
+}
+
+impl ShardedHyperLogLog {
+    /// Creates a sketch with `m` registers per shard and one shard per
+    /// thread the machine can run in parallel.
+    ///
+    /// Panics unless `m` is a power of two from 16 to 2^24, as for
+    /// [`HyperLogLog::new`].
+    pub fn new(m: usize) -> ShardedHyperLogLog {
+        ShardedHyperLogLog::with_hasher(m, DefaultBuildHasher::default())
+    }
+}
+
+impl ShardedHyperLogLog<SeededState> {
+    /// Like [`ShardedHyperLogLog::new`], hashing as
+    /// [`HyperLogLog::new_seeded`] does.
+    pub fn new_seeded(m: usize, seed: u64) -> ShardedHyperLogLog<SeededState> {
+        ShardedHyperLogLog::with_hasher(m, SeededState::new(seed))
+    }
+}
+
+impl<S: BuildHasher + Clone> ShardedHyperLogLog<S> {
+    /// Creates a sketch with `m` registers per shard, one shard per thread
+    /// the machine can run in parallel, hashing items with `build_hasher`.
+    pub fn with_hasher(m: usize, build_hasher: S) -> ShardedHyperLogLog<S> {
+        let shards = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        ShardedHyperLogLog::with_shards(m, shards, build_hasher)
+    }
+
+    /// Creates a sketch of `shards` shards with `m` registers each.
+    ///
+    /// Panics if `shards` is 0, or if `m` is unusable.
+    pub fn with_shards(m: usize, shards: usize, build_hasher: S) -> ShardedHyperLogLog<S> {
+        assert!(shards > 0, "a sharded sketch needs at least one shard");
+        let shards = (0..shards)
+            .map(|_| Mutex::new(HyperLogLog::with_hasher(m, build_hasher.clone())))
+            .collect();
+        ShardedHyperLogLog {
+            shards,
+            build_hasher,
+        }
+    }
+
+    /// Adds an item to the calling thread's shard.
+    pub fn add<H: Hash>(&self, item: H) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h);
+    }
+
+    /// Adds an item by its precomputed 64-bit hash; see
+    /// [`HyperLogLog::add_hashed`].
+    pub fn add_hashed(&self, hash: u64) {
+        let shard = THREAD.with(|&thread| thread % self.shards.len());
+        lock(&self.shards[shard]).add_hashed(hash);
+    }
+
+    /// Merges the shards into one sketch, locking one shard at a time.
+    ///
+    /// Additions made while the snapshot is taken may or may not be in it,
+    /// depending on whether their shard had been read yet.
+    pub fn snapshot(&self) -> HyperLogLog<S> {
+        let mut merged = lock(&self.shards[0]).clone();
+        for shard in &self.shards[1..] {
+            // The shards were all built alike.
+            merged.merge(&lock(shard)).unwrap();
+        }
+        merged
+    }
+
+    /// Estimates the cardinality of everything added, from a
+    /// [`snapshot`](ShardedHyperLogLog::snapshot).
+    pub fn count(&self) -> f64 {
+        self.snapshot().count()
+    }
+}
+
+impl<S> ShardedHyperLogLog<S> {
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn precision(&self) -> u8 {
+        lock(&self.shards[0]).precision()
+    }
+}
+
+/// Locks a shard. A writer that panicked while holding the lock left it a
+/// valid sketch, so poisoning is ignored.
+fn lock<S>(shard: &Mutex<HyperLogLog<S>>) -> MutexGuard<'_, HyperLogLog<S>> {
+    shard.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl<S> fmt::Debug for ShardedHyperLogLog<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ShardedHyperLogLog")
+            .field("shards", &self.shards.len())
+            .field("precision", &self.precision())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::ShardedHyperLogLog;
+    use {HyperLogLog, SeededState};
+
+    #[test]
+    fn threads_match_a_single_sketch() {
+        let sharded = ShardedHyperLogLog::with_shards(1 << 12, 4, SeededState::new(74));
+        // More threads than shards, so some share one.
+        thread::scope(|scope| {
+            for t in 0..6u64 {
+                let sharded = &sharded;
+                scope.spawn(move || (t * 10_000..t * 10_000 + 25_000).for_each(|i| sharded.add(i)));
+            }
+        });
+        let mut reference = HyperLogLog::new_seeded(1 << 12, 74);
+        (0..75_000u64).for_each(|i| reference.add(i));
+
+        assert_eq!(sharded.snapshot(), reference);
+        assert_eq!(sharded.count(), reference.count());
+    }
+
+    #[test]
+    fn defaults_to_available_parallelism() {
+        let sharded = ShardedHyperLogLog::new(1 << 8);
+        let cores = thread::available_parallelism().map_or(1, |n| n.get());
+        assert_eq!(sharded.num_shards(), cores);
+        assert_eq!(sharded.precision(), 8);
+        assert_eq!(sharded.count(), 0.);
+    }
+}
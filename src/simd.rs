@@ -0,0 +1,267 @@
+//! Wide operations over dense byte registers.
+//!
+//! Merging is a register-wise unsigned maximum, done 16 or 32 bytes at a time
+//! with SSE2 or AVX2 on x86_64 (AVX2 is detected at run time when `std` is
+//! available) and with NEON on aarch64. Other targets use the scalar loop,
+//! which every wide path also uses for its tail.
+//!
+//! Estimates are computed from a histogram of register values rather than by
+//! summing `2^-register` per register, which turns `m` evaluations of `exp2`
+//! into `m` integer increments. The counts are exact, so the result doesn't
+//! depend on how the histogram was gathered.
+
+/// Raises each byte of `dst` to at least the matching byte of `src`,
+/// returning whether any byte changed.
+pub(crate) fn max_into(dst: &mut [u8], src: &[u8]) -> bool {
+    assert_eq!(dst.len(), src.len());
+    imp::max_into(dst, src)
+}
+
+fn max_into_scalar(dst: &mut [u8], src: &[u8]) -> bool {
+    let mut changed = false;
+    for (d, &s) in dst.iter_mut().zip(src) {
+        if *d < s {
+            *d = s;
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// Counts the registers holding each value.
+///
+/// Four interleaved tables let runs of equal values update different
+/// counters, instead of each increment waiting on the previous one.
+pub(crate) fn histogram(registers: &[u8]) -> [u64; 65] {
+    let mut tables = [[0u64; 65]; 4];
+    let mut chunks = registers.chunks_exact(4);
+    for chunk in &mut chunks {
+        tables[0][usize::from(chunk[0])] += 1;
+        tables[1][usize::from(chunk[1])] += 1;
+        tables[2][usize::from(chunk[2])] += 1;
+        tables[3][usize::from(chunk[3])] += 1;
+    }
+    for &r in chunks.remainder() {
+        tables[0][usize::from(r)] += 1;
+    }
+    let mut counts = tables[0];
+    for table in &tables[1..] {
+        for (c, &t) in counts.iter_mut().zip(table.iter()) {
+            *c += t;
+        }
+    }
+    counts
+}
+
+#[cfg(target_arch = "x86_64")]
+mod imp {
+    use core::arch::x86_64::*;
+
+    use super::max_into_scalar;
+
+    pub(super) fn max_into(dst: &mut [u8], src: &[u8]) -> bool {
+        #[cfg(any(feature = "std", test))]
+        {
+            if is_x86_feature_detected!("avx2") {
+                // SAFETY: AVX2 support was just checked.
+                return unsafe { max_into_avx2(dst, src) };
+            }
+        }
+        // SAFETY: SSE2 is part of the x86_64 baseline.
+        unsafe { max_into_sse2(dst, src) }
+    }
+
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn max_into_sse2(dst: &mut [u8], src: &[u8]) -> bool {
+        let mut changed = _mm_setzero_si128();
+        let mut d = dst.chunks_exact_mut(16);
+        let mut s = src.chunks_exact(16);
+        for (d, s) in (&mut d).zip(&mut s) {
+            let a = _mm_loadu_si128(d.as_ptr() as *const __m128i);
+            let b = _mm_loadu_si128(s.as_ptr() as *const __m128i);
+            let m = _mm_max_epu8(a, b);
+            changed = _mm_or_si128(changed, _mm_xor_si128(m, a));
+            _mm_storeu_si128(d.as_mut_ptr() as *mut __m128i, m);
+        }
+        let tail = max_into_scalar(d.into_remainder(), s.remainder());
+        let unchanged = _mm_movemask_epi8(_mm_cmpeq_epi8(changed, _mm_setzero_si128())) == 0xffff;
+        !unchanged || tail
+    }
+
+    #[cfg(any(feature = "std", test))]
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn max_into_avx2(dst: &mut [u8], src: &[u8]) -> bool {
+        let mut changed = _mm256_setzero_si256();
+        let mut d = dst.chunks_exact_mut(32);
+        let mut s = src.chunks_exact(32);
+        for (d, s) in (&mut d).zip(&mut s) {
+            let a = _mm256_loadu_si256(d.as_ptr() as *const __m256i);
+            let b = _mm256_loadu_si256(s.as_ptr() as *const __m256i);
+            let m = _mm256_max_epu8(a, b);
+            changed = _mm256_or_si256(changed, _mm256_xor_si256(m, a));
+            _mm256_storeu_si256(d.as_mut_ptr() as *mut __m256i, m);
+        }
+        let tail = max_into_scalar(d.into_remainder(), s.remainder());
+        _mm256_testz_si256(changed, changed) == 0 || tail
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod imp {
+    use core::arch::aarch64::*;
+
+    use super::max_into_scalar;
+
+    pub(super) fn max_into(dst: &mut [u8], src: &[u8]) -> bool {
+        // SAFETY: NEON is part of the aarch64 baseline.
+        unsafe { max_into_neon(dst, src) }
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn max_into_neon(dst: &mut [u8], src: &[u8]) -> bool {
+        let mut changed = vdupq_n_u8(0);
+        let mut d = dst.chunks_exact_mut(16);
+        let mut s = src.chunks_exact(16);
+        for (d, s) in (&mut d).zip(&mut s) {
+            let a = vld1q_u8(d.as_ptr());
+            let m = vmaxq_u8(a, vld1q_u8(s.as_ptr()));
+            changed = vorrq_u8(changed, veorq_u8(m, a));
+            vst1q_u8(d.as_mut_ptr(), m);
+        }
+        let tail = max_into_scalar(d.into_remainder(), s.remainder());
+        vmaxvq_u8(changed) != 0 || tail
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod imp {
+    pub(super) use super::max_into_scalar as max_into;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{histogram, max_into, max_into_scalar};
+    use core::cmp;
+
+    use simulation::splitmix64;
+    use {max_rank, sum_and_zeros};
+
+    /// Random register arrays of awkward lengths, with many equal bytes so
+    /// that both "changed" and "unchanged" merges occur.
+    fn random_registers(len: usize, rng: &mut u64) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                let x = splitmix64(rng);
+                if x & 3 == 0 {
+                    (x >> 8) as u8 % 62
+                } else {
+                    3
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wide_max_matches_scalar() {
+        let mut rng = 1;
+        for &len in &[0, 1, 15, 16, 17, 31, 32, 33, 100, 1 << 12] {
+            for _ in 0..20 {
+                let a = random_registers(len, &mut rng);
+                let b = random_registers(len, &mut rng);
+                let (mut wide, mut scalar) = (a.clone(), a.clone());
+                let changed = max_into(&mut wide, &b);
+                assert_eq!(changed, max_into_scalar(&mut scalar, &b), "len {}", len);
+                assert_eq!(wide, scalar);
+                assert!(!max_into(&mut wide, &b));
+            }
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn every_x86_path_matches_scalar() {
+        use super::imp::{max_into_avx2, max_into_sse2};
+
+        let mut rng = 2;
+        for &len in &[5, 48, 1000] {
+            let a = random_registers(len, &mut rng);
+            let b = random_registers(len, &mut rng);
+            let mut scalar = a.clone();
+            let changed = max_into_scalar(&mut scalar, &b);
+
+            let mut sse2 = a.clone();
+            assert_eq!(unsafe { max_into_sse2(&mut sse2, &b) }, changed);
+            assert_eq!(sse2, scalar);
+            if is_x86_feature_detected!("avx2") {
+                let mut avx2 = a.clone();
+                assert_eq!(unsafe { max_into_avx2(&mut avx2, &b) }, changed);
+                assert_eq!(avx2, scalar);
+            }
+        }
+    }
+
+    #[test]
+    fn histogram_matches_naive_count() {
+        let mut rng = 3;
+        for &len in &[0, 3, 4, 5, 1 << 10] {
+            let registers = random_registers(len, &mut rng);
+            let mut expected = [0u64; 65];
+            for &r in &registers {
+                expected[usize::from(r)] += 1;
+            }
+            assert_eq!(histogram(&registers)[..], expected[..]);
+        }
+    }
+
+    #[test]
+    fn histogram_sum_is_exact() {
+        let mut rng = 73;
+        for p in 4..=16u8 {
+            let cap = cmp::min(53 - p, max_rank(p));
+            for trial in 0..6 {
+                let registers: Vec<u8> = (0..1usize << p)
+                    .map(|_| {
+                        let x = splitmix64(&mut rng);
+                        let rank = if trial % 2 == 1 {
+                            x as u8 % 64
+                        } else if x & 3 == 0 {
+                            0
+                        } else {
+                            // Geometric, as hashing gives.
+                            (x | 3).leading_zeros() as u8 + 1
+                        };
+                        cmp::min(rank, cap)
+                    })
+                    .collect();
+                let naive: f64 = registers.iter().map(|&r| 2f64.powf(-f64::from(r))).sum();
+                let (_, sum, _) = sum_and_zeros(&histogram(&registers));
+                assert_eq!(
+                    sum.to_bits(),
+                    naive.to_bits(),
+                    "p {}: {} vs {}",
+                    p,
+                    sum,
+                    naive
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn histogram_sum_matches_per_register_sum() {
+        let mut rng = 4;
+        for _ in 0..20 {
+            let registers = random_registers(1 << 10, &mut rng);
+            let direct: f64 = registers.iter().map(|&r| (-f64::from(r)).exp2()).sum();
+            let (m, sum, zeros) = sum_and_zeros(&histogram(&registers));
+            assert_eq!(m, 1 << 10);
+            assert_eq!(zeros, registers.iter().filter(|&&r| r == 0).count());
+            assert!(
+                (sum - direct).abs() <= direct * 1e-12,
+                "{} vs {}",
+                sum,
+                direct
+            );
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Simulated sketches fed ideal random hashes, for fitting and checking the
+//! estimators' empirical constants without going through a real hasher.
+
+pub(crate) use testing::splitmix64;
+use {alpha, index_and_rank};
+
+/// The registers of one simulated sketch, with the estimator's inputs kept
+/// up to date incrementally.
+pub(crate) struct Sim {
+    p: u8,
+    registers: Vec<u8>,
+    powers: [f64; 66],
+    /// The sum of `2^-register` over all registers.
+    pub(crate) sum: f64,
+    /// The number of registers that are still zero.
+    pub(crate) zeros: usize,
+    /// The number of distinct hashes added so far.
+    pub(crate) n: usize,
+}
+
+impl Sim {
+    pub(crate) fn new(p: u8) -> Sim {
+        let m = 1usize << p;
+        let mut powers = [0.; 66];
+        for (r, power) in powers.iter_mut().enumerate() {
+            *power = 2f64.powi(-(r as i32));
+        }
+        Sim {
+            p,
+            registers: vec![0; m],
+            powers,
+            sum: m as f64,
+            zeros: m,
+            n: 0,
+        }
+    }
+
+    /// Adds random hashes drawn from `rng` until `n` have been added.
+    pub(crate) fn advance_to(&mut self, n: usize, rng: &mut u64) {
+        while self.n < n {
+            let (i, r) = index_and_rank(splitmix64(rng), self.p);
+            let old = self.registers[i];
+            if old < r {
+                self.sum += self.powers[usize::from(r)] - self.powers[usize::from(old)];
+                if old == 0 {
+                    self.zeros -= 1;
+                }
+                self.registers[i] = r;
+            }
+            self.n += 1;
+        }
+    }
+
+    /// The uncorrected HyperLogLog estimate.
+    pub(crate) fn raw(&self) -> f64 {
+        let m = self.registers.len();
+        alpha(m) * (m * m) as f64 / self.sum
+    }
+}
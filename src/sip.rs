@@ -0,0 +1,201 @@
+//! A keyed SipHash-2-4 implementation whose output depends only on the keys and
+//! the bytes written, so it is stable across processes, Rust versions and
+//! platforms.
+
+use core::hash::{BuildHasher, Hasher};
+
+use mix64;
+
+/// SipHash-2-4 with explicit keys.
+///
+/// Integer writes are encoded little-endian and `usize` is widened to `u64`,
+/// so the same value hashes identically on every platform.
+#[derive(Debug, Clone)]
+pub struct SipHasher24 {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    tail: u64,
+    ntail: usize,
+    length: usize,
+}
+
+macro_rules! sipround {
+    ($s:expr) => {{
+        $s.v0 = $s.v0.wrapping_add($s.v1);
+        $s.v1 = $s.v1.rotate_left(13);
+        $s.v1 ^= $s.v0;
+        $s.v0 = $s.v0.rotate_left(32);
+        $s.v2 = $s.v2.wrapping_add($s.v3);
+        $s.v3 = $s.v3.rotate_left(16);
+        $s.v3 ^= $s.v2;
+        $s.v0 = $s.v0.wrapping_add($s.v3);
+        $s.v3 = $s.v3.rotate_left(21);
+        $s.v3 ^= $s.v0;
+        $s.v2 = $s.v2.wrapping_add($s.v1);
+        $s.v1 = $s.v1.rotate_left(17);
+        $s.v1 ^= $s.v2;
+        $s.v2 = $s.v2.rotate_left(32);
+    }};
+}
+
+impl SipHasher24 {
+    pub fn new_with_keys(k0: u64, k1: u64) -> SipHasher24 {
+        SipHasher24 {
+            v0: k0 ^ 0x736f_6d65_7073_6575,
+            v1: k1 ^ 0x646f_7261_6e64_6f6d,
+            v2: k0 ^ 0x6c79_6765_6e65_7261,
+            v3: k1 ^ 0x7465_6462_7974_6573,
+            tail: 0,
+            ntail: 0,
+            length: 0,
+        }
+    }
+
+    fn compress(&mut self, m: u64) {
+        self.v3 ^= m;
+        sipround!(self);
+        sipround!(self);
+        self.v0 ^= m;
+    }
+}
+
+/// SipHash-2-4 with both keys zero.
+impl Default for SipHasher24 {
+    fn default() -> SipHasher24 {
+        SipHasher24::new_with_keys(0, 0)
+    }
+}
+
+impl Hasher for SipHasher24 {
+    fn write(&mut self, msg: &[u8]) {
+        self.length += msg.len();
+        let mut msg = msg;
+
+        if self.ntail != 0 {
+            while self.ntail < 8 && !msg.is_empty() {
+                self.tail |= u64::from(msg[0]) << (8 * self.ntail);
+                self.ntail += 1;
+                msg = &msg[1..];
+            }
+            if self.ntail < 8 {
+                return;
+            }
+            let m = self.tail;
+            self.compress(m);
+            self.tail = 0;
+            self.ntail = 0;
+        }
+
+        let mut chunks = msg.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(chunk);
+            self.compress(u64::from_le_bytes(word));
+        }
+        for (i, &b) in chunks.remainder().iter().enumerate() {
+            self.tail |= u64::from(b) << (8 * i);
+        }
+        self.ntail = chunks.remainder().len();
+    }
+
+    little_endian_writes!();
+
+    fn finish(&self) -> u64 {
+        let mut s = self.clone();
+        let b = ((self.length as u64 & 0xff) << 56) | self.tail;
+        s.compress(b);
+        s.v2 ^= 0xff;
+        sipround!(s);
+        sipround!(s);
+        sipround!(s);
+        sipround!(s);
+        s.v0 ^ s.v1 ^ s.v2 ^ s.v3
+    }
+}
+
+/// A `BuildHasher` producing [`SipHasher24`]s with fixed keys.
+///
+/// Two sketches built from the same `SeededState` map every item to the same
+/// register with the same rank, no matter which process built them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SeededState {
+    k0: u64,
+    k1: u64,
+}
+
+impl SeededState {
+    /// Derives both SipHash keys from a single 64-bit seed.
+    pub fn new(seed: u64) -> SeededState {
+        SeededState::with_keys(seed, mix64(seed))
+    }
+
+    pub fn with_keys(k0: u64, k1: u64) -> SeededState {
+        SeededState { k0, k1 }
+    }
+
+    pub fn keys(&self) -> (u64, u64) {
+        (self.k0, self.k1)
+    }
+}
+
+impl BuildHasher for SeededState {
+    type Hasher = SipHasher24;
+
+    fn build_hasher(&self) -> SipHasher24 {
+        SipHasher24::new_with_keys(self.k0, self.k1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SeededState, SipHasher24};
+    use std::hash::{BuildHasher, Hasher};
+
+    fn reference_keys() -> (u64, u64) {
+        (0x0706_0504_0302_0100, 0x0f0e_0d0c_0b0a_0908)
+    }
+
+    #[test]
+    fn reference_vectors() {
+        // From the SipHash paper: key 00..0f, message 00..(n-1).
+        let (k0, k1) = reference_keys();
+        let expected = [
+            (0, 0x726f_db47_dd0e_0e31),
+            (1, 0x74f8_39c5_93dc_67fd),
+            (63, 0x958a_324c_eb06_4572),
+        ];
+        for &(n, want) in &expected {
+            let msg: Vec<u8> = (0..n as u8).collect();
+            let mut h = SipHasher24::new_with_keys(k0, k1);
+            h.write(&msg);
+            assert_eq!(h.finish(), want, "message length {}", n);
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn matches_std_siphash_for_split_writes() {
+        let (k0, k1) = reference_keys();
+        let msg: Vec<u8> = (0..100u8).collect();
+        for split in 0..msg.len() {
+            let mut ours = SipHasher24::new_with_keys(k0, k1);
+            ours.write(&msg[..split]);
+            ours.write(&msg[split..]);
+
+            let mut theirs = ::std::hash::SipHasher::new_with_keys(k0, k1);
+            theirs.write(&msg);
+            assert_eq!(ours.finish(), theirs.finish(), "split at {}", split);
+        }
+    }
+
+    #[test]
+    fn seeded_state_is_deterministic() {
+        let a = SeededState::new(42);
+        let b = SeededState::new(42);
+        assert_eq!(a, b);
+        assert_eq!(a.hash_one("item"), b.hash_one("item"));
+        assert_ne!(a.hash_one("item"), SeededState::new(43).hash_one("item"));
+    }
+}
@@ -0,0 +1,166 @@
+//! A common interface for the crate's mergeable cardinality estimators, so
+//! the estimator can be chosen at run time.
+
+use core::any::Any;
+use core::hash::BuildHasher;
+
+use error::MergeError;
+use {HyperLogLog, KMinValues, LinearCounter};
+
+/// A cardinality estimator fed precomputed 64-bit hashes.
+///
+/// The trait is object safe: `Box<dyn CardinalitySketch>` can hold any of
+/// the estimators. Items are added by hash because `add<H: Hash>` is generic;
+/// hash them with the same function for every sketch that will be merged.
+pub trait CardinalitySketch {
+    /// Adds an item by its 64-bit hash.
+    fn add_hashed(&mut self, hash: u64);
+
+    /// Estimates the number of distinct items added.
+    fn count(&self) -> f64;
+
+    /// Folds `other` into `self`. Fails with [`MergeError::KindMismatch`] if
+    /// `other` is a different type of sketch, and otherwise as that type's
+    /// own `merge` does.
+    fn merge(&mut self, other: &dyn CardinalitySketch) -> Result<(), MergeError>;
+
+    /// The memory the sketch uses, including what it holds on the heap.
+    fn memory_bytes(&self) -> usize;
+
+    /// The sketch as `Any`, for `merge` to recover its concrete type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<S: BuildHasher + 'static> CardinalitySketch for HyperLogLog<S> {
+    fn add_hashed(&mut self, hash: u64) {
+        HyperLogLog::add_hashed(self, hash)
+    }
+
+    fn count(&self) -> f64 {
+        HyperLogLog::count(self)
+    }
+
+    fn merge(&mut self, other: &dyn CardinalitySketch) -> Result<(), MergeError> {
+        match other.as_any().downcast_ref::<HyperLogLog<S>>() {
+            Some(other) => HyperLogLog::merge(self, other).map(drop),
+            None => Err(MergeError::KindMismatch),
+        }
+    }
+
+    fn memory_bytes(&self) -> usize {
+        HyperLogLog::memory_bytes(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<S: 'static> CardinalitySketch for LinearCounter<S> {
+    fn add_hashed(&mut self, hash: u64) {
+        LinearCounter::add_hashed(self, hash)
+    }
+
+    fn count(&self) -> f64 {
+        LinearCounter::count(self)
+    }
+
+    fn merge(&mut self, other: &dyn CardinalitySketch) -> Result<(), MergeError> {
+        match other.as_any().downcast_ref::<LinearCounter<S>>() {
+            Some(other) => LinearCounter::merge(self, other),
+            None => Err(MergeError::KindMismatch),
+        }
+    }
+
+    fn memory_bytes(&self) -> usize {
+        LinearCounter::memory_bytes(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl<S: 'static> CardinalitySketch for KMinValues<S> {
+    fn add_hashed(&mut self, hash: u64) {
+        KMinValues::add_hashed(self, hash)
+    }
+
+    fn count(&self) -> f64 {
+        KMinValues::count(self)
+    }
+
+    fn merge(&mut self, other: &dyn CardinalitySketch) -> Result<(), MergeError> {
+        match other.as_any().downcast_ref::<KMinValues<S>>() {
+            Some(other) => KMinValues::merge(self, other),
+            None => Err(MergeError::KindMismatch),
+        }
+    }
+
+    fn memory_bytes(&self) -> usize {
+        KMinValues::memory_bytes(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CardinalitySketch;
+    use simulation::splitmix64;
+    use {HyperLogLog, LinearCounter, MergeError};
+
+    /// Picks an estimator the way a service might, by expected cardinality.
+    fn sketch_for(expected: usize) -> Box<dyn CardinalitySketch> {
+        if expected < 10_000 {
+            Box::new(LinearCounter::new(expected))
+        } else {
+            Box::new(HyperLogLog::new(1 << 12))
+        }
+    }
+
+    #[test]
+    fn both_through_trait_objects() {
+        let mut rng = 21;
+        let mut memory = Vec::new();
+        for &n in &[1_000usize, 100_000] {
+            let mut sketches: Vec<Box<dyn CardinalitySketch>> =
+                (0..2).map(|_| sketch_for(n)).collect();
+            let hashes: Vec<u64> = (0..n).map(|_| splitmix64(&mut rng)).collect();
+            for (i, &h) in hashes.iter().enumerate() {
+                sketches[i % 2].add_hashed(h);
+            }
+            let other = sketches.pop().unwrap();
+            let mut merged = sketches.pop().unwrap();
+            merged.merge(&*other).unwrap();
+            memory.push(merged.memory_bytes());
+            let error = (merged.count() - n as f64).abs() / n as f64;
+            assert!(error < 0.05, "n = {}: counted {}", n, merged.count());
+        }
+        // 1000 bits against 4096 byte registers.
+        assert!(memory[0] < 200 && memory[1] > 4_096, "{:?}", memory);
+    }
+
+    #[test]
+    fn merge_checks_kind_and_size() {
+        let mut hll: Box<dyn CardinalitySketch> = Box::new(HyperLogLog::new(1 << 10));
+        let linear: Box<dyn CardinalitySketch> = Box::new(LinearCounter::new(1_000));
+        assert_eq!(hll.merge(&*linear).unwrap_err(), MergeError::KindMismatch);
+
+        let mut small: Box<dyn CardinalitySketch> = Box::new(HyperLogLog::new(1 << 8));
+        assert_eq!(
+            small.merge(&*hll).unwrap_err(),
+            MergeError::PrecisionMismatch { left: 8, right: 10 }
+        );
+        let mut other: Box<dyn CardinalitySketch> = Box::new(LinearCounter::new(500));
+        assert_eq!(
+            other.merge(&*linear).unwrap_err(),
+            MergeError::SizeMismatch {
+                left: 500,
+                right: 1_000
+            }
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! A sliding-window HyperLogLog (Chabchoub and Hébrail, 2010), counting the
+//! distinct items seen in any trailing window up to a configured length.
+//!
+//! Instead of a single rank, each register keeps its list of future possible
+//! maxima: the `(timestamp, rank)` pairs that could still be the register's
+//! maximum for some window ending now or later. A pair stops qualifying once
+//! a pair at least as recent has at least its rank, or once it is older than
+//! the longest window, and `add` drops it then. What remains is ordered by
+//! time with strictly falling ranks, so a window's maximum is the rank of its
+//! oldest surviving pair.
+//!
+//! Ranks strictly fall along a list, so a register never holds more than
+//! `65 - p` pairs. In practice it holds far fewer: each rank is one of the
+//! pairs with probability one half, so a register that saw `k` distinct items
+//! in the longest window keeps about `1 + log2(k) / 2` of them, around 6 for
+//! a thousand items per register.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::mem;
+
+use {check_register_count, estimate, index_and_rank, DefaultBuildHasher};
+
+/// Whether an item added at `timestamp` falls in the `window` ending at
+/// `now`. Items stamped after `now` count as inside it.
+fn in_window(timestamp: u64, now: u64, window: u64) -> bool {
+    timestamp > now || now - timestamp < window
+}
+
+/// A HyperLogLog sketch over timestamped items that counts the distinct items
+/// in any trailing window up to `max_window` long.
+///
+/// Timestamps are plain `u64`s in whatever unit the caller picks, seconds or
+/// milliseconds say, as long as windows use the same unit. Items should mostly
+/// arrive in timestamp order; a late item is still counted at its own
+/// timestamp, but expiry is driven by the timestamps being added.
+#[derive(Clone)]
+pub struct SlidingHyperLogLog<S = DefaultBuildHasher> {
+    /// Each register's `(timestamp, rank)` pairs, oldest first.
+    registers: Vec<Vec<(u64, u8)>>,
+    precision: u8,
+    max_window: u64,
+    build_hasher: S,
+}
+
+impl SlidingHyperLogLog {
+    /// Creates a sketch with `m` registers answering windows of up to
+    /// `max_window`.
+    ///
+    /// Panics unless `m` is a power of two from 16 to 2^24, as for
+    /// [`HyperLogLog::new`](::HyperLogLog::new).
+    pub fn new(m: usize, max_window: u64) -> SlidingHyperLogLog {
+        SlidingHyperLogLog::with_hasher(m, max_window, DefaultBuildHasher::default())
+    }
+}
+
+impl<S: BuildHasher> SlidingHyperLogLog<S> {
+    /// Like [`SlidingHyperLogLog::new`], hashing items with `build_hasher`.
+    pub fn with_hasher(m: usize, max_window: u64, build_hasher: S) -> SlidingHyperLogLog<S> {
+        let precision = match check_register_count(m) {
+            Ok(p) => p,
+            Err(e) => panic!("{}", e),
+        };
+        SlidingHyperLogLog {
+            registers: vec![Vec::new(); m],
+            precision,
+            max_window,
+            build_hasher,
+        }
+    }
+
+    /// Adds an item seen at `timestamp`, hashing it with the sketch's hasher.
+    pub fn add<H: Hash>(&mut self, item: H, timestamp: u64) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h, timestamp);
+    }
+}
+
+impl<S> SlidingHyperLogLog<S> {
+    /// Adds an item by its precomputed 64-bit hash; see
+    /// [`HyperLogLog::add_hashed`](::HyperLogLog::add_hashed).
+    pub fn add_hashed(&mut self, hash: u64, timestamp: u64) {
+        let (index, rank) = index_and_rank(hash, self.precision);
+        let max_window = self.max_window;
+        let pairs = &mut self.registers[index];
+
+        // No window ending at `timestamp` or later reaches these.
+        let expired = pairs
+            .iter()
+            .take_while(|&&(t, _)| !in_window(t, timestamp, max_window))
+            .count();
+        pairs.drain(..expired);
+
+        if pairs.iter().any(|&(t, r)| t >= timestamp && r >= rank) {
+            return;
+        }
+        pairs.retain(|&(t, r)| t > timestamp || r > rank);
+        let at = pairs.iter().take_while(|&&(t, _)| t <= timestamp).count();
+        pairs.insert(at, (timestamp, rank));
+    }
+
+    /// Estimates the number of distinct items added with timestamps in the
+    /// `window` ending at `now`: after `now - window`, up to and including
+    /// `now`. Items stamped after `now` are counted too, so `now` is best the
+    /// latest timestamp added.
+    ///
+    /// Panics if `window` is longer than the sketch's maximum window.
+    pub fn count_window(&self, now: u64, window: u64) -> f64 {
+        assert!(
+            window <= self.max_window,
+            "window {} is longer than the maximum of {}",
+            window,
+            self.max_window
+        );
+        let mut counts = [0u64; 65];
+        for pairs in &self.registers {
+            // The oldest pair in the window has the largest rank in it.
+            let rank = pairs
+                .iter()
+                .find(|&&(t, _)| in_window(t, now, window))
+                .map_or(0, |&(_, r)| r);
+            counts[rank as usize] += 1;
+        }
+        estimate(&counts)
+    }
+
+    /// The longest window [`count_window`](SlidingHyperLogLog::count_window)
+    /// answers.
+    pub fn max_window(&self) -> u64 {
+        self.max_window
+    }
+
+    /// The register count, `m`.
+    pub fn num_registers(&self) -> usize {
+        self.registers.len()
+    }
+
+    /// The memory the sketch uses, including every register's pair list.
+    pub fn memory_bytes(&self) -> usize {
+        let pairs: usize = self.registers.iter().map(Vec::capacity).sum();
+        mem::size_of::<Self>()
+            + self.registers.capacity() * mem::size_of::<Vec<(u64, u8)>>()
+            + pairs * mem::size_of::<(u64, u8)>()
+    }
+}
+
+impl<S> fmt::Debug for SlidingHyperLogLog<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pairs: usize = self.registers.iter().map(Vec::len).sum();
+        f.debug_struct("SlidingHyperLogLog")
+            .field("precision", &self.precision)
+            .field("max_window", &self.max_window)
+            .field("pairs", &pairs)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::SlidingHyperLogLog;
+    use simulation::splitmix64;
+    use {max_rank, standard_error, HyperLogLog};
+
+    /// The hash of user `id`, the same every time the user appears.
+    fn user_hash(id: u64) -> u64 {
+        let mut state = id;
+        splitmix64(&mut state)
+    }
+
+    #[test]
+    fn windows_match_known_cardinalities() {
+        // An hour of traffic, 30 events a second from a user base that
+        // drifts by 200 users a minute, so that every window length sees a
+        // different number of distinct users.
+        let p = 10;
+        let hour = 3_600;
+        let mut sketch = SlidingHyperLogLog::new(1 << p, hour);
+        let mut last_seen: HashMap<u64, u64> = HashMap::new();
+        let mut rng = 42;
+        let bound = 4. * standard_error(p);
+        for now in 0..=2 * hour {
+            for _ in 0..30 {
+                let id = now / 60 * 200 + splitmix64(&mut rng) % 3_000;
+                sketch.add_hashed(user_hash(id), now);
+                last_seen.insert(id, now);
+            }
+            if now % 900 != 0 || now == 0 {
+                continue;
+            }
+            for &window in &[60, 300, 1_800, hour] {
+                let exact = last_seen.values().filter(|&&t| now - t < window).count() as f64;
+                let counted = sketch.count_window(now, window);
+                assert!(
+                    (counted - exact).abs() / exact < bound,
+                    "window {} at {}: counted {}, exact {}",
+                    window,
+                    now,
+                    counted,
+                    exact
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn full_window_matches_plain_sketch() {
+        // At a window covering everything, the sketch is a plain HyperLogLog.
+        let mut sliding = SlidingHyperLogLog::new(1 << 8, 1_000);
+        let mut plain = HyperLogLog::new(1 << 8);
+        for i in 0..5_000u64 {
+            sliding.add(i, i / 10);
+            plain.add(i);
+        }
+        assert_eq!(sliding.count_window(499, 1_000), plain.count());
+        assert_eq!(sliding.count_window(10_000, 1), 0.);
+    }
+
+    #[test]
+    fn pruning_bounds_memory() {
+        let p = 8;
+        let mut sketch = SlidingHyperLogLog::new(1 << p, 100);
+        let mut rng = 7;
+        for t in 0..10_000u64 {
+            for _ in 0..100 {
+                sketch.add_hashed(splitmix64(&mut rng), t);
+            }
+        }
+        // 10,000 items in the last window, about 39 per register.
+        let lens: Vec<usize> = sketch.registers.iter().map(Vec::len).collect();
+        let mean = lens.iter().sum::<usize>() as f64 / lens.len() as f64;
+        assert!(lens.iter().all(|&l| 0 < l && l <= max_rank(p) as usize));
+        assert!(mean > 2. && mean < 5., "mean of {} pairs", mean);
+        for pairs in &sketch.registers {
+            // Expired pairs go when their register is next added to.
+            let newest = pairs[pairs.len() - 1].0;
+            assert!(pairs.iter().all(|&(t, _)| newest - t < 100));
+            assert!(pairs
+                .windows(2)
+                .all(|w| w[0].0 <= w[1].0 && w[0].1 > w[1].1));
+        }
+    }
+
+    #[test]
+    fn late_items_land_at_their_timestamp() {
+        let mut sketch = SlidingHyperLogLog::new(1 << 4, 100);
+        // Rank 2 in register 0 at t = 50, then a late rank 1 at t = 40 and a
+        // late rank 3 at t = 45.
+        sketch.add_hashed(1 << 58, 50);
+        sketch.add_hashed(1 << 59, 40);
+        sketch.add_hashed(1 << 57, 45);
+        assert_eq!(sketch.registers[0], [(45, 3), (50, 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "longer than the maximum")]
+    fn rejects_windows_past_the_maximum() {
+        SlidingHyperLogLog::new(1 << 4, 10).count_window(0, 11);
+    }
+}
@@ -0,0 +1,386 @@
+//! Register storage that starts sparse and is promoted to dense.
+//!
+//! A sparse sketch keeps a sorted list of `(index, rank)` pairs for the
+//! registers that have been touched, packed into one `u32` each as
+//! `index << 8 | rank`. Once the list would take more memory than a dense
+//! array of one byte per register, it is converted to a dense `Vec<u8>`.
+//!
+//! Both representations hold exactly the same information, so estimates don't
+//! depend on which one a sketch happens to be in.
+
+use alloc::vec::Vec;
+use core::{mem, slice};
+
+use simd;
+
+/// Sparse entries hold the index in 24 bits, so no sketch is larger.
+pub(crate) const MAX_SPARSE_PRECISION: u8 = 24;
+
+/// The default number of sparse entries a sketch with `m` registers holds
+/// before it is promoted: the point where four bytes per entry reach the size
+/// of the dense array.
+pub(crate) fn default_sparse_threshold(p: u8) -> usize {
+    (1usize << p) / 4
+}
+
+pub(crate) fn encode(index: usize, rank: u8) -> u32 {
+    (index as u32) << 8 | u32::from(rank)
+}
+
+pub(crate) fn decode(entry: u32) -> (usize, u8) {
+    ((entry >> 8) as usize, entry as u8)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Registers {
+    Sparse(Vec<u32>),
+    Dense(Vec<u8>),
+}
+
+impl Registers {
+    pub(crate) fn is_sparse(&self) -> bool {
+        match *self {
+            Registers::Sparse(_) => true,
+            Registers::Dense(_) => false,
+        }
+    }
+
+    /// The number of sparse entries, or `None` for a dense sketch.
+    pub(crate) fn sparse_len(&self) -> Option<usize> {
+        match *self {
+            Registers::Sparse(ref entries) => Some(entries.len()),
+            Registers::Dense(_) => None,
+        }
+    }
+
+    /// The bytes allocated on the heap for the registers.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        match *self {
+            Registers::Sparse(ref entries) => entries.capacity() * mem::size_of::<u32>(),
+            Registers::Dense(ref registers) => registers.capacity(),
+        }
+    }
+
+    /// Whether every register is zero.
+    pub(crate) fn is_empty(&self) -> bool {
+        match *self {
+            Registers::Sparse(ref entries) => entries.is_empty(),
+            Registers::Dense(ref registers) => registers.iter().all(|&r| r == 0),
+        }
+    }
+
+    /// Zeroes every register, keeping the representation and its allocation.
+    pub(crate) fn clear(&mut self) {
+        match *self {
+            Registers::Sparse(ref mut entries) => entries.clear(),
+            Registers::Dense(ref mut registers) => registers.fill(0),
+        }
+    }
+
+    /// Counts the registers holding each value; see [`simd::histogram`].
+    pub(crate) fn histogram(&self, m: usize) -> [u64; 65] {
+        match *self {
+            Registers::Sparse(ref entries) => {
+                let mut counts = [0u64; 65];
+                for &e in entries {
+                    counts[usize::from(decode(e).1)] += 1;
+                }
+                counts[0] = (m - entries.len()) as u64;
+                counts
+            }
+            Registers::Dense(ref registers) => simd::histogram(registers),
+        }
+    }
+
+    /// Raises register `index` to `rank` if it is lower, returning its
+    /// previous value if it changed.
+    pub(crate) fn raise(&mut self, index: usize, rank: u8) -> Option<u8> {
+        match *self {
+            Registers::Sparse(ref mut entries) => {
+                match entries.binary_search_by_key(&index, |&e| decode(e).0) {
+                    Ok(at) => {
+                        let old = decode(entries[at]).1;
+                        if old < rank {
+                            entries[at] = encode(index, rank);
+                            Some(old)
+                        } else {
+                            None
+                        }
+                    }
+                    Err(at) => {
+                        entries.insert(at, encode(index, rank));
+                        Some(0)
+                    }
+                }
+            }
+            Registers::Dense(ref mut registers) => {
+                let old = registers[index];
+                if old < rank {
+                    registers[index] = rank;
+                    Some(old)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// The index and value of the first register above `max`, if any is.
+    pub(crate) fn first_above(&self, max: u8) -> Option<(usize, u8)> {
+        match *self {
+            Registers::Sparse(ref entries) => entries
+                .iter()
+                .map(|&e| decode(e))
+                .find(|&(_, rank)| rank > max),
+            Registers::Dense(ref registers) => registers
+                .iter()
+                .position(|&r| r > max)
+                .map(|index| (index, registers[index])),
+        }
+    }
+
+    /// Iterates over all `m` register values in index order.
+    pub(crate) fn iter(&self, m: usize) -> Iter<'_> {
+        match *self {
+            Registers::Sparse(ref entries) => Iter::Sparse {
+                entries: entries.iter(),
+                next: None,
+                index: 0,
+                m,
+            },
+            Registers::Dense(ref registers) => Iter::Dense(registers.iter()),
+        }
+    }
+
+    pub(crate) fn to_dense(&self, m: usize) -> Vec<u8> {
+        match *self {
+            Registers::Sparse(ref entries) => {
+                let mut registers = vec![0; m];
+                for &e in entries {
+                    let (index, rank) = decode(e);
+                    registers[index] = rank;
+                }
+                registers
+            }
+            Registers::Dense(ref registers) => registers.clone(),
+        }
+    }
+
+    pub(crate) fn promote(&mut self, m: usize) {
+        if self.is_sparse() {
+            *self = Registers::Dense(self.to_dense(m));
+        }
+    }
+
+    /// Promotes a sparse sketch holding more than `threshold` entries; a
+    /// threshold of zero always promotes.
+    pub(crate) fn promote_past(&mut self, m: usize, threshold: usize) {
+        if let Some(len) = self.sparse_len() {
+            if len > threshold || threshold == 0 {
+                self.promote(m);
+            }
+        }
+    }
+
+    /// Applies a batch of `(index, rank)` updates, promoting as
+    /// [`promote_past`](Registers::promote_past) would after each one and
+    /// moving each raised register between the values it is counted under
+    /// in `histogram`. Once the registers are dense the rest of the batch
+    /// runs without checking the representation per update. Returns how many
+    /// updates raised a register.
+    pub(crate) fn raise_all<I>(
+        &mut self,
+        mut updates: I,
+        m: usize,
+        threshold: usize,
+        histogram: &mut [u64; 65],
+    ) -> usize
+    where
+        I: Iterator<Item = (usize, u8)>,
+    {
+        let mut raised = 0;
+        if self.is_sparse() {
+            for (index, rank) in &mut updates {
+                if let Some(old) = self.raise(index, rank) {
+                    histogram[usize::from(old)] -= 1;
+                    histogram[usize::from(rank)] += 1;
+                    raised += 1;
+                    self.promote_past(m, threshold);
+                    if !self.is_sparse() {
+                        break;
+                    }
+                }
+            }
+        }
+        if let Registers::Dense(ref mut registers) = *self {
+            for (index, rank) in updates {
+                let old = registers[index];
+                if old < rank {
+                    registers[index] = rank;
+                    histogram[usize::from(old)] -= 1;
+                    histogram[usize::from(rank)] += 1;
+                    raised += 1;
+                }
+            }
+        }
+        raised
+    }
+
+    /// Raises every register to at least the value in `other`, returning
+    /// whether any register changed.
+    pub(crate) fn merge(&mut self, other: &Registers, m: usize) -> bool {
+        let mut changed = false;
+        match (&mut *self, other) {
+            (Registers::Dense(mine), Registers::Dense(theirs)) => {
+                return simd::max_into(mine, theirs);
+            }
+            (Registers::Dense(mine), Registers::Sparse(theirs)) => {
+                for &e in theirs {
+                    let (index, rank) = decode(e);
+                    if mine[index] < rank {
+                        mine[index] = rank;
+                        changed = true;
+                    }
+                }
+                return changed;
+            }
+            (Registers::Sparse(mine), Registers::Sparse(theirs)) => {
+                let merged = merge_sorted(mine, theirs);
+                changed = merged != *mine;
+                *mine = merged;
+                return changed;
+            }
+            (Registers::Sparse(_), Registers::Dense(_)) => {}
+        }
+        self.promote(m);
+        self.merge(other, m)
+    }
+}
+
+fn merge_sorted(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let (ai, bi) = (decode(a[i]).0, decode(b[j]).0);
+        if ai < bi {
+            out.push(a[i]);
+            i += 1;
+        } else if bi < ai {
+            out.push(b[j]);
+            j += 1;
+        } else {
+            out.push(a[i].max(b[j]));
+            i += 1;
+            j += 1;
+        }
+    }
+    out.extend_from_slice(&a[i..]);
+    out.extend_from_slice(&b[j..]);
+    out
+}
+
+/// An iterator over every register value, filling in zeros for a sparse sketch.
+#[derive(Clone)]
+pub(crate) enum Iter<'a> {
+    Sparse {
+        entries: slice::Iter<'a, u32>,
+        next: Option<(usize, u8)>,
+        index: usize,
+        m: usize,
+    },
+    Dense(slice::Iter<'a, u8>),
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        match *self {
+            Iter::Sparse {
+                ref mut entries,
+                ref mut next,
+                ref mut index,
+                m,
+            } => {
+                if *index >= m {
+                    return None;
+                }
+                if next.is_none() {
+                    *next = entries.next().map(|&e| decode(e));
+                }
+                let value = match *next {
+                    Some((at, rank)) if at == *index => {
+                        *next = None;
+                        rank
+                    }
+                    _ => 0,
+                };
+                *index += 1;
+                Some(value)
+            }
+            Iter::Dense(ref mut it) => it.next().cloned(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match *self {
+            Iter::Sparse { index, m, .. } => m - index,
+            Iter::Dense(ref it) => it.len(),
+        };
+        (len, Some(len))
+    }
+}
+
+impl<'a> ExactSizeIterator for Iter<'a> {}
+
+#[cfg(test)]
+mod tests {
+    use super::Registers;
+
+    #[test]
+    fn sparse_and_dense_agree() {
+        let m = 64;
+        let mut sparse = Registers::Sparse(Vec::new());
+        let mut dense = Registers::Dense(vec![0; m]);
+        for &(i, r) in &[(5, 3), (63, 1), (0, 2), (5, 1), (5, 7), (20, 4)] {
+            assert_eq!(sparse.raise(i, r), dense.raise(i, r));
+        }
+        assert_eq!(sparse.sparse_len(), Some(4));
+        assert_eq!(sparse.to_dense(m)[5], 7);
+        assert_eq!(sparse.to_dense(m)[6], 0);
+        assert_eq!(sparse.to_dense(m), dense.to_dense(m));
+        assert_eq!(
+            sparse.iter(m).collect::<Vec<_>>(),
+            dense.iter(m).collect::<Vec<_>>()
+        );
+        assert_eq!(sparse.iter(m).len(), m);
+    }
+
+    #[test]
+    fn merge_across_representations() {
+        let m = 32;
+        let build = |pairs: &[(usize, u8)], sparse: bool| {
+            let mut r = if sparse {
+                Registers::Sparse(Vec::new())
+            } else {
+                Registers::Dense(vec![0; m])
+            };
+            for &(i, v) in pairs {
+                r.raise(i, v);
+            }
+            r
+        };
+        let a = [(1, 3), (4, 2), (9, 9)];
+        let b = [(1, 5), (3, 1), (9, 2), (31, 6)];
+        let expected = build(&a, false).to_dense(m);
+        let mut expected = Registers::Dense(expected);
+        expected.merge(&build(&b, false), m);
+        let expected = expected.to_dense(m);
+
+        for &(sa, sb) in &[(true, true), (true, false), (false, true), (false, false)] {
+            let mut left = build(&a, sa);
+            left.merge(&build(&b, sb), m);
+            assert_eq!(left.to_dense(m), expected, "sparse: {} {}", sa, sb);
+        }
+    }
+}
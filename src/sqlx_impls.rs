@@ -0,0 +1,160 @@
+//! Storing sketches in Postgres `bytea` columns with sqlx.
+//!
+//! A sketch binds and decodes as its [`to_bytes`](HyperLogLog::to_bytes)
+//! encoding, so it can be a query parameter or a field of a `FromRow` or
+//! `query_as!` result directly; with the macros, name the type in the
+//! query, as in `SELECT sketch AS "sketch: HyperLogLog" ...`. Encoding
+//! writes straight into the argument buffer, and decoding reads the
+//! registers from the row's bytes without copying them first. Either
+//! version of the format decodes, so columns written with
+//! [`to_bytes_compressed`](HyperLogLog::to_bytes_compressed) read back
+//! too.
+
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgArgumentBuffer, PgTypeInfo, PgValueFormat, PgValueRef, Postgres};
+use sqlx::{Decode, Encode, Type};
+
+use {HyperLogLog, StableBuildHasher};
+
+impl<S> Type<Postgres> for HyperLogLog<S> {
+    fn type_info() -> PgTypeInfo {
+        <[u8] as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <[u8] as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl<'q, S: StableBuildHasher> Encode<'q, Postgres> for HyperLogLog<S> {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> Result<IsNull, BoxDynError> {
+        self.write_bytes(buf);
+        Ok(IsNull::No)
+    }
+
+    fn size_hint(&self) -> usize {
+        self.bytes_len()
+    }
+}
+
+/// Fails with the [`DecodeError`](::DecodeError) if the bytes aren't a
+/// sketch hashed as `S` hashes.
+impl<'r, S: StableBuildHasher> Decode<'r, Postgres> for HyperLogLog<S> {
+    fn decode(value: PgValueRef<'r>) -> Result<HyperLogLog<S>, BoxDynError> {
+        match value.format() {
+            PgValueFormat::Binary => from_bytea(value.as_bytes()?),
+            // Simple queries send `bytea` as hex text, which sqlx decodes.
+            PgValueFormat::Text => from_bytea(&<Vec<u8> as Decode<Postgres>>::decode(value)?),
+        }
+    }
+}
+
+fn from_bytea<S: StableBuildHasher>(bytes: &[u8]) -> Result<HyperLogLog<S>, BoxDynError> {
+    Ok(HyperLogLog::from_bytes(bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use sqlx::encode::IsNull;
+    use sqlx::postgres::{PgArgumentBuffer, PgConnection, Postgres};
+    use sqlx::{Connection, Encode, Executor, Row, Type};
+    use tokio::runtime::Runtime;
+
+    use super::from_bytea;
+    use error::DecodeError;
+    use simulation::splitmix64;
+    use {HyperLogLog, SeededState};
+
+    fn sketch(n: usize) -> HyperLogLog<SeededState> {
+        let mut state = 90;
+        let mut h = HyperLogLog::new_seeded(1 << 10, 90);
+        (0..n).for_each(|_| h.add_hashed(splitmix64(&mut state)));
+        h
+    }
+
+    #[test]
+    fn encodes_and_decodes_the_binary_format() {
+        assert_eq!(
+            <HyperLogLog as Type<Postgres>>::type_info(),
+            <Vec<u8> as Type<Postgres>>::type_info()
+        );
+        for &n in &[0, 300, 20_000] {
+            let h = sketch(n);
+            let mut buf = PgArgumentBuffer::default();
+            buf.push(0xff);
+            let null = Encode::<Postgres>::encode_by_ref(&h, &mut buf).unwrap();
+            assert!(matches!(null, IsNull::No));
+            assert_eq!(buf[1..], h.to_bytes()[..]);
+            assert_eq!(Encode::<Postgres>::size_hint(&h), buf.len() - 1);
+            assert_eq!(from_bytea::<SeededState>(&buf[1..]).unwrap(), h);
+            assert_eq!(
+                from_bytea::<SeededState>(&h.to_bytes_compressed()).unwrap(),
+                h
+            );
+        }
+    }
+
+    #[test]
+    fn surfaces_decode_errors() {
+        let bytes = sketch(10).to_bytes();
+        let err = from_bytea::<SeededState>(&bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DecodeError>(),
+            Some(&DecodeError::Truncated {
+                expected: bytes.len(),
+                actual: bytes.len() - 1
+            })
+        );
+        let err = from_bytea::<::DefaultBuildHasher>(&bytes).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<DecodeError>(),
+            Some(&DecodeError::HasherMismatch)
+        );
+    }
+
+    /// Round-trips sketches through a server named by `HLL_TEST_DATABASE_URL`,
+    /// and passes without one. sqlx needs a runtime to connect:
+    ///
+    /// ```text
+    /// HLL_TEST_DATABASE_URL=postgres://localhost/test \
+    ///     cargo test --features sqlx-postgres,sqlx/runtime-tokio
+    /// ```
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn round_trips_through_postgres() {
+        let url = match env::var("HLL_TEST_DATABASE_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let rt = Runtime::new().unwrap();
+        let mut conn = rt.block_on(PgConnection::connect(&url)).unwrap();
+        let h = sketch(5_000);
+        let (binary,): (HyperLogLog<SeededState>,) = rt
+            .block_on(
+                sqlx::query_as("SELECT $1::bytea")
+                    .bind(&h)
+                    .fetch_one(&mut conn),
+            )
+            .unwrap();
+        assert_eq!(binary, h);
+
+        // A simple query returns the bytes as hex text.
+        let hex: String = rt
+            .block_on(
+                sqlx::query_scalar("SELECT encode($1::bytea, 'hex')")
+                    .bind(&h)
+                    .fetch_one(&mut conn),
+            )
+            .unwrap();
+        let sql = format!("SELECT '\\x{}'::bytea", hex);
+        let row = rt.block_on(conn.fetch_one(&*sql)).unwrap();
+        assert_eq!(row.try_get::<HyperLogLog<SeededState>, _>(0).unwrap(), h);
+
+        let bad = sqlx::query_as::<_, (HyperLogLog<SeededState>,)>("SELECT '\\x00'::bytea");
+        let err = rt.block_on(bad.fetch_one(&mut conn)).unwrap_err();
+        assert!(err.to_string().contains("truncated"), "{}", err);
+    }
+}
@@ -0,0 +1,250 @@
+//! Writing and reading sketches over `io::Write` and `io::Read`, one frame
+//! at a time.
+//!
+//! A frame is exactly what [`HyperLogLog::to_bytes`] returns. Its length
+//! follows from the header, from the precision and whether hasher keys are
+//! present, so frames can be concatenated in one stream and read back in
+//! order without any other framing.
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::vec::Vec;
+
+use encoding::{
+    read_header, read_watermark, validate_registers, FLAG_KEYED, HEADER_LEN, ITEMS_LEN,
+    MAX_HEADER_LEN, VERSION, VERSION_COUNTED, WATERMARK_LEN,
+};
+use error::DecodeError;
+use sparse::Registers;
+use {HyperLogLog, StableBuildHasher};
+
+/// Reads until `buf` is full or the input ends, returning how much was read.
+fn fill<R: Read>(r: &mut R, buf: &mut [u8]) -> Result<usize, DecodeError> {
+    let mut read = 0;
+    while read < buf.len() {
+        match r.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(DecodeError::Io(e.kind())),
+        }
+    }
+    Ok(read)
+}
+
+impl<S: StableBuildHasher> HyperLogLog<S> {
+    /// Writes the sketch to `w` in the format of
+    /// [`to_bytes`](HyperLogLog::to_bytes), without building it in memory
+    /// first, and returns the number of bytes written.
+    pub fn write_to<W: Write>(&self, w: &mut W) -> io::Result<usize> {
+        let (header, len) = self.header_bytes(self.counted(VERSION));
+        w.write_all(&header[..len])?;
+        let m = self.num_registers();
+        match self.registers {
+            Registers::Dense(ref registers) => w.write_all(registers)?,
+            Registers::Sparse(_) => {
+                let mut registers = self.registers.iter(m);
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let mut n = 0;
+                    for (byte, r) in chunk.iter_mut().zip(&mut registers) {
+                        *byte = r;
+                        n += 1;
+                    }
+                    if n == 0 {
+                        break;
+                    }
+                    w.write_all(&chunk[..n])?;
+                }
+            }
+        }
+        match self.watermark {
+            Some(watermark) => {
+                w.write_all(&watermark.to_le_bytes())?;
+                Ok(len + m + WATERMARK_LEN)
+            }
+            None => Ok(len + m),
+        }
+    }
+
+    /// Reads one sketch written by [`write_to`](HyperLogLog::write_to) or
+    /// [`to_bytes`](HyperLogLog::to_bytes) from `r`, consuming exactly its
+    /// bytes so that the next read starts at the following frame.
+    ///
+    /// A stream that has ended fails with [`DecodeError::Truncated`] with
+    /// `actual` 0, and one that ends partway through a frame with the
+    /// number of bytes there were. Other I/O errors are
+    /// [`DecodeError::Io`]. The compressed format isn't accepted.
+    pub fn read_from<R: Read>(r: &mut R) -> Result<HyperLogLog<S>, DecodeError> {
+        let mut head = [0u8; MAX_HEADER_LEN];
+        let mut len = fill(r, &mut head[..HEADER_LEN])?;
+        if len == HEADER_LEN {
+            let keys = if head[6] & FLAG_KEYED != 0 { 16 } else { 0 };
+            let items = if head[4] == VERSION_COUNTED {
+                ITEMS_LEN
+            } else {
+                0
+            };
+            len += fill(r, &mut head[HEADER_LEN..HEADER_LEN + keys + items])?;
+        }
+        let header = read_header::<S>(&head[..len], &[VERSION, VERSION_COUNTED])?;
+        let m = 1usize << header.precision;
+        // Grows as the registers arrive, rather than trusting the header
+        // with a 2^p allocation up front.
+        let mut registers = Vec::new();
+        if let Err(e) = r.take(m as u64).read_to_end(&mut registers) {
+            return Err(DecodeError::Io(e.kind()));
+        }
+        if registers.len() < m {
+            return Err(DecodeError::Truncated {
+                expected: header.body_at + m,
+                actual: header.body_at + registers.len(),
+            });
+        }
+        validate_registers(header.precision, &registers)?;
+        let watermark = if header.watermark {
+            let mut trailer = [0u8; WATERMARK_LEN];
+            let read = fill(r, &mut trailer)?;
+            if read < WATERMARK_LEN {
+                return Err(DecodeError::Truncated {
+                    expected: header.body_at + m + WATERMARK_LEN,
+                    actual: header.body_at + m + read,
+                });
+            }
+            Some(read_watermark(&trailer)?)
+        } else {
+            None
+        };
+        let mut h = HyperLogLog::from_dense(header.precision, registers, header.build_hasher);
+        h.watermark = watermark;
+        h.estimator = header.estimator;
+        h.items_added = header.items_added;
+        Ok(h)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read};
+
+    use error::DecodeError;
+    use {HyperLogLog, SeededState};
+
+    #[test]
+    fn frames_concatenate() {
+        let mut dense = HyperLogLog::new_seeded(1 << 12, 7);
+        (0..100_000u32).for_each(|i| dense.add(i));
+        let mut sparse = HyperLogLog::new_seeded(1 << 14, 7);
+        (0..300u32).for_each(|i| sparse.add(i));
+        assert!(!dense.is_sparse() && sparse.is_sparse());
+        let empty = HyperLogLog::new_seeded(1 << 4, 7);
+
+        let mut out = Vec::new();
+        let mut written = 0;
+        for h in &[&dense, &sparse, &empty] {
+            let n = h.write_to(&mut out).unwrap();
+            assert_eq!(n, h.to_bytes().len());
+            written += n;
+        }
+        assert_eq!(out.len(), written);
+
+        let mut input = Cursor::new(&out[..]);
+        let mut ends = Vec::new();
+        for h in &[&dense, &sparse, &empty] {
+            let back: HyperLogLog<SeededState> = HyperLogLog::read_from(&mut input).unwrap();
+            assert_eq!(&back, *h);
+            assert_eq!(back.count(), h.count());
+            ends.push(input.position() as usize);
+        }
+        let dense_len = dense.to_bytes().len();
+        assert_eq!(
+            ends,
+            [dense_len, dense_len + sparse.to_bytes().len(), out.len()]
+        );
+        assert_eq!(
+            HyperLogLog::<SeededState>::read_from(&mut input),
+            Err(DecodeError::Truncated {
+                expected: 7,
+                actual: 0
+            })
+        );
+    }
+
+    #[test]
+    fn truncated_final_frame() {
+        let mut out = Vec::new();
+        let mut first = HyperLogLog::new(1 << 6);
+        first.add("a");
+        first.write_to(&mut out).unwrap();
+        let frame = out.len();
+        HyperLogLog::new(1 << 8).write_to(&mut out).unwrap();
+        out.truncate(out.len() - 10);
+
+        let mut input = Cursor::new(&out[..]);
+        let back: HyperLogLog = HyperLogLog::read_from(&mut input).unwrap();
+        assert_eq!(back, first);
+        assert_eq!(
+            HyperLogLog::<::DefaultBuildHasher>::read_from(&mut input),
+            Err(DecodeError::Truncated {
+                expected: 7 + 256,
+                actual: out.len() - frame,
+            })
+        );
+
+        // The header itself cut short.
+        let mut partial = Cursor::new(&out[frame..frame + 3]);
+        assert_eq!(
+            HyperLogLog::<::DefaultBuildHasher>::read_from(&mut partial),
+            Err(DecodeError::Truncated {
+                expected: 7,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn frames_carry_the_watermark() {
+        let mut h = HyperLogLog::new_seeded(1 << 6, 86);
+        h.extend(0..100u32);
+        h.set_monotonic(true);
+        h.watermark = Some(h.count() + 2.);
+        let frame = h.to_bytes();
+        let mut out = Vec::new();
+        assert_eq!(h.write_to(&mut out).unwrap(), frame.len());
+        assert_eq!(out, frame);
+        h.write_to(&mut out).unwrap();
+        out.truncate(out.len() - 3);
+
+        let mut input = Cursor::new(&out[..]);
+        let back: HyperLogLog<SeededState> = HyperLogLog::read_from(&mut input).unwrap();
+        assert_eq!(back, h);
+        assert_eq!(back.count(), h.count());
+        assert_eq!(
+            HyperLogLog::<SeededState>::read_from(&mut input),
+            Err(DecodeError::Truncated {
+                expected: frame.len(),
+                actual: frame.len() - 3,
+            })
+        );
+    }
+
+    #[test]
+    fn compressed_frames_and_io_errors() {
+        let h = HyperLogLog::new(1 << 6);
+        let mut compressed = Cursor::new(h.to_bytes_compressed());
+        assert_eq!(
+            HyperLogLog::<::DefaultBuildHasher>::read_from(&mut compressed),
+            Err(DecodeError::UnsupportedVersion(2))
+        );
+
+        struct Broken;
+        impl Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::PermissionDenied.into())
+            }
+        }
+        assert_eq!(
+            HyperLogLog::<::DefaultBuildHasher>::read_from(&mut Broken),
+            Err(DecodeError::Io(io::ErrorKind::PermissionDenied))
+        );
+    }
+}
@@ -0,0 +1,310 @@
+//! A sketch that stores each register in 4 bits, as an offset from a base
+//! shared by the whole sketch, after HLL-TailCut (Xiao, Zhou, and Chen,
+//! "Better with Fewer Bits", INFOCOM 2017).
+//!
+//! Register values cluster within a few ranks of `log2(n / m)`, so once every
+//! register has risen above the base, the base goes up by one and every
+//! offset down by one. Registers are then exact, except in the tail: a rank
+//! more than 15 above the base is cut to 15, which for any realistic
+//! cardinality happens to a handful of registers at most and moves the
+//! estimate by a tiny fraction of its standard error.
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hash};
+
+use error::MergeError;
+use {
+    check_register_count, estimate, index_and_rank, rank_histogram, standard_error,
+    DefaultBuildHasher, HyperLogLog,
+};
+
+const MAX_OFFSET: u8 = 15;
+
+/// A HyperLogLog sketch with 4-bit registers relative to a shared base,
+/// using half the memory of [`HyperLogLog`] and 2/3 that of
+/// [`PackedHyperLogLog`](::PackedHyperLogLog).
+///
+/// Estimates match a [`HyperLogLog`] fed the same items unless some register
+/// was cut; see [`TailCutHyperLogLog::cut_registers`].
+pub struct TailCutHyperLogLog<S = DefaultBuildHasher> {
+    /// Two offsets per byte, the even register in the low nibble.
+    offsets: Vec<u8>,
+    base: u8,
+    /// How many registers have offset 0, i.e. sit at the base.
+    at_base: usize,
+    /// How many times a rank was cut to fit.
+    cuts: u64,
+    precision: u8,
+    build_hasher: S,
+}
+
+impl TailCutHyperLogLog {
+    /// Creates a sketch with `m` registers; panics like [`HyperLogLog::new`].
+    pub fn new(m: usize) -> TailCutHyperLogLog {
+        TailCutHyperLogLog::with_hasher(m, DefaultBuildHasher::default())
+    }
+}
+
+impl<S> TailCutHyperLogLog<S> {
+    fn empty(precision: u8, build_hasher: S) -> TailCutHyperLogLog<S> {
+        let m = 1usize << precision;
+        TailCutHyperLogLog {
+            offsets: vec![0; m.div_ceil(2)],
+            base: 0,
+            at_base: m,
+            cuts: 0,
+            precision,
+            build_hasher,
+        }
+    }
+
+    fn num_registers(&self) -> usize {
+        1 << self.precision
+    }
+
+    fn offset(&self, i: usize) -> u8 {
+        self.offsets[i / 2] >> (i % 2 * 4) & 0xf
+    }
+
+    fn set_offset(&mut self, i: usize, offset: u8) {
+        let shift = i % 2 * 4;
+        let byte = &mut self.offsets[i / 2];
+        *byte = *byte & !(0xf << shift) | offset << shift;
+    }
+
+    /// Raises register `i` to `value` if that's higher, cutting it to fit.
+    fn raise(&mut self, i: usize, value: u8) {
+        if value <= self.base {
+            return;
+        }
+        let old = self.offset(i);
+        let mut offset = value - self.base;
+        if offset <= old {
+            return;
+        }
+        if offset > MAX_OFFSET {
+            self.cuts += 1;
+            offset = MAX_OFFSET;
+            if old == MAX_OFFSET {
+                return;
+            }
+        }
+        self.set_offset(i, offset);
+        if old == 0 {
+            self.at_base -= 1;
+            if self.at_base == 0 {
+                self.rebase();
+            }
+        }
+    }
+
+    /// Moves the base up for as long as no register sits at it.
+    fn rebase(&mut self) {
+        while self.at_base == 0 {
+            self.base += 1;
+            for i in 0..self.num_registers() {
+                let offset = self.offset(i) - 1;
+                self.set_offset(i, offset);
+                if offset == 0 {
+                    self.at_base += 1;
+                }
+            }
+        }
+    }
+
+    fn registers(&self) -> impl ExactSizeIterator<Item = u8> + '_ {
+        (0..self.num_registers()).map(move |i| self.base + self.offset(i))
+    }
+
+    /// The value every register is at least.
+    pub fn base(&self) -> u8 {
+        self.base
+    }
+
+    /// How many times an added or merged rank was more than 15 above the
+    /// base and was cut. While this is 0 the registers are exact.
+    pub fn cut_registers(&self) -> u64 {
+        self.cuts
+    }
+}
+
+impl<S: BuildHasher> TailCutHyperLogLog<S> {
+    /// Creates a sketch with `m` registers that hashes items with `build_hasher`.
+    pub fn with_hasher(m: usize, build_hasher: S) -> TailCutHyperLogLog<S> {
+        match check_register_count(m) {
+            Ok(p) => TailCutHyperLogLog::empty(p, build_hasher),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn add<H: Hash>(&mut self, item: H) {
+        let h = self.build_hasher.hash_one(item);
+        self.add_hashed(h);
+    }
+
+    /// Adds an item by its precomputed 64-bit hash; see [`HyperLogLog::add_hashed`].
+    pub fn add_hashed(&mut self, hash: u64) {
+        let (i, rank) = index_and_rank(hash, self.precision);
+        self.raise(i, rank);
+    }
+
+    /// Folds `other` into `self` by taking the register-wise maximum.
+    pub fn merge(&mut self, other: &TailCutHyperLogLog<S>) -> Result<(), MergeError> {
+        if self.precision != other.precision {
+            return Err(MergeError::PrecisionMismatch {
+                left: self.precision,
+                right: other.precision,
+            });
+        }
+        for (i, value) in other.registers().enumerate() {
+            self.raise(i, value);
+        }
+        self.cuts += other.cuts;
+        Ok(())
+    }
+
+    pub fn count(&self) -> f64 {
+        estimate(&rank_histogram(self.registers()))
+    }
+
+    pub fn error_estimate(&self) -> f64 {
+        standard_error(self.precision)
+    }
+}
+
+/// Compacts a sketch, cutting registers more than 15 above the smallest.
+impl<S> From<HyperLogLog<S>> for TailCutHyperLogLog<S> {
+    fn from(hll: HyperLogLog<S>) -> TailCutHyperLogLog<S> {
+        let m = hll.num_registers();
+        let mut compact = TailCutHyperLogLog::empty(hll.precision, hll.build_hasher);
+        compact.base = hll.registers.iter(m).min().unwrap_or(0);
+        compact.at_base = 0;
+        for (i, r) in hll.registers.iter(m).enumerate() {
+            let offset = r - compact.base;
+            if offset > MAX_OFFSET {
+                compact.cuts += 1;
+            }
+            compact.set_offset(i, offset.min(MAX_OFFSET));
+            if offset == 0 {
+                compact.at_base += 1;
+            }
+        }
+        compact
+    }
+}
+
+impl<S> From<TailCutHyperLogLog<S>> for HyperLogLog<S> {
+    fn from(compact: TailCutHyperLogLog<S>) -> HyperLogLog<S> {
+        let registers = compact.registers().collect();
+        HyperLogLog::from_dense(compact.precision, registers, compact.build_hasher)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TailCutHyperLogLog;
+    use simulation::splitmix64;
+    use HyperLogLog;
+
+    #[test]
+    fn offsets_share_bytes() {
+        let mut t = TailCutHyperLogLog::new(1 << 4);
+        for i in 0..16 {
+            t.set_offset(i, (i as u8 * 5) % 16);
+        }
+        for i in 0..16 {
+            assert_eq!(t.offset(i), (i as u8 * 5) % 16, "register {}", i);
+        }
+    }
+
+    #[test]
+    fn base_rises_and_registers_stay_exact() {
+        let mut compact = TailCutHyperLogLog::new(1 << 6);
+        let mut plain = HyperLogLog::new(1 << 6);
+        for i in 0..200_000u64 {
+            compact.add(i);
+            plain.add(i);
+        }
+        assert!(compact.base() >= 5, "base {}", compact.base());
+        assert_eq!(compact.cut_registers(), 0);
+        assert_eq!(
+            compact.registers().collect::<Vec<_>>(),
+            plain.registers().collect::<Vec<_>>()
+        );
+        assert_eq!(compact.count(), plain.count());
+    }
+
+    #[test]
+    fn cuts_the_tail() {
+        let mut registers = vec![3u8; 16];
+        registers[7] = 30;
+        let plain = HyperLogLog::from_dense(4, registers, Default::default());
+        let compact = TailCutHyperLogLog::from(plain);
+        assert_eq!(compact.base(), 3);
+        assert_eq!(compact.cut_registers(), 1);
+        let back: HyperLogLog = compact.into();
+        assert_eq!(back.registers().nth(7), Some(18));
+
+        let mut t = TailCutHyperLogLog::new(1 << 4);
+        t.raise(2, 40);
+        t.raise(2, 41);
+        assert_eq!(t.registers().nth(2), Some(15));
+        assert_eq!(t.cut_registers(), 2);
+    }
+
+    #[test]
+    fn accuracy_matches_the_plain_sketch() {
+        let mut state = 56;
+        for &n in &[100u64, 2_000, 50_000, 1_000_000, 5_000_000] {
+            let mut compact = TailCutHyperLogLog::new(1 << 12);
+            let mut plain = HyperLogLog::new(1 << 12);
+            for _ in 0..n {
+                let h = splitmix64(&mut state);
+                compact.add_hashed(h);
+                plain.add_hashed(h);
+            }
+            let (c, p) = (compact.count(), plain.count());
+            assert!(
+                (c - p).abs() <= 1e-3 * p,
+                "n {}: tail cut {} vs plain {}",
+                n,
+                c,
+                p
+            );
+            let error = (c - n as f64).abs() / n as f64;
+            assert!(error < 4. * compact.error_estimate(), "n {}: {}", n, c);
+        }
+    }
+
+    #[test]
+    fn four_bits_per_register() {
+        let compact = TailCutHyperLogLog::new(1 << 14);
+        assert_eq!(compact.offsets.len() * 8, 4 << 14);
+        assert_eq!(compact.offsets.capacity(), 1 << 13);
+    }
+
+    #[test]
+    fn merge_tail_cut() {
+        let mut a = TailCutHyperLogLog::new(1 << 8);
+        let mut b = TailCutHyperLogLog::new(1 << 8);
+        let mut both = HyperLogLog::new(1 << 8);
+        for i in 0..3_000u64 {
+            a.add(i);
+            both.add(i);
+        }
+        for i in 2_000..60_000u64 {
+            b.add(i);
+            both.add(i);
+        }
+        assert!(b.base() > a.base());
+        a.merge(&b).unwrap();
+        assert_eq!(a.base(), b.base());
+        assert_eq!(
+            HyperLogLog::from(a).registers().collect::<Vec<_>>(),
+            both.registers().collect::<Vec<_>>()
+        );
+
+        let mut small = TailCutHyperLogLog::new(1 << 4);
+        assert!(small.merge(&b).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+//! Measuring a sketch's estimation error empirically, for checking accuracy
+//! claims and choosing a precision. Enabled by the `testing` feature.
+//!
+//! Everything here is deterministic: the same seed builds the same sketches
+//! from the same items, so a failing accuracy check can be rerun exactly.
+
+use alloc::vec::Vec;
+
+use {math, BuiltinEstimator, Estimator, HyperLogLog};
+
+/// The error of one precision's estimates at one cardinality, over many
+/// independent sketches. Errors are relative: `(estimate - n) / n`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ErrorStats {
+    /// The number of distinct items each sketch was given.
+    pub cardinality: u64,
+    /// The number of sketches measured.
+    pub trials: usize,
+    /// The mean relative error, which is the estimator's bias.
+    pub mean_relative_error: f64,
+    /// The sample standard deviation of the relative error, comparable to
+    /// [`HyperLogLog::standard_error`]. Zero for a single trial.
+    pub std_dev: f64,
+    /// The relative error furthest from zero, of either sign.
+    pub worst_relative_error: f64,
+}
+
+/// Measures the error of [`HyperLogLog::count`] with `2^precision`
+/// registers at each of `cardinalities`, returning one [`ErrorStats`] per
+/// cardinality in the same order.
+///
+/// Each of the `trials` sketches gets its own hasher seed and its own
+/// pseudo-random distinct items, all derived from `seed`. A sketch is
+/// filled once up to the largest cardinality and counted on the way, so
+/// the cost is `trials` times that many additions.
+///
+/// Panics if `trials` or any cardinality is 0, or unless `2^precision` registers are a size
+/// [`HyperLogLog::new`] accepts.
+pub fn error_profile(
+    precision: u8,
+    cardinalities: &[u64],
+    trials: usize,
+    seed: u64,
+) -> Vec<ErrorStats> {
+    error_profile_with(
+        &BuiltinEstimator::default(),
+        precision,
+        cardinalities,
+        trials,
+        seed,
+    )
+}
+
+/// Like [`error_profile`], but measuring `estimator` instead of the default
+/// [`count`](HyperLogLog::count), on the same sketches.
+pub fn error_profile_with<E: Estimator + ?Sized>(
+    estimator: &E,
+    precision: u8,
+    cardinalities: &[u64],
+    trials: usize,
+    seed: u64,
+) -> Vec<ErrorStats> {
+    assert!(trials > 0, "an error profile needs at least one trial");
+    assert!(
+        !cardinalities.contains(&0),
+        "relative error is undefined at cardinality 0"
+    );
+    let m = 1usize.checked_shl(u32::from(precision)).unwrap_or(0);
+    let mut order: Vec<usize> = (0..cardinalities.len()).collect();
+    order.sort_by_key(|&i| cardinalities[i]);
+
+    // errors[i] holds every trial's relative error at cardinalities[i].
+    let mut errors = vec![Vec::with_capacity(trials); cardinalities.len()];
+    let mut rng = seed;
+    for _ in 0..trials {
+        let mut h = HyperLogLog::new_seeded(m, splitmix64(&mut rng));
+        // Splitmix64 outputs never repeat within one stream.
+        let mut items = splitmix64(&mut rng);
+        let mut added = 0;
+        for &i in &order {
+            let n = cardinalities[i];
+            while added < n {
+                h.add(splitmix64(&mut items));
+                added += 1;
+            }
+            errors[i].push((h.count_with(estimator) - n as f64) / n as f64);
+        }
+    }
+
+    errors
+        .iter()
+        .zip(cardinalities)
+        .map(|(errors, &cardinality)| stats(cardinality, errors))
+        .collect()
+}
+
+fn stats(cardinality: u64, errors: &[f64]) -> ErrorStats {
+    let trials = errors.len();
+    let mean = errors.iter().sum::<f64>() / trials as f64;
+    let variance = if trials > 1 {
+        errors.iter().map(|e| (e - mean) * (e - mean)).sum::<f64>() / (trials - 1) as f64
+    } else {
+        0.
+    };
+    let worst = errors.iter().cloned().fold(
+        0f64,
+        |worst, e| if e.abs() > worst.abs() { e } else { worst },
+    );
+    ErrorStats {
+        cardinality,
+        trials,
+        mean_relative_error: mean,
+        std_dev: math::sqrt(variance),
+        worst_relative_error: worst,
+    }
+}
+
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{error_profile, error_profile_with, stats};
+    use {BuiltinEstimator, ErtlEstimator};
+
+    #[test]
+    fn is_deterministic_and_keeps_the_input_order() {
+        let profile = error_profile(6, &[2_000, 50, 500], 5, 78);
+        assert_eq!(profile, error_profile(6, &[2_000, 50, 500], 5, 78));
+        assert_ne!(profile, error_profile(6, &[2_000, 50, 500], 5, 79));
+        let cardinalities: Vec<_> = profile.iter().map(|s| s.cardinality).collect();
+        assert_eq!(cardinalities, [2_000, 50, 500]);
+        // The same sketches, counted at fewer points.
+        assert_eq!(error_profile(6, &[500], 5, 78)[0], profile[2]);
+        // The default estimator is what `count` uses; others see the same sketches.
+        let default = BuiltinEstimator::default();
+        assert_eq!(
+            error_profile_with(&default, 6, &[2_000, 50, 500], 5, 78),
+            profile
+        );
+        assert_ne!(
+            error_profile_with(&ErtlEstimator, 6, &[2_000, 50, 500], 5, 78),
+            profile
+        );
+    }
+
+    #[test]
+    fn summarizes_errors() {
+        let s = stats(10, &[0.1, -0.3, 0.2]);
+        assert_eq!(s.trials, 3);
+        assert!(s.mean_relative_error.abs() < 1e-15);
+        assert!((s.std_dev - 0.07f64.sqrt()).abs() < 1e-15);
+        assert_eq!(s.worst_relative_error, -0.3);
+        assert_eq!(stats(10, &[0.25]).std_dev, 0.);
+    }
+}
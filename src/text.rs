@@ -0,0 +1,179 @@
+//! Sketches as text, for storing them in text columns, JSON, or URLs.
+//!
+//! The text is the compressed binary encoding from
+//! [`HyperLogLog::to_bytes_compressed`] in base64 with the URL-safe alphabet
+//! (`-` and `_` for 62 and 63) and no `=` padding, so it needs no escaping
+//! in a query string.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+use error::DecodeError;
+use {HyperLogLog, StableBuildHasher};
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let mut group = [0u8; 3];
+        group[..chunk.len()].copy_from_slice(chunk);
+        let n = u32::from(group[0]) << 16 | u32::from(group[1]) << 8 | u32::from(group[2]);
+        for i in 0..=chunk.len() {
+            out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+        }
+    }
+    out
+}
+
+fn sextet(c: u8) -> Option<u32> {
+    let v = match c {
+        b'A'..=b'Z' => c - b'A',
+        b'a'..=b'z' => c - b'a' + 26,
+        b'0'..=b'9' => c - b'0' + 52,
+        b'-' => 62,
+        b'_' => 63,
+        _ => return None,
+    };
+    Some(u32::from(v))
+}
+
+/// Decodes unpadded URL-safe base64, rejecting any other alphabet, padding,
+/// and encodings whose unused trailing bits aren't zero.
+fn decode(text: &str) -> Result<Vec<u8>, DecodeError> {
+    let text = text.as_bytes();
+    if text.len() % 4 == 1 {
+        return Err(DecodeError::InvalidBase64 {
+            index: text.len() - 1,
+        });
+    }
+    let mut out = Vec::with_capacity(text.len() / 4 * 3 + 2);
+    for (c, chunk) in text.chunks(4).enumerate() {
+        let mut n = 0u32;
+        for (i, &b) in chunk.iter().enumerate() {
+            let index = 4 * c + i;
+            n |= sextet(b).ok_or(DecodeError::InvalidBase64 { index })? << (18 - 6 * i);
+        }
+        let len = chunk.len() - 1;
+        if n & (0xff_ffff >> (8 * len)) != 0 {
+            return Err(DecodeError::InvalidBase64 { index: 4 * c + len });
+        }
+        out.extend_from_slice(&n.to_be_bytes()[1..=len]);
+    }
+    Ok(out)
+}
+
+impl<S: StableBuildHasher> HyperLogLog<S> {
+    /// Encodes the sketch as URL-safe, unpadded base64 text. This is also
+    /// what `Display` writes.
+    pub fn to_base64(&self) -> String {
+        encode(&self.to_bytes_compressed())
+    }
+
+    /// Decodes text written by [`HyperLogLog::to_base64`]. Also available
+    /// through `str::parse`.
+    ///
+    /// Text that isn't base64 is rejected with
+    /// [`DecodeError::InvalidBase64`]; base64 that doesn't hold a sketch
+    /// fails as [`HyperLogLog::from_bytes`] does.
+    pub fn from_base64(text: &str) -> Result<HyperLogLog<S>, DecodeError> {
+        HyperLogLog::from_bytes(&decode(text)?)
+    }
+}
+
+impl<S: StableBuildHasher> fmt::Display for HyperLogLog<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_base64())
+    }
+}
+
+impl<S: StableBuildHasher> FromStr for HyperLogLog<S> {
+    type Err = DecodeError;
+
+    fn from_str(text: &str) -> Result<HyperLogLog<S>, DecodeError> {
+        HyperLogLog::from_base64(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode};
+    use error::DecodeError;
+    use {max_rank, HyperLogLog, SeededState};
+
+    #[test]
+    fn base64_matches_rfc_4648() {
+        let cases: [(&[u8], &str); 7] = [
+            (b"", ""),
+            (b"f", "Zg"),
+            (b"fo", "Zm8"),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg"),
+            (b"fooba", "Zm9vYmE"),
+            (&[0xfb, 0xff, 0xbf], "-_-_"),
+        ];
+        for &(bytes, text) in &cases {
+            assert_eq!(encode(bytes), text);
+            assert_eq!(decode(text).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn known_answer() {
+        let registers = (0..16).map(|i| i % 5).collect();
+        let h = HyperLogLog::from_dense(4, registers, SeededState::with_keys(1, 2));
+        let text = "SExMUgIEAQEAAAAAAAAAAgAAAAAAAAAAEjQAEjQAEjQA";
+        assert_eq!(h.to_base64(), text);
+        assert_eq!(h.to_string(), text);
+        let back: HyperLogLog<SeededState> = text.parse().unwrap();
+        assert_eq!(back, h);
+    }
+
+    #[test]
+    fn round_trips() {
+        let empty = HyperLogLog::new_seeded(1 << 12, 7);
+        let mut small = empty.clone();
+        for i in 0..20u32 {
+            small.add(i);
+        }
+        let saturated =
+            HyperLogLog::from_dense(12, vec![max_rank(12); 1 << 12], SeededState::new(7));
+        for h in &[empty, small, saturated] {
+            let back = HyperLogLog::<SeededState>::from_base64(&h.to_base64()).unwrap();
+            assert_eq!(&back, h);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_text() {
+        let decode = |s: &str| HyperLogLog::<SeededState>::from_base64(s).map(|_| ());
+        let good = HyperLogLog::new_seeded(1 << 4, 3).to_base64();
+
+        assert_eq!(
+            decode(&good.replacen('A', "+", 1)),
+            Err(DecodeError::InvalidBase64 {
+                index: good.find('A').unwrap()
+            })
+        );
+        assert_eq!(
+            decode(&format!("{}=", good)),
+            Err(DecodeError::InvalidBase64 { index: good.len() })
+        );
+        assert_eq!(decode("Zh"), Err(DecodeError::InvalidBase64 { index: 1 }));
+        assert_eq!(
+            decode("Zm9vY"),
+            Err(DecodeError::InvalidBase64 { index: 4 })
+        );
+        // Valid base64, but not a sketch.
+        assert!(matches!(
+            decode("Zm9vYmFy"),
+            Err(DecodeError::Truncated { .. })
+        ));
+        assert!(matches!(
+            decode("Zm9vYmFyYmF6"),
+            Err(DecodeError::BadMagic(_))
+        ));
+    }
+}
@@ -0,0 +1,278 @@
+//! A sketch that only accepts items of one type, so that sketches of
+//! different kinds of item can't be merged by mistake.
+//!
+//! The tagged encoding is the sketch as [`HyperLogLog::to_bytes`] writes
+//! it, behind a prefix naming the item type (integers little-endian):
+//!
+//! | offset | size | contents               |
+//! |--------|------|------------------------|
+//! | 0      | 4    | magic `b"HLLT"`        |
+//! | 4      | 2    | tag length `n`         |
+//! | 6      | `n`  | the tag, UTF-8         |
+//! | 6 + n  | ...  | the sketch             |
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+
+use error::{DecodeError, MergeError};
+use {DefaultBuildHasher, HyperLogLog, StableBuildHasher};
+
+const TAGGED_MAGIC: [u8; 4] = *b"HLLT";
+const TAG_AT: usize = 6;
+
+/// A [`HyperLogLog`] of items of type `T`.
+///
+/// `add` takes only `&T`, and `merge` only another sketch of `T`:
+///
+/// ```compile_fail
+/// use hyperloglog::TypedHyperLogLog;
+///
+/// #[derive(Hash)]
+/// struct UserId(u64);
+/// #[derive(Hash)]
+/// struct SessionId(u64);
+///
+/// let mut users = TypedHyperLogLog::<UserId>::new(1 << 10);
+/// let sessions = TypedHyperLogLog::<SessionId>::new(1 << 10);
+/// users.merge(&sessions);
+/// ```
+///
+/// The wrapper is the same size as the sketch, and everything else is left
+/// to it: see [`as_untyped`](TypedHyperLogLog::as_untyped) and
+/// [`into_untyped`](TypedHyperLogLog::into_untyped).
+pub struct TypedHyperLogLog<T: ?Sized, S = DefaultBuildHasher> {
+    inner: HyperLogLog<S>,
+    /// A function-pointer marker, so that `T` affects neither auto
+    /// traits nor drop checking.
+    items: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized> TypedHyperLogLog<T> {
+    /// Creates a sketch with `m` registers; panics like [`HyperLogLog::new`].
+    pub fn new(m: usize) -> TypedHyperLogLog<T> {
+        TypedHyperLogLog::from_untyped(HyperLogLog::new(m))
+    }
+}
+
+impl<T: ?Sized, S> TypedHyperLogLog<T, S> {
+    /// Wraps a sketch, vouching that every item added to it was a `T`.
+    pub fn from_untyped(inner: HyperLogLog<S>) -> TypedHyperLogLog<T, S> {
+        TypedHyperLogLog {
+            inner,
+            items: PhantomData,
+        }
+    }
+
+    /// The sketch, for the operations the wrapper doesn't forward.
+    pub fn as_untyped(&self) -> &HyperLogLog<S> {
+        &self.inner
+    }
+
+    /// Unwraps the sketch, giving up the type check.
+    pub fn into_untyped(self) -> HyperLogLog<S> {
+        self.inner
+    }
+
+    pub fn precision(&self) -> u8 {
+        self.inner.precision()
+    }
+}
+
+impl<T: Hash + ?Sized, S: BuildHasher> TypedHyperLogLog<T, S> {
+    /// Creates a sketch with `m` registers that hashes items with `build_hasher`.
+    pub fn with_hasher(m: usize, build_hasher: S) -> TypedHyperLogLog<T, S> {
+        TypedHyperLogLog::from_untyped(HyperLogLog::with_hasher(m, build_hasher))
+    }
+
+    pub fn add(&mut self, item: &T) {
+        self.inner.add(item);
+    }
+
+    /// Folds `other` into `self`, returning whether any register rose; see
+    /// [`HyperLogLog::merge`].
+    pub fn merge(&mut self, other: &TypedHyperLogLog<T, S>) -> Result<bool, MergeError> {
+        self.inner.merge(&other.inner)
+    }
+
+    pub fn count(&self) -> f64 {
+        self.inner.count()
+    }
+
+    pub fn error_estimate(&self) -> f64 {
+        self.inner.error_estimate()
+    }
+}
+
+impl<T: ?Sized, S: StableBuildHasher> TypedHyperLogLog<T, S> {
+    /// Encodes the sketch as [`HyperLogLog::to_bytes`] does, without a tag.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes()
+    }
+
+    /// Decodes an untagged sketch, which is trusted to hold `T`s.
+    pub fn from_bytes(bytes: &[u8]) -> Result<TypedHyperLogLog<T, S>, DecodeError> {
+        HyperLogLog::from_bytes(bytes).map(TypedHyperLogLog::from_untyped)
+    }
+
+    /// Encodes the sketch behind `tag`, a name for `T` that
+    /// [`from_bytes_tagged`](TypedHyperLogLog::from_bytes_tagged) checks.
+    ///
+    /// Panics if `tag` is longer than 65535 bytes.
+    pub fn to_bytes_tagged(&self, tag: &str) -> Vec<u8> {
+        let len = match u16::try_from(tag.len()) {
+            Ok(len) => len,
+            Err(_) => panic!("a type tag is at most 65535 bytes, not {}", tag.len()),
+        };
+        let sketch = self.inner.to_bytes();
+        let mut out = Vec::with_capacity(TAG_AT + tag.len() + sketch.len());
+        out.extend_from_slice(&TAGGED_MAGIC);
+        out.extend_from_slice(&len.to_le_bytes());
+        out.extend_from_slice(tag.as_bytes());
+        out.extend(sketch);
+        out
+    }
+
+    /// Decodes bytes written by
+    /// [`to_bytes_tagged`](TypedHyperLogLog::to_bytes_tagged), failing with
+    /// [`DecodeError::TagMismatch`] unless they were tagged with `tag`.
+    pub fn from_bytes_tagged(
+        bytes: &[u8],
+        tag: &str,
+    ) -> Result<TypedHyperLogLog<T, S>, DecodeError> {
+        if bytes.len() < TAG_AT {
+            return Err(DecodeError::Truncated {
+                expected: TAG_AT,
+                actual: bytes.len(),
+            });
+        }
+        if bytes[..4] != TAGGED_MAGIC {
+            let mut magic = [0u8; 4];
+            magic.copy_from_slice(&bytes[..4]);
+            return Err(DecodeError::BadMagic(magic));
+        }
+        let sketch_at = TAG_AT + usize::from(u16::from_le_bytes([bytes[4], bytes[5]]));
+        if bytes.len() < sketch_at {
+            return Err(DecodeError::Truncated {
+                expected: sketch_at,
+                actual: bytes.len(),
+            });
+        }
+        let found = &bytes[TAG_AT..sketch_at];
+        if found != tag.as_bytes() {
+            return Err(DecodeError::TagMismatch {
+                expected: tag.into(),
+                found: String::from_utf8_lossy(found).into_owned(),
+            });
+        }
+        TypedHyperLogLog::from_bytes(&bytes[sketch_at..])
+    }
+}
+
+impl<T: ?Sized, S: Clone> Clone for TypedHyperLogLog<T, S> {
+    fn clone(&self) -> TypedHyperLogLog<T, S> {
+        TypedHyperLogLog::from_untyped(self.inner.clone())
+    }
+}
+
+impl<T: ?Sized, S> PartialEq for TypedHyperLogLog<T, S> {
+    fn eq(&self, other: &TypedHyperLogLog<T, S>) -> bool {
+        self.inner == other.inner
+    }
+}
+
+impl<T: ?Sized, S> Eq for TypedHyperLogLog<T, S> {}
+
+impl<T: ?Sized, S> fmt::Debug for TypedHyperLogLog<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("TypedHyperLogLog")
+            .field(&self.inner)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypedHyperLogLog;
+    use alloc::string::String;
+    use core::mem;
+    use error::DecodeError;
+    use HyperLogLog;
+
+    #[derive(Hash)]
+    struct UserId(u64);
+
+    #[test]
+    fn wraps_without_cost() {
+        assert_eq!(
+            mem::size_of::<TypedHyperLogLog<UserId>>(),
+            mem::size_of::<HyperLogLog>()
+        );
+        assert_eq!(
+            mem::size_of::<TypedHyperLogLog<str>>(),
+            mem::size_of::<HyperLogLog>()
+        );
+
+        let mut typed = TypedHyperLogLog::new(1 << 10);
+        let mut plain = HyperLogLog::new(1 << 10);
+        for i in 0..5_000 {
+            typed.add(&UserId(i));
+            plain.add(UserId(i));
+        }
+        assert_eq!(typed.count(), plain.count());
+        let mut other = TypedHyperLogLog::new(1 << 10);
+        other.add(&UserId(9_999));
+        typed.merge(&other).unwrap();
+        plain.add(UserId(9_999));
+        assert_eq!(typed.as_untyped(), &plain);
+        assert_eq!(typed.into_untyped(), plain);
+    }
+
+    #[test]
+    fn unsized_items() {
+        let mut names = TypedHyperLogLog::<str>::new(1 << 10);
+        names.add("alice");
+        names.add(&String::from("bob"));
+        let mut plain = HyperLogLog::new(1 << 10);
+        plain.add("alice");
+        plain.add("bob");
+        assert_eq!(names.as_untyped(), &plain);
+    }
+
+    #[test]
+    fn tagged_round_trip() {
+        let mut typed = TypedHyperLogLog::new(1 << 8);
+        (0..300).for_each(|i| typed.add(&UserId(i)));
+        let bytes = typed.to_bytes_tagged("user_id");
+        assert_eq!(&bytes[..6], b"HLLT\x07\x00");
+        assert_eq!(&bytes[13..], &typed.to_bytes()[..]);
+        assert_eq!(
+            TypedHyperLogLog::<UserId>::from_bytes_tagged(&bytes, "user_id").unwrap(),
+            typed
+        );
+
+        assert_eq!(
+            TypedHyperLogLog::<UserId>::from_bytes_tagged(&bytes, "session_id"),
+            Err(DecodeError::TagMismatch {
+                expected: "session_id".into(),
+                found: "user_id".into(),
+            })
+        );
+        assert_eq!(
+            TypedHyperLogLog::<UserId>::from_bytes_tagged(&bytes[..10], "user_id"),
+            Err(DecodeError::Truncated {
+                expected: 13,
+                actual: 10,
+            })
+        );
+        // Untagged bytes aren't accepted as tagged, nor the other way around.
+        assert!(matches!(
+            TypedHyperLogLog::<UserId>::from_bytes_tagged(&typed.to_bytes(), "user_id"),
+            Err(DecodeError::BadMagic(_))
+        ));
+        assert!(TypedHyperLogLog::<UserId>::from_bytes(&bytes).is_err());
+    }
+}
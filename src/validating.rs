@@ -0,0 +1,238 @@
+//! A sketch run alongside exact counting, for measuring its error on real
+//! traffic before relying on it.
+
+use core::fmt;
+use core::hash::{BuildHasher, Hash};
+use core::marker::PhantomData;
+use std::collections::HashSet;
+
+use {standard_error, DefaultBuildHasher, HyperLogLog};
+
+/// A [`HyperLogLog`] of items of type `T` that also keeps the exact set of
+/// their hashes, to compare the estimate against.
+///
+/// Each item is hashed once, with the sketch's hasher, and the hash goes to
+/// both sides, so the exact side counts exactly what the sketch sees: at
+/// 64 bits, hash collisions between distinct items are too rare to matter
+/// below billions of items. The exact side costs about 16 bytes per
+/// distinct item; [`set_exact_limit`](ValidatingHyperLogLog::set_exact_limit)
+/// caps it.
+pub struct ValidatingHyperLogLog<T: ?Sized, S = DefaultBuildHasher> {
+    inner: HyperLogLog<S>,
+    exact: HashSet<u64>,
+    limit: Option<usize>,
+    truncated: bool,
+    /// A function-pointer marker, so that `T` affects neither auto
+    /// traits nor drop checking.
+    items: PhantomData<fn(&T)>,
+}
+
+/// The estimate of a [`ValidatingHyperLogLog`] next to the exact count,
+/// from [`report`](ValidatingHyperLogLog::report).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValidationReport {
+    /// The sketch's [`count`](HyperLogLog::count).
+    pub estimate: f64,
+    /// The number of distinct items added, or of those added before the
+    /// exact side stopped if `truncated`.
+    pub exact: u64,
+    /// `estimate - exact`, positive when the sketch overcounts.
+    pub absolute_error: f64,
+    /// `absolute_error / exact`: 0 when both are 0, and infinite when
+    /// only `exact` is.
+    pub relative_error: f64,
+    pub precision: u8,
+    /// The relative standard error expected at this precision,
+    /// `1.04 / √(2^precision)`, to compare `relative_error` against.
+    pub standard_error: f64,
+    /// Whether the exact side hit its limit and stopped, leaving `exact`
+    /// a lower bound and the errors meaningless.
+    pub truncated: bool,
+}
+
+impl<T: ?Sized> ValidatingHyperLogLog<T> {
+    /// Creates an empty sketch with `m` registers and no limit on the exact
+    /// side; panics like [`HyperLogLog::new`].
+    pub fn new(m: usize) -> ValidatingHyperLogLog<T> {
+        ValidatingHyperLogLog::with_hasher(m, DefaultBuildHasher::default())
+    }
+}
+
+impl<T: ?Sized, S> ValidatingHyperLogLog<T, S> {
+    /// Stops the exact side once it holds `hashes` distinct hashes: the
+    /// next new one marks it [truncated](ValidationReport::truncated) and is
+    /// only added to the sketch, as is everything after it. A side already
+    /// past the limit is truncated at once. `None` lifts the limit, but
+    /// doesn't resume tracking.
+    pub fn set_exact_limit(&mut self, hashes: Option<usize>) {
+        self.limit = hashes;
+        if hashes.is_some_and(|limit| self.exact.len() > limit) {
+            self.truncated = true;
+        }
+    }
+
+    /// The number of distinct items added; once
+    /// [truncated](ValidationReport::truncated), only those added before
+    /// tracking stopped.
+    pub fn exact(&self) -> u64 {
+        self.exact.len() as u64
+    }
+
+    /// The production sketch, for the operations the wrapper doesn't
+    /// forward.
+    pub fn as_inner(&self) -> &HyperLogLog<S> {
+        &self.inner
+    }
+
+    /// Unwraps the production sketch, dropping the exact side.
+    pub fn into_inner(self) -> HyperLogLog<S> {
+        self.inner
+    }
+}
+
+impl<T: ?Sized, S: BuildHasher> ValidatingHyperLogLog<T, S> {
+    /// Creates an empty sketch with `m` registers that hashes items with
+    /// `build_hasher`; panics like [`HyperLogLog::with_hasher`].
+    pub fn with_hasher(m: usize, build_hasher: S) -> ValidatingHyperLogLog<T, S> {
+        ValidatingHyperLogLog {
+            inner: HyperLogLog::with_hasher(m, build_hasher),
+            exact: HashSet::new(),
+            limit: None,
+            truncated: false,
+            items: PhantomData,
+        }
+    }
+
+    /// The sketch's estimate, what production would see.
+    pub fn estimate(&self) -> f64 {
+        self.inner.count()
+    }
+
+    /// The relative error of the estimate, positive when it overcounts; see
+    /// [`ValidationReport::relative_error`].
+    pub fn observed_error(&self) -> f64 {
+        self.report().relative_error
+    }
+
+    /// The estimate and the exact count side by side, with the errors
+    /// between them.
+    pub fn report(&self) -> ValidationReport {
+        let estimate = self.estimate();
+        let exact = self.exact();
+        let absolute_error = estimate - exact as f64;
+        let relative_error = if exact > 0 {
+            absolute_error / exact as f64
+        } else if estimate == 0. {
+            0.
+        } else {
+            f64::INFINITY
+        };
+        ValidationReport {
+            estimate,
+            exact,
+            absolute_error,
+            relative_error,
+            precision: self.inner.precision(),
+            standard_error: standard_error(self.inner.precision()),
+            truncated: self.truncated,
+        }
+    }
+}
+
+impl<T: Hash + ?Sized, S: BuildHasher> ValidatingHyperLogLog<T, S> {
+    /// Adds the item to the sketch and, unless it has stopped, the exact
+    /// side.
+    pub fn add(&mut self, item: &T) {
+        let hash = self.inner.build_hasher.hash_one(item);
+        self.inner.add_hashed(hash);
+        if self.truncated || self.exact.contains(&hash) {
+            return;
+        }
+        if self.limit.is_some_and(|limit| self.exact.len() >= limit) {
+            self.truncated = true;
+        } else {
+            self.exact.insert(hash);
+        }
+    }
+}
+
+impl<T: ?Sized, S> fmt::Debug for ValidatingHyperLogLog<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ValidatingHyperLogLog")
+            .field("precision", &self.inner.precision())
+            .field("exact", &self.exact.len())
+            .field("truncated", &self.truncated)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ValidatingHyperLogLog, ValidationReport};
+    use simulation::splitmix64;
+    use {standard_error, HyperLogLog};
+
+    #[test]
+    fn reports_exact_and_estimated_counts() {
+        let mut state = 96;
+        let mut v = ValidatingHyperLogLog::<u64>::new(1 << 12);
+        let mut plain = HyperLogLog::new(1 << 12);
+        assert_eq!(v.report().relative_error, 0.);
+        // 20,000 distinct items, each added one to three times.
+        for i in 0..20_000u64 {
+            for _ in 0..1 + splitmix64(&mut state) % 3 {
+                v.add(&i);
+                plain.add(i);
+            }
+        }
+        let report = v.report();
+        assert_eq!(
+            report,
+            ValidationReport {
+                estimate: plain.count(),
+                exact: 20_000,
+                absolute_error: plain.count() - 20_000.,
+                relative_error: (plain.count() - 20_000.) / 20_000.,
+                precision: 12,
+                standard_error: standard_error(12),
+                truncated: false,
+            }
+        );
+        assert_eq!(v.estimate(), report.estimate);
+        assert_eq!(v.exact(), report.exact);
+        assert_eq!(v.observed_error(), report.relative_error);
+        assert!(report.relative_error.abs() < 4. * report.standard_error);
+        assert_eq!(v.into_inner(), plain);
+    }
+
+    #[test]
+    fn stops_tracking_at_the_limit() {
+        let mut v = ValidatingHyperLogLog::<str>::new(1 << 8);
+        v.set_exact_limit(Some(100));
+        let words: Vec<String> = (0..1_000).map(|i| format!("w{}", i)).collect();
+        for w in &words[..100] {
+            v.add(w);
+            v.add(w);
+        }
+        assert!(!v.report().truncated);
+        v.add(&words[100]);
+        assert!(v.report().truncated);
+        for w in &words {
+            v.add(w);
+        }
+        let mut plain = HyperLogLog::new(1 << 8);
+        words.iter().for_each(|w| plain.add(w.as_str()));
+        let report = v.report();
+        assert_eq!((report.exact, report.estimate), (100, plain.count()));
+        assert!(report.estimate > 500.);
+
+        // Lowering the limit below what is held truncates at once.
+        let mut v = ValidatingHyperLogLog::<str>::new(1 << 8);
+        words[..10].iter().for_each(|w| v.add(w));
+        v.set_exact_limit(Some(10));
+        assert!(!v.report().truncated);
+        v.set_exact_limit(Some(9));
+        assert!(v.report().truncated);
+        assert_eq!(v.exact(), 10);
+    }
+}
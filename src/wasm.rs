@@ -0,0 +1,163 @@
+//! JavaScript bindings through `wasm-bindgen`, for counting in the browser
+//! and shipping only the sketch to a backend.
+//!
+//! From JavaScript the class is `HyperLogLog`:
+//!
+//! ```js
+//! const h = new HyperLogLog(14);
+//! h.add("user-17");
+//! h.addBytes(new Uint8Array([1, 2, 3]));
+//! const bytes = h.toBytes();
+//! ```
+//!
+//! Items are hashed with SipHash-2-4 under [`WASM_SEED`], not the default
+//! hasher, whose output may change between Rust releases and so between the
+//! build in the browser and the one on the server. Strings are hashed as
+//! their UTF-8 bytes, so `add(s)` and `addBytes` of the same bytes count as
+//! one item, and a Rust backend that decodes `toBytes` with
+//! `HyperLogLog::<SeededState>::from_bytes` and adds with
+//! [`add_bytes`](::HyperLogLog::add_bytes) agrees with the browser.
+
+use wasm_bindgen::prelude::*;
+
+use {HyperLogLog, SeededState};
+
+/// The seed for [`SeededState::new`] that every sketch created from
+/// JavaScript hashes items with.
+pub const WASM_SEED: u64 = 0;
+
+/// A [`HyperLogLog`] usable from JavaScript.
+#[wasm_bindgen(js_name = HyperLogLog)]
+#[derive(Clone, Debug)]
+pub struct WasmHyperLogLog {
+    inner: HyperLogLog<SeededState>,
+}
+
+#[wasm_bindgen(js_class = HyperLogLog)]
+impl WasmHyperLogLog {
+    /// Creates a sketch with `2^precision` registers; throws unless
+    /// `precision` is from 4 to 24.
+    #[wasm_bindgen(constructor)]
+    pub fn new(precision: u8) -> Result<WasmHyperLogLog, JsError> {
+        let inner = HyperLogLog::builder()
+            .precision(precision)
+            .seed(WASM_SEED)
+            .build()
+            .map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(WasmHyperLogLog { inner })
+    }
+
+    /// Adds a string, hashed as its UTF-8 bytes.
+    pub fn add(&mut self, item: &str) {
+        self.inner.add_bytes(item);
+    }
+
+    #[wasm_bindgen(js_name = addBytes)]
+    pub fn add_bytes(&mut self, bytes: &[u8]) {
+        self.inner.add_bytes(bytes);
+    }
+
+    pub fn count(&self) -> f64 {
+        self.inner.count()
+    }
+
+    /// Encodes the sketch in the crate's binary format; see
+    /// [`HyperLogLog::to_bytes`].
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.inner.to_bytes()
+    }
+
+    /// Decodes a sketch written by `toBytes`, throwing on malformed input.
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmHyperLogLog, JsError> {
+        let inner = HyperLogLog::from_bytes(bytes).map_err(|e| JsError::new(&e.to_string()))?;
+        Ok(WasmHyperLogLog { inner })
+    }
+
+    /// Folds `other` into this sketch, throwing if their precisions differ.
+    pub fn merge(&mut self, other: &WasmHyperLogLog) -> Result<(), JsError> {
+        self.inner
+            .merge(&other.inner)
+            .map(drop)
+            .map_err(|e| JsError::new(&e.to_string()))
+    }
+}
+
+impl WasmHyperLogLog {
+    /// The wrapped sketch.
+    pub fn sketch(&self) -> &HyperLogLog<SeededState> {
+        &self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    use super::{WasmHyperLogLog, WASM_SEED};
+    use {HyperLogLog, SeededState};
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn add_and_count() {
+        let mut h = WasmHyperLogLog::new(12).unwrap();
+        for i in 0..1_000 {
+            h.add(&format!("user-{}", i));
+        }
+        h.add("user-0");
+        h.add_bytes(b"user-1");
+        let count = h.count();
+        assert!((count - 1_000.).abs() < 30., "counted {}", count);
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn bytes_round_trip_and_match_a_rust_sketch() {
+        let mut h = WasmHyperLogLog::new(10).unwrap();
+        let mut native = HyperLogLog::new_seeded(1 << 10, WASM_SEED);
+        for i in 0..5_000 {
+            let item = format!("event-{}", i);
+            h.add(&item);
+            native.add_bytes(&item);
+        }
+        let bytes = h.to_bytes();
+        assert_eq!(bytes, native.to_bytes());
+
+        let restored = WasmHyperLogLog::from_bytes(&bytes).unwrap();
+        assert_eq!(restored.count(), h.count());
+        let decoded = HyperLogLog::<SeededState>::from_bytes(&bytes).unwrap();
+        assert_eq!(&decoded, restored.sketch());
+    }
+
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test)]
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    fn merge_combines_sketches() {
+        let mut a = WasmHyperLogLog::new(10).unwrap();
+        let mut b = WasmHyperLogLog::new(10).unwrap();
+        let mut both = WasmHyperLogLog::new(10).unwrap();
+        for i in 0..3_000 {
+            let item = i.to_string();
+            if i < 2_000 {
+                a.add(&item);
+            }
+            if i >= 1_000 {
+                b.add(&item);
+            }
+            both.add(&item);
+        }
+        a.merge(&b).unwrap();
+        assert_eq!(a.count(), both.count());
+    }
+
+    // Building a `JsError` needs a JavaScript host.
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn errors_throw() {
+        assert!(WasmHyperLogLog::new(3).is_err());
+        assert!(WasmHyperLogLog::from_bytes(&[1, 2, 3]).is_err());
+        let mut a = WasmHyperLogLog::new(10).unwrap();
+        assert!(a.merge(&WasmHyperLogLog::new(12).unwrap()).is_err());
+    }
+}
@@ -0,0 +1,422 @@
+//! The 64-bit XXH3 hash (xxHash 0.8), for sketches that must agree with ones
+//! built by other xxHash implementations, such as Go's `zeebo/xxh3` or
+//! `XXH3_64bits_withSeed` in C.
+//!
+//! XXH3 hashes inputs of up to 240 bytes with a handful of multiplications
+//! against the 192-byte default secret, and longer ones in 1 KiB blocks of
+//! eight parallel accumulators. A nonzero seed is folded into the secret.
+
+use alloc::vec::Vec;
+use core::hash::{BuildHasher, Hasher};
+
+use {HashBackend, StableBuildHasher};
+
+const PRIME32_1: u64 = 0x9e37_79b1;
+const PRIME32_2: u64 = 0x85eb_ca77;
+const PRIME32_3: u64 = 0xc2b2_ae3d;
+const PRIME64_1: u64 = 0x9e37_79b1_85eb_ca87;
+const PRIME64_2: u64 = 0xc2b2_ae3d_27d4_eb4f;
+const PRIME64_3: u64 = 0x1656_67b1_9e37_79f9;
+const PRIME64_4: u64 = 0x85eb_ca77_c2b2_ae63;
+const PRIME64_5: u64 = 0x27d4_eb2f_1656_67c5;
+const PRIME_MX1: u64 = 0x1656_6791_9e37_79f9;
+const PRIME_MX2: u64 = 0x9fb2_1c65_1e98_df25;
+
+const SECRET_SIZE: usize = 192;
+const STRIPE_LEN: usize = 64;
+const STRIPES_PER_BLOCK: usize = (SECRET_SIZE - STRIPE_LEN) / 8;
+const BLOCK_LEN: usize = STRIPE_LEN * STRIPES_PER_BLOCK;
+
+const DEFAULT_SECRET: [u8; SECRET_SIZE] = [
+    0xb8, 0xfe, 0x6c, 0x39, 0x23, 0xa4, 0x4b, 0xbe, 0x7c, 0x01, 0x81, 0x2c, 0xf7, 0x21, 0xad, 0x1c,
+    0xde, 0xd4, 0x6d, 0xe9, 0x83, 0x90, 0x97, 0xdb, 0x72, 0x40, 0xa4, 0xa4, 0xb7, 0xb3, 0x67, 0x1f,
+    0xcb, 0x79, 0xe6, 0x4e, 0xcc, 0xc0, 0xe5, 0x78, 0x82, 0x5a, 0xd0, 0x7d, 0xcc, 0xff, 0x72, 0x21,
+    0xb8, 0x08, 0x46, 0x74, 0xf7, 0x43, 0x24, 0x8e, 0xe0, 0x35, 0x90, 0xe6, 0x81, 0x3a, 0x26, 0x4c,
+    0x3c, 0x28, 0x52, 0xbb, 0x91, 0xc3, 0x00, 0xcb, 0x88, 0xd0, 0x65, 0x8b, 0x1b, 0x53, 0x2e, 0xa3,
+    0x71, 0x64, 0x48, 0x97, 0xa2, 0x0d, 0xf9, 0x4e, 0x38, 0x19, 0xef, 0x46, 0xa9, 0xde, 0xac, 0xd8,
+    0xa8, 0xfa, 0x76, 0x3f, 0xe3, 0x9c, 0x34, 0x3f, 0xf9, 0xdc, 0xbb, 0xc7, 0xc7, 0x0b, 0x4f, 0x1d,
+    0x8a, 0x51, 0xe0, 0x4b, 0xcd, 0xb4, 0x59, 0x31, 0xc8, 0x9f, 0x7e, 0xc9, 0xd9, 0x78, 0x73, 0x64,
+    0xea, 0xc5, 0xac, 0x83, 0x34, 0xd3, 0xeb, 0xc3, 0xc5, 0x81, 0xa0, 0xff, 0xfa, 0x13, 0x63, 0xeb,
+    0x17, 0x0d, 0xdd, 0x51, 0xb7, 0xf0, 0xda, 0x49, 0xd3, 0x16, 0x55, 0x26, 0x29, 0xd4, 0x68, 0x9e,
+    0x2b, 0x16, 0xbe, 0x58, 0x7d, 0x47, 0xa1, 0xfc, 0x8f, 0xf8, 0xb8, 0xd1, 0x7a, 0xd0, 0x31, 0xce,
+    0x45, 0xcb, 0x3a, 0x8f, 0x95, 0x16, 0x04, 0x28, 0xaf, 0xd7, 0xfb, 0xca, 0xbb, 0x4b, 0x40, 0x7e,
+];
+
+fn read64(bytes: &[u8], at: usize) -> u64 {
+    let mut word = [0u8; 8];
+    word.copy_from_slice(&bytes[at..at + 8]);
+    u64::from_le_bytes(word)
+}
+
+fn read32(bytes: &[u8], at: usize) -> u64 {
+    let mut word = [0u8; 4];
+    word.copy_from_slice(&bytes[at..at + 4]);
+    u64::from(u32::from_le_bytes(word))
+}
+
+/// The full 128-bit product of `a` and `b`, folded to 64 bits by xor.
+fn mul128_fold64(a: u64, b: u64) -> u64 {
+    let product = u128::from(a) * u128::from(b);
+    product as u64 ^ (product >> 64) as u64
+}
+
+/// The XXH64 finalizer, which XXH3 uses for inputs of up to 3 bytes.
+fn xxh64_avalanche(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(PRIME64_2);
+    h ^= h >> 29;
+    h = h.wrapping_mul(PRIME64_3);
+    h ^ (h >> 32)
+}
+
+fn avalanche(mut h: u64) -> u64 {
+    h ^= h >> 37;
+    h = h.wrapping_mul(PRIME_MX1);
+    h ^ (h >> 32)
+}
+
+/// The stronger finalizer for 4 to 8 bytes, whose input is less mixed.
+fn rrmxmx(mut h: u64, len: u64) -> u64 {
+    h ^= h.rotate_left(49) ^ h.rotate_left(24);
+    h = h.wrapping_mul(PRIME_MX2);
+    h ^= (h >> 35).wrapping_add(len);
+    h = h.wrapping_mul(PRIME_MX2);
+    h ^ (h >> 28)
+}
+
+fn mix16(input: &[u8], secret: &[u8], seed: u64) -> u64 {
+    mul128_fold64(
+        read64(input, 0) ^ read64(secret, 0).wrapping_add(seed),
+        read64(input, 8) ^ read64(secret, 8).wrapping_sub(seed),
+    )
+}
+
+fn hash_0_to_16(input: &[u8], secret: &[u8], seed: u64) -> u64 {
+    let len = input.len();
+    if len > 8 {
+        let flip1 = (read64(secret, 24) ^ read64(secret, 32)).wrapping_add(seed);
+        let flip2 = (read64(secret, 40) ^ read64(secret, 48)).wrapping_sub(seed);
+        let lo = read64(input, 0) ^ flip1;
+        let hi = read64(input, len - 8) ^ flip2;
+        let acc = (len as u64)
+            .wrapping_add(lo.swap_bytes())
+            .wrapping_add(hi)
+            .wrapping_add(mul128_fold64(lo, hi));
+        avalanche(acc)
+    } else if len >= 4 {
+        let seed = seed ^ u64::from((seed as u32).swap_bytes()) << 32;
+        let flip = (read64(secret, 8) ^ read64(secret, 16)).wrapping_sub(seed);
+        let combined = read32(input, len - 4).wrapping_add(read32(input, 0) << 32);
+        rrmxmx(combined ^ flip, len as u64)
+    } else if len > 0 {
+        let combined = u64::from(input[0]) << 16
+            | u64::from(input[len >> 1]) << 24
+            | u64::from(input[len - 1])
+            | (len as u64) << 8;
+        let flip = (read32(secret, 0) ^ read32(secret, 4)).wrapping_add(seed);
+        xxh64_avalanche(combined ^ flip)
+    } else {
+        xxh64_avalanche(seed ^ read64(secret, 56) ^ read64(secret, 64))
+    }
+}
+
+fn hash_17_to_128(input: &[u8], secret: &[u8], seed: u64) -> u64 {
+    let len = input.len();
+    let mut acc = (len as u64).wrapping_mul(PRIME64_1);
+    // Pairs of 16-byte lanes from each end, more of them for longer inputs.
+    let pairs = (len - 1) / 32;
+    for i in 0..=pairs {
+        acc = acc
+            .wrapping_add(mix16(&input[16 * i..], &secret[32 * i..], seed))
+            .wrapping_add(mix16(
+                &input[len - 16 * (i + 1)..],
+                &secret[32 * i + 16..],
+                seed,
+            ));
+    }
+    avalanche(acc)
+}
+
+fn hash_129_to_240(input: &[u8], secret: &[u8], seed: u64) -> u64 {
+    let len = input.len();
+    let mut acc = (len as u64).wrapping_mul(PRIME64_1);
+    for i in 0..8 {
+        acc = acc.wrapping_add(mix16(&input[16 * i..], &secret[16 * i..], seed));
+    }
+    acc = avalanche(acc);
+    for i in 8..len / 16 {
+        acc = acc.wrapping_add(mix16(&input[16 * i..], &secret[16 * (i - 8) + 3..], seed));
+    }
+    // The last 16 bytes, against the end of the 136-byte minimum secret.
+    acc = acc.wrapping_add(mix16(&input[len - 16..], &secret[136 - 17..], seed));
+    avalanche(acc)
+}
+
+fn accumulate_stripe(acc: &mut [u64; 8], stripe: &[u8], secret: &[u8]) {
+    for i in 0..8 {
+        let value = read64(stripe, 8 * i);
+        let keyed = value ^ read64(secret, 8 * i);
+        acc[i ^ 1] = acc[i ^ 1].wrapping_add(value);
+        acc[i] = acc[i].wrapping_add((keyed & 0xffff_ffff).wrapping_mul(keyed >> 32));
+    }
+}
+
+fn scramble(acc: &mut [u64; 8], secret: &[u8]) {
+    for (i, a) in acc.iter_mut().enumerate() {
+        *a ^= *a >> 47;
+        *a ^= read64(secret, 8 * i);
+        *a = a.wrapping_mul(PRIME32_1);
+    }
+}
+
+fn hash_long(input: &[u8], secret: &[u8; SECRET_SIZE]) -> u64 {
+    let len = input.len();
+    let mut acc = [
+        PRIME32_3, PRIME64_1, PRIME64_2, PRIME64_3, PRIME64_4, PRIME32_2, PRIME64_5, PRIME32_1,
+    ];
+    let blocks = (len - 1) / BLOCK_LEN;
+    for block in input.chunks_exact(BLOCK_LEN).take(blocks) {
+        for (s, stripe) in block.chunks_exact(STRIPE_LEN).enumerate() {
+            accumulate_stripe(&mut acc, stripe, &secret[8 * s..]);
+        }
+        scramble(&mut acc, &secret[SECRET_SIZE - STRIPE_LEN..]);
+    }
+    // The whole stripes of the last, partial block, then the final 64 bytes,
+    // which may overlap them.
+    let tail = &input[blocks * BLOCK_LEN..];
+    for s in 0..(tail.len() - 1) / STRIPE_LEN {
+        accumulate_stripe(&mut acc, &tail[STRIPE_LEN * s..], &secret[8 * s..]);
+    }
+    accumulate_stripe(
+        &mut acc,
+        &input[len - STRIPE_LEN..],
+        &secret[SECRET_SIZE - STRIPE_LEN - 7..],
+    );
+
+    let mut result = (len as u64).wrapping_mul(PRIME64_1);
+    for i in 0..4 {
+        let secret = &secret[11 + 16 * i..];
+        result = result.wrapping_add(mul128_fold64(
+            acc[2 * i] ^ read64(secret, 0),
+            acc[2 * i + 1] ^ read64(secret, 8),
+        ));
+    }
+    avalanche(result)
+}
+
+/// `XXH3_64bits_withSeed`: the 64-bit XXH3 hash of `input` with `seed`.
+pub(crate) fn xxh3_64(input: &[u8], seed: u64) -> u64 {
+    let secret = &DEFAULT_SECRET;
+    match input.len() {
+        0..=16 => hash_0_to_16(input, secret, seed),
+        17..=128 => hash_17_to_128(input, secret, seed),
+        129..=240 => hash_129_to_240(input, secret, seed),
+        _ if seed == 0 => hash_long(input, secret),
+        _ => {
+            // Long inputs take the seed through a secret derived from it.
+            let mut derived = [0u8; SECRET_SIZE];
+            for i in 0..SECRET_SIZE / 16 {
+                let lo = read64(secret, 16 * i).wrapping_add(seed);
+                let hi = read64(secret, 16 * i + 8).wrapping_sub(seed);
+                derived[16 * i..16 * i + 8].copy_from_slice(&lo.to_le_bytes());
+                derived[16 * i + 8..16 * i + 16].copy_from_slice(&hi.to_le_bytes());
+            }
+            hash_long(input, &derived)
+        }
+    }
+}
+
+/// A `BuildHasher` for 64-bit XXH3 with a fixed seed, matching the sketches
+/// of any other XXH3 implementation given the same bytes and seed.
+///
+/// Add items with [`HyperLogLog::add_bytes`](::HyperLogLog::add_bytes) to
+/// hash exactly their bytes, as other languages do; `add` hashes whatever the
+/// item's `Hash` implementation writes, which for strings and slices includes
+/// a terminator or length prefix. Integers are written little-endian on every
+/// platform.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Xxh3State {
+    seed: u64,
+}
+
+impl Xxh3State {
+    /// XXH3 with seed 0, the default in most implementations.
+    pub fn new() -> Xxh3State {
+        Xxh3State::with_seed(0)
+    }
+
+    pub fn with_seed(seed: u64) -> Xxh3State {
+        Xxh3State { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+/// The hasher built by [`Xxh3State`]: XXH3 over every byte written.
+#[derive(Debug, Clone)]
+pub struct Xxh3Hasher {
+    seed: u64,
+    bytes: Vec<u8>,
+}
+
+impl Hasher for Xxh3Hasher {
+    fn write(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    little_endian_writes!();
+
+    fn finish(&self) -> u64 {
+        xxh3_64(&self.bytes, self.seed)
+    }
+}
+
+impl BuildHasher for Xxh3State {
+    type Hasher = Xxh3Hasher;
+
+    fn build_hasher(&self) -> Xxh3Hasher {
+        Xxh3Hasher {
+            seed: self.seed,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+/// Records a nonzero seed as the first key.
+impl StableBuildHasher for Xxh3State {
+    const BACKEND: HashBackend = HashBackend::Xxh3;
+
+    fn keys(&self) -> Option<(u64, u64)> {
+        match self.seed {
+            0 => None,
+            seed => Some((seed, 0)),
+        }
+    }
+
+    fn from_keys(keys: Option<(u64, u64)>) -> Option<Self> {
+        match keys {
+            None => Some(Xxh3State::new()),
+            Some((seed, 0)) => Some(Xxh3State::with_seed(seed)),
+            Some(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::hash::{BuildHasher, Hasher};
+
+    use super::{xxh3_64, Xxh3State};
+    use error::MergeError;
+    use {index_and_rank, HashBackend, HyperLogLog, StableBuildHasher};
+
+    /// `0, 1, 2, ...` wrapping at 256, for inputs longer than a literal.
+    fn counting(n: usize) -> Vec<u8> {
+        (0..n).map(|i| i as u8).collect()
+    }
+
+    #[test]
+    fn matches_reference_implementation() {
+        // From `XXH3_64bits_withSeed` in the xxHash 0.8 C library. The inputs
+        // cover every length class: empty, 1-3, 4-8, 9-16, 17-128, 129-240,
+        // and one and several blocks past that.
+        let cases: [(Vec<u8>, u64, u64); 14] = [
+            (b"".to_vec(), 0, 0x2d06_8005_38d3_94c2),
+            (b"".to_vec(), 42, 0xb029_411f_f43d_84d2),
+            (b"foo".to_vec(), 0, 0xab6e_5f64_077e_7d8a),
+            (b"foo".to_vec(), 42, 0xd33d_a54e_20eb_f99e),
+            (b"hello".to_vec(), 0, 0x9555_e855_5c62_dcfd),
+            (b"hello".to_vec(), 42, 0xbafa_072f_07db_7937),
+            (b"hyperloglog".to_vec(), 0, 0xe190_6b35_452c_c471),
+            (b"hyperloglog".to_vec(), 42, 0x81ef_b800_8ca3_d41b),
+            (
+                b"The quick brown fox jumps over the lazy dog".to_vec(),
+                0,
+                0xce7d_19a5_418f_b365,
+            ),
+            (
+                b"The quick brown fox jumps over the lazy dog".to_vec(),
+                42,
+                0xb4a3_f3c3_6b3c_7d26,
+            ),
+            (counting(200), 0, 0xf42a_8864_feaf_0703),
+            (counting(200), 42, 0xc335_a2de_8a09_a90e),
+            (counting(1000), 0, 0xd33d_d80b_46f6_0e50),
+            (counting(1000), 42, 0x1ba5_b309_df6f_67d3),
+        ];
+        for &(ref input, seed, want) in &cases {
+            assert_eq!(
+                xxh3_64(input, seed),
+                want,
+                "{} bytes, seed {}",
+                input.len(),
+                seed
+            );
+        }
+    }
+
+    #[test]
+    fn register_and_rank_vectors() {
+        // What a p = 14 sketch fed these strings as raw bytes must touch, in
+        // any language.
+        for &(item, register, rank) in &[
+            ("foo", 10_971, 1),
+            ("hello", 9_557, 2),
+            ("hyperloglog", 14_436, 4),
+            ("The quick brown fox jumps over the lazy dog", 13_215, 2),
+        ] {
+            let mut h = HyperLogLog::with_hasher(1 << 14, Xxh3State::new());
+            h.add_bytes(item);
+            let touched: Vec<(usize, u8)> =
+                h.registers().enumerate().filter(|&(_, r)| r > 0).collect();
+            assert_eq!(touched, [(register, rank)], "{:?}", item);
+            assert_eq!(
+                index_and_rank(xxh3_64(item.as_bytes(), 0), 14),
+                (register, rank)
+            );
+        }
+    }
+
+    #[test]
+    fn hasher_buffers_split_writes() {
+        let data = counting(3000);
+        let state = Xxh3State::with_seed(7);
+        for &split in &[0, 1, 100, 1024, 2999] {
+            let mut h = state.build_hasher();
+            h.write(&data[..split]);
+            h.write(&data[split..]);
+            assert_eq!(h.finish(), xxh3_64(&data, 7));
+        }
+        assert_eq!(state.hash_one(5u32), xxh3_64(&5u32.to_le_bytes(), 7));
+    }
+
+    #[test]
+    fn records_its_backend_and_seed() {
+        assert_eq!(Xxh3State::BACKEND, HashBackend::Xxh3);
+        for &seed in &[0, 1, u64::MAX] {
+            let state = Xxh3State::with_seed(seed);
+            assert_eq!(Xxh3State::from_keys(state.keys()), Some(state));
+        }
+        assert_eq!(Xxh3State::from_keys(Some((1, 2))), None);
+    }
+
+    #[test]
+    fn decoded_sketches_merge_only_with_the_same_seed() {
+        let mut h = HyperLogLog::with_hasher(1 << 8, Xxh3State::with_seed(99));
+        h.extend(0..1_000u32);
+        let bytes = h.to_bytes();
+        let decoded = HyperLogLog::<Xxh3State>::from_bytes(&bytes).unwrap();
+        let mut same = HyperLogLog::with_hasher(1 << 8, Xxh3State::with_seed(99));
+        assert_eq!(same.merge(&decoded), Ok(true));
+        let mut other = HyperLogLog::with_hasher(1 << 8, Xxh3State::new());
+        assert_eq!(
+            other.merge(&decoded),
+            Err(MergeError::HasherMismatch {
+                left: other.hasher_id().unwrap(),
+                right: h.hasher_id().unwrap()
+            })
+        );
+    }
+}
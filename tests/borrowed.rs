@@ -0,0 +1,75 @@
+//! Walks a buffer of encoded sketches through `HyperLogLogRef` under an
+//! allocator that counts, checking that reading them in place never
+//! allocates.
+
+extern crate hyperloglog;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use hyperloglog::{HyperLogLog, HyperLogLogRef, SeededState};
+
+/// Counts the allocations made by each thread, so tests running in
+/// parallel don't see each other's.
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn reads_concatenated_sketches_without_allocating() {
+    let sizes = [0u32, 10, 1_000, 100_000];
+    let sketches: Vec<HyperLogLog<SeededState>> = sizes
+        .iter()
+        .map(|&n| {
+            let mut h = HyperLogLog::new_seeded(1 << 12, 83);
+            h.extend(0..n);
+            h
+        })
+        .collect();
+    let mut buffer = Vec::new();
+    for h in &sketches {
+        buffer.extend_from_slice(&h.to_bytes());
+    }
+    let mut counts = [0.; 4];
+    let mut merged = HyperLogLog::new_seeded(1 << 12, 83);
+    // Make the target dense first: raising a sparse sketch may allocate.
+    merged.extend(0..5_000u32);
+
+    let before = allocations();
+    let mut rest = &buffer[..];
+    for count in &mut counts {
+        let (view, after) = HyperLogLogRef::<SeededState>::parse_prefix(rest).unwrap();
+        *count = view.count();
+        view.merge_into(&mut merged).unwrap();
+        rest = after;
+    }
+    assert_eq!(allocations(), before);
+
+    assert!(rest.is_empty());
+    for (count, h) in counts.iter().zip(&sketches) {
+        assert_eq!(*count, h.count());
+    }
+    assert_eq!(merged, sketches[3]);
+    let (first, _) = HyperLogLogRef::<SeededState>::parse_prefix(&buffer).unwrap();
+    assert_eq!(first.to_owned(), sketches[0]);
+}
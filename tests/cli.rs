@@ -0,0 +1,151 @@
+//! Drives the `hll` binary over the files in `tests/fixtures/cli`.
+
+#![cfg(feature = "cli")]
+
+extern crate assert_cmd;
+extern crate hyperloglog;
+extern crate tempfile;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use assert_cmd::Command;
+
+use hyperloglog::{ClassicEstimator, HyperLogLog, SeededState};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join("cli")
+        .join(name)
+}
+
+fn hll() -> Command {
+    Command::cargo_bin("hll").unwrap()
+}
+
+fn stdout(cmd: &mut Command) -> String {
+    let out = cmd.assert().success().get_output().stdout.clone();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn count_reads_distinct_lines_from_stdin() {
+    let mut cmd = hll();
+    cmd.arg("count").pipe_stdin(fixture("a.txt")).unwrap();
+    assert_eq!(stdout(&mut cmd), "4\n");
+
+    let lines: String = (0..20_000).map(|i| format!("{}\n", i)).collect();
+    let mut cmd = hll();
+    cmd.args(["count", "--precision", "12"])
+        .write_stdin(lines.clone());
+    let count: f64 = stdout(&mut cmd).trim().parse().unwrap();
+    assert!((count - 20_000.).abs() < 1_000., "counted {}", count);
+
+    let mut sketch = HyperLogLog::new_seeded(1 << 12, 0);
+    (0..20_000).for_each(|i| sketch.add_bytes(i.to_string()));
+    for (name, estimator) in [
+        ("bias-corrected", sketch.count()),
+        ("classic", sketch.count_with(&ClassicEstimator)),
+        ("loglog-beta", sketch.count_beta()),
+        ("ertl", sketch.count_improved()),
+    ] {
+        let mut cmd = hll();
+        cmd.args(["count", "-p", "12", "--estimator", name])
+            .write_stdin(lines.clone());
+        assert_eq!(
+            stdout(&mut cmd),
+            format!("{}\n", estimator.round()),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn sketch_merge_and_inspect() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.hll");
+    let b = dir.path().join("b.hll");
+    let total = dir.path().join("total.hll");
+    for (input, output) in [("a.txt", &a), ("b.txt", &b)] {
+        hll()
+            .args(["sketch", "-p", "10", "-o"])
+            .arg(output)
+            .pipe_stdin(fixture(input))
+            .unwrap()
+            .assert()
+            .success();
+    }
+    hll()
+        .arg("merge")
+        .args([&a, &b])
+        .arg("-o")
+        .arg(&total)
+        .assert()
+        .success();
+
+    let merged = HyperLogLog::<SeededState>::from_bytes(&fs::read(&total).unwrap()).unwrap();
+    let mut expected = HyperLogLog::new_seeded(1 << 10, 0);
+    for item in [
+        "apple",
+        "banana",
+        "cherry",
+        "date",
+        "elderberry",
+        "fig",
+        "grape",
+    ] {
+        expected.add_bytes(item);
+    }
+    assert_eq!(merged, expected);
+
+    let mut cmd = hll();
+    cmd.arg("inspect").arg(&total);
+    assert_eq!(
+        stdout(&mut cmd),
+        "precision: 10\nregisters: 7 of 1024 non-zero\nestimate: 7\n"
+    );
+}
+
+#[test]
+fn refuses_incompatible_or_bad_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let small = dir.path().join("small.hll");
+    let large = dir.path().join("large.hll");
+    for (precision, output) in [("8", &small), ("12", &large)] {
+        hll()
+            .args(["sketch", "--precision", precision, "--output"])
+            .arg(output)
+            .pipe_stdin(fixture("a.txt"))
+            .unwrap()
+            .assert()
+            .success();
+    }
+
+    let out = hll()
+        .arg("merge")
+        .args([&small, &large])
+        .arg("-o")
+        .arg(dir.path().join("total.hll"))
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(out.stderr).unwrap();
+    assert!(stderr.starts_with("hll: "), "{}", stderr);
+    assert!(stderr.contains("different precisions"), "{}", stderr);
+    assert!(!dir.path().join("total.hll").exists());
+
+    hll()
+        .arg("inspect")
+        .arg(fixture("a.txt"))
+        .assert()
+        .failure();
+    hll()
+        .args(["count", "--precision", "2"])
+        .write_stdin("x\n")
+        .assert()
+        .failure();
+}
@@ -0,0 +1,85 @@
+//! Builds the crate as a static library, links `tests/ffi/roundtrip.c`
+//! against it with the generated header, and runs the result.
+
+#![cfg(feature = "ffi")]
+
+extern crate cc;
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The target triple rustc builds for by default.
+fn host_triple() -> String {
+    let out = Command::new("rustc").arg("-vV").output().unwrap();
+    let info = String::from_utf8(out.stdout).unwrap();
+    info.lines()
+        .find_map(|l| l.strip_prefix("host: "))
+        .unwrap()
+        .to_string()
+}
+
+/// Builds `libhyperloglog.a` under `target_dir`, returning the library and
+/// the system libraries rustc says it needs.
+fn build_static_lib(root: &Path, target_dir: &Path) -> (PathBuf, Vec<String>) {
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let out = Command::new(cargo)
+        .current_dir(root)
+        .args([
+            "rustc",
+            "--lib",
+            "--features",
+            "ffi",
+            "--crate-type",
+            "staticlib",
+        ])
+        .arg("--target-dir")
+        .arg(target_dir)
+        .args(["--", "--print", "native-static-libs"])
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{}", stderr);
+    let native = stderr
+        .lines()
+        .find_map(|l| l.split("native-static-libs: ").nth(1))
+        .map(|libs| libs.split_whitespace().map(String::from).collect())
+        .unwrap_or_default();
+    (target_dir.join("debug").join("libhyperloglog.a"), native)
+}
+
+#[test]
+fn c_program_round_trips_a_sketch() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let target_dir = root.join("target").join("ffi-test");
+    let (lib, native) = build_static_lib(root, &target_dir);
+
+    let host = host_triple();
+    let compiler = cc::Build::new()
+        .cargo_metadata(false)
+        .target(&host)
+        .host(&host)
+        .opt_level(0)
+        .get_compiler();
+    let exe = target_dir.join("roundtrip");
+    let status = compiler
+        .to_command()
+        .arg(root.join("tests").join("ffi").join("roundtrip.c"))
+        .arg("-I")
+        .arg(root.join("include"))
+        .arg(&lib)
+        .args(&native)
+        .arg("-lm")
+        .arg("-o")
+        .arg(&exe)
+        .status()
+        .unwrap();
+    assert!(status.success(), "compiling the C test failed");
+
+    let out = Command::new(&exe).output().unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
@@ -0,0 +1,83 @@
+/* Exercises the C interface end to end: build, merge, serialize, restore.
+ * Run by tests/ffi.rs; exits non-zero on the first failed check. */
+
+#include <math.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "hyperloglog.h"
+
+#define CHECK(cond)                                                         \
+    do {                                                                    \
+        if (!(cond)) {                                                      \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, \
+                    #cond);                                                 \
+            return 1;                                                       \
+        }                                                                   \
+    } while (0)
+
+static int add_range(Hll *hll, int start, int end) {
+    char item[32];
+    for (int i = start; i < end; i++) {
+        int len = snprintf(item, sizeof item, "item-%d", i);
+        if (hll_add_bytes(hll, (const uint8_t *)item, (size_t)len) != HLL_OK) {
+            return 1;
+        }
+    }
+    return 0;
+}
+
+int main(void) {
+    Hll *a = hll_new(12);
+    Hll *b = hll_new(12);
+    Hll *whole = hll_new(12);
+    CHECK(a && b && whole);
+    CHECK(add_range(a, 0, 6000) == 0);
+    CHECK(add_range(b, 4000, 10000) == 0);
+    CHECK(add_range(whole, 0, 10000) == 0);
+
+    CHECK(hll_merge(a, b) == HLL_OK);
+    double count = hll_count(a);
+    CHECK(count == hll_count(whole));
+    CHECK(fabs(count - 10000.0) < 500.0);
+
+    /* Ask for the size, then serialize and restore. */
+    size_t len = 0;
+    CHECK(hll_serialize(a, NULL, &len) == HLL_ERR_BUFFER_TOO_SMALL);
+    CHECK(len > 4096);
+    uint8_t *buf = malloc(len);
+    CHECK(buf);
+    size_t small = len - 1;
+    CHECK(hll_serialize(a, buf, &small) == HLL_ERR_BUFFER_TOO_SMALL);
+    CHECK(hll_serialize(a, buf, &len) == HLL_OK);
+    Hll *restored = hll_deserialize(buf, len);
+    CHECK(restored);
+    CHECK(hll_count(restored) == count);
+
+    /* Another key, in the bytes after the 7-byte header, hashes differently. */
+    buf[7] ^= 1;
+    Hll *rekeyed = hll_deserialize(buf, len);
+    CHECK(rekeyed);
+    CHECK(hll_merge(a, rekeyed) == HLL_ERR_HASHER_MISMATCH);
+
+    /* Corrupt input and bad arguments fail without crashing. */
+    buf[0] ^= 0xff;
+    CHECK(hll_deserialize(buf, len) == NULL);
+    CHECK(hll_deserialize(NULL, len) == NULL);
+    CHECK(hll_new(2) == NULL);
+    CHECK(isnan(hll_count(NULL)));
+    CHECK(hll_add_bytes(NULL, buf, len) == HLL_ERR_INVALID_POINTER);
+    Hll *other = hll_new(10);
+    CHECK(hll_merge(a, other) == HLL_ERR_PRECISION_MISMATCH);
+
+    free(buf);
+    hll_free(a);
+    hll_free(b);
+    hll_free(whole);
+    hll_free(restored);
+    hll_free(rekeyed);
+    hll_free(other);
+    hll_free(NULL);
+    return 0;
+}
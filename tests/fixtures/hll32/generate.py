@@ -0,0 +1,99 @@
+"""Generates vectors.txt: sketches and estimates from the original 32-bit
+HyperLogLog, transcribed from Figure 3 of Flajolet, Fusy, Gandouet and
+Meunier, "HyperLogLog: the analysis of a near-optimal cardinality estimation
+algorithm" (2007), for checking HyperLogLog32 against it.
+
+Run with `python3 generate.py > vectors.txt`.
+"""
+
+import math
+
+TWO_32 = 2.0 ** 32
+MASK64 = (1 << 64) - 1
+
+
+def splitmix64(state):
+    state = (state + 0x9E3779B97F4A7C15) & MASK64
+    z = state
+    z = ((z ^ (z >> 30)) * 0xBF58476D1CE4E5B9) & MASK64
+    z = ((z ^ (z >> 27)) * 0x94D049BB133111EB) & MASK64
+    return state, z ^ (z >> 31)
+
+
+def rho(w, bits):
+    """The position of the leftmost 1-bit of the `bits`-bit word `w`."""
+    for position in range(1, bits + 1):
+        if w >> (bits - position) & 1:
+            return position
+    return bits + 1
+
+
+def add(registers, p, x):
+    j = x >> (32 - p)
+    w = x & ((1 << (32 - p)) - 1)
+    registers[j] = max(registers[j], rho(w, 32 - p))
+
+
+def alpha(m):
+    return {16: 0.673, 32: 0.697, 64: 0.709}.get(m, 0.7213 / (1.0 + 1.079 / m))
+
+
+def count(registers):
+    m = len(registers)
+    e = alpha(m) * m * m / sum(2.0 ** -r for r in registers)
+    if e <= 5.0 / 2.0 * m:
+        v = registers.count(0)
+        return m * math.log(m / v) if v != 0 else e
+    if e <= TWO_32 / 30.0:
+        return e
+    return -TWO_32 * math.log(1.0 - e / TWO_32)
+
+
+def raw(registers):
+    m = len(registers)
+    return alpha(m) * m * m / sum(2.0 ** -r for r in registers)
+
+
+def line(kind, p, fields, registers):
+    print(kind, p, *fields, repr(count(registers)), bytes(registers).hex())
+
+
+def stream(p, seed, n):
+    registers = [0] * (1 << p)
+    state = seed
+    for _ in range(n):
+        state, h = splitmix64(state)
+        add(registers, p, h & 0xFFFFFFFF)
+    line("stream", p, [seed, n], registers)
+
+
+def around(p, target):
+    """Raises registers one at a time from a uniform start, printing the
+    last sketch whose raw estimate is at most `target` and the first over.
+    """
+    m = 1 << p
+    # Every register at `r` gives a raw estimate of alpha * m * 2^r.
+    registers = [int(math.log2(target / (alpha(m) * m))) - 1] * m
+    j = 0
+    while True:
+        before = list(registers)
+        registers[j % m] += 1
+        j += 1
+        if raw(registers) > target:
+            line("registers", p, [], before)
+            line("registers", p, [], registers)
+            return
+
+
+print("# Generated by generate.py; see there for the reference algorithm.")
+print("# stream <p> <splitmix64 seed> <hashes> <estimate> <registers>")
+print("# registers <p> <estimate> <registers>")
+print("# Hashes are the low 32 bits of successive splitmix64 outputs.")
+for p, seed, n in [(4, 70, 10), (4, 70, 1000), (10, 70, 100), (10, 70, 2000),
+                   (10, 70, 100_000), (12, 70, 1_000_000)]:
+    stream(p, seed, n)
+for p in [4, 10, 12]:
+    around(p, TWO_32 / 30.0)
+for p in [4, 10]:
+    around(p, TWO_32 / 2.0)
+    around(p, TWO_32 * 0.9)
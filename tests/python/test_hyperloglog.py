@@ -0,0 +1,69 @@
+"""Tests for the Python bindings.
+
+Build the module into the current environment first, then run this file:
+
+    maturin develop
+    python tests/python/test_hyperloglog.py
+"""
+
+import pickle
+import unittest
+
+from hyperloglog import HyperLogLog
+
+
+class HyperLogLogTest(unittest.TestCase):
+    def test_counts_distinct_items(self):
+        h = HyperLogLog(12)
+        for i in range(10_000):
+            h.add(f"user-{i}")
+            h.add(f"user-{i}")
+        self.assertAlmostEqual(h.count(), 10_000, delta=500)
+
+    def test_hashes_values_not_objects(self):
+        a = HyperLogLog(10)
+        b = HyperLogLog(10)
+        a.add("café")
+        b.add("café".encode())
+        a.add(7)
+        b.add(True)
+        b.add(7)
+        a.add(1)
+        self.assertEqual(a.to_bytes(), b.to_bytes())
+
+    def test_rejects_other_types(self):
+        h = HyperLogLog()
+        with self.assertRaises(TypeError):
+            h.add(1.5)
+        with self.assertRaises(OverflowError):
+            h.add(2**64)
+        with self.assertRaises(ValueError):
+            HyperLogLog(3)
+
+    def test_merge_and_union(self):
+        a = HyperLogLog(12)
+        b = HyperLogLog(12)
+        for i in range(3_000):
+            (a if i % 2 else b).add(i)
+        union = a | b
+        a.merge(b)
+        self.assertEqual(union.count(), a.count())
+        self.assertAlmostEqual(a.count(), 3_000, delta=150)
+        with self.assertRaises(ValueError):
+            a.merge(HyperLogLog(10))
+
+    def test_bytes_and_pickle_round_trip(self):
+        h = HyperLogLog(11)
+        for i in range(5_000):
+            h.add(i)
+        restored = HyperLogLog.from_bytes(h.to_bytes())
+        self.assertEqual(restored.to_bytes(), h.to_bytes())
+        unpickled = pickle.loads(pickle.dumps(h))
+        self.assertEqual(unpickled.precision, 11)
+        self.assertEqual(unpickled.count(), h.count())
+        with self.assertRaises(ValueError):
+            HyperLogLog.from_bytes(b"nope")
+
+
+if __name__ == "__main__":
+    unittest.main()